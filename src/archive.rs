@@ -0,0 +1,255 @@
+//! The archive of the build (`build --archive`): the files of the list of the build in a `.tar.gz`
+//! or a `.zip`.
+//!
+//! The archive is reproducible: the entries are sorted by path and their modification times,
+//! owners and permissions are the same for all the files.
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Permissions of the files in the archive.
+const MODE: u32 = 0o644;
+
+/// The format of the archive, given by the extension of its path.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Format {
+    TarGz,
+    Zip,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Result<Self> {
+        let name = path
+            .file_name()
+            .map(|x| x.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(Self::TarGz)
+        } else if name.ends_with(".zip") {
+            Ok(Self::Zip)
+        } else {
+            bail!(
+                "unknown format of archive `{}`, expected `.tar.gz`, `.tgz` or `.zip`",
+                path.display()
+            )
+        }
+    }
+}
+
+/// Check the path of the archive before the build so the build is not made for nothing.
+pub(crate) fn check(path: &Path, build_path: &Path, force: bool) -> Result<Format> {
+    let format = Format::from_path(path)?;
+
+    let absolute = |path: &Path| {
+        std::env::current_dir()
+            .map(|x| x.join(path))
+            .unwrap_or_else(|_| path.to_owned())
+    };
+    if absolute(path).starts_with(absolute(build_path)) {
+        bail!(
+            "the archive `{}` can't be written in the build directory `{}`",
+            path.display(),
+            build_path.display()
+        );
+    }
+    if path.exists() && !force {
+        bail!(
+            "`{}` already exists (pass `--force` to replace it)",
+            path.display()
+        );
+    }
+
+    Ok(format)
+}
+
+/// Write the files of the list of the build to the archive and returns the SHA-256 of the
+/// archive.
+pub(crate) fn write(path: &Path, format: Format, build_path: &Path) -> Result<String> {
+    let files = entries(build_path)?;
+
+    let mut archive = Vec::new();
+    match format {
+        Format::TarGz => write_tar_gz(&mut archive, build_path, &files)?,
+        Format::Zip => write_zip(&mut archive, build_path, &files)?,
+    }
+
+    if let Some(parent) = path.parent().filter(|x| !x.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("could not create directory `{}`", parent.display()))?;
+    }
+    fs::write(path, &archive).with_context(|| format!("could not write `{}`", path.display()))?;
+    log::info!(
+        "Archived {} files in `{}` ({})",
+        files.len(),
+        path.display(),
+        crate::format_size(archive.len())
+    );
+
+    Ok(format!("{:x}", Sha256::digest(&archive)))
+}
+
+/// The files of the list of the build and their names in the archive, sorted by name.
+fn entries(build_path: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut files: Vec<_> = crate::build_files::recorded(build_path)
+        .context("could not find the files of the build")?
+        .into_iter()
+        .map(|x| (crate::slash_path(&x), x))
+        .collect();
+    files.sort();
+
+    Ok(files)
+}
+
+fn read(build_path: &Path, path: &Path) -> Result<Vec<u8>> {
+    let path = build_path.join(path);
+    fs::read(&path).with_context(|| format!("could not read `{}`", path.display()))
+}
+
+fn write_tar_gz(
+    archive: &mut Vec<u8>,
+    build_path: &Path,
+    files: &[(String, PathBuf)],
+) -> Result<()> {
+    // NOTE: the header of gzip has no modification time by default
+    let encoder = flate2::write::GzEncoder::new(archive, flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for (name, path) in files {
+        let content = read(build_path, path)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(MODE);
+        header.set_mtime(0);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_entry_type(tar::EntryType::Regular);
+        builder
+            .append_data(&mut header, name, content.as_slice())
+            .with_context(|| format!("could not archive `{}`", name))?;
+    }
+    builder
+        .into_inner()
+        .and_then(|x| x.finish())
+        .context("could not compress the archive")?;
+
+    Ok(())
+}
+
+fn write_zip(archive: &mut Vec<u8>, build_path: &Path, files: &[(String, PathBuf)]) -> Result<()> {
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip::DateTime::default())
+        .unix_permissions(MODE);
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(archive));
+    for (name, path) in files {
+        let content = read(build_path, path)?;
+        writer
+            .start_file(name.as_str(), options)
+            .and_then(|()| Ok(writer.write_all(&content)?))
+            .with_context(|| format!("could not archive `{}`", name))?;
+    }
+    writer.finish().context("could not finish the archive")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let build_path = dir.path().join("build");
+        fs::create_dir_all(build_path.join("snippets")).unwrap();
+        fs::write(build_path.join("index.html"), "index").unwrap();
+        fs::write(build_path.join("app.js"), "js").unwrap();
+        fs::write(build_path.join("snippets").join("a.js"), "snippet").unwrap();
+        fs::write(build_path.join("stale.js"), "stale").unwrap();
+        fs::write(
+            build_path.join(crate::build_files::LIST_FILE),
+            "app.js\nindex.html\nsnippets/a.js\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn archive_formats() {
+        assert_eq!(
+            Format::from_path(Path::new("dist/app.tar.gz")).unwrap(),
+            Format::TarGz
+        );
+        assert_eq!(
+            Format::from_path(Path::new("app.TGZ")).unwrap(),
+            Format::TarGz
+        );
+        assert_eq!(
+            Format::from_path(Path::new("app.zip")).unwrap(),
+            Format::Zip
+        );
+        assert!(Format::from_path(Path::new("app.tar")).is_err());
+
+        let dir = build_dir();
+        let build_path = dir.path().join("build");
+        assert!(check(&build_path.join("app.zip"), &build_path, true).is_err());
+        fs::write(dir.path().join("app.zip"), "").unwrap();
+        let err = check(&dir.path().join("app.zip"), &build_path, false).unwrap_err();
+        assert!(err.to_string().contains("--force"), "{}", err);
+        check(&dir.path().join("app.zip"), &build_path, true).unwrap();
+    }
+
+    #[test]
+    fn reproducible_tar_gz() {
+        let dir = build_dir();
+        let build_path = dir.path().join("build");
+        let path = dir.path().join("dist").join("app.tar.gz");
+
+        let sha256 = write(&path, Format::TarGz, &build_path).unwrap();
+        let mut archive =
+            tar::Archive::new(flate2::read::GzDecoder::new(fs::File::open(&path).unwrap()));
+        let entries: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .map(|x| {
+                let x = x.unwrap();
+                (
+                    x.path().unwrap().to_string_lossy().into_owned(),
+                    x.header().mtime().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("app.js".to_string(), 0),
+                ("index.html".to_string(), 0),
+                ("snippets/a.js".to_string(), 0),
+            ]
+        );
+
+        // NOTE: the modification times of the files are not in the archive
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        fs::write(build_path.join("app.js"), "js").unwrap();
+        assert_eq!(write(&path, Format::TarGz, &build_path).unwrap(), sha256);
+    }
+
+    #[test]
+    fn reproducible_zip() {
+        let dir = build_dir();
+        let build_path = dir.path().join("build");
+        let path = dir.path().join("app.zip");
+
+        let sha256 = write(&path, Format::Zip, &build_path).unwrap();
+        let mut archive = zip::ZipArchive::new(fs::File::open(&path).unwrap()).unwrap();
+        let names: Vec<_> = (0..archive.len())
+            .map(|i| archive.by_index(i).unwrap().name().to_string())
+            .collect();
+        assert_eq!(names, ["app.js", "index.html", "snippets/a.js"]);
+
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        fs::write(build_path.join("index.html"), "index").unwrap();
+        assert_eq!(write(&path, Format::Zip, &build_path).unwrap(), sha256);
+    }
+}
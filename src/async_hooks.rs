@@ -0,0 +1,67 @@
+//! Support for `async fn` hooks in the code generated by the macro. This is not part of the public
+//! API.
+//!
+//! The macro cannot know if a hook is an `async fn` so the generated code dispatches on the type
+//! returned by the call: `(&output).wasmbl_hook_kind().resolve(output)`. A future is picked by
+//! [`AsyncHookKind`] before auto-referencing, anything else falls back to [`SyncHookKind`].
+
+use std::future::Future;
+
+/// The hook returned a future that needs to be driven to completion.
+pub struct AsyncHook;
+
+impl AsyncHook {
+    /// Run the future on the current thread until it completes.
+    pub fn resolve<F: Future>(self, future: F) -> F::Output {
+        futures::executor::block_on(future)
+    }
+}
+
+/// The hook returned its result directly.
+pub struct SyncHook;
+
+impl SyncHook {
+    /// Return the result unchanged.
+    pub fn resolve<T, E>(self, result: Result<T, E>) -> Result<T, E> {
+        result
+    }
+}
+
+pub trait AsyncHookKind {
+    fn wasmbl_hook_kind(&self) -> AsyncHook {
+        AsyncHook
+    }
+}
+
+impl<F: Future> AsyncHookKind for F {}
+
+pub trait SyncHookKind {
+    fn wasmbl_hook_kind(&self) -> SyncHook {
+        SyncHook
+    }
+}
+
+impl<T, E> SyncHookKind for &Result<T, E> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sync_hook(value: u32) -> anyhow::Result<u32> {
+        Ok(value)
+    }
+
+    async fn async_hook(value: u32) -> anyhow::Result<u32> {
+        Ok(value + 1)
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrow)]
+    fn resolve_sync_and_async_hooks() {
+        let output = sync_hook(1);
+        assert_eq!((&output).wasmbl_hook_kind().resolve(output).unwrap(), 1);
+
+        let output = async_hook(1);
+        assert_eq!((&output).wasmbl_hook_kind().resolve(output).unwrap(), 2);
+    }
+}
@@ -0,0 +1,340 @@
+//! The list of the files produced by the builds, used to remove the stale files of the previous
+//! builds from the build directory.
+//!
+//! A file is produced by a build if it has been written since the build started, by the build
+//! itself or by a hook. Only the files recorded in the list are removed: the files that were
+//! already in the build directory when it was used for the first time are kept, unless `--clean`
+//! is given explicitly.
+//!
+//! The build directory is checked when it is opened, before the build starts: a build path on a
+//! read-only or full file system fails right away instead of after the compilation.
+
+use crate::Error;
+use anyhow::Context;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the list of the files produced by the builds, in the build directory.
+pub(crate) const LIST_FILE: &str = ".wasmbl-files";
+
+/// Returns `true` for the files of the build directory (relative to it) that are written by
+/// wasmbl for itself, not by the builds: the list of the files and the record of the last build.
+pub(crate) fn is_internal(path: &Path) -> bool {
+    path == Path::new(LIST_FILE) || path == Path::new(crate::build_meta::META_FILE)
+}
+
+pub(crate) struct BuildFiles {
+    build_path: PathBuf,
+    /// The files of the list (relative to the build directory), `None` if there is no list.
+    recorded: Option<Vec<PathBuf>>,
+    /// The build directory already contained files but no list.
+    foreign: bool,
+    /// The start of the build, truncated to the second for the file systems that do not store
+    /// the modification times precisely.
+    start: SystemTime,
+}
+
+/// The start of a build, taken before the `pre_build` hook: the files written by the hooks are
+/// produced by the build too.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Started {
+    start: SystemTime,
+    /// The build directory already contained files when the build started.
+    foreign: bool,
+}
+
+impl BuildFiles {
+    /// Create the build directory if it doesn't exist, check that files can be written in it and
+    /// read the list of the previous build.
+    #[cfg(test)]
+    pub fn open(build_path: &Path) -> Result<Self, Error> {
+        Ok(Self::resume(build_path, Self::start(build_path)?))
+    }
+
+    /// Create the build directory if it doesn't exist and check that files can be written in it,
+    /// before the build starts.
+    pub fn start(build_path: &Path) -> Result<Started, Error> {
+        let start = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| UNIX_EPOCH + Duration::from_secs(x.as_secs()))
+            .unwrap_or(UNIX_EPOCH);
+
+        let foreign = match fs::read_dir(build_path) {
+            Ok(mut entries) => entries.next().is_some(),
+            Err(_) => {
+                fs::create_dir_all(build_path).map_err(|source| Error::BuildPathNotWritable {
+                    path: build_path.to_owned(),
+                    source,
+                })?;
+                false
+            }
+        };
+        probe(build_path).map_err(|source| Error::BuildPathNotWritable {
+            path: build_path.to_owned(),
+            source,
+        })?;
+
+        Ok(Started { start, foreign })
+    }
+
+    /// Read the list of the previous build, once the build that started has files to write.
+    pub fn resume(build_path: &Path, started: Started) -> Self {
+        let recorded = recorded(build_path).ok();
+
+        Self {
+            build_path: build_path.to_owned(),
+            foreign: started.foreign && recorded.is_none(),
+            recorded,
+            start: started.start,
+        }
+    }
+
+    /// Returns `true` if the file (relative to the build directory) has been produced by a
+    /// previous build.
+    pub fn is_recorded(&self, path: &Path) -> bool {
+        self.recorded
+            .as_ref()
+            .map(|x| x.iter().any(|x| x == path))
+            .unwrap_or(false)
+    }
+
+    /// Remove the stale files if `clean` is enabled and record the files of the build.
+    ///
+    /// The stale files are the files of the list that have not been produced by this build, or
+    /// all the files that have not been produced by this build if there is no list and `explicit`
+    /// is enabled.
+    pub fn finish(self, clean: bool, explicit: bool) -> anyhow::Result<Vec<PathBuf>> {
+        let files = self.list()?;
+        let (mut produced, previous): (Vec<_>, Vec<_>) =
+            files.into_iter().partition(|x| self.is_produced(x));
+
+        let stale: Vec<_> = match self.recorded.as_ref() {
+            Some(recorded) => previous
+                .iter()
+                .filter(|x| recorded.contains(x))
+                .cloned()
+                .collect(),
+            None if self.foreign && explicit => previous.clone(),
+            None => {
+                if self.foreign && clean {
+                    log::debug!(
+                        "The build directory `{}` has not been created by a build, the files of \
+                        the previous builds are not removed",
+                        self.build_path.display()
+                    );
+                }
+                Vec::new()
+            }
+        };
+
+        let mut removed = Vec::new();
+        if clean {
+            for path in stale.iter() {
+                let full_path = self.build_path.join(path);
+                log::debug!("Removing stale file `{}`", full_path.display());
+                fs::remove_file(&full_path)
+                    .with_context(|| format!("could not remove `{}`", full_path.display()))?;
+                self.remove_empty_parents(path);
+                removed.push(full_path);
+            }
+        } else {
+            // NOTE: the previous files are still there so they remain recorded
+            produced.extend(stale);
+        }
+
+        produced.sort();
+        let list: String = produced
+            .iter()
+            .filter_map(|x| crate::url_path(x))
+            .map(|x| format!("{}\n", x))
+            .collect();
+        let list_path = self.build_path.join(LIST_FILE);
+        crate::write_atomic(&list_path, list.as_bytes())
+            .with_context(|| format!("could not write `{}`", list_path.display()))?;
+
+        Ok(removed)
+    }
+
+    /// All the files of the build directory, relative to it, except the list.
+    fn list(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let files = crate::list_files(&self.build_path).with_context(|| {
+            format!(
+                "could not list the files of the build directory `{}`",
+                self.build_path.display()
+            )
+        })?;
+
+        Ok(files
+            .into_iter()
+            .filter_map(|x| x.strip_prefix(&self.build_path).ok().map(Path::to_path_buf))
+            .filter(|x| !is_internal(x))
+            .collect())
+    }
+
+    fn is_produced(&self, path: &Path) -> bool {
+        self.build_path
+            .join(path)
+            .metadata()
+            .and_then(|x| x.modified())
+            .map(|x| x >= self.start)
+            .unwrap_or(false)
+    }
+
+    /// Remove the directories left empty by the removal of a file, up to the build directory.
+    fn remove_empty_parents(&self, path: &Path) {
+        for parent in path.ancestors().skip(1) {
+            if parent.as_os_str().is_empty()
+                || fs::remove_dir(self.build_path.join(parent)).is_err()
+            {
+                break;
+            }
+        }
+    }
+}
+
+/// The files of the list of the last build, relative to the build directory.
+pub(crate) fn recorded(build_path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let list_path = build_path.join(LIST_FILE);
+    let list = fs::read_to_string(&list_path)
+        .with_context(|| format!("could not read `{}`", list_path.display()))?;
+
+    // NOTE: the names are percent-encoded to keep the names that are not valid UTF-8
+    Ok(list.lines().filter_map(crate::path_from_url).collect())
+}
+
+/// Write and remove a small file in the build directory. It is synced so a full disk is noticed
+/// even if the file system delays the allocation of the blocks.
+fn probe(build_path: &Path) -> io::Result<()> {
+    let path = build_path.join(format!(".wasmbl-probe-{}", std::process::id()));
+    let res = fs::File::create(&path).and_then(|mut file| {
+        file.write_all(&[0; 4096])?;
+        file.sync_all()
+    });
+    let _ = fs::remove_file(&path);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_old(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+        let old = SystemTime::now() - Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+    }
+
+    #[test]
+    fn remove_stale_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let build_path = dir.path().join("build");
+
+        let files = BuildFiles::open(&build_path).unwrap();
+        assert!(build_path.exists());
+        assert_eq!(fs::read_dir(&build_path).unwrap().count(), 0);
+        fs::create_dir(build_path.join("hashed")).unwrap();
+        for file in ["app-1234.js", "hashed/app-1234.wasm", "index.html"] {
+            fs::write(build_path.join(file), "").unwrap();
+        }
+        assert!(files.finish(true, false).unwrap().is_empty());
+
+        wait_next_second();
+        let files = BuildFiles::open(&build_path).unwrap();
+        fs::write(build_path.join("index.html"), "").unwrap();
+        fs::write(build_path.join("app.js"), "").unwrap();
+        assert!(files.finish(false, false).unwrap().is_empty());
+        assert!(build_path.join("app-1234.js").exists());
+
+        wait_next_second();
+        let files = BuildFiles::open(&build_path).unwrap();
+        fs::write(build_path.join("index.html"), "").unwrap();
+        fs::write(build_path.join("app.js"), "").unwrap();
+        assert_eq!(
+            files.finish(true, false).unwrap(),
+            [
+                build_path.join("app-1234.js"),
+                build_path.join("hashed").join("app-1234.wasm"),
+            ]
+        );
+        assert!(!build_path.join("hashed").exists());
+        assert_eq!(
+            fs::read_to_string(build_path.join(LIST_FILE)).unwrap(),
+            "app.js\nindex.html\n"
+        );
+    }
+
+    #[test]
+    fn keep_foreign_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_old(&dir.path().join("README.md"));
+
+        let files = BuildFiles::open(dir.path()).unwrap();
+        fs::write(dir.path().join("index.html"), "").unwrap();
+        assert!(files.finish(true, false).unwrap().is_empty());
+        assert!(dir.path().join("README.md").exists());
+
+        // NOTE: the foreign file is not in the list
+        wait_next_second();
+        let files = BuildFiles::open(dir.path()).unwrap();
+        fs::write(dir.path().join("index.html"), "").unwrap();
+        assert!(files.finish(true, true).unwrap().is_empty());
+        assert!(dir.path().join("README.md").exists());
+
+        fs::remove_file(dir.path().join(LIST_FILE)).unwrap();
+        let files = BuildFiles::open(dir.path()).unwrap();
+        fs::write(dir.path().join("index.html"), "").unwrap();
+        assert_eq!(files.finish(true, true).unwrap().len(), 1);
+        assert!(!dir.path().join("README.md").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remove_non_utf8_files() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.txt");
+
+        let files = BuildFiles::open(dir.path()).unwrap();
+        fs::write(dir.path().join(name), "").unwrap();
+        files.finish(true, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join(LIST_FILE)).unwrap(),
+            "caf%E9.txt\n"
+        );
+
+        wait_next_second();
+        let files = BuildFiles::open(dir.path()).unwrap();
+        assert_eq!(files.finish(true, false).unwrap(), [dir.path().join(name)]);
+    }
+
+    #[test]
+    fn unwritable_build_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+
+        let err = BuildFiles::open(&file.join("build")).err().unwrap();
+        assert!(
+            matches!(&err, Error::BuildPathNotWritable { path, .. } if *path == file.join("build")),
+            "{:?}",
+            err
+        );
+    }
+
+    /// Wait for the next second so the files of the previous build are older than the start.
+    fn wait_next_second() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        std::thread::sleep(Duration::from_nanos(
+            1_000_000_000 - now.subsec_nanos() as u64 + 10_000_000,
+        ));
+    }
+}
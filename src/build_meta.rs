@@ -0,0 +1,268 @@
+//! The record of the last build in the build directory: its profile, when it finished, the commit
+//! of the workspace and the arguments of the command.
+//!
+//! The builds of all the profiles write the same files in the build directory, so a `serve`
+//! running in the background replaces a release build with a dev build without notice. The record
+//! is compared with the profile of every build and a dev build is not archived or deployed unless
+//! `--force` is given.
+//!
+//! The reproducible builds record the time of [`source_date_epoch`] instead of the current time,
+//! so two builds of the same commit write the same record.
+
+use crate::BuildProfile;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the record of the last build, in the build directory.
+pub(crate) const META_FILE: &str = ".wasmbl-meta.json";
+
+/// The variable that fixes the time of a build, see
+/// <https://reproducible-builds.org/specs/source-date-epoch/>.
+pub(crate) const SOURCE_DATE_EPOCH_VAR: &str = "SOURCE_DATE_EPOCH";
+
+/// The last build of a build directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BuildMeta {
+    /// The name of the profile: `dev`, `release` or `profiling`.
+    pub profile: String,
+    /// When the build finished, in seconds since the Unix epoch.
+    pub finished: u64,
+    /// The commit `HEAD` of the workspace, if it is in a git repository.
+    pub commit: Option<String>,
+    /// The arguments of the command that made the build, without the program.
+    pub args: Vec<String>,
+}
+
+impl BuildMeta {
+    /// The build that just finished in the workspace.
+    fn new(profile: BuildProfile, workspace_root: &Path, finished: u64) -> Self {
+        Self {
+            profile: profile.name().to_string(),
+            finished,
+            commit: head_commit(workspace_root),
+            args: std::env::args().skip(1).collect(),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "profile": self.profile,
+            "finished": self.finished,
+            "commit": self.commit,
+            "args": self.args,
+        })
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        Some(Self {
+            profile: value.get("profile")?.as_str()?.to_string(),
+            finished: value.get("finished")?.as_u64()?,
+            commit: value
+                .get("commit")
+                .and_then(|x| x.as_str())
+                .map(ToString::to_string),
+            args: value
+                .get("args")
+                .and_then(|x| x.as_array())
+                .map(|x| {
+                    x.iter()
+                        .filter_map(|x| x.as_str().map(ToString::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Where the build comes from: `the dev build of `serve` 3 min ago (commit 1a2b3c4)`.
+    fn describe(&self) -> String {
+        let mut description = format!("the {} build", self.profile);
+        if !self.args.is_empty() {
+            description.push_str(&format!(" of `{}`", self.args.join(" ")));
+        }
+        description.push_str(&format!(
+            " {} ago",
+            format_age(now().saturating_sub(self.finished))
+        ));
+        if let Some(commit) = self.commit.as_ref() {
+            description.push_str(&format!(" (commit {})", commit));
+        }
+        description
+    }
+}
+
+/// The record of the last build of the build directory, if there is one that can be read.
+pub(crate) fn read(build_path: &Path) -> Option<BuildMeta> {
+    let content = fs::read_to_string(build_path.join(META_FILE)).ok()?;
+    BuildMeta::from_json(&serde_json::from_str(&content).ok()?)
+}
+
+/// Record the build that just finished in the build directory, at the time of
+/// [`source_date_epoch`] if the build is `reproducible`.
+pub(crate) fn write(
+    build_path: &Path,
+    profile: BuildProfile,
+    workspace_root: &Path,
+    reproducible: bool,
+) -> Result<()> {
+    let path = build_path.join(META_FILE);
+    let finished = if reproducible {
+        source_date_epoch(workspace_root)
+    } else {
+        now()
+    };
+    let meta = BuildMeta::new(profile, workspace_root, finished);
+    crate::write_atomic(&path, format!("{:#}\n", meta.to_json()).as_bytes())
+        .with_context(|| format!("could not write `{}`", path.display()))
+}
+
+/// Warn if the build directory contains the build of another profile, which is about to be
+/// replaced.
+pub(crate) fn check_profile(build_path: &Path, profile: BuildProfile) {
+    if let Some(meta) = read(build_path).filter(|x| x.profile != profile.name()) {
+        crate::reporter::reporter().warning(&format!(
+            "the build directory `{}` contains {}: it is replaced by a {} build",
+            build_path.display(),
+            meta.describe(),
+            profile.name()
+        ));
+    }
+}
+
+/// Fail if the build directory contains a dev build, unless `force` is enabled: another command
+/// (like a `serve` running in the background) replaced the release build.
+#[cfg(any(feature = "archive", feature = "deploy"))]
+pub(crate) fn check_shippable(build_path: &Path, force: bool) -> Result<()> {
+    let meta = match read(build_path) {
+        Some(meta) if meta.profile == BuildProfile::Dev.name() => meta,
+        _ => return Ok(()),
+    };
+    if force {
+        crate::reporter::reporter().warning(&format!(
+            "the build directory `{}` contains {}",
+            build_path.display(),
+            meta.describe()
+        ));
+        return Ok(());
+    }
+
+    anyhow::bail!(
+        "the build directory `{}` contains {}, it is not shipped (stop the other command and \
+        build again, or pass `--force`)",
+        build_path.display(),
+        meta.describe()
+    )
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
+
+/// The time of a reproducible build, in seconds since the Unix epoch: `SOURCE_DATE_EPOCH`, or the
+/// time of the commit `HEAD` of the workspace, or 0 outside of a git repository.
+pub(crate) fn source_date_epoch(workspace_root: &Path) -> u64 {
+    std::env::var(SOURCE_DATE_EPOCH_VAR)
+        .ok()
+        .and_then(|x| x.trim().parse().ok())
+        .or_else(|| {
+            git(workspace_root, &["log", "-1", "--format=%ct"])?
+                .parse()
+                .ok()
+        })
+        .unwrap_or(0)
+}
+
+fn git(repo: &Path, args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .current_dir(repo)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|x| x.status.success())
+        .map(|x| String::from_utf8_lossy(&x.stdout).trim().to_string())
+}
+
+/// The short hash of the commit `HEAD` of the repository, with `-dirty` if the working tree has
+/// uncommitted changes.
+fn head_commit(repo: &Path) -> Option<String> {
+    let head = git(
+        repo,
+        &["rev-parse", "--short", "--verify", "--quiet", "HEAD"],
+    )?;
+    match git(repo, &["status", "--porcelain", "--untracked-files=no"]) {
+        Some(status) if !status.is_empty() => Some(format!("{}-dirty", head)),
+        _ => Some(head),
+    }
+}
+
+/// A duration in seconds, rounded: `12 s`, `3 min`, `5 h`, `2 days`.
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{} s", secs),
+        60..=3599 => format!("{} min", secs / 60),
+        3600..=86399 => format!("{} h", secs / 3600),
+        _ => format!("{} days", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(feature = "archive", feature = "deploy"))]
+    #[test]
+    fn refuse_to_ship_a_dev_build() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(read(dir.path()), None);
+        check_shippable(dir.path(), false).unwrap();
+
+        write(dir.path(), BuildProfile::Release, dir.path(), false).unwrap();
+        let meta = read(dir.path()).unwrap();
+        assert_eq!(meta.profile, "release");
+        assert_eq!(meta.commit, None);
+        check_shippable(dir.path(), false).unwrap();
+
+        fs::write(
+            dir.path().join(META_FILE),
+            r#"{ "profile": "dev", "finished": 0, "commit": "1a2b3c4", "args": ["serve"] }"#,
+        )
+        .unwrap();
+        let err = check_shippable(dir.path(), false).unwrap_err().to_string();
+        assert!(
+            err.contains("contains the dev build of `serve` "),
+            "{}",
+            err
+        );
+        assert!(err.contains(" days ago (commit 1a2b3c4)"), "{}", err);
+        assert!(err.contains("--force"), "{}", err);
+        check_shippable(dir.path(), true).unwrap();
+    }
+
+    #[test]
+    fn reproducible_record() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), BuildProfile::Release, dir.path(), true).unwrap();
+        let first = fs::read(dir.path().join(META_FILE)).unwrap();
+        assert_eq!(
+            read(dir.path()).unwrap().finished,
+            source_date_epoch(dir.path())
+        );
+
+        write(dir.path(), BuildProfile::Release, dir.path(), true).unwrap();
+        assert_eq!(fs::read(dir.path().join(META_FILE)).unwrap(), first);
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(format_age(0), "0 s");
+        assert_eq!(format_age(150), "2 min");
+        assert_eq!(format_age(7200), "2 h");
+        assert_eq!(format_age(3 * 86400), "3 days");
+    }
+}
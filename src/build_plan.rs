@@ -0,0 +1,375 @@
+//! The plan of a build: the external commands it runs (`build --explain` prints them without
+//! running them) or a record of what it did (`build --emit-build-plan <path>`), for the reports of
+//! builds that don't behave as expected.
+//!
+//! The record is a JSON document with the commands that ran (their program, arguments,
+//! environment, directory, exit code and duration), the effective settings of the build
+//! ([`crate::config`]) and the files of the build. Only the names of the environment variables
+//! that wasmbl does not set itself are kept (the variables added by the hooks might be secrets):
+//! their value is `null`.
+
+use crate::{BuildArgs, BuildProfile, BuildTarget, Error, Hooks};
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// The environment variables set by wasmbl, the values of the others are not kept.
+const KNOWN_VARS: &[&str] = &[
+    "CARGO_ENCODED_RUSTFLAGS",
+    "CARGO_TERM_COLOR",
+    "CARGO_PROFILE_DEV_PANIC",
+    "CARGO_PROFILE_DEV_DEBUG_ASSERTIONS",
+    "CARGO_PROFILE_RELEASE_PANIC",
+    "CARGO_PROFILE_RELEASE_DEBUG_ASSERTIONS",
+    "DYLD_LIBRARY_PATH",
+    "WASMBL_WORKSPACE_ROOT",
+    "WASMBL_PACKAGE_DIR",
+    "WASMBL_PACKAGE_NAME",
+    "WASMBL_BUILD_PATH",
+    "WASMBL_PROFILE",
+    "WASMBL_JS_GLUE",
+    "WASMBL_SNIPPETS_DIR",
+    "SOURCE_DATE_EPOCH",
+    "BINARYEN_CORES",
+];
+
+/// The commands that ran since [`record`], `None` if they are not recorded.
+static COMMANDS: Lazy<Mutex<Option<Vec<Value>>>> = Lazy::new(Default::default);
+
+/// A command line: the program, its arguments, the environment variables added to the environment
+/// of wasmbl and the directory it runs in.
+#[derive(Debug)]
+struct Invocation {
+    program: String,
+    args: Vec<String>,
+    /// The value is `None` if it is not kept.
+    env: Vec<(String, Option<String>)>,
+    cwd: PathBuf,
+}
+
+impl Invocation {
+    fn of(command: &Command) -> Self {
+        let lossy = |x: &std::ffi::OsStr| x.to_string_lossy().into_owned();
+        let mut env: Vec<_> = command
+            .get_envs()
+            .filter_map(|(name, value)| {
+                let name = lossy(name);
+                // NOTE: the variables removed from the environment are not additions
+                let value = value.map(lossy)?;
+                let known = KNOWN_VARS.contains(&name.as_str());
+                Some((name, known.then_some(value)))
+            })
+            .collect();
+        env.sort();
+
+        Self {
+            program: lossy(command.get_program()),
+            args: command.get_args().map(lossy).collect(),
+            env,
+            cwd: command
+                .get_current_dir()
+                .map(Path::to_path_buf)
+                .or_else(|| std::env::current_dir().ok())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let env: serde_json::Map<_, _> = self
+            .env
+            .iter()
+            .map(|(name, value)| (name.clone(), json!(value)))
+            .collect();
+        json!({
+            "program": self.program,
+            "args": self.args,
+            "env": env,
+            "cwd": self.cwd.display().to_string(),
+        })
+    }
+
+    /// The command line for a shell, like `cd /app && WASMBL_PROFILE=release sh -c 'npm run build'`.
+    fn to_text(&self) -> String {
+        let mut words = vec![
+            "cd".to_string(),
+            quote(&self.cwd.display().to_string()),
+            "&&".to_string(),
+        ];
+        words.extend(self.env.iter().map(|(name, value)| match value {
+            Some(value) => format!("{}={}", name, quote(value)),
+            None => format!("{}=<redacted>", name),
+        }));
+        words.push(quote(&self.program));
+        words.extend(self.args.iter().map(|x| quote(x)));
+        words.join(" ")
+    }
+}
+
+/// A word of a command line quoted for a shell if needed.
+pub(crate) fn quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else if word.chars().any(char::is_control) {
+        // NOTE: the separator of `CARGO_ENCODED_RUSTFLAGS` would be invisible
+        format!("{:?}", word)
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Start to record the commands run by [`crate::process::GroupChild`], for [`write`].
+pub(crate) fn record() {
+    *COMMANDS.lock().unwrap() = Some(Vec::new());
+}
+
+/// A command that is running while the commands are recorded.
+pub(crate) struct Running {
+    invocation: Invocation,
+    start: Instant,
+}
+
+/// The command about to start, `None` if the commands are not recorded.
+pub(crate) fn started(command: &Command) -> Option<Running> {
+    COMMANDS.lock().unwrap().as_ref()?;
+    Some(Running {
+        invocation: Invocation::of(command),
+        start: Instant::now(),
+    })
+}
+
+impl Running {
+    /// Record the command with its exit status, `None` if it could not start or be waited for.
+    pub(crate) fn finish(self, status: Option<&ExitStatus>) {
+        let mut command = self.invocation.to_json();
+        command["exit_code"] = json!(status.and_then(ExitStatus::code));
+        command["duration"] = json!(self.start.elapsed().as_secs_f64());
+        if let Some(commands) = COMMANDS.lock().unwrap().as_mut() {
+            commands.push(command);
+        }
+    }
+}
+
+/// Write the plan of the build that started at `start` with the commands recorded since
+/// [`record`]. The files of the build directory are only listed if the build succeeded.
+pub(crate) fn write(
+    path: &Path,
+    args: &dyn BuildArgs,
+    hooks: &Hooks,
+    start: Instant,
+    error: Option<&Error>,
+) -> Result<(), Error> {
+    let commands = COMMANDS.lock().unwrap().take().unwrap_or_default();
+    let artifacts = match error {
+        Some(_) => Vec::new(),
+        None => artifacts(&args.build_path())?,
+    };
+    let plan = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "duration": start.elapsed().as_secs_f64(),
+        "error": error.map(crate::error_chain),
+        "commands": commands,
+        "config": crate::config::to_json(&crate::config::resolve(args, None, hooks)),
+        "artifacts": artifacts,
+    });
+    fs::write(path, format!("{:#}\n", plan)).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    log::info!("Wrote the plan of the build to `{}`", path.display());
+
+    Ok(())
+}
+
+/// The files of the build (like the archive of `build --archive`, without the stale files of
+/// the build directory) with their sizes and SHA-256 checksums.
+fn artifacts(build_path: &Path) -> Result<Vec<Value>, Error> {
+    let mut artifacts = Vec::new();
+    for file in crate::build_files::recorded(build_path)? {
+        let path = build_path.join(&file);
+        let content = fs::read(&path).map_err(|source| Error::Io { path, source })?;
+        artifacts.push(json!({
+            "path": crate::slash_path(&file),
+            "size": content.len(),
+            "sha256": format!("{:x}", Sha256::digest(&content)),
+        }));
+    }
+    Ok(artifacts)
+}
+
+/// The commands that the `build` command would run, with the names of their steps.
+///
+/// The `pre_build` hook is not run: the changes it makes to the cargo command are missing.
+fn planned(args: &dyn BuildArgs) -> Result<Vec<(String, Invocation)>, Error> {
+    let profile = crate::resolve_profile(BuildProfile::Release, args);
+    let mut commands = Vec::new();
+
+    let mut command = crate::cargo_command("build", profile, args, false)?;
+    crate::rustflags::merge_rustflags(&mut command);
+    commands.push((
+        "cargo (without the changes of the `pre_build` hook)".to_string(),
+        Invocation::of(&command),
+    ));
+
+    // NOTE: the library of binaryen optimizes the WASM in the process
+    #[cfg(feature = "prebuilt-wasm-opt")]
+    if args.wasm_opt() {
+        let wasm_opt = crate::prebuilt_wasm_opt::installed_wasm_opt(args.target_path())
+            .ok()
+            .flatten();
+        let mut command = Command::new(match wasm_opt.as_deref() {
+            Some(path) => path.as_os_str(),
+            None => "wasm-opt".as_ref(),
+        });
+        command.args(crate::wasm_opt_args(&crate::effective_wasm_opt_options(
+            args, profile,
+        )));
+        commands.push((
+            format!(
+                "wasm-opt{}, the WASM generated by `wasm-bindgen` is given on its standard input",
+                if wasm_opt.is_none() {
+                    " (downloaded by the build)"
+                } else {
+                    ""
+                }
+            ),
+            Invocation::of(&command),
+        ));
+    }
+
+    if let Some(line) = args
+        .bundle_command()
+        .filter(|_| args.target() == BuildTarget::Web)
+    {
+        let js_path = args
+            .build_path()
+            .join(args.out_layout().dir())
+            .join(format!("{}.js", args.out_name()));
+        let command = crate::bundle_command(args, &line, profile, &js_path);
+        commands.push(("bundle command".to_string(), Invocation::of(&command)));
+    }
+
+    Ok(commands)
+}
+
+/// Print the commands that the `build` command would run, see [`BuildArgs::explain`].
+pub(crate) fn explain(args: &dyn BuildArgs) -> Result<(), Error> {
+    for (step, invocation) in planned(args)? {
+        println!("# {}\n{}", step, invocation.to_text());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_the_unknown_variables() {
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", "echo 'it works'"])
+            .current_dir("/app")
+            .env("WASMBL_PROFILE", "release")
+            .env("NPM_TOKEN", "secret")
+            .env("CARGO_ENCODED_RUSTFLAGS", "--cfg\x1fa b")
+            .env_remove("NODE_ENV");
+        let invocation = Invocation::of(&command);
+        assert_eq!(
+            invocation.env,
+            [
+                (
+                    "CARGO_ENCODED_RUSTFLAGS".to_string(),
+                    Some("--cfg\x1fa b".to_string())
+                ),
+                ("NPM_TOKEN".to_string(), None),
+                ("WASMBL_PROFILE".to_string(), Some("release".to_string())),
+            ]
+        );
+        assert_eq!(
+            invocation.to_json(),
+            json!({
+                "program": "sh",
+                "args": ["-c", "echo 'it works'"],
+                "env": {
+                    "CARGO_ENCODED_RUSTFLAGS": "--cfg\x1fa b",
+                    "NPM_TOKEN": null,
+                    "WASMBL_PROFILE": "release",
+                },
+                "cwd": "/app",
+            })
+        );
+        assert_eq!(
+            invocation.to_text(),
+            "cd /app && CARGO_ENCODED_RUSTFLAGS=\"--cfg\\u{1f}a b\" NPM_TOKEN=<redacted> \
+            WASMBL_PROFILE=release sh -c 'echo '\\''it works'\\'''"
+        );
+    }
+
+    #[test]
+    fn commands_of_the_build() {
+        crate::runner::tests::init_project();
+        let args = <crate::DefaultBuildArgs as structopt::StructOpt>::from_iter_safe([
+            "build",
+            "--explain",
+            "--no-wasm-opt",
+            "--features",
+            "a,b",
+            "--bundle-command",
+            "npm run build",
+        ])
+        .unwrap();
+        assert!(args.explain());
+        let commands = planned(&args).unwrap();
+        let steps: Vec<_> = commands.iter().map(|(step, _)| step.as_str()).collect();
+        assert_eq!(
+            steps,
+            [
+                "cargo (without the changes of the `pre_build` hook)",
+                "bundle command"
+            ]
+        );
+
+        let cargo = &commands[0].1;
+        assert_eq!(cargo.program, "cargo");
+        assert_eq!(cargo.args[..2], ["build", "--lib"]);
+        assert!(cargo.args.contains(&"--release".to_string()));
+        assert!(cargo.args.windows(2).any(|x| x == ["--features", "a,b"]));
+        let bundle = commands[1].1.to_text();
+        assert!(bundle.contains(" sh -c 'npm run build'"), "{}", bundle);
+        assert!(bundle.contains(" WASMBL_PROFILE=release "), "{}", bundle);
+    }
+
+    #[test]
+    fn record_the_commands() {
+        record();
+        let mut command = Command::new("sh");
+        command.args(["-c", "exit 3"]);
+        let status = crate::process::GroupChild::spawn(&mut command)
+            .and_then(|mut x| x.wait())
+            .unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert!(crate::process::GroupChild::spawn(&mut Command::new("/nonexistent")).is_err());
+
+        // NOTE: the other tests might run commands in the meantime
+        let commands = COMMANDS.lock().unwrap().take().unwrap();
+        let exited = commands
+            .iter()
+            .find(|x| x["args"] == json!(["-c", "exit 3"]))
+            .unwrap();
+        assert_eq!(exited["exit_code"], json!(3));
+        assert!(exited["duration"].as_f64().unwrap() >= 0.0);
+        let missing = commands
+            .iter()
+            .find(|x| x["program"] == json!("/nonexistent"))
+            .unwrap();
+        assert_eq!(missing["exit_code"], Value::Null);
+
+        assert!(started(&command).is_none());
+    }
+}
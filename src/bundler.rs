@@ -89,7 +89,7 @@ fn list_cargo_rerun_if_changed_files(opt: &WebBundlerOpt) -> Result<()> {
         println!("cargo:rerun-if-changed={}", entry.path().display());
     }
     for additional_watch_dir in &opt.additional_watch_dirs {
-        for entry in WalkDir::new(&additional_watch_dir)
+        for entry in WalkDir::new(additional_watch_dir)
             .into_iter()
             .filter_map(|e| e.ok())
         {
@@ -135,12 +135,9 @@ fn run_with_clean_build_script_environment_variables<T>(
         "RUSTC_LINKER",
     ];
 
-    let build_script_var_prefixes = vec!["CARGO_FEATURE_", "CARGO_CFG_", "DEP_"];
+    let build_script_var_prefixes = ["CARGO_FEATURE_", "CARGO_CFG_", "DEP_"];
 
-    for key in build_script_vars_list
-        .into_iter()
-        .chain(additional_vars.into_iter())
-    {
+    for key in build_script_vars_list.into_iter().chain(additional_vars) {
         existing_values.insert(key.into(), std::env::var_os(key));
         std::env::remove_var(key);
     }
@@ -148,7 +145,7 @@ fn run_with_clean_build_script_environment_variables<T>(
     for (key, value) in std::env::vars_os() {
         if build_script_var_prefixes
             .iter()
-            .any(|prefix| key.to_string_lossy().starts_with(prefix))
+            .any(|prefix| key.as_encoded_bytes().starts_with(prefix.as_bytes()))
         {
             existing_values.insert(key.clone(), Some(value));
             std::env::remove_var(key);
@@ -239,15 +236,13 @@ fn bundle_assets(opt: &WebBundlerOpt) -> Result<()> {
     let src = opt.src_dir.join("static");
     let dest = &opt.dist_dir;
     if src.exists() {
-        fs_extra::dir::copy(&src, &dest, &fs_extra::dir::CopyOptions::new()).with_context(
-            || {
-                format!(
-                    "Failed to copy static files from {} to {}",
-                    src.display(),
-                    dest.display()
-                )
-            },
-        )?;
+        fs_extra::dir::copy(&src, dest, &fs_extra::dir::CopyOptions::new()).with_context(|| {
+            format!(
+                "Failed to copy static files from {} to {}",
+                src.display(),
+                dest.display()
+            )
+        })?;
     }
     Ok(())
 }
@@ -284,7 +279,7 @@ fn bundle_index_html(opt: &WebBundlerOpt) -> Result<()> {
         indented_syntax: true,
         include_paths: Vec::new(),
     };
-    let style_src_path = opt.src_dir.join("css/style.scss");
+    let style_src_path = opt.src_dir.join("css").join("style.scss");
     let style_css_content = sass_rs::compile_file(&style_src_path, sass_options)
         .map_err(|e| anyhow!("Sass compilation failed: {}", e))?;
 
@@ -322,15 +317,13 @@ fn bundle_js_snippets(opt: &WebBundlerOpt) -> Result<()> {
     let dest = &opt.dist_dir;
 
     if src.exists() {
-        fs_extra::dir::copy(&src, &dest, &fs_extra::dir::CopyOptions::new()).with_context(
-            || {
-                format!(
-                    "Failed to copy js snippets from {} to {}",
-                    src.display(),
-                    dest.display()
-                )
-            },
-        )?;
+        fs_extra::dir::copy(&src, dest, &fs_extra::dir::CopyOptions::new()).with_context(|| {
+            format!(
+                "Failed to copy js snippets from {} to {}",
+                src.display(),
+                dest.display()
+            )
+        })?;
     }
     Ok(())
 }
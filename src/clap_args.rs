@@ -0,0 +1,1451 @@
+//! Implementations of the `clap` traits for the default arguments.
+//!
+//! The traits are implemented manually because `clap` also interprets the `structopt` attributes
+//! of the fields and some of them are not compatible.
+
+use super::{DefaultBuildArgs, DefaultCleanArgs, DefaultServeArgs, DefaultTestArgs, Verbosity};
+use clap::parser::ValueSource;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Args, Command, FromArgMatches};
+use std::ffi::OsString;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+fn flag(id: &'static str, long: &'static str, help: &'static str) -> Arg {
+    Arg::new(id)
+        .long(long)
+        .action(ArgAction::SetTrue)
+        .help(help)
+}
+
+impl FromArgMatches for DefaultBuildArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self {
+            build_path: matches.get_one::<PathBuf>("build_path").cloned(),
+            profiling: matches.get_flag("profiling"),
+            manifest_path: matches.get_one::<PathBuf>("manifest_path").cloned(),
+            package: matches.get_one::<String>("package").cloned(),
+            features: matches
+                .get_many::<String>("features")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default(),
+            no_default_features: matches.get_flag("no_default_features"),
+            all_features: matches.get_flag("all_features"),
+            target_dir: matches.get_one::<PathBuf>("target_dir").cloned(),
+            wasm_opt_level: matches.get_one::<u32>("wasm_opt_level").copied(),
+            wasm_opt_shrink_level: matches.get_one::<u32>("wasm_opt_shrink_level").copied(),
+            wasm_opt_converge: matches.get_flag("wasm_opt_converge"),
+            wasm_opt_passes: matches
+                .get_many::<String>("wasm_opt_passes")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default(),
+            wasm_opt_retries: matches
+                .get_one::<u32>("wasm_opt_retries")
+                .copied()
+                .unwrap_or(0),
+            no_wasm_opt: matches.get_flag("no_wasm_opt"),
+            panic_strategy: matches
+                .get_one::<String>("panic_strategy")
+                .map(|x| x.parse().expect("the value has been validated; qed")),
+            typescript: matches.get_flag("typescript"),
+            reference_types: matches.get_flag("reference_types"),
+            clean: matches.get_flag("clean"),
+            no_clean: matches.get_flag("no_clean"),
+            allow_unoptimized: matches.get_flag("allow_unoptimized"),
+            out_name: matches.get_one::<String>("out_name").cloned(),
+            static_dir: matches.get_one::<PathBuf>("static_dir").cloned(),
+            target: matches
+                .get_one::<String>("target")
+                .map(|x| x.parse().expect("the value has been validated; qed")),
+            out_layout: matches
+                .get_one::<String>("out_layout")
+                .map(|x| x.parse().expect("the value has been validated; qed")),
+            package_json: matches.get_flag("package_json"),
+            overwrite_package_json: matches.get_flag("overwrite_package_json"),
+            bundle_command: matches.get_one::<String>("bundle_command").cloned(),
+            bundle_outputs: matches
+                .get_many::<PathBuf>("bundle_outputs")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default(),
+            bundle_sources: matches.get_one::<PathBuf>("bundle_sources").cloned(),
+            emit_server_config: matches
+                .get_one::<String>("emit_server_config")
+                .map(|x| x.parse().expect("the value has been validated; qed")),
+            base_url: matches.get_one::<String>("base_url").cloned(),
+            archive: matches.get_one::<PathBuf>("archive").cloned(),
+            force: matches.get_flag("force"),
+            size_compare: matches.get_one::<PathBuf>("size_compare").cloned(),
+            size_compare_fail: matches
+                .get_one::<crate::SizeThreshold>("size_compare_fail")
+                .copied(),
+            size_compare_format: matches
+                .get_one::<String>("size_compare_format")
+                .map(|x| x.parse().expect("the value has been validated; qed"))
+                .unwrap_or(crate::SizeFormat::Table),
+            size_manifest: matches.get_one::<PathBuf>("size_manifest").cloned(),
+            check_i18n: matches.get_flag("check_i18n"),
+            env_files: matches
+                .get_many::<PathBuf>("env_files")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default(),
+            #[cfg(feature = "prerender")]
+            prerender: matches
+                .get_many::<String>("prerender")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default(),
+            #[cfg(feature = "prerender")]
+            prerender_ready: matches.get_one::<String>("prerender_ready").cloned(),
+            print_config: if matches.value_source("print_config") == Some(ValueSource::CommandLine)
+            {
+                Some(
+                    matches
+                        .get_one::<String>("print_config")
+                        .map(|x| x.parse().expect("the value has been validated; qed")),
+                )
+            } else {
+                None
+            },
+            emit_build_plan: matches.get_one::<PathBuf>("emit_build_plan").cloned(),
+            explain: matches.get_flag("explain"),
+            trunk_index: matches.get_one::<PathBuf>("trunk_index").cloned(),
+            frontend_dir: matches.get_one::<PathBuf>("frontend_dir").cloned(),
+            reproducible: matches.get_flag("reproducible"),
+            release_id: matches.get_one::<String>("release_id").cloned(),
+            sourcemap_upload_command: matches
+                .get_one::<String>("sourcemap_upload_command")
+                .cloned(),
+            upload_errors_fatal: matches.get_flag("upload_errors_fatal"),
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Args for DefaultBuildArgs {
+    fn augment_args(cmd: Command) -> Command {
+        let cmd = cmd.arg(
+            Arg::new("build_path")
+                .long("build-path")
+                .short('d')
+                .visible_alias("out-dir")
+                .value_name("BUILD_PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("Build directory output"),
+        )
+        .arg(flag(
+            "profiling",
+            "profiling",
+            "Create a profiling build. Enable optimizations and debug info",
+        ))
+        .arg(
+            Arg::new("manifest_path")
+                .long("manifest-path")
+                .value_name("MANIFEST_PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help(
+                    "Path to the manifest of the frontend package, relative to the workspace root",
+                ),
+        )
+        // NOTE: `-p` is already the port of the `serve` command
+        .arg(
+            Arg::new("package")
+                .long("package")
+                .value_name("PACKAGE")
+                .conflicts_with("manifest_path")
+                .help("Name of the frontend package in the workspace"),
+        )
+        .arg(
+            Arg::new("features")
+                .long("features")
+                .value_name("FEATURES")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .help("Features of the frontend package to activate (comma separated)"),
+        )
+        .arg(flag(
+            "no_default_features",
+            "no-default-features",
+            "Do not activate the default features of the frontend package",
+        ))
+        .arg(flag(
+            "all_features",
+            "all-features",
+            "Activate all the features of the frontend package",
+        ))
+        .arg(
+            Arg::new("target_dir")
+                .long("target-dir")
+                .value_name("TARGET_DIR")
+                .value_parser(value_parser!(PathBuf))
+                .help("Directory for all the artifacts of cargo"),
+        )
+        .arg(
+            Arg::new("wasm_opt_level")
+                .long("wasm-opt-level")
+                .value_name("WASM_OPT_LEVEL")
+                .value_parser(value_parser!(u32).range(0..=4))
+                .help(
+                    "Optimization level of `wasm-opt` for the release and profiling builds \
+                    (default: 2)",
+                ),
+        )
+        .arg(
+            Arg::new("wasm_opt_shrink_level")
+                .long("wasm-opt-shrink-level")
+                .value_name("WASM_OPT_SHRINK_LEVEL")
+                .value_parser(value_parser!(u32).range(0..=2))
+                .help(
+                    "Shrink level of `wasm-opt` (default: 1 for the release builds, 0 for the \
+                    profiling builds)",
+                ),
+        )
+        .arg(flag(
+            "wasm_opt_converge",
+            "wasm-opt-converge",
+            "Run the optimizations of `wasm-opt` until the WASM stops shrinking",
+        ))
+        .arg(
+            Arg::new("wasm_opt_passes")
+                .long("wasm-opt-passes")
+                .value_name("WASM_OPT_PASSES")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .help(
+                    "Passes of `wasm-opt` run after the optimizations (comma separated, without \
+                    `--`)",
+                ),
+        )
+        .arg(
+            Arg::new("wasm_opt_retries")
+                .long("wasm-opt-retries")
+                .value_name("WASM_OPT_RETRIES")
+                .value_parser(value_parser!(u32))
+                .default_value("0")
+                .help("Run `wasm-opt` again up to this number of times when it crashes or fails"),
+        )
+        .arg(flag(
+            "no_wasm_opt",
+            "no-wasm-opt",
+            "Do not optimize the WASM of the release and profiling builds with `wasm-opt`",
+        ))
+        .arg(
+            Arg::new("panic_strategy")
+                .long("panic-strategy")
+                .value_name("PANIC_STRATEGY")
+                .value_parser(["unwind-verbose", "abort", "abort-strip"])
+                .help(
+                    "How the WASM handles the panics: `unwind-verbose`, `abort` or `abort-strip` \
+                    (default: the key `panic-strategy` of the metadata of the frontend package)",
+                ),
+        )
+        .arg(flag(
+            "typescript",
+            "typescript",
+            "Generate the TypeScript declarations (`app.d.ts`) in the build directory",
+        ))
+        .arg(flag(
+            "reference_types",
+            "reference-types",
+            "Pass the JS values to the WASM with the reference types instead of the table of the \
+            JS bindings (the browsers must support them)",
+        ))
+        .arg(
+            flag(
+                "clean",
+                "clean",
+                "Remove the files of the previous builds that this build did not produce",
+            )
+            .conflicts_with("no_clean"),
+        )
+        .arg(flag(
+            "no_clean",
+            "no-clean",
+            "Keep the files of the previous builds in the build directory",
+        ))
+        .arg(flag(
+            "allow_unoptimized",
+            "allow-unoptimized",
+            "Build in release mode even if `wasm-opt` is not available: the WASM is not optimized",
+        ))
+        .arg(
+            Arg::new("out_name")
+                .long("out-name")
+                .value_name("OUT_NAME")
+                .help(
+                    "Base name of the generated files: `<name>.js`, `<name>_bg.wasm` and \
+                    `<name>.d.ts` (default: `app`)",
+                ),
+        )
+        .arg(
+            Arg::new("static_dir")
+                .long("static-dir")
+                .value_name("STATIC_DIR")
+                .value_parser(value_parser!(PathBuf))
+                .help(
+                    "Directory of the static files copied to the build directory (default: \
+                    `static` in the frontend package)",
+                ),
+        )
+        .arg(
+            Arg::new("target")
+                .long("target")
+                .value_name("TARGET")
+                .value_parser(["wasm32-unknown-unknown", "wasm32-wasi", "wasm32-wasip1"])
+                .help(
+                    "Target of the WASM: `wasm32-unknown-unknown` (the default) or `wasm32-wasi`",
+                ),
+        )
+        .arg(
+            Arg::new("out_layout")
+                .long("out-layout")
+                .value_name("OUT_LAYOUT")
+                .value_parser(["flat", "pkg", "node"])
+                .help(
+                    "Layout of the generated files: `flat` (the default), `pkg` to generate an \
+                    npm package in `pkg/` like `wasm-pack` or `node` to generate the bindings \
+                    for Node.js in `node/`",
+                ),
+        )
+        .arg(flag(
+            "package_json",
+            "package-json",
+            "Write a `package.json` in the build directory",
+        ))
+        .arg(flag(
+            "overwrite_package_json",
+            "overwrite-package-json",
+            "Replace the `package.json` even if it has not been written by a build",
+        ))
+        .arg(
+            Arg::new("bundle_command")
+                .long("bundle-command")
+                .value_name("BUNDLE_COMMAND")
+                .help(
+                    "Command run by the shell after the build to bundle the JS bindings and the \
+                    snippets",
+                ),
+        )
+        .arg(
+            Arg::new("bundle_outputs")
+                .long("bundle-output")
+                .value_name("BUNDLE_OUTPUT")
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append)
+                .help(
+                    "File written by the bundle command, relative to the build directory (can be \
+                    repeated)",
+                ),
+        )
+        .arg(
+            Arg::new("bundle_sources")
+                .long("bundle-sources")
+                .value_name("BUNDLE_SOURCES")
+                .value_parser(value_parser!(PathBuf))
+                .help(
+                    "Directory of the sources of the bundle: their changes only run the bundle \
+                    command again",
+                ),
+        )
+        .arg(
+            Arg::new("emit_server_config")
+                .long("emit-server-config")
+                .value_name("EMIT_SERVER_CONFIG")
+                .value_parser(["nginx", "caddy", "netlify", "vercel"])
+                .help(
+                    "Write the configuration of a static host in the build directory: `nginx`, \
+                    `caddy`, `netlify` or `vercel`",
+                ),
+        )
+        .arg(
+            Arg::new("base_url")
+                .long("base-url")
+                .value_name("BASE_URL")
+                .help(
+                    "URL path where the build directory is deployed, used by the server \
+                    configuration and the default `index.html` (default: `/`)",
+                ),
+        )
+        .arg(
+            Arg::new("archive")
+                .long("archive")
+                .value_name("ARCHIVE")
+                .value_parser(value_parser!(PathBuf))
+                .help(
+                    "Write the files of the build in an archive: `<path>.tar.gz`, `<path>.tgz` or \
+                    `<path>.zip` (requires the feature `archive`)",
+                ),
+        )
+        .arg(flag(
+            "force",
+            "force",
+            "Replace the archive if it already exists, archive or deploy the build directory even \
+            if it contains a dev build",
+        ))
+        .arg(
+            Arg::new("size_compare")
+                .long("size-compare")
+                .value_name("SIZE_COMPARE")
+                .value_parser(value_parser!(PathBuf))
+                .help(
+                    "Compare the sizes of the files of the build with a previous build: a \
+                    manifest written by `--size-manifest` or a build directory",
+                ),
+        )
+        .arg(
+            Arg::new("size_compare_fail")
+                .long("size-compare-fail")
+                .value_name("SIZE_COMPARE_FAIL")
+                .value_parser(|x: &str| x.parse::<crate::SizeThreshold>().map_err(|x| x.to_string()))
+                .requires("size_compare")
+                .help(
+                    "Fail if the total gzip size grew more than this since the previous build: a \
+                    size (`10KB`) or a percentage (`5%`)",
+                ),
+        )
+        .arg(
+            Arg::new("size_compare_format")
+                .long("size-compare-format")
+                .value_name("SIZE_COMPARE_FORMAT")
+                .value_parser(["table", "json", "markdown"])
+                .default_value("table")
+                .help("Format of the comparison of the sizes: `table`, `json` or `markdown`"),
+        )
+        .arg(
+            Arg::new("size_manifest")
+                .long("size-manifest")
+                .value_name("SIZE_MANIFEST")
+                .value_parser(value_parser!(PathBuf))
+                .help("Write the sizes of the files of the build in a JSON manifest, for `--size-compare`"),
+        )
+        .arg(flag(
+            "check_i18n",
+            "check-i18n",
+            "Fail the build if the locales do not define the same messages as the default locale",
+        ))
+        .arg(
+            Arg::new("env_files")
+                .long("env-file")
+                .value_name("ENV_FILE")
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append)
+                .help(
+                    "File of the variables given to the templates of the pages, instead of the \
+                    `.env` files of the workspace and of the frontend package (can be repeated)",
+                ),
+        )
+        .arg(
+            Arg::new("print_config")
+                .long("print-config")
+                .value_name("FORMAT")
+                .num_args(0..=1)
+                .value_parser(["text", "json"])
+                .help(
+                    "Print the effective settings and where they come from instead of building: \
+                    `text` (the default) or `json`",
+                ),
+        )
+        .arg(
+            Arg::new("emit_build_plan")
+                .long("emit-build-plan")
+                .value_name("EMIT_BUILD_PLAN")
+                .value_parser(value_parser!(PathBuf))
+                .help(
+                    "Write the commands run by the build, the effective settings and the files of \
+                    the build in a JSON file, to report or reproduce what the build did",
+                ),
+        )
+        .arg(flag(
+            "explain",
+            "explain",
+            "Print the commands that the build would run instead of building",
+        ))
+        .arg(
+            Arg::new("trunk_index")
+                .long("trunk-index")
+                .value_name("TRUNK_INDEX")
+                .value_parser(value_parser!(PathBuf))
+                .help(
+                    "HTML template with the asset directives of Trunk (`<link data-trunk ...>`), \
+                    written as the `index.html` of the build directory",
+                ),
+        )
+        .arg(
+            Arg::new("frontend_dir")
+                .long("frontend-dir")
+                .value_name("FRONTEND_DIR")
+                .value_parser(value_parser!(PathBuf))
+                .help(
+                    "Directory of the web assets (the pages, the static files and the styles) if \
+                    they are not in the frontend package. The relative paths of `--static-dir` and \
+                    `--trunk-index` are relative to it",
+                ),
+        )
+        .arg(flag(
+            "reproducible",
+            "reproducible",
+            "Make two builds of the same commit write the same files: the time of the build comes \
+            from `SOURCE_DATE_EPOCH` or the commit, and `wasm-opt` runs on a single thread",
+        ))
+        .arg(
+            Arg::new("release_id")
+                .long("release-id")
+                .value_name("RELEASE_ID")
+                .help(
+                    "Identifier of the release given to the `post_release` hook and to the \
+                    sourcemap upload command (default: the commit or the version of the package)",
+                ),
+        )
+        .arg(
+            Arg::new("sourcemap_upload_command")
+                .long("sourcemap-upload-command")
+                .value_name("SOURCEMAP_UPLOAD_COMMAND")
+                .help(
+                    "Command run after a release build for every sourcemap and WASM, with \
+                    `{file}`, `{url}` and `{release}` replaced by the path and the URL of the \
+                    file and the identifier of the release",
+                ),
+        )
+        .arg(flag(
+            "upload_errors_fatal",
+            "upload-errors-fatal",
+            "Fail the build if the `post_release` hook or the sourcemap upload command fails",
+        ));
+        #[cfg(feature = "prerender")]
+        let cmd = cmd
+            .arg(
+                Arg::new("prerender")
+                    .long("prerender")
+                    .value_name("ROUTES")
+                    .value_delimiter(',')
+                    .action(ArgAction::Append)
+                    .help(
+                        "Routes prerendered with a headless Chrome after the release builds \
+                        (comma separated)",
+                    ),
+            )
+            .arg(
+                Arg::new("prerender_ready")
+                    .long("prerender-ready")
+                    .value_name("SELECTOR")
+                    .help(
+                        "CSS selector of an element that is present once a prerendered page is \
+                        ready (default: the page sets `window.__wasmbl_prerendered`)",
+                    ),
+            );
+        cmd
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+impl FromArgMatches for DefaultServeArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self {
+            log: matches.get_flag("log"),
+            ip: *matches
+                .get_one::<IpAddr>("ip")
+                .expect("has a default value; qed"),
+            port: *matches
+                .get_one::<u16>("port")
+                .expect("has a default value; qed"),
+            bind: matches
+                .get_many::<String>("bind")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default(),
+            no_live_reload: matches.get_flag("no_live_reload"),
+            control_api: matches.get_flag("control_api"),
+            serve_sources: matches.get_flag("serve_sources"),
+            dir_listing: matches.get_flag("dir_listing"),
+            index_document: matches.get_one::<String>("index_document").cloned(),
+            extensionless_html: matches.get_flag("extensionless_html"),
+            trailing_slash: matches
+                .get_one::<String>("trailing_slash")
+                .map(|x| x.parse().expect("the value has been validated; qed")),
+            release: matches.get_flag("release"),
+            poll: if matches.value_source("poll") == Some(ValueSource::CommandLine) {
+                Some(matches.get_one::<String>("poll").cloned())
+            } else {
+                None
+            },
+            clear: matches.get_flag("clear"),
+            verbose: matches.get_flag("verbose"),
+            notify: matches.get_flag("notify"),
+            check_first: matches.get_flag("check_first"),
+            no_gitignore: matches.get_flag("no_gitignore"),
+            debounce: matches.get_one::<String>("debounce").cloned(),
+            watch_paths: matches
+                .get_many::<PathBuf>("watch_paths")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default(),
+            ignore_globs: matches
+                .get_many::<String>("ignore_globs")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default(),
+            build_args: DefaultBuildArgs::from_arg_matches(matches)?,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Args for DefaultServeArgs {
+    fn augment_args(cmd: Command) -> Command {
+        let cmd = cmd
+            .arg(flag("log", "log", "Activate HTTP logs"))
+            // NOTE: `-h` is reserved for the help with clap
+            .arg(
+                Arg::new("ip")
+                    .long("ip")
+                    .value_name("IP")
+                    .value_parser(value_parser!(IpAddr))
+                    .default_value("127.0.0.1")
+                    .help("IP address to bind")
+                    .long_help(
+                        "IP address to bind.\n\nUse 0.0.0.0 (or :: for IPv6) to expose the server \
+                        to your network.",
+                    ),
+            )
+            .arg(
+                Arg::new("port")
+                    .long("port")
+                    .short('p')
+                    .value_name("PORT")
+                    .default_value("3000")
+                    .value_parser(value_parser!(u16))
+                    .help("Port number"),
+            )
+            .arg(
+                Arg::new("bind")
+                    .long("bind")
+                    .value_name("BIND")
+                    .action(ArgAction::Append)
+                    .value_parser(|x: &str| {
+                        crate::parse_bind_addr(x, 0)
+                            .map(|_| x.to_string())
+                            .map_err(|err| err.to_string())
+                    })
+                    .help(
+                        "Address to listen on instead of `--ip`: an IP address (with the port of \
+                        `--port`) or an IP address and a port (`[::1]:3000`). Can be repeated to \
+                        listen on several addresses",
+                    ),
+            )
+            .arg(flag(
+                "no_live_reload",
+                "no-live-reload",
+                "Do not reload the browser after the rebuilds",
+            ))
+            .arg(flag(
+                "control_api",
+                "control-api",
+                "Enable the control API of the development server: `POST /__wasmbl/reload`, \
+                `POST /__wasmbl/rebuild` and `GET /__wasmbl/status`, for the requests of this \
+                machine only",
+            ))
+            .arg(flag(
+                "serve_sources",
+                "serve-sources",
+                "Serve the Rust sources to the debuggers of the browsers at `/__wasmbl/sources/` \
+                (dev builds only)",
+            ))
+            .arg(flag(
+                "dir_listing",
+                "dir-listing",
+                "List the files of the directories that have no `index.html`",
+            ))
+            .arg(
+                Arg::new("index_document")
+                    .long("index-document")
+                    .value_name("INDEX_DOCUMENT")
+                    .value_parser(|x: &str| {
+                        crate::validate_index_document(x.to_string()).map(|_| x.to_string())
+                    })
+                    .help("Name of the index documents of the directories (default: `index.html`)"),
+            )
+            .arg(flag(
+                "extensionless_html",
+                "extensionless-html",
+                "Serve `<path>.html` for the paths without extension that are not files, like the \
+                static hosts with extensionless URLs (`/about` serves `about.html`)",
+            ))
+            .arg(
+                Arg::new("trailing_slash")
+                    .long("trailing-slash")
+                    .value_name("TRAILING_SLASH")
+                    .value_parser(["keep", "add", "remove"])
+                    .help(
+                        "Redirect the URLs of the pages to the ones that end with a `/` (`add`), \
+                        to the ones that don't (`remove`) or don't redirect (`keep`, the default)",
+                    ),
+            )
+            .arg(flag(
+                "release",
+                "release",
+                "Build in release mode, with optimizations (`--profiling` takes precedence)",
+            ))
+            .arg(
+                Arg::new("poll")
+                    .long("poll")
+                    .value_name("POLL")
+                    .num_args(0..=1)
+                    .value_parser(|x: &str| {
+                        crate::parse_duration(x)
+                            .map(|_| x.to_string())
+                            .map_err(|err| err.to_string())
+                    })
+                    .help(
+                        "Poll the file system for changes at the given interval (default: 1s) \
+                        instead of using the native events of the platform",
+                    ),
+            )
+            .arg(flag(
+                "clear",
+                "clear",
+                "Clear the terminal before every rebuild and print a status line at the end",
+            ))
+            .arg(flag(
+                "verbose",
+                "verbose",
+                "Show the full output of the rebuilds when `--clear` is used",
+            ))
+            .arg(flag(
+                "notify",
+                "notify",
+                "Send a desktop notification when a rebuild finishes (requires the feature \
+                `desktop-notifications`)",
+            ))
+            .arg(flag(
+                "check_first",
+                "check-first",
+                "Run `cargo check` before every rebuild and skip the full build if it fails",
+            ))
+            .arg(flag(
+                "no_gitignore",
+                "no-gitignore",
+                "Do not use the `.gitignore` and `.ignore` files to filter out the changes \
+                detected by the watcher",
+            ))
+            .arg(
+                Arg::new("debounce")
+                    .long("debounce")
+                    .value_name("DEBOUNCE")
+                    .value_parser(|x: &str| {
+                        crate::parse_duration(x)
+                            .map(|_| x.to_string())
+                            .map_err(|err| err.to_string())
+                    })
+                    .help(
+                        "Delay during which the native events of the watcher are debounced \
+                        (default: 2s)",
+                    ),
+            )
+            .arg(
+                Arg::new("watch_paths")
+                    .long("watch-path")
+                    .value_name("WATCH_PATH")
+                    .value_parser(value_parser!(PathBuf))
+                    .action(ArgAction::Append)
+                    .help("Additional path to watch (can be repeated)"),
+            )
+            .arg(
+                Arg::new("ignore_globs")
+                    .long("ignore")
+                    .value_name("IGNORE")
+                    .action(ArgAction::Append)
+                    .help(
+                        "Ignore the changes on the paths that match this pattern, with the syntax \
+                        of `.gitignore` relative to the workspace (can be repeated)",
+                    ),
+            );
+        DefaultBuildArgs::augment_args(cmd)
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+impl FromArgMatches for DefaultTestArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self {
+            chrome: matches.get_flag("chrome"),
+            firefox: matches.get_flag("firefox"),
+            headless: matches.get_flag("headless"),
+            release: matches.get_flag("release"),
+            watch: matches.get_flag("watch"),
+            filters: matches
+                .get_many::<String>("filters")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default(),
+            build_args: DefaultBuildArgs::from_arg_matches(matches)?,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Args for DefaultTestArgs {
+    fn augment_args(cmd: Command) -> Command {
+        let cmd = cmd
+            .arg(
+                flag(
+                    "chrome",
+                    "chrome",
+                    "Run the tests in Chrome (requires `chromedriver`)",
+                )
+                .conflicts_with("firefox"),
+            )
+            .arg(flag(
+                "firefox",
+                "firefox",
+                "Run the tests in Firefox (requires `geckodriver`)",
+            ))
+            .arg(flag(
+                "headless",
+                "headless",
+                "Run the browser without a window",
+            ))
+            .arg(flag(
+                "release",
+                "release",
+                "Build the tests in release mode",
+            ))
+            .arg(flag(
+                "watch",
+                "watch",
+                "Run the tests again when the files of the frontend package change",
+            ))
+            .arg(
+                Arg::new("filters")
+                    .value_name("FILTERS")
+                    .num_args(0..)
+                    .trailing_var_arg(true)
+                    .allow_hyphen_values(true)
+                    .help("Arguments given to the test harness (test name filters for example)"),
+            );
+        DefaultBuildArgs::augment_args(cmd)
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+impl FromArgMatches for DefaultCleanArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self {
+            all: matches.get_flag("all"),
+            dry_run: matches.get_flag("dry_run"),
+            build_args: DefaultBuildArgs::from_arg_matches(matches)?,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Args for DefaultCleanArgs {
+    fn augment_args(cmd: Command) -> Command {
+        let cmd = cmd
+            .arg(flag(
+                "all",
+                "all",
+                "Also remove the WASM artifacts in the target directory",
+            ))
+            .arg(flag(
+                "dry_run",
+                "dry-run",
+                "Print what would be removed without removing anything",
+            ));
+        DefaultBuildArgs::augment_args(cmd)
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+impl FromArgMatches for crate::DefaultRunArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self {
+            release: matches.get_flag("release"),
+            runtime: matches.get_one::<String>("runtime").cloned(),
+            build_args: DefaultBuildArgs::from_arg_matches(matches)?,
+            args: matches
+                .get_many::<OsString>("args")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Args for crate::DefaultRunArgs {
+    fn augment_args(cmd: Command) -> Command {
+        let cmd = cmd
+            .arg(flag(
+                "release",
+                "release",
+                "Build in release mode, with optimizations",
+            ))
+            .arg(
+                Arg::new("runtime")
+                    .long("runtime")
+                    .value_name("RUNTIME")
+                    .value_parser(["wasmtime", "wasmer"])
+                    .help("WASI runtime (default: the first one installed)"),
+            )
+            .arg(
+                Arg::new("args")
+                    .value_name("ARGS")
+                    .last(true)
+                    .num_args(0..)
+                    .value_parser(value_parser!(OsString))
+                    .help("Arguments given to the WASM (after `--`)"),
+            );
+        DefaultBuildArgs::augment_args(cmd)
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+impl FromArgMatches for crate::DefaultRunNodeArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self {
+            release: matches.get_flag("release"),
+            entry: matches.get_one::<PathBuf>("entry").cloned(),
+            build_args: DefaultBuildArgs::from_arg_matches(matches)?,
+            args: matches
+                .get_many::<OsString>("args")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Args for crate::DefaultRunNodeArgs {
+    fn augment_args(cmd: Command) -> Command {
+        let cmd = cmd
+            .arg(flag(
+                "release",
+                "release",
+                "Build in release mode, with optimizations",
+            ))
+            .arg(
+                Arg::new("entry")
+                    .long("entry")
+                    .value_name("ENTRY")
+                    .value_parser(value_parser!(PathBuf))
+                    .help(
+                        "Script run by `node` instead of calling the export `main` of the JS \
+                        bindings (their path is in the environment variable `WASMBL_JS_GLUE`)",
+                    ),
+            )
+            .arg(
+                Arg::new("args")
+                    .value_name("ARGS")
+                    .last(true)
+                    .num_args(0..)
+                    .value_parser(value_parser!(OsString))
+                    .help("Arguments given to the script (after `--`)"),
+            );
+        DefaultBuildArgs::augment_args(cmd)
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+impl FromArgMatches for crate::DefaultCompletionsArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self {
+            shell: matches
+                .get_one::<String>("shell")
+                .expect("the argument is required; qed")
+                .parse()
+                .expect("the value has been validated; qed"),
+            out: matches.get_one::<PathBuf>("out").cloned(),
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Args for crate::DefaultCompletionsArgs {
+    fn augment_args(cmd: Command) -> Command {
+        cmd.arg(
+            Arg::new("shell")
+                .value_name("SHELL")
+                .required(true)
+                .value_parser(["bash", "zsh", "fish", "powershell"])
+                .help("Shell of the completion script"),
+        )
+        .arg(
+            Arg::new("out")
+                .long("out")
+                .value_name("OUT")
+                .value_parser(value_parser!(PathBuf))
+                .help("Write the completion script to this file instead of the standard output"),
+        )
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+impl FromArgMatches for crate::DefaultVersionArgs {
+    fn from_arg_matches(_matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self {})
+    }
+
+    fn update_from_arg_matches(&mut self, _matches: &ArgMatches) -> Result<(), clap::Error> {
+        Ok(())
+    }
+}
+
+impl Args for crate::DefaultVersionArgs {
+    fn augment_args(cmd: Command) -> Command {
+        cmd
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        cmd
+    }
+}
+
+impl FromArgMatches for Verbosity {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self {
+            verbose: matches.get_count("wasmbl_verbose"),
+            quiet: matches.get_flag("wasmbl_quiet"),
+            log_format: matches
+                .get_one::<String>("wasmbl_log_format")
+                .map(|x| x.parse().expect("the value has been validated; qed"))
+                .unwrap_or_default(),
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Args for Verbosity {
+    fn augment_args(cmd: Command) -> Command {
+        cmd.arg(
+            Arg::new("wasmbl_verbose")
+                .short('v')
+                .action(ArgAction::Count)
+                .global(true)
+                .help(
+                    "Show more logs: `-v` for the debug logs (the commands that are run), `-vv` \
+                    for the trace logs (the file events)",
+                ),
+        )
+        .arg(
+            flag(
+                "wasmbl_quiet",
+                "quiet",
+                "Only show the warnings and the errors",
+            )
+            .short('q')
+            .conflicts_with("wasmbl_verbose")
+            .global(true),
+        )
+        .arg(
+            Arg::new("wasmbl_log_format")
+                .long("log-format")
+                .value_name("LOG_FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .global(true)
+                .help("Format of the logs: `text` or `json` (one JSON object per line)"),
+        )
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+#[cfg(feature = "deploy")]
+impl FromArgMatches for crate::DefaultDeployArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self {
+            destination: matches.get_one::<String>("destination").cloned(),
+            dry_run: matches.get_flag("dry_run"),
+            gh_pages: matches.get_flag("gh_pages"),
+            allow_dirty: matches.get_flag("allow_dirty"),
+            docker: matches.get_one::<String>("docker").cloned(),
+            push: matches.get_flag("push"),
+            base_image: matches.get_one::<String>("base_image").cloned(),
+            container_runtime: matches.get_one::<String>("container_runtime").cloned(),
+            build_args: DefaultBuildArgs::from_arg_matches(matches)?,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "deploy")]
+impl Args for crate::DefaultDeployArgs {
+    fn augment_args(cmd: Command) -> Command {
+        let cmd = cmd
+            .arg(Arg::new("destination").value_name("DESTINATION").help(
+                "Where to copy the build: a local directory or `user@host:/path`. \
+                        Defaults to the key `destination` of `[package.metadata.wasmbl.deploy]`",
+            ))
+            .arg(flag(
+                "dry_run",
+                "dry-run",
+                "Print the files that would be deployed without deploying them",
+            ))
+            .arg(flag(
+                "gh_pages",
+                "gh-pages",
+                "Publish the build to the branch `gh-pages` of the remote `origin` for GitHub \
+                Pages instead of copying it to the destination",
+            ))
+            .arg(
+                flag(
+                    "allow_dirty",
+                    "allow-dirty",
+                    "Publish to GitHub Pages even if the working tree has uncommitted changes",
+                )
+                .requires("gh_pages"),
+            )
+            .arg(
+                Arg::new("docker")
+                    .long("docker")
+                    .value_name("IMAGE")
+                    .help(
+                        "Build a container image that serves the build with nginx instead of \
+                        copying it to the destination: `registry/app` or `registry/app:tag`. \
+                        The default tag is the short hash of the commit `HEAD`",
+                    )
+                    .conflicts_with("gh_pages"),
+            )
+            .arg(flag("push", "push", "Push the image built with `--docker`").requires("docker"))
+            .arg(
+                Arg::new("base_image")
+                    .long("base-image")
+                    .value_name("IMAGE")
+                    .help(
+                        "Base image of `--docker`, it must serve the files with nginx. Defaults \
+                        to the key `base-image` of `[package.metadata.wasmbl.deploy]`, or \
+                        `nginx:alpine`",
+                    )
+                    .requires("docker"),
+            )
+            .arg(
+                Arg::new("container_runtime")
+                    .long("container-runtime")
+                    .value_name("PROGRAM")
+                    .help(
+                        "Program that builds the image of `--docker`. Defaults to `docker`, or \
+                        `podman` if `docker` is not found",
+                    )
+                    .requires("docker"),
+            );
+        DefaultBuildArgs::augment_args(cmd)
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+#[cfg(feature = "scaffold")]
+impl FromArgMatches for crate::DefaultNewArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self {
+            name: matches
+                .get_one::<String>("name")
+                .cloned()
+                .expect("the argument is required; qed"),
+            template: matches
+                .get_one::<String>("template")
+                .expect("has a default value; qed")
+                .parse()
+                .expect("the value has been validated; qed"),
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "scaffold")]
+impl Args for crate::DefaultNewArgs {
+    fn augment_args(cmd: Command) -> Command {
+        cmd.arg(Arg::new("name").value_name("NAME").required(true).help(
+            "Name of the package. It is created in a directory of the same name at the \
+                    root of the workspace",
+        ))
+        .arg(
+            Arg::new("template")
+                .long("template")
+                .value_name("TEMPLATE")
+                .default_value("minimal")
+                .value_parser(["minimal", "yew"])
+                .help("Template of the package"),
+        )
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WatchArgs;
+    use clap::Parser;
+    use std::time::Duration;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        args: DefaultServeArgs,
+    }
+
+    #[test]
+    fn parse_serve_args() {
+        let args = Cli::try_parse_from(["app", "--port", "8080", "--poll", "--clear"])
+            .unwrap()
+            .args;
+        assert_eq!(args.port, 8080);
+        assert_eq!(args.ip, std::net::Ipv4Addr::LOCALHOST);
+        assert_eq!(args.poll, Some(None));
+        assert!(args.clear);
+        assert!(!args.check_first);
+        assert!(!args.build_args.profiling);
+
+        let args =
+            Cli::try_parse_from(["app", "--ip", "::", "--bind", "::1", "--bind", "[::1]:9000"])
+                .unwrap()
+                .args;
+        assert_eq!(args.ip, std::net::Ipv6Addr::UNSPECIFIED);
+        assert_eq!(args.bind, ["::1", "[::1]:9000"]);
+        assert!(Cli::try_parse_from(["app", "--bind", "localhost"]).is_err());
+
+        let args = Cli::try_parse_from(["app", "--poll", "500ms", "--profiling"])
+            .unwrap()
+            .args;
+        assert_eq!(args.poll, Some(Some("500ms".to_string())));
+        assert!(args.build_args.profiling);
+
+        let args = Cli::try_parse_from([
+            "app",
+            "--debounce",
+            "100ms",
+            "--watch-path",
+            "shared",
+            "--ignore",
+            "*.gen.rs",
+            "--ignore",
+            "fixtures/",
+        ])
+        .unwrap()
+        .args;
+        assert_eq!(args.debounce(), Duration::from_millis(100));
+        assert_eq!(args.watch_paths(), [PathBuf::from("shared")]);
+        assert_eq!(args.ignore_globs(), ["*.gen.rs", "fixtures/"]);
+
+        let args = Cli::try_parse_from(["app", "--manifest-path", "frontend/Cargo.toml"])
+            .unwrap()
+            .args;
+        assert_eq!(
+            args.build_args.manifest_path,
+            Some(PathBuf::from("frontend/Cargo.toml"))
+        );
+
+        let args = Cli::try_parse_from([
+            "app",
+            "--index-document",
+            "index.htm",
+            "--extensionless-html",
+            "--trailing-slash",
+            "add",
+        ])
+        .unwrap()
+        .args;
+        assert_eq!(args.index_document.as_deref(), Some("index.htm"));
+        assert!(args.extensionless_html);
+        assert_eq!(args.trailing_slash, Some(crate::TrailingSlash::Add));
+        assert!(Cli::try_parse_from(["app", "--index-document", "docs/index.html"]).is_err());
+        assert!(Cli::try_parse_from(["app", "--trailing-slash", "always"]).is_err());
+
+        let args = Cli::try_parse_from(["app", "--panic-strategy", "unwind-verbose"])
+            .unwrap()
+            .args;
+        assert_eq!(
+            args.build_args.panic_strategy,
+            Some(crate::PanicStrategy::UnwindVerbose)
+        );
+
+        for flag in ["--build-path", "--out-dir", "-d"] {
+            let args = Cli::try_parse_from(["app", flag, "dist"]).unwrap().args;
+            assert_eq!(args.build_args.build_path, Some(PathBuf::from("dist")));
+        }
+        assert!(
+            Cli::try_parse_from(["app", "--build-path", "dist", "--out-dir", "public"]).is_err()
+        );
+
+        let args = Cli::try_parse_from([
+            "app",
+            "--features",
+            "a,b",
+            "--features",
+            "c",
+            "--no-default-features",
+            "--wasm-opt-level",
+            "3",
+            "--wasm-opt-passes",
+            "dce,strip-producers",
+            "--wasm-opt-retries",
+            "2",
+            "--no-wasm-opt",
+        ])
+        .unwrap()
+        .args;
+        assert_eq!(args.build_args.features, ["a", "b", "c"]);
+        assert!(args.build_args.no_default_features);
+        assert_eq!(args.build_args.wasm_opt_level, Some(3));
+        assert_eq!(args.build_args.wasm_opt_passes, ["dce", "strip-producers"]);
+        assert_eq!(args.build_args.wasm_opt_retries, 2);
+        assert!(args.build_args.no_wasm_opt);
+
+        let args = Cli::try_parse_from([
+            "app",
+            "--bundle-command",
+            "esbuild src/main.ts --bundle --outfile=build/bundle.js",
+            "--bundle-output",
+            "bundle.js",
+            "--bundle-output",
+            "bundle.css",
+        ])
+        .unwrap()
+        .args;
+        assert_eq!(
+            args.build_args.bundle_command.as_deref(),
+            Some("esbuild src/main.ts --bundle --outfile=build/bundle.js")
+        );
+        assert_eq!(
+            args.build_args.bundle_outputs,
+            [PathBuf::from("bundle.js"), PathBuf::from("bundle.css")]
+        );
+
+        let args = Cli::try_parse_from(["app", "--print-config"]).unwrap().args;
+        assert_eq!(args.build_args.print_config, Some(None));
+        let args = Cli::try_parse_from(["app", "--print-config", "json"])
+            .unwrap()
+            .args;
+        assert_eq!(
+            args.build_args.print_config,
+            Some(Some(crate::ConfigFormat::Json))
+        );
+        let args = Cli::try_parse_from([
+            "app",
+            "--explain",
+            "--emit-build-plan",
+            "plan.json",
+            "--trunk-index",
+            "index.html",
+            "--frontend-dir",
+            "web",
+            "--release-id",
+            "v1.2.0",
+            "--sourcemap-upload-command",
+            "tracker upload {file}",
+            "--upload-errors-fatal",
+            "--reproducible",
+        ])
+        .unwrap()
+        .args;
+        assert_eq!(
+            args.build_args.trunk_index,
+            Some(PathBuf::from("index.html"))
+        );
+        assert_eq!(args.build_args.frontend_dir, Some(PathBuf::from("web")));
+        assert_eq!(args.build_args.release_id.as_deref(), Some("v1.2.0"));
+        assert_eq!(
+            args.build_args.sourcemap_upload_command.as_deref(),
+            Some("tracker upload {file}")
+        );
+        assert!(args.build_args.upload_errors_fatal);
+        assert!(args.build_args.reproducible);
+        assert!(args.build_args.explain);
+        assert_eq!(
+            args.build_args.emit_build_plan,
+            Some(PathBuf::from("plan.json"))
+        );
+
+        let args = Cli::try_parse_from(["app"]).unwrap().args;
+        assert_eq!(args.poll, None);
+        assert!(args.build_args.features.is_empty());
+        assert_eq!(args.build_args.wasm_opt_level, None);
+        assert_eq!(args.build_args.wasm_opt_retries, 0);
+        assert_eq!(args.build_args.print_config, None);
+        assert!(!args.build_args.explain);
+
+        assert!(Cli::try_parse_from(["app", "--poll", "soon"]).is_err());
+        assert!(Cli::try_parse_from(["app", "--print-config", "yaml"]).is_err());
+        assert!(Cli::try_parse_from(["app", "--wasm-opt-level", "5"]).is_err());
+    }
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(flatten)]
+        args: DefaultTestArgs,
+    }
+
+    #[test]
+    fn parse_test_args() {
+        let args =
+            TestCli::try_parse_from(["app", "--chrome", "--headless", "my_test", "--nocapture"])
+                .unwrap()
+                .args;
+        assert!(args.chrome);
+        assert!(args.headless);
+        assert!(!args.release);
+        assert_eq!(args.filters, ["my_test", "--nocapture"]);
+
+        assert!(TestCli::try_parse_from(["app", "--chrome", "--firefox"]).is_err());
+    }
+}
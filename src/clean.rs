@@ -0,0 +1,162 @@
+//! The `clean` command: remove the outputs of the builds.
+
+use crate::{BuildArgs, BuildTarget, DefaultBuildArgs};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+/// Clean arguments.
+#[derive(StructOpt, Debug)]
+pub struct DefaultCleanArgs {
+    /// Also remove the WASM artifacts in the target directory.
+    #[structopt(long)]
+    pub all: bool,
+
+    /// Print what would be removed without removing anything.
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Build arguments.
+    #[structopt(flatten)]
+    pub build_args: DefaultBuildArgs,
+}
+
+impl DefaultCleanArgs {
+    /// Run the `clean` command.
+    ///
+    /// The build directory is only removed if it is inside the workspace of the frontend package
+    /// or if it has been given explicitly (for example with `--build-path`).
+    pub fn run(self) -> Result<()> {
+        crate::Project::select_frontend(&self.build_args)?;
+
+        let build_args = &self.build_args;
+        let build_path = build_args.build_path();
+        let explicit = &build_path != build_args.default_build_path();
+        let mut paths = vec![checked_build_path(
+            &build_path,
+            &build_args.frontend_metadata().workspace_root,
+            explicit,
+        )?];
+        paths.extend(caches(build_args.target_path())?);
+        if self.all {
+            for target in [BuildTarget::Web, BuildTarget::Wasi] {
+                paths.push(build_args.target_path().join(target.triple()));
+            }
+        }
+
+        for path in paths.iter().filter(|x| x.exists()) {
+            if self.dry_run {
+                log::info!("Would remove `{}`", path.display());
+                continue;
+            }
+
+            log::info!("Removing `{}`", path.display());
+            let res = if path.is_dir() {
+                fs::remove_dir_all(path)
+            } else {
+                fs::remove_file(path)
+            };
+            res.with_context(|| format!("could not remove `{}`", path.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check that removing the build directory can't remove something unexpected.
+fn checked_build_path(build_path: &Path, workspace_root: &Path, explicit: bool) -> Result<PathBuf> {
+    let canonicalize = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let build_path = canonicalize(build_path);
+    let workspace_root = canonicalize(workspace_root);
+
+    if workspace_root.starts_with(&build_path) {
+        bail!(
+            "refusing to remove `{}`: it contains the workspace",
+            build_path.display()
+        );
+    }
+    if !explicit && !build_path.starts_with(&workspace_root) {
+        bail!(
+            "refusing to remove `{}`: it is outside of the workspace, use `--build-path` to \
+            remove it anyway",
+            build_path.display()
+        );
+    }
+
+    Ok(build_path)
+}
+
+/// The directories where the prebuilt wasm-opt is downloaded and the caches of the static files.
+fn caches(target_path: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(target_path) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| {
+            format!(
+                "could not read the target directory `{}`",
+                target_path.display()
+            )
+        })?;
+        let name = entry.file_name();
+        let name = name.as_encoded_bytes();
+        if name.starts_with(b"wasm-opt-")
+            || name.starts_with(b".wasm-opt-")
+            || name.starts_with(crate::static_cache::PREFIX.as_bytes())
+        {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuse_unexpected_build_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace_root = dir.path().join("workspace");
+        fs::create_dir_all(&workspace_root).unwrap();
+        let workspace_root = workspace_root.canonicalize().unwrap();
+
+        let build_path = workspace_root.join("build");
+        assert_eq!(
+            checked_build_path(&build_path, &workspace_root, false).unwrap(),
+            build_path,
+        );
+
+        assert!(checked_build_path(&workspace_root, &workspace_root, true).is_err());
+        assert!(checked_build_path(dir.path(), &workspace_root, true).is_err());
+
+        let outside = dir.path().join("public");
+        assert!(checked_build_path(&outside, &workspace_root, false).is_err());
+        assert!(checked_build_path(&outside, &workspace_root, true).is_ok());
+    }
+
+    #[test]
+    fn find_caches() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("wasm-opt-0123")).unwrap();
+        fs::create_dir(dir.path().join(".wasm-opt-4567")).unwrap();
+        fs::create_dir(dir.path().join("debug")).unwrap();
+        fs::write(dir.path().join(".wasmbl-static-89ab.json"), "{}").unwrap();
+
+        assert_eq!(
+            caches(dir.path()).unwrap(),
+            vec![
+                dir.path().join(".wasm-opt-4567"),
+                dir.path().join(".wasmbl-static-89ab.json"),
+                dir.path().join("wasm-opt-0123"),
+            ],
+        );
+        assert!(caches(&dir.path().join("missing")).unwrap().is_empty());
+    }
+}
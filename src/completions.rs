@@ -0,0 +1,134 @@
+//! The `completions` command: print the shell completion script of the command-line.
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// A shell supported by the `completions` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// Bash.
+    Bash,
+    /// Zsh.
+    Zsh,
+    /// Fish.
+    Fish,
+    /// PowerShell.
+    PowerShell,
+}
+
+impl FromStr for Shell {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            "powershell" => Ok(Self::PowerShell),
+            _ => bail!(
+                "unknown shell `{}`, expected `bash`, `zsh`, `fish` or `powershell`",
+                s
+            ),
+        }
+    }
+}
+
+/// Completions arguments.
+#[derive(StructOpt, Debug)]
+pub struct DefaultCompletionsArgs {
+    /// Shell of the completion script: `bash`, `zsh`, `fish` or `powershell`.
+    #[structopt(possible_values = &["bash", "zsh", "fish", "powershell"])]
+    pub shell: Shell,
+
+    /// Write the completion script to this file instead of the standard output.
+    #[structopt(long)]
+    pub out: Option<PathBuf>,
+}
+
+impl DefaultCompletionsArgs {
+    /// Run the `completions` command for a command-line parsed with `structopt`.
+    ///
+    /// The macro [`crate::main`] gives the command-line it generated, including the commands added
+    /// by the user.
+    pub fn run_structopt(self, mut app: structopt::clap::App) -> Result<()> {
+        use structopt::clap::Shell as ClapShell;
+
+        let shell = match self.shell {
+            Shell::Bash => ClapShell::Bash,
+            Shell::Zsh => ClapShell::Zsh,
+            Shell::Fish => ClapShell::Fish,
+            Shell::PowerShell => ClapShell::PowerShell,
+        };
+        let bin_name = app.get_name().to_string();
+        self.write(|mut out| app.gen_completions_to(bin_name, shell, &mut out))
+    }
+
+    /// Run the `completions` command for a command-line parsed with `clap`.
+    #[cfg(feature = "clap")]
+    pub fn run_clap(self, mut command: clap::Command) -> Result<()> {
+        use clap_complete::Shell as ClapShell;
+
+        let shell = match self.shell {
+            Shell::Bash => ClapShell::Bash,
+            Shell::Zsh => ClapShell::Zsh,
+            Shell::Fish => ClapShell::Fish,
+            Shell::PowerShell => ClapShell::PowerShell,
+        };
+        let bin_name = command.get_name().to_string();
+        self.write(|out| clap_complete::generate(shell, &mut command, bin_name, out))
+    }
+
+    fn write(&self, generate: impl FnOnce(&mut dyn Write)) -> Result<()> {
+        match self.out.as_ref() {
+            Some(path) => {
+                let mut file = fs::File::create(path)
+                    .with_context(|| format!("could not create `{}`", path.display()))?;
+                generate(&mut file);
+                log::info!("Completion script written to `{}`", path.display());
+            }
+            None => generate(&mut std::io::stdout()),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(StructOpt)]
+    enum Cli {
+        Completions(DefaultCompletionsArgs),
+        Hello,
+    }
+
+    #[test]
+    fn write_completion_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.bash");
+        let args = match Cli::from_iter_safe(
+            ["app", "completions", "bash", "--out"]
+                .iter()
+                .copied()
+                .chain(path.to_str()),
+        )
+        .unwrap()
+        {
+            Cli::Completions(args) => args,
+            Cli::Hello => unreachable!(),
+        };
+        assert_eq!(args.shell, Shell::Bash);
+
+        args.run_structopt(Cli::clap()).unwrap();
+        let script = fs::read_to_string(&path).unwrap();
+        assert!(script.contains("hello"));
+        assert!(script.contains("completions"));
+
+        assert!(Cli::from_iter_safe(["app", "completions", "tcsh"]).is_err());
+    }
+}
@@ -0,0 +1,523 @@
+//! The effective configuration of the `build` and `serve` commands (`--print-config`): the settings
+//! resolved from the command-line, the macro, the `[package.metadata.wasmbl]` table of the
+//! frontend package and the hooks, with where each of them comes from. Nothing is built.
+//!
+//! The sources are only known for the default arguments: the settings of the custom arguments come
+//! from their implementation of [`BuildArgs`] or [`ServeArgs`].
+
+use crate::{
+    Asset, BuildArgs, BuildProfile, BuildTarget, DefaultBuildArgs, Hooks, OutLayout, ServeArgs,
+    DEFAULT_BUILD_PATH,
+};
+use anyhow::{bail, Result};
+use cargo_metadata::Package;
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// The format of the configuration printed by `--print-config`.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ConfigFormat {
+    /// A table aligned for the terminal.
+    #[default]
+    Text,
+    /// A JSON array of the settings with their values and their sources.
+    Json,
+}
+
+impl std::str::FromStr for ConfigFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("unknown format `{}`, expected `text` or `json`", s),
+        }
+    }
+}
+
+/// Where a setting comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Source {
+    /// An option of the command-line.
+    Flag(&'static str),
+    /// A key of the `[package.metadata.wasmbl]` table of the frontend package.
+    Metadata(&'static str),
+    /// A function given to the macro (or to the [`crate::Runner`]).
+    Macro(&'static str),
+    /// What a hook does.
+    Hook(&'static str),
+    /// The implementation of the custom arguments.
+    Arguments,
+    Default,
+}
+
+impl Source {
+    fn kind(self) -> &'static str {
+        match self {
+            Self::Flag(_) => "flag",
+            Self::Metadata(_) => "metadata",
+            Self::Macro(_) => "macro",
+            Self::Hook(_) => "hook",
+            Self::Arguments => "arguments",
+            Self::Default => "default",
+        }
+    }
+
+    fn key(self) -> Option<&'static str> {
+        match self {
+            Self::Flag(key) | Self::Metadata(key) | Self::Macro(key) | Self::Hook(key) => Some(key),
+            Self::Arguments | Self::Default => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.key() {
+            Some(key) => write!(f, "{} `{}`", self.kind(), key),
+            None => f.write_str(self.kind()),
+        }
+    }
+}
+
+/// A setting with its effective value.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Setting {
+    pub name: &'static str,
+    pub value: Value,
+    pub source: Source,
+}
+
+/// Resolves the sources of the settings of the default arguments.
+struct Resolver<'a> {
+    defaults: Option<&'a DefaultBuildArgs>,
+    package: &'a Package,
+    settings: Vec<Setting>,
+}
+
+impl<'a> Resolver<'a> {
+    /// The source of a setting given by the option `flag` of the default arguments, or by the key
+    /// `key` of the metadata.
+    fn source(
+        &self,
+        flag: &'static str,
+        given: impl FnOnce(&DefaultBuildArgs) -> bool,
+        key: Option<&'static str>,
+    ) -> Source {
+        match self.defaults {
+            Some(args) if given(args) => Source::Flag(flag),
+            Some(_) => match key.filter(|x| has_metadata(self.package, x)) {
+                Some(key) => Source::Metadata(key),
+                None => Source::Default,
+            },
+            None => Source::Arguments,
+        }
+    }
+
+    fn push(&mut self, name: &'static str, value: impl Into<Value>, source: Source) {
+        self.settings.push(Setting {
+            name,
+            value: value.into(),
+            source,
+        });
+    }
+}
+
+/// The effective settings of a command: the settings of the development server are only resolved
+/// for `serve`.
+pub(crate) fn resolve(
+    args: &dyn BuildArgs,
+    serve: Option<&dyn ServeArgs>,
+    hooks: &Hooks,
+) -> Vec<Setting> {
+    let package = args.frontend_package();
+    let mut resolver = Resolver {
+        defaults: args.downcast_ref::<DefaultBuildArgs>(),
+        package,
+        settings: Vec::new(),
+    };
+
+    let source = resolver.source("--package", |x| x.package.is_some(), None);
+    let source = match source {
+        Source::Default => resolver.source("--manifest-path", |x| x.manifest_path.is_some(), None),
+        source => source,
+    };
+    resolver.push("package", package.name.as_str(), source);
+    let source = match resolver.source("--build-path", |x| x.build_path.is_some(), None) {
+        Source::Default if matches!(DEFAULT_BUILD_PATH.get(), Some(Some(_))) => {
+            Source::Macro("default_build_path")
+        }
+        source => source,
+    };
+    resolver.push("build-path", path(&args.build_path()), source);
+    let source = resolver.source("--target-dir", |x| x.target_dir.is_some(), None);
+    resolver.push("target-dir", path(args.target_path()), source);
+
+    let profile = match serve {
+        Some(serve) => {
+            let source = match serve.downcast_ref::<crate::DefaultServeArgs>() {
+                Some(_) if args.profiling() => Source::Flag("--profiling"),
+                Some(x) if x.release => Source::Flag("--release"),
+                Some(_) => Source::Default,
+                None => Source::Arguments,
+            };
+            resolver.push("profile", serve.profile().name(), source);
+            serve.profile()
+        }
+        None => {
+            let source = resolver.source("--profiling", |x| x.profiling, None);
+            let profile = crate::resolve_profile(BuildProfile::Release, args);
+            resolver.push("profile", profile.name(), source);
+            profile
+        }
+    };
+
+    let source = resolver.source("--features", |x| !x.features.is_empty(), None);
+    resolver.push("features", args.features().to_vec(), source);
+    let source = resolver.source("--no-default-features", |x| x.no_default_features, None);
+    resolver.push("default-features", args.default_features(), source);
+    let source = resolver.source("--all-features", |x| x.all_features, None);
+    resolver.push("all-features", args.all_features(), source);
+
+    let source = resolver.source("--target", |x| x.target.is_some(), None);
+    resolver.push("target", args.target().triple(), source);
+    let source = resolver.source("--out-layout", |x| x.out_layout.is_some(), None);
+    let layout = args.out_layout();
+    resolver.push("out-layout", layout_name(layout), source);
+    // NOTE: the bindings are not generated for WASI
+    let bindgen_target = match args.target() {
+        BuildTarget::Web => Some(bindgen_target(layout)),
+        BuildTarget::Wasi => None,
+    };
+    resolver.push("bindgen-target", bindgen_target, source);
+    let source = resolver.source("--out-name", |x| x.out_name.is_some(), None);
+    resolver.push("out-name", args.out_name(), source);
+
+    let source = match profile {
+        BuildProfile::Dev if resolver.defaults.is_some() => Source::Default,
+        _ => resolver.source("--no-wasm-opt", |x| x.no_wasm_opt, None),
+    };
+    resolver.push(
+        "wasm-opt",
+        args.wasm_opt() && profile != BuildProfile::Dev,
+        source,
+    );
+    let options = args.wasm_opt_options(profile);
+    let source = resolver.source("--wasm-opt-level", |x| x.wasm_opt_level.is_some(), None);
+    resolver.push("wasm-opt-level", options.level, source);
+    let source = resolver.source(
+        "--wasm-opt-shrink-level",
+        |x| x.wasm_opt_shrink_level.is_some(),
+        None,
+    );
+    resolver.push("wasm-opt-shrink-level", options.shrink_level, source);
+    let source = resolver.source("--wasm-opt-converge", |x| x.wasm_opt_converge, None);
+    resolver.push("wasm-opt-converge", options.converge, source);
+    let source = resolver.source("--wasm-opt-passes", |x| !x.wasm_opt_passes.is_empty(), None);
+    resolver.push("wasm-opt-passes", options.extra_passes, source);
+    let source = resolver.source("--wasm-opt-retries", |x| x.wasm_opt_retries != 0, None);
+    resolver.push("wasm-opt-retries", options.retries, source);
+
+    let source = resolver.source(
+        "--panic-strategy",
+        |x| x.panic_strategy.is_some(),
+        Some("panic-strategy"),
+    );
+    let strategy = args.panic_strategy(profile).map(|x| x.name());
+    resolver.push("panic-strategy", strategy, source);
+    let source = resolver.source(
+        "--reference-types",
+        |x| x.reference_types,
+        Some("reference-types"),
+    );
+    resolver.push("reference-types", args.reference_types(), source);
+    let source = resolver.source("--typescript", |x| x.typescript, None);
+    resolver.push("typescript", args.typescript(), source);
+
+    let source = resolver.source(
+        "--frontend-dir",
+        |x| x.frontend_dir.is_some(),
+        Some("frontend-dir"),
+    );
+    let frontend_dir = args.frontend_dir().map(|x| path(&x));
+    resolver.push("frontend-dir", frontend_dir, source);
+    let source = resolver.source("--static-dir", |x| x.static_dir.is_some(), None);
+    resolver.push("static-dir", path(&args.static_dir()), source);
+    let source = match resolver.defaults {
+        Some(_) if has_metadata(package, "pages") => Source::Metadata("pages"),
+        Some(_) => Source::Default,
+        None => Source::Arguments,
+    };
+    let templates: Vec<_> = args.pages().iter().map(|x| path(&x.template)).collect();
+    resolver.push("pages", templates, source);
+    #[cfg(feature = "sass")]
+    {
+        let styles: Vec<_> = args
+            .sass_lookup_directories(profile)
+            .iter()
+            .map(|x| path(x))
+            .collect();
+        let source = match resolver.defaults {
+            Some(_) => Source::Default,
+            None => Source::Arguments,
+        };
+        resolver.push("style-dirs", styles, source);
+    }
+    let source = resolver.source(
+        "--trunk-index",
+        |x| x.trunk_index.is_some(),
+        Some("trunk-index"),
+    );
+    let trunk_index = args.trunk_index().map(|x| path(&x));
+    resolver.push("trunk-index", trunk_index, source);
+    let source = resolver.source("--base-url", |x| x.base_url.is_some(), Some("base-url"));
+    resolver.push("base-url", args.base_url(), source);
+    let source = resolver.source(
+        "--bundle-command",
+        |x| x.bundle_command.is_some(),
+        Some("bundle-command"),
+    );
+    resolver.push("bundle-command", args.bundle_command(), source);
+    let source = resolver.source("--reproducible", |x| x.reproducible, Some("reproducible"));
+    resolver.push("reproducible", args.reproducible(), source);
+    let source = resolver.source("--release-id", |x| x.release_id.is_some(), None);
+    resolver.push("release-id", args.release_id(), source);
+    let source = resolver.source(
+        "--sourcemap-upload-command",
+        |x| x.sourcemap_upload_command.is_some(),
+        Some("sourcemap-upload-command"),
+    );
+    resolver.push(
+        "sourcemap-upload-command",
+        args.sourcemap_upload_command(),
+        source,
+    );
+    let source = resolver.source(
+        "--upload-errors-fatal",
+        |x| x.upload_errors_fatal,
+        Some("upload-errors-fatal"),
+    );
+    resolver.push("upload-errors-fatal", args.upload_errors_fatal(), source);
+    let source = resolver.source("--env-file", |x| !x.env_files.is_empty(), None);
+    let env_files: Vec<_> = args.env_files().iter().map(|x| path(x)).collect();
+    resolver.push("env-files", env_files, source);
+    resolver.push(
+        "hashed-assets",
+        hashed_assets(args, hooks),
+        Source::Hook("asset_filter"),
+    );
+
+    #[cfg(feature = "dev-server")]
+    if let Some(serve) = serve {
+        resolve_serve(&mut resolver, serve);
+    }
+
+    resolver.settings
+}
+
+/// The settings of the development server.
+#[cfg(feature = "dev-server")]
+fn resolve_serve(resolver: &mut Resolver, args: &dyn ServeArgs) {
+    let defaults = args.downcast_ref::<crate::DefaultServeArgs>();
+    let source =
+        |flag: &'static str, given: &dyn Fn(&crate::DefaultServeArgs) -> bool| match defaults {
+            Some(args) if given(args) => Source::Flag(flag),
+            Some(_) => Source::Default,
+            None => Source::Arguments,
+        };
+
+    // NOTE: the serve path is the build path without the function of the macro
+    let serve_source = match crate::Project::get().default_serve_path {
+        Some(_) => Source::Macro("default_serve_path"),
+        None => resolver
+            .settings
+            .iter()
+            .find(|x| x.name == "build-path")
+            .map(|x| x.source)
+            .unwrap_or(Source::Default),
+    };
+    resolver.push("serve-path", path(&args.serve_path()), serve_source);
+    // NOTE: the defaults of structopt can't be told apart from the same values on the
+    //       command-line, they are the same anyway
+    let source_ip = source("--ip", &|x| x.ip != std::net::Ipv4Addr::LOCALHOST);
+    resolver.push("ip", args.ip().to_string(), source_ip);
+    let source_port = source("--port", &|x| x.port != 3000);
+    resolver.push("port", args.port(), source_port);
+    let bind: Vec<_> = args.bind().iter().map(ToString::to_string).collect();
+    resolver.push("bind", bind, source("--bind", &|x| !x.bind.is_empty()));
+    let source_live_reload = source("--no-live-reload", &|x| x.no_live_reload);
+    resolver.push("live-reload", args.live_reload(), source_live_reload);
+    let source_index = source("--index-document", &|x| x.index_document.is_some());
+    resolver.push("index-document", args.index_document(), source_index);
+}
+
+/// Print the settings on the standard output.
+pub(crate) fn print(settings: &[Setting], format: ConfigFormat) {
+    match format {
+        ConfigFormat::Text => print!("{}", to_text(settings)),
+        ConfigFormat::Json => println!("{:#}", to_json(settings)),
+    }
+}
+
+/// A table of the settings, their values and their sources:
+///
+/// ```text
+/// package     app         default
+/// build-path  /app/build  flag `--build-path`
+/// ```
+fn to_text(settings: &[Setting]) -> String {
+    let values: Vec<_> = settings.iter().map(|x| text_value(&x.value)).collect();
+    let name_width = settings.iter().map(|x| x.name.len()).max().unwrap_or(0);
+    let value_width = values.iter().map(|x| x.chars().count()).max().unwrap_or(0);
+
+    let mut text = String::new();
+    for (setting, value) in settings.iter().zip(values.iter()) {
+        text.push_str(&format!(
+            "{:name_width$}  {:value_width$}  {}\n",
+            setting.name,
+            value,
+            setting.source,
+            name_width = name_width,
+            value_width = value_width,
+        ));
+    }
+    text
+}
+
+fn text_value(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(value) => value.clone(),
+        Value::Array(values) if values.is_empty() => "-".to_string(),
+        Value::Array(values) => values.iter().map(text_value).collect::<Vec<_>>().join(", "),
+        value => value.to_string(),
+    }
+}
+
+/// The settings in order: `[{"name": "build-path", "value": "/app/build", "source": "flag",
+/// "key": "--build-path"}, ...]`.
+pub(crate) fn to_json(settings: &[Setting]) -> Value {
+    settings
+        .iter()
+        .map(|x| {
+            json!({
+                "name": x.name,
+                "value": x.value,
+                "source": x.source.kind(),
+                "key": x.source.key(),
+            })
+        })
+        .collect()
+}
+
+fn has_metadata(package: &Package, key: &str) -> bool {
+    package
+        .metadata
+        .get("wasmbl")
+        .and_then(|x| x.get(key))
+        .is_some()
+}
+
+fn path(path: &Path) -> String {
+    path.display().to_string()
+}
+
+fn layout_name(layout: OutLayout) -> &'static str {
+    match layout {
+        OutLayout::Flat => "flat",
+        OutLayout::Pkg => "pkg",
+        OutLayout::Node => "node",
+    }
+}
+
+/// The target of `wasm-bindgen` of a layout.
+fn bindgen_target(layout: OutLayout) -> &'static str {
+    match layout {
+        OutLayout::Flat => "web",
+        OutLayout::Pkg => "bundler",
+        OutLayout::Node => "nodejs",
+    }
+}
+
+/// The `asset_filter` hook renames the WASM, it probably adds a hash to the names of the assets.
+/// The hook is given a WASM without code, nothing is written.
+fn hashed_assets(args: &dyn BuildArgs, hooks: &Hooks) -> bool {
+    let name = format!("{}_bg.wasm", args.out_name());
+    let asset = Asset::new(&name, name.clone(), b"\0asm\x01\0\0\0".to_vec());
+    matches!((hooks.asset_filter)(asset), Ok(Some(x)) if x.path != Path::new(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    #[test]
+    fn sources_of_the_settings() {
+        let (_, package) = crate::runner::tests::init_project();
+        let args = DefaultBuildArgs::from_iter_safe([
+            "build",
+            "--wasm-opt-level",
+            "3",
+            "--build-path",
+            "public",
+            "--print-config",
+            "json",
+        ])
+        .unwrap();
+        assert_eq!(args.print_config(), Some(ConfigFormat::Json));
+        let hooks = Hooks {
+            asset_filter: Box::new(|mut asset| {
+                asset.path = asset.path.with_extension("1234.wasm");
+                Ok(Some(asset))
+            }),
+            ..Hooks::default()
+        };
+        let settings = resolve(&args, None, &hooks);
+        let setting = |name: &str| settings.iter().find(|x| x.name == name).unwrap().clone();
+
+        assert_eq!(setting("package").value, json!(package.name));
+        assert_eq!(setting("package").source, Source::Default);
+        assert_eq!(setting("build-path").source, Source::Flag("--build-path"));
+        assert_eq!(setting("profile").value, json!("release"));
+        assert_eq!(setting("wasm-opt-level").value, json!(3));
+        assert_eq!(
+            setting("wasm-opt-level").source,
+            Source::Flag("--wasm-opt-level")
+        );
+        assert_eq!(setting("wasm-opt-shrink-level").value, json!(1));
+        assert_eq!(setting("wasm-opt-shrink-level").source, Source::Default);
+        assert_eq!(setting("bindgen-target").value, json!("web"));
+        assert_eq!(setting("hashed-assets").value, json!(true));
+        assert_eq!(
+            setting("hashed-assets").source,
+            Source::Hook("asset_filter")
+        );
+        let settings = resolve(&args, None, &Hooks::default());
+        assert_eq!(
+            settings
+                .iter()
+                .find(|x| x.name == "hashed-assets")
+                .unwrap()
+                .value,
+            json!(false)
+        );
+
+        let text = to_text(&settings[..2]);
+        assert!(text.starts_with("package     wasmbl"), "{}", text);
+        assert!(text.ends_with("flag `--build-path`\n"), "{}", text);
+        assert_eq!(
+            to_json(&settings[1..2])[0],
+            json!({
+                "name": "build-path",
+                "value": setting("build-path").value,
+                "source": "flag",
+                "key": "--build-path",
+            })
+        );
+        assert_eq!(text_value(&json!(["a", "b"])), "a, b");
+        assert_eq!(text_value(&json!([])), "-");
+        assert_eq!(text_value(&Value::Null), "-");
+    }
+}
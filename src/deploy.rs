@@ -0,0 +1,844 @@
+//! The `deploy` command: make a release build and copy it to a destination.
+
+use crate::{hooks, BuildArgs, BuildProfile, DefaultBuildArgs, Project, ServerConfig};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use structopt::StructOpt;
+
+/// Base image of `--docker` if none is configured.
+const DEFAULT_BASE_IMAGE: &str = "nginx:alpine";
+
+/// Container runtimes looked up for `--docker`, in order.
+const CONTAINER_RUNTIMES: &[&str] = &["docker", "podman"];
+
+/// Directory of the image where nginx serves the files.
+const NGINX_ROOT: &str = "/usr/share/nginx/html";
+
+/// Deploy arguments.
+#[derive(StructOpt, Debug)]
+pub struct DefaultDeployArgs {
+    /// Where to copy the build: a local directory or `user@host:/path` (copied with `rsync` if it
+    /// is available, `scp` otherwise). Defaults to the key `destination` of the
+    /// `[package.metadata.wasmbl.deploy]` table of the frontend package.
+    pub destination: Option<String>,
+
+    /// Print the files that would be deployed without deploying them.
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Publish the build to the branch `gh-pages` of the remote `origin` for GitHub Pages instead
+    /// of copying it to the destination.
+    #[structopt(long)]
+    pub gh_pages: bool,
+
+    /// Publish to GitHub Pages even if the working tree has uncommitted changes.
+    #[structopt(long)]
+    pub allow_dirty: bool,
+
+    /// Build a container image that serves the build with nginx instead of copying it to the
+    /// destination: `registry/app` or `registry/app:tag`. The default tag is the short hash of the
+    /// commit `HEAD` (with `-dirty` if the working tree has uncommitted changes).
+    #[structopt(long, value_name = "IMAGE", conflicts_with = "gh-pages")]
+    pub docker: Option<String>,
+
+    /// Push the image built with `--docker`.
+    #[structopt(long, requires = "docker")]
+    pub push: bool,
+
+    /// Base image of `--docker`, it must serve the files with nginx. Defaults to the key
+    /// `base-image` of the `[package.metadata.wasmbl.deploy]` table of the frontend package, or
+    /// `nginx:alpine`.
+    #[structopt(long, value_name = "IMAGE", requires = "docker")]
+    pub base_image: Option<String>,
+
+    /// Program that builds the image of `--docker`. Defaults to `docker`, or `podman` if
+    /// `docker` is not found.
+    #[structopt(long, value_name = "PROGRAM", requires = "docker")]
+    pub container_runtime: Option<String>,
+
+    /// Build arguments.
+    #[structopt(flatten)]
+    pub build_args: DefaultBuildArgs,
+}
+
+/// Where the build is deployed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    /// A directory on this machine.
+    Local(PathBuf),
+    /// A location on another machine, reachable with ssh: `user@host:/path`.
+    Remote(String),
+}
+
+impl Destination {
+    /// Parse a destination the same way `rsync` does: a colon before the first slash means the
+    /// destination is on another machine. Windows drive letters (`C:\...`) are local paths.
+    pub fn parse(destination: &str) -> Self {
+        let is_drive = destination.len() >= 2
+            && destination.as_bytes()[1] == b':'
+            && destination.as_bytes()[0].is_ascii_alphabetic();
+
+        match destination.find(':') {
+            Some(colon) if !is_drive && !destination[..colon].contains('/') => {
+                Self::Remote(destination.to_string())
+            }
+            _ => Self::Local(PathBuf::from(destination)),
+        }
+    }
+}
+
+impl std::fmt::Display for Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local(path) => write!(f, "{}", path.display()),
+            Self::Remote(destination) => write!(f, "{}", destination),
+        }
+    }
+}
+
+impl DefaultDeployArgs {
+    /// The destination given on the command-line or in the metadata of the frontend package.
+    ///
+    /// A relative local path from the metadata is relative to the workspace root.
+    pub fn destination(&self) -> Result<Destination> {
+        if let Some(destination) = self.destination.as_deref() {
+            return Ok(Destination::parse(destination));
+        }
+
+        let destination = self
+            .build_args
+            .frontend_package()
+            .metadata
+            .get("wasmbl")
+            .and_then(|x| x.get("deploy"))
+            .and_then(|x| x.get("destination"))
+            .and_then(|x| x.as_str())
+            .context(
+                "no destination to deploy to: give one on the command-line or with the key \
+                `destination` in `[package.metadata.wasmbl.deploy]`",
+            )?;
+
+        Ok(match Destination::parse(destination) {
+            Destination::Local(path) => {
+                Destination::Local(self.build_args.metadata().workspace_root.join(path))
+            }
+            remote => remote,
+        })
+    }
+
+    /// Run the `deploy` command.
+    ///
+    /// The files of the release build are given to the `deploy` hook, which copies them to the
+    /// destination by default.
+    pub fn run(self) -> Result<()> {
+        let hooks = hooks()?;
+        Project::select_frontend(&self.build_args)?;
+
+        if self.gh_pages {
+            return self.deploy_gh_pages();
+        }
+        if self.docker.is_some() {
+            return self.deploy_docker();
+        }
+
+        // NOTE: fail early rather than after the build
+        let destination = self.destination()?;
+
+        crate::build(
+            BuildProfile::Release,
+            &self.build_args,
+            hooks,
+            false,
+            crate::BuildTrigger::Build,
+        )?;
+        let build_path = self.build_args.build_path();
+        crate::build_meta::check_shippable(&build_path, self.build_args.force)?;
+        let files = build_files(&build_path)?;
+
+        if self.dry_run {
+            for file in files.iter() {
+                log::info!("Would deploy `{}` to `{}`", file.display(), destination);
+            }
+            return Ok(());
+        }
+
+        (hooks.deploy)(&self, &files)
+    }
+
+    /// Build with the base URL of the site of the repository and commit the files of the build to
+    /// the branch `gh-pages` of `origin`, with a `.nojekyll` so GitHub Pages does not ignore the
+    /// files that start with `_`.
+    fn deploy_gh_pages(mut self) -> Result<()> {
+        let hooks = hooks()?;
+        let repo = self.build_args.metadata().workspace_root.clone();
+
+        // NOTE: fail early rather than after the build
+        if !self.allow_dirty && !git_output(git(&repo).args(["status", "--porcelain"]))?.is_empty()
+        {
+            bail!(
+                "the working tree has uncommitted changes (pass `--allow-dirty` to deploy anyway)"
+            );
+        }
+        let remote = git_output(git(&repo).args(["remote", "get-url", "origin"]))
+            .context("could not find the remote `origin`")?;
+        let site = GhPages::from_remote(&remote).with_context(|| {
+            format!(
+                "the remote `origin` is not a GitHub repository: `{}`",
+                remote
+            )
+        })?;
+        if self.build_args.base_url.is_none() {
+            self.build_args.base_url = Some(site.base_url());
+        }
+
+        crate::build(
+            BuildProfile::Release,
+            &self.build_args,
+            hooks,
+            false,
+            crate::BuildTrigger::Build,
+        )?;
+        let build_path = self.build_args.build_path();
+        crate::build_meta::check_shippable(&build_path, self.build_args.force)?;
+        let files = build_files(&build_path)?;
+
+        if self.dry_run {
+            for file in files.iter() {
+                log::info!("Would publish `{}` to `gh-pages`", file.display());
+            }
+            log::info!("Would deploy to {}", site.url());
+            return Ok(());
+        }
+
+        publish_gh_pages(&repo, &build_path, &files)?;
+        log::info!("Deployed to {}", site.url());
+        Ok(())
+    }
+}
+
+impl DefaultDeployArgs {
+    /// The base image given on the command-line or in the metadata of the frontend package.
+    pub fn base_image(&self) -> String {
+        if let Some(base_image) = self.base_image.as_deref() {
+            return base_image.to_string();
+        }
+
+        self.build_args
+            .frontend_package()
+            .metadata
+            .get("wasmbl")
+            .and_then(|x| x.get("deploy"))
+            .and_then(|x| x.get("base-image"))
+            .and_then(|x| x.as_str())
+            .unwrap_or(DEFAULT_BASE_IMAGE)
+            .to_string()
+    }
+
+    /// Build with the configuration of nginx and build an image of the base image with the files
+    /// of the build and this configuration, then push it with `--push`.
+    fn deploy_docker(mut self) -> Result<()> {
+        let hooks = hooks()?;
+        let repo = self.build_args.metadata().workspace_root.clone();
+
+        // NOTE: fail early rather than after the build
+        let runtime = match (self.container_runtime.as_deref(), self.dry_run) {
+            (Some(runtime), _) => runtime.to_string(),
+            (None, dry_run) => match find_container_runtime() {
+                Some(runtime) => runtime.to_string(),
+                None if dry_run => CONTAINER_RUNTIMES[0].to_string(),
+                None => bail!(
+                    "could not find a container runtime: install `docker` or `podman`, or give \
+                    one with `--container-runtime`"
+                ),
+            },
+        };
+        match self.build_args.emit_server_config {
+            None | Some(ServerConfig::Nginx) => {
+                self.build_args.emit_server_config = Some(ServerConfig::Nginx)
+            }
+            Some(server) => bail!(
+                "`--docker` serves the build with nginx, it can't emit the server config {:?}",
+                server
+            ),
+        }
+        let image = image_tag(
+            self.docker.as_deref().expect("checked by run(); qed"),
+            &repo,
+        );
+        let base_image = self.base_image();
+
+        crate::build(
+            BuildProfile::Release,
+            &self.build_args,
+            hooks,
+            false,
+            crate::BuildTrigger::Build,
+        )?;
+        let build_path = self.build_args.build_path();
+        crate::build_meta::check_shippable(&build_path, self.build_args.force)?;
+        let nginx_conf = crate::written_asset("nginx.conf")
+            .map(|x| std::env::current_dir().unwrap_or_default().join(x))
+            .unwrap_or_else(|| build_path.join("nginx.conf"));
+        let files: Vec<_> = build_files(&build_path)?
+            .into_iter()
+            .filter(|x| *x != nginx_conf)
+            .collect();
+
+        let context = tempfile::Builder::new()
+            .prefix("wasmbl-docker-")
+            .tempdir()
+            .context("could not create the context of the image")?;
+        let site = context.path().join("site");
+        copy_files(&build_path, &files, &site)?;
+        fs::copy(&nginx_conf, context.path().join("wasmbl.conf")).with_context(|| {
+            format!(
+                "could not copy `{}` to the context of the image",
+                nginx_conf.display()
+            )
+        })?;
+        for (name, content) in [
+            ("default.conf", nginx_server()),
+            (
+                "Dockerfile",
+                dockerfile(&base_image, &crate::base_url(&self.build_args)),
+            ),
+        ] {
+            fs::write(context.path().join(name), content)
+                .with_context(|| format!("could not write `{}` in the context", name))?;
+        }
+
+        let mut commands = vec![{
+            let mut command = Command::new(&runtime);
+            command.args(["build", "--tag", &image]).arg(context.path());
+            command
+        }];
+        if self.push {
+            let mut command = Command::new(&runtime);
+            command.args(["push", &image]);
+            commands.push(command);
+        }
+
+        if self.dry_run {
+            for file in files.iter() {
+                log::info!("Would add `{}` to the image `{}`", file.display(), image);
+            }
+            for command in commands.iter() {
+                log::info!("Would run {:?}", command);
+            }
+            return Ok(());
+        }
+
+        log::info!(
+            "Building the image `{}` with {} files from `{}`",
+            image,
+            files.len(),
+            base_image
+        );
+        for mut command in commands {
+            log::debug!("Running {:?}", command);
+            let status = command
+                .status()
+                .with_context(|| format!("could not run {:?}", command))?;
+            if !status.success() {
+                bail!(
+                    "could not deploy the image `{}`: {:?} failed",
+                    image,
+                    command
+                );
+            }
+        }
+        if self.push {
+            log::info!("Pushed the image `{}`", image);
+        } else {
+            log::info!("Built the image `{}`", image);
+        }
+
+        Ok(())
+    }
+}
+
+/// The first of [`CONTAINER_RUNTIMES`] that can be run.
+fn find_container_runtime() -> Option<&'static str> {
+    CONTAINER_RUNTIMES.iter().copied().find(|runtime| {
+        Command::new(runtime)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|x| x.success())
+            .unwrap_or(false)
+    })
+}
+
+/// The image with the tag of the commit `HEAD` of the repository if it has no tag, or `latest`
+/// if it is not in a repository.
+fn image_tag(image: &str, repo: &Path) -> String {
+    // NOTE: the port of the registry is not a tag: `localhost:5000/app`
+    let name = image.rsplit('/').next().unwrap_or(image);
+    if name.contains(':') || name.contains('@') {
+        return image.to_string();
+    }
+
+    let head = Command::new("git")
+        .current_dir(repo)
+        .args(["rev-parse", "--short", "--verify", "--quiet", "HEAD"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|x| x.status.success())
+        .map(|x| String::from_utf8_lossy(&x.stdout).trim().to_string());
+    let head = match head {
+        Some(head) => head,
+        None => {
+            log::warn!("Could not get the commit `HEAD`, the image is tagged `latest`");
+            return format!("{}:latest", image);
+        }
+    };
+    let dirty = Command::new("git")
+        .current_dir(repo)
+        .args(["status", "--porcelain"])
+        .stderr(Stdio::null())
+        .output()
+        .map(|x| !x.stdout.is_empty())
+        .unwrap_or(false);
+
+    with_tag(image, &head, dirty)
+}
+
+fn with_tag(image: &str, head: &str, dirty: bool) -> String {
+    if dirty {
+        format!("{}:{}-dirty", image, head)
+    } else {
+        format!("{}:{}", image, head)
+    }
+}
+
+/// The `server` block of nginx that includes the configuration emitted with the build.
+fn nginx_server() -> String {
+    format!(
+        "# Generated by wasmbl.\nserver {{\n    listen 80;\n    root {};\n    include /etc/nginx/wasmbl.conf;\n}}\n",
+        NGINX_ROOT
+    )
+}
+
+/// The Dockerfile of the image: the files of the build are served at the base URL.
+fn dockerfile(base_image: &str, base_url: &str) -> String {
+    format!(
+        "# Generated by wasmbl.\n\
+        FROM {}\n\
+        COPY default.conf /etc/nginx/conf.d/default.conf\n\
+        COPY wasmbl.conf /etc/nginx/wasmbl.conf\n\
+        COPY site/ {}{}\n",
+        base_image, NGINX_ROOT, base_url
+    )
+}
+
+/// The site of GitHub Pages of a repository.
+#[derive(Debug, PartialEq, Eq)]
+struct GhPages {
+    owner: String,
+    repo: String,
+}
+
+impl GhPages {
+    /// The repository of a remote URL of GitHub: `git@github.com:owner/repo.git`,
+    /// `https://github.com/owner/repo`, ...
+    fn from_remote(url: &str) -> Option<Self> {
+        let (_, path) = url.split_once("github.com")?;
+        let path = path.strip_prefix(':').or_else(|| path.strip_prefix('/'))?;
+        let path = path.trim_end_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        let (owner, repo) = path.split_once('/')?;
+        if owner.is_empty() || repo.is_empty() || repo.contains('/') {
+            return None;
+        }
+
+        Some(Self {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+        })
+    }
+
+    /// The site of a user or an organization (`<owner>.github.io`) is served at the root.
+    fn base_url(&self) -> String {
+        if self
+            .repo
+            .eq_ignore_ascii_case(&format!("{}.github.io", self.owner))
+        {
+            "/".to_string()
+        } else {
+            format!("/{}/", self.repo)
+        }
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "https://{}.github.io{}",
+            self.owner.to_lowercase(),
+            self.base_url()
+        )
+    }
+}
+
+/// Commit the files of the build to the branch `gh-pages` of `origin` and push it, the previous
+/// commit of the branch is the parent.
+///
+/// The commit is made with a temporary index: the working tree, the index and the branches of the
+/// repository are not touched.
+fn publish_gh_pages(repo: &Path, build_path: &Path, files: &[PathBuf]) -> Result<()> {
+    let git_dir = PathBuf::from(git_output(
+        git(repo).args(["rev-parse", "--absolute-git-dir"]),
+    )?);
+    let index = std::env::temp_dir().join(format!("wasmbl-gh-pages-{}", std::process::id()));
+    let git = |dir: &Path| {
+        let mut command = git(dir);
+        command
+            .env("GIT_DIR", &git_dir)
+            .env("GIT_INDEX_FILE", &index)
+            .env("GIT_WORK_TREE", build_path);
+        command
+    };
+
+    let res = (|| {
+        let branch = git_output(git(repo).args(["ls-remote", "--heads", "origin", "gh-pages"]))?;
+        let parent = if branch.is_empty() {
+            None
+        } else {
+            git_output(git(repo).args(["fetch", "--quiet", "origin", "gh-pages"]))?;
+            Some(git_output(git(repo).args(["rev-parse", "FETCH_HEAD"]))?)
+        };
+
+        let relative: Vec<_> = files
+            .iter()
+            .filter_map(|x| x.strip_prefix(build_path).ok())
+            .collect();
+        git_output(
+            git(build_path)
+                .args(["add", "--force", "--"])
+                .args(relative),
+        )?;
+        let empty = git_output(
+            git(repo)
+                .args(["hash-object", "-w", "--stdin"])
+                .stdin(Stdio::null()),
+        )?;
+        git_output(git(repo).args([
+            "update-index",
+            "--add",
+            "--cacheinfo",
+            &format!("100644,{},.nojekyll", empty),
+        ]))?;
+        let tree = git_output(git(repo).arg("write-tree"))?;
+
+        if let Some(parent) = parent.as_deref() {
+            let parent_tree =
+                git_output(git(repo).args(["rev-parse", &format!("{}^{{tree}}", parent)]))?;
+            if parent_tree == tree {
+                log::info!("The branch `gh-pages` is already up to date");
+                return Ok(());
+            }
+        }
+
+        let message = match git_output(git(repo).args([
+            "rev-parse",
+            "--short",
+            "--verify",
+            "--quiet",
+            "HEAD",
+        ])) {
+            Ok(head) => format!("Deploy {}", head),
+            Err(_) => "Deploy".to_string(),
+        };
+        let mut commit_tree = git(repo);
+        commit_tree.args(["commit-tree", &tree, "-m", &message]);
+        if let Some(parent) = parent.as_deref() {
+            commit_tree.args(["-p", parent]);
+        }
+        let commit = git_output(&mut commit_tree)?;
+
+        log::info!("Pushing {} files to `gh-pages`", files.len());
+        git_output(git(repo).args([
+            "push",
+            "--quiet",
+            "origin",
+            &format!("{}:refs/heads/gh-pages", commit),
+        ]))?;
+        Ok(())
+    })();
+
+    let _ = fs::remove_file(&index);
+    res
+}
+
+/// A git command run in a directory.
+fn git(dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.current_dir(dir);
+    command
+}
+
+/// Run a git command and returns its output, trimmed. Its errors are shown in the terminal.
+fn git_output(command: &mut Command) -> Result<String> {
+    log::debug!("Running {:?}", command);
+    let output = command
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("could not run {:?}", command))?;
+    if !output.status.success() {
+        bail!("{:?} failed", command);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// All the files in the build directory, sorted.
+fn build_files(build_path: &Path) -> Result<Vec<PathBuf>> {
+    let files = crate::list_files(build_path).with_context(|| {
+        format!(
+            "could not list the files of the build directory `{}`",
+            build_path.display()
+        )
+    })?;
+
+    Ok(files
+        .into_iter()
+        .filter(|x| {
+            !x.strip_prefix(build_path)
+                .map(crate::build_files::is_internal)
+                .unwrap_or(false)
+        })
+        .collect())
+}
+
+/// The default `deploy` hook.
+pub(crate) fn deploy(args: &DefaultDeployArgs, files: &[PathBuf]) -> Result<()> {
+    let build_path = args.build_args.build_path();
+
+    match args.destination()? {
+        Destination::Local(path) => {
+            log::info!("Deploying {} files to `{}`", files.len(), path.display());
+            copy_files(&build_path, files, &path)
+        }
+        Destination::Remote(destination) => {
+            log::info!("Deploying {} files to `{}`", files.len(), destination);
+            let mut source = build_path.as_os_str().to_owned();
+            let mut command = if has_rsync() {
+                // NOTE: the trailing slash copies the content of the directory
+                source.push("/");
+                let mut command = Command::new("rsync");
+                command.arg("-az");
+                command
+            } else {
+                source.push("/.");
+                let mut command = Command::new("scp");
+                command.arg("-r");
+                command
+            };
+            command.arg(source).arg(&destination);
+
+            log::debug!("Running {:?}", command);
+            let status = command
+                .status()
+                .with_context(|| format!("could not run {:?}", command))?;
+            if !status.success() {
+                bail!(
+                    "could not deploy to `{}`: {:?} failed",
+                    destination,
+                    command
+                );
+            }
+
+            Ok(())
+        }
+    }
+}
+
+fn has_rsync() -> bool {
+    Command::new("rsync")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|x| x.success())
+        .unwrap_or(false)
+}
+
+/// Copy the files of the build directory to `destination`, keeping their relative paths.
+fn copy_files(build_path: &Path, files: &[PathBuf], destination: &Path) -> Result<()> {
+    for file in files {
+        let relative = file
+            .strip_prefix(build_path)
+            .with_context(|| format!("`{}` is not in the build directory", file.display()))?;
+        let target = destination.join(relative);
+        let dir = target.parent().expect("the file is in a directory; qed");
+
+        fs::create_dir_all(dir)
+            .with_context(|| format!("could not create directory `{}`", dir.display()))?;
+        fs::copy(file, &target).with_context(|| {
+            format!(
+                "could not copy `{}` to `{}`",
+                file.display(),
+                target.display()
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_destinations() {
+        assert_eq!(
+            Destination::parse("user@example.com:/var/www"),
+            Destination::Remote("user@example.com:/var/www".to_string()),
+        );
+        assert_eq!(
+            Destination::parse("example.com:www"),
+            Destination::Remote("example.com:www".to_string()),
+        );
+        assert_eq!(
+            Destination::parse("/var/www"),
+            Destination::Local(PathBuf::from("/var/www")),
+        );
+        assert_eq!(
+            Destination::parse("./dir:with-colon"),
+            Destination::Local(PathBuf::from("./dir:with-colon")),
+        );
+        assert_eq!(
+            Destination::parse("C:\\www"),
+            Destination::Local(PathBuf::from("C:\\www")),
+        );
+    }
+
+    #[test]
+    fn copy_build_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let build_path = dir.path().join("build");
+        fs::create_dir_all(build_path.join("assets")).unwrap();
+        fs::write(build_path.join("index.html"), "index").unwrap();
+        fs::write(build_path.join("assets").join("app.css"), "css").unwrap();
+
+        let files = build_files(&build_path).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                build_path.join("assets").join("app.css"),
+                build_path.join("index.html"),
+            ],
+        );
+
+        let destination = dir.path().join("public");
+        copy_files(&build_path, &files, &destination).unwrap();
+        assert_eq!(
+            fs::read_to_string(destination.join("assets").join("app.css")).unwrap(),
+            "css",
+        );
+        assert_eq!(
+            fs::read_to_string(destination.join("index.html")).unwrap(),
+            "index",
+        );
+    }
+
+    #[test]
+    fn docker_image() {
+        assert_eq!(
+            with_tag("registry/app", "1a2b3c4", false),
+            "registry/app:1a2b3c4"
+        );
+        assert_eq!(
+            with_tag("registry/app", "1a2b3c4", true),
+            "registry/app:1a2b3c4-dirty"
+        );
+        let dir = tempfile::tempdir().unwrap();
+        for image in ["registry/app:v1", "localhost:5000/app:v1", "app@sha256:abc"] {
+            assert_eq!(image_tag(image, dir.path()), image);
+        }
+        assert_eq!(
+            image_tag("localhost:5000/app", dir.path()),
+            "localhost:5000/app:latest"
+        );
+
+        assert_eq!(
+            dockerfile("nginx:alpine", "/app/"),
+            "# Generated by wasmbl.\n\
+            FROM nginx:alpine\n\
+            COPY default.conf /etc/nginx/conf.d/default.conf\n\
+            COPY wasmbl.conf /etc/nginx/wasmbl.conf\n\
+            COPY site/ /usr/share/nginx/html/app/\n"
+        );
+        assert!(nginx_server().contains("root /usr/share/nginx/html;"));
+    }
+
+    #[test]
+    fn parse_github_remotes() {
+        let site = GhPages {
+            owner: "Owner".to_string(),
+            repo: "app".to_string(),
+        };
+        assert_eq!(
+            GhPages::from_remote("git@github.com:Owner/app.git"),
+            Some(site)
+        );
+        assert_eq!(
+            GhPages::from_remote("https://github.com/Owner/app/")
+                .unwrap()
+                .url(),
+            "https://owner.github.io/app/"
+        );
+        let user_site = GhPages::from_remote("ssh://git@github.com/owner/owner.github.io").unwrap();
+        assert_eq!(user_site.base_url(), "/");
+        assert_eq!(user_site.url(), "https://owner.github.io/");
+        assert_eq!(
+            GhPages::from_remote("https://gitlab.com/owner/app.git"),
+            None
+        );
+        assert_eq!(GhPages::from_remote("https://github.com/owner"), None);
+    }
+
+    #[test]
+    fn publish_to_gh_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let run = |dir: &Path, args: &[&str]| git_output(git(dir).args(args)).unwrap();
+        let remote = dir.path().join("remote.git");
+        let repo = dir.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        run(dir.path(), &["init", "--quiet", "--bare", "remote.git"]);
+        run(&repo, &["init", "--quiet"]);
+        run(&repo, &["config", "user.name", "wasmbl"]);
+        run(&repo, &["config", "user.email", "wasmbl@example.com"]);
+        run(
+            &repo,
+            &["remote", "add", "origin", &remote.to_string_lossy()],
+        );
+
+        let build_path = dir.path().join("build");
+        fs::create_dir_all(build_path.join("snippets")).unwrap();
+        fs::write(build_path.join("index.html"), "index").unwrap();
+        fs::write(build_path.join("snippets").join("a.js"), "snippet").unwrap();
+        let files = vec![
+            build_path.join("index.html"),
+            build_path.join("snippets").join("a.js"),
+        ];
+
+        publish_gh_pages(&repo, &build_path, &files).unwrap();
+        let ls_tree = |remote: &Path| run(remote, &["ls-tree", "-r", "--name-only", "gh-pages"]);
+        assert_eq!(ls_tree(&remote), ".nojekyll\nindex.html\nsnippets/a.js");
+
+        // NOTE: the new commit is made on top of the previous deployment
+        fs::write(build_path.join("index.html"), "index 2").unwrap();
+        publish_gh_pages(&repo, &build_path, &files[..1]).unwrap();
+        assert_eq!(ls_tree(&remote), ".nojekyll\nindex.html");
+        assert_eq!(run(&remote, &["rev-list", "--count", "gh-pages"]), "2");
+        assert_eq!(run(&remote, &["show", "gh-pages:index.html"]), "index 2");
+
+        publish_gh_pages(&repo, &build_path, &files[..1]).unwrap();
+        assert_eq!(run(&remote, &["rev-list", "--count", "gh-pages"]), "2");
+        assert!(run(&repo, &["status", "--porcelain"]).is_empty());
+    }
+}
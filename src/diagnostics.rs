@@ -0,0 +1,273 @@
+//! The errors and the warnings of the compiler during the last cargo build, summarized after the
+//! failed rebuilds of the watcher, in the overlay of the browsers and by the control API.
+
+use crate::Error;
+use cargo_metadata::diagnostic::{Diagnostic, DiagnosticLevel};
+use once_cell::sync::Lazy;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+static LAST: Lazy<Mutex<Diagnostics>> = Lazy::new(Default::default);
+
+/// The errors and the warnings of a cargo build.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Diagnostics {
+    /// The paths of the diagnostics are relative to this directory if they are in it.
+    root: PathBuf,
+    pub entries: Vec<Entry>,
+}
+
+/// An error or a warning of the compiler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub error: bool,
+    /// The code of the diagnostic, like `E0308`.
+    pub code: Option<String>,
+    pub message: String,
+    /// The file, line and column of the primary span.
+    pub location: Option<(String, usize, usize)>,
+}
+
+impl Diagnostics {
+    fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_owned(),
+            entries: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, diagnostic: &Diagnostic) {
+        let error = match diagnostic.level {
+            DiagnosticLevel::Error | DiagnosticLevel::Ice => true,
+            DiagnosticLevel::Warning => false,
+            _ => return,
+        };
+        // NOTE: the counts printed by rustc at the end are not diagnostics of the sources
+        if diagnostic.spans.is_empty()
+            && (diagnostic.message.starts_with("aborting due to")
+                || diagnostic.message.ends_with(" emitted"))
+        {
+            return;
+        }
+
+        let location = diagnostic
+            .spans
+            .iter()
+            .find(|x| x.is_primary)
+            .or_else(|| diagnostic.spans.first())
+            .map(|span| {
+                let path = Path::new(&span.file_name);
+                let path = path.strip_prefix(&self.root).unwrap_or(path);
+                (crate::slash_path(path), span.line_start, span.column_start)
+            });
+        self.entries.push(Entry {
+            error,
+            code: diagnostic.code.as_ref().map(|x| x.code.clone()),
+            message: diagnostic.message.clone(),
+            location,
+        });
+    }
+
+    pub(crate) fn errors(&self) -> usize {
+        self.entries.iter().filter(|x| x.error).count()
+    }
+
+    pub(crate) fn warnings(&self) -> usize {
+        self.entries.len() - self.errors()
+    }
+
+    /// The counts of the errors and the warnings, then every error with its location:
+    ///
+    /// ```text
+    /// 1 error, 2 warnings
+    ///   src/lib.rs:3:5: error[E0308]: mismatched types
+    /// ```
+    pub(crate) fn summary(&self) -> String {
+        let plural = |count: usize, name: &str| {
+            format!("{} {}{}", count, name, if count == 1 { "" } else { "s" })
+        };
+        let mut summary = format!(
+            "{}, {}",
+            plural(self.errors(), "error"),
+            plural(self.warnings(), "warning")
+        );
+        for entry in self.entries.iter().filter(|x| x.error) {
+            summary.push_str("\n  ");
+            if let Some((file, line, column)) = entry.location.as_ref() {
+                let _ = write!(summary, "{}:{}:{}: ", file, line, column);
+            }
+            summary.push_str("error");
+            if let Some(code) = entry.code.as_ref() {
+                let _ = write!(summary, "[{}]", code);
+            }
+            let _ = write!(summary, ": {}", entry.message);
+        }
+        summary
+    }
+
+    #[cfg(feature = "dev-server")]
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        let messages: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let (file, line, column) = match entry.location.as_ref() {
+                    Some((file, line, column)) => (Some(file), Some(line), Some(column)),
+                    None => (None, None, None),
+                };
+                serde_json::json!({
+                    "level": if entry.error { "error" } else { "warning" },
+                    "code": entry.code,
+                    "message": entry.message,
+                    "file": file,
+                    "line": line,
+                    "column": column,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "errors": self.errors(),
+            "warnings": self.warnings(),
+            "messages": messages,
+        })
+    }
+}
+
+/// Forget the diagnostics of the previous build, the paths of the next ones are relative to the
+/// root of the workspace.
+pub(crate) fn start(workspace_root: &Path) {
+    *LAST.lock().unwrap() = Diagnostics::new(workspace_root);
+}
+
+/// Record a diagnostic of the compiler.
+pub(crate) fn record(diagnostic: &Diagnostic) {
+    LAST.lock().unwrap().push(diagnostic);
+}
+
+/// The diagnostics of the last cargo build.
+pub(crate) fn last() -> Diagnostics {
+    LAST.lock().unwrap().clone()
+}
+
+/// Log a diagnostic of the compiler as a `diagnostic` event of `--log-format json`.
+pub(crate) fn log_event(diagnostic: &Diagnostic) {
+    let level = match diagnostic.level {
+        DiagnosticLevel::Error | DiagnosticLevel::Ice => log::Level::Error,
+        DiagnosticLevel::Warning => log::Level::Warn,
+        _ => log::Level::Info,
+    };
+    let span = diagnostic
+        .spans
+        .iter()
+        .find(|x| x.is_primary)
+        .or_else(|| diagnostic.spans.first());
+    crate::logger::event(
+        level,
+        "diagnostic",
+        serde_json::json!({
+            "code": diagnostic.code.as_ref().map(|x| &x.code),
+            "file": span.map(|x| &x.file_name),
+            "line": span.map(|x| x.line_start),
+            "column": span.map(|x| x.column_start),
+        }),
+        Some(format_args!("{}", diagnostic.message)),
+    );
+}
+
+/// The summary of the diagnostics if the error is a failure of cargo.
+pub(crate) fn failure_summary(err: &anyhow::Error) -> Option<String> {
+    match err.downcast_ref::<Error>() {
+        Some(Error::CargoBuildFailed { .. }) => Some(last().summary()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(level: &str, message: &str, file: Option<&str>) -> Diagnostic {
+        let spans = match file {
+            Some(file) => serde_json::json!([{
+                "file_name": file, "byte_start": 0, "byte_end": 1, "line_start": 3,
+                "line_end": 3, "column_start": 5, "column_end": 6, "is_primary": true,
+                "text": [], "label": null, "suggested_replacement": null,
+                "suggestion_applicability": null, "expansion": null,
+            }]),
+            None => serde_json::json!([]),
+        };
+        let code = match level {
+            "error" => serde_json::json!({ "code": "E0308", "explanation": null }),
+            _ => serde_json::Value::Null,
+        };
+        serde_json::from_value(serde_json::json!({
+            "message": message,
+            "code": code,
+            "level": level,
+            "spans": spans,
+            "children": [],
+            "rendered": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn summary_of_the_errors() {
+        let root = Path::new("/workspace");
+        let mut diagnostics = Diagnostics::new(root);
+        for diagnostic in [
+            diagnostic("warning", "unused variable: `x`", Some("src/lib.rs")),
+            diagnostic(
+                "error",
+                "mismatched types",
+                Some(
+                    &root
+                        .join("app")
+                        .join("src")
+                        .join("main.rs")
+                        .display()
+                        .to_string(),
+                ),
+            ),
+            diagnostic("note", "a note", None),
+            diagnostic("error", "linking with `rust-lld` failed", None),
+            diagnostic("error", "aborting due to 2 previous errors", None),
+            diagnostic("warning", "1 warning emitted", None),
+        ] {
+            diagnostics.push(&diagnostic);
+        }
+
+        assert_eq!(diagnostics.errors(), 2);
+        assert_eq!(diagnostics.warnings(), 1);
+        assert_eq!(
+            diagnostics.summary(),
+            "2 errors, 1 warning\n  \
+            app/src/main.rs:3:5: error[E0308]: mismatched types\n  \
+            error[E0308]: linking with `rust-lld` failed"
+        );
+    }
+
+    #[cfg(feature = "dev-server")]
+    #[test]
+    fn diagnostics_in_json() {
+        let mut diagnostics = Diagnostics::new(Path::new("/workspace"));
+        diagnostics.push(&diagnostic("error", "mismatched types", Some("src/lib.rs")));
+
+        assert_eq!(
+            diagnostics.to_json(),
+            serde_json::json!({
+                "errors": 1,
+                "warnings": 0,
+                "messages": [{
+                    "level": "error",
+                    "code": "E0308",
+                    "message": "mismatched types",
+                    "file": "src/lib.rs",
+                    "line": 3,
+                    "column": 5,
+                }],
+            })
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! The listing of the directories of the development server (`serve --dir-listing`): a page with
+//! the files of a directory that has no `index.html`, their sizes and modification times.
+//!
+//! Only the directories that exist are listed, the other paths still fall back to an
+//! `index.html`.
+
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// An entry of a listed directory.
+struct Entry {
+    name: String,
+    /// The name percent-encoded for the URL.
+    segment: String,
+    dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+/// The HTML listing of a directory of the serve directory, given by its path relative to the
+/// serve directory. The links start with the base URL, like `/app/img/logo.svg`.
+pub(crate) fn render(serve_path: &Path, relative: &Path, base_url: &str) -> io::Result<String> {
+    let dir = serve_path.join(relative);
+    let url = match crate::url_path(relative).unwrap_or_default().as_str() {
+        "" => base_url.to_string(),
+        path => format!("{}{}/", base_url, path),
+    };
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let name = entry.file_name();
+        let segment = match crate::url_path(Path::new(&name)) {
+            Some(segment) => segment,
+            None => continue,
+        };
+        entries.push(Entry {
+            name: name.to_string_lossy().into_owned(),
+            segment,
+            dir: metadata.is_dir(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        });
+    }
+    // NOTE: the directories first, like most file managers
+    entries.sort_by(|a, b| b.dir.cmp(&a.dir).then_with(|| a.name.cmp(&b.name)));
+
+    let title = escape(&url);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Index of {0}</title>\n\
+        <style>body {{ font-family: monospace; }} td {{ padding: 0 1em 0 0; }} \
+        td.size {{ text-align: right; }}</style>\n</head>\n<body>\n<h1>Index of {0}</h1>\n\
+        <table>\n",
+        title
+    );
+    if relative.components().next().is_some() {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+    for entry in &entries {
+        let href = format!(
+            "{}{}{}",
+            url,
+            entry.segment,
+            if entry.dir { "/" } else { "" }
+        );
+        let _ = writeln!(
+            html,
+            "<tr><td><a href=\"{}\">{}{}</a></td><td class=\"size\">{}</td><td>{}</td></tr>",
+            escape(&href),
+            escape(&entry.name),
+            if entry.dir { "/" } else { "" },
+            if entry.dir {
+                "-".to_string()
+            } else {
+                crate::format_size(entry.size as usize)
+            },
+            entry.modified.map(format_time).unwrap_or_default(),
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+
+    Ok(html)
+}
+
+/// Escape the text for the content and the attributes of the HTML.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The time in UTC, like `2021-03-04 05:06`.
+fn format_time(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(x) => x.as_secs() as i64,
+        Err(_) => return String::new(),
+    };
+    let (days, rest) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // NOTE: the civil date of a count of days since 1970-01-01 (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn listing_of_a_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("snippets")).unwrap();
+        fs::write(dir.path().join("app.js"), "x".repeat(2048)).unwrap();
+        fs::write(dir.path().join("<b>&.js"), "").unwrap();
+
+        fs::create_dir(dir.path().join("img")).unwrap();
+        let html = render(dir.path(), Path::new("img"), "/app/").unwrap();
+        assert!(html.contains("<title>Index of /app/img/</title>"));
+        assert!(html.contains("href=\"../\""));
+
+        let html = render(dir.path(), Path::new(""), "/app/").unwrap();
+        let rows: Vec<_> = html.lines().filter(|x| x.starts_with("<tr>")).collect();
+        assert_eq!(rows.len(), 4, "{}", html);
+        assert!(rows[0].contains("<a href=\"/app/img/\">img/</a>"));
+        assert!(rows[1].contains("<a href=\"/app/snippets/\">snippets/</a>"));
+        assert!(rows[2].contains(
+            "<a href=\"/app/%3Cb%3E&amp;.js\">&lt;b&gt;&amp;.js</a></td><td class=\"size\">0 B"
+        ));
+        assert!(rows[3].contains("<a href=\"/app/app.js\">app.js</a></td><td class=\"size\">2 KB"));
+        assert!(html.contains("<title>Index of /app/</title>"));
+        assert!(!html.contains("href=\"../\""));
+    }
+
+    #[test]
+    fn times_in_utc() {
+        assert_eq!(format_time(UNIX_EPOCH), "1970-01-01 00:00");
+        assert_eq!(
+            format_time(UNIX_EPOCH + Duration::from_secs(1_614_834_360)),
+            "2021-03-04 05:06"
+        );
+        assert_eq!(
+            format_time(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29 00:00"
+        );
+    }
+}
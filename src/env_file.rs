@@ -0,0 +1,261 @@
+//! The environment variables of the templates of the pages (`{{ env.PUBLIC_NAME }}`), from the
+//! environment and from the `.env` files.
+//!
+//! Only the variables that start with [`crate::BuildArgs::public_env_prefix`] are given to the
+//! templates: the pages are shipped, the other variables might be secrets. The templates are
+//! checked before they are rendered so a missing variable is reported with its location.
+
+use anyhow::{bail, Context, Result};
+use cargo_metadata::{Metadata, Package};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Default prefix of the variables given to the templates.
+pub(crate) const DEFAULT_PREFIX: &str = "PUBLIC_";
+
+/// The `.env` file of the workspace, then the one of the frontend package.
+pub(crate) fn default_files(metadata: &Metadata, package: &Package) -> Vec<PathBuf> {
+    let workspace = metadata.workspace_root.join(".env");
+    let package = package.manifest_path.parent().unwrap().join(".env");
+    if workspace == package {
+        vec![workspace]
+    } else {
+        vec![workspace, package]
+    }
+}
+
+/// The variables of the `.env` files that exist (the last ones take precedence) and of the
+/// environment (which takes precedence over the files) that start with the prefix.
+pub(crate) fn public_env(files: &[PathBuf], prefix: &str) -> Result<BTreeMap<String, String>> {
+    let mut env = BTreeMap::new();
+    for path in files {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("could not read `{}`", path.display()))
+            }
+        };
+        let vars = parse(&content)
+            .map_err(|(line, msg)| anyhow::anyhow!("{}:{}: {}", path.display(), line, msg))?;
+        env.extend(
+            vars.into_iter()
+                .filter(|(name, _)| name.starts_with(prefix)),
+        );
+    }
+    env.extend(
+        std::env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .filter(|(name, _)| name.starts_with(prefix)),
+    );
+
+    Ok(env)
+}
+
+/// The variables of a `.env` file: `NAME=value` lines with an optional `export`, the values can
+/// be quoted (the escapes `\n`, `\t`, `\"` and `\\` are only read between double quotes). The
+/// empty lines and the lines starting with `#` are ignored.
+fn parse(content: &str) -> Result<Vec<(String, String)>, (usize, String)> {
+    let mut vars = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| (i + 1, "expected `NAME=value`".to_string()))?;
+        let name = name.trim();
+        if !is_name(name) {
+            return Err((i + 1, format!("invalid variable name `{}`", name)));
+        }
+
+        let value = value.trim();
+        let value = if let Some(value) = value.strip_prefix('"') {
+            let mut unescaped = String::new();
+            let mut chars = value.chars();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => unescaped.push('\n'),
+                        Some('t') => unescaped.push('\t'),
+                        Some(c @ ('"' | '\\')) => unescaped.push(c),
+                        Some(c) => {
+                            unescaped.push('\\');
+                            unescaped.push(c);
+                        }
+                        None => return Err((i + 1, "unterminated quoted value".to_string())),
+                    },
+                    Some(c) => unescaped.push(c),
+                    None => return Err((i + 1, "unterminated quoted value".to_string())),
+                }
+            }
+            unescaped
+        } else if let Some(value) = value.strip_prefix('\'') {
+            value
+                .split_once('\'')
+                .ok_or_else(|| (i + 1, "unterminated quoted value".to_string()))?
+                .0
+                .to_string()
+        } else {
+            // NOTE: a comment after an unquoted value must be separated by a space
+            match value.find(" #") {
+                Some(comment) => value[..comment].trim_end().to_string(),
+                None => value.to_string(),
+            }
+        };
+        vars.push((name.to_string(), value));
+    }
+
+    Ok(vars)
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|x| x.is_ascii_alphabetic() || x == '_')
+        .unwrap_or(false)
+        && chars.all(|x| x.is_ascii_alphanumeric() || x == '_')
+}
+
+/// Check that the variables used by a template (`env.NAME` in its tags) are defined, or have a
+/// fallback with the filter `default`. The errors are prefixed by `location:line:column`.
+pub(crate) fn check_template(
+    template: &str,
+    location: &str,
+    env: &BTreeMap<String, String>,
+    prefix: &str,
+) -> Result<()> {
+    let mut errors = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{']) {
+        let tag = &rest[start..];
+        let end = match (tag.get(..2), tag.get(1..).and_then(|x| x.find(['}']))) {
+            (Some("{{" | "{%"), Some(end)) => start + 1 + end,
+            _ => {
+                rest = &rest[start + 1..];
+                continue;
+            }
+        };
+        let offset = template.len() - rest.len();
+        let tag = &rest[start..end];
+
+        let mut search = 0;
+        while let Some(found) = tag[search..].find("env.") {
+            let at = search + found;
+            search = at + 4;
+            let before = tag[..at].chars().next_back();
+            if before
+                .map(|x| x.is_alphanumeric() || x == '_' || x == '.')
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            let name: String = tag[at + 4..]
+                .chars()
+                .take_while(|x| x.is_ascii_alphanumeric() || *x == '_')
+                .collect();
+            let after = tag[at + 4 + name.len()..].trim_start();
+            let has_default = after
+                .strip_prefix('|')
+                .map(|x| x.trim_start().starts_with("default"))
+                .unwrap_or(false);
+            if env.contains_key(&name) || has_default {
+                continue;
+            }
+
+            let position = &template[..offset + start + at];
+            let line = position.matches('\n').count() + 1;
+            let column = position.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+            let message = if name.starts_with(prefix) {
+                format!("the environment variable `{}` is not defined", name)
+            } else {
+                format!(
+                    "the environment variable `{}` can't be used in the pages: only the \
+                    variables that start with `{}` are public",
+                    name, prefix
+                )
+            };
+            errors.push(format!("{}:{}:{}: {}", location, line, column, message));
+        }
+
+        rest = &rest[end..];
+    }
+
+    if !errors.is_empty() {
+        bail!("{}", errors.join("\n"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_env_files() {
+        let content = r#"
+# analytics
+PUBLIC_ID=UA-1234 # the id
+export PUBLIC_TITLE="My \"app\"\n"
+PUBLIC_RAW='a\nb # c'
+PUBLIC_URL=https://example.com/#top
+EMPTY=
+"#;
+        assert_eq!(
+            parse(content).unwrap(),
+            [
+                ("PUBLIC_ID", "UA-1234"),
+                ("PUBLIC_TITLE", "My \"app\"\n"),
+                ("PUBLIC_RAW", "a\\nb # c"),
+                ("PUBLIC_URL", "https://example.com/#top"),
+                ("EMPTY", ""),
+            ]
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+        );
+        assert_eq!(parse("\nPUBLIC_ID").unwrap_err().0, 2);
+        assert_eq!(parse("1ID=x").unwrap_err().0, 1);
+        assert_eq!(parse("ID=\"x").unwrap_err().0, 1);
+    }
+
+    #[test]
+    fn only_the_public_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join(".env");
+        let package = dir.path().join("package.env");
+        fs::write(
+            &workspace,
+            "PUBLIC_A=workspace\nPUBLIC_B=workspace\nSECRET=x\n",
+        )
+        .unwrap();
+        fs::write(&package, "PUBLIC_B=package\n").unwrap();
+
+        let files = [workspace, package, dir.path().join("missing.env")];
+        let env = public_env(&files, "PUBLIC_").unwrap();
+        assert_eq!(env["PUBLIC_A"], "workspace");
+        assert_eq!(env["PUBLIC_B"], "package");
+        assert!(!env.contains_key("SECRET"));
+    }
+
+    #[test]
+    fn check_the_variables_of_the_templates() {
+        let env: BTreeMap<_, _> = [("PUBLIC_ID".to_string(), "1".to_string())].into();
+        let check = |template: &str| check_template(template, "index.html", &env, "PUBLIC_");
+
+        assert!(check("<p>{{ env.PUBLIC_ID }}</p>{% if env.PUBLIC_ID %}{% endif %}").is_ok());
+        assert!(check("{{ env.PUBLIC_MISSING | default(value='') }} {{ nenv.X }} env.X").is_ok());
+        assert_eq!(
+            check("<html>\n  <p>{{ env.PUBLIC_MISSING }}</p>\n{{env.DATABASE_URL}}")
+                .unwrap_err()
+                .to_string(),
+            "index.html:2:9: the environment variable `PUBLIC_MISSING` is not defined\n\
+            index.html:3:3: the environment variable `DATABASE_URL` can't be used in the pages: \
+            only the variables that start with `PUBLIC_` are public"
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! The error returned by the commands and the build pipeline.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+/// The error returned by the commands (like [`crate::BuildArgs::run`]) and by [`crate::Runner`].
+///
+/// The hooks return an [`anyhow::Error`] that is given as [`Error::Hook`]. An [`Error`] converted to
+/// an [`anyhow::Error`] (by `?` in a hook for example) keeps its kind when it is converted back,
+/// even if a context has been added.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The build process of cargo did not exit successfully. Its output is shown in the terminal.
+    CargoBuildFailed {
+        /// Exit status of cargo.
+        status: ExitStatus,
+    },
+    /// The test harness did not exit successfully: a test failed. Its output is shown in the
+    /// terminal. The `main` of the macro exits with the code of the harness.
+    TestsFailed {
+        /// Exit status of the test harness.
+        status: ExitStatus,
+    },
+    /// The program ran by the `run` command did not exit successfully. Its output is shown in the
+    /// terminal. The `main` of the macro exits with the code of the program.
+    RunFailed {
+        /// The program that ran the build (the runtime of WASI or `node`).
+        program: String,
+        /// Exit status of the program.
+        status: ExitStatus,
+    },
+    /// The command of [`crate::BuildArgs::bundle_command`] did not exit successfully. Its output
+    /// is shown in the terminal.
+    BundleFailed {
+        /// The command given to the shell.
+        command: String,
+        /// Exit status of the command.
+        status: ExitStatus,
+    },
+    /// The rebuild of the watcher waited for a lock held by another cargo command and files changed
+    /// in the meantime. It is restarted with the new changes.
+    BuildCancelled,
+    /// The WASM could not be found after the build.
+    ArtifactNotFound {
+        /// The paths where the WASM has been searched: the artifacts reported by cargo, then the
+        /// target directory.
+        searched: Vec<PathBuf>,
+    },
+    /// `wasm-bindgen` could not generate the JS bindings.
+    BindgenFailed(anyhow::Error),
+    /// `wasm-opt` could not optimize the WASM.
+    WasmOptFailed(anyhow::Error),
+    /// `wasm-opt` is not available for a release build: it could not be downloaded or started, or
+    /// no optimization feature is enabled. See [`crate::BuildArgs::allow_unoptimized`].
+    WasmOptUnavailable(anyhow::Error),
+    /// The development server could not listen on its address because it is already used.
+    AddrInUse {
+        /// The address of the server.
+        addr: String,
+        /// The name and the PID of the process that listens on the port, if it could be found
+        /// (only on Linux).
+        holder: Option<String>,
+    },
+    /// The development server could not listen on its address for another reason: a privileged
+    /// port, an IP address that is not on this machine, ...
+    AddrNotAvailable {
+        /// The address of the server.
+        addr: String,
+        /// The error of the operation.
+        source: io::Error,
+    },
+    /// A hook failed.
+    Hook {
+        /// Name of the hook (`pre_build`, `post_build`, ...).
+        hook: &'static str,
+        /// The error returned by the hook.
+        source: anyhow::Error,
+    },
+    /// A file or a directory of the build could not be written.
+    Io {
+        /// Path of the file or directory.
+        path: PathBuf,
+        /// The error of the operation.
+        source: io::Error,
+    },
+    /// The build directory could not be created or a file could not be written in it, checked
+    /// before the build starts: a read-only file system, a permission denied, a full disk, ...
+    BuildPathNotWritable {
+        /// Path of the build directory.
+        path: PathBuf,
+        /// The error of the operation.
+        source: io::Error,
+    },
+    /// The binary is not ran inside the workspace it was built for: the manifest of its crate does
+    /// not exist. The `main` of the macro exits with [`OUTSIDE_WORKSPACE_EXIT_CODE`].
+    OutsideWorkspace {
+        /// The manifest that was looked for.
+        manifest_path: PathBuf,
+        /// The manifest was given by the environment variable `WASMBL_MANIFEST_PATH`.
+        from_env: bool,
+        /// The current directory of the process.
+        current_dir: Option<PathBuf>,
+    },
+    /// A command ran before [`crate::Runner::init`] installed the hooks (the `main` of the macro
+    /// calls it before the commands).
+    NotInitialized,
+    /// Any other error.
+    Other(anyhow::Error),
+}
+
+/// The exit code of the binaries of the macro when they are not ran inside their workspace (see
+/// [`Error::OutsideWorkspace`]), like `EX_CONFIG` of `sysexits.h`.
+pub const OUTSIDE_WORKSPACE_EXIT_CODE: i32 = 78;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CargoBuildFailed { status } => match status.code() {
+                Some(code) => write!(f, "build process exit with code {}", code),
+                None => write!(f, "build process has been terminated by a signal"),
+            },
+            Self::TestsFailed { status } => match status.code() {
+                Some(code) => write!(f, "the tests failed with code {}", code),
+                None => write!(f, "the tests have been terminated by a signal"),
+            },
+            Self::RunFailed { program, status } => match status.code() {
+                Some(code) => write!(f, "`{}` exit with code {}", program, code),
+                None => write!(f, "`{}` has been terminated by a signal", program),
+            },
+            Self::BundleFailed { command, status } => match status.code() {
+                Some(code) => write!(
+                    f,
+                    "the bundle command `{}` exit with code {}",
+                    command, code
+                ),
+                None => write!(
+                    f,
+                    "the bundle command `{}` has been terminated by a signal",
+                    command
+                ),
+            },
+            Self::BuildCancelled => write!(
+                f,
+                "the build has been cancelled: files changed while waiting for another cargo \
+                command"
+            ),
+            Self::ArtifactNotFound { searched } => {
+                write!(f, "could not find the WASM, searched:")?;
+                for path in searched {
+                    write!(f, "\n  {}", path.display())?;
+                }
+                Ok(())
+            }
+            Self::BindgenFailed(_) => write!(f, "could not generate WASM bindgen file"),
+            Self::WasmOptFailed(_) => write!(f, "could not optimize the WASM"),
+            Self::WasmOptUnavailable(_) => write!(
+                f,
+                "could not optimize the WASM of the release build: `wasm-opt` is not available \
+                (pass `--allow-unoptimized` to build it anyway)"
+            ),
+            Self::AddrInUse { addr, holder } => {
+                write!(f, "the address `{}` is already in use", addr)?;
+                if let Some(holder) = holder {
+                    write!(f, " by {}", holder)?;
+                }
+                write!(f, ": choose another port with `--port`")
+            }
+            Self::AddrNotAvailable { addr, source }
+                if source.kind() == io::ErrorKind::PermissionDenied =>
+            {
+                write!(f, "permission denied to listen on `{}`", addr)?;
+                let port = addr.rsplit(':').next().and_then(|x| x.parse::<u16>().ok());
+                if port.map(|x| x < 1024).unwrap_or(false) {
+                    write!(
+                        f,
+                        ": the ports below 1024 are privileged, choose a higher port with `--port`"
+                    )?;
+                }
+                Ok(())
+            }
+            Self::AddrNotAvailable { addr, .. } => write!(f, "could not listen on `{}`", addr),
+            Self::Hook { hook, .. } => write!(f, "the `{}` hook failed", hook),
+            Self::Io { path, .. } => write!(f, "could not write `{}`", path.display()),
+            Self::BuildPathNotWritable { path, source } => {
+                write!(f, "the build path `{}` is not writable", path.display())?;
+                let reason = match source.kind() {
+                    io::ErrorKind::ReadOnlyFilesystem => Some("it is on a read-only file system"),
+                    io::ErrorKind::PermissionDenied => Some("the permission is denied"),
+                    io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => {
+                        Some("there is no space left on its device")
+                    }
+                    _ => None,
+                };
+                if let Some(reason) = reason {
+                    write!(f, ", {}", reason)?;
+                }
+                write!(f, " (give a writable directory with `--build-path`)")
+            }
+            Self::OutsideWorkspace {
+                manifest_path,
+                from_env,
+                current_dir,
+            } => {
+                if *from_env {
+                    write!(
+                        f,
+                        "the manifest `{}` of the environment variable `WASMBL_MANIFEST_PATH` does \
+                        not exist",
+                        manifest_path.display()
+                    )?;
+                } else {
+                    write!(
+                        f,
+                        "this binary must run inside the workspace it was built for: the manifest \
+                        `{}` does not exist",
+                        manifest_path.display()
+                    )?;
+                }
+                if let Some(dir) = current_dir {
+                    write!(f, " (the current directory is `{}`)", dir.display())?;
+                }
+                write!(
+                    f,
+                    ". Run it inside the workspace or set the environment variable \
+                    `WASMBL_MANIFEST_PATH` to the `Cargo.toml` of the crate of the binary"
+                )
+            }
+            Self::NotInitialized => write!(
+                f,
+                "the runner has not been initialized: `Runner::init()` must be called before the \
+                commands"
+            ),
+            Self::Other(err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::BindgenFailed(source)
+            | Self::WasmOptFailed(source)
+            | Self::WasmOptUnavailable(source)
+            | Self::Hook { source, .. } => Some(source.as_ref()),
+            Self::Io { source, .. }
+            | Self::BuildPathNotWritable { source, .. }
+            | Self::AddrNotAvailable { source, .. } => Some(source),
+            // NOTE: the message of the error is already displayed by `Display`
+            Self::Other(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<Self>() {
+            Ok(err) => err,
+            Err(err) => Self::Other(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn keep_kind_through_anyhow() {
+        let err = anyhow::Error::from(Error::ArtifactNotFound {
+            searched: vec![PathBuf::from("app.wasm")],
+        });
+        let err: Error = Err::<(), _>(err)
+            .context("could not build")
+            .unwrap_err()
+            .into();
+        assert!(matches!(err, Error::ArtifactNotFound { .. }));
+
+        let err: Error = anyhow::anyhow!("missing backend crate name").into();
+        assert!(matches!(err, Error::Other(_)));
+        assert_eq!(err.to_string(), "missing backend crate name");
+
+        let err = Error::Hook {
+            hook: "pre_build",
+            source: anyhow::anyhow!("no network"),
+        };
+        assert_eq!(
+            format!("{:#}", anyhow::Error::from(err)),
+            "the `pre_build` hook failed: no network"
+        );
+    }
+
+    #[test]
+    fn cargo_build_failed() {
+        let err = crate::run_cargo(
+            std::process::Command::new("cargo").args([
+                "build",
+                "--quiet",
+                "--manifest-path",
+                "/nonexistent/Cargo.toml",
+            ]),
+            &crate::ConsoleReporter,
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, Error::CargoBuildFailed { .. }));
+        assert_eq!(err.to_string(), "build process exit with code 101");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tests_failed() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let err = Error::TestsFailed {
+            status: ExitStatus::from_raw(1 << 8),
+        };
+        assert_eq!(err.to_string(), "the tests failed with code 1");
+    }
+
+    #[test]
+    fn build_path_not_writable() {
+        let err = Error::BuildPathNotWritable {
+            path: PathBuf::from("/src/build"),
+            source: io::Error::from(io::ErrorKind::ReadOnlyFilesystem),
+        };
+        assert_eq!(
+            format!("{:#}", anyhow::Error::from(err)),
+            "the build path `/src/build` is not writable, it is on a read-only file system (give \
+            a writable directory with `--build-path`): read-only filesystem or storage medium"
+        );
+
+        let err = Error::BuildPathNotWritable {
+            path: PathBuf::from("/tmp/build"),
+            source: io::Error::from(io::ErrorKind::StorageFull),
+        };
+        assert!(err
+            .to_string()
+            .contains("not writable, there is no space left on its device"));
+    }
+
+    #[test]
+    fn outside_workspace() {
+        let err = Error::OutsideWorkspace {
+            manifest_path: PathBuf::from("/src/app/Cargo.toml"),
+            from_env: false,
+            current_dir: Some(PathBuf::from("/srv")),
+        };
+        assert_eq!(
+            err.to_string(),
+            "this binary must run inside the workspace it was built for: the manifest \
+            `/src/app/Cargo.toml` does not exist (the current directory is `/srv`). Run it inside \
+            the workspace or set the environment variable `WASMBL_MANIFEST_PATH` to the \
+            `Cargo.toml` of the crate of the binary"
+        );
+
+        let err = Error::OutsideWorkspace {
+            manifest_path: PathBuf::from("/app/Cargo.toml"),
+            from_env: true,
+            current_dir: None,
+        };
+        assert!(err.to_string().starts_with(
+            "the manifest `/app/Cargo.toml` of the environment variable `WASMBL_MANIFEST_PATH` \
+            does not exist. Run"
+        ));
+    }
+}
@@ -0,0 +1,194 @@
+//! Support for the different signatures of the `pre_build` hook in the code generated by the
+//! macro. This is not part of the public API.
+//!
+//! The `pre_build` hook receives the metadata of the workspace and the frontend package since
+//! they have been added to its signature. The hooks with the previous signature (without them)
+//! are deprecated but still supported through [`DeprecatedPreBuildHook`]: the generated code
+//! imports both traits and calls `call_hook` as a method, the implementation is picked from the
+//! arguments of the function and the deprecation warning is reported in the crate of the hook.
+//!
+//! The hooks that also take the [`BuildTrigger`] after the package get it, the others are still
+//! supported without it.
+//!
+//! The lifetime is a parameter of the trait so the future returned by an `async fn` hook can
+//! borrow the arguments.
+//!
+//! The `post_build` hook receives a [`BuildOutput`] instead of the profile, the JS and the WASM.
+//! The hooks with the previous signature are called through [`DeprecatedPostBuildHook`]: the
+//! generated code imports both traits and calls `call_post_build` as a method, the only trait
+//! implemented by the hook is picked and the deprecation warning is reported in the crate of the
+//! hook.
+//!
+//! The hooks receive the arguments as the type of their first parameter, through [`FromArgs`]:
+//! the trait object itself (`&dyn BuildArgs`) for the hooks shared by commands with different
+//! types of arguments (the `build_args()` of a custom `ServeArgs` can return another type than the
+//! `Build` command), or a concrete type that the arguments are downcast to.
+
+use crate::{BuildArgs, BuildOutput, BuildProfile, BuildTrigger, ServeArgs, TestArgs};
+use cargo_metadata::{Metadata, Package};
+use std::process::Command;
+
+/// The marker of the `pre_build` hooks that receive the metadata and the trigger of the build.
+pub struct WithTrigger;
+
+/// The marker of the `pre_build` hooks that receive the metadata.
+pub struct WithMetadata;
+
+/// The arguments of a command given to a hook as the type of its first parameter: `&'a D` or a
+/// reference to a type implementing the trait `D`.
+pub trait FromArgs<'a, D: ?Sized> {
+    fn from_args(args: &'a D) -> Self;
+}
+
+macro_rules! impl_from_args {
+    ($($trait:ident),*) => {$(
+        impl<'a> FromArgs<'a, dyn $trait> for &'a dyn $trait {
+            fn from_args(args: &'a dyn $trait) -> Self {
+                args
+            }
+        }
+
+        impl<'a, T: $trait> FromArgs<'a, dyn $trait> for &'a T {
+            fn from_args(args: &'a dyn $trait) -> Self {
+                args.downcast_ref::<T>().unwrap_or_else(|| {
+                    panic!(
+                        "the hook expects the arguments of type `{}`, the command has others: the \
+                        hook can take `&dyn {}` to receive the arguments of all the commands",
+                        std::any::type_name::<T>(),
+                        stringify!($trait),
+                    )
+                })
+            }
+        }
+    )*};
+}
+
+impl_from_args!(BuildArgs, ServeArgs, TestArgs);
+
+pub trait PreBuildHook<'a, A: ?Sized + 'a, Marker> {
+    type Output;
+
+    fn call_hook(
+        &self,
+        args: &'a A,
+        profile: BuildProfile,
+        command: &'a mut Command,
+        metadata: &'a Metadata,
+        package: &'a Package,
+        trigger: BuildTrigger,
+    ) -> Self::Output;
+}
+
+impl<'a, A: ?Sized + 'a, F, R> PreBuildHook<'a, A, WithTrigger> for F
+where
+    F: Fn(&'a A, BuildProfile, &'a mut Command, &'a Metadata, &'a Package, BuildTrigger) -> R,
+{
+    type Output = R;
+
+    fn call_hook(
+        &self,
+        args: &'a A,
+        profile: BuildProfile,
+        command: &'a mut Command,
+        metadata: &'a Metadata,
+        package: &'a Package,
+        trigger: BuildTrigger,
+    ) -> R {
+        self(args, profile, command, metadata, package, trigger)
+    }
+}
+
+impl<'a, A: ?Sized + 'a, F, R> PreBuildHook<'a, A, WithMetadata> for F
+where
+    F: Fn(&'a A, BuildProfile, &'a mut Command, &'a Metadata, &'a Package) -> R,
+{
+    type Output = R;
+
+    fn call_hook(
+        &self,
+        args: &'a A,
+        profile: BuildProfile,
+        command: &'a mut Command,
+        metadata: &'a Metadata,
+        package: &'a Package,
+        _trigger: BuildTrigger,
+    ) -> R {
+        self(args, profile, command, metadata, package)
+    }
+}
+
+pub trait DeprecatedPreBuildHook<'a, A: ?Sized + 'a> {
+    type Output;
+
+    #[deprecated(
+        note = "the `pre_build` hook receives the metadata and the frontend package: \
+            `fn pre_build(args: &A, profile: BuildProfile, command: &mut Command, \
+            metadata: &Metadata, package: &Package) -> Result<()>`"
+    )]
+    fn call_hook(
+        &self,
+        args: &'a A,
+        profile: BuildProfile,
+        command: &'a mut Command,
+        metadata: &'a Metadata,
+        package: &'a Package,
+        trigger: BuildTrigger,
+    ) -> Self::Output;
+}
+
+impl<'a, A: ?Sized + 'a, F, R> DeprecatedPreBuildHook<'a, A> for F
+where
+    F: Fn(&'a A, BuildProfile, &'a mut Command) -> R,
+{
+    type Output = R;
+
+    fn call_hook(
+        &self,
+        args: &'a A,
+        profile: BuildProfile,
+        command: &'a mut Command,
+        _metadata: &'a Metadata,
+        _package: &'a Package,
+        _trigger: BuildTrigger,
+    ) -> R {
+        self(args, profile, command)
+    }
+}
+
+pub trait PostBuildHook<'a, A: ?Sized + 'a> {
+    type Output;
+
+    fn call_post_build(&self, args: &'a A, output: &'a BuildOutput) -> Self::Output;
+}
+
+impl<'a, A: ?Sized + 'a, F, R> PostBuildHook<'a, A> for F
+where
+    F: Fn(&'a A, &'a BuildOutput) -> R,
+{
+    type Output = R;
+
+    fn call_post_build(&self, args: &'a A, output: &'a BuildOutput) -> R {
+        self(args, output)
+    }
+}
+
+pub trait DeprecatedPostBuildHook<'a, A: ?Sized + 'a> {
+    type Output;
+
+    #[deprecated(
+        note = "the `post_build` hook receives `&BuildOutput` instead of the profile, the JS and \
+            the WASM: `fn post_build(args: &A, output: &BuildOutput) -> Result<()>`"
+    )]
+    fn call_post_build(&self, args: &'a A, output: &'a BuildOutput) -> Self::Output;
+}
+
+impl<'a, A: ?Sized + 'a, F, R> DeprecatedPostBuildHook<'a, A> for F
+where
+    F: Fn(&'a A, BuildProfile, String, Vec<u8>) -> R,
+{
+    type Output = R;
+
+    fn call_post_build(&self, args: &'a A, output: &'a BuildOutput) -> R {
+        self(args, output.profile, output.js.clone(), output.wasm.clone())
+    }
+}
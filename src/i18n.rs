@@ -0,0 +1,615 @@
+//! The localization files of Fluent (`.ftl`) copied to the build directory by the default
+//! `post_build` hook, configured with the `[package.metadata.wasmbl.i18n]` table of the frontend
+//! package.
+//!
+//! The files are checked before they are copied: a malformed message fails the build. They are
+//! given to the [`crate::Hooks::asset_filter`] hook like the other files of the build and listed
+//! with their SHA-256 in `locales/manifest.json`, so the app loads the files of the same build.
+
+use crate::{Asset, Hooks};
+use anyhow::{bail, Context, Result};
+use cargo_metadata::Package;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest of the locales, in the directory `locales` of the build directory.
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// The configuration of the localization files, see [`crate::BuildArgs::i18n`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct I18n {
+    /// Directory of the locales: a directory by locale (`en`, `fr`, ...) that contains its `.ftl`
+    /// files.
+    pub locales_dir: PathBuf,
+    /// The locale the others are compared to by [`crate::BuildArgs::check_i18n`].
+    pub default_locale: String,
+}
+
+impl I18n {
+    /// The locales of this directory, compared to `default_locale`.
+    pub fn new(locales_dir: impl Into<PathBuf>, default_locale: impl Into<String>) -> Self {
+        Self {
+            locales_dir: locales_dir.into(),
+            default_locale: default_locale.into(),
+        }
+    }
+
+    /// The configuration of the `[package.metadata.wasmbl.i18n]` table of a package: the keys
+    /// `locales` (relative to the package, `locales` by default) and `default-locale` (`en` by
+    /// default).
+    pub(crate) fn from_metadata(package: &Package) -> Option<Self> {
+        let table = package.metadata.get("wasmbl")?.get("i18n")?;
+        let string = |key: &str| table.get(key).and_then(|x| x.as_str());
+
+        Some(Self::new(
+            package
+                .manifest_path
+                .parent()
+                .unwrap()
+                .join(string("locales").unwrap_or("locales")),
+            string("default-locale").unwrap_or("en"),
+        ))
+    }
+}
+
+/// An error of syntax in a Fluent file.
+#[derive(Debug, PartialEq, Eq)]
+struct SyntaxError {
+    line: usize,
+    message: String,
+}
+
+/// The localization files of a locale: their paths relative to its directory and their contents.
+type Locale = Vec<(PathBuf, String)>;
+
+/// Check and copy the localization files to the build directory, then write their manifest.
+pub(crate) fn build(i18n: &I18n, build_path: &Path, hooks: &Hooks, check: bool) -> Result<()> {
+    let locales = read_locales(&i18n.locales_dir)?;
+    if !locales.contains_key(&i18n.default_locale) {
+        bail!(
+            "the default locale `{}` is not in `{}`",
+            i18n.default_locale,
+            i18n.locales_dir.display()
+        );
+    }
+
+    let mut errors = Vec::new();
+    let mut messages = BTreeMap::new();
+    for (locale, files) in locales.iter() {
+        let mut ids = BTreeSet::new();
+        for (path, source) in files {
+            match parse(source) {
+                Ok(x) => ids.extend(x),
+                Err(x) => errors.extend(x.into_iter().map(|err| {
+                    format!(
+                        "{}:{}: {}",
+                        i18n.locales_dir.join(locale).join(path).display(),
+                        err.line,
+                        err.message
+                    )
+                })),
+            }
+        }
+        messages.insert(locale.as_str(), ids);
+    }
+    if !errors.is_empty() {
+        bail!("malformed Fluent files:\n{}", errors.join("\n"));
+    }
+
+    if check {
+        let errors = compare_locales(&messages, &i18n.default_locale);
+        if !errors.is_empty() {
+            bail!(
+                "the locales do not define the same messages as `{}`:\n{}",
+                i18n.default_locale,
+                errors.join("\n")
+            );
+        }
+    }
+
+    let mut manifest = serde_json::Map::new();
+    for (locale, files) in locales {
+        let mut entries = Vec::new();
+        for (path, source) in files {
+            let path = Path::new("locales").join(&locale).join(path);
+            let name = crate::slash_path(&path);
+            let sha256 = format!("{:x}", Sha256::digest(source.as_bytes()));
+            if let Some(written) =
+                crate::write_asset(build_path, hooks, Asset::new(path, &name, source.into()))?
+            {
+                let written = written.strip_prefix(build_path).unwrap_or(&written);
+                entries.push(serde_json::json!({
+                    "name": name,
+                    "path": crate::slash_path(written),
+                    "sha256": sha256,
+                }));
+            }
+        }
+        manifest.insert(locale, entries.into());
+    }
+    let manifest = serde_json::json!({
+        "default_locale": i18n.default_locale,
+        "locales": manifest,
+    });
+    let path = Path::new("locales").join(MANIFEST_FILE);
+    let name = crate::slash_path(&path);
+    let mut content = serde_json::to_string_pretty(&manifest).expect("valid JSON; qed");
+    content.push('\n');
+    crate::write_asset(build_path, hooks, Asset::new(path, name, content.into()))?;
+
+    Ok(())
+}
+
+/// The `.ftl` files of every locale, by locale.
+fn read_locales(locales_dir: &Path) -> Result<BTreeMap<String, Locale>> {
+    let entries = fs::read_dir(locales_dir)
+        .with_context(|| format!("could not read `{}`", locales_dir.display()))?;
+
+    let mut locales = BTreeMap::new();
+    for entry in entries {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let locale = path
+            .file_name()
+            .expect("an entry of a directory; qed")
+            .to_string_lossy()
+            .into_owned();
+
+        let mut files = Vec::new();
+        for file in crate::list_files(&path)
+            .with_context(|| format!("could not list the files of `{}`", path.display()))?
+        {
+            if file.extension().map(|x| x != "ftl").unwrap_or(true) {
+                continue;
+            }
+            let source = fs::read_to_string(&file)
+                .with_context(|| format!("could not read `{}`", file.display()))?;
+            let relative = file
+                .strip_prefix(&path)
+                .expect("the file is in the directory of the locale; qed")
+                .to_owned();
+            files.push((relative, source));
+        }
+        locales.insert(locale, files);
+    }
+
+    Ok(locales)
+}
+
+/// The messages missing or extra in the locales, compared to the default locale.
+fn compare_locales(messages: &BTreeMap<&str, BTreeSet<String>>, default: &str) -> Vec<String> {
+    let expected = &messages[default];
+    let list = |ids: Vec<&String>| {
+        ids.iter()
+            .map(|x| format!("`{}`", x))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut errors = Vec::new();
+    for (locale, ids) in messages.iter().filter(|(x, _)| **x != default) {
+        let missing: Vec<_> = expected.difference(ids).collect();
+        if !missing.is_empty() {
+            errors.push(format!("`{}` is missing {}", locale, list(missing)));
+        }
+        let extra: Vec<_> = ids.difference(expected).collect();
+        if !extra.is_empty() {
+            errors.push(format!(
+                "`{}` has the extra messages {}",
+                locale,
+                list(extra)
+            ));
+        }
+    }
+    errors
+}
+
+/// An entry being parsed: a message or a term and its attributes.
+struct Entry {
+    id: String,
+    line: usize,
+    has_value: bool,
+    /// The attributes: their names, their lines and if they have a value.
+    attributes: Vec<(String, usize, bool)>,
+    /// The open placeables: if they are select expressions and their number of default variants.
+    placeables: Vec<(bool, usize)>,
+}
+
+impl Entry {
+    fn new(id: &str, line: usize) -> Self {
+        Self {
+            id: id.to_string(),
+            line,
+            has_value: false,
+            attributes: Vec::new(),
+            placeables: Vec::new(),
+        }
+    }
+
+    /// The errors of the entry once all its lines are parsed.
+    fn finish(self, errors: &mut Vec<SyntaxError>) -> Option<String> {
+        let error = |line, message| SyntaxError { line, message };
+        let len = errors.len();
+        if !self.placeables.is_empty() {
+            errors.push(error(
+                self.line,
+                format!("`{}` has a placeable that is not closed", self.id),
+            ));
+        }
+        for (name, line, _) in self.attributes.iter().filter(|x| !x.2) {
+            errors.push(error(
+                *line,
+                format!("the attribute `.{}` of `{}` has no value", name, self.id),
+            ));
+        }
+        if self.id.starts_with('-') {
+            if !self.has_value {
+                errors.push(error(
+                    self.line,
+                    format!("the term `{}` has no value", self.id),
+                ));
+            }
+            return None;
+        }
+        if !self.has_value && self.attributes.is_empty() {
+            errors.push(error(
+                self.line,
+                format!("the message `{}` has no value", self.id),
+            ));
+        }
+
+        (errors.len() == len).then_some(self.id)
+    }
+}
+
+/// Check the syntax of a Fluent file and returns the identifiers of its messages.
+///
+/// This is the syntax of Fluent 1.0 for the entries, the comments, the attributes, the placeables
+/// and the select expressions. The expressions inside the placeables are not checked.
+fn parse(source: &str) -> Result<Vec<String>, Vec<SyntaxError>> {
+    let mut messages = Vec::new();
+    let mut errors = Vec::new();
+    let mut entry: Option<Entry> = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // NOTE: the end of a placeable can start the line
+        let in_placeable = entry.as_ref().map(|x| !x.placeables.is_empty()) == Some(true);
+        let res = if !line.starts_with([' ', '\t']) && !(in_placeable && line.starts_with('}')) {
+            messages.extend(entry.take().and_then(|x| x.finish(&mut errors)));
+            parse_entry(line, line_number).map(|new| entry = new)
+        } else {
+            match entry.as_mut() {
+                Some(entry) => parse_continuation(entry, line.trim_start(), line_number),
+                None => Err("an indented line must continue a message or a term".into()),
+            }
+        };
+        if let Err(message) = res {
+            errors.push(SyntaxError {
+                line: line_number,
+                message,
+            });
+        }
+    }
+    messages.extend(entry.take().and_then(|x| x.finish(&mut errors)));
+
+    if errors.is_empty() {
+        Ok(messages)
+    } else {
+        errors.sort_by_key(|x| x.line);
+        Err(errors)
+    }
+}
+
+/// Parse a line that is not indented: a comment or the start of an entry.
+fn parse_entry(line: &str, line_number: usize) -> Result<Option<Entry>, String> {
+    if line.starts_with('#') {
+        let level = line.len() - line.trim_start_matches('#').len();
+        if level > 3 || !(line.len() == level || line[level..].starts_with(' ')) {
+            return Err("a comment starts with `#`, `##` or `###` and a space".into());
+        }
+        return Ok(None);
+    }
+
+    let (id, value) = split_definition(line)
+        .ok_or("expected a message (`id = ...`), a term (`-id = ...`) or a comment")?;
+    let mut entry = Entry::new(id, line_number);
+    scan(&mut entry, value)?;
+
+    Ok(Some(entry))
+}
+
+/// Parse an indented line of an entry: an attribute or the continuation of a pattern.
+fn parse_continuation(entry: &mut Entry, text: &str, line_number: usize) -> Result<(), String> {
+    if !entry.placeables.is_empty() {
+        return scan(entry, text);
+    }
+
+    if let Some(attribute) = text.strip_prefix('.') {
+        match split_definition(attribute) {
+            Some((name, value)) if !name.starts_with('-') => {
+                entry
+                    .attributes
+                    .push((name.to_string(), line_number, false));
+                scan(entry, value)
+            }
+            _ => Err("expected an attribute (`.name = ...`)".into()),
+        }
+    } else if text.starts_with(['[', '*']) {
+        Err("a variant (`[key]` or `*[key]`) must be in a select expression".into())
+    } else {
+        scan(entry, text)
+    }
+}
+
+/// The identifier and the rest of the line of a definition (`id = value`), `None` if the line is
+/// not a definition.
+fn split_definition(line: &str) -> Option<(&str, &str)> {
+    let (id, value) = line.split_once('=')?;
+    let id = id.trim_end();
+    let name = id.strip_prefix('-').unwrap_or(id);
+    let mut chars = name.chars();
+    if !chars.next()?.is_ascii_alphabetic()
+        || !chars.all(|x| x.is_ascii_alphanumeric() || x == '_' || x == '-')
+    {
+        return None;
+    }
+
+    Some((id, value))
+}
+
+/// Scan a part of the pattern of an entry for the placeables and the variants of the select
+/// expressions.
+fn scan(entry: &mut Entry, text: &str) -> Result<(), String> {
+    let is_text = |entry: &Entry| entry.placeables.is_empty();
+    let mut chars = text.char_indices().peekable();
+    let mut has_text = is_text(entry) && !text.trim().is_empty();
+
+    // NOTE: a variant key starts the line inside a select expression
+    if let Some((true, defaults)) = entry.placeables.last_mut() {
+        let trimmed = text.trim_start();
+        if let Some(rest) = trimmed.strip_prefix('*') {
+            if !rest.starts_with('[') {
+                return Err("expected a default variant (`*[key]`)".into());
+            }
+            *defaults += 1;
+        }
+    }
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '{' => entry.placeables.push((false, 0)),
+            '}' => match entry.placeables.pop() {
+                Some((true, defaults)) if defaults != 1 => {
+                    return Err(
+                        "a select expression must have exactly one default variant (`*[key]`)"
+                            .into(),
+                    )
+                }
+                Some(_) => has_text = true,
+                None => return Err("`}` does not close a placeable".into()),
+            },
+            '"' if !is_text(entry) => loop {
+                match chars.next() {
+                    Some((_, '\\')) => {
+                        chars.next();
+                    }
+                    Some((_, '"')) => break,
+                    Some(_) => {}
+                    None => return Err("a string literal is not closed".into()),
+                }
+            },
+            '-' if !is_text(entry) && text[i..].starts_with("->") => {
+                chars.next();
+                match entry.placeables.last_mut() {
+                    Some((select @ false, _)) => *select = true,
+                    _ => return Err("a placeable has several selectors (`->`)".into()),
+                }
+            }
+            '[' if !is_text(entry) && entry.placeables.last().map(|x| x.0) == Some(true) => {
+                // NOTE: the value of a variant is text, until the next variant or the end of the
+                //       select expression
+                if !text[i..].contains(']') {
+                    return Err("a variant key is not closed (`]`)".into());
+                }
+                for (_, c) in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if has_text {
+        match entry.attributes.last_mut() {
+            Some((_, _, has_value)) => *has_value = true,
+            None => entry.has_value = true,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(source: &str) -> Vec<(usize, String)> {
+        parse(source)
+            .unwrap_err()
+            .into_iter()
+            .map(|x| (x.line, x.message))
+            .collect()
+    }
+
+    #[test]
+    fn parse_valid_files() {
+        let source = "\
+### Resource comment
+
+# Message comment
+hello = Hello, { $name }!
+-brand = Wasmbl
+    .gender = masculine
+welcome =
+    Welcome to { -brand }.
+    It is { \"{\" } here.
+emails = { $count ->
+        [one] You have one email.
+       *[other] You have { $count } emails.
+    }
+login-input =
+    .placeholder = email@example.com
+    .aria-label = Login input
+";
+        assert_eq!(
+            parse(source).unwrap(),
+            ["hello", "welcome", "emails", "login-input"]
+        );
+    }
+
+    #[test]
+    fn report_malformed_messages() {
+        assert_eq!(
+            errors("hello = { $name\nbye = Bye\n"),
+            [(1, "`hello` has a placeable that is not closed".to_string())]
+        );
+        assert_eq!(
+            errors("hello = Hello }\n"),
+            [(1, "`}` does not close a placeable".to_string())]
+        );
+        assert_eq!(
+            errors("empty =\n-term =\n    .attr = x\n"),
+            [
+                (1, "the message `empty` has no value".to_string()),
+                (2, "the term `-term` has no value".to_string()),
+            ]
+        );
+        assert_eq!(
+            errors("emails = { $count ->\n    [one] One\n    [other] Many\n}\n"),
+            [(
+                4,
+                "a select expression must have exactly one default variant (`*[key]`)".to_string()
+            )]
+        );
+        assert_eq!(
+            errors("hello world = Hi\n####\n    floating\nok = [not a variant]\n    [one] x\n"),
+            [
+                (
+                    1,
+                    "expected a message (`id = ...`), a term (`-id = ...`) or a comment"
+                        .to_string()
+                ),
+                (
+                    2,
+                    "a comment starts with `#`, `##` or `###` and a space".to_string()
+                ),
+                (
+                    3,
+                    "an indented line must continue a message or a term".to_string()
+                ),
+                (
+                    5,
+                    "a variant (`[key]` or `*[key]`) must be in a select expression".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            errors("login =\n    .placeholder =\n    .label = Login\n"),
+            [(
+                2,
+                "the attribute `.placeholder` of `login` has no value".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn compare_messages_of_locales() {
+        let messages: BTreeMap<_, _> = vec![
+            ("en", vec!["bye", "hello"]),
+            ("fr", vec!["hello", "merci"]),
+            ("nl", vec!["bye", "hello"]),
+        ]
+        .into_iter()
+        .map(|(locale, ids)| (locale, ids.into_iter().map(String::from).collect()))
+        .collect();
+
+        assert_eq!(
+            compare_locales(&messages, "en"),
+            [
+                "`fr` is missing `bye`",
+                "`fr` has the extra messages `merci`"
+            ]
+        );
+    }
+
+    #[test]
+    fn copy_locales_with_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let locales_dir = dir.path().join("locales");
+        let build_path = dir.path().join("build");
+        fs::create_dir_all(locales_dir.join("en")).unwrap();
+        fs::create_dir_all(locales_dir.join("fr")).unwrap();
+        fs::write(locales_dir.join("en").join("main.ftl"), "hello = Hello\n").unwrap();
+        fs::write(locales_dir.join("en").join("notes.txt"), "").unwrap();
+        fs::write(
+            locales_dir.join("fr").join("main.ftl"),
+            "bonjour = Bonjour\n",
+        )
+        .unwrap();
+        let i18n = I18n::new(&locales_dir, "en");
+        let hooks = Hooks::default();
+
+        let err = build(&i18n, &build_path, &hooks, true).unwrap_err();
+        assert!(err.to_string().contains("same messages as `en`"), "{}", err);
+        assert!(!build_path.exists());
+
+        build(&i18n, &build_path, &hooks, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(build_path.join("locales").join("fr").join("main.ftl")).unwrap(),
+            "bonjour = Bonjour\n"
+        );
+        assert!(!build_path
+            .join("locales")
+            .join("en")
+            .join("notes.txt")
+            .exists());
+        let manifest: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(build_path.join("locales").join(MANIFEST_FILE)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest["default_locale"], "en");
+        assert_eq!(
+            manifest["locales"]["en"],
+            serde_json::json!([{
+                "name": "locales/en/main.ftl",
+                "path": "locales/en/main.ftl",
+                "sha256": format!("{:x}", Sha256::digest(b"hello = Hello\n")),
+            }])
+        );
+
+        fs::write(
+            locales_dir.join("fr").join("main.ftl"),
+            "bonjour = { $name\n",
+        )
+        .unwrap();
+        let err = build(&i18n, &build_path, &hooks, false).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("main.ftl:1: `bonjour` has a placeable that is not closed"),
+            "{}",
+            err
+        );
+    }
+}
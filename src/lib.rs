@@ -47,6 +47,10 @@
 //!
 //! All the details about the hooks can be found on the macro [`main`].
 //!
+//! The macro generates the code that configures a [`Runner`]. It can be used directly if you
+//! prefer to configure the hooks in plain Rust, in an `xtask` for example. A crate that wraps
+//! wasmbl in its own binary can call [`run`] with the arguments of the command line.
+//!
 //! # Additional Information
 //!
 //!  *  You can use this library to build examples in the `examples/` directory of your project.
@@ -59,10 +63,19 @@
 //!     backend to also be recompiled when a file changes (otherwise only the frontend is
 //!     re-compiled). You will also need to specify `run_server` to the macro arguments to run your
 //!     backend.
-//!  *  You can add commands to the CLI by adding variants in the `enum`.
+//!  *  You can add commands to the CLI by adding variants in the `enum`. The stages of the build
+//!     (cargo, `wasm-bindgen`, `wasm-opt`, ...) and the server are available in [`stages`] for
+//!     the commands that run only some of them.
 //!  *  You can add parameters to the `Build` and `Serve` commands by overriding them. Please check
 //!     the documentation on the macro `main`.
-//!  *  If you run `cargo run -- serve --profiling`, the WASM will be optimized.
+//!  *  If you run `cargo run -- serve --release` or `cargo run -- serve --profiling`, the WASM
+//!     will be optimized.
+//!  *  The commands return an [`Error`] that tells what failed (cargo, `wasm-bindgen`, `wasm-opt`, a
+//!     hook, ...) if you run them from your own code. The hooks return an `anyhow::Error`.
+//!  *  The metadata of the workspace is read when the binary starts, from the manifest of the crate
+//!     that uses the macro. You can set the environment variable `WASMBL_MANIFEST_PATH` to the path
+//!     of another `Cargo.toml` if the binary is ran after being moved out of its workspace. If the
+//!     manifest does not exist, the binary exits with [`OUTSIDE_WORKSPACE_EXIT_CODE`].
 //!
 //! # Features
 //!
@@ -77,6 +90,23 @@
 //!     [`BuildArgs::build_sass_from_dir`], [`BuildArgs::sass_lookup_directories`],
 //!     [`BuildArgs::sass_options`] or completely overriden in the [`Hooks::post_build`] hook.
 //!     `sass-rs` is re-exported in the prelude of `wasmbl` for this purpose.
+//!  *  `clap`: support for `clap` v4 in addition to `structopt`. Use `parser = "clap"` in the
+//!     arguments of the macro [`main`] and derive `clap::Subcommand` on the `enum`.
+//!     [`DefaultBuildArgs`], [`DefaultServeArgs`], [`DefaultTestArgs`] and [`DefaultCleanArgs`]
+//!     implement `clap::Args` when this feature is enabled. Note that the short option `-h` of
+//!     `--ip` is not available with `clap` because it is reserved for the help.
+//!  *  `scaffold`: adds the command `new` that creates a new frontend package in the workspace
+//!     (`cargo run -- new my-frontend --template yew`) and adds it to the members of the
+//!     workspace. The templates are `minimal` (the default) and `yew`.
+//!  *  `deploy`: adds the command `deploy` that makes a release build and copies the build
+//!     directory to a local directory or to `user@host:/path` (with `rsync` or `scp`). The
+//!     destination is given on the command-line or with the key `destination` in the
+//!     `[package.metadata.wasmbl.deploy]` table of the frontend package. The copy can be replaced
+//!     with the [`Hooks::deploy`] hook (to upload the files somewhere else for example).
+//!  *  `archive`: `build --archive` writes the files of the build in a reproducible `.tar.gz` or
+//!     `.zip` (see [`BuildArgs::archive`]).
+//!  *  `desktop-notifications`: `serve --notify` sends a desktop notification when a rebuild
+//!     finishes (with `notify-rust`, which uses D-Bus on Linux).
 //!  *  `full-restart`: when this feature is active, the command is entirely restarted when changes
 //!     are detected when serving files for development (`cargo run -- serve`). This is useful with
 //!     custom `serve` command that uses a custom backend and if you need to detect changes in the
@@ -84,859 +114,5683 @@
 
 #![warn(missing_docs)]
 
+#[cfg(feature = "archive")]
+mod archive;
+#[doc(hidden)]
+pub mod async_hooks;
+mod build_files;
+mod build_meta;
+mod build_plan;
 /// Merge of web-bundler.
 ///
 /// TODO: This is the simple first iteration, we need to integrate properly.
 #[cfg(feature = "sass")]
 pub mod bundler;
+#[cfg(feature = "clap")]
+mod clap_args;
+mod clean;
+mod completions;
+mod config;
+#[cfg(feature = "deploy")]
+mod deploy;
+mod diagnostics;
+#[cfg(feature = "dev-server")]
+mod dir_listing;
+mod env_file;
+mod error;
+#[doc(hidden)]
+pub mod hook_args;
+mod i18n;
+mod live_reload;
+mod logger;
+mod node;
+mod notification;
+mod pages;
+mod pipeline;
+#[cfg(feature = "dev-server")]
+mod port;
 #[cfg(feature = "prebuilt-wasm-opt")]
 mod prebuilt_wasm_opt;
+#[cfg(feature = "prerender")]
+mod prerender;
+mod process;
+mod release;
+mod reporter;
+mod runner;
+mod rustflags;
+#[cfg(feature = "scaffold")]
+mod scaffold;
+mod server_config;
+mod size_report;
+#[cfg(feature = "dev-server")]
+mod sources;
+pub mod stages;
+mod static_cache;
+mod test_watch;
+mod trunk;
+mod version;
+mod wasi;
+mod watcher;
 
 use anyhow::{anyhow, bail, Context, Result};
-use cargo_metadata::{Metadata, MetadataCommand, Package};
+use cargo_metadata::{Message, Metadata, MetadataCommand, Package};
 use downcast_rs::*;
-use fs_extra::dir;
-use notify::RecommendedWatcher;
-use once_cell::sync::OnceCell;
-use std::collections::{HashMap, HashSet};
+use once_cell::sync::{Lazy, OnceCell};
+use percent_encoding::{AsciiSet, CONTROLS};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fs;
-use std::io::BufReader;
-use std::iter;
-use std::iter::FromIterator;
+use std::io::{self, BufReader};
+use std::net::{IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
 #[cfg(feature = "dev-server")]
 use std::pin::Pin;
-use std::process::{Child, ChildStdout, Command, Stdio};
-use std::sync::mpsc;
-use std::time;
+use std::process::{ChildStdout, Command, Stdio};
+use std::sync::{mpsc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
 #[cfg(feature = "dev-server")]
 use tide::Server;
 
+pub use clean::DefaultCleanArgs;
+pub use completions::{DefaultCompletionsArgs, Shell};
+pub use config::ConfigFormat;
+#[cfg(feature = "deploy")]
+pub use deploy::{DefaultDeployArgs, Destination};
+pub use error::{Error, OUTSIDE_WORKSPACE_EXIT_CODE};
+pub use i18n::I18n;
+#[doc(hidden)]
+pub use logger::exit_on_error;
+#[doc(hidden)]
+pub use logger::set_verbosity;
+pub use logger::{LogFormat, Verbosity};
+pub use node::DefaultRunNodeArgs;
+pub use pages::Page;
+pub use release::{Release, ReleaseArtifact};
+pub use runner::{run, Runner};
+pub use rustflags::append_rustflags;
+#[cfg(feature = "scaffold")]
+pub use scaffold::{DefaultNewArgs, Template};
+pub use server_config::ServerConfig;
+pub use size_report::{SizeFormat, SizeThreshold};
+pub use version::DefaultVersionArgs;
+pub use wasi::DefaultRunArgs;
 pub use wasmbl_proc_macro::*;
+pub use watcher::{FileWatcher, RebuildHandle, WatchConfig};
 
+#[doc(hidden)]
+#[cfg(feature = "clap")]
+pub use clap;
 #[doc(hidden)]
 pub use structopt;
 
-const DEFAULT_INDEX: &str = r#"<!DOCTYPE html><html><head><meta charset="utf-8"/><script type="module">import init from "/app.js";init(new URL('app_bg.wasm', import.meta.url));</script></head><body></body></html>"#;
+/// Default base name of the files generated by the build.
+const DEFAULT_OUT_NAME: &str = "app";
+
+/// The `index.html` written when there is no static directory, the JS bindings are imported from
+/// the base URL so it can be served for any path.
+fn default_index(out_name: &str, base_url: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html><html><head><meta charset="utf-8"/><script type="module">import init from "{1}{0}.js";init(new URL('{0}_bg.wasm', import.meta.url));</script></head><body></body></html>"#,
+        out_name, base_url
+    )
+}
 
-static METADATA: OnceCell<Metadata> = OnceCell::new();
-static DEFAULT_BUILD_PATH: OnceCell<PathBuf> = OnceCell::new();
-static FRONTEND_PACKAGE: OnceCell<&Package> = OnceCell::new();
-static BACKEND_PACKAGE: OnceCell<Option<&Package>> = OnceCell::new();
+static MANIFEST_PATH: OnceCell<PathBuf> = OnceCell::new();
+static PROJECT: OnceCell<RwLock<Project>> = OnceCell::new();
+static DEFAULT_BUILD_PATH: OnceCell<Option<DefaultPath>> = OnceCell::new();
+static DEFAULT_SERVE_PATH: OnceCell<Option<DefaultPath>> = OnceCell::new();
 static HOOKS: OnceCell<Hooks> = OnceCell::new();
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-/// A build profile for the WASM.
-pub enum BuildProfile {
-    /// Development profile (no `--release`, no optimization).
-    Dev,
-    /// Release profile (`--profile`, `-O2 -Os`).
-    Release,
-    /// Release profile (`--profile`, `-O2 --debuginfo`).
-    Profiling,
+/// The hooks installed by [`Runner::init`], for the commands.
+fn hooks() -> Result<&'static Hooks, Error> {
+    HOOKS.get().ok_or(Error::NotInitialized)
 }
 
-/// This function is called early before any command starts. This is not part of the public API.
-#[doc(hidden)]
-pub fn wasmbl_init(
-    pkg_name: &str,
-    backend_pkg_name: Option<&str>,
-    default_build_path: Option<Box<dyn FnOnce(&Metadata, &Package) -> PathBuf>>,
-    hooks: Hooks,
-) -> Result<(&'static Metadata, &'static Package)> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+/// A function of the macro that provides a default path for the frontend package.
+type DefaultPath = Box<dyn Fn(&Metadata, &Package) -> PathBuf + Send + Sync>;
 
-    let metadata = MetadataCommand::new()
-        .exec()
-        .context("this binary is not meant to be ran outside of its workspace")?;
+/// Delay between two attempts to reload the metadata after a manifest changed.
+const METADATA_RETRY_DELAY: Duration = Duration::from_millis(500);
 
-    METADATA
-        .set(metadata)
-        .expect("the cell is initially empty; qed");
+/// Number of attempts to reload the metadata after a manifest changed.
+const METADATA_RETRIES: usize = 5;
 
-    let metadata = METADATA.get().unwrap();
+/// The message printed by cargo when another cargo command holds a lock it needs.
+const CARGO_LOCK_MESSAGE: &str = "waiting for file lock";
 
-    let frontend_package = METADATA
-        .get()
-        .unwrap()
-        .packages
-        .iter()
-        .find(|x| x.name == pkg_name)
-        .expect("the frontend package existence has been checked during compile time; qed");
+/// How often a build waiting for a lock checks if it has been superseded by a newer change.
+const CARGO_LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-    FRONTEND_PACKAGE
-        .set(frontend_package)
-        .expect("the cell is initially empty; qed");
+/// Environment variable used by cargo to find the runner of the WASM tests.
+const TEST_RUNNER_VAR: &str = "CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER";
 
-    let frontend_package = FRONTEND_PACKAGE.get().unwrap();
+/// The metadata of the workspace and the packages being built.
+///
+/// It is replaced when a manifest changes while serving. The previous metadata is leaked on
+/// purpose so the references handed out by [`BuildArgs`] remain valid.
+#[derive(Clone, Copy)]
+struct Project {
+    metadata: &'static Metadata,
+    /// The metadata of the workspace of the frontend package. It is `metadata` unless the
+    /// frontend package is excluded from the workspace of the runner or has its own workspace.
+    frontend_metadata: &'static Metadata,
+    frontend_package: &'static Package,
+    backend_package: Option<&'static Package>,
+    default_build_path: &'static PathBuf,
+    default_serve_path: Option<&'static PathBuf>,
+}
 
-    if let Some(name) = backend_pkg_name {
-        let backend_package = METADATA
-            .get()
-            .unwrap()
-            .packages
-            .iter()
-            .find(|x| x.name == name)
-            .expect("the backend package existence has been checked during compile time; qed");
+impl Project {
+    /// Load the metadata of the workspace of the manifest, with the `default_build_path` and
+    /// `default_serve_path` functions of the macro.
+    fn load(
+        manifest_path: &Path,
+        frontend: PackageSelector,
+        backend_pkg_name: Option<&str>,
+        default_build_path: Option<&DefaultPath>,
+        default_serve_path: Option<&DefaultPath>,
+    ) -> Result<Self> {
+        let metadata = load_metadata(manifest_path)?;
+
+        let find_package = |name: &str| {
+            metadata
+                .packages
+                .iter()
+                .find(|x| x.name == name)
+                .with_context(|| package_not_found(metadata, name))
+        };
 
-        BACKEND_PACKAGE
-            .set(Some(backend_package))
-            .expect("the cell is initially empty; qed");
-    } else {
-        BACKEND_PACKAGE
-            .set(None)
-            .expect("the cell is initially empty; qed");
-    }
+        let (frontend_metadata, frontend_package) = match frontend {
+            PackageSelector::Name(name) => frontend_workspace(metadata, find_package(name)?)?,
+            PackageSelector::ManifestPath(path) => find_frontend_by_manifest(metadata, path)?,
+        };
 
-    DEFAULT_BUILD_PATH
-        .set(if let Some(default_build_path) = default_build_path {
-            default_build_path(metadata, frontend_package)
-        } else {
-            metadata.workspace_root.join("build")
+        Ok(Self {
+            metadata,
+            frontend_metadata,
+            frontend_package,
+            backend_package: backend_pkg_name.map(find_package).transpose()?,
+            default_build_path: Self::default_build_path(
+                default_build_path,
+                metadata,
+                frontend_metadata,
+                frontend_package,
+                false,
+            ),
+            default_serve_path: Self::default_serve_path(
+                default_serve_path,
+                metadata,
+                frontend_package,
+            ),
         })
-        .expect("the cell is initially empty; qed");
+    }
 
-    if HOOKS.set(hooks).is_err() {
-        panic!("the cell is initially empty; qed");
+    /// The default build path of the frontend package.
+    ///
+    /// Without the `default_build_path` function of the macro, the frontend package is built in
+    /// the `build` directory of its own workspace and the packages selected at runtime are built
+    /// in their own subdirectory so they don't overwrite each other. The function of the macro
+    /// receives the metadata of the workspace of the runner.
+    fn default_build_path(
+        default_build_path: Option<&DefaultPath>,
+        metadata: &Metadata,
+        frontend_metadata: &Metadata,
+        frontend_package: &Package,
+        selected: bool,
+    ) -> &'static PathBuf {
+        Box::leak(Box::new(match default_build_path {
+            Some(default_build_path) => default_build_path(metadata, frontend_package),
+            None if selected => frontend_metadata
+                .workspace_root
+                .join("build")
+                .join(&frontend_package.name),
+            None => frontend_metadata.workspace_root.join("build"),
+        }))
     }
 
-    Ok((metadata, frontend_package))
-}
+    /// The default serve path of the frontend package, if the macro has been given a
+    /// `default_serve_path` function.
+    fn default_serve_path(
+        default_serve_path: Option<&DefaultPath>,
+        metadata: &Metadata,
+        frontend_package: &Package,
+    ) -> Option<&'static PathBuf> {
+        let default_serve_path = default_serve_path?;
+
+        Some(Box::leak(Box::new(default_serve_path(
+            metadata,
+            frontend_package,
+        ))))
+    }
 
-/// Build arguments.
-#[derive(StructOpt, Debug)]
-pub struct DefaultBuildArgs {
-    /// Build directory output.
-    #[structopt(long)]
-    pub build_path: Option<PathBuf>,
+    fn get() -> Self {
+        *PROJECT
+            .get()
+            .expect("metadata has been initialized on startup; qed")
+            .read()
+            .unwrap()
+    }
 
-    /// Create a profiling build. Enable optimizations and debug info.
-    #[structopt(long)]
-    pub profiling: bool,
-}
+    /// Reload the metadata after a manifest changed.
+    ///
+    /// The manifest might be half-saved when this is called so the command is retried a few
+    /// times. The previous metadata is kept if it fails.
+    fn reload() -> Result<()> {
+        let current = Self::get();
+        let manifest_path = MANIFEST_PATH
+            .get()
+            .expect("the manifest path has been initialized on startup; qed");
+        // NOTE: the frontend package is selected by its manifest so it is still found if its
+        //       name is ambiguous or has changed
+        let frontend = PackageSelector::ManifestPath(&current.frontend_package.manifest_path);
+        let backend_pkg_name = current.backend_package.map(|x| x.name.as_str());
+
+        // NOTE: the default paths are kept, the package has not been selected again
+        let mut attempt = 1;
+        let project = loop {
+            match Self::load(manifest_path, frontend, backend_pkg_name, None, None) {
+                Ok(project) => break project,
+                Err(err) if attempt < METADATA_RETRIES => {
+                    log::debug!("Could not reload metadata (attempt {}): {:?}", attempt, err);
+                    attempt += 1;
+                    std::thread::sleep(METADATA_RETRY_DELAY);
+                }
+                Err(err) => return Err(err.context("could not reload metadata")),
+            }
+        };
 
-/// A trait that allows overriding the `build` command.
-pub trait BuildArgs: Downcast {
-    /// Build directory output.
-    fn build_path(&self) -> &PathBuf;
+        Self::set(Self {
+            default_build_path: current.default_build_path,
+            default_serve_path: current.default_serve_path,
+            ..project
+        });
 
-    /// Default path for the build/public directory.
-    fn default_build_path(&self) -> &PathBuf {
-        DEFAULT_BUILD_PATH
-            .get()
-            .expect("default_build_path has been initialized on startup; qed")
+        Ok(())
     }
 
-    /// Path to the `target` directory.
-    fn target_path(&self) -> &PathBuf {
-        &self.metadata().target_directory
+    /// Replace the frontend package by the package selected in the build arguments (by its name
+    /// or its manifest), if any.
+    fn select_frontend(build_args: &dyn BuildArgs) -> Result<()> {
+        let current = Self::get();
+        let (frontend_metadata, frontend_package) =
+            match (build_args.package(), build_args.manifest_path()) {
+                (Some(_), Some(_)) => {
+                    bail!("`--package` and `--manifest-path` can not be used together")
+                }
+                (Some(name), None) => (current.metadata, find_member(current.metadata, name)?),
+                (None, Some(path)) => find_frontend_by_manifest(current.metadata, path)?,
+                (None, None) => return Ok(()),
+            };
+
+        Self::set(Self {
+            frontend_metadata,
+            frontend_package,
+            default_build_path: Self::default_build_path(
+                DEFAULT_BUILD_PATH.get().and_then(Option::as_ref),
+                current.metadata,
+                frontend_metadata,
+                frontend_package,
+                true,
+            ),
+            default_serve_path: Self::default_serve_path(
+                DEFAULT_SERVE_PATH.get().and_then(Option::as_ref),
+                current.metadata,
+                frontend_package,
+            ),
+            ..current
+        });
+
+        Ok(())
     }
 
-    /// Metadata of the project.
-    fn metadata(&self) -> &Metadata {
-        METADATA
+    fn set(project: Self) {
+        *PROJECT
             .get()
             .expect("metadata has been initialized on startup; qed")
+            .write()
+            .unwrap() = project;
     }
+}
 
-    /// Package metadata.
-    fn frontend_package(&self) -> &Package {
-        FRONTEND_PACKAGE
-            .get()
-            .expect("frontend_package has been initialized on startup; qed")
-    }
+/// How the frontend package is selected in the workspace.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum PackageSelector<'a> {
+    /// The name of the package.
+    Name(&'a str),
+    /// The path to the manifest of the package (or its directory), relative to the workspace
+    /// root.
+    ManifestPath(&'a Path),
+}
 
-    /// Backend frontend_package metadata.
-    fn backend_package(&self) -> Option<&Package> {
-        BACKEND_PACKAGE
-            .get()
-            .expect("frontend_package has been initialized on startup; qed")
-            .to_owned()
-    }
+fn load_metadata(manifest_path: &Path) -> Result<&'static Metadata> {
+    let metadata = MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .exec()
+        .with_context(|| {
+            format!(
+                "could not get the metadata of the workspace of `{}`",
+                manifest_path.display()
+            )
+        })?;
 
-    /// Create a profiling build. Enable optimizations and debug info.
-    fn profiling(&self) -> bool;
+    Ok(Box::leak(Box::new(metadata)))
+}
 
-    /// Transpile SASS and SCSS files to CSS in the build directory.
-    #[cfg(feature = "sass")]
-    fn build_sass_from_dir(
-        &self,
-        input_dir: &std::path::Path,
-        options: sass_rs::Options,
-    ) -> Result<()> {
-        use walkdir::{DirEntry, WalkDir};
+/// The metadata of the workspace of the frontend package and the package in it.
+///
+/// The metadata of the runner is used if the package is a member of its workspace. Otherwise the
+/// package is excluded or has its own workspace so the metadata of its manifest is loaded.
+fn frontend_workspace(
+    metadata: &'static Metadata,
+    package: &'static Package,
+) -> Result<(&'static Metadata, &'static Package)> {
+    if metadata.workspace_members.contains(&package.id) {
+        return Ok((metadata, package));
+    }
 
-        let build_path = self.build_path();
+    let frontend_metadata = load_metadata(&package.manifest_path)?;
+    let package = find_package_by_manifest(frontend_metadata, &package.manifest_path)?;
+    Ok((frontend_metadata, package))
+}
 
-        fn is_sass(entry: &DirEntry) -> bool {
-            matches!(
-                entry.path().extension().map(|x| x.to_str()).flatten(),
-                Some("sass") | Some("scss")
-            )
+/// Find the frontend package by its manifest, in the workspace of the runner or in its own
+/// workspace.
+fn find_frontend_by_manifest(
+    metadata: &'static Metadata,
+    path: &Path,
+) -> Result<(&'static Metadata, &'static Package)> {
+    match find_package_by_manifest(metadata, path) {
+        Ok(package) => frontend_workspace(metadata, package),
+        Err(err) => {
+            // NOTE: the packages excluded from the workspace are not in its metadata at all
+            let manifest_path = manifest_file(&metadata.workspace_root.join(path));
+            if !manifest_path.is_file() {
+                return Err(err);
+            }
+            let frontend_metadata = load_metadata(&manifest_path)?;
+            let package = find_package_by_manifest(frontend_metadata, &manifest_path)?;
+            Ok((frontend_metadata, package))
         }
+    }
+}
 
-        fn should_ignore(entry: &DirEntry) -> bool {
-            entry
-                .file_name()
-                .to_str()
-                .map(|x| x.starts_with("_"))
+/// The path to a manifest, given the manifest itself or the directory of its package.
+fn manifest_file(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join("Cargo.toml")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+fn find_package_by_manifest<'a>(metadata: &'a Metadata, path: &Path) -> Result<&'a Package> {
+    let path = manifest_file(&metadata.workspace_root.join(path));
+    let canonical_path = path
+        .canonicalize()
+        .with_context(|| format!("could not find the manifest `{}`", path.display()))?;
+
+    metadata
+        .packages
+        .iter()
+        .find(|x| {
+            x.manifest_path
+                .canonicalize()
+                .map(|x| x == canonical_path)
                 .unwrap_or(false)
-        }
+        })
+        .with_context(|| {
+            format!(
+                "no package with the manifest `{}` in the workspace (available packages: {})",
+                path.display(),
+                format_names(&workspace_members(metadata)),
+            )
+        })
+}
 
-        log::info!("Building SASS from {:?}", input_dir);
+fn find_member<'a>(metadata: &'a Metadata, name: &str) -> Result<&'a Package> {
+    metadata
+        .packages
+        .iter()
+        .filter(|x| metadata.workspace_members.contains(&x.id))
+        .find(|x| x.name == name)
+        .with_context(|| package_not_found(metadata, name))
+}
 
-        let walker = WalkDir::new(&input_dir).into_iter();
-        for entry in walker
-            .filter_map(|x| match x {
-                Ok(x) => Some(x),
-                Err(err) => {
-                    log::warn!(
-                        "Could not walk into directory `{}`: {}",
-                        input_dir.display(),
-                        err,
-                    );
-                    None
-                }
-            })
-            .filter(|x| x.path().is_file() && is_sass(x) && !should_ignore(x))
-        {
-            let file_path = entry.path();
-            let css_path = build_path
-                .join(file_path.strip_prefix(&input_dir).unwrap())
-                .with_extension("css");
+fn package_not_found(metadata: &Metadata, name: &str) -> String {
+    let members = workspace_members(metadata);
+    let suggestion = closest_match(name, &members)
+        .map(|x| format!("; did you mean `{}`?", x))
+        .unwrap_or_default();
+
+    format!(
+        "package `{}` not found in the workspace (available packages: {}){}",
+        name,
+        format_names(&members),
+        suggestion,
+    )
+}
 
-            match sass_rs::compile_file(file_path, options.clone()) {
-                Ok(css) => {
-                    let _ = fs::create_dir_all(css_path.parent().unwrap());
-                    fs::write(&css_path, css).with_context(|| {
-                        format!("could not write CSS to file `{}`", css_path.display())
-                    })?;
-                }
-                Err(err) => bail!(
-                    "could not convert SASS file `{}` to `{}`: {}",
-                    file_path.display(),
-                    css_path.display(),
-                    err,
-                ),
-            }
-        }
+fn workspace_members(metadata: &Metadata) -> Vec<&str> {
+    let mut members: Vec<_> = metadata
+        .packages
+        .iter()
+        .filter(|x| metadata.workspace_members.contains(&x.id))
+        .map(|x| x.name.as_str())
+        .collect();
+    members.sort_unstable();
+    members
+}
 
-        Ok(())
+fn format_names(names: &[&str]) -> String {
+    names
+        .iter()
+        .map(|x| format!("`{}`", x))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Find the name closest to `name` by edit distance, if it is close enough to be a typo.
+fn closest_match<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let max_distance = std::cmp::max(name.chars().count(), 3) / 3;
+
+    candidates
+        .iter()
+        .map(|x| (edit_distance(name, x), *x))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, x)| x)
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, x) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, y) in b.iter().enumerate() {
+            current[j + 1] = if x == *y {
+                previous[j]
+            } else {
+                1 + previous[j].min(previous[j + 1]).min(current[j])
+            };
+        }
+        previous = current;
     }
 
-    /// Returns a list of directories to lookup to transpile SASS and SCSS files to CSS.
-    #[cfg(feature = "sass")]
-    fn sass_lookup_directories(&self, _profile: BuildProfile) -> Vec<PathBuf> {
-        const STYLE_CANDIDATES: &[&str] = &["assets", "styles", "css", "sass"];
+    previous[b.len()]
+}
 
-        let package_path = self.frontend_package().manifest_path.parent().unwrap();
+/// A stage of the build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Stage {
+    /// The `pre_build` hook.
+    PreBuild,
+    /// The `cargo check` of the rebuilds of [`ServeArgs::check_first`].
+    Check,
+    /// The compilation of the WASM by cargo.
+    Cargo,
+    /// The generation of the JS bindings by `wasm-bindgen`.
+    Bindgen,
+    /// The optimization of the WASM by `wasm-opt` (only for the release and profiling builds).
+    WasmOpt,
+    /// The `post_build` hook.
+    PostBuild,
+    /// The copy of the static files by the default `post_build` hook.
+    StaticFiles,
+    /// The command of [`BuildArgs::bundle_command`].
+    Bundle,
+    /// The prerendering of the routes of [`BuildArgs::prerender`] (only for the release builds).
+    #[cfg(feature = "prerender")]
+    Prerender,
+}
 
-        STYLE_CANDIDATES
-            .iter()
-            .map(|x| package_path.join(x))
-            .filter(|x| x.exists())
-            .collect()
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::PreBuild => "pre-build hook",
+            Self::Check => "cargo check",
+            Self::Cargo => "cargo build",
+            Self::Bindgen => "wasm-bindgen",
+            Self::WasmOpt => "wasm-opt",
+            Self::PostBuild => "post-build hook",
+            Self::StaticFiles => "static files",
+            Self::Bundle => "bundle command",
+            #[cfg(feature = "prerender")]
+            Self::Prerender => "prerendering",
+        })
     }
+}
 
-    /// Default profile to transpile SASS and SCSS files to CSS.
-    #[cfg(feature = "sass")]
-    fn sass_options(&self, profile: BuildProfile) -> sass_rs::Options {
-        sass_rs::Options {
-            output_style: match profile {
-                BuildProfile::Release | BuildProfile::Profiling => sass_rs::OutputStyle::Compressed,
-                _ => sass_rs::OutputStyle::Nested,
-            },
-            ..sass_rs::Options::default()
+/// Receive the progress of the build, the development server and the watcher.
+///
+/// The default implementation of the methods produce the console output of `wasmbl`, override them
+/// to show the progress somewhere else. A reporter is installed with [`Runner::reporter`].
+///
+/// The logs of the `log` crate are not reported: their verbosity is set with
+/// [`set_verbosity`].
+pub trait Reporter: Send + Sync {
+    /// A stage of the build started.
+    fn stage_started(&self, stage: Stage) {
+        match stage {
+            Stage::PreBuild => log::info!("Running pre-build hook"),
+            Stage::Check => log::info!("Checking frontend"),
+            Stage::Cargo => log::info!("Building frontend"),
+            Stage::PostBuild => log::info!("Running post-build hook"),
+            Stage::Bundle => log::info!("Running bundle command"),
+            #[cfg(feature = "prerender")]
+            Stage::Prerender => log::info!("Prerendering routes"),
+            _ => log::debug!("Running {}", stage),
         }
     }
 
-    /// Run the `build` command.
-    fn run(self) -> Result<PathBuf>
-    where
-        Self: Sized + 'static,
-    {
-        let hooks = HOOKS.get().expect("wasmbl_init() has not been called");
-        build(BuildProfile::Release, &self, hooks)?;
-        Ok(self.build_path().to_owned())
+    /// A stage of the build finished successfully.
+    fn stage_finished(&self, stage: Stage, duration: Duration) {
+        log::debug!("Finished {} in {:.2?}", stage, duration);
     }
-}
 
-impl_downcast!(BuildArgs);
+    /// A diagnostic (error, warning, ...) of the compiler.
+    fn diagnostic(&self, diagnostic: &cargo_metadata::diagnostic::Diagnostic) {
+        if logger::is_json() {
+            diagnostics::log_event(diagnostic);
+        } else if let Some(rendered) = diagnostic.rendered.as_ref() {
+            eprint!("{}", rendered);
+        }
+    }
 
-impl BuildArgs for DefaultBuildArgs {
-    fn build_path(&self) -> &PathBuf {
-        self.build_path
-            .as_ref()
-            .unwrap_or_else(|| self.default_build_path())
+    /// A line of the standard error of cargo that is not a diagnostic (the progress of the
+    /// compilation, the errors of cargo itself, ...), without its line ending.
+    fn cargo_output(&self, line: &str) {
+        if logger::is_json() {
+            log_cargo_line(line);
+        } else {
+            eprintln!("{}", line);
+        }
     }
 
-    fn profiling(&self) -> bool {
-        self.profiling
+    /// A file has been written to the build directory.
+    fn artifact_written(&self, path: &Path, size: u64) {
+        log::debug!("Written `{}` ({} bytes)", path.display(), size);
     }
-}
 
-/// Serve arguments.
-#[derive(StructOpt, Debug)]
-pub struct DefaultServeArgs {
-    /// Activate HTTP logs.
-    #[structopt(long)]
-    pub log: bool,
+    /// Cargo waits for a lock held by another cargo command, `lock` is what cargo waits for (like
+    /// `build directory`). The build continues when the other command finishes or releases it.
+    fn waiting_for_lock(&self, lock: &str) {
+        log::warn!(
+            "Waiting for the lock on the {} held by another cargo command (pass `--target-dir` \
+            to build in another directory)",
+            lock
+        );
+    }
 
-    /// IP address to bind.
-    ///
-    /// Use 0.0.0.0 to expose the server to your network.
-    #[structopt(long, short = "h", default_value = "127.0.0.1")]
-    pub ip: String,
+    /// Something went wrong but the build continues.
+    fn warning(&self, message: &str) {
+        log::warn!("{}", message);
+    }
 
-    /// Port number.
-    #[structopt(long, short = "p", default_value = "3000")]
-    pub port: u16,
+    /// The development server is listening.
+    fn server_started(&self, url: &str) {
+        log::info!("Development server started: {}", url);
+    }
 
-    /// Build arguments.
-    #[structopt(flatten)]
-    pub build_args: DefaultBuildArgs,
+    /// The watcher is about to rebuild and clears the screen (`serve --clear`).
+    fn clear(&self) {
+        clear_screen();
+    }
+
+    /// The status of a rebuild of the watcher (`serve --clear`).
+    fn status(&self, success: bool, message: &str) {
+        print_status(success, message);
+    }
 }
 
-/// A trait that allows overriding the `serve` command.
-pub trait ServeArgs: Downcast + Send {
-    /// Activate HTTP logs.
-    #[cfg(feature = "dev-server")]
-    fn log(&self) -> bool;
+/// The reporter that prints the console output of `wasmbl`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ConsoleReporter;
 
-    /// IP address to bind.
-    ///
-    /// Use 0.0.0.0 to expose the server to your network.
-    #[cfg(feature = "dev-server")]
-    fn ip(&self) -> &str;
+impl Reporter for ConsoleReporter {}
 
-    /// Port number.
-    #[cfg(feature = "dev-server")]
-    fn port(&self) -> u16;
+/// The result of a build, given to the [`Hooks::post_build`] hook.
+///
+/// The JS and the WASM are not written yet when the hook runs: the default hook writes them to
+/// [`BuildOutput::js_path`] and [`BuildOutput::wasm_path`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct BuildOutput {
+    /// Build profile of the WASM.
+    pub profile: BuildProfile,
+    /// Target of the WASM. There are no JS bindings, snippets or TypeScript declarations for
+    /// [`BuildTarget::Wasi`].
+    pub target: BuildTarget,
+    /// What started the build.
+    pub trigger: BuildTrigger,
+    /// Path of the JS bindings in the build directory.
+    pub js_path: PathBuf,
+    /// Content of the JS bindings.
+    pub js: String,
+    /// Path of the WASM in the build directory.
+    pub wasm_path: PathBuf,
+    /// The WASM, after the optimization.
+    pub wasm: Vec<u8>,
+    /// The WASM has been optimized by `wasm-opt`. It is never the case for the dev builds, nor when
+    /// `wasm-opt` is not available.
+    pub optimized: bool,
+    /// The JS snippets of the dependencies (path relative to the build directory and content).
+    /// They are written in the directory `snippets` of the build directory.
+    pub snippets: Vec<(PathBuf, String)>,
+    /// The TypeScript declarations, if [`BuildArgs::typescript`] is enabled. They are written to
+    /// `app.d.ts` in the build directory (named after [`BuildArgs::out_name`]).
+    pub typescript: Option<String>,
+    /// The time spent in each step of the build.
+    pub timings: BuildTimings,
+    /// The files written to the build directory before the `post_build` hook.
+    pub files: Vec<PathBuf>,
+}
 
-    /// Build arguments.
-    fn build_args(&self) -> &dyn BuildArgs;
+/// A file written to the build directory by the build, given to the [`Hooks::asset_filter`] hook.
+///
+/// This covers the files generated by the build (the JS bindings, the WASM, the snippets, the
+/// TypeScript declarations), the compiled CSS and the files copied from the static directory.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Asset {
+    /// Path of the file relative to the build directory. It can be changed to write the file
+    /// somewhere else.
+    pub path: PathBuf,
+    /// Logical name of the file, always separated by `/`, it is not changed by the hook: the
+    /// default path of the generated files (`app.js`, `app_bg.wasm`, `snippets/...` with the base
+    /// name of [`BuildArgs::out_name`]), `static/` followed by the path in the static directory or
+    /// the path of the SASS file with the directory of the styles (`styles/main.scss`), the path
+    /// of the localization file with the directory of the locales (`locales/en/main.ftl`) or
+    /// `trunk/` followed by the `href` of a Trunk directive (see [`BuildArgs::trunk_index`]).
+    pub name: String,
+    /// MIME type of the file, guessed from the extension of its path.
+    pub content_type: &'static str,
+    /// Content of the file.
+    pub content: Vec<u8>,
+}
 
-    /// Run the `serve` command.
-    fn run(self) -> Result<()>
-    where
-        Self: Sync + Sized + 'static,
+impl Asset {
+    /// A new asset with the content type guessed from the extension of `path`.
+    pub fn new(path: impl Into<PathBuf>, name: impl Into<String>, content: Vec<u8>) -> Self {
+        let path = path.into();
+        Self {
+            content_type: content_type(&path),
+            path,
+            name: name.into(),
+            content,
+        }
+    }
+}
+
+/// A relative path with `/` as separator on all the platforms, as in a URL.
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|x| x.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The characters percent-encoded in the segments of a URL path.
+const URL_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'\\')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// A relative path percent-encoded as the path of a URL, the names that are not valid UTF-8 are
+/// encoded byte-for-byte.
+///
+/// Returns `None` if a name can't be represented in a URL: a name that is not valid Unicode on
+/// Windows.
+fn url_path(path: &Path) -> Option<String> {
+    let segments = path
+        .components()
+        .map(|x| {
+            let name = x.as_os_str();
+            let bytes = if cfg!(unix) {
+                name.as_encoded_bytes()
+            } else {
+                name.to_str()?.as_bytes()
+            };
+            Some(percent_encoding::percent_encode(bytes, URL_SEGMENT).to_string())
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(segments.join("/"))
+}
+
+/// The relative path of a percent-encoded URL path, the opposite of [`url_path`].
+///
+/// Returns `None` if the path goes outside of its directory (`..` or an encoded separator) or if a
+/// name can't be a file name on this platform.
+fn path_from_url(url_path: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for segment in url_path.split('/') {
+        let bytes: Vec<u8> = percent_encoding::percent_decode_str(segment).collect();
+        match bytes.as_slice() {
+            b"" | b"." => continue,
+            b".." => return None,
+            _ if bytes.contains(&b'/') => return None,
+            _ if cfg!(windows) && bytes.iter().any(|x| matches!(x, b'\\' | b':')) => return None,
+            _ => {}
+        }
+
+        #[cfg(unix)]
+        let name = <OsString as std::os::unix::ffi::OsStringExt>::from_vec(bytes);
+        #[cfg(not(unix))]
+        let name = OsString::from(String::from_utf8(bytes).ok()?);
+        path.push(name);
+    }
+
+    Some(path)
+}
+
+/// All the files of a directory and of its sub-directories, sorted. The names don't need to be
+/// valid UTF-8.
+///
+/// The symlinks are followed, except the ones that lead to a directory that contains them: they
+/// would be listed endlessly.
+fn list_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    // NOTE: the real locations of the directory and of its parents, to detect the cycles
+    let mut dirs = vec![(dir.to_path_buf(), vec![dir.canonicalize()?])];
+    while let Some((dir, ancestors)) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if !path.is_dir() {
+                files.push(path);
+                continue;
+            }
+
+            let real_path = if entry.file_type()?.is_symlink() {
+                path.canonicalize()?
+            } else {
+                ancestors[ancestors.len() - 1].join(entry.file_name())
+            };
+            if ancestors.contains(&real_path) {
+                log::warn!(
+                    "Skipping `{}`: the symlink leads to a directory that contains it",
+                    path.display()
+                );
+                continue;
+            }
+            let mut ancestors = ancestors.clone();
+            ancestors.push(real_path);
+            dirs.push((path, ancestors));
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// The MIME type of a file from its extension.
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("html") | Some("htm") => "text/html",
+        Some("js") | Some("mjs") => "text/javascript",
+        Some("css") => "text/css",
+        Some("wasm") => "application/wasm",
+        Some("ts") => "application/typescript",
+        Some("json") | Some("map") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        Some("woff") => "font/woff",
+        Some("txt") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The `Content-Type` of a response of the development server: the JS is always `text/javascript`
+/// (browsers refuse to run a module served otherwise), the type is guessed from the extension of
+/// the URL if tide could not tell and the textual files are UTF-8.
+#[cfg(feature = "dev-server")]
+fn served_content_type(mime: &tide::http::Mime, url_path: &str) -> tide::http::Mime {
+    let essence = match mime.essence() {
+        "application/javascript" => "text/javascript",
+        x @ "text/plain" | x @ "application/octet-stream" => {
+            match content_type(Path::new(url_path)) {
+                "application/octet-stream" => x,
+                guessed => guessed,
+            }
+        }
+        x => x,
+    };
+    let textual = matches!(
+        essence,
+        "text/html"
+            | "text/css"
+            | "text/javascript"
+            | "text/plain"
+            | "application/json"
+            | "image/svg+xml"
+    );
+    if essence == mime.essence() && (!textual || mime.param("charset").is_some()) {
+        return mime.clone();
+    }
+
+    let content_type = if textual {
+        format!("{};charset=utf-8", essence)
+    } else {
+        essence.to_string()
+    };
+    content_type.parse().unwrap_or_else(|_| mime.clone())
+}
+
+/// Set the `Content-Type` of all the responses of the development server with
+/// [`served_content_type`].
+#[cfg(feature = "dev-server")]
+fn content_type_middleware(
+    req: tide::Request<()>,
+    next: tide::Next<'_, ()>,
+) -> Pin<Box<dyn std::future::Future<Output = tide::Result> + Send + '_>> {
+    let url_path = req.url().path().to_owned();
+    Box::pin(async move {
+        let mut res = next.run(req).await;
+        if let Some(mime) = res.content_type() {
+            res.set_content_type(served_content_type(&mime, &url_path));
+        }
+        Ok(res)
+    })
+}
+
+/// The response of a listing of [`ServeArgs::dir_listing`].
+#[cfg(feature = "dev-server")]
+fn listing_response(html: String) -> tide::Response {
+    tide::Response::builder(200)
+        .body(html)
+        .content_type(tide::http::mime::HTML)
+        .build()
+}
+
+/// The explanation of a serve directory without index document, `None` if there is one.
+#[cfg(feature = "dev-server")]
+fn check_index(args: &dyn ServeArgs) -> Option<String> {
+    if args.serve_path().join(args.index_document()).is_file() {
+        return None;
+    }
+    Some(missing_index(args))
+}
+
+/// Why the serve directory has no index document and how to add one.
+#[cfg(feature = "dev-server")]
+fn missing_index(args: &dyn ServeArgs) -> String {
+    let build_args = args.build_args();
+    let index = args.index_document();
+    format!(
+        "there is no `{}` in the serve directory `{}`, the development server has no page to \
+        show at `/`. Add one to the static directory `{}` (or remove the static directory to get \
+        a generated one), add the page template `pages/{}` to the frontend {}, or write it in a \
+        `post_build` hook",
+        index,
+        args.serve_path().display(),
+        build_args.static_dir().display(),
+        index,
+        if build_args.frontend_dir().is_some() {
+            "directory"
+        } else {
+            "package"
+        },
+    )
+}
+
+/// Warn after a build if the serve directory has no index document (see [`missing_index`]).
+#[cfg(feature = "dev-server")]
+fn warn_missing_index(args: &dyn ServeArgs) {
+    if let Some(message) = check_index(args) {
+        reporter::reporter().warning(&message);
+    }
+}
+
+/// The placeholder served instead of a missing index document, never written to the serve
+/// directory. It is reloaded by the live reload when the index appears.
+#[cfg(feature = "dev-server")]
+fn missing_index_response(message: &str) -> tide::Response {
+    let message = dir_listing::escape(message);
+    tide::Response::builder(404)
+        .body(format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+            <title>No index document</title>\n<style>body {{ font-family: sans-serif; \
+            max-width: 40em; margin: 4em auto; line-height: 1.5; }}</style>\n</head>\n<body>\n\
+            <h1>No index document</h1>\n<p>{}.</p>\n</body>\n</html>\n",
+            message
+        ))
+        .content_type(tide::http::mime::HTML)
+        .build()
+}
+
+/// How the development server serves the paths of the serve directory.
+#[cfg(feature = "dev-server")]
+#[derive(Debug, Clone)]
+struct ServeFiles {
+    /// See [`ServeArgs::index_document`].
+    index_document: String,
+    /// See [`ServeArgs::extensionless_html`].
+    extensionless_html: bool,
+    /// See [`ServeArgs::trailing_slash`].
+    trailing_slash: TrailingSlash,
+    /// See [`ServeArgs::dir_listing`].
+    listing: bool,
+}
+
+/// The response to a request of the serve directory.
+#[cfg(feature = "dev-server")]
+#[derive(Debug, PartialEq, Eq)]
+enum Served {
+    /// A file of the serve directory: the one of the path, a page or the index of a fallback.
+    File(PathBuf),
+    /// A redirect to this URL, for [`ServeArgs::trailing_slash`].
+    Redirect(String),
+    /// The listing of this directory, relative to the serve directory.
+    Listing(PathBuf),
+}
+
+#[cfg(feature = "dev-server")]
+impl ServeFiles {
+    /// The file of the path of the URL, the page of a directory or of an extensionless URL, or the
+    /// index of the closest directory.
+    fn resolve(&self, serve_path: &Path, url: &tide::http::Url) -> Served {
+        let url_path = url.path();
+        let slash = url_path.len() > 1 && url_path.ends_with('/');
+        // NOTE: the segments of the URL are percent-decoded and joined one by one to get the
+        //       separators of the platform, `..` is not allowed
+        let path = match path_from_url(url_path) {
+            Some(path) => path,
+            None => {
+                return Served::File(pages::fallback_index(
+                    serve_path,
+                    None,
+                    &self.index_document,
+                ))
+            }
+        };
+        // NOTE: only the path and the query, the host might not be the one of the browser
+        let redirect = |slash: bool| {
+            let query = url.query().map(|x| format!("?{}", x)).unwrap_or_default();
+            match slash {
+                true => Served::Redirect(format!("{}/{}", url_path, query)),
+                false => Served::Redirect(format!("{}{}", url_path.trim_end_matches('/'), query)),
+            }
+        };
+
+        let full_path = serve_path.join(&path);
+        if full_path.is_file() {
+            return Served::File(full_path);
+        }
+        if full_path.is_dir() {
+            let index = full_path.join(&self.index_document);
+            if index.is_file() {
+                return match self.trailing_slash {
+                    TrailingSlash::Add if !slash => redirect(true),
+                    TrailingSlash::Remove if slash => redirect(false),
+                    _ => Served::File(index),
+                };
+            }
+            // NOTE: only the directories that exist are listed, not the paths of the fallback
+            //       to an index
+            if self.listing {
+                return Served::Listing(path);
+            }
+        }
+        if self.extensionless_html && path.extension().is_none() {
+            let mut html = full_path.into_os_string();
+            html.push(".html");
+            let html = PathBuf::from(html);
+            if html.is_file() && !path.as_os_str().is_empty() {
+                return match self.trailing_slash {
+                    TrailingSlash::Add | TrailingSlash::Remove if slash => redirect(false),
+                    _ => Served::File(html),
+                };
+            }
+        }
+
+        Served::File(pages::fallback_index(
+            serve_path,
+            Some(&path),
+            &self.index_document,
+        ))
+    }
+}
+
+/// The paths of the files written by the current build, by their logical names.
+static WRITTEN_ASSETS: Lazy<Mutex<HashMap<String, PathBuf>>> = Lazy::new(Default::default);
+
+/// The path of a file written by the current build, given its logical name ([`Asset::name`]).
+fn written_asset(name: &str) -> Option<PathBuf> {
+    WRITTEN_ASSETS.lock().unwrap().get(name).cloned()
+}
+
+/// Give a file to the [`Hooks::asset_filter`] hook and write what it returns to the build
+/// directory. Returns the path of the file written or `None` if the hook skipped it.
+fn write_asset(build_path: &Path, hooks: &Hooks, asset: Asset) -> Result<Option<PathBuf>, Error> {
+    let name = asset.name.clone();
+    let asset = match (hooks.asset_filter)(asset).map_err(|source| Error::Hook {
+        hook: "asset_filter",
+        source,
+    })? {
+        Some(asset) => asset,
+        None => {
+            WRITTEN_ASSETS.lock().unwrap().remove(&name);
+            return Ok(None);
+        }
+    };
+
+    let path = build_path.join(&asset.path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|source| Error::Io {
+            path: parent.to_owned(),
+            source,
+        })?;
+    }
+    write_atomic(&path, &asset.content).map_err(|source| Error::Io {
+        path: path.clone(),
+        source,
+    })?;
+    reporter::reporter().artifact_written(&path, asset.content.len() as u64);
+    WRITTEN_ASSETS.lock().unwrap().insert(name, path.clone());
+
+    Ok(Some(path))
+}
+
+/// Write a file of the build directory to a temporary file next to it, then rename it: a crash or
+/// a full disk never leaves a truncated WASM or JS that the server would serve.
+fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = path.with_file_name(name);
+
+    let res = fs::write(&tmp_path, content).and_then(|()| fs::rename(&tmp_path, path));
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res
+}
+
+/// The time spent in each step of a build. See [`BuildOutput::timings`].
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct BuildTimings {
+    /// Compilation of the WASM by cargo.
+    pub cargo: Duration,
+    /// Generation of the JS bindings by `wasm-bindgen` (zero for [`BuildTarget::Wasi`]).
+    pub bindgen: Duration,
+    /// Optimization of the WASM (zero if it was not optimized).
+    pub wasm_opt: Duration,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+/// A build profile for the WASM.
+pub enum BuildProfile {
+    /// Development profile (no `--release`, no optimization).
+    Dev,
+    /// Release profile (`--profile`, `-O2 -Os`).
+    Release,
+    /// Release profile (`--profile`, `-O2 --debuginfo`).
+    Profiling,
+}
+
+impl BuildProfile {
+    /// The name of the profile: `dev`, `release` or `profiling`.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Dev => "dev",
+            Self::Release => "release",
+            Self::Profiling => "profiling",
+        }
+    }
+}
+
+/// What started a build, given to the `pre_build` hook and in [`BuildOutput::trigger`] so the
+/// hooks can skip the slow steps in the development loop for example.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BuildTrigger {
+    /// A command that builds once: `build`, `deploy`, `run`, ...
+    Build,
+    /// The first build of the `serve` command, before the server starts.
+    ServeInitial,
+    /// A rebuild of the watcher of the `serve` command, after a change or a request of the
+    /// control API. The `post_build` hook of the rebuilds of the assets gets it too.
+    WatchRebuild,
+}
+
+/// Options of `wasm-opt` for the release and profiling builds, given by
+/// [`BuildArgs::wasm_opt_options`].
+///
+/// The options are the same with the feature `prebuilt-wasm-opt` (the binary `wasm-opt`) and the
+/// feature `binaryen` (the library), except `low_memory_unused` and `enabled_features` that are
+/// only supported by the binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WasmOptOptions {
+    /// Optimization level, from 0 to 4 (`-O<level>`).
+    pub level: u32,
+    /// Shrink level, from 0 to 2 (`-s`).
+    pub shrink_level: u32,
+    /// Keep the debug info (`-g`).
+    pub debug_info: bool,
+    /// Run the optimizations until the WASM stops shrinking (`--converge`).
+    pub converge: bool,
+    /// Assume that the first 1K of the memory is not used (`--low-memory-unused`).
+    pub low_memory_unused: bool,
+    /// Passes run in order after the optimizations, without the leading `--` (`dce`,
+    /// `strip-producers`, ...).
+    pub extra_passes: Vec<String>,
+    /// WASM features enabled, without the leading `--enable-` (`bulk-memory`, `simd`, ...).
+    pub enabled_features: Vec<String>,
+    /// How many times the binary `wasm-opt` runs again when it crashes or fails, for the crashes
+    /// that don't always happen. The library of binaryen is never run again.
+    pub retries: u32,
+    /// Run the passes on a single thread (`BINARYEN_CORES=1`), for the reproducible builds. Only
+    /// supported by the binary, the library of binaryen uses all the cores.
+    pub single_threaded: bool,
+}
+
+impl WasmOptOptions {
+    /// The default options of a profile: level 2, shrink level 1 for the release builds and the
+    /// debug info for the profiling builds.
+    pub fn for_profile(profile: BuildProfile) -> Self {
+        Self {
+            level: 2,
+            shrink_level: match profile {
+                BuildProfile::Release => 1,
+                BuildProfile::Profiling | BuildProfile::Dev => 0,
+            },
+            debug_info: profile == BuildProfile::Profiling,
+            converge: false,
+            low_memory_unused: false,
+            extra_passes: Vec::new(),
+            enabled_features: Vec::new(),
+            retries: 0,
+            single_threaded: false,
+        }
+    }
+}
+
+/// The feature of the frontend package enabled by [`PanicStrategy::UnwindVerbose`], if the
+/// package declares it.
+const PANIC_HOOK_FEATURE: &str = "console_error_panic_hook";
+
+/// How the WASM handles the panics, given by [`BuildArgs::panic_strategy`].
+///
+/// The strategy is applied to the cargo profile of the build with the environment variables
+/// `CARGO_PROFILE_<PROFILE>_*`, the manifests are not changed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PanicStrategy {
+    /// The panics keep their messages and the debug assertions are enabled. The feature
+    /// `console_error_panic_hook` of the frontend package is enabled if it has one, so the panics
+    /// are printed in the console of the browser.
+    UnwindVerbose,
+    /// The panics abort (`panic = "abort"`), without unwinding code in the WASM.
+    Abort,
+    /// Like [`PanicStrategy::Abort`], without the debug assertions, and `wasm-opt` strips the
+    /// debug info and the producers section (`--strip-debug`, `--strip-producers`).
+    AbortStrip,
+}
+
+impl PanicStrategy {
+    /// The name of the strategy, like on the command line: `unwind-verbose`, `abort` or
+    /// `abort-strip`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UnwindVerbose => "unwind-verbose",
+            Self::Abort => "abort",
+            Self::AbortStrip => "abort-strip",
+        }
+    }
+
+    /// Set the profile settings of the strategy on the cargo command.
+    fn apply(self, command: &mut Command, profile: BuildProfile) {
+        // NOTE: the profiling builds use the release profile
+        let prefix = match profile {
+            BuildProfile::Dev => "CARGO_PROFILE_DEV",
+            BuildProfile::Release | BuildProfile::Profiling => "CARGO_PROFILE_RELEASE",
+        };
+        let (panic, debug_assertions) = match self {
+            Self::UnwindVerbose => (None, Some(true)),
+            Self::Abort => (Some("abort"), None),
+            Self::AbortStrip => (Some("abort"), Some(false)),
+        };
+        if let Some(panic) = panic {
+            command.env(format!("{}_PANIC", prefix), panic);
+        }
+        if let Some(debug_assertions) = debug_assertions {
+            command.env(
+                format!("{}_DEBUG_ASSERTIONS", prefix),
+                debug_assertions.to_string(),
+            );
+        }
+    }
+
+    /// The passes of `wasm-opt` run after the optimizations.
+    fn wasm_opt_passes(self) -> &'static [&'static str] {
+        match self {
+            Self::UnwindVerbose | Self::Abort => &[],
+            Self::AbortStrip => &["strip-debug", "strip-producers"],
+        }
+    }
+}
+
+impl std::str::FromStr for PanicStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "unwind-verbose" => Ok(Self::UnwindVerbose),
+            "abort" => Ok(Self::Abort),
+            "abort-strip" => Ok(Self::AbortStrip),
+            _ => bail!(
+                "unknown panic strategy `{}`, expected `unwind-verbose`, `abort` or `abort-strip`",
+                s
+            ),
+        }
+    }
+}
+
+/// The panic strategy of a profile in the key `panic-strategy` of the `[package.metadata.wasmbl]`
+/// table of a package: a strategy for every profile or a table of strategies by profile (`dev`,
+/// `release` and `profiling`, which defaults to the one of `release`).
+fn metadata_panic_strategy(package: &Package, profile: BuildProfile) -> Option<PanicStrategy> {
+    let value = package.metadata.get("wasmbl")?.get("panic-strategy")?;
+    let name = match value.as_object() {
+        Some(table) => table
+            .get(profile.name())
+            .or_else(|| {
+                table
+                    .get("release")
+                    .filter(|_| profile == BuildProfile::Profiling)
+            })?
+            .as_str()?,
+        None => value.as_str()?,
+    };
+    match name.parse() {
+        Ok(strategy) => Some(strategy),
+        Err(err) => {
+            reporter::reporter().warning(&format!(
+                "the key `panic-strategy` of `[package.metadata.wasmbl]` is ignored: {}",
+                err
+            ));
+            None
+        }
+    }
+}
+
+/// The target of the WASM.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum BuildTarget {
+    /// `wasm32-unknown-unknown`: the library of the frontend package runs in the browser with the
+    /// JS bindings of `wasm-bindgen`.
+    #[default]
+    Web,
+    /// `wasm32-wasi`: the binary of the frontend package runs with a WASI runtime (see the command
+    /// `run`). `wasm-bindgen` is not used, the WASM is written to `app.wasm` in the build
+    /// directory (named after [`BuildArgs::out_name`]).
+    ///
+    /// The target has been renamed `wasm32-wasip1` by Rust, it is the name given to cargo.
+    Wasi,
+}
+
+impl BuildTarget {
+    /// The target triple given to cargo.
+    pub fn triple(self) -> &'static str {
+        match self {
+            Self::Web => "wasm32-unknown-unknown",
+            Self::Wasi => "wasm32-wasip1",
+        }
+    }
+}
+
+impl std::str::FromStr for BuildTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "wasm32-unknown-unknown" => Ok(Self::Web),
+            "wasm32-wasi" | "wasm32-wasip1" => Ok(Self::Wasi),
+            _ => bail!(
+                "unknown target `{}`, expected `wasm32-unknown-unknown` or `wasm32-wasi`",
+                s
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for BuildTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.triple())
+    }
+}
+
+/// The layout of the files generated in the build directory.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum OutLayout {
+    /// The JS bindings, the WASM and the static files are written in the build directory to be
+    /// served (`app.js`, `app_bg.wasm`, `index.html`, ...).
+    #[default]
+    Flat,
+    /// The layout of `wasm-pack` for a bundler: the JS bindings, the WASM, the TypeScript
+    /// declarations and a `package.json` are written in `pkg/` in the build directory so it can
+    /// be consumed as an npm package. The static files are not copied.
+    Pkg,
+    /// The JS bindings for Node.js (CommonJS) and the WASM are written in `node/` in the build
+    /// directory, they are run by the `run-node` command. The static files are not copied.
+    Node,
+}
+
+impl OutLayout {
+    /// The directory of the generated files, relative to the build directory.
+    pub fn dir(self) -> &'static Path {
+        match self {
+            Self::Flat => Path::new(""),
+            Self::Pkg => Path::new("pkg"),
+            Self::Node => Path::new("node"),
+        }
+    }
+}
+
+impl std::str::FromStr for OutLayout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "flat" => Ok(Self::Flat),
+            "pkg" => Ok(Self::Pkg),
+            "node" => Ok(Self::Node),
+            _ => bail!("unknown layout `{}`, expected `flat`, `pkg` or `node`", s),
+        }
+    }
+}
+
+/// How the development server redirects the URLs of the pages that end with a `/` or not, see
+/// [`ServeArgs::trailing_slash`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum TrailingSlash {
+    /// The pages are served at both URLs, without a redirect.
+    #[default]
+    Keep,
+    /// The index documents of the directories are redirected to the URL that ends with a `/`
+    /// (`/about` to `/about/`), the `.html` files of [`ServeArgs::extensionless_html`] to the URL
+    /// without it (`/contact/` to `/contact`), like most static hosts.
+    Add,
+    /// Every page is redirected to the URL that doesn't end with a `/` (`/about/` to `/about`).
+    Remove,
+}
+
+impl std::str::FromStr for TrailingSlash {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "add" => Ok(Self::Add),
+            "remove" => Ok(Self::Remove),
+            _ => bail!(
+                "unknown trailing slash policy `{}`, expected `keep`, `add` or `remove`",
+                s
+            ),
+        }
+    }
+}
+
+/// Build arguments.
+#[derive(StructOpt, Debug, Default)]
+pub struct DefaultBuildArgs {
+    /// Build directory output.
+    #[structopt(long, short = "d", visible_alias = "out-dir")]
+    pub build_path: Option<PathBuf>,
+
+    /// Create a profiling build. Enable optimizations and debug info.
+    #[structopt(long)]
+    pub profiling: bool,
+
+    /// Path to the manifest of the frontend package, relative to the workspace root.
+    #[structopt(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// Name of the frontend package in the workspace. It is built in `build/<package>` by default.
+    // NOTE: `-p` is already the port of the `serve` command
+    #[structopt(long, conflicts_with = "manifest-path")]
+    pub package: Option<String>,
+
+    /// Features of the frontend package to activate (comma separated).
+    #[structopt(long, use_delimiter = true)]
+    pub features: Vec<String>,
+
+    /// Do not activate the default features of the frontend package.
+    #[structopt(long)]
+    pub no_default_features: bool,
+
+    /// Activate all the features of the frontend package.
+    #[structopt(long)]
+    pub all_features: bool,
+
+    /// Directory for all the artifacts of cargo.
+    #[structopt(long)]
+    pub target_dir: Option<PathBuf>,
+
+    /// Optimization level of `wasm-opt` for the release and profiling builds (default: 2).
+    #[structopt(long, possible_values = &["0", "1", "2", "3", "4"])]
+    pub wasm_opt_level: Option<u32>,
+
+    /// Shrink level of `wasm-opt` (default: 1 for the release builds, 0 for the profiling builds).
+    #[structopt(long, possible_values = &["0", "1", "2"])]
+    pub wasm_opt_shrink_level: Option<u32>,
+
+    /// Run the optimizations of `wasm-opt` until the WASM stops shrinking.
+    #[structopt(long)]
+    pub wasm_opt_converge: bool,
+
+    /// Passes of `wasm-opt` run after the optimizations (comma separated, without `--`).
+    #[structopt(long, use_delimiter = true)]
+    pub wasm_opt_passes: Vec<String>,
+
+    /// Run `wasm-opt` again up to this number of times when it crashes or fails.
+    #[structopt(long, default_value = "0")]
+    pub wasm_opt_retries: u32,
+
+    /// Do not optimize the WASM of the release and profiling builds with `wasm-opt`.
+    #[structopt(long)]
+    pub no_wasm_opt: bool,
+
+    /// How the WASM handles the panics: `unwind-verbose`, `abort` or `abort-strip` (default: the
+    /// key `panic-strategy` of the metadata of the frontend package).
+    #[structopt(long, possible_values = &["unwind-verbose", "abort", "abort-strip"])]
+    pub panic_strategy: Option<PanicStrategy>,
+
+    /// Generate the TypeScript declarations (`app.d.ts`) in the build directory.
+    #[structopt(long)]
+    pub typescript: bool,
+
+    /// Pass the JS values to the WASM with the reference types instead of the table of the JS
+    /// bindings (the browsers must support them).
+    #[structopt(long)]
+    pub reference_types: bool,
+
+    /// Remove the files of the previous builds that this build did not produce (default for the
+    /// builds that are not rebuilds of the watcher).
+    #[structopt(long, conflicts_with = "no-clean")]
+    pub clean: bool,
+
+    /// Keep the files of the previous builds in the build directory.
+    #[structopt(long)]
+    pub no_clean: bool,
+
+    /// Build in release mode even if `wasm-opt` is not available: the WASM is not optimized.
+    #[structopt(long)]
+    pub allow_unoptimized: bool,
+
+    /// Base name of the generated files: `<name>.js`, `<name>_bg.wasm` and `<name>.d.ts`
+    /// (default: `app`).
+    #[structopt(long)]
+    pub out_name: Option<String>,
+
+    /// Directory of the static files copied to the build directory (default: `static` in the
+    /// frontend directory or in the frontend package).
+    #[structopt(long)]
+    pub static_dir: Option<PathBuf>,
+
+    /// Target of the WASM: `wasm32-unknown-unknown` (the default) or `wasm32-wasi`.
+    #[structopt(
+        long,
+        possible_values = &["wasm32-unknown-unknown", "wasm32-wasi", "wasm32-wasip1"]
+    )]
+    pub target: Option<BuildTarget>,
+
+    /// Layout of the generated files: `flat` (the default), `pkg` to generate an npm package in
+    /// `pkg/` like `wasm-pack` or `node` to generate the bindings for Node.js in `node/`.
+    #[structopt(long, possible_values = &["flat", "pkg", "node"])]
+    pub out_layout: Option<OutLayout>,
+
+    /// Write a `package.json` in the build directory.
+    #[structopt(long)]
+    pub package_json: bool,
+
+    /// Replace the `package.json` even if it has not been written by a build.
+    #[structopt(long)]
+    pub overwrite_package_json: bool,
+
+    /// Command run by the shell after the build to bundle the JS bindings and the snippets.
+    #[structopt(long)]
+    pub bundle_command: Option<String>,
+
+    /// File written by the bundle command, relative to the build directory (can be repeated).
+    #[structopt(long = "bundle-output", parse(from_os_str))]
+    pub bundle_outputs: Vec<PathBuf>,
+
+    /// Directory of the sources of the bundle: their changes only run the bundle command again.
+    #[structopt(long, parse(from_os_str))]
+    pub bundle_sources: Option<PathBuf>,
+
+    /// Write the configuration of a static host in the build directory: `nginx`, `caddy`,
+    /// `netlify` or `vercel`.
+    #[structopt(long, possible_values = &["nginx", "caddy", "netlify", "vercel"])]
+    pub emit_server_config: Option<ServerConfig>,
+
+    /// URL path where the build directory is deployed, used by the server configuration and the
+    /// default `index.html` (default: `/`).
+    #[structopt(long)]
+    pub base_url: Option<String>,
+
+    /// Write the files of the build in an archive: `<path>.tar.gz`, `<path>.tgz` or `<path>.zip`
+    /// (requires the feature `archive`).
+    #[structopt(long, parse(from_os_str))]
+    pub archive: Option<PathBuf>,
+
+    /// Replace the archive if it already exists, archive or deploy the build directory even if
+    /// it contains a dev build.
+    #[structopt(long)]
+    pub force: bool,
+
+    /// Compare the sizes of the files of the build with a previous build: a manifest written by
+    /// `--size-manifest` or a build directory.
+    #[structopt(long, parse(from_os_str))]
+    pub size_compare: Option<PathBuf>,
+
+    /// Fail if the total gzip size grew more than this since the previous build: a size (`10KB`)
+    /// or a percentage (`5%`).
+    #[structopt(long, requires = "size-compare")]
+    pub size_compare_fail: Option<SizeThreshold>,
+
+    /// Format of the comparison of the sizes: `table`, `json` or `markdown`.
+    #[structopt(
+        long,
+        default_value = "table",
+        possible_values = &["table", "json", "markdown"],
+    )]
+    pub size_compare_format: SizeFormat,
+
+    /// Write the sizes of the files of the build in a JSON manifest, for `--size-compare`.
+    #[structopt(long, parse(from_os_str))]
+    pub size_manifest: Option<PathBuf>,
+
+    /// Fail the build if the locales do not define the same messages as the default locale.
+    #[structopt(long)]
+    pub check_i18n: bool,
+
+    /// File of the variables given to the templates of the pages, instead of the `.env` files of
+    /// the workspace and of the frontend package (can be repeated).
+    #[structopt(long = "env-file", parse(from_os_str))]
+    pub env_files: Vec<PathBuf>,
+
+    /// Routes prerendered with a headless Chrome after the release builds (comma separated).
+    #[cfg(feature = "prerender")]
+    #[structopt(long, use_delimiter = true)]
+    pub prerender: Vec<String>,
+
+    /// CSS selector of an element that is present once a prerendered page is ready (default: the
+    /// page sets `window.__wasmbl_prerendered`).
+    #[cfg(feature = "prerender")]
+    #[structopt(long)]
+    pub prerender_ready: Option<String>,
+
+    /// Print the effective settings and where they come from instead of building: `text` (the
+    /// default) or `json`.
+    #[structopt(long, possible_values = &["text", "json"])]
+    pub print_config: Option<Option<ConfigFormat>>,
+
+    /// Write the commands run by the build, the effective settings and the files of the build in
+    /// a JSON file, to report or reproduce what the build did.
+    #[structopt(long, parse(from_os_str))]
+    pub emit_build_plan: Option<PathBuf>,
+
+    /// Print the commands that the build would run instead of building.
+    #[structopt(long)]
+    pub explain: bool,
+
+    /// HTML template with the asset directives of Trunk (`<link data-trunk ...>`), written as the
+    /// `index.html` of the build directory.
+    #[structopt(long, parse(from_os_str))]
+    pub trunk_index: Option<PathBuf>,
+
+    /// Directory of the web assets (the pages, the static files and the styles) if they are not
+    /// in the frontend package. The relative paths of `--static-dir` and `--trunk-index` are
+    /// relative to it.
+    #[structopt(long, parse(from_os_str))]
+    pub frontend_dir: Option<PathBuf>,
+
+    /// Make two builds of the same commit write the same files: the time of the build comes from
+    /// `SOURCE_DATE_EPOCH` or the commit, and `wasm-opt` runs on a single thread.
+    #[structopt(long)]
+    pub reproducible: bool,
+
+    /// Identifier of the release given to the `post_release` hook and to the sourcemap upload
+    /// command (default: the commit of the workspace or the version of the package).
+    #[structopt(long)]
+    pub release_id: Option<String>,
+
+    /// Command run by the shell after a release build for every sourcemap and WASM of the build
+    /// directory, with `{file}`, `{url}` and `{release}` replaced by the path and the URL of the
+    /// file and the identifier of the release.
+    #[structopt(long)]
+    pub sourcemap_upload_command: Option<String>,
+
+    /// Fail the build if the `post_release` hook or the sourcemap upload command fails, instead of
+    /// only warning.
+    #[structopt(long)]
+    pub upload_errors_fatal: bool,
+}
+
+/// A trait that allows overriding the `build` command.
+pub trait BuildArgs: Downcast {
+    /// Build directory output.
+    ///
+    /// The path can be computed, it is resolved once by every step of the pipeline.
+    ///
+    /// Migrating from the previous signature (`fn build_path(&self) -> &PathBuf`): return an owned
+    /// path, for example `self.build_path.clone()`.
+    fn build_path(&self) -> PathBuf;
+
+    /// Default path for the build/public directory.
+    ///
+    /// It follows the frontend package selected with [`BuildArgs::package`] or
+    /// [`BuildArgs::manifest_path`].
+    fn default_build_path(&self) -> &PathBuf {
+        Project::get().default_build_path
+    }
+
+    /// Path to the `target` directory.
+    ///
+    /// It is [`BuildArgs::target_dir`] if it is provided.
+    fn target_path(&self) -> &PathBuf {
+        self.target_dir()
+            .unwrap_or(&self.frontend_metadata().target_directory)
+    }
+
+    /// Directory for all the artifacts of cargo (`--target-dir`). The target directory of the
+    /// workspace of the frontend package is used by default.
+    fn target_dir(&self) -> Option<&PathBuf> {
+        None
+    }
+
+    /// Features of the frontend package to activate (`--features`).
+    fn features(&self) -> &[String] {
+        &[]
+    }
+
+    /// Activate the default features of the frontend package. If `false`,
+    /// `--no-default-features` is given to cargo.
+    fn default_features(&self) -> bool {
+        true
+    }
+
+    /// Activate all the features of the frontend package (`--all-features`).
+    fn all_features(&self) -> bool {
+        false
+    }
+
+    /// Optimization level of `wasm-opt` for the release and profiling builds. The level is 2 by
+    /// default.
+    fn wasm_opt_level(&self) -> Option<u32> {
+        None
+    }
+
+    /// Options of `wasm-opt` for the release and profiling builds.
+    ///
+    /// By default these are the options of the profile ([`WasmOptOptions::for_profile`]) with the
+    /// level of [`BuildArgs::wasm_opt_level`].
+    fn wasm_opt_options(&self, profile: BuildProfile) -> WasmOptOptions {
+        let mut options = WasmOptOptions::for_profile(profile);
+        if let Some(level) = self.wasm_opt_level() {
+            options.level = level;
+        }
+        options
+    }
+
+    /// Optimize the WASM of the release and profiling builds with `wasm-opt`. The WASM is not
+    /// optimized if this is disabled, like when `wasm-opt` is not available.
+    fn wasm_opt(&self) -> bool {
+        true
+    }
+
+    /// How the WASM of a profile handles the panics. The profile of cargo is left as is if there
+    /// is none. See [`PanicStrategy`].
+    ///
+    /// The default is the key `panic-strategy` in the `[package.metadata.wasmbl]` table of the
+    /// frontend package, a strategy or a table of strategies by profile:
+    ///
+    /// ```toml
+    /// [package.metadata.wasmbl.panic-strategy]
+    /// dev = "unwind-verbose"
+    /// release = "abort-strip"
+    /// ```
+    fn panic_strategy(&self, profile: BuildProfile) -> Option<PanicStrategy> {
+        metadata_panic_strategy(self.frontend_package(), profile)
+    }
+
+    /// Generate the TypeScript declarations of the bindings in `app.d.ts` in the build directory.
+    fn typescript(&self) -> bool {
+        false
+    }
+
+    /// Enable the reference types in the JS bindings (`externref`): the JS values are given to the
+    /// WASM as references instead of indexes in a table of the JS bindings, which makes them
+    /// smaller. The browsers must support the reference types. The WASM is compiled with the
+    /// target feature `reference-types` (given in `RUSTFLAGS`) and the feature is also enabled for
+    /// `wasm-opt` so it accepts the WASM.
+    ///
+    /// The JS bindings are always generated with the weak references (`FinalizationRegistry`) if
+    /// the browser supports them, the objects are freed without calling `free()`.
+    ///
+    /// The default is the key `reference-types` in the `[package.metadata.wasmbl]` table of the
+    /// frontend package, or `false`.
+    fn reference_types(&self) -> bool {
+        package_metadata_bool(self.frontend_package(), "reference-types").unwrap_or(false)
+    }
+
+    /// Remove the files of the previous builds that the build did not produce from the build
+    /// directory.
+    ///
+    /// The files produced by the builds are recorded in the file `.wasmbl-files` of the build
+    /// directory and only these can be removed. If the build directory already contained files
+    /// without this list, nothing is removed unless this returns `Some(true)` explicitly: the
+    /// build directory is then cleaned of all the files that the build did not produce.
+    ///
+    /// `None` picks the default: enabled except for the rebuilds of the watcher.
+    fn clean(&self) -> Option<bool> {
+        None
+    }
+
+    /// Continue the release builds with the WASM that is not optimized when `wasm-opt` is not
+    /// available (it could not be downloaded or no optimization feature is enabled).
+    ///
+    /// The profiling builds always continue, with a warning. The release builds fail by default so
+    /// a WASM that is not optimized is not shipped by accident.
+    fn allow_unoptimized(&self) -> bool {
+        false
+    }
+
+    /// Base name of the files generated in the build directory: the JS bindings (`app.js`), the
+    /// WASM (`app_bg.wasm`, or `app.wasm` for WASI) and the TypeScript declarations (`app.d.ts`).
+    ///
+    /// It is given to `wasm-bindgen` so the JS bindings load the WASM with this name. The default
+    /// `index.html` uses it too.
+    fn out_name(&self) -> &str {
+        DEFAULT_OUT_NAME
+    }
+
+    /// Target of the WASM. With [`BuildTarget::Wasi`], the binary of the frontend package is built
+    /// and the JS bindings are not generated.
+    fn target(&self) -> BuildTarget {
+        BuildTarget::Web
+    }
+
+    /// Layout of the generated files. With [`OutLayout::Pkg`], the JS bindings are generated for a
+    /// bundler and the TypeScript declarations are always generated. With [`OutLayout::Node`],
+    /// they are generated for Node.js.
+    fn out_layout(&self) -> OutLayout {
+        OutLayout::Flat
+    }
+
+    /// Write a `package.json` in the build directory so the JS bindings can be imported as a
+    /// module. The layout [`OutLayout::Pkg`] always has one in `pkg/`.
+    ///
+    /// The fields come from the manifest of the frontend package and can be replaced in the table
+    /// `[package.metadata.wasmbl.package-json]`.
+    fn package_json(&self) -> bool {
+        false
+    }
+
+    /// Replace the `package.json` even if it has not been written by a build.
+    fn overwrite_package_json(&self) -> bool {
+        false
+    }
+
+    /// Command run by the shell after the `post_build` hook to bundle the JS bindings, the snippets
+    /// and the sources of the frontend with a JS bundler (`esbuild`, `rollup`, ...).
+    ///
+    /// It runs in the directory of the frontend package with the environment variables of the
+    /// processes started by wasmbl:
+    ///
+    /// - `WASMBL_WORKSPACE_ROOT`: the root of the workspace,
+    /// - `WASMBL_PACKAGE_DIR`: the directory of the frontend package,
+    /// - `WASMBL_PACKAGE_NAME`: the name of the frontend package,
+    /// - `WASMBL_BUILD_PATH`: the build directory,
+    /// - `WASMBL_PROFILE`: `dev`, `release` or `profiling`,
+    ///
+    /// and:
+    ///
+    /// - `WASMBL_JS_GLUE`: the path of the JS bindings,
+    /// - `WASMBL_SNIPPETS_DIR`: the directory of the JS snippets.
+    ///
+    /// The paths are absolute.
+    ///
+    /// The default is the key `bundle-command` in the `[package.metadata.wasmbl]` table of the
+    /// frontend package.
+    fn bundle_command(&self) -> Option<String> {
+        package_metadata_string(self.frontend_package(), "bundle-command")
+    }
+
+    /// Files written by [`BuildArgs::bundle_command`], relative to the build directory. They are
+    /// given to the [`Hooks::asset_filter`] hook like the other files of the build.
+    ///
+    /// The default is the key `bundle-outputs` in the `[package.metadata.wasmbl]` table of the
+    /// frontend package.
+    fn bundle_outputs(&self) -> Vec<PathBuf> {
+        package_metadata_strings(self.frontend_package(), "bundle-outputs")
+            .map(|x| x.into_iter().map(PathBuf::from).collect())
+            .unwrap_or_default()
+    }
+
+    /// Directory of the sources of the bundle (TypeScript, JS, ...). While serving, their changes
+    /// only run [`BuildArgs::bundle_command`] again, cargo is not invoked.
+    ///
+    /// The default is the key `bundle-sources` in the `[package.metadata.wasmbl]` table, relative
+    /// to the frontend package.
+    fn bundle_sources(&self) -> Option<PathBuf> {
+        let package = self.frontend_package();
+        package_metadata_string(package, "bundle-sources")
+            .map(|x| package.manifest_path.parent().unwrap().join(x))
+    }
+
+    /// Write the configuration of a static host in the build directory at the end of the build:
+    /// the WASM served as `application/wasm`, the files renamed by the [`Hooks::asset_filter`] hook
+    /// cached forever, the precompressed files (`.br`, `.gz`) and the fallback to `index.html`.
+    /// See [`ServerConfig`] for the files written.
+    fn emit_server_config(&self) -> Option<ServerConfig> {
+        None
+    }
+
+    /// URL path where the build directory is deployed, used by
+    /// [`BuildArgs::emit_server_config`] and by the default `index.html` to import the JS bindings.
+    ///
+    /// The default is the key `base-url` in the `[package.metadata.wasmbl]` table of the frontend
+    /// package, or `/`.
+    fn base_url(&self) -> String {
+        package_metadata_string(self.frontend_package(), "base-url").unwrap_or_else(|| "/".into())
+    }
+
+    /// Write the files of the `build` command in an archive (`.tar.gz`, `.tgz` or `.zip`, given by
+    /// the extension) and print its SHA-256.
+    ///
+    /// Only the files of the build are archived, not the stale files of the build directory. The
+    /// archive is reproducible: the entries are sorted and their modification times are zeroed.
+    ///
+    /// The `build` command fails if the feature `archive` is not enabled.
+    fn archive(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Replace the archive of [`BuildArgs::archive`] if it already exists. The build directory is
+    /// archived or deployed even if a dev build replaced the release build in the meantime (like
+    /// the rebuilds of a `serve` in the background).
+    fn force(&self) -> bool {
+        false
+    }
+
+    /// Compare the raw and gzip sizes of the files of the `build` command with a previous build
+    /// and print the files added, removed and changed with the total. The previous build is a
+    /// manifest of [`BuildArgs::size_manifest`] or a build directory, read before the build (it
+    /// can be the build directory itself).
+    fn size_compare(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Fail the `build` command if the total gzip size grew more than this since the build of
+    /// [`BuildArgs::size_compare`].
+    fn size_compare_fail(&self) -> Option<SizeThreshold> {
+        None
+    }
+
+    /// The format of the comparison of [`BuildArgs::size_compare`], printed on the standard
+    /// output.
+    fn size_compare_format(&self) -> SizeFormat {
+        SizeFormat::Table
+    }
+
+    /// Write the raw, gzip and brotli sizes of the files of the `build` command in a JSON
+    /// manifest, to compare the next builds with [`BuildArgs::size_compare`] or chart them.
+    fn size_manifest(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Routes rendered to static HTML after the release builds, for the search engines: the build
+    /// directory is served on a local port, each route is loaded by a headless Chrome and its DOM
+    /// is written to `<route>/index.html` in the build directory once the page is ready (see
+    /// [`BuildArgs::prerender_ready`]).
+    ///
+    /// Chrome is looked up in `PATH` or given with the environment variable `CHROME`. The
+    /// prerendering is skipped with a warning if it is not found.
+    ///
+    /// The default is the key `prerender` in the `[package.metadata.wasmbl]` table of the frontend
+    /// package.
+    #[cfg(feature = "prerender")]
+    fn prerender(&self) -> Vec<String> {
+        package_metadata_strings(self.frontend_package(), "prerender").unwrap_or_default()
+    }
+
+    /// CSS selector of an element that is present once a prerendered page is ready. Without a
+    /// selector, the page is ready when the app sets `window.__wasmbl_prerendered = true`.
+    ///
+    /// The default is the key `prerender-ready` in the `[package.metadata.wasmbl]` table of the
+    /// frontend package.
+    #[cfg(feature = "prerender")]
+    fn prerender_ready(&self) -> Option<String> {
+        package_metadata_string(self.frontend_package(), "prerender-ready")
+    }
+
+    /// The localization files of Fluent copied to `locales/<locale>/` in the build directory by
+    /// the default `post_build` hook, after the check of their syntax. They are listed with their
+    /// SHA-256 in `locales/manifest.json`.
+    ///
+    /// The default is the table `[package.metadata.wasmbl.i18n]` of the frontend package, with
+    /// the keys `locales` (the directory of the locales, `locales` by default) and
+    /// `default-locale` (`en` by default). There are no localization files without this table.
+    fn i18n(&self) -> Option<I18n> {
+        I18n::from_metadata(self.frontend_package())
+    }
+
+    /// Fail the build if the locales of [`BuildArgs::i18n`] do not define the same messages as
+    /// the default locale.
+    fn check_i18n(&self) -> bool {
+        false
+    }
+
+    /// Print the effective settings of the command (the build directory, the profile, the options
+    /// of `wasm-opt`, the address of the development server, ...) with where each of them comes
+    /// from (a flag, the `[package.metadata.wasmbl]` table, the macro, a hook or the default) in
+    /// this format, instead of running it. Nothing is built.
+    fn print_config(&self) -> Option<ConfigFormat> {
+        None
+    }
+
+    /// Write the plan of the `build` command in this JSON file, even if the build fails: the
+    /// external commands it ran (their program, arguments, environment variables, directory, exit
+    /// code and duration), the effective settings of [`BuildArgs::print_config`] and the files of
+    /// the build with their sizes and SHA-256 checksums.
+    ///
+    /// Only the names of the environment variables that wasmbl does not set itself are written
+    /// (the variables added by the `pre_build` hook, ...), not their values.
+    fn emit_build_plan(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Print the external commands that the `build` command would run (cargo, `wasm-opt` and
+    /// [`BuildArgs::bundle_command`]) instead of running it. Nothing is built and the hooks are not
+    /// run: the changes of the `pre_build` hook to the cargo command are not shown.
+    fn explain(&self) -> bool {
+        false
+    }
+
+    /// The pages of a multi-page app: HTML templates rendered by the default `post_build` hook at
+    /// their paths in the build directory, after the static files. The templates are rendered
+    /// with Tera and get the variables `base_url`, `js` and `wasm` (the URLs of the JS bindings
+    /// and of the WASM, after the renames of the [`Hooks::asset_filter`] hook) and `url` (the URL
+    /// of the page). The default `index.html` is not written if a page is written there.
+    ///
+    /// The variables of the environment and of [`BuildArgs::env_files`] that start with
+    /// [`BuildArgs::public_env_prefix`] are given to the templates in `env`:
+    /// `{{ env.PUBLIC_ANALYTICS_ID }}`. The build fails if a template uses a variable that is not
+    /// defined, unless it has a fallback (`{{ env.PUBLIC_ID | default(value="") }}`).
+    ///
+    /// The development server falls back to the `index.html` of the closest directory of the
+    /// path: `/admin/settings` gets `admin/index.html` if it exists.
+    ///
+    /// The default is the key `pages` in the `[package.metadata.wasmbl]` table of the frontend
+    /// package: a directory (relative to the package, or to [`BuildArgs::frontend_dir`]) of which
+    /// every `.html` file is a page at the same path, or a table of the routes and their
+    /// templates (`"/admin/" = "admin.html"`). Without this key, the directory `pages` of the
+    /// frontend package (or of [`BuildArgs::frontend_dir`]) if it exists.
+    fn pages(&self) -> Vec<Page> {
+        Page::from_metadata(self.frontend_package(), &frontend_root(self))
+    }
+
+    /// The `.env` files of the variables of the templates of [`BuildArgs::pages`] (`NAME=value`
+    /// lines), the last ones take precedence and the environment takes precedence over all of
+    /// them. The files that do not exist are ignored.
+    ///
+    /// The default is the `.env` file of the workspace, then the one of the frontend package.
+    fn env_files(&self) -> Vec<PathBuf> {
+        env_file::default_files(self.metadata(), self.frontend_package())
+    }
+
+    /// The prefix of the variables given to the templates of [`BuildArgs::pages`]: the other
+    /// variables are not public and can't be used by the templates.
+    ///
+    /// The default is the key `public-env-prefix` in the `[package.metadata.wasmbl]` table of the
+    /// frontend package, or `PUBLIC_`.
+    fn public_env_prefix(&self) -> String {
+        package_metadata_string(self.frontend_package(), "public-env-prefix")
+            .unwrap_or_else(|| env_file::DEFAULT_PREFIX.to_string())
+    }
+
+    /// Directory of the static files copied to the build directory by the default `post_build`
+    /// hook when there is no `index.html`.
+    ///
+    /// The default is `static` in [`BuildArgs::frontend_dir`] or in the frontend package.
+    fn static_dir(&self) -> PathBuf {
+        frontend_root(self).join("static")
+    }
+
+    /// Directory of the web assets when they are kept apart from the Rust crate, like a `web/`
+    /// directory at the root of the repository. The default locations of the pages, of the static
+    /// files, of the styles and of the Trunk template are in this directory instead of the
+    /// frontend package, and it is watched while serving: its changes only run the `post_build`
+    /// hook. The sources of the crate stay where the metadata of cargo says.
+    ///
+    /// The default is the key `frontend-dir` in the `[package.metadata.wasmbl]` table of the
+    /// frontend package (relative to the package), none otherwise.
+    fn frontend_dir(&self) -> Option<PathBuf> {
+        let package = self.frontend_package();
+        package_metadata_string(package, "frontend-dir")
+            .map(|x| package.manifest_path.parent().unwrap().join(x))
+    }
+
+    /// Build the same files for two builds of the same commit, for the attestations of the
+    /// supply chain:
+    ///
+    /// - the time of the build, in the record of the build directory and in `SOURCE_DATE_EPOCH`
+    ///   given to cargo (the build scripts) and to the other processes of the build, is
+    ///   `SOURCE_DATE_EPOCH` if it is set, else the time of the commit `HEAD`,
+    /// - `wasm-opt` runs its passes on a single thread (see [`WasmOptOptions::single_threaded`]).
+    ///
+    /// The JSON files (the manifest of the locales, the record of the build) have their keys
+    /// sorted, the files are listed in order and the archives have no modification times in every
+    /// build. The files still depend on the toolchain: the versions of rustc, of the dependencies
+    /// (commit the `Cargo.lock`) and of binaryen, the absolute paths of the workspace and of the
+    /// registry of cargo in the panic messages (build in the same directories or add
+    /// `--remap-path-prefix` to `RUSTFLAGS`) and the output of the bundle command. The build plan
+    /// and the size report record how long the build took, they are not part of the build.
+    ///
+    /// The default is the key `reproducible` in the `[package.metadata.wasmbl]` table of the
+    /// frontend package, or `false`.
+    fn reproducible(&self) -> bool {
+        package_metadata_bool(self.frontend_package(), "reproducible").unwrap_or(false)
+    }
+
+    /// Identifier of the release given to the [`Hooks::post_release`] hook and to
+    /// [`BuildArgs::sourcemap_upload_command`], like the version given by a CI.
+    ///
+    /// The default is the commit `HEAD` of the workspace, or the version of the frontend package
+    /// outside of a git repository.
+    fn release_id(&self) -> Option<String> {
+        None
+    }
+
+    /// A command run by the shell (`sh -c`, `cmd /C` on Windows) after a release build of the
+    /// `build` command, once for every sourcemap (`.map`) and every WASM of the build directory,
+    /// to upload them to an error tracker. The placeholders are replaced, quoted for the shell:
+    ///
+    /// - `{file}`: the path of the file,
+    /// - `{url}`: its URL, the base URL followed by its path in the build directory,
+    /// - `{release}`: the identifier of the release (see [`BuildArgs::release_id`]).
+    ///
+    /// It runs in the directory of the frontend package with the variables of
+    /// [`BuildArgs::bundle_command`], after the [`Hooks::post_release`] hook. Its failures are
+    /// warnings unless [`BuildArgs::upload_errors_fatal`].
+    ///
+    /// The default is the key `sourcemap-upload-command` in the `[package.metadata.wasmbl]`
+    /// table of the frontend package.
+    fn sourcemap_upload_command(&self) -> Option<String> {
+        package_metadata_string(self.frontend_package(), "sourcemap-upload-command")
+    }
+
+    /// Fail the build if the [`Hooks::post_release`] hook or
+    /// [`BuildArgs::sourcemap_upload_command`] fails. They are only warnings otherwise, so a flaky
+    /// uploader doesn't block the release.
+    ///
+    /// The default is the key `upload-errors-fatal` in the `[package.metadata.wasmbl]` table of
+    /// the frontend package, or `false`.
+    fn upload_errors_fatal(&self) -> bool {
+        package_metadata_bool(self.frontend_package(), "upload-errors-fatal").unwrap_or(false)
+    }
+
+    /// An HTML template with the asset directives of Trunk, for the projects that migrate from
+    /// Trunk: the default `post_build` hook writes it as the `index.html` of the build directory,
+    /// after the static files and the pages, with the files of its directives. The tags
+    /// `<link data-trunk rel="..." href="...">` with these values of `rel` are supported (`href` is
+    /// relative to the template):
+    ///
+    /// - `rust`: replaced with the script that loads the JS bindings and the WASM of the build (the
+    ///   frontend package is built, whatever `href` is),
+    /// - `scss`: the file is compiled to CSS (with the feature `sass`) and replaced with a
+    ///   stylesheet,
+    /// - `css`: the file is copied and replaced with a stylesheet,
+    /// - `icon`: the file is copied and replaced with an icon,
+    /// - `copy-file`: the file is copied to the build directory (or to its `data-target-path`),
+    /// - `copy-dir`: the directory is copied to the build directory (or to its
+    ///   `data-target-path`).
+    ///
+    /// The copied files are given to the [`Hooks::asset_filter`] hook: the tags refer to their
+    /// final names (with the hashes the hook adds for example). The other attributes of the tags
+    /// (`media`, `sizes`, ...) are kept. Any other directive fails the build with its line.
+    ///
+    /// The default is the key `trunk-index` in the `[package.metadata.wasmbl]` table of the
+    /// frontend package (relative to the package, or to [`BuildArgs::frontend_dir`]), no template
+    /// otherwise.
+    fn trunk_index(&self) -> Option<PathBuf> {
+        package_metadata_string(self.frontend_package(), "trunk-index")
+            .map(|x| frontend_root(self).join(x))
+    }
+
+    /// Metadata of the project.
+    ///
+    /// The metadata is reloaded when a manifest changes while serving.
+    fn metadata(&self) -> &Metadata {
+        Project::get().metadata
+    }
+
+    /// Metadata of the workspace of the frontend package.
+    ///
+    /// It is [`BuildArgs::metadata`] unless the frontend package is excluded from the workspace
+    /// of the runner or lives in a nested workspace.
+    fn frontend_metadata(&self) -> &Metadata {
+        Project::get().frontend_metadata
+    }
+
+    /// Package metadata.
+    fn frontend_package(&self) -> &Package {
+        Project::get().frontend_package
+    }
+
+    /// Backend frontend_package metadata.
+    fn backend_package(&self) -> Option<&Package> {
+        Project::get().backend_package
+    }
+
+    /// Create a profiling build. Enable optimizations and debug info.
+    fn profiling(&self) -> bool;
+
+    /// Path to the manifest of the frontend package, overriding the package given to the macro.
+    ///
+    /// A relative path is resolved against the workspace root.
+    fn manifest_path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Name of the frontend package, overriding the package given to the macro.
+    ///
+    /// The package must be a member of the workspace.
+    fn package(&self) -> Option<&str> {
+        None
+    }
+
+    /// Transpile SASS and SCSS files to CSS in the build directory, with the
+    /// [`Hooks::asset_filter`] of the runner.
+    #[cfg(feature = "sass")]
+    fn build_sass_from_dir(
+        &self,
+        input_dir: &std::path::Path,
+        options: sass_rs::Options,
+    ) -> Result<()> {
+        use walkdir::{DirEntry, WalkDir};
+
+        let build_path = self.build_path();
+
+        fn is_sass(entry: &DirEntry) -> bool {
+            matches!(
+                entry.path().extension().and_then(|x| x.to_str()),
+                Some("sass") | Some("scss")
+            )
+        }
+
+        fn should_ignore(entry: &DirEntry) -> bool {
+            entry.file_name().as_encoded_bytes().starts_with(b"_")
+        }
+
+        log::info!("Building SASS from {:?}", input_dir);
+
+        let walker = WalkDir::new(input_dir).into_iter();
+        for entry in walker
+            .filter_map(|x| match x {
+                Ok(x) => Some(x),
+                Err(err) => {
+                    log::warn!(
+                        "Could not walk into directory `{}`: {}",
+                        input_dir.display(),
+                        err,
+                    );
+                    None
+                }
+            })
+            .filter(|x| x.path().is_file() && is_sass(x) && !should_ignore(x))
+        {
+            let file_path = entry.path();
+            let relative_path = file_path.strip_prefix(input_dir).unwrap();
+            let css_path = build_path.join(relative_path).with_extension("css");
+
+            match sass_rs::compile_file(file_path, options.clone()) {
+                Ok(css) => {
+                    let name = input_dir
+                        .file_name()
+                        .map(|x| Path::new(x).join(relative_path))
+                        .unwrap_or_else(|| relative_path.to_owned());
+                    let asset = Asset::new(
+                        relative_path.with_extension("css"),
+                        slash_path(&name),
+                        css.into_bytes(),
+                    );
+                    write_asset(&build_path, hooks()?, asset).with_context(|| {
+                        format!("could not write CSS to file `{}`", css_path.display())
+                    })?;
+                }
+                Err(err) => bail!(
+                    "could not convert SASS file `{}` to `{}`: {}",
+                    file_path.display(),
+                    css_path.display(),
+                    err,
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a list of directories to lookup to transpile SASS and SCSS files to CSS.
+    #[cfg(feature = "sass")]
+    fn sass_lookup_directories(&self, _profile: BuildProfile) -> Vec<PathBuf> {
+        const STYLE_CANDIDATES: &[&str] = &["assets", "styles", "css", "sass"];
+
+        let root = frontend_root(self);
+
+        STYLE_CANDIDATES
+            .iter()
+            .map(|x| root.join(x))
+            .filter(|x| x.exists())
+            .collect()
+    }
+
+    /// Default profile to transpile SASS and SCSS files to CSS.
+    #[cfg(feature = "sass")]
+    fn sass_options(&self, profile: BuildProfile) -> sass_rs::Options {
+        sass_rs::Options {
+            output_style: match profile {
+                BuildProfile::Release | BuildProfile::Profiling => sass_rs::OutputStyle::Compressed,
+                _ => sass_rs::OutputStyle::Nested,
+            },
+            ..sass_rs::Options::default()
+        }
+    }
+
+    /// Run the `build` command.
+    fn run(self) -> Result<PathBuf, Error>
+    where
+        Self: Sized + 'static,
+    {
+        let hooks = hooks()?;
+        Project::select_frontend(&self)?;
+        if let Some(format) = self.print_config() {
+            config::print(&config::resolve(&self, None, hooks), format);
+            return Ok(self.build_path());
+        }
+        if self.explain() {
+            build_plan::explain(&self)?;
+            return Ok(self.build_path());
+        }
+
+        // NOTE: fail early rather than after the build
+        let build_path = self.build_path();
+        #[cfg(feature = "archive")]
+        let archive = match self.archive() {
+            Some(path) => Some((archive::check(&path, &build_path, self.force())?, path)),
+            None => None,
+        };
+        #[cfg(not(feature = "archive"))]
+        if self.archive().is_some() {
+            return Err(
+                anyhow!("writing an archive requires the feature `archive` of wasmbl").into(),
+            );
+        }
+        // NOTE: read before the build so it can be the build directory itself
+        let previous_sizes = match self.size_compare() {
+            Some(path) => Some(size_report::Sizes::load(&path)?),
+            None => None,
+        };
+
+        let plan = self.emit_build_plan();
+        if plan.is_some() {
+            build_plan::record();
+        }
+        let start = Instant::now();
+        let res = build(
+            BuildProfile::Release,
+            &self,
+            hooks,
+            false,
+            BuildTrigger::Build,
+        );
+        if let Some(path) = plan {
+            let written = build_plan::write(&path, &self, hooks, start, res.as_ref().err());
+            res?;
+            written?;
+        } else {
+            res?;
+        }
+
+        #[cfg(feature = "archive")]
+        if let Some((format, path)) = archive {
+            build_meta::check_shippable(&build_path, self.force())?;
+            let sha256 = archive::write(&path, format, &build_path)?;
+            println!("{}  {}", sha256, path.display());
+        }
+
+        let size_manifest = self.size_manifest();
+        if previous_sizes.is_some() || size_manifest.is_some() {
+            let sizes = match size_report::Sizes::last_build() {
+                Some(sizes) => sizes,
+                None => size_report::Sizes::of_build(&build_path, true)?,
+            };
+            if let Some(path) = size_manifest {
+                fs::write(&path, sizes.to_manifest()).map_err(|source| Error::Io {
+                    path: path.clone(),
+                    source,
+                })?;
+                log::info!("Wrote the sizes of the build to `{}`", path.display());
+            }
+            if let Some(previous) = previous_sizes {
+                let report = size_report::Report::new(&previous, &sizes);
+                print!("{}", report.render(self.size_compare_format()));
+                if let Some(threshold) = self.size_compare_fail() {
+                    report.check(threshold)?;
+                }
+            }
+        }
+
+        release::upload(&self, hooks, resolve_profile(BuildProfile::Release, &self))?;
+
+        Ok(build_path)
+    }
+}
+
+impl_downcast!(BuildArgs);
+
+impl BuildArgs for DefaultBuildArgs {
+    fn build_path(&self) -> PathBuf {
+        match self.build_path.as_deref() {
+            Some(path) => absolute_path(path),
+            None => self.default_build_path().to_owned(),
+        }
+    }
+
+    fn profiling(&self) -> bool {
+        self.profiling
+    }
+
+    fn manifest_path(&self) -> Option<&Path> {
+        self.manifest_path.as_deref()
+    }
+
+    fn package(&self) -> Option<&str> {
+        self.package.as_deref()
+    }
+
+    fn target_dir(&self) -> Option<&PathBuf> {
+        self.target_dir.as_ref()
+    }
+
+    fn features(&self) -> &[String] {
+        &self.features
+    }
+
+    fn default_features(&self) -> bool {
+        !self.no_default_features
+    }
+
+    fn all_features(&self) -> bool {
+        self.all_features
+    }
+
+    fn wasm_opt_level(&self) -> Option<u32> {
+        self.wasm_opt_level
+    }
+
+    fn wasm_opt_options(&self, profile: BuildProfile) -> WasmOptOptions {
+        let mut options = WasmOptOptions::for_profile(profile);
+        if let Some(level) = self.wasm_opt_level {
+            options.level = level;
+        }
+        if let Some(shrink_level) = self.wasm_opt_shrink_level {
+            options.shrink_level = shrink_level;
+        }
+        options.converge = self.wasm_opt_converge;
+        options.extra_passes = self
+            .wasm_opt_passes
+            .iter()
+            .filter(|x| !x.is_empty())
+            .cloned()
+            .collect();
+        options.retries = self.wasm_opt_retries;
+        options
+    }
+
+    fn panic_strategy(&self, profile: BuildProfile) -> Option<PanicStrategy> {
+        self.panic_strategy
+            .or_else(|| metadata_panic_strategy(self.frontend_package(), profile))
+    }
+
+    fn wasm_opt(&self) -> bool {
+        !self.no_wasm_opt
+    }
+
+    fn typescript(&self) -> bool {
+        self.typescript
+    }
+
+    fn reference_types(&self) -> bool {
+        self.reference_types
+            || package_metadata_bool(self.frontend_package(), "reference-types").unwrap_or(false)
+    }
+
+    fn out_name(&self) -> &str {
+        self.out_name.as_deref().unwrap_or(DEFAULT_OUT_NAME)
+    }
+
+    fn clean(&self) -> Option<bool> {
+        match (self.clean, self.no_clean) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+
+    fn allow_unoptimized(&self) -> bool {
+        self.allow_unoptimized
+    }
+
+    fn target(&self) -> BuildTarget {
+        self.target.unwrap_or_default()
+    }
+
+    fn out_layout(&self) -> OutLayout {
+        self.out_layout.unwrap_or_default()
+    }
+
+    fn package_json(&self) -> bool {
+        self.package_json
+    }
+
+    fn overwrite_package_json(&self) -> bool {
+        self.overwrite_package_json
+    }
+
+    fn bundle_command(&self) -> Option<String> {
+        self.bundle_command
+            .clone()
+            .or_else(|| package_metadata_string(self.frontend_package(), "bundle-command"))
+    }
+
+    fn bundle_outputs(&self) -> Vec<PathBuf> {
+        if !self.bundle_outputs.is_empty() {
+            return self.bundle_outputs.clone();
+        }
+        package_metadata_strings(self.frontend_package(), "bundle-outputs")
+            .map(|x| x.into_iter().map(PathBuf::from).collect())
+            .unwrap_or_default()
+    }
+
+    fn emit_server_config(&self) -> Option<ServerConfig> {
+        self.emit_server_config
+    }
+
+    fn archive(&self) -> Option<PathBuf> {
+        self.archive.clone()
+    }
+
+    fn force(&self) -> bool {
+        self.force
+    }
+
+    fn size_compare(&self) -> Option<PathBuf> {
+        self.size_compare.clone()
+    }
+
+    fn size_compare_fail(&self) -> Option<SizeThreshold> {
+        self.size_compare_fail
+    }
+
+    fn size_compare_format(&self) -> SizeFormat {
+        self.size_compare_format
+    }
+
+    fn size_manifest(&self) -> Option<PathBuf> {
+        self.size_manifest.clone()
+    }
+
+    fn check_i18n(&self) -> bool {
+        self.check_i18n
+    }
+
+    fn print_config(&self) -> Option<ConfigFormat> {
+        self.print_config.map(Option::unwrap_or_default)
+    }
+
+    fn emit_build_plan(&self) -> Option<PathBuf> {
+        self.emit_build_plan.clone()
+    }
+
+    fn explain(&self) -> bool {
+        self.explain
+    }
+
+    fn trunk_index(&self) -> Option<PathBuf> {
+        match &self.trunk_index {
+            Some(path) => Some(frontend_path(self, path)),
+            None => package_metadata_string(self.frontend_package(), "trunk-index")
+                .map(|x| frontend_root(self).join(x)),
+        }
+    }
+
+    fn reproducible(&self) -> bool {
+        self.reproducible
+            || package_metadata_bool(self.frontend_package(), "reproducible").unwrap_or(false)
+    }
+
+    fn release_id(&self) -> Option<String> {
+        self.release_id.clone()
+    }
+
+    fn sourcemap_upload_command(&self) -> Option<String> {
+        self.sourcemap_upload_command.clone().or_else(|| {
+            package_metadata_string(self.frontend_package(), "sourcemap-upload-command")
+        })
+    }
+
+    fn upload_errors_fatal(&self) -> bool {
+        self.upload_errors_fatal
+            || package_metadata_bool(self.frontend_package(), "upload-errors-fatal")
+                .unwrap_or(false)
+    }
+
+    fn frontend_dir(&self) -> Option<PathBuf> {
+        let package = self.frontend_package();
+        self.frontend_dir.clone().or_else(|| {
+            package_metadata_string(package, "frontend-dir")
+                .map(|x| package.manifest_path.parent().unwrap().join(x))
+        })
+    }
+
+    fn env_files(&self) -> Vec<PathBuf> {
+        if !self.env_files.is_empty() {
+            return self.env_files.clone();
+        }
+        env_file::default_files(self.metadata(), self.frontend_package())
+    }
+
+    #[cfg(feature = "prerender")]
+    fn prerender(&self) -> Vec<String> {
+        if !self.prerender.is_empty() {
+            return self.prerender.clone();
+        }
+        package_metadata_strings(self.frontend_package(), "prerender").unwrap_or_default()
+    }
+
+    #[cfg(feature = "prerender")]
+    fn prerender_ready(&self) -> Option<String> {
+        self.prerender_ready
+            .clone()
+            .or_else(|| package_metadata_string(self.frontend_package(), "prerender-ready"))
+    }
+
+    fn base_url(&self) -> String {
+        self.base_url.clone().unwrap_or_else(|| {
+            package_metadata_string(self.frontend_package(), "base-url")
+                .unwrap_or_else(|| "/".into())
+        })
+    }
+
+    fn bundle_sources(&self) -> Option<PathBuf> {
+        let package = self.frontend_package();
+        self.bundle_sources.clone().or_else(|| {
+            package_metadata_string(package, "bundle-sources")
+                .map(|x| package.manifest_path.parent().unwrap().join(x))
+        })
+    }
+
+    fn static_dir(&self) -> PathBuf {
+        match &self.static_dir {
+            Some(path) => frontend_path(self, path),
+            None => frontend_root(self).join("static"),
+        }
+    }
+}
+
+/// Serve arguments.
+#[derive(StructOpt, Debug)]
+pub struct DefaultServeArgs {
+    /// Activate HTTP logs.
+    #[structopt(long)]
+    pub log: bool,
+
+    /// IP address to bind.
+    ///
+    /// Use 0.0.0.0 (or :: for IPv6) to expose the server to your network.
+    #[structopt(long, short = "h", default_value = "127.0.0.1")]
+    pub ip: IpAddr,
+
+    /// Port number.
+    #[structopt(long, short = "p", default_value = "3000")]
+    pub port: u16,
+
+    /// Address to listen on instead of `--ip`: an IP address (with the port of `--port`) or an IP
+    /// address and a port (`[::1]:3000`). Can be repeated to listen on several addresses.
+    #[structopt(long, validator = validate_bind_addr)]
+    pub bind: Vec<String>,
+
+    /// Do not reload the browser after the rebuilds.
+    #[structopt(long)]
+    pub no_live_reload: bool,
+
+    /// Enable the control API of the development server: `POST /__wasmbl/reload`,
+    /// `POST /__wasmbl/rebuild` and `GET /__wasmbl/status`, for the requests of this machine only.
+    #[structopt(long)]
+    pub control_api: bool,
+
+    /// Serve the Rust sources to the debuggers of the browsers at `/__wasmbl/sources/` (dev builds
+    /// only).
+    #[structopt(long)]
+    pub serve_sources: bool,
+
+    /// List the files of the directories that have no `index.html`.
+    #[structopt(long)]
+    pub dir_listing: bool,
+
+    /// Name of the index documents of the directories (default: `index.html`).
+    #[structopt(long, validator = validate_index_document)]
+    pub index_document: Option<String>,
+
+    /// Serve `<path>.html` for the paths without extension that are not files, like the static
+    /// hosts with extensionless URLs (`/about` serves `about.html`).
+    #[structopt(long)]
+    pub extensionless_html: bool,
+
+    /// Redirect the URLs of the pages to the ones that end with a `/` (`add`), to the ones that
+    /// don't (`remove`) or don't redirect (`keep`, the default).
+    #[structopt(long, possible_values = &["keep", "add", "remove"])]
+    pub trailing_slash: Option<TrailingSlash>,
+
+    /// Build in release mode, with optimizations (`--profiling` takes precedence).
+    #[structopt(long)]
+    pub release: bool,
+
+    /// Poll the file system for changes at the given interval (default: 1s) instead of using the
+    /// native events of the platform.
+    ///
+    /// This is useful when the events are not available, for example with Docker volumes or
+    /// network file systems.
+    #[structopt(long, validator = validate_duration)]
+    pub poll: Option<Option<String>>,
+
+    /// Clear the terminal before every rebuild and print a status line at the end.
+    #[structopt(long)]
+    pub clear: bool,
+
+    /// Show the full output of the rebuilds when `--clear` is used.
+    #[structopt(long)]
+    pub verbose: bool,
+
+    /// Send a desktop notification when a rebuild finishes (requires the feature
+    /// `desktop-notifications`).
+    #[structopt(long)]
+    pub notify: bool,
+
+    /// Run `cargo check` before every rebuild and skip the full build if it fails.
+    #[structopt(long)]
+    pub check_first: bool,
+
+    /// Do not use the `.gitignore` and `.ignore` files to filter out the changes detected by the
+    /// watcher.
+    #[structopt(long)]
+    pub no_gitignore: bool,
+
+    /// Delay during which the native events of the watcher are debounced (default: 2s).
+    #[structopt(long, validator = validate_duration)]
+    pub debounce: Option<String>,
+
+    /// Additional path to watch (can be repeated).
+    #[structopt(long = "watch-path", parse(from_os_str))]
+    pub watch_paths: Vec<PathBuf>,
+
+    /// Ignore the changes on the paths that match this pattern, with the syntax of `.gitignore`
+    /// relative to the workspace (can be repeated).
+    #[structopt(long = "ignore")]
+    pub ignore_globs: Vec<String>,
+
+    /// Build arguments.
+    #[structopt(flatten)]
+    pub build_args: DefaultBuildArgs,
+}
+
+/// A trait that allows configuring the file watcher of the `serve` command.
+///
+/// It is a supertrait of [`ServeArgs`] but it can be implemented by the arguments of any command
+/// that watches the files.
+pub trait WatchArgs {
+    /// Delay during which the native events are debounced. The default is 2 seconds.
+    fn debounce(&self) -> Duration {
+        watcher::DEFAULT_DEBOUNCE_DELAY
+    }
+
+    /// Paths watched in addition to the paths registered by the watch hooks. Directories are
+    /// watched recursively.
+    ///
+    /// Their changes are classified by extension like the others.
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Patterns of the paths whose changes are ignored, with the syntax of `.gitignore` relative
+    /// to the root of the workspace.
+    ///
+    /// They apply even if [`WatchArgs::gitignore`] is disabled.
+    fn ignore_globs(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Poll the file system for changes at this interval instead of relying on the native events
+    /// of the platform.
+    ///
+    /// The watcher falls back to polling anyway if the native watcher can not be initialized.
+    fn poll_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Ignore the changes on files that are ignored by a `.gitignore` or a `.ignore` file.
+    fn gitignore(&self) -> bool {
+        true
+    }
+}
+
+/// A trait that allows overriding the `serve` command.
+///
+/// The file watcher is configured by the supertrait [`WatchArgs`].
+pub trait ServeArgs: WatchArgs + Downcast + Send {
+    /// Activate HTTP logs.
+    ///
+    /// The requests are logged with the other logs, at the `info` level.
+    #[cfg(feature = "dev-server")]
+    fn log(&self) -> bool;
+
+    /// IP address to bind, IPv4 or IPv6.
+    ///
+    /// Use 0.0.0.0 (or :: for IPv6) to expose the server to your network.
+    #[cfg(feature = "dev-server")]
+    fn ip(&self) -> IpAddr;
+
+    /// Port number.
+    #[cfg(feature = "dev-server")]
+    fn port(&self) -> u16;
+
+    /// The addresses the development server listens on, all at once (like `::1` and `127.0.0.1`
+    /// for the tools that resolve `localhost` differently). The URL of each address is printed
+    /// when the server starts.
+    ///
+    /// By default the address of [`ServeArgs::ip`] and [`ServeArgs::port`].
+    #[cfg(feature = "dev-server")]
+    fn bind(&self) -> Vec<SocketAddr> {
+        vec![SocketAddr::new(self.ip(), self.port())]
+    }
+
+    /// Reload the browser after every successful rebuild, or only replace the stylesheets if they
+    /// are the only files of the build directory that changed. If a rebuild fails, the error is
+    /// shown in an overlay instead.
+    #[cfg(feature = "dev-server")]
+    fn live_reload(&self) -> bool {
+        true
+    }
+
+    /// Enable the control API of the development server, so the external tools can reload the
+    /// browsers or trigger a rebuild. Only the requests from a loopback address are accepted, with
+    /// a `Host` header naming the server on a loopback address and, if any, an `Origin` header of
+    /// the server itself (so the pages of other sites cannot use it from a browser):
+    ///
+    ///  -  `POST /__wasmbl/reload` reloads the connected browsers;
+    ///  -  `POST /__wasmbl/rebuild` enqueues a rebuild, like a change of a source file;
+    ///  -  `GET /__wasmbl/status` returns the outcome of the last build in JSON:
+    ///     `{"status": "success", "queue": "idle", "error": null, "duration": 1.5, "finished":
+    ///     1700000000, "artifacts": [{"path": "app_bg.wasm", "size": 123456}, ...],
+    ///     "diagnostics": {"errors": 0, "warnings": 1, "messages": [{"level": "warning", "code":
+    ///     null, "message": "unused variable: `x`", "file": "src/lib.rs", "line": 3, "column":
+    ///     9}]}}` (`status` is
+    ///     `failure` and `error` the error message if the build failed, `duration` is in seconds,
+    ///     `finished` in seconds since the UNIX epoch and `diagnostics` are the errors and the
+    ///     warnings of the compiler during the last cargo build, the paths are relative to the
+    ///     workspace). `queue` is the state of the rebuilds: `idle`, `building`, or `pending` if
+    ///     a rebuild is queued after the running one.
+    ///
+    /// For example: `curl -X POST http://127.0.0.1:3000/__wasmbl/rebuild`.
+    #[cfg(feature = "dev-server")]
+    fn control_api(&self) -> bool {
+        false
+    }
+
+    /// Serve the Rust sources of the workspace and of the dependencies (from the registry and git)
+    /// to the debuggers of the browsers, at `/__wasmbl/sources/`. The paths of the sources in the
+    /// debug info of the WASM are remapped to this route and the debug info is kept by
+    /// `wasm-bindgen`.
+    ///
+    /// Only the `.rs` files are served and only for the dev builds: the paths are not remapped for
+    /// the release builds.
+    #[cfg(feature = "dev-server")]
+    fn serve_sources(&self) -> bool {
+        false
+    }
+
+    /// List the files of the directories of the serve directory that have no `index.html`, with
+    /// their sizes and modification times, instead of falling back to an `index.html`. The paths
+    /// that don't exist still fall back to an `index.html`.
+    #[cfg(feature = "dev-server")]
+    fn dir_listing(&self) -> bool {
+        false
+    }
+
+    /// Name of the index documents served for the directories of the serve directory and for the
+    /// paths that fall back to an index. The default is `index.html`.
+    #[cfg(feature = "dev-server")]
+    fn index_document(&self) -> String {
+        "index.html".to_string()
+    }
+
+    /// Serve `<path>.html` for the paths that have no extension and are not files, then
+    /// `<path>/` followed by the index document, before falling back to the index of the closest
+    /// directory. This matches the static hosts that map `/about` to `about.html`.
+    #[cfg(feature = "dev-server")]
+    fn extensionless_html(&self) -> bool {
+        false
+    }
+
+    /// Redirect the URLs of the pages that end with a `/` or not, so the relative URLs of the
+    /// pages resolve like on the static host. The default is [`TrailingSlash::Keep`].
+    #[cfg(feature = "dev-server")]
+    fn trailing_slash(&self) -> TrailingSlash {
+        TrailingSlash::Keep
+    }
+
+    /// Build in release mode. The backend is run in release mode too.
+    fn release(&self) -> bool {
+        false
+    }
+
+    /// The profile of the builds: [`BuildProfile::Profiling`] if [`BuildArgs::profiling`] is
+    /// enabled, otherwise [`BuildProfile::Release`] if [`ServeArgs::release`] is enabled,
+    /// [`BuildProfile::Dev`] by default.
+    ///
+    /// This is the profile actually used by the builds so the hooks that receive the arguments can
+    /// rely on it.
+    fn profile(&self) -> BuildProfile {
+        let profile = if self.release() {
+            BuildProfile::Release
+        } else {
+            BuildProfile::Dev
+        };
+        resolve_profile(profile, self.build_args())
+    }
+
+    /// Clear the terminal before every rebuild and print a concise status line at the end.
+    ///
+    /// The terminal is not cleared if the standard output is not a terminal.
+    fn clear(&self) -> bool {
+        false
+    }
+
+    /// Show the full output of the rebuilds even when [`ServeArgs::clear`] is enabled.
+    fn verbose(&self) -> bool {
+        false
+    }
+
+    /// Send a desktop notification when a rebuild triggered by the watcher finishes.
+    ///
+    /// Nothing is sent if no notification service is available (SSH, CI, ...) or if the feature
+    /// `desktop-notifications` is not enabled.
+    fn notify(&self) -> bool {
+        false
+    }
+
+    /// Run `cargo check` before the full rebuilds of the watcher: the errors of the compiler are
+    /// reported (and shown in the overlay of the browsers) without waiting for a full build, and
+    /// the build, the bindings and the optimization are skipped if the check fails.
+    ///
+    /// The check is restarted if a file changes while it runs.
+    fn check_first(&self) -> bool {
+        false
+    }
+
+    /// Extensions of the files that trigger a full rebuild when they change.
+    ///
+    /// The default can be overridden with the key `watch-extensions` in the
+    /// `[package.metadata.wasmbl]` table of the frontend package. Files without extension and
+    /// `Cargo.lock` always trigger a full rebuild.
+    fn watch_extensions(&self) -> Vec<String> {
+        default_watch_extensions(self.build_args().frontend_package())
+    }
+
+    /// Extensions of the JS snippets imported by the bindings (`#[wasm_bindgen(module = ...)]`):
+    /// they trigger a full rebuild so the bindings and the snippets are generated again.
+    ///
+    /// The default can be overridden with the key `watch-snippet-extensions` in the
+    /// `[package.metadata.wasmbl]` table of the frontend package. The files of
+    /// [`BuildArgs::static_dir`] are always assets.
+    fn watch_snippet_extensions(&self) -> Vec<String> {
+        default_watch_snippet_extensions(self.build_args().frontend_package())
+    }
+
+    /// Extensions of the files that only trigger the `post_build` hook when they change (cargo is
+    /// not invoked).
+    ///
+    /// The default can be overridden with the key `watch-asset-extensions` in the
+    /// `[package.metadata.wasmbl]` table of the frontend package. All the files of
+    /// [`BuildArgs::static_dir`] are assets, whatever their extensions.
+    fn watch_asset_extensions(&self) -> Vec<String> {
+        package_metadata_strings(
+            self.build_args().frontend_package(),
+            "watch-asset-extensions",
+        )
+        .unwrap_or_else(|| {
+            ["html", "css", "scss", "sass"]
+                .iter()
+                .map(|x| x.to_string())
+                .collect()
+        })
+    }
+
+    /// Directory served by the development server.
+    ///
+    /// It is the build directory unless the macro has been given a `default_serve_path` function.
+    /// The changes in this directory only reload the browser, nothing is rebuilt.
+    fn serve_path(&self) -> PathBuf {
+        match Project::get().default_serve_path {
+            Some(serve_path) => serve_path.to_owned(),
+            None => self.build_args().build_path(),
+        }
+    }
+
+    /// Build arguments.
+    fn build_args(&self) -> &dyn BuildArgs;
+
+    /// Run the `serve` command.
+    fn run(self) -> Result<(), Error>
+    where
+        Self: Sync + Sized + 'static,
+    {
+        let hooks = hooks()?;
+        Project::select_frontend(self.build_args())?;
+        if let Some(format) = self.build_args().print_config() {
+            config::print(
+                &config::resolve(self.build_args(), Some(&self), hooks),
+                format,
+            );
+            return Ok(());
+        }
+        if self.build_args().target() == BuildTarget::Wasi {
+            return Err(anyhow!(
+                "the `serve` command does not support the target `wasm32-wasi`: use the `run` \
+                command to run the WASM with a WASI runtime"
+            )
+            .into());
+        }
+        // NOTE: the address is checked before the first build, it can take a while
+        #[cfg(feature = "dev-server")]
+        for addr in self.bind() {
+            port::check_addr(addr)?;
+        }
+        #[cfg(feature = "dev-server")]
+        if self.serve_sources() {
+            if self.profile() == BuildProfile::Dev {
+                sources::enable(self.bind()[0]);
+            } else {
+                log::warn!("The sources are only served for the dev builds");
+            }
+        }
+        // NOTE: the first step for serving is to call `build` a first time. The build directory
+        //       must be present before we start watching files there.
+        let start = Instant::now();
+        let live_reload = live_reload::LiveReload::default();
+        let output = live_reload
+            .orchestrator()
+            .run(pipeline::Stage::Frontend, || {
+                build(
+                    self.profile(),
+                    self.build_args(),
+                    hooks,
+                    false,
+                    BuildTrigger::ServeInitial,
+                )
+            })?;
+        #[cfg(feature = "dev-server")]
+        warn_missing_index(&self);
+        live_reload.set_status(live_reload::BuildStatus::new(
+            &Ok(()),
+            start.elapsed(),
+            Some(&output),
+        ));
+        #[cfg(not(feature = "dev-server"))]
+        if self.build_args().backend_package().is_none() {
+            return Err(anyhow!("missing backend crate name").into());
+        }
+
+        (hooks.pre_serve)(&self).map_err(|source| Error::Hook {
+            hook: "pre_serve",
+            source,
+        })?;
+        let args = std::sync::Arc::new(self);
+        {
+            let args = std::sync::Arc::clone(&args);
+            process::on_shutdown(move || {
+                if let Err(err) = (hooks.on_shutdown)(&*args) {
+                    reporter::reporter()
+                        .warning(&format!("the `on_shutdown` hook failed: {:#}", err));
+                }
+            });
+        }
+
+        // NOTE: the process groups of the builds would keep running after a panic
+        #[cfg(feature = "dev-server")]
+        let res = process::terminate_on_panic(|| {
+            async_std::task::block_on(async {
+                let server = stages::start_server(&*args, hooks, &live_reload)?;
+                let t1 = async_std::task::spawn(server.listen);
+                let t2 = async_std::task::spawn_blocking(move || {
+                    process::terminate_on_panic(|| {
+                        watch_frontend(&*args, hooks, &live_reload, output)
+                    })
+                });
+                futures::try_join!(t1, t2)?;
+                Err(anyhow!("server and watcher unexpectedly exited"))
+            })
+        })
+        .map_err(Error::from);
+        #[cfg(not(feature = "dev-server"))]
+        let res = {
+            use std::sync::Arc;
+            use std::thread;
+
+            let orchestrator = live_reload.orchestrator().clone();
+            let t1 = {
+                let args = Arc::clone(&args);
+                thread::spawn(move || {
+                    process::terminate_on_panic(|| {
+                        watch_frontend(&*args, hooks, &live_reload, output)
+                    })
+                })
+            };
+            let t2 = thread::spawn(move || {
+                process::terminate_on_panic(|| watch_backend(&*args, hooks, &orchestrator))
+            });
+            let _ = t1.join();
+            let _ = t2.join();
+
+            Err(anyhow!("server and watcher unexpectedly exited").into())
+        };
+        process::shutdown();
+        res
+    }
+}
+
+impl_downcast!(ServeArgs);
+
+impl WatchArgs for DefaultServeArgs {
+    fn debounce(&self) -> Duration {
+        self.debounce
+            .as_deref()
+            .map(|x| parse_duration(x).expect("the value has been validated; qed"))
+            .unwrap_or(watcher::DEFAULT_DEBOUNCE_DELAY)
+    }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        self.watch_paths.clone()
+    }
+
+    fn ignore_globs(&self) -> Vec<String> {
+        self.ignore_globs.clone()
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        self.poll.as_ref().map(|x| {
+            x.as_deref()
+                .map(|x| parse_duration(x).expect("the value has been validated; qed"))
+                .unwrap_or(watcher::DEFAULT_POLL_INTERVAL)
+        })
+    }
+
+    fn gitignore(&self) -> bool {
+        !self.no_gitignore
+    }
+}
+
+impl ServeArgs for DefaultServeArgs {
+    #[cfg(feature = "dev-server")]
+    fn log(&self) -> bool {
+        self.log
+    }
+
+    #[cfg(feature = "dev-server")]
+    fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    #[cfg(feature = "dev-server")]
+    fn port(&self) -> u16 {
+        self.port
+    }
+
+    #[cfg(feature = "dev-server")]
+    fn bind(&self) -> Vec<SocketAddr> {
+        if self.bind.is_empty() {
+            return vec![SocketAddr::new(self.ip, self.port)];
+        }
+        self.bind
+            .iter()
+            .map(|x| parse_bind_addr(x, self.port).expect("the value has been validated; qed"))
+            .collect()
+    }
+
+    #[cfg(feature = "dev-server")]
+    fn live_reload(&self) -> bool {
+        !self.no_live_reload
+    }
+
+    #[cfg(feature = "dev-server")]
+    fn control_api(&self) -> bool {
+        self.control_api
+    }
+
+    #[cfg(feature = "dev-server")]
+    fn serve_sources(&self) -> bool {
+        self.serve_sources
+    }
+
+    #[cfg(feature = "dev-server")]
+    fn dir_listing(&self) -> bool {
+        self.dir_listing
+    }
+
+    #[cfg(feature = "dev-server")]
+    fn index_document(&self) -> String {
+        self.index_document
+            .clone()
+            .unwrap_or_else(|| "index.html".to_string())
+    }
+
+    #[cfg(feature = "dev-server")]
+    fn extensionless_html(&self) -> bool {
+        self.extensionless_html
+    }
+
+    #[cfg(feature = "dev-server")]
+    fn trailing_slash(&self) -> TrailingSlash {
+        self.trailing_slash.unwrap_or_default()
+    }
+
+    fn release(&self) -> bool {
+        self.release
+    }
+
+    fn clear(&self) -> bool {
+        self.clear
+    }
+
+    fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn notify(&self) -> bool {
+        self.notify
+    }
+
+    fn check_first(&self) -> bool {
+        self.check_first
+    }
+
+    fn build_args(&self) -> &dyn BuildArgs {
+        &self.build_args
+    }
+}
+
+/// Test arguments.
+#[derive(StructOpt, Debug)]
+pub struct DefaultTestArgs {
+    /// Run the tests in Chrome (requires `chromedriver`).
+    #[structopt(long, conflicts_with = "firefox")]
+    pub chrome: bool,
+
+    /// Run the tests in Firefox (requires `geckodriver`).
+    #[structopt(long)]
+    pub firefox: bool,
+
+    /// Run the browser without a window.
+    #[structopt(long)]
+    pub headless: bool,
+
+    /// Build the tests in release mode.
+    #[structopt(long)]
+    pub release: bool,
+
+    /// Run the tests again when the files of the frontend package change.
+    #[structopt(long)]
+    pub watch: bool,
+
+    /// Arguments given to the test harness (test name filters for example).
+    pub filters: Vec<String>,
+
+    /// Build arguments.
+    #[structopt(flatten)]
+    pub build_args: DefaultBuildArgs,
+}
+
+/// The browser of the `test` command, see [`TestArgs::browser`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestBrowser {
+    /// Chrome, with `chromedriver` (or the driver of the variable `CHROMEDRIVER`).
+    Chrome,
+    /// Firefox, with `geckodriver` (or the driver of the variable `GECKODRIVER`).
+    Firefox,
+}
+
+impl TestBrowser {
+    /// The variable of the driver for `wasm-bindgen-test-runner` and its default program.
+    pub(crate) fn driver(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Chrome => ("CHROMEDRIVER", "chromedriver"),
+            Self::Firefox => ("GECKODRIVER", "geckodriver"),
+        }
+    }
+}
+
+/// A trait that allows overriding the `test` command.
+///
+/// The tests of the library of the frontend package are run with
+/// `cargo test --lib --target wasm32-unknown-unknown` and `wasm-bindgen-test-runner` (from the
+/// package `wasm-bindgen-cli`) as the runner. Cargo gets the flags of the builds from
+/// [`TestArgs::build_args`]: the features, the target directory, the panic strategy, ... The
+/// Chrome and Firefox tests require `chromedriver` and `geckodriver` respectively.
+///
+/// The file watcher of [`TestArgs::watch`] is configured by the supertrait [`WatchArgs`].
+pub trait TestArgs: WatchArgs + Downcast {
+    /// Run the tests in Chrome.
+    fn chrome(&self) -> bool;
+
+    /// Run the tests in Firefox.
+    fn firefox(&self) -> bool;
+
+    /// Run the browser without a window.
+    fn headless(&self) -> bool;
+
+    /// The browser the tests are run in, or `None` to let `wasm-bindgen-test-runner` pick (Node.js
+    /// unless the tests are configured to run in a browser).
+    ///
+    /// By default it is given by [`TestArgs::chrome`] and [`TestArgs::firefox`].
+    fn browser(&self) -> Option<TestBrowser> {
+        if self.chrome() {
+            Some(TestBrowser::Chrome)
+        } else if self.firefox() {
+            Some(TestBrowser::Firefox)
+        } else {
+            None
+        }
+    }
+
+    /// Build the tests in release mode.
+    fn release(&self) -> bool;
+
+    /// Run the tests again when the files of the frontend package and its path dependencies
+    /// change, until Ctrl-C. The failures do not stop the loop and a summary is printed after
+    /// each run.
+    ///
+    /// The driver of the browser is started once and used by all the runs.
+    fn watch(&self) -> bool {
+        false
+    }
+
+    /// Arguments given to the test harness.
+    fn filters(&self) -> &[String];
+
+    /// The filter of the names of the tests: the first argument of [`TestArgs::filters`] that is
+    /// not an option, or `None` to run all the tests.
+    fn filter(&self) -> Option<&str> {
+        self.filters()
+            .iter()
+            .find(|x| !x.starts_with('-'))
+            .map(String::as_str)
+    }
+
+    /// Build arguments.
+    fn build_args(&self) -> &dyn BuildArgs;
+
+    /// Run the `test` command.
+    ///
+    /// Returns [`Error::TestsFailed`] if the tests fail, unless [`TestArgs::watch`] is enabled.
+    fn run(self) -> Result<(), Error>
+    where
+        Self: Sized + 'static,
     {
-        let hooks = HOOKS.get().expect("wasmbl_init() has not been called");
-        // NOTE: the first step for serving is to call `build` a first time. The build directory
-        //       must be present before we start watching files there.
-        build(BuildProfile::Dev, self.build_args(), hooks)?;
-        #[cfg(feature = "dev-server")]
-        {
-            async_std::task::block_on(async {
-                let t1 = async_std::task::spawn(serve_frontend(&self, hooks)?);
-                let t2 = async_std::task::spawn_blocking(move || watch_frontend(&self, hooks));
-                futures::try_join!(t1, t2)?;
-                Err(anyhow!("server and watcher unexpectedly exited"))
+        let hooks = hooks()?;
+        Project::select_frontend(self.build_args())?;
+
+        if self.watch() {
+            return test_watch::watch(&self, hooks);
+        }
+
+        let mut command = test_command(&self, hooks, None)?;
+        log::info!("Testing frontend");
+        log::debug!("Running {:?}", command);
+        let status = process::GroupChild::spawn(&mut command)
+            .and_then(|mut x| x.wait())
+            .context("could not start test process")?;
+        if !status.success() {
+            return Err(Error::TestsFailed { status });
+        }
+
+        Ok(())
+    }
+}
+
+impl_downcast!(TestArgs);
+
+/// The command of the tests, with the URL of the driver of the browser if it is already running.
+fn test_command(
+    args: &dyn TestArgs,
+    hooks: &Hooks,
+    driver_url: Option<&str>,
+) -> Result<Command, Error> {
+    let mut command = Command::new("cargo");
+    command
+        .args([
+            "test",
+            "--lib",
+            "--target",
+            "wasm32-unknown-unknown",
+            "--manifest-path",
+        ])
+        .arg(&args.build_args().frontend_package().manifest_path);
+    let profile = if args.release() {
+        BuildProfile::Release
+    } else {
+        BuildProfile::Dev
+    };
+    cargo_flags(&mut command, profile, args.build_args());
+    if std::env::var_os(TEST_RUNNER_VAR).is_none() {
+        command.env(TEST_RUNNER_VAR, "wasm-bindgen-test-runner");
+    }
+    // NOTE: the runner picks the first driver it finds so the others are removed
+    if let Some(browser) = args.browser() {
+        let (var, default) = browser.driver();
+        for other in ["CHROMEDRIVER", "GECKODRIVER", "SAFARIDRIVER"] {
+            if other != var {
+                command.env_remove(other);
+            }
+        }
+        // NOTE: the runner only connects to the remote driver if there is no local one
+        match driver_url {
+            Some(url) => command.env_remove(var).env(format!("{}_REMOTE", var), url),
+            None => command.env(var, std::env::var_os(var).unwrap_or_else(|| default.into())),
+        };
+        if !args.headless() {
+            command.env("NO_HEADLESS", "1");
+        }
+    }
+
+    log::info!("Running pre_test hook");
+    (hooks.pre_test)(args, &mut command)?;
+    rustflags::merge_rustflags(&mut command);
+
+    command.arg("--").args(args.filters());
+
+    Ok(command)
+}
+
+impl WatchArgs for DefaultTestArgs {}
+
+impl TestArgs for DefaultTestArgs {
+    fn chrome(&self) -> bool {
+        self.chrome
+    }
+
+    fn firefox(&self) -> bool {
+        self.firefox
+    }
+
+    fn headless(&self) -> bool {
+        self.headless
+    }
+
+    fn release(&self) -> bool {
+        self.release
+    }
+
+    fn watch(&self) -> bool {
+        self.watch
+    }
+
+    fn filters(&self) -> &[String] {
+        &self.filters
+    }
+
+    fn build_args(&self) -> &dyn BuildArgs {
+        &self.build_args
+    }
+}
+
+/// Hooks.
+///
+/// Check the code of [`Hooks::default()`] implementation to see what they do by default.
+///
+/// If you don't provide your own hook, the default code will be executed. But if you do provide a
+/// hook, the code will be *replaced*.
+pub struct Hooks {
+    /// This hook will be run before the WASM is compiled. It does nothing by default.
+    /// You can tweak the command-line arguments of the build command here or create additional
+    /// files in the build directory. The [`BuildTrigger`] tells what started the build.
+    #[allow(clippy::type_complexity)]
+    pub pre_build: Box<
+        dyn Fn(&dyn BuildArgs, BuildProfile, &mut Command, BuildTrigger) -> Result<()>
+            + Send
+            + Sync,
+    >,
+
+    /// This hook will be run after the WASM is compiled and optimized.
+    /// By default it writes the JS and the WASM of the [`BuildOutput`] to their paths and copies
+    /// the static files to the build directory (only the WASM is written for
+    /// [`BuildTarget::Wasi`]). What started the build is in [`BuildOutput::trigger`].
+    /// It receives the hooks of the build: the default hook writes the files with their
+    /// [`Hooks::asset_filter`].
+    #[allow(clippy::type_complexity)]
+    pub post_build: Box<dyn Fn(&dyn BuildArgs, &BuildOutput, &Hooks) -> Result<()> + Send + Sync>,
+
+    /// This hook will be run before generating the JS bindings, after the options of `wasm-bindgen`
+    /// have been set by wasmbl. It does nothing by default.
+    /// You can enable the other options of the [`Bindgen`](prelude::Bindgen) builder here
+    /// (`reference_types`, `omit_default_module_path`, `remove_producers_section`, ...). It is not
+    /// run for [`BuildTarget::Wasi`].
+    #[allow(clippy::type_complexity)]
+    pub bindgen: Box<
+        dyn Fn(&dyn BuildArgs, &mut wasm_bindgen_cli_support::Bindgen) -> Result<()> + Send + Sync,
+    >,
+
+    /// This hook will be run for every file written to the build directory by the build and by the
+    /// default `post_build` hook, before it is written. It returns the [`Asset`] to write (its
+    /// content and its path can be changed) or `None` to skip the file. By default it returns the
+    /// asset as-is.
+    ///
+    /// The static files are filtered on several threads at once. The hook is not run again for the
+    /// static files that did not change since the previous build of the same program and profile:
+    /// their previous output is kept.
+    #[allow(clippy::type_complexity)]
+    pub asset_filter: Box<dyn Fn(Asset) -> Result<Option<Asset>> + Send + Sync>,
+
+    /// This hook will be run first when the HTTP server is being configured. It does nothing by
+    /// default.
+    /// The middleware added here with [`Server::with`] wraps all the routes of the server: the
+    /// files of the build directory (including the fallback to `index.html`), the routes added by
+    /// the `serve` hook and the endpoint of the live reload.
+    #[cfg(feature = "dev-server")]
+    #[allow(clippy::type_complexity)]
+    pub serve_middleware: Box<dyn Fn(&dyn ServeArgs, &mut Server<()>) -> Result<()> + Send + Sync>,
+
+    /// This hook will be run before running the HTTP server.
+    /// By default it will add routes to the files in the build directory.
+    /// The profile of the builds is given by [`ServeArgs::profile`].
+    #[cfg(feature = "dev-server")]
+    #[allow(clippy::type_complexity)]
+    pub serve: Box<dyn Fn(&dyn ServeArgs, &mut Server<()>) -> Result<()> + Send + Sync>,
+
+    /// This hook will be run once by the `serve` command, after the first build succeeded and
+    /// before the HTTP server listens (and before the watchers and the backend start). It does
+    /// nothing by default. The command fails with its error.
+    /// You can prepare what the server needs here, like the data of a mock API or a companion
+    /// process.
+    #[allow(clippy::type_complexity)]
+    pub pre_serve: Box<dyn Fn(&dyn ServeArgs) -> Result<()> + Send + Sync>,
+
+    /// This hook will be run once when the `serve` command stops after the `pre_serve` hook: on
+    /// Ctrl-C (or `SIGTERM`) before the child processes are terminated, or when the command
+    /// fails. It does nothing by default. Its error is only reported.
+    #[allow(clippy::type_complexity)]
+    pub on_shutdown: Box<dyn Fn(&dyn ServeArgs) -> Result<()> + Send + Sync>,
+
+    /// This hook will be run after a release build of the `build` command, once all the files of
+    /// the build directory are written (and archived), with the [`Release`]: its identifier, the
+    /// commit and the files. It does nothing by default. You can upload the sourcemaps of the
+    /// release to an error tracker here, see also [`BuildArgs::sourcemap_upload_command`]. Its
+    /// error is only a warning unless [`BuildArgs::upload_errors_fatal`].
+    #[allow(clippy::type_complexity)]
+    pub post_release: Box<dyn Fn(&dyn BuildArgs, &Release) -> Result<()> + Send + Sync>,
+
+    /// This hook will be run before starting to watch for changes in files.
+    /// By default it will add the frontend crate directory and the directories of its path
+    /// dependencies (transitively) that are members of the workspace. For the path dependencies
+    /// that live outside the workspace, only their `src/` directory and their `Cargo.toml` are
+    /// watched.
+    ///
+    /// A rebuild can also be triggered programmatically with [`FileWatcher::rebuild_handle`]. The
+    /// changes that trigger a rebuild can be filtered with [`FileWatcher::config_mut`]: ignore a
+    /// pattern or a path, or watch a directory without its subdirectories.
+    #[allow(clippy::type_complexity)]
+    pub frontend_watch: Box<dyn Fn(&dyn ServeArgs, &mut FileWatcher) -> Result<()> + Send + Sync>,
+
+    /// This hook will be run before starting to watch for changes in files.
+    /// By default it will add the backend crate directory and all its path dependencies (like
+    /// `frontend_watch`). But it excludes the target directory. The changes can be filtered like
+    /// for `frontend_watch`.
+    #[allow(clippy::type_complexity)]
+    pub backend_watch: Box<dyn Fn(&dyn ServeArgs, &mut FileWatcher) -> Result<()> + Send + Sync>,
+
+    /// This hook will be run before (re-)starting the backend.
+    /// You can tweak the cargo command that is run here: adding/removing environment variables or
+    /// adding arguments.
+    /// By default it will do `cargo run -p <backend_crate>`, with `--release` if the profile given
+    /// by [`ServeArgs::profile`] is [`BuildProfile::Release`].
+    #[allow(clippy::type_complexity)]
+    pub backend_command: Box<dyn Fn(&dyn ServeArgs, &mut Command) -> Result<()> + Send + Sync>,
+
+    /// This hook will be run before running the tests. It does nothing by default.
+    /// You can tweak the cargo command of the tests here (the arguments of the test harness are
+    /// added after it), for example to set `WASM_BINDGEN_TEST_TIMEOUT`.
+    #[allow(clippy::type_complexity)]
+    pub pre_test: Box<dyn Fn(&dyn TestArgs, &mut Command) -> Result<()> + Send + Sync>,
+
+    /// This hook will be run after the release build of the `deploy` command with the files of the
+    /// build directory. By default it copies them to the destination.
+    #[cfg(feature = "deploy")]
+    #[allow(clippy::type_complexity)]
+    pub deploy: Box<dyn Fn(&DefaultDeployArgs, &[PathBuf]) -> Result<()> + Send + Sync>,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Self {
+            backend_command: Box::new(|args, command| {
+                command.args([
+                    "run",
+                    "-p",
+                    &args
+                        .build_args()
+                        .backend_package()
+                        .context("missing backend crate name")?
+                        .name,
+                ]);
+                if args.profile() == BuildProfile::Release {
+                    command.arg("--release");
+                }
+                Ok(())
+            }),
+            backend_watch: Box::new(|args, watcher| {
+                use notify::Watcher;
+
+                let metadata = args.build_args().metadata();
+                let backend = args
+                    .build_args()
+                    .backend_package()
+                    .context("missing backend crate name")?;
+
+                for (path, mode) in watcher::package_watch_paths(metadata, backend) {
+                    watcher.watch(path, mode)?;
+                }
+
+                Ok(())
+            }),
+            frontend_watch: Box::new(|args, watcher| {
+                use notify::Watcher;
+
+                let metadata = args.build_args().frontend_metadata();
+                let frontend = args.build_args().frontend_package();
+
+                for (path, mode) in watcher::package_watch_paths(metadata, frontend) {
+                    watcher.watch(path, mode)?;
+                }
+
+                Ok(())
+            }),
+            pre_build: Box::new(|_, _, _, _| Ok(())),
+            pre_serve: Box::new(|_| Ok(())),
+            on_shutdown: Box::new(|_| Ok(())),
+            post_release: Box::new(|_, _| Ok(())),
+            bindgen: Box::new(|_, _| Ok(())),
+            asset_filter: Box::new(|asset| Ok(Some(asset))),
+            pre_test: Box::new(|_, _| Ok(())),
+            #[cfg(feature = "deploy")]
+            deploy: Box::new(deploy::deploy),
+            post_build: Box::new(write_build),
+            #[cfg(feature = "dev-server")]
+            serve_middleware: Box::new(|_, _| Ok(())),
+            #[cfg(feature = "dev-server")]
+            serve: Box::new(|args, server| {
+                use tide::{Body, Request, Response};
+
+                let serve_path = args.serve_path();
+                let index_document = args.index_document();
+                let index_path = serve_path.join(&index_document);
+                let listing = args.dir_listing().then(|| base_url(args.build_args()));
+                let options = ServeFiles {
+                    index_document,
+                    extensionless_html: args.extensionless_html(),
+                    trailing_slash: args.trailing_slash(),
+                    listing: args.dir_listing(),
+                };
+
+                // NOTE: checked for every request, the placeholder disappears with the first build
+                //       that writes the index
+                let missing = missing_index(args);
+                let missing_root = missing.clone();
+                server.at("/").serve_dir(&serve_path)?;
+                let root = serve_path.clone();
+                let root_listing = listing.clone();
+                let root_index = index_path.clone();
+                server.at("/").get(move |_| {
+                    let index_path = root_index.clone();
+                    let exists = index_path.is_file();
+                    let listing = root_listing.as_ref().filter(|_| !exists);
+                    let listing = listing.map(|x| dir_listing::render(&root, Path::new(""), x));
+                    let missing = missing_root.clone();
+                    async move {
+                        match listing {
+                            Some(html) => Ok(listing_response(html?)),
+                            None if !exists => Ok(missing_index_response(&missing)),
+                            None => Ok(Response::from(Body::from_file(index_path).await?)),
+                        }
+                    }
+                });
+                server.at("/*path").get(move |req: Request<()>| {
+                    let served = options.resolve(&serve_path, req.url());
+                    let serve_path = serve_path.clone();
+                    let listing = listing.clone().unwrap_or_default();
+                    let missing = (matches!(&served, Served::File(path) if *path == index_path)
+                        && !index_path.is_file())
+                    .then(|| missing.clone());
+                    async move {
+                        match served {
+                            Served::File(_) if missing.is_some() => {
+                                Ok(missing_index_response(&missing.unwrap_or_default()))
+                            }
+                            Served::File(path) => Ok(Response::from(Body::from_file(path).await?)),
+                            Served::Redirect(url) => Ok(tide::Redirect::new(url).into()),
+                            Served::Listing(path) => Ok(listing_response(dir_listing::render(
+                                &serve_path,
+                                &path,
+                                &listing,
+                            )?)),
+                        }
+                    }
+                });
+
+                Ok(())
+            }),
+        }
+    }
+}
+
+/// The default `post_build` hook: write the JS and the WASM of the build, then the static files,
+/// the pages, the localization files and the styles, with the given hooks.
+fn write_build(args: &dyn BuildArgs, output: &BuildOutput, hooks: &Hooks) -> Result<()> {
+    let build_path = args.build_path();
+    let relative = |path: &Path| path.strip_prefix(&build_path).unwrap_or(path).to_owned();
+
+    let wasm_path = relative(&output.wasm_path);
+    let wasm_name = slash_path(&wasm_path);
+    write_asset(
+        &build_path,
+        hooks,
+        Asset::new(wasm_path, wasm_name, output.wasm.clone()),
+    )?;
+    if output.target == BuildTarget::Wasi {
+        return Ok(());
+    }
+
+    let js_path = relative(&output.js_path);
+    let js_name = slash_path(&js_path);
+    write_asset(
+        &build_path,
+        hooks,
+        Asset::new(js_path, js_name, output.js.clone().into_bytes()),
+    )?;
+
+    // NOTE: the package of the `pkg` layout is consumed by a bundler, not served
+    if args.out_layout() == OutLayout::Flat {
+        let pages = args.pages();
+        let trunk_index = args.trunk_index();
+        let has_index =
+            trunk_index.is_some() || pages.iter().any(|x| x.path == Path::new("index.html"));
+        copy_static_files(args, output.profile, &build_path, hooks, !has_index)?;
+        pages::build(args, &pages, output, hooks)?;
+        if let Some(template) = trunk_index {
+            trunk::build(args, &template, output, hooks)?;
+        }
+    }
+
+    if let Some(i18n) = args.i18n() {
+        i18n::build(&i18n, &build_path, hooks, args.check_i18n())?;
+    }
+
+    #[cfg(feature = "sass")]
+    {
+        let options = args.sass_options(output.profile);
+        for style_path in args.sass_lookup_directories(output.profile) {
+            args.build_sass_from_dir(&style_path, options.clone())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The profile of a build: `--profiling` takes precedence over the profile of the command.
+fn resolve_profile(profile: BuildProfile, args: &dyn BuildArgs) -> BuildProfile {
+    if args.profiling() {
+        BuildProfile::Profiling
+    } else {
+        profile
+    }
+}
+
+/// Build the frontend package. The build is an event of `--log-format json` (`build_started`,
+/// then `build_finished` or `build_failed`).
+fn build(
+    profile: BuildProfile,
+    args: &dyn BuildArgs,
+    hooks: &Hooks,
+    quiet: bool,
+    trigger: BuildTrigger,
+) -> Result<BuildOutput, Error> {
+    let profile = resolve_profile(profile, args);
+    let start = Instant::now();
+    logger::event(
+        log::Level::Info,
+        "build_started",
+        serde_json::json!({
+            "profile": profile.name(),
+            "rebuild": trigger == BuildTrigger::WatchRebuild,
+        }),
+        None,
+    );
+
+    let res = build_wasm(profile, args, hooks, quiet, trigger, start);
+    match res.as_ref() {
+        Err(Error::BuildCancelled) | Ok(_) => {}
+        Err(err) => {
+            logger::event(
+                log::Level::Error,
+                "build_failed",
+                serde_json::json!({
+                    "profile": profile.name(),
+                    "duration": start.elapsed().as_secs_f64(),
+                    "error": error_chain(err),
+                }),
+                None,
+            );
+        }
+    }
+
+    res
+}
+
+/// The message of an error followed by the messages of its sources, like `a: b: c`.
+fn error_chain(err: &Error) -> String {
+    let mut error = err.to_string();
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        error = format!("{}: {}", error, err);
+        source = err.source();
+    }
+    error
+}
+
+fn build_wasm(
+    profile: BuildProfile,
+    args: &dyn BuildArgs,
+    hooks: &Hooks,
+    quiet: bool,
+    trigger: BuildTrigger,
+    build_start: Instant,
+) -> Result<BuildOutput, Error> {
+    use std::io::IsTerminal;
+
+    let rebuild = trigger == BuildTrigger::WatchRebuild;
+    let target = args.target();
+
+    let artifact = stages::compile_quiet(args, hooks, profile, trigger, quiet)?;
+    let bindings = stages::bindgen(args, hooks, artifact)?;
+    let optimized = stages::optimize(args, bindings)?;
+    let stages::AssembledBuild { build_path, output } = stages::assemble(args, hooks, optimized)?;
+
+    // NOTE: measured from the files of the build, after the renames of the `asset_filter` hook
+    let sizes = size_report::Sizes::of_build(&build_path, profile != BuildProfile::Dev)?;
+    let panic_strategy = args.panic_strategy(profile);
+    let built = format!(
+        "Built `{}` ({}, {}{}{})",
+        output.wasm_path.display(),
+        format_size(output.wasm.len()),
+        if output.optimized {
+            "optimized"
+        } else {
+            "not optimized"
+        },
+        if args.reference_types() {
+            ", reference types"
+        } else {
+            ""
+        },
+        panic_strategy
+            .map(|x| format!(", panic: {}", x.name()))
+            .unwrap_or_default(),
+    );
+    let finished = serde_json::json!({
+        "profile": profile.name(),
+        "duration": build_start.elapsed().as_secs_f64(),
+        "wasm": {
+            "path": output.wasm_path.display().to_string(),
+            "size": output.wasm.len(),
+        },
+        "js": (target == BuildTarget::Web).then(|| serde_json::json!({
+            "path": output.js_path.display().to_string(),
+            "size": output.js.len(),
+        })),
+        "optimized": output.optimized,
+        "panic_strategy": panic_strategy.map(PanicStrategy::name),
+    });
+    if rebuild {
+        logger::event(
+            log::Level::Info,
+            "build_finished",
+            finished,
+            Some(format_args!("{}: {}", built, sizes.summary_line())),
+        );
+    } else {
+        logger::event(
+            log::Level::Info,
+            "build_finished",
+            finished,
+            Some(format_args!("{}", built)),
+        );
+        if log::log_enabled!(log::Level::Info) && !logger::is_json() {
+            let relative = |path: &Path| path.strip_prefix(&build_path).ok().map(slash_path);
+            let wasm_name = relative(&output.wasm_path).unwrap_or_default();
+            let wasm = written_asset(&wasm_name)
+                .and_then(|x| relative(&x))
+                .unwrap_or(wasm_name);
+            eprint!("{}", sizes.summary(&wasm, std::io::stderr().is_terminal()));
+        }
+    }
+    sizes.set_last_build();
+
+    Ok(output)
+}
+
+/// The options of `wasm-opt` with the features and the passes needed by the other options of the
+/// build (the reference types, the panic strategy).
+fn effective_wasm_opt_options(args: &dyn BuildArgs, profile: BuildProfile) -> WasmOptOptions {
+    let mut options = args.wasm_opt_options(profile);
+    options.single_threaded |= args.reproducible();
+    if args.reference_types()
+        && !options
+            .enabled_features
+            .iter()
+            .any(|x| x.trim_start_matches("--enable-") == "reference-types")
+    {
+        options.enabled_features.push("reference-types".to_string());
+    }
+    for pass in args
+        .panic_strategy(profile)
+        .map(PanicStrategy::wasm_opt_passes)
+        .unwrap_or_default()
+    {
+        if !options.extra_passes.iter().any(|x| x == pass) {
+            options.extra_passes.push(pass.to_string());
+        }
+    }
+    options
+}
+
+/// The WASM of a package: the artifact reported by cargo, wherever the target directory is
+/// (`CARGO_TARGET_DIR`, `build.target-dir` in `.cargo/config.toml`, ...). If cargo did not report
+/// it, it is searched in the directory of the profile in the target directory.
+fn find_wasm(
+    artifacts: &[cargo_metadata::Artifact],
+    package: &Package,
+    profile_path: &Path,
+    artifact_name: &str,
+) -> Result<PathBuf, Error> {
+    let mut searched: Vec<PathBuf> = artifacts
+        .iter()
+        .filter(|x| x.package_id == package.id)
+        .flat_map(|x| x.filenames.iter())
+        .filter(|x| x.extension().map(|x| x == "wasm").unwrap_or(false))
+        .cloned()
+        .collect();
+    for dir in [profile_path.to_owned(), profile_path.join("deps")] {
+        let path = dir.join(format!("{}.wasm", artifact_name));
+        if !searched.contains(&path) {
+            searched.push(path);
+        }
+    }
+
+    match searched.iter().find(|x| x.exists()) {
+        Some(path) => Ok(path.clone()),
+        None => Err(Error::ArtifactNotFound { searched }),
+    }
+}
+
+/// Run [`BuildArgs::bundle_command`] if there is one and give its outputs to the
+/// [`Hooks::asset_filter`] hook. An output moved or skipped by the hook is removed.
+fn run_bundle_command(
+    args: &dyn BuildArgs,
+    hooks: &Hooks,
+    output: &BuildOutput,
+) -> Result<(), Error> {
+    let line = match args.bundle_command() {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let reporter = reporter::reporter();
+    reporter.stage_started(Stage::Bundle);
+    let start = Instant::now();
+
+    let mut command = bundle_command(args, &line, output.profile, &output.js_path);
+    log::debug!("Running {:?}", command);
+    let status = process::GroupChild::spawn(&mut command)
+        .and_then(|mut x| x.wait())
+        .with_context(|| format!("could not start the bundle command `{}`", line))?;
+    if !status.success() {
+        return Err(Error::BundleFailed {
+            command: line,
+            status,
+        });
+    }
+
+    let build_path = args.build_path();
+    for path in args.bundle_outputs() {
+        let full_path = build_path.join(&path);
+        let content = fs::read(&full_path).with_context(|| {
+            format!(
+                "could not read `{}` written by the bundle command",
+                full_path.display()
+            )
+        })?;
+        let written = write_asset(
+            &build_path,
+            hooks,
+            Asset::new(&path, slash_path(&path), content),
+        )?;
+        if written.as_ref() != Some(&full_path) {
+            fs::remove_file(&full_path).map_err(|source| Error::Io {
+                path: full_path,
+                source,
+            })?;
+        }
+    }
+
+    reporter.stage_finished(Stage::Bundle, start.elapsed());
+    Ok(())
+}
+
+/// The command of [`BuildArgs::bundle_command`], run by the shell in the directory of the frontend
+/// package, for a build of this profile. `js_path` is the path of the JS bindings before the
+/// `asset_filter` hook.
+fn bundle_command(
+    args: &dyn BuildArgs,
+    bundle_command: &str,
+    profile: BuildProfile,
+    js_path: &Path,
+) -> Command {
+    let mut command = shell_command(bundle_command);
+
+    // NOTE: the JS bindings might have been renamed by the `asset_filter` hook
+    let build_path = args.build_path();
+    let layout_path = build_path.join(args.out_layout().dir());
+    let js_name = slash_path(
+        &args
+            .out_layout()
+            .dir()
+            .join(format!("{}.js", args.out_name())),
+    );
+    process_env(args, profile, &mut command);
+    command
+        .current_dir(args.frontend_package().manifest_path.parent().unwrap())
+        .env(
+            "WASMBL_JS_GLUE",
+            written_asset(&js_name).unwrap_or_else(|| js_path.to_owned()),
+        )
+        .env("WASMBL_SNIPPETS_DIR", layout_path.join("snippets"));
+    command
+}
+
+/// A command line run by the shell: `sh -c`, or `cmd /C` on Windows.
+fn shell_command(line: &str) -> Command {
+    let mut command = if cfg!(windows) {
+        Command::new("cmd")
+    } else {
+        Command::new("sh")
+    };
+    command
+        .arg(if cfg!(windows) { "/C" } else { "-c" })
+        .arg(line);
+    command
+}
+
+/// Set the environment variables given to every process started for a build (the bundle command,
+/// the scripts of `run-node`, the WASI runtimes), so the commands don't depend on the directory
+/// they run in: `WASMBL_WORKSPACE_ROOT`, `WASMBL_PACKAGE_DIR`, `WASMBL_PACKAGE_NAME`,
+/// `WASMBL_BUILD_PATH` and `WASMBL_PROFILE`, and `SOURCE_DATE_EPOCH` for the reproducible builds.
+/// See [`BuildArgs::bundle_command`].
+pub(crate) fn process_env(args: &dyn BuildArgs, profile: BuildProfile, command: &mut Command) {
+    let package = args.frontend_package();
+    command
+        .env("WASMBL_WORKSPACE_ROOT", &args.metadata().workspace_root)
+        .env(
+            "WASMBL_PACKAGE_DIR",
+            package.manifest_path.parent().unwrap(),
+        )
+        .env("WASMBL_PACKAGE_NAME", &package.name)
+        .env("WASMBL_BUILD_PATH", args.build_path())
+        .env("WASMBL_PROFILE", profile.name());
+    reproducible_env(args, command);
+}
+
+/// Give the time of the reproducible build to the command. See [`BuildArgs::reproducible`].
+fn reproducible_env(args: &dyn BuildArgs, command: &mut Command) {
+    if args.reproducible() {
+        let epoch = build_meta::source_date_epoch(&args.metadata().workspace_root);
+        command.env(build_meta::SOURCE_DATE_EPOCH_VAR, epoch.to_string());
+    }
+}
+
+/// The URL path of [`BuildArgs::base_url`] with a `/` at both ends.
+fn base_url(args: &dyn BuildArgs) -> String {
+    match args.base_url().trim_matches('/') {
+        "" => "/".to_string(),
+        base_url => format!("/{}/", base_url),
+    }
+}
+
+/// Write the configuration of the static host from the files in the build directory. See
+/// [`BuildArgs::emit_server_config`].
+fn write_server_config(args: &dyn BuildArgs, hooks: &Hooks, server: ServerConfig) -> Result<()> {
+    let build_path = args.build_path();
+    let files: Vec<PathBuf> = list_files(&build_path)
+        .with_context(|| format!("could not list the files of `{}`", build_path.display()))?
+        .into_iter()
+        .filter_map(|x| x.strip_prefix(&build_path).ok().map(Path::to_path_buf))
+        .collect();
+    let has_extension =
+        |path: &Path, extension: &str| path.extension().map(|x| x == extension).unwrap_or(false);
+    let precompressed = |extension: &str| {
+        files
+            .iter()
+            .any(|x| has_extension(x, extension) && files.contains(&x.with_extension("")))
+    };
+
+    // NOTE: a file is hashed if the `asset_filter` hook changed its name
+    let mut hashed: Vec<String> = WRITTEN_ASSETS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(name, path)| {
+            path.file_name().map(|x| x.to_string_lossy()) != name.rsplit('/').next().map(Into::into)
+        })
+        .filter_map(|(_, path)| path.strip_prefix(&build_path).ok().map(slash_path))
+        .collect();
+    hashed.sort();
+
+    let site = server_config::Site {
+        base_url: base_url(args),
+        wasm: files
+            .iter()
+            .filter(|x| has_extension(x, "wasm"))
+            .map(|x| slash_path(x))
+            .collect(),
+        hashed,
+        precompressed: vec![("br", "br"), ("gz", "gzip")]
+            .into_iter()
+            .filter(|(extension, _)| precompressed(extension))
+            .map(|(_, encoding)| encoding)
+            .collect(),
+        spa: files.iter().any(|x| x == Path::new("index.html")),
+    };
+
+    for (name, content) in site.config(server) {
+        write_asset(
+            &build_path,
+            hooks,
+            Asset::new(name, name, content.into_bytes()),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Copy the static files to the build directory, or write the default `index.html` if there is
+/// no static directory and `write_index` is enabled.
+///
+/// The files are read, filtered and written in parallel, the ones that did not change since the
+/// previous build are not copied again (see [`static_cache`]).
+fn copy_static_files(
+    args: &dyn BuildArgs,
+    profile: BuildProfile,
+    build_path: &Path,
+    hooks: &Hooks,
+    write_index: bool,
+) -> Result<()> {
+    let static_dir = args.static_dir();
+
+    if static_dir.exists() {
+        let reporter = reporter::reporter();
+        reporter.stage_started(Stage::StaticFiles);
+        let start = Instant::now();
+        let files = list_files(&static_dir).with_context(|| {
+            format!(
+                "could not list the content of the directory static: `{}`",
+                static_dir.display()
+            )
+        })?;
+        let files: Vec<_> = files
+            .into_iter()
+            .filter(|file| {
+                let path = file
+                    .strip_prefix(&static_dir)
+                    .expect("the file is in the static directory; qed");
+                let valid = url_path(path).is_some();
+                if !valid {
+                    reporter.warning(&format!(
+                        "`{}` is not copied: its name can't be used in a URL",
+                        file.display()
+                    ));
+                }
+                valid
+            })
+            .collect();
+
+        let cache = static_cache::StaticCache::open(args.target_path(), build_path, profile);
+        let unchanged = std::sync::atomic::AtomicUsize::new(0);
+        // NOTE: `collect` keeps the order of the files, the first error is the one of the first
+        //       file in this order
+        files
+            .par_iter()
+            .map(|file| {
+                let path = file
+                    .strip_prefix(&static_dir)
+                    .expect("the file is in the static directory; qed");
+                let name = slash_path(&Path::new("static").join(path));
+                if let Some(entry) = cache.unchanged(&name, file, build_path) {
+                    if let Some((output, size)) = entry.output.as_ref() {
+                        let output = build_path.join(output);
+                        static_cache::touch(&output).map_err(|source| Error::Io {
+                            path: output.clone(),
+                            source,
+                        })?;
+                        reporter.artifact_written(&output, *size);
+                        WRITTEN_ASSETS.lock().unwrap().insert(name.clone(), output);
+                    }
+                    unchanged.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    cache.insert(name, entry);
+                    return Ok(());
+                }
+
+                let bytes = fs::read(file).with_context(|| {
+                    format!(
+                        "could not copy `{}` to `{}`",
+                        file.display(),
+                        build_path.display()
+                    )
+                })?;
+                let output = write_asset(
+                    build_path,
+                    hooks,
+                    Asset::new(path.to_owned(), name.clone(), bytes),
+                )?;
+                let output = match output {
+                    Some(output) => {
+                        let size = fs::metadata(&output).map(|x| x.len()).unwrap_or(0);
+                        output
+                            .strip_prefix(build_path)
+                            .ok()
+                            .map(|x| (slash_path(x), size))
+                    }
+                    None => None,
+                };
+                if let Some(entry) = static_cache::Entry::of(file, output) {
+                    cache.insert(name, entry);
+                }
+                Ok(())
+            })
+            .collect::<Result<Vec<()>>>()?;
+        cache.save();
+
+        let duration = start.elapsed();
+        reporter.stage_finished(Stage::StaticFiles, duration);
+        log::info!(
+            "Copied {} static files in {:.2?} ({} unchanged)",
+            files.len(),
+            duration,
+            unchanged.into_inner()
+        );
+    } else if write_index {
+        write_asset(
+            build_path,
+            hooks,
+            Asset::new(
+                "index.html",
+                "index.html",
+                default_index(args.out_name(), &base_url(args)).into(),
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The output of `wasm-bindgen`.
+struct Bindings {
+    js: String,
+    wasm: Vec<u8>,
+    typescript: Option<String>,
+    snippets: Vec<(PathBuf, String)>,
+    /// The JS imported by the JS bindings of a bundler (`app_bg.js`).
+    start: Option<String>,
+}
+
+fn bindgen(
+    wasm_path: &Path,
+    profile: BuildProfile,
+    args: &dyn BuildArgs,
+    hooks: &Hooks,
+) -> Result<Bindings, Error> {
+    use wasm_bindgen_cli_support::Bindgen;
+
+    let layout = args.out_layout();
+    let pkg = layout == OutLayout::Pkg;
+    let mut bindgen = Bindgen::new();
+    bindgen
+        .input_path(wasm_path)
+        .out_name(args.out_name())
+        .web(layout == OutLayout::Flat)
+        .and_then(|x| x.bundler(pkg))
+        .and_then(|x| x.nodejs(layout == OutLayout::Node))
+        .expect("fails only if multiple modes specified; qed")
+        .debug(!matches!(profile, BuildProfile::Release))
+        .typescript(args.typescript() || pkg);
+    // NOTE: the debuggers need the DWARF to find the sources
+    #[cfg(feature = "dev-server")]
+    if profile == BuildProfile::Dev && sources::url().is_some() {
+        bindgen.keep_debug(true);
+    }
+    (hooks.bindgen)(args, &mut bindgen).map_err(|source| Error::Hook {
+        hook: "bindgen",
+        source,
+    })?;
+    let mut output = bindgen.generate_output().map_err(Error::BindgenFailed)?;
+
+    // NOTE: same layout as `wasm-bindgen`, the JS bindings import the snippets from there
+    let snippets = output
+        .snippets()
+        .iter()
+        .flat_map(|(identifier, list)| {
+            list.iter().enumerate().map(move |(i, js)| {
+                (
+                    Path::new("snippets")
+                        .join(identifier)
+                        .join(format!("inline{}.js", i)),
+                    js.clone(),
+                )
             })
+        })
+        .chain(
+            output
+                .local_modules()
+                .iter()
+                .map(|(path, js)| (Path::new("snippets").join(path), js.clone())),
+        )
+        .collect();
+
+    Ok(Bindings {
+        js: output.js().to_owned(),
+        typescript: output.ts().map(str::to_owned),
+        start: output.start().cloned(),
+        wasm: output.wasm_mut().emit_wasm(),
+        snippets,
+    })
+}
+
+/// The entries of the `package.json` of a build, relative to its directory.
+struct PackageEntries {
+    js: String,
+    typescript: Option<String>,
+    /// The files of the package, only for the layout `pkg`: the build directory is not published.
+    files: Option<Vec<String>>,
+    snippets: bool,
+}
+
+/// Write the `package.json` of the build: in `pkg/` for the layout `pkg`, in the build directory
+/// if [`BuildArgs::package_json`] is enabled.
+///
+/// A `package.json` that has not been written by a previous build is only replaced if
+/// [`BuildArgs::overwrite_package_json`] is enabled.
+fn write_package_json(
+    args: &dyn BuildArgs,
+    output: &BuildOutput,
+    build_files: &build_files::BuildFiles,
+    hooks: &Hooks,
+    bg_js: Option<String>,
+) -> Result<(), Error> {
+    let build_path = args.build_path();
+    let layout = args.out_layout();
+    let path = layout.dir().join("package.json");
+    if build_path.join(&path).exists()
+        && !build_files.is_recorded(&path)
+        && !args.overwrite_package_json()
+    {
+        reporter::reporter().warning(&format!(
+            "`{}` has not been generated by a build, it is not replaced (pass \
+            `--overwrite-package-json` to replace it)",
+            build_path.join(&path).display()
+        ));
+        return Ok(());
+    }
+
+    // NOTE: the name of a file written by the `asset_filter` hook, relative to the package
+    let package_dir = build_path.join(layout.dir());
+    let entry = |name: String| {
+        written_asset(&slash_path(&layout.dir().join(&name)))
+            .and_then(|x| x.strip_prefix(&package_dir).ok().map(slash_path))
+            .unwrap_or(name)
+    };
+    let out_name = args.out_name();
+    let entries = PackageEntries {
+        js: entry(format!("{}.js", out_name)),
+        typescript: output
+            .typescript
+            .as_ref()
+            .map(|_| entry(format!("{}.d.ts", out_name))),
+        files: (layout == OutLayout::Pkg).then(|| {
+            vec![
+                Some(format!("{}.js", out_name)),
+                Some(format!("{}_bg.wasm", out_name)),
+                bg_js,
+            ]
+            .into_iter()
+            .flatten()
+            .chain(
+                output
+                    .typescript
+                    .as_ref()
+                    .map(|_| format!("{}.d.ts", out_name)),
+            )
+            .map(entry)
+            .chain((!output.snippets.is_empty()).then(|| "snippets".to_string()))
+            .collect()
+        }),
+        snippets: !output.snippets.is_empty(),
+    };
+
+    let content = package_json(args.frontend_package(), &entries);
+    write_asset(
+        &build_path,
+        hooks,
+        Asset::new(&path, slash_path(&path), content.into_bytes()),
+    )?;
+    Ok(())
+}
+
+/// The `package.json` of a build, with the fields of the manifest of the frontend package like
+/// `wasm-pack` does. The fields of the table `[package.metadata.wasmbl.package-json]` replace the
+/// generated ones.
+fn package_json(package: &Package, entries: &PackageEntries) -> String {
+    let mut side_effects = vec![format!("./{}", entries.js)];
+    if entries.snippets {
+        side_effects.push("./snippets/*".to_string());
+    }
+
+    let mut json = serde_json::json!({
+        "name": package.name,
+        "version": package.version.to_string(),
+        "type": "module",
+        "main": entries.js,
+        "module": entries.js,
+        "sideEffects": side_effects,
+    });
+    if let Some(typescript) = entries.typescript.as_ref() {
+        json["types"] = typescript.as_str().into();
+    }
+    if let Some(files) = entries.files.as_ref() {
+        json["files"] = files.clone().into();
+    }
+    if let Some(description) = package.description.as_ref() {
+        json["description"] = description.as_str().into();
+    }
+    if let Some(license) = package.license.as_ref() {
+        json["license"] = license.as_str().into();
+    }
+    if let Some(repository) = package.repository.as_ref() {
+        json["repository"] = serde_json::json!({ "type": "git", "url": repository });
+    }
+
+    let overrides = package
+        .metadata
+        .get("wasmbl")
+        .and_then(|x| x.get("package-json"))
+        .and_then(|x| x.as_object());
+    let fields = json.as_object_mut().expect("the JSON is an object; qed");
+    for (key, value) in overrides.into_iter().flatten() {
+        fields.insert(key.clone(), value.clone());
+    }
+
+    format!(
+        "{}\n",
+        serde_json::to_string_pretty(&json).expect("the JSON is built in memory; qed")
+    )
+}
+
+/// Name of the WASM of the frontend package built for [`BuildTarget::Web`]: the name of its library
+/// (`[lib] name`), the name of the package by default.
+fn lib_name(package: &Package) -> String {
+    let name = package
+        .targets
+        .iter()
+        .find(|x| x.kind.iter().any(|kind| kind == "cdylib"))
+        .map(|x| x.name.as_str())
+        .unwrap_or(&package.name);
+
+    // NOTE: the binaries keep the dashes of their names, not the libraries
+    name.replace('-', "_")
+}
+
+/// Name of the binary of the frontend package built for [`BuildTarget::Wasi`]: the binary with the
+/// name of the package or its only binary.
+fn wasi_binary(package: &Package) -> Result<&str> {
+    let binaries: Vec<&str> = package
+        .targets
+        .iter()
+        .filter(|x| x.kind.iter().any(|kind| kind == "bin"))
+        .map(|x| x.name.as_str())
+        .collect();
+
+    match binaries.as_slice() {
+        [] => bail!(
+            "the package `{}` has no binary to build for `wasm32-wasi`",
+            package.name
+        ),
+        [name] => Ok(name),
+        _ => binaries
+            .iter()
+            .find(|x| **x == package.name)
+            .copied()
+            .with_context(|| {
+                format!(
+                    "the package `{}` has multiple binaries and none of them is named after the \
+                    package: {}",
+                    package.name,
+                    format_names(&binaries),
+                )
+            }),
+    }
+}
+
+/// Run a cargo command and fail if it doesn't exit successfully.
+///
+/// The diagnostics of the compiler are given to the reporter and the artifacts are returned if the
+/// messages are in JSON (`--message-format=json`).
+/// The output of cargo, read by the threads of [`run_cargo`].
+enum CargoOutput {
+    Message(Box<io::Result<Message>>),
+    /// A line of the standard error of cargo.
+    Line(String),
+    /// Cargo waits for a lock held by another cargo command (the lock of the build directory,
+    /// of the package cache, ...).
+    Blocked(String),
+    /// Cargo printed something else after waiting for a lock.
+    Unblocked,
+}
+
+/// The cargo command that compiles the frontend package (`build`) or only checks it (`check`),
+/// before the `pre_build` hook.
+///
+/// NOTE: the check and the build get the same flags so they share the incremental state of cargo
+fn cargo_command(
+    subcommand: &str,
+    profile: BuildProfile,
+    args: &dyn BuildArgs,
+    quiet: bool,
+) -> Result<Command, Error> {
+    use std::io::IsTerminal;
+
+    let target = args.target();
+    let frontend_package = args.frontend_package();
+    let mut command = Command::new("cargo");
+
+    command.arg(subcommand);
+    match target {
+        BuildTarget::Web => command.arg("--lib"),
+        BuildTarget::Wasi => command.args(["--bin", wasi_binary(frontend_package)?]),
+    };
+    command
+        .args(["--target", target.triple(), "--manifest-path"])
+        .arg(&frontend_package.manifest_path);
+    cargo_flags(&mut command, profile, args);
+    if quiet {
+        command.arg("--quiet");
+    }
+    // NOTE: the diagnostics are given to the reporter, rendered like cargo would do
+    command.arg(if std::io::stderr().is_terminal() {
+        "--message-format=json-diagnostic-rendered-ansi"
+    } else {
+        "--message-format=json"
+    });
+
+    #[cfg(feature = "dev-server")]
+    if let Some(url) = sources::url().filter(|_| profile == BuildProfile::Dev) {
+        append_rustflags(
+            &mut command,
+            sources::remap_flags(url, &sources::roots(&args.metadata().workspace_root)),
+        );
+    }
+
+    Ok(command)
+}
+
+/// The flags of cargo given by the build arguments (the profile, the features, the target
+/// directory, the panic strategy, ...), shared by the builds and the tests.
+fn cargo_flags(command: &mut Command, profile: BuildProfile, args: &dyn BuildArgs) {
+    let frontend_package = args.frontend_package();
+    command.args(match profile {
+        BuildProfile::Profiling => &["--release"] as &[&str],
+        BuildProfile::Release => &["--release"],
+        BuildProfile::Dev => &[],
+    });
+    let panic_strategy = args.panic_strategy(profile);
+    let mut features: Vec<_> = args
+        .features()
+        .iter()
+        .filter(|x| !x.is_empty())
+        .map(String::as_str)
+        .collect();
+    if panic_strategy == Some(PanicStrategy::UnwindVerbose)
+        && frontend_package.features.contains_key(PANIC_HOOK_FEATURE)
+        && !features.contains(&PANIC_HOOK_FEATURE)
+    {
+        features.push(PANIC_HOOK_FEATURE);
+    }
+    if !features.is_empty() {
+        command.arg("--features").arg(features.join(","));
+    }
+    if !args.default_features() {
+        command.arg("--no-default-features");
+    }
+    if args.all_features() {
+        command.arg("--all-features");
+    }
+    if let Some(target_dir) = args.target_dir() {
+        command.arg("--target-dir").arg(target_dir);
+    }
+    if let Some(strategy) = panic_strategy {
+        strategy.apply(command, profile);
+    }
+    // NOTE: `wasm-bindgen` enables the reference types if the WASM has been compiled with them
+    if args.reference_types() {
+        append_rustflags(command, ["-Ctarget-feature=+reference-types"]);
+    }
+    reproducible_env(args, command);
+}
+
+/// Check the frontend package with `cargo check`, for [`ServeArgs::check_first`]. The `pre_build`
+/// hook is called like for a build. The check is cancelled as soon as a file changes
+/// ([`Error::BuildCancelled`]).
+fn check(
+    profile: BuildProfile,
+    args: &dyn BuildArgs,
+    hooks: &Hooks,
+    quiet: bool,
+) -> Result<(), Error> {
+    let profile = resolve_profile(profile, args);
+    let mut command = cargo_command("check", profile, args, quiet)?;
+    // NOTE: only the rebuilds are checked first
+    (hooks.pre_build)(args, profile, &mut command, BuildTrigger::WatchRebuild).map_err(
+        |source| Error::Hook {
+            hook: "pre_build",
+            source,
+        },
+    )?;
+    rustflags::merge_rustflags(&mut command);
+
+    let reporter = reporter::reporter();
+    reporter.stage_started(Stage::Check);
+    log::debug!("Running {:?}", command);
+    let start = Instant::now();
+    diagnostics::start(&args.metadata().workspace_root);
+    run_cargo_cancellable(&mut command, reporter, true)?;
+    reporter.stage_finished(Stage::Check, start.elapsed());
+
+    Ok(())
+}
+
+/// Run cargo and collect the artifacts of the build.
+///
+/// The standard error of cargo is forwarded to the terminal and watched for the messages that
+/// cargo prints when another cargo command holds a lock. A rebuild of the watcher that waits for
+/// a lock is cancelled if a file changes in the meantime ([`Error::BuildCancelled`]).
+fn run_cargo(
+    command: &mut Command,
+    reporter: &dyn Reporter,
+) -> Result<Vec<cargo_metadata::Artifact>, Error> {
+    run_cargo_cancellable(command, reporter, false)
+}
+
+/// Like [`run_cargo`] but the command is cancelled as soon as a file changes if `always` is
+/// `true`, even if cargo doesn't wait for a lock.
+fn run_cargo_cancellable(
+    command: &mut Command,
+    reporter: &dyn Reporter,
+    always: bool,
+) -> Result<Vec<cargo_metadata::Artifact>, Error> {
+    use std::io::{BufRead, IsTerminal};
+
+    // NOTE: cargo doesn't color its output when the standard error is piped
+    let json = logger::is_json();
+    if std::io::stderr().is_terminal() && std::env::var_os("CARGO_TERM_COLOR").is_none() && !json {
+        command.env("CARGO_TERM_COLOR", "always");
+    }
+    let mut child =
+        process::GroupChild::spawn(command.stdout(Stdio::piped()).stderr(Stdio::piped()))
+            .context("could not start build process")?;
+
+    let (tx, rx) = mpsc::channel();
+    let stdout = BufReader::new(child.stdout.take().expect("stdout is piped; qed"));
+    let stdout_tx = tx.clone();
+    std::thread::spawn(move || {
+        for message in Message::parse_stream(stdout) {
+            if stdout_tx
+                .send(CargoOutput::Message(Box::new(message)))
+                .is_err()
+            {
+                break;
+            }
         }
-        #[cfg(not(feature = "dev-server"))]
-        {
-            use std::sync::Arc;
-            use std::thread;
+    });
+    let mut stderr = BufReader::new(child.stderr.take().expect("stderr is piped; qed"));
+    std::thread::spawn(move || {
+        let mut line = Vec::new();
+        let mut blocked = false;
+        while matches!(stderr.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(['\n', '\r']);
+            if tx.send(CargoOutput::Line(text.to_string())).is_err() {
+                break;
+            }
+            let output = match text.split_once(CARGO_LOCK_MESSAGE) {
+                Some((_, lock)) => {
+                    blocked = true;
+                    let lock = lock
+                        .trim()
+                        .trim_start_matches("on ")
+                        .trim_end_matches("\x1b[0m");
+                    Some(CargoOutput::Blocked(lock.to_string()))
+                }
+                None if blocked => {
+                    blocked = false;
+                    Some(CargoOutput::Unblocked)
+                }
+                None => None,
+            };
+            if output.is_some_and(|x| tx.send(x).is_err()) {
+                break;
+            }
+            line.clear();
+        }
+    });
+
+    let mut artifacts = Vec::new();
+    let mut blocked = false;
+    loop {
+        let output = match rx.recv_timeout(CARGO_LOCK_POLL_INTERVAL) {
+            Ok(output) => output,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if (blocked || always) && watcher::superseded() {
+                    log::debug!("Cancelling the build after the new changes");
+                    child
+                        .terminate()
+                        .context("could not stop the build process")?;
+                    return Err(Error::BuildCancelled);
+                }
+                continue;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+
+        match output {
+            CargoOutput::Message(message) => {
+                match (*message).context("could not read the output of the build process")? {
+                    Message::CompilerMessage(message) => {
+                        diagnostics::record(&message.message);
+                        reporter.diagnostic(&message.message);
+                    }
+                    Message::CompilerArtifact(artifact) => artifacts.push(artifact),
+                    Message::TextLine(line) => log::debug!("{}", line),
+                    _ => {}
+                }
+            }
+            CargoOutput::Line(line) => reporter.cargo_output(&line),
+            CargoOutput::Blocked(lock) => {
+                blocked = true;
+                reporter.waiting_for_lock(&lock);
+            }
+            CargoOutput::Unblocked => blocked = false,
+        }
+    }
+
+    let status = child
+        .wait()
+        .context("could not wait for the build process")?;
+    if !status.success() {
+        return Err(Error::CargoBuildFailed { status });
+    }
+
+    Ok(artifacts)
+}
+
+/// Log a line of the standard error of cargo for `--log-format json`, at the level of its prefix.
+fn log_cargo_line(line: &str) {
+    let level = if line.starts_with("error") {
+        log::Level::Error
+    } else if line.starts_with("warning") {
+        log::Level::Warn
+    } else {
+        log::Level::Debug
+    };
+    if !line.trim().is_empty() {
+        log::log!(target: "wasmbl::cargo", level, "{}", line.trim());
+    }
+}
+
+/// Configure the HTTP server of the `serve` command.
+///
+/// NOTE: the middleware registered first wraps the others, the middleware of the
+///       `serve_middleware` hook applies to the live reload too
+#[cfg(feature = "dev-server")]
+fn dev_server(
+    args: &dyn ServeArgs,
+    hooks: &Hooks,
+    live_reload: &live_reload::LiveReload,
+) -> Result<Server<()>> {
+    let mut app = tide::new();
+
+    if args.log() && logger::is_json() {
+        app.with(logger::request_events);
+    }
+    (hooks.serve_middleware)(args, &mut app)?;
+
+    // NOTE: the files of the mounts of the `serve` hook and the index of the fallback too
+    app.with(content_type_middleware);
+
+    if args.live_reload() {
+        live_reload.register(&mut app);
+    }
+    if args.control_api() {
+        live_reload::register_control(live_reload, &mut app);
+    }
+    if args.serve_sources() && args.profile() == BuildProfile::Dev {
+        sources::register(
+            &mut app,
+            sources::roots(&args.build_args().metadata().workspace_root),
+        );
+    }
+
+    (hooks.serve)(args, &mut app)?;
+
+    Ok(app)
+}
+
+#[cfg(not(feature = "dev-server"))]
+fn watch_backend(
+    args: &dyn ServeArgs,
+    hooks: &Hooks,
+    orchestrator: &pipeline::Orchestrator,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+
+    let mut watcher = FileWatcher::new(tx, args)?;
+
+    let watch = |args: &dyn ServeArgs, watcher: &mut FileWatcher| -> Result<()> {
+        (hooks.backend_watch)(args, watcher)?;
+        watch_extra_paths(args, watcher)
+    };
+    ignore_builtin(args, &mut watcher);
+    watcher.register(|watcher| watch(args, watcher))?;
+
+    // NOTE: the process group of the server is terminated when it is dropped
+    let run_server = || -> Result<process::GroupChild> {
+        let mut command = Command::new("cargo");
+        (hooks.backend_command)(args, &mut command)?;
+        log::debug!("Running {:?}", command);
+        Ok(process::GroupChild::spawn(&mut command)?)
+    };
+
+    let mut process_guard = Some(run_server()?);
+
+    // NOTE: the changes of the backend don't delay the rebuilds of the frontend
+    watch_loop(args, rx, &mut watcher, None, |change, watcher| {
+        if change == watcher::Change::Manifest {
+            rescan(args, watcher, &watch)?;
+        }
+        orchestrator.run(pipeline::Stage::Backend, || {
+            drop(process_guard.take());
+            process_guard.replace(run_server()?);
+            Ok(())
+        })
+    })
+}
+
+fn watch_frontend(
+    args: &dyn ServeArgs,
+    hooks: &Hooks,
+    live_reload: &live_reload::LiveReload,
+    output: BuildOutput,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+
+    let mut watcher = FileWatcher::new(tx, args)?;
+
+    let watch = |args: &dyn ServeArgs, watcher: &mut FileWatcher| -> Result<()> {
+        (hooks.frontend_watch)(args, watcher)?;
+        watch_extra_paths(args, watcher)?;
+        watch_bundle_sources(args, watcher)?;
+        watch_locales(args, watcher)?;
+        watch_templates(args, watcher)?;
+        watch_serve_path(args, watcher)
+    };
+    ignore_builtin(args, &mut watcher);
+    watcher.register(|watcher| watch(args, watcher))?;
+    live_reload.set_rebuild_handle(watcher.rebuild_handle());
+
+    let build_args = args.build_args();
+    // NOTE: the changes of the assets only run the `post_build` hook on the output of the last
+    //       build, starting with the first one
+    let mut last_output = Some(BuildOutput {
+        trigger: BuildTrigger::WatchRebuild,
+        ..output
+    });
+
+    let quiet = args.clear() && !args.verbose();
+    let mut notifier = notification::Notifier::default();
+
+    let orchestrator = live_reload.orchestrator();
+    watch_loop(
+        args,
+        rx,
+        &mut watcher,
+        Some(orchestrator),
+        |change, watcher| {
+            if change == watcher::Change::Served {
+                log::debug!("Reloading after a change in the serve directory");
+                live_reload.broadcast(live_reload::ReloadEvent::Reload);
+                return Ok(());
+            }
+
+            log::debug!("Rebuilding after a change: {:?}", change);
+            let start = Instant::now();
+            if args.clear() {
+                reporter::reporter().clear();
+            }
+
+            let res = orchestrator.run(pipeline::Stage::Frontend, || {
+                live_reload.rebuild(&build_args.build_path(), || {
+                    match (change, last_output.as_ref()) {
+                        (watcher::Change::Asset, Some(output)) => {
+                            let reporter = reporter::reporter();
+                            reporter.stage_started(Stage::PostBuild);
+                            let start = Instant::now();
+                            (hooks.post_build)(build_args, output, hooks)?;
+                            reporter.stage_finished(Stage::PostBuild, start.elapsed());
+                            if output.target == BuildTarget::Web {
+                                run_bundle_command(build_args, hooks, output)?;
+                            }
+                            Ok(())
+                        }
+                        (watcher::Change::Bundle, Some(output))
+                            if output.target == BuildTarget::Web =>
+                        {
+                            run_bundle_command(build_args, hooks, output)?;
+                            Ok(())
+                        }
+                        (watcher::Change::Manifest, _) => {
+                            // NOTE: members and path dependencies might have been added or removed
+                            rescan(args, watcher, &watch)?;
+                            if args.check_first() {
+                                check(args.profile(), build_args, hooks, quiet)?;
+                            }
+                            last_output = Some(build(
+                                args.profile(),
+                                build_args,
+                                hooks,
+                                quiet,
+                                BuildTrigger::WatchRebuild,
+                            )?);
+                            Ok(())
+                        }
+                        // NOTE: for a snippet, cargo might have nothing to rebuild but the bindings always
+                        //       copy the snippets again
+                        _ => {
+                            if args.check_first() {
+                                check(args.profile(), build_args, hooks, quiet)?;
+                            }
+                            last_output = Some(build(
+                                args.profile(),
+                                build_args,
+                                hooks,
+                                quiet,
+                                BuildTrigger::WatchRebuild,
+                            )?);
+                            Ok(())
+                        }
+                    }
+                })
+            });
+            if res.as_ref().is_err_and(is_cancelled) {
+                return res;
+            }
+            #[cfg(feature = "dev-server")]
+            if res.is_ok() {
+                warn_missing_index(args);
+            }
+            live_reload.set_status(live_reload::BuildStatus::new(
+                &res,
+                start.elapsed(),
+                last_output.as_ref(),
+            ));
+            // NOTE: the errors are often far above in the output of cargo
+            if let Some(summary) = res.as_ref().err().and_then(diagnostics::failure_summary) {
+                log::error!("Build failed: {}", summary);
+            }
 
-            if self.build_args().backend_package().is_none() {
-                bail!("missing backend crate name");
+            if args.notify() {
+                notifier.notify(res.as_ref().err().map(first_line).as_deref());
             }
 
-            let args = Arc::new(self);
-            let t1 = {
-                let args = Arc::clone(&args);
-                thread::spawn(move || watch_frontend(&*args, hooks))
-            };
-            let t2 = thread::spawn(move || watch_backend(&*args, hooks));
-            let _ = t1.join();
-            let _ = t2.join();
+            if !args.clear() {
+                return res;
+            }
 
-            Err(anyhow!("server and watcher unexpectedly exited"))
-        }
-    }
+            match res {
+                Ok(()) => reporter::reporter().status(
+                    true,
+                    &format!(
+                        "rebuilt in {:.1}s (wasm {}{}) \u{2014} {}",
+                        start.elapsed().as_secs_f64(),
+                        format_size(last_output.as_ref().map(|x| x.wasm.len()).unwrap_or(0)),
+                        match last_output.as_ref() {
+                            Some(output)
+                                if output.profile != BuildProfile::Dev && !output.optimized =>
+                            {
+                                ", not optimized"
+                            }
+                            _ => "",
+                        },
+                        orchestrator.state().describe(),
+                    ),
+                ),
+                Err(err) => {
+                    if args.verbose() {
+                        log::error!("{:?}", err);
+                    }
+                    reporter::reporter().status(
+                        false,
+                        &format!(
+                            "build failed: {} \u{2014} {}",
+                            first_line(&err),
+                            orchestrator.state().describe()
+                        ),
+                    );
+                }
+            }
+
+            Ok(())
+        },
+    )
 }
 
-impl_downcast!(ServeArgs);
+/// Watch the paths of [`WatchArgs::watch_paths`].
+fn watch_extra_paths(args: &dyn WatchArgs, watcher: &mut FileWatcher) -> Result<()> {
+    use notify::Watcher;
 
-impl ServeArgs for DefaultServeArgs {
-    #[cfg(feature = "dev-server")]
-    fn log(&self) -> bool {
-        self.log
+    for path in args.watch_paths() {
+        watcher
+            .watch(&path, notify::RecursiveMode::Recursive)
+            .with_context(|| format!("could not watch `{}`", path.display()))?;
     }
 
-    #[cfg(feature = "dev-server")]
-    fn ip(&self) -> &str {
-        &self.ip
-    }
+    Ok(())
+}
 
-    #[cfg(feature = "dev-server")]
-    fn port(&self) -> u16 {
-        self.port
-    }
+/// Watch the directory of [`BuildArgs::bundle_sources`] if there is one.
+fn watch_bundle_sources(args: &dyn ServeArgs, watcher: &mut FileWatcher) -> Result<()> {
+    use notify::Watcher;
 
-    fn build_args(&self) -> &dyn BuildArgs {
-        &self.build_args
+    match args.build_args().bundle_sources() {
+        Some(path) => watcher
+            .watch(&path, notify::RecursiveMode::Recursive)
+            .with_context(|| format!("could not watch `{}`", path.display())),
+        None => Ok(()),
     }
 }
 
-/// Hooks.
-///
-/// Check the code of [`Hooks::default()`] implementation to see what they do by default.
-///
-/// If you don't provide your own hook, the default code will be executed. But if you do provide a
-/// hook, the code will be *replaced*.
-pub struct Hooks {
-    /// This hook will be run before the WASM is compiled. It does nothing by default.
-    /// You can tweak the command-line arguments of the build command here or create additional
-    /// files in the build directory.
-    pub pre_build:
-        Box<dyn Fn(&dyn BuildArgs, BuildProfile, &mut Command) -> Result<()> + Send + Sync>,
-
-    /// This hook will be run after the WASM is compiled and optimized.
-    /// By default it copies the static files to the build directory.
-    #[allow(clippy::type_complexity)]
-    pub post_build:
-        Box<dyn Fn(&dyn BuildArgs, BuildProfile, String, Vec<u8>) -> Result<()> + Send + Sync>,
+/// Watch the directory of the locales of [`BuildArgs::i18n`] if there is one.
+fn watch_locales(args: &dyn ServeArgs, watcher: &mut FileWatcher) -> Result<()> {
+    use notify::Watcher;
 
-    /// This hook will be run before running the HTTP server.
-    /// By default it will add routes to the files in the build directory.
-    #[cfg(feature = "dev-server")]
-    #[allow(clippy::type_complexity)]
-    pub serve: Box<dyn Fn(&dyn ServeArgs, &mut Server<()>) -> Result<()> + Send + Sync>,
+    match args.build_args().i18n() {
+        Some(i18n) if i18n.locales_dir.exists() => watcher
+            .watch(&i18n.locales_dir, notify::RecursiveMode::Recursive)
+            .with_context(|| format!("could not watch `{}`", i18n.locales_dir.display())),
+        _ => Ok(()),
+    }
+}
 
-    /// This hook will be run before starting to watch for changes in files.
-    /// By default it will add all the `src/` directories and `Cargo.toml` files of all the crates
-    /// in the workspace plus the `static/` directory if it exists in the frontend crate.
-    pub frontend_watch:
-        Box<dyn Fn(&dyn ServeArgs, &mut RecommendedWatcher) -> Result<()> + Send + Sync>,
+/// Watch the templates of [`BuildArgs::pages`], the static directory and
+/// [`BuildArgs::frontend_dir`] that are not watched yet, like a template outside of the frontend
+/// package. Their changes only run the `post_build` hook.
+fn watch_templates(args: &dyn ServeArgs, watcher: &mut FileWatcher) -> Result<()> {
+    use notify::Watcher;
 
-    /// This hook will be run before starting to watch for changes in files.
-    /// By default it will add the backend crate directory and all its dependencies. But it
-    /// excludes the target directory.
-    pub backend_watch:
-        Box<dyn Fn(&dyn ServeArgs, &mut RecommendedWatcher) -> Result<()> + Send + Sync>,
+    let build_args = args.build_args();
+    let dirs = pages::dir(build_args.frontend_package(), &frontend_root(build_args))
+        .into_iter()
+        .chain(Some(build_args.static_dir()).filter(|x| x.is_dir()))
+        .chain(build_args.frontend_dir().filter(|x| x.is_dir()));
+    let templates = build_args
+        .pages()
+        .into_iter()
+        .map(|x| x.template)
+        .filter(|x| x.is_file());
+    for (path, mode) in dirs
+        .map(|x| (x, notify::RecursiveMode::Recursive))
+        .chain(templates.map(|x| (x, notify::RecursiveMode::NonRecursive)))
+    {
+        if !watcher.is_watched(&path) {
+            watcher
+                .watch(&path, mode)
+                .with_context(|| format!("could not watch `{}`", path.display()))?;
+        }
+    }
 
-    /// This hook will be run before (re-)starting the backend.
-    /// You can tweak the cargo command that is run here: adding/removing environment variables or
-    /// adding arguments.
-    /// By default it will do `cargo run -p <backend_crate>`.
-    pub backend_command: Box<dyn Fn(&dyn ServeArgs, &mut Command) -> Result<()> + Send + Sync>,
+    Ok(())
 }
 
-impl Default for Hooks {
-    fn default() -> Self {
-        Self {
-            backend_command: Box::new(|args, command| {
-                command.args(&[
-                    "run",
-                    "-p",
-                    &args
-                        .build_args()
-                        .backend_package()
-                        .context("missing backend crate name")?
-                        .name,
-                ]);
-                Ok(())
-            }),
-            backend_watch: Box::new(|args, watcher| {
-                use notify::{RecursiveMode, Watcher};
-
-                let metadata = args.build_args().metadata();
-                let backend = args
-                    .build_args()
-                    .backend_package()
-                    .context("missing backend crate name")?;
-                let packages: HashMap<_, _> = metadata
-                    .packages
-                    .iter()
-                    .map(|x| (x.name.as_str(), x))
-                    .collect();
-                let members: HashSet<_> = HashSet::from_iter(&metadata.workspace_members);
-
-                backend
-                    .dependencies
-                    .iter()
-                    .map(|x| packages.get(x.name.as_str()).unwrap())
-                    .filter(|x| members.contains(&x.id))
-                    .map(|x| x.manifest_path.parent().unwrap())
-                    .chain(iter::once(backend.manifest_path.parent().unwrap()))
-                    .try_for_each(|x| watcher.watch(x, RecursiveMode::Recursive))?;
-
-                Ok(())
-            }),
-            frontend_watch: Box::new(|args, watcher| {
-                use notify::{RecursiveMode, Watcher};
+/// Watch the serve directory if it is not the build directory.
+fn watch_serve_path(args: &dyn ServeArgs, watcher: &mut FileWatcher) -> Result<()> {
+    use notify::Watcher;
 
-                let metadata = args.build_args().metadata();
-                let frontend = args.build_args().frontend_package();
-                let packages: HashMap<_, _> = metadata
-                    .packages
-                    .iter()
-                    .map(|x| (x.name.as_str(), x))
-                    .collect();
-                let members: HashSet<_> = HashSet::from_iter(&metadata.workspace_members);
-
-                frontend
-                    .dependencies
-                    .iter()
-                    .map(|x| packages.get(x.name.as_str()).unwrap())
-                    .filter(|x| members.contains(&x.id))
-                    .map(|x| x.manifest_path.parent().unwrap())
-                    .chain(iter::once(frontend.manifest_path.parent().unwrap()))
-                    .try_for_each(|x| watcher.watch(x, RecursiveMode::Recursive))?;
+    let serve_path = args.serve_path();
+    if watcher::same_path(&serve_path, &args.build_args().build_path()) {
+        return Ok(());
+    }
 
-                Ok(())
-            }),
-            pre_build: Box::new(|_, _, _| Ok(())),
-            post_build: Box::new(
-                |args, #[allow(unused_variables)] profile, wasm_js, wasm_bin| {
-                    let build_path = args.build_path();
-                    let wasm_js_path = build_path.join("app.js");
-                    let wasm_bin_path = build_path.join("app_bg.wasm");
-
-                    fs::write(&wasm_js_path, wasm_js).with_context(|| {
-                        format!("could not write JS file to `{}`", wasm_js_path.display())
-                    })?;
-                    fs::write(&wasm_bin_path, wasm_bin).with_context(|| {
-                        format!("could not write WASM file to `{}`", wasm_bin_path.display())
-                    })?;
+    watcher
+        .watch(&serve_path, notify::RecursiveMode::Recursive)
+        .with_context(|| format!("could not watch `{}`", serve_path.display()))
+}
 
-                    let index_path = build_path.join("index.html");
-                    let static_dir = args
-                        .frontend_package()
-                        .manifest_path
-                        .parent()
-                        .unwrap()
-                        .join("static");
-
-                    if index_path.exists() {
-                        fs::copy("index.html", &index_path).context(format!(
-                            "could not copy index.html to `{}`",
-                            index_path.display()
-                        ))?;
-                    } else if static_dir.exists() {
-                        dir::copy(
-                            &static_dir,
-                            &build_path,
-                            &dir::CopyOptions {
-                                content_only: true,
-                                ..dir::CopyOptions::new()
-                            },
-                        )
-                        .with_context(|| {
-                            format!(
-                                "could not copy content of directory static: `{}` to `{}`",
-                                static_dir.display(),
-                                build_path.display()
-                            )
-                        })?;
-                    } else {
-                        fs::write(&index_path, DEFAULT_INDEX).with_context(|| {
-                            format!(
-                                "could not write default index.html to `{}`",
-                                index_path.display()
-                            )
-                        })?;
-                    }
+/// The default of [`ServeArgs::watch_extensions`].
+fn default_watch_extensions(package: &Package) -> Vec<String> {
+    package_metadata_strings(package, "watch-extensions")
+        .unwrap_or_else(|| vec!["rs".to_string(), "toml".to_string()])
+}
 
-                    #[cfg(feature = "sass")]
-                    {
-                        let options = args.sass_options(profile);
-                        for style_path in args.sass_lookup_directories(profile) {
-                            args.build_sass_from_dir(&style_path, options.clone())?;
-                        }
-                    }
+/// The default of [`ServeArgs::watch_snippet_extensions`].
+fn default_watch_snippet_extensions(package: &Package) -> Vec<String> {
+    package_metadata_strings(package, "watch-snippet-extensions")
+        .unwrap_or_else(|| vec!["js".to_string(), "mjs".to_string()])
+}
 
-                    Ok(())
-                },
-            ),
-            #[cfg(feature = "dev-server")]
-            serve: Box::new(|args, server| {
-                use tide::{Body, Request, Response};
+/// Returns `true` if the rebuild gave up for newer changes ([`Error::BuildCancelled`]).
+pub(crate) fn is_cancelled(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<Error>(), Some(Error::BuildCancelled))
+}
 
-                let build_path = args.build_args().build_path().to_owned();
-                let index_path = build_path.join("index.html");
+/// First line of an error message.
+fn first_line(err: &anyhow::Error) -> String {
+    err.to_string()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
 
-                server.at("/").serve_dir(args.build_args().build_path())?;
-                server.at("/").get(move |_| {
-                    let index_path = index_path.clone();
-                    async move { Ok(Response::from(Body::from_file(index_path).await?)) }
-                });
-                server.at("/*path").get(move |req: Request<()>| {
-                    let build_path = build_path.clone();
-                    async move {
-                        match Body::from_file(build_path.join(req.param("path").unwrap())).await {
-                            Ok(body) => Ok(Response::from(body)),
-                            Err(_) => Ok(Response::from(
-                                Body::from_file(build_path.join("index.html")).await?,
-                            )),
-                        }
-                    }
-                });
+/// Clear the terminal if the standard output is a terminal.
+fn clear_screen() {
+    use std::io::{IsTerminal, Write};
 
-                Ok(())
-            }),
-        }
+    let mut stdout = std::io::stdout();
+    if stdout.is_terminal() {
+        let _ = write!(stdout, "\x1b[2J\x1b[3J\x1b[H");
+        let _ = stdout.flush();
     }
 }
 
-fn build(mut profile: BuildProfile, args: &dyn BuildArgs, hooks: &Hooks) -> Result<()> {
-    use wasm_bindgen_cli_support::Bindgen;
+/// Print a status line after a rebuild, colored if the standard output is a terminal.
+fn print_status(success: bool, message: &str) {
+    use std::io::IsTerminal;
 
-    if args.profiling() {
-        profile = BuildProfile::Profiling;
+    let (symbol, color) = if success {
+        ("\u{2713}", "\x1b[32m")
+    } else {
+        ("\u{2717}", "\x1b[31m")
+    };
+    if std::io::stdout().is_terminal() {
+        println!("{}{} {}\x1b[0m", color, symbol, message);
+    } else {
+        println!("{} {}", symbol, message);
     }
+}
 
-    let frontend_package = args.frontend_package();
-
-    let build_path = args.build_path();
-    let _ = fs::remove_dir_all(build_path);
-    fs::create_dir_all(build_path).with_context(|| {
-        format!(
-            "could not create build directory `{}`",
-            build_path.display()
-        )
-    })?;
+/// The absolute path of a path given on the command line, with the symlinks of the part that
+/// exists resolved: the steps of the build get the same path whatever the way it is written.
+fn absolute_path(path: &Path) -> PathBuf {
+    let path = match std::env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path.to_path_buf(),
+    };
+    watcher::canonicalize(&path)
+}
 
-    let mut command = Command::new("cargo");
+/// Check that the build directory is not mixed with the sources: the files of the build are
+/// written there and the stale ones are removed.
+fn check_build_path(build_path: &Path, workspace_root: &Path, package_dir: &Path) -> Result<()> {
+    let build_path = watcher::canonicalize(build_path);
+    let workspace_root = watcher::canonicalize(workspace_root);
+    let package_dir = watcher::canonicalize(package_dir);
+
+    let reason = if workspace_root.starts_with(&build_path) {
+        "it contains the workspace"
+    } else if package_dir.starts_with(&build_path) {
+        "it contains the frontend package"
+    } else if build_path.starts_with(package_dir.join("src")) {
+        "it is inside the sources of the frontend package"
+    } else {
+        return Ok(());
+    };
+    bail!(
+        "the build path `{}` can't be used: {}, the files of the builds would overwrite the \
+        sources and the stale ones would be removed (use a directory of its own like `build`)",
+        build_path.display(),
+        reason
+    );
+}
 
-    command
-        .args(&[
-            "build",
-            "--lib",
-            "--target",
-            "wasm32-unknown-unknown",
-            "--manifest-path",
-        ])
-        .arg(&frontend_package.manifest_path)
-        .args(match profile {
-            BuildProfile::Profiling => &["--release"] as &[&str],
-            BuildProfile::Release => &["--release"],
-            BuildProfile::Dev => &[],
-        });
+/// Format a size in bytes for humans.
+fn format_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{} KB", (bytes as f64 / 1024.0).round())
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
 
-    log::info!("Running pre-build hook");
-    (hooks.pre_build)(args, profile, &mut command)?;
+/// Ignore the build directory and the target directory, no matter what directories have been
+/// registered by the hooks. Otherwise every build would trigger another build.
+fn ignore_builtin(args: &dyn ServeArgs, watcher: &mut FileWatcher) {
+    let build_args = args.build_args();
+    watcher.ignore_builtin(
+        &build_args.metadata().workspace_root,
+        vec![
+            build_args.build_path(),
+            build_args.target_path().to_path_buf(),
+        ],
+    );
+}
 
-    log::info!("Building frontend");
-    let status = command.status().context("could not start build process")?;
+/// Reload the metadata and let the watch hook register the paths to watch again.
+fn rescan(
+    args: &dyn ServeArgs,
+    watcher: &mut FileWatcher,
+    watch_hook: &dyn Fn(&dyn ServeArgs, &mut FileWatcher) -> Result<()>,
+) -> Result<()> {
+    log::info!("Manifest changed, reloading metadata");
+    Project::reload()?;
+    watcher.rewatch(|watcher| watch_hook(args, watcher))
+}
 
-    if !status.success() {
-        if let Some(code) = status.code() {
-            bail!("build process exit with code {}", code);
-        } else {
-            bail!("build process has been terminated by a signal");
-        }
+fn watch_loop(
+    args: &dyn ServeArgs,
+    rx: mpsc::Receiver<notify::DebouncedEvent>,
+    watcher: &mut FileWatcher,
+    orchestrator: Option<&pipeline::Orchestrator>,
+    callback: impl FnMut(watcher::Change, &mut FileWatcher) -> Result<()>,
+) -> Result<()> {
+    let mut filter = watcher::WatchFilter::new(
+        args.gitignore(),
+        args.watch_extensions(),
+        args.watch_snippet_extensions(),
+        args.watch_asset_extensions(),
+    );
+    filter.ignore_globs(
+        &args.build_args().metadata().workspace_root,
+        &args.ignore_globs(),
+    )?;
+    let build_path = args.build_args().build_path();
+    let serve_path = args.serve_path();
+    for path in watcher.config().builtin_ignored_paths() {
+        filter.exclude(path);
+    }
+    filter.assets(args.build_args().static_dir());
+    if let Some(dir) = pages::dir(
+        args.build_args().frontend_package(),
+        &frontend_root(args.build_args()),
+    ) {
+        filter.assets(dir);
+    }
+    // NOTE: the sources of the crate might be inside
+    if let Some(dir) = args.build_args().frontend_dir().filter(|dir| {
+        !args
+            .build_args()
+            .frontend_package()
+            .manifest_path
+            .starts_with(watcher::canonicalize(dir))
+    }) {
+        filter.assets(dir);
+    }
+    for page in args.build_args().pages() {
+        filter.assets(page.template);
+    }
+    for path in args.build_args().env_files() {
+        filter.assets(path);
+    }
+    if let Some(i18n) = args.build_args().i18n() {
+        filter.assets(i18n.locales_dir);
+    }
+    if let Some(bundle_sources) = args.build_args().bundle_sources() {
+        filter.bundle(bundle_sources);
+    }
+    if !watcher::same_path(&serve_path, &build_path) {
+        filter.serve(&serve_path);
     }
 
-    let wasm_path = args
-        .target_path()
-        .join("wasm32-unknown-unknown")
-        .join(match profile {
-            BuildProfile::Profiling => "release",
-            BuildProfile::Release => "release",
-            BuildProfile::Dev => "debug",
-        })
-        .join(frontend_package.name.replace("-", "_"))
-        .with_extension("wasm");
+    watcher::watch_loop(rx, watcher, filter, orchestrator, callback);
 
-    let mut output = Bindgen::new()
-        .input_path(wasm_path)
-        .out_name("app")
-        .web(true)
-        .expect("fails only if multiple modes specified; qed")
-        .debug(!matches!(profile, BuildProfile::Release))
-        .generate_output()
-        .context("could not generate WASM bindgen file")?;
+    Err(anyhow!("the watcher unexpectedly stopped"))
+}
 
-    let wasm_js = output.js().to_owned();
-    let wasm_bin = output.wasm_mut().emit_wasm();
+/// Optimize the WASM with `wasm-opt`.
+///
+/// Returns [`Error::WasmOptUnavailable`] if `wasm-opt` can't run at all: it could not be
+/// downloaded, it could not be started or no optimization feature is enabled.
+#[allow(unused_variables, unreachable_code)]
+fn wasm_opt(
+    binary: &[u8],
+    options: &WasmOptOptions,
+    target_path: impl AsRef<Path>,
+) -> Result<Vec<u8>, Error> {
+    #[cfg(feature = "binaryen")]
+    return binaryen_wasm_opt(binary, options).map_err(Error::WasmOptFailed);
 
-    let wasm_bin = match profile {
-        BuildProfile::Profiling => wasm_opt(wasm_bin, 0, 2, true, args.target_path())?,
-        BuildProfile::Release => wasm_opt(wasm_bin, 1, 2, false, args.target_path())?,
-        BuildProfile::Dev => wasm_bin,
+    #[cfg(feature = "prebuilt-wasm-opt")]
+    return {
+        let wasm_opt =
+            prebuilt_wasm_opt::install_wasm_opt(target_path).map_err(Error::WasmOptUnavailable)?;
+        prebuilt_wasm_opt(&wasm_opt, binary, options).map_err(|err| match Error::from(err) {
+            Error::Other(err) => Error::WasmOptFailed(err),
+            err => err,
+        })
     };
 
-    log::info!("Running post-build hook");
-    (hooks.post_build)(args, profile, wasm_js, wasm_bin)?;
-
-    Ok(())
+    Err(Error::WasmOptUnavailable(anyhow!(
+        "no optimization feature is enabled (`prebuilt-wasm-opt` or `binaryen`)"
+    )))
 }
 
-#[cfg(feature = "dev-server")]
-fn serve_frontend(
-    args: &dyn ServeArgs,
-    hooks: &Hooks,
-) -> Result<Pin<Box<impl std::future::Future<Output = Result<()>> + Send + 'static>>> {
-    use futures::TryFutureExt;
-
-    if args.log() {
-        tide::log::start();
+#[cfg(feature = "binaryen")]
+fn binaryen_wasm_opt(binary: &[u8], options: &WasmOptOptions) -> Result<Vec<u8>> {
+    if options.low_memory_unused || !options.enabled_features.is_empty() {
+        bail!(
+            "`low_memory_unused` and `enabled_features` are not supported by the library of \
+            binaryen, use the feature `prebuilt-wasm-opt` instead"
+        );
     }
-    let mut app = tide::new();
 
-    (hooks.serve)(args, &mut app)?;
+    let config = binaryen::CodegenConfig {
+        shrink_level: options.shrink_level,
+        optimization_level: options.level,
+        debug_info: options.debug_info,
+    };
+    let mut module = match binaryen::Module::read(binary) {
+        Ok(module) => module,
+        Err(()) => bail!("could not load WASM module"),
+    };
 
-    log::info!(
-        "Development server started: http://{}:{}",
-        args.ip(),
-        args.port()
-    );
+    module.optimize(&config);
+    let mut wasm = module.write();
+    // NOTE: same as `--converge`: optimize again as long as the WASM shrinks
+    while options.converge {
+        module.optimize(&config);
+        let optimized = module.write();
+        if optimized.len() >= wasm.len() {
+            break;
+        }
+        wasm = optimized;
+    }
+
+    // NOTE: the passes are run one by one to report the one that is invalid
+    if !options.extra_passes.is_empty() {
+        for pass in options.extra_passes.iter() {
+            if module.run_optimization_passes([pass], &config).is_err() {
+                bail!("invalid pass of wasm-opt: `{}`", pass);
+            }
+        }
+        wasm = module.write();
+    }
 
-    Ok(Box::pin(
-        app.listen(format!("{}:{}", args.ip(), args.port()))
-            .map_err(Into::into),
-    ))
+    Ok(wasm)
 }
 
-#[cfg(not(feature = "dev-server"))]
-fn watch_backend(args: &dyn ServeArgs, hooks: &Hooks) -> Result<()> {
-    let (tx, rx) = mpsc::channel();
+#[cfg(feature = "prebuilt-wasm-opt")]
+fn prebuilt_wasm_opt(wasm_opt: &Path, binary: &[u8], options: &WasmOptOptions) -> Result<Vec<u8>> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let output = run_prebuilt_wasm_opt(wasm_opt, binary, options)?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.success() {
+            if !logger::is_json() {
+                eprint!("{}", stderr);
+            } else if !stderr.trim().is_empty() {
+                log::warn!("{}", stderr.trim());
+            }
+            return Ok(output.stdout);
+        }
 
-    let mut watcher: RecommendedWatcher = notify::Watcher::new(tx, time::Duration::from_secs(2))
-        .context("could not initialize watcher")?;
+        if let Some(pass) = invalid_pass(options, &stderr) {
+            return Err(anyhow!("{}", stderr.trim())
+                .context(format!("invalid pass of wasm-opt: `{}`", pass)));
+        }
+        if attempt <= options.retries {
+            log::warn!(
+                "`wasm-opt` failed ({}), running it again ({}/{})",
+                output.status,
+                attempt,
+                options.retries
+            );
+            continue;
+        }
 
-    (hooks.backend_watch)(args, &mut watcher)?;
+        return Err(wasm_opt_failure(
+            wasm_opt,
+            binary,
+            options,
+            output.status,
+            &stderr,
+            attempt,
+        ));
+    }
+}
 
-    struct BackgroundProcess(std::process::Child);
+#[cfg(feature = "prebuilt-wasm-opt")]
+fn run_prebuilt_wasm_opt(
+    wasm_opt: &Path,
+    binary: &[u8],
+    options: &WasmOptOptions,
+) -> Result<std::process::Output> {
+    let mut command = Command::new(wasm_opt);
+    command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .args(wasm_opt_args(options));
+    if options.single_threaded {
+        command.env("BINARYEN_CORES", "1");
+    }
 
-    impl Drop for BackgroundProcess {
-        fn drop(&mut self) {
-            // TODO: cleaner exit on Unix
-            let _ = self.0.kill();
-            let _ = self.0.wait();
-        }
+    #[cfg(target_os = "macos")]
+    {
+        command.env("DYLD_LIBRARY_PATH", wasm_opt.parent().unwrap());
     }
 
-    let run_server = || -> Result<BackgroundProcess> {
-        let mut command = Command::new("cargo");
-        (hooks.backend_command)(args, &mut command)?;
-        Ok(command.spawn().map(BackgroundProcess)?)
-    };
+    #[cfg(windows)]
+    let delete_guard = {
+        use std::io::Write;
 
-    let mut process_guard = Some(run_server()?);
+        let tmp = tempfile::NamedTempFile::new()?;
+        tmp.as_file().write_all(binary)?;
+        command.stdin(Stdio::null()).arg(tmp.path());
+        tmp
+    };
 
-    watch_loop(args, rx, || {
-        drop(process_guard.take());
-        process_guard.replace(run_server()?);
-        Ok(())
-    });
-}
+    #[cfg(unix)]
+    {
+        use std::io::{Seek, SeekFrom, Write};
 
-fn watch_frontend(args: &dyn ServeArgs, hooks: &Hooks) -> Result<()> {
-    let (tx, rx) = mpsc::channel();
+        let mut file = tempfile::tempfile()?;
+        file.write_all(binary)?;
+        file.seek(SeekFrom::Start(0))?;
+        command.stdin(file);
+    }
 
-    let mut watcher: RecommendedWatcher = notify::Watcher::new(tx, time::Duration::from_secs(2))
-        .context("could not initialize watcher")?;
+    log::debug!("Running {:?}", command);
+    let child = process::GroupChild::spawn(&mut command).map_err(|err| {
+        Error::WasmOptUnavailable(
+            anyhow::Error::new(err).context(format!("could not start `{}`", wasm_opt.display())),
+        )
+    })?;
+    Ok(child.wait_with_output()?)
+}
 
-    (hooks.frontend_watch)(args, &mut watcher)?;
+/// The error of `wasm-opt` when it crashed or failed with everything needed to reproduce it: the
+/// version of binaryen, the command and a copy of the WASM given to it, kept in the temporary
+/// directory.
+#[cfg(feature = "prebuilt-wasm-opt")]
+fn wasm_opt_failure(
+    wasm_opt: &Path,
+    binary: &[u8],
+    options: &WasmOptOptions,
+    status: std::process::ExitStatus,
+    stderr: &str,
+    attempts: u32,
+) -> anyhow::Error {
+    use std::io::Write;
+
+    let input = tempfile::Builder::new()
+        .prefix("wasmbl-wasm-opt-")
+        .suffix(".wasm")
+        .tempfile()
+        .and_then(|mut file| {
+            file.write_all(binary)?;
+            file.keep().map(|(_, path)| path).map_err(|err| err.error)
+        });
 
-    let build_args = args.build_args();
+    let mut message = format!(
+        "`wasm-opt` {} ({})",
+        if status.code().is_some() {
+            "failed"
+        } else {
+            "crashed"
+        },
+        status
+    );
+    if attempts > 1 {
+        message.push_str(&format!(" {} times", attempts));
+    }
+    message.push_str(": pass `--no-wasm-opt` to build without optimizing the WASM");
+    if attempts == 1 {
+        message.push_str(", or `--wasm-opt-retries 2` if it doesn't always fail");
+    }
+    message.push_str(&format!(
+        "\n  stage: {}\n  version: binaryen {}",
+        Stage::WasmOpt,
+        prebuilt_wasm_opt::BINARYEN_VERSION
+    ));
+    let mut command = vec![wasm_opt.display().to_string()];
+    command.extend(wasm_opt_args(options));
+    match input {
+        Ok(input) => {
+            command.push(input.display().to_string());
+            message.push_str(&format!(
+                "\n  command: {}\n  input: {}",
+                command.join(" "),
+                input.display()
+            ));
+        }
+        Err(err) => message.push_str(&format!(
+            "\n  command: {}\n  input: could not be kept ({})",
+            command.join(" "),
+            err
+        )),
+    }
 
-    watch_loop(args, rx, || build(BuildProfile::Dev, build_args, hooks));
+    match stderr.trim() {
+        "" => anyhow!("{}", message),
+        stderr => anyhow!("{}", stderr).context(message),
+    }
 }
 
-fn watch_loop(
-    args: &dyn ServeArgs,
-    rx: mpsc::Receiver<notify::DebouncedEvent>,
-    mut callback: impl FnMut() -> Result<()>,
-) -> ! {
-    loop {
-        use notify::DebouncedEvent::*;
-
-        let message = rx.recv();
-        match &message {
-            Ok(Create(path)) | Ok(Write(path)) | Ok(Remove(path)) | Ok(Rename(_, path))
-                if !path.starts_with(args.build_args().build_path())
-                    && !path.starts_with(args.build_args().target_path())
-                    && !path
-                        .file_name()
-                        .and_then(|x| x.to_str())
-                        .map(|x| x.starts_with('.'))
-                        .unwrap_or(false) =>
-            {
-                if let Err(err) = callback() {
-                    log::error!("{}", err);
-                }
-            }
-            Ok(_) => {}
-            Err(e) => log::error!("Watch error: {}", e),
-        }
+/// The arguments of the binary `wasm-opt` for the options, the WASM is read from the standard input
+/// and written to the standard output.
+#[cfg_attr(not(feature = "prebuilt-wasm-opt"), allow(dead_code))]
+fn wasm_opt_args(options: &WasmOptOptions) -> Vec<String> {
+    let mut args: Vec<String> = vec!["-o".into(), "-".into(), "-O".into()];
+    args.extend(["-ol".into(), options.level.to_string()]);
+    args.extend(["-s".into(), options.shrink_level.to_string()]);
+    if options.debug_info {
+        args.push("-g".into());
+    }
+    if options.converge {
+        args.push("--converge".into());
     }
+    if options.low_memory_unused {
+        args.push("--low-memory-unused".into());
+    }
+    args.extend(
+        options
+            .enabled_features
+            .iter()
+            .map(|x| format!("--enable-{}", x.trim_start_matches("--enable-"))),
+    );
+    args.extend(
+        options
+            .extra_passes
+            .iter()
+            .map(|x| format!("--{}", x.trim_start_matches("--"))),
+    );
+    args
 }
 
-#[allow(unused_variables, unreachable_code)]
-fn wasm_opt(
-    binary: Vec<u8>,
-    shrink_level: u32,
-    optimization_level: u32,
-    debug_info: bool,
-    target_path: impl AsRef<Path>,
-) -> Result<Vec<u8>> {
-    #[cfg(feature = "binaryen")]
-    return match binaryen::Module::read(&binary) {
-        Ok(mut module) => {
-            module.optimize(&binaryen::CodegenConfig {
-                shrink_level,
-                optimization_level,
-                debug_info,
-            });
-            Ok(module.write())
-        }
-        Err(()) => bail!("could not load WASM module"),
+/// The pass of the options that `wasm-opt` complains about in its error output, if any.
+#[cfg_attr(not(feature = "prebuilt-wasm-opt"), allow(dead_code))]
+fn invalid_pass<'a>(options: &'a WasmOptOptions, stderr: &str) -> Option<&'a str> {
+    options
+        .extra_passes
+        .iter()
+        .map(|x| x.trim_start_matches("--"))
+        .find(|pass| {
+            stderr
+                .split(|c: char| c.is_whitespace() || c == '\'' || c == '"')
+                .any(|word| word == format!("--{}", pass))
+        })
+}
+
+/// Parse a duration from the command-line: `500ms`, `2s` or a number of seconds.
+fn parse_duration(value: &str) -> Result<Duration> {
+    let (number, unit) = if let Some(x) = value.strip_suffix("ms") {
+        (x, 0.001)
+    } else if let Some(x) = value.strip_suffix('s') {
+        (x, 1.0)
+    } else {
+        (value, 1.0)
     };
+    let number: f64 = number
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid duration: `{}`", value))?;
+    if !number.is_finite() || number < 0.0 {
+        bail!("invalid duration: `{}`", value);
+    }
+    Ok(Duration::from_secs_f64(number * unit))
+}
 
-    #[cfg(feature = "prebuilt-wasm-opt")]
-    return {
-        let wasm_opt = prebuilt_wasm_opt::install_wasm_opt(target_path)?;
+/// Parse an address of [`ServeArgs::bind`]: an IP address (IPv6 with or without brackets) with
+/// the default port, or an IP address and a port.
+fn parse_bind_addr(value: &str, port: u16) -> Result<SocketAddr> {
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    let ip = value
+        .strip_prefix('[')
+        .and_then(|x| x.strip_suffix(']'))
+        .unwrap_or(value);
+    match ip.parse::<IpAddr>() {
+        Ok(ip) => Ok(SocketAddr::new(ip, port)),
+        Err(_) => bail!(
+            "invalid address `{}`: expected an IP address (`::1`) or an IP address and a port \
+            (`[::1]:3000`, `127.0.0.1:3000`)",
+            value
+        ),
+    }
+}
 
-        let mut command = Command::new(&wasm_opt);
-        command
-            .stderr(Stdio::inherit())
-            .args(&["-o", "-", "-O"])
-            .args(&["-ol", &optimization_level.to_string()])
-            .args(&["-s", &shrink_level.to_string()]);
-        if debug_info {
-            command.arg("-g");
-        }
+fn validate_bind_addr(value: String) -> std::result::Result<(), String> {
+    parse_bind_addr(&value, 0)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
 
-        #[cfg(target_os = "macos")]
-        {
-            command.env("DYLD_LIBRARY_PATH", wasm_opt.parent().unwrap());
-        }
+fn validate_duration(value: String) -> std::result::Result<(), String> {
+    parse_duration(&value)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
 
-        #[cfg(windows)]
-        let delete_guard = {
-            use std::io::Write;
+/// The index document must be a file name, not a path.
+fn validate_index_document(value: String) -> std::result::Result<(), String> {
+    match value.as_str() {
+        "" | "." | ".." => Err(format!("`{}` is not a file name", value)),
+        _ if value.contains(['/', '\\']) => Err(format!("`{}` is not a file name", value)),
+        _ => Ok(()),
+    }
+}
 
-            let tmp = tempfile::NamedTempFile::new()?;
-            tmp.as_file().write_all(&binary)?;
-            command.arg(tmp.path());
-            tmp
-        };
+/// The directory of the default locations of the web assets: [`BuildArgs::frontend_dir`] or the
+/// frontend package.
+fn frontend_root<A: BuildArgs + ?Sized>(args: &A) -> PathBuf {
+    args.frontend_dir().unwrap_or_else(|| {
+        args.frontend_package()
+            .manifest_path
+            .parent()
+            .unwrap()
+            .to_path_buf()
+    })
+}
 
-        #[cfg(unix)]
-        {
-            use std::io::{Seek, SeekFrom, Write};
+/// A path given to an option of the web assets: relative to [`BuildArgs::frontend_dir`] if there
+/// is one.
+fn frontend_path<A: BuildArgs + ?Sized>(args: &A, path: &Path) -> PathBuf {
+    match args.frontend_dir() {
+        Some(dir) => dir.join(path),
+        None => path.to_path_buf(),
+    }
+}
 
-            let mut file = tempfile::tempfile()?;
-            file.write_all(&binary)?;
-            file.seek(SeekFrom::Start(0))?;
-            command.stdin(file);
-        }
+/// Read a list of strings from the `[package.metadata.wasmbl]` table of a package.
+fn package_metadata_strings(package: &Package, key: &str) -> Option<Vec<String>> {
+    let values = package.metadata.get("wasmbl")?.get(key)?.as_array()?;
+    Some(
+        values
+            .iter()
+            .filter_map(|x| x.as_str())
+            .map(ToString::to_string)
+            .collect(),
+    )
+}
 
-        let output = command.output()?;
-        if !output.status.success() {
-            bail!("command `wasm-opt` failed.");
-        }
-        Ok(output.stdout)
-    };
+/// Read a boolean from the `[package.metadata.wasmbl]` table of a package.
+fn package_metadata_bool(package: &Package, key: &str) -> Option<bool> {
+    package.metadata.get("wasmbl")?.get(key)?.as_bool()
+}
 
-    log::warn!("No optimization has been done on the WASM");
-    Ok(binary)
+/// Read a string from the `[package.metadata.wasmbl]` table of a package.
+fn package_metadata_string(package: &Package, key: &str) -> Option<String> {
+    package
+        .metadata
+        .get("wasmbl")?
+        .get(key)?
+        .as_str()
+        .map(ToString::to_string)
 }
 
 /// An extension for [`Package`] and for [`Metadata`] to run a cargo command a bit more easily.
@@ -956,7 +5810,8 @@ impl PackageExt for Package {
 
         builder(&mut command);
 
-        Ok(CargoChild(command.spawn()?))
+        log::debug!("Running {:?}", command);
+        Ok(CargoChild(process::GroupChild::spawn(&mut command)?))
     }
 }
 
@@ -969,14 +5824,16 @@ impl PackageExt for Metadata {
 
         builder(&mut command);
 
-        Ok(CargoChild(command.spawn()?))
+        log::debug!("Running {:?}", command);
+        Ok(CargoChild(process::GroupChild::spawn(&mut command)?))
     }
 }
 
 /// A cargo child process.
 ///
-/// The child process is killed and waited if the instance is dropped.
-pub struct CargoChild(Child);
+/// The child process is started in its own process group, the group is terminated and the child
+/// process waited if the instance is dropped.
+pub struct CargoChild(process::GroupChild);
 
 impl CargoChild {
     /// Wait for the child process to finish and return an `Err(_)` if it didn't ended
@@ -1005,13 +5862,6 @@ impl CargoChild {
     }
 }
 
-impl Drop for CargoChild {
-    fn drop(&mut self) {
-        let _ = self.0.kill();
-        let _ = self.0.wait();
-    }
-}
-
 /// The wasmbl Prelude
 ///
 /// The purpose of this module is to alleviate imports of many common types:
@@ -1020,6 +5870,12 @@ impl Drop for CargoChild {
 /// # #![allow(unused_imports)]
 /// use wasmbl::prelude::*;
 /// ```
+///
+/// The types of `cargo_metadata` given to the hooks ([`Metadata`], [`Package`], ...) must be
+/// imported from here (or from the crate `cargo_metadata` re-exported here) rather than from a
+/// direct dependency of your project. A direct dependency on another version of
+/// `cargo_metadata` has different types with the same names and the hooks would not match the
+/// signatures expected by the macro.
 pub mod prelude {
     pub use wasmbl_proc_macro::*;
 
@@ -1027,21 +5883,1299 @@ pub mod prelude {
     #[cfg(feature = "dev-server")]
     pub use async_std;
     pub use cargo_metadata;
-    pub use cargo_metadata::{Message, Metadata, Package};
+    pub use cargo_metadata::diagnostic::Diagnostic;
+    pub use cargo_metadata::{Message, Metadata, Package, Target};
     pub use fs_extra;
     #[cfg(feature = "dev-server")]
     pub use futures;
     pub use notify;
     pub use notify::RecommendedWatcher;
+    pub use notify::Watcher;
     #[cfg(feature = "sass")]
     pub use sass_rs;
     #[cfg(feature = "dev-server")]
     pub use tide;
     #[cfg(feature = "dev-server")]
     pub use tide::Server;
+    pub use wasm_bindgen_cli_support::Bindgen;
 
     pub use super::{
-        BuildArgs, BuildProfile, CargoChild, DefaultBuildArgs, DefaultServeArgs, Hooks, PackageExt,
-        ServeArgs,
+        append_rustflags, Asset, BuildArgs, BuildOutput, BuildProfile, BuildTarget, BuildTrigger,
+        CargoChild, ConfigFormat, DefaultBuildArgs, DefaultCleanArgs, DefaultCompletionsArgs,
+        DefaultRunArgs, DefaultRunNodeArgs, DefaultServeArgs, DefaultTestArgs, DefaultVersionArgs,
+        FileWatcher, Hooks, I18n, LogFormat, OutLayout, PackageExt, Page, PanicStrategy,
+        RebuildHandle, Release, ReleaseArtifact, Reporter, ServeArgs, ServerConfig, SizeFormat,
+        SizeThreshold, Stage, TestArgs, TestBrowser, TrailingSlash, Verbosity, WasmOptOptions,
+        WatchArgs, WatchConfig,
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggest_closest_package() {
+        assert_eq!(edit_distance("frontend", "frontend"), 0);
+        assert_eq!(edit_distance("frontnd", "frontend"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+
+        let members = ["backend", "frontend", "frontend-tests"];
+        assert_eq!(closest_match("fronted", &members), Some("frontend"));
+        assert_eq!(closest_match("backedn", &members), Some("backend"));
+        assert_eq!(closest_match("database", &members), None);
+    }
+
+    #[test]
+    fn find_frontend_outside_of_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let write_package = |name: &str, extra: &str| {
+            fs::create_dir_all(root.join(name).join("src")).unwrap();
+            fs::write(root.join(name).join("src/lib.rs"), "").unwrap();
+            fs::write(
+                root.join(name).join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n{}",
+                    name, extra
+                ),
+            )
+            .unwrap();
+        };
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"runner\"]\nexclude = [\"excluded\"]\n",
+        )
+        .unwrap();
+        write_package("runner", "");
+        write_package("excluded", "");
+        write_package("web", "[workspace]\n");
+
+        let metadata = load_metadata(&root.join("Cargo.toml")).unwrap();
+        let (frontend_metadata, package) =
+            find_frontend_by_manifest(metadata, Path::new("runner")).unwrap();
+        assert_eq!(package.name, "runner");
+        assert!(std::ptr::eq(frontend_metadata, metadata));
+
+        for name in ["excluded", "web"] {
+            let (frontend_metadata, package) =
+                find_frontend_by_manifest(metadata, Path::new(name)).unwrap();
+            assert_eq!(package.name, name);
+            assert_eq!(frontend_metadata.workspace_root, root.join(name));
+            assert_eq!(
+                frontend_metadata.target_directory,
+                root.join(name).join("target")
+            );
+        }
+
+        assert!(find_frontend_by_manifest(metadata, Path::new("missing")).is_err());
+    }
+
+    #[test]
+    fn filter_assets() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks = Hooks {
+            asset_filter: Box::new(|mut asset| {
+                if asset.content_type == "text/css" {
+                    return Ok(None);
+                }
+                asset.path.set_extension("mjs");
+                asset.content.extend(b"// cdn");
+                Ok(Some(asset))
+            }),
+            ..Hooks::default()
+        };
+
+        let path = write_asset(
+            dir.path(),
+            &hooks,
+            Asset::new("snippets/a.js", "snippets/a.js", b"x;".to_vec()),
+        )
+        .unwrap();
+        assert_eq!(path, Some(dir.path().join("snippets/a.mjs")));
+        assert_eq!(fs::read_to_string(path.unwrap()).unwrap(), "x;// cdn");
+
+        let path = write_asset(
+            dir.path(),
+            &hooks,
+            Asset::new("main.css", "styles/main.scss", Vec::new()),
+        )
+        .unwrap();
+        assert_eq!(path, None);
+        assert!(!dir.path().join("main.css").exists());
+    }
+
+    #[test]
+    fn write_through_a_temporary_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app_bg.wasm");
+        fs::write(&path, "old").unwrap();
+
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // NOTE: nothing is left behind when the file can't be written
+        assert!(write_atomic(&dir.path().join("missing").join("app.js"), b"js").is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(feature = "dev-server")]
+    #[test]
+    fn middleware_wraps_all_the_routes() {
+        use tide::http::{Method, Request, Response, Url};
+
+        crate::runner::tests::init_project();
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "").unwrap();
+        fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        let args = DefaultServeArgs::from_iter(vec![
+            std::ffi::OsStr::new("serve"),
+            "--build-path".as_ref(),
+            dir.path().as_os_str(),
+        ]);
+        let hooks = Hooks {
+            serve_middleware: Box::new(|_, app| {
+                app.with(tide::utils::After(|mut res: tide::Response| async move {
+                    res.insert_header("x-middleware", "1");
+                    Ok(res)
+                }));
+                Ok(())
+            }),
+            ..Hooks::default()
+        };
+        let app = dev_server(&args, &hooks, &live_reload::LiveReload::default()).unwrap();
+
+        for path in ["/app.js", "/", "/some/route"] {
+            let url = Url::parse(&format!("http://127.0.0.1:3000{}", path)).unwrap();
+            let res: Response =
+                async_std::task::block_on(app.respond(Request::new(Method::Get, url))).unwrap();
+            assert_eq!(res.status(), 200, "{}", path);
+            assert_eq!(
+                res.header("x-middleware").map(|x| x.as_str()),
+                Some("1"),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[cfg(feature = "dev-server")]
+    #[test]
+    fn placeholder_without_index() {
+        use tide::http::{Method, Request, Response, Url};
+
+        crate::runner::tests::init_project();
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "").unwrap();
+        let args = DefaultServeArgs::from_iter(vec![
+            std::ffi::OsStr::new("serve"),
+            "--build-path".as_ref(),
+            dir.path().as_os_str(),
+        ]);
+        let app = dev_server(
+            &args,
+            &Hooks::default(),
+            &live_reload::LiveReload::default(),
+        )
+        .unwrap();
+        let get = |path: &str| -> Response {
+            let url = Url::parse(&format!("http://127.0.0.1:3000{}", path)).unwrap();
+            async_std::task::block_on(app.respond(Request::new(Method::Get, url))).unwrap()
+        };
+
+        assert!(check_index(&args).unwrap().contains("`post_build` hook"));
+        for path in ["/", "/some/route"] {
+            let mut res = get(path);
+            assert_eq!(res.status(), 404, "{}", path);
+            assert_eq!(
+                res.header("content-type").map(|x| x.as_str()),
+                Some("text/html;charset=utf-8")
+            );
+            let html = async_std::task::block_on(res.body_string()).unwrap();
+            assert!(html.contains("<h1>No index document</h1>"), "{}", html);
+            assert!(html.contains("there is no `index.html` in the serve directory"));
+        }
+        assert_eq!(get("/app.js").status(), 200);
+        assert!(!dir.path().join("index.html").exists());
+
+        fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        assert_eq!(check_index(&args), None);
+        for path in ["/", "/some/route"] {
+            let mut res = get(path);
+            assert_eq!(res.status(), 200, "{}", path);
+            let html = async_std::task::block_on(res.body_string()).unwrap();
+            assert!(!html.contains("No index document"), "{}", html);
+        }
+    }
+
+    #[cfg(feature = "dev-server")]
+    #[test]
+    fn serve_content_types() {
+        use tide::http::{Method, Request, Response, Url};
+
+        crate::runner::tests::init_project();
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("snippets")).unwrap();
+        fs::create_dir(dir.path().join("assets")).unwrap();
+        for file in [
+            "index.html",
+            "app.js",
+            "app_bg.wasm",
+            "snippets/helpers.mjs",
+            "style.css",
+            "data.json",
+            "logo.svg",
+            "logo.png",
+            "robots.txt",
+            "assets/theme.css",
+        ] {
+            fs::write(dir.path().join(file), "").unwrap();
+        }
+        let args = DefaultServeArgs::from_iter(vec![
+            std::ffi::OsStr::new("serve"),
+            "--build-path".as_ref(),
+            dir.path().as_os_str(),
+        ]);
+        let assets_path = dir.path().join("assets");
+        let hooks = Hooks {
+            serve: Box::new(move |args, server| {
+                (Hooks::default().serve)(args, server)?;
+                server.at("/mounted").serve_dir(&assets_path)?;
+                Ok(())
+            }),
+            ..Hooks::default()
+        };
+        let app = dev_server(&args, &hooks, &live_reload::LiveReload::default()).unwrap();
+
+        for (path, expected) in [
+            ("/", "text/html;charset=utf-8"),
+            ("/index.html", "text/html;charset=utf-8"),
+            ("/some/route", "text/html;charset=utf-8"),
+            ("/app.js", "text/javascript;charset=utf-8"),
+            ("/snippets/helpers.mjs", "text/javascript;charset=utf-8"),
+            ("/style.css", "text/css;charset=utf-8"),
+            ("/mounted/theme.css", "text/css;charset=utf-8"),
+            ("/data.json", "application/json;charset=utf-8"),
+            ("/logo.svg", "image/svg+xml;charset=utf-8"),
+            ("/robots.txt", "text/plain;charset=utf-8"),
+            ("/app_bg.wasm", "application/wasm"),
+            ("/logo.png", "image/png"),
+        ] {
+            let url = Url::parse(&format!("http://127.0.0.1:3000{}", path)).unwrap();
+            let res: Response =
+                async_std::task::block_on(app.respond(Request::new(Method::Get, url))).unwrap();
+            assert_eq!(res.status(), 200, "{}", path);
+            assert_eq!(
+                res.header("content-type").map(|x| x.as_str()),
+                Some(expected),
+                "{}",
+                path
+            );
+        }
+
+        let mime = "text/javascript;charset=latin1".parse().unwrap();
+        assert_eq!(
+            served_content_type(&mime, "/app.js").to_string(),
+            "text/javascript;charset=latin1"
+        );
+    }
+
+    #[cfg(feature = "dev-server")]
+    #[test]
+    fn control_api() {
+        use futures::StreamExt;
+        use tide::http::{Method, Request, Response, Url};
+
+        crate::runner::tests::init_project();
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "").unwrap();
+        let args = |control_api: bool| {
+            let mut args = DefaultServeArgs::from_iter(vec![
+                std::ffi::OsStr::new("serve"),
+                "--build-path".as_ref(),
+                dir.path().as_os_str(),
+            ]);
+            args.control_api = control_api;
+            args
+        };
+        let live_reload = live_reload::LiveReload::default();
+        let mut rx = live_reload.subscribe();
+        let build_request = |method, path: &str, peer_addr: &str| {
+            let url = Url::parse(&format!("http://127.0.0.1:3000{}", path)).unwrap();
+            let mut req = Request::new(method, url);
+            req.set_peer_addr(Some(peer_addr));
+            req.set_local_addr(Some("127.0.0.1:3000"));
+            req.insert_header("Host", "127.0.0.1:3000");
+            req
+        };
+        let respond = |app: &Server<()>, req: Request| {
+            let res: Response = async_std::task::block_on(app.respond(req)).unwrap();
+            res
+        };
+        let request = |app: &Server<()>, method, path: &str, peer_addr: &str| {
+            respond(app, build_request(method, path, peer_addr))
+        };
+
+        let app = dev_server(&args(false), &Hooks::default(), &live_reload).unwrap();
+        let res = request(&app, Method::Post, "/__wasmbl/reload", "127.0.0.1:1234");
+        assert_ne!(res.status(), 204);
+
+        let app = dev_server(&args(true), &Hooks::default(), &live_reload).unwrap();
+        let res = request(&app, Method::Post, "/__wasmbl/reload", "192.168.1.2:1234");
+        assert_eq!(res.status(), 403);
+        let res = request(&app, Method::Post, "/__wasmbl/reload", "[::1]:1234");
+        assert_eq!(res.status(), 204);
+        assert_eq!(
+            async_std::task::block_on(rx.next()),
+            Some(live_reload::ReloadEvent::Reload)
+        );
+
+        // NOTE: the requests of the pages of other sites in the browser of the developer
+        let mut req = build_request(Method::Post, "/__wasmbl/reload", "127.0.0.1:1234");
+        req.insert_header("Origin", "http://evil.example");
+        assert_eq!(respond(&app, req).status(), 403);
+        let mut req = build_request(Method::Post, "/__wasmbl/reload", "127.0.0.1:1234");
+        req.insert_header("Host", "evil.example:3000");
+        assert_eq!(respond(&app, req).status(), 403);
+        let mut req = build_request(Method::Post, "/__wasmbl/reload", "127.0.0.1:1234");
+        req.insert_header("Origin", "http://localhost:3000");
+        assert_eq!(respond(&app, req).status(), 204);
+        assert_eq!(
+            async_std::task::block_on(rx.next()),
+            Some(live_reload::ReloadEvent::Reload)
+        );
+
+        // NOTE: the watcher is not started
+        let res = request(&app, Method::Post, "/__wasmbl/rebuild", "127.0.0.1:1234");
+        assert_eq!(res.status(), 503);
+
+        let mut res = request(&app, Method::Get, "/__wasmbl/status", "127.0.0.1:1234");
+        let json: serde_json::Value = async_std::task::block_on(res.body_json()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "status": "pending", "queue": "idle" })
+        );
+        live_reload.set_status(live_reload::BuildStatus {
+            error: Some("could not compile".to_string()),
+            duration: Duration::from_millis(1500),
+            finished: 1_700_000_000,
+            artifacts: vec![("app_bg.wasm".to_string(), 1234)],
+            diagnostics: Default::default(),
+        });
+        live_reload.orchestrator().trigger();
+        let mut res = request(&app, Method::Get, "/__wasmbl/status", "127.0.0.1:1234");
+        let json: serde_json::Value = async_std::task::block_on(res.body_json()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "status": "failure",
+                "error": "could not compile",
+                "duration": 1.5,
+                "finished": 1_700_000_000,
+                "artifacts": [{ "path": "app_bg.wasm", "size": 1234 }],
+                "diagnostics": { "errors": 0, "warnings": 0, "messages": [] },
+                "queue": "pending",
+            })
+        );
+    }
+
+    #[cfg(feature = "dev-server")]
+    #[test]
+    fn serve_special_file_names() {
+        use tide::http::{Method, Request, Response, Url};
+
+        crate::runner::tests::init_project();
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "index").unwrap();
+        fs::write(dir.path().join("logo \u{1f600}.svg"), "emoji").unwrap();
+        let args = DefaultServeArgs::from_iter(vec![
+            std::ffi::OsStr::new("serve"),
+            "--build-path".as_ref(),
+            dir.path().as_os_str(),
+        ]);
+        let app = dev_server(
+            &args,
+            &Hooks::default(),
+            &live_reload::LiveReload::default(),
+        )
+        .unwrap();
+        let get = |path: &str| -> String {
+            let url = Url::parse(&format!("http://127.0.0.1:3000{}", path)).unwrap();
+            let mut res: Response =
+                async_std::task::block_on(app.respond(Request::new(Method::Get, url))).unwrap();
+            async_std::task::block_on(res.body_string()).unwrap()
+        };
+
+        assert_eq!(get("/logo \u{1f600}.svg"), "emoji");
+        assert_eq!(get("/logo%20%F0%9F%98%80.svg"), "emoji");
+        assert!(get("/..%2Fsecret").starts_with("index"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let name = std::ffi::OsStr::from_bytes(b"caf\xe9.txt");
+            fs::write(dir.path().join(name), "latin-1").unwrap();
+            assert_eq!(get("/caf%E9.txt"), "latin-1");
+        }
+    }
+
+    #[cfg(feature = "dev-server")]
+    #[test]
+    fn extensionless_urls_and_trailing_slashes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("docs").join("api")).unwrap();
+        fs::write(dir.path().join("index.htm"), "").unwrap();
+        fs::write(dir.path().join("about.html"), "").unwrap();
+        fs::write(dir.path().join("app.js"), "").unwrap();
+        fs::write(dir.path().join("docs").join("index.htm"), "").unwrap();
+        let mut files = ServeFiles {
+            index_document: "index.htm".to_string(),
+            extensionless_html: true,
+            trailing_slash: TrailingSlash::Keep,
+            listing: false,
+        };
+        let resolve = |files: &ServeFiles, path: &str| {
+            let url = tide::http::Url::parse(&format!("http://127.0.0.1:3000{}", path)).unwrap();
+            files.resolve(dir.path(), &url)
+        };
+        let file = |path: &str| Served::File(dir.path().join(path));
+        let redirect = |url: &str| Served::Redirect(url.to_string());
+        let docs = dir.path().join("docs").join("index.htm");
+
+        assert_eq!(resolve(&files, "/app.js"), file("app.js"));
+        assert_eq!(resolve(&files, "/about"), file("about.html"));
+        assert_eq!(resolve(&files, "/about/"), file("about.html"));
+        assert_eq!(resolve(&files, "/docs"), Served::File(docs.clone()));
+        assert_eq!(
+            resolve(&files, "/docs/api/users"),
+            Served::File(docs.clone())
+        );
+        assert_eq!(resolve(&files, "/contact"), file("index.htm"));
+        assert_eq!(resolve(&files, "/app"), file("index.htm"));
+
+        files.trailing_slash = TrailingSlash::Add;
+        assert_eq!(resolve(&files, "/docs?page=2"), redirect("/docs/?page=2"));
+        assert_eq!(resolve(&files, "/docs/"), Served::File(docs.clone()));
+        assert_eq!(resolve(&files, "/about/"), redirect("/about"));
+        assert_eq!(resolve(&files, "/about"), file("about.html"));
+
+        files.trailing_slash = TrailingSlash::Remove;
+        assert_eq!(resolve(&files, "/docs/"), redirect("/docs"));
+        assert_eq!(resolve(&files, "/docs"), Served::File(docs));
+        assert_eq!(resolve(&files, "/about/"), redirect("/about"));
+
+        files.extensionless_html = false;
+        files.listing = true;
+        assert_eq!(resolve(&files, "/about"), file("index.htm"));
+        assert_eq!(
+            resolve(&files, "/docs/api/"),
+            Served::Listing(Path::new("docs").join("api"))
+        );
+    }
+
+    #[test]
+    fn url_paths() {
+        let path = Path::new("img").join("logo #1.svg");
+        assert_eq!(url_path(&path).as_deref(), Some("img/logo%20%231.svg"));
+        assert_eq!(path_from_url("img/logo%20%231.svg"), Some(path));
+        assert_eq!(
+            url_path(Path::new("caf\u{e9}.txt")).as_deref(),
+            Some("caf%C3%A9.txt")
+        );
+        assert_eq!(path_from_url("./a//b"), Some(Path::new("a").join("b")));
+        assert_eq!(path_from_url("a/../secret"), None);
+        assert_eq!(path_from_url("a/..%2Fsecret"), None);
+        assert_eq!(path_from_url("%2E%2E/secret"), None);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let path = Path::new(std::ffi::OsStr::from_bytes(b"static/caf\xe9\n.txt"));
+            assert_eq!(url_path(path).as_deref(), Some("static/caf%E9%0A.txt"));
+            assert_eq!(path_from_url("static/caf%E9%0A.txt").as_deref(), Some(path));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn list_non_utf8_files() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.txt");
+        fs::create_dir(dir.path().join("img")).unwrap();
+        fs::write(dir.path().join("img").join(name), [0xe9]).unwrap();
+        fs::write(dir.path().join("index.html"), "").unwrap();
+
+        assert_eq!(
+            list_files(dir.path()).unwrap(),
+            [
+                dir.path().join("img").join(name),
+                dir.path().join("index.html")
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn list_files_through_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared");
+        let static_dir = dir.path().join("static");
+        fs::create_dir_all(shared.join("fonts")).unwrap();
+        fs::write(shared.join("fonts").join("mono.woff2"), "").unwrap();
+        fs::write(shared.join("logo.svg"), "").unwrap();
+        fs::create_dir_all(static_dir.join("img")).unwrap();
+        symlink(
+            shared.join("logo.svg"),
+            static_dir.join("img").join("logo.svg"),
+        )
+        .unwrap();
+        symlink(shared.join("fonts"), static_dir.join("fonts")).unwrap();
+        symlink(&static_dir, static_dir.join("img").join("loop")).unwrap();
+        symlink(".", shared.join("fonts").join("self")).unwrap();
+
+        assert_eq!(
+            list_files(&static_dir).unwrap(),
+            [
+                static_dir.join("fonts").join("mono.woff2"),
+                static_dir.join("img").join("logo.svg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn pick_the_wasi_binary() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/bin")).unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"cli-tool\"\nversion = \"0.1.0\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+        let metadata = || {
+            MetadataCommand::new()
+                .manifest_path(dir.path().join("Cargo.toml"))
+                .no_deps()
+                .exec()
+                .unwrap()
+        };
+
+        let package = metadata().packages.remove(0);
+        assert_eq!(wasi_binary(&package).unwrap(), "cli-tool");
+
+        fs::write(dir.path().join("src/bin/other.rs"), "fn main() {}\n").unwrap();
+        let package = metadata().packages.remove(0);
+        assert_eq!(wasi_binary(&package).unwrap(), "cli-tool");
+
+        fs::remove_file(dir.path().join("src/main.rs")).unwrap();
+        let package = metadata().packages.remove(0);
+        assert_eq!(wasi_binary(&package).unwrap(), "other");
+
+        fs::remove_file(dir.path().join("src/bin/other.rs")).unwrap();
+        let package = metadata().packages.remove(0);
+        assert!(wasi_binary(&package).is_err());
+    }
+
+    #[test]
+    fn find_wasm_in_configured_target_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::create_dir_all(dir.path().join(".cargo")).unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"web-app\"\nversion = \"0.1.0\"\n\n[lib]\n\
+            crate-type = [\"cdylib\"]\n\n[workspace]\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        fs::write(
+            dir.path().join(".cargo/config.toml"),
+            "[build]\ntarget-dir = \"shared-target\"\n",
+        )
+        .unwrap();
+
+        // NOTE: cargo reads the configuration from the current directory
+        let metadata = MetadataCommand::new()
+            .manifest_path(dir.path().join("Cargo.toml"))
+            .current_dir(dir.path())
+            .no_deps()
+            .exec()
+            .unwrap();
+        if std::env::var_os("CARGO_TARGET_DIR").is_none() {
+            assert!(metadata.target_directory.ends_with("shared-target"));
+        }
+        let package = &metadata.packages[0];
+        let profile_path = metadata
+            .target_directory
+            .join("wasm32-unknown-unknown/debug");
+
+        let err = find_wasm(&[], package, &profile_path, "web_app").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "could not find the WASM, searched:\n  {}\n  {}",
+                profile_path.join("web_app.wasm").display(),
+                profile_path.join("deps").join("web_app.wasm").display()
+            )
+        );
+
+        let artifacts = run_cargo(
+            Command::new("cargo")
+                .args([
+                    "build",
+                    "--lib",
+                    "--quiet",
+                    "--target",
+                    "wasm32-unknown-unknown",
+                    "--message-format=json",
+                ])
+                .current_dir(dir.path()),
+            &ConsoleReporter,
+        )
+        .unwrap();
+        let wasm = find_wasm(&artifacts, package, Path::new("/nonexistent"), "web_app").unwrap();
+        assert!(
+            wasm.starts_with(&metadata.target_directory),
+            "{}",
+            wasm.display()
+        );
+        assert_eq!(
+            find_wasm(&[], package, &profile_path, "web_app").unwrap(),
+            profile_path.join("web_app.wasm")
+        );
+    }
+
+    #[test]
+    fn generate_package_json() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"web-app\"\nversion = \"1.2.3\"\ndescription = \"An app\"\n\
+            license = \"MIT\"\nrepository = \"https://example.com/web-app\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        let package = MetadataCommand::new()
+            .manifest_path(dir.path().join("Cargo.toml"))
+            .no_deps()
+            .exec()
+            .unwrap()
+            .packages
+            .remove(0);
+
+        let entries = PackageEntries {
+            js: "app.mjs".to_string(),
+            typescript: Some("app.d.ts".to_string()),
+            files: Some(vec!["app.mjs".to_string(), "app_bg.wasm".to_string()]),
+            snippets: true,
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&package_json(&package, &entries)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "name": "web-app",
+                "version": "1.2.3",
+                "description": "An app",
+                "license": "MIT",
+                "repository": { "type": "git", "url": "https://example.com/web-app" },
+                "type": "module",
+                "files": ["app.mjs", "app_bg.wasm"],
+                "main": "app.mjs",
+                "module": "app.mjs",
+                "types": "app.d.ts",
+                "sideEffects": ["./app.mjs", "./snippets/*"],
+            })
+        );
+
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"web-app\"\nversion = \"1.2.3\"\nlicense = \"MIT\"\n\n\
+            [package.metadata.wasmbl.package-json]\nname = \"@scope/web-app\"\n\
+            sideEffects = false\nprivate = true\n\n[workspace]\n",
+        )
+        .unwrap();
+        let package = MetadataCommand::new()
+            .manifest_path(dir.path().join("Cargo.toml"))
+            .no_deps()
+            .exec()
+            .unwrap()
+            .packages
+            .remove(0);
+        let entries = PackageEntries {
+            files: None,
+            typescript: None,
+            ..entries
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&package_json(&package, &entries)).unwrap();
+        assert_eq!(json["name"], "@scope/web-app");
+        assert_eq!(json["private"], true);
+        assert_eq!(json["sideEffects"], false);
+        assert_eq!(json["license"], "MIT");
+        assert!(json.get("files").is_none() && json.get("types").is_none());
+    }
+
+    #[test]
+    fn pick_the_lib_name() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        let package = |lib: &str| {
+            fs::write(
+                dir.path().join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"web-app\"\nversion = \"0.1.0\"\n\n[lib]\n{}\
+                    crate-type = [\"cdylib\"]\n\n[workspace]\n",
+                    lib
+                ),
+            )
+            .unwrap();
+            MetadataCommand::new()
+                .manifest_path(dir.path().join("Cargo.toml"))
+                .no_deps()
+                .exec()
+                .unwrap()
+                .packages
+                .remove(0)
+        };
+
+        assert_eq!(lib_name(&package("")), "web_app");
+        assert_eq!(lib_name(&package("name = \"app_core\"\n")), "app_core");
+    }
+
+    #[test]
+    fn translate_wasm_opt_options() {
+        let mut options = WasmOptOptions::for_profile(BuildProfile::Profiling);
+        assert_eq!(
+            wasm_opt_args(&options),
+            ["-o", "-", "-O", "-ol", "2", "-s", "0", "-g"]
+        );
+
+        options = WasmOptOptions::for_profile(BuildProfile::Release);
+        options.level = 4;
+        options.converge = true;
+        options.enabled_features = vec!["bulk-memory".to_string()];
+        options.extra_passes = vec!["dce".to_string(), "--strip-producers".to_string()];
+        assert_eq!(
+            wasm_opt_args(&options),
+            [
+                "-o",
+                "-",
+                "-O",
+                "-ol",
+                "4",
+                "-s",
+                "1",
+                "--converge",
+                "--enable-bulk-memory",
+                "--dce",
+                "--strip-producers",
+            ]
+        );
+
+        options.extra_passes.push("not-a-pass".to_string());
+        assert_eq!(
+            invalid_pass(&options, "Unknown option '--not-a-pass'\n"),
+            Some("not-a-pass")
+        );
+        assert_eq!(invalid_pass(&options, "[wasm-validator error]\n"), None);
+    }
+
+    #[test]
+    fn test_browser() {
+        let args = DefaultTestArgs::from_iter(["test", "--firefox", "--headless"]);
+        assert_eq!(args.browser(), Some(TestBrowser::Firefox));
+
+        let args = DefaultTestArgs::from_iter(["test", "--chrome"]);
+        assert_eq!(args.browser(), Some(TestBrowser::Chrome));
+
+        let args = DefaultTestArgs::from_iter(["test", "my_test"]);
+        assert_eq!(args.browser(), None);
+        assert_eq!(args.filters(), ["my_test"]);
+        assert_eq!(args.filter(), Some("my_test"));
+
+        let args = DefaultTestArgs::from_iter(["test", "--", "--nocapture", "my_test"]);
+        assert_eq!(args.filter(), Some("my_test"));
+
+        let args = DefaultTestArgs::from_iter(["test"]);
+        assert_eq!(args.filter(), None);
+    }
+
+    #[test]
+    fn test_command_flags() {
+        struct Args {
+            metadata: Metadata,
+            features: Vec<String>,
+            target_dir: PathBuf,
+            filters: Vec<String>,
+        }
+
+        impl BuildArgs for Args {
+            fn build_path(&self) -> PathBuf {
+                PathBuf::from("build")
+            }
+
+            fn metadata(&self) -> &Metadata {
+                &self.metadata
+            }
+
+            fn frontend_package(&self) -> &Package {
+                &self.metadata.packages[0]
+            }
+
+            fn profiling(&self) -> bool {
+                false
+            }
+
+            fn features(&self) -> &[String] {
+                &self.features
+            }
+
+            fn default_features(&self) -> bool {
+                false
+            }
+
+            fn target_dir(&self) -> Option<&PathBuf> {
+                Some(&self.target_dir)
+            }
+        }
+
+        impl WatchArgs for Args {}
+
+        impl TestArgs for Args {
+            fn chrome(&self) -> bool {
+                false
+            }
+
+            fn firefox(&self) -> bool {
+                false
+            }
+
+            fn headless(&self) -> bool {
+                false
+            }
+
+            fn release(&self) -> bool {
+                true
+            }
+
+            fn filters(&self) -> &[String] {
+                &self.filters
+            }
+
+            fn build_args(&self) -> &dyn BuildArgs {
+                self
+            }
+        }
+
+        let args = Args {
+            metadata: MetadataCommand::new().no_deps().exec().unwrap(),
+            features: vec!["a".to_string(), "b".to_string()],
+            target_dir: PathBuf::from("tests-target"),
+            filters: vec!["my_test".to_string()],
+        };
+        let command = test_command(&args, &Hooks::default(), None).unwrap();
+        let command_args: Vec<_> = command.get_args().map(|x| x.to_str().unwrap()).collect();
+        assert!(command_args.contains(&"--release"));
+        assert!(command_args.contains(&"--no-default-features"));
+        assert!(command_args.windows(2).any(|x| x == ["--features", "a,b"]));
+        assert!(command_args
+            .windows(2)
+            .any(|x| x == ["--target-dir", "tests-target"]));
+        assert_eq!(command_args[command_args.len() - 2..], ["--", "my_test"]);
+    }
+
+    #[test]
+    fn serve_profile() {
+        let args = DefaultServeArgs::from_iter(["serve"]);
+        assert_eq!(args.profile(), BuildProfile::Dev);
+
+        let args = DefaultServeArgs::from_iter(["serve", "--release"]);
+        assert_eq!(args.profile(), BuildProfile::Release);
+
+        let args = DefaultServeArgs::from_iter(["serve", "--release", "--profiling"]);
+        assert_eq!(args.profile(), BuildProfile::Profiling);
+    }
+
+    #[test]
+    fn out_name() {
+        let args = DefaultBuildArgs::from_iter(["build"]);
+        assert_eq!(args.out_name(), "app");
+
+        let args = DefaultBuildArgs::from_iter(["build", "--out-name", "client"]);
+        assert_eq!(args.out_name(), "client");
+
+        let index = default_index(args.out_name(), "/");
+        assert!(index.contains(r#"import init from "/client.js""#));
+        let index = default_index(args.out_name(), "/demo/");
+        assert!(index.contains(r#"import init from "/demo/client.js""#));
+        assert!(index.contains("new URL('client_bg.wasm', import.meta.url)"));
+    }
+
+    #[test]
+    fn build_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let dist = dir.join("dist");
+        for flag in ["--build-path", "--out-dir", "-d"] {
+            let args =
+                DefaultBuildArgs::from_iter(["build".as_ref(), flag.as_ref(), dist.as_os_str()]);
+            assert_eq!(args.build_path(), dist);
+        }
+        let args = DefaultBuildArgs::from_iter(["build", "--out-dir", "dist"]);
+        assert!(args.build_path().is_absolute());
+        assert!(args.build_path().ends_with("dist"));
+        assert!(
+            DefaultBuildArgs::from_iter_safe(["build", "--build-path", "a", "-d", "b"]).is_err()
+        );
+
+        let package_dir = dir.join("frontend");
+        fs::create_dir_all(package_dir.join("src")).unwrap();
+        let check = |path: &Path| check_build_path(path, &dir, &package_dir);
+        assert!(check(&dir.join("build")).is_ok());
+        assert!(check(&package_dir.join("build")).is_ok());
+        assert!(check(&package_dir.join("srcs")).is_ok());
+        assert!(check(&dir.join("frontend").join("..")).is_err());
+        assert!(check(&package_dir).is_err());
+        let err = check(&package_dir.join("src").join("build")).unwrap_err();
+        assert!(err.to_string().contains("inside the sources"), "{}", err);
+    }
+
+    #[cfg(feature = "prebuilt-wasm-opt")]
+    #[test]
+    fn wasm_opt_unavailable() {
+        let err = Error::from(
+            prebuilt_wasm_opt(
+                Path::new("/nonexistent/wasm-opt"),
+                b"\0asm",
+                &WasmOptOptions::for_profile(BuildProfile::Release),
+            )
+            .unwrap_err(),
+        );
+        assert!(matches!(err, Error::WasmOptUnavailable(_)), "{:?}", err);
+        assert!(err.to_string().contains("`--allow-unoptimized`"), "{}", err);
+
+        assert!(!DefaultBuildArgs::from_iter(["build"]).allow_unoptimized());
+        assert!(DefaultBuildArgs::from_iter(["build", "--allow-unoptimized"]).allow_unoptimized());
+    }
+
+    #[cfg(all(unix, feature = "prebuilt-wasm-opt"))]
+    #[test]
+    fn wasm_opt_crashes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // NOTE: it crashes only the first time
+        let dir = tempfile::tempdir().unwrap();
+        let wasm_opt = dir.path().join("wasm-opt");
+        let crashed = dir.path().join("crashed");
+        fs::write(
+            &wasm_opt,
+            format!(
+                "#!/bin/sh\nif [ -e '{0}' ]; then cat; else touch '{0}'; kill -SEGV $$; fi\n",
+                crashed.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&wasm_opt, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut options = WasmOptOptions::for_profile(BuildProfile::Release);
+        let err = format!(
+            "{:#}",
+            prebuilt_wasm_opt(&wasm_opt, b"\0asm", &options).unwrap_err()
+        );
+        assert!(err.starts_with("`wasm-opt` crashed (signal: 11"), "{}", err);
+        assert!(err.contains("pass `--no-wasm-opt`"), "{}", err);
+        assert!(err.contains("  stage: wasm-opt\n"), "{}", err);
+        assert!(err.contains(&format!(
+            "  version: binaryen {}\n",
+            prebuilt_wasm_opt::BINARYEN_VERSION
+        )));
+        let input = err
+            .lines()
+            .find_map(|x| x.strip_prefix("  input: "))
+            .unwrap();
+        assert!(err.contains(&format!(
+            "  command: {} -o - -O -ol 2 -s 1 {}",
+            wasm_opt.display(),
+            input
+        )));
+        assert_eq!(fs::read(input).unwrap(), b"\0asm");
+        fs::remove_file(input).unwrap();
+
+        fs::remove_file(&crashed).unwrap();
+        options.retries = 1;
+        assert_eq!(
+            prebuilt_wasm_opt(&wasm_opt, b"\0asm", &options).unwrap(),
+            b"\0asm"
+        );
+
+        let args = DefaultBuildArgs::from_iter(["build", "--wasm-opt-retries", "2"]);
+        assert_eq!(args.wasm_opt_options(BuildProfile::Release).retries, 2);
+        assert!(args.wasm_opt());
+        assert!(!DefaultBuildArgs::from_iter(["build", "--no-wasm-opt"]).wasm_opt());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn environment_of_the_processes() {
+        struct Args {
+            metadata: Metadata,
+            build_path: PathBuf,
+        }
+
+        impl BuildArgs for Args {
+            fn build_path(&self) -> PathBuf {
+                self.build_path.clone()
+            }
+
+            fn metadata(&self) -> &Metadata {
+                &self.metadata
+            }
+
+            fn frontend_package(&self) -> &Package {
+                &self.metadata.packages[0]
+            }
+
+            fn profiling(&self) -> bool {
+                false
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("app").join("src")).unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"app\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("app").join("Cargo.toml"),
+            "[package]\nname = \"web-app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::write(root.join("app").join("src").join("lib.rs"), "").unwrap();
+        let args = Args {
+            metadata: MetadataCommand::new()
+                .manifest_path(root.join("Cargo.toml"))
+                .no_deps()
+                .exec()
+                .unwrap(),
+            build_path: root.join("app").join("build"),
+        };
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(format!(
+            "test \"$WASMBL_WORKSPACE_ROOT\" = '{0}' || exit 1\n\
+            test \"$WASMBL_PACKAGE_DIR\" = '{0}/app' || exit 2\n\
+            test \"$WASMBL_PACKAGE_NAME\" = web-app || exit 3\n\
+            test \"$WASMBL_BUILD_PATH\" = '{0}/app/build' || exit 4\n\
+            test \"$WASMBL_PROFILE\" = release || exit 5\n",
+            root.display(),
+        ));
+        process_env(&args, BuildProfile::Release, &mut command);
+        let status = command.current_dir(std::env::temp_dir()).status().unwrap();
+        assert!(status.success(), "{}", status);
+
+        let status = bundle_command(
+            &args,
+            "test \"$PWD\" = \"$WASMBL_PACKAGE_DIR\" && test \"$WASMBL_PROFILE\" = dev \
+            && test -n \"$WASMBL_JS_GLUE\" && test -n \"$WASMBL_SNIPPETS_DIR\"",
+            BuildProfile::Dev,
+            &root.join("app").join("build").join("app.js"),
+        )
+        .status()
+        .unwrap();
+        assert!(status.success(), "{}", status);
+    }
+
+    #[test]
+    fn panic_strategies() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src").join("lib.rs"), "").unwrap();
+        let package = |metadata: &str| {
+            fs::write(
+                dir.path().join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"web-app\"\nversion = \"0.1.0\"\n\n[workspace]\n\n\
+                    [package.metadata.wasmbl]\n{}",
+                    metadata
+                ),
+            )
+            .unwrap();
+            MetadataCommand::new()
+                .manifest_path(dir.path().join("Cargo.toml"))
+                .no_deps()
+                .exec()
+                .unwrap()
+                .packages
+                .remove(0)
+        };
+
+        let all = package("panic-strategy = \"abort\"\n");
+        for profile in [BuildProfile::Dev, BuildProfile::Release] {
+            assert_eq!(
+                metadata_panic_strategy(&all, profile),
+                Some(PanicStrategy::Abort)
+            );
+        }
+        let by_profile =
+            package("panic-strategy = { dev = \"unwind-verbose\", release = \"abort-strip\" }\n");
+        for (profile, strategy) in [
+            (BuildProfile::Dev, PanicStrategy::UnwindVerbose),
+            (BuildProfile::Release, PanicStrategy::AbortStrip),
+            (BuildProfile::Profiling, PanicStrategy::AbortStrip),
+        ] {
+            assert_eq!(
+                metadata_panic_strategy(&by_profile, profile),
+                Some(strategy)
+            );
+        }
+        let unknown = package("panic-strategy = \"unwind\"\n");
+        assert_eq!(metadata_panic_strategy(&unknown, BuildProfile::Dev), None);
+
+        let env = |strategy: PanicStrategy, profile: BuildProfile| {
+            let mut command = Command::new("cargo");
+            strategy.apply(&mut command, profile);
+            command
+                .get_envs()
+                .map(|(name, value)| {
+                    (
+                        name.to_string_lossy().into_owned(),
+                        value.unwrap().to_string_lossy().into_owned(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let var = |name: &str, value: &str| (name.to_string(), value.to_string());
+        assert_eq!(
+            env(PanicStrategy::UnwindVerbose, BuildProfile::Release),
+            [var("CARGO_PROFILE_RELEASE_DEBUG_ASSERTIONS", "true")]
+        );
+        assert_eq!(
+            env(PanicStrategy::Abort, BuildProfile::Dev),
+            [var("CARGO_PROFILE_DEV_PANIC", "abort")]
+        );
+        assert_eq!(
+            env(PanicStrategy::AbortStrip, BuildProfile::Profiling),
+            [
+                var("CARGO_PROFILE_RELEASE_DEBUG_ASSERTIONS", "false"),
+                var("CARGO_PROFILE_RELEASE_PANIC", "abort"),
+            ]
+        );
+        assert_eq!(
+            DefaultBuildArgs::from_iter(["build", "--panic-strategy", "abort-strip"])
+                .panic_strategy,
+            Some(PanicStrategy::AbortStrip)
+        );
+    }
+
+    #[test]
+    fn clean() {
+        assert_eq!(DefaultBuildArgs::from_iter(["build"]).clean(), None);
+        assert_eq!(
+            DefaultBuildArgs::from_iter(["build", "--clean"]).clean(),
+            Some(true)
+        );
+        assert_eq!(
+            DefaultBuildArgs::from_iter(["build", "--no-clean"]).clean(),
+            Some(false)
+        );
+        assert!(DefaultBuildArgs::from_iter_safe(["build", "--clean", "--no-clean"]).is_err());
+    }
+
+    #[test]
+    fn assets_in_the_frontend_dir() {
+        let (_, package) = crate::runner::tests::init_project();
+        let package_path = package.manifest_path.parent().unwrap();
+        let args = DefaultBuildArgs::from_iter(["build", "--static-dir", "public"]);
+        assert_eq!(args.frontend_dir(), None);
+        assert_eq!(args.static_dir(), Path::new("public"));
+        assert_eq!(frontend_root(&args), package_path);
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("pages").join("admin")).unwrap();
+        fs::write(
+            dir.path().join("pages").join("admin").join("index.html"),
+            "",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("styles")).unwrap();
+        let args = DefaultBuildArgs::from_iter([
+            "build".as_ref(),
+            "--frontend-dir".as_ref(),
+            dir.path().as_os_str(),
+            "--trunk-index".as_ref(),
+            "index.html".as_ref(),
+        ]);
+        assert_eq!(args.static_dir(), dir.path().join("static"));
+        assert_eq!(args.trunk_index(), Some(dir.path().join("index.html")));
+        assert_eq!(
+            args.pages(),
+            [Page::new(
+                dir.path().join("pages").join("admin").join("index.html"),
+                Path::new("admin").join("index.html")
+            )]
+        );
+        #[cfg(feature = "sass")]
+        assert_eq!(
+            args.sass_lookup_directories(BuildProfile::Release),
+            [dir.path().join("styles")]
+        );
+
+        let settings = config::resolve(&args, None, &Hooks::default());
+        let setting = |name: &str| {
+            settings
+                .iter()
+                .find(|x| x.name == name)
+                .unwrap()
+                .value
+                .clone()
+        };
+        let path = |path: PathBuf| serde_json::json!(path.display().to_string());
+        assert_eq!(setting("frontend-dir"), path(dir.path().to_path_buf()));
+        assert_eq!(setting("static-dir"), path(dir.path().join("static")));
+        assert_eq!(setting("trunk-index"), path(dir.path().join("index.html")));
+    }
+
+    #[cfg(feature = "dev-server")]
+    #[test]
+    fn bind_addresses() {
+        let args = DefaultServeArgs::from_iter(["serve", "--ip", "::1", "--port", "8080"]);
+        assert_eq!(args.bind(), ["[::1]:8080".parse().unwrap()]);
+
+        let args = DefaultServeArgs::from_iter([
+            "serve",
+            "--port",
+            "8080",
+            "--bind",
+            "::1",
+            "--bind",
+            "[::1]:9000",
+            "--bind",
+            "127.0.0.1",
+        ]);
+        assert_eq!(
+            args.bind(),
+            [
+                "[::1]:8080".parse::<SocketAddr>().unwrap(),
+                "[::1]:9000".parse().unwrap(),
+                "127.0.0.1:8080".parse().unwrap(),
+            ]
+        );
+
+        assert!(DefaultServeArgs::from_iter_safe(["serve", "--bind", "localhost"]).is_err());
+        assert!(DefaultServeArgs::from_iter_safe(["serve", "--ip", "localhost"]).is_err());
+    }
+}
@@ -0,0 +1,529 @@
+use anyhow::Result;
+use futures::channel::mpsc;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Path of the server-sent events endpoint the browsers subscribe to.
+#[cfg(feature = "dev-server")]
+const EVENTS_PATH: &str = "/__wasmbl/events";
+
+/// Prefix of the endpoints of the control API (see [`crate::ServeArgs::control_api`]).
+#[cfg(feature = "dev-server")]
+const CONTROL_PATH: &str = "/__wasmbl";
+
+/// Script injected in the HTML pages served by the development server.
+#[cfg(feature = "dev-server")]
+const SCRIPT: &str = r#"<script>(function(){var o=null;var s=new EventSource("/__wasmbl/events");s.addEventListener("reload",function(){location.reload();});s.addEventListener("css-update",function(e){var p=JSON.parse(e.data),n=0;document.querySelectorAll('link[rel="stylesheet"]').forEach(function(l){var u=new URL(l.href);if(p.some(function(x){return u.pathname.endsWith("/"+x);})){u.searchParams.set("wasmbl",Date.now());l.href=u.href;n++;}});if(!n){location.reload();return;}if(o){o.remove();o=null;}});s.addEventListener("error",function(e){if(!e.data)return;if(!o){o=document.createElement("pre");o.style.cssText="position:fixed;inset:0;margin:0;padding:2em;z-index:2147483647;overflow:auto;background:rgba(0,0,0,.85);color:#ff6b6b;font:14px monospace;white-space:pre-wrap";o.onclick=function(){o.remove();o=null;};document.body.appendChild(o);}o.textContent=e.data;});})();</script>"#;
+
+/// An event sent to the browsers at the end of a rebuild.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ReloadEvent {
+    /// The rebuild succeeded, the page must be reloaded.
+    Reload,
+    /// The rebuild only changed these stylesheets (relative to the build directory, separated by
+    /// `/`): they are replaced in the page without reloading it.
+    CssUpdate(Vec<String>),
+    /// The rebuild failed, the error is displayed in an overlay.
+    Error(String),
+}
+
+/// The outcome of the last build, returned by the control API.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BuildStatus {
+    /// The error of the build if it failed.
+    pub error: Option<String>,
+    pub duration: Duration,
+    /// When the build finished, in seconds since the UNIX epoch.
+    pub finished: u64,
+    /// The names and the sizes of the WASM and of the JS bindings of the last successful build.
+    pub artifacts: Vec<(String, usize)>,
+    /// The errors and the warnings of the compiler during the last cargo build.
+    pub diagnostics: crate::diagnostics::Diagnostics,
+}
+
+impl BuildStatus {
+    pub(crate) fn new(
+        res: &Result<()>,
+        duration: Duration,
+        output: Option<&crate::BuildOutput>,
+    ) -> Self {
+        let name = |path: &Path| {
+            path.file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        Self {
+            error: res.as_ref().err().map(|err| format!("{:#}", err)),
+            duration,
+            finished: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or(0),
+            artifacts: output
+                .map(|output| {
+                    vec![
+                        (name(&output.wasm_path), output.wasm.len()),
+                        (name(&output.js_path), output.js.len()),
+                    ]
+                })
+                .unwrap_or_default(),
+            diagnostics: crate::diagnostics::last(),
+        }
+    }
+
+    #[cfg(feature = "dev-server")]
+    fn to_json(&self) -> serde_json::Value {
+        let artifacts: Vec<_> = self
+            .artifacts
+            .iter()
+            .map(|(path, size)| serde_json::json!({ "path": path, "size": size }))
+            .collect();
+        serde_json::json!({
+            "status": if self.error.is_none() { "success" } else { "failure" },
+            "error": self.error,
+            "duration": self.duration.as_secs_f64(),
+            "finished": self.finished,
+            "artifacts": artifacts,
+            "diagnostics": self.diagnostics.to_json(),
+        })
+    }
+}
+
+/// Broadcasts the outcome of the rebuilds to the connected browsers.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LiveReload {
+    clients: Arc<Mutex<Vec<mpsc::UnboundedSender<ReloadEvent>>>>,
+    /// Triggers the rebuilds requested with the control API, once the watcher is started.
+    rebuild: Arc<Mutex<Option<crate::RebuildHandle>>>,
+    status: Arc<Mutex<Option<BuildStatus>>>,
+    /// The last rebuild failed: the browsers show its error until the next successful rebuild.
+    failed: Arc<Mutex<bool>>,
+    /// Runs the builds one at a time, its queue is returned by the control API.
+    orchestrator: crate::pipeline::Orchestrator,
+}
+
+impl LiveReload {
+    /// Subscribe to the events.
+    #[cfg(any(test, feature = "dev-server"))]
+    pub(crate) fn subscribe(&self) -> mpsc::UnboundedReceiver<ReloadEvent> {
+        let (tx, rx) = mpsc::unbounded();
+        self.clients.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Send an event to all the subscribers. Disconnected subscribers are dropped.
+    pub(crate) fn broadcast(&self, event: ReloadEvent) {
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|client| client.unbounded_send(event.clone()).is_ok());
+
+        let kind = match &event {
+            ReloadEvent::Reload => "reload",
+            ReloadEvent::CssUpdate(_) => "css-update",
+            ReloadEvent::Error(_) => "error",
+        };
+        crate::logger::event(
+            log::Level::Debug,
+            "reload_sent",
+            serde_json::json!({ "kind": kind, "clients": clients.len() }),
+            Some(format_args!(
+                "Sent `{}` to {} browsers",
+                kind,
+                clients.len()
+            )),
+        );
+    }
+
+    /// Let the control API trigger the rebuilds with the watcher.
+    pub(crate) fn set_rebuild_handle(&self, handle: crate::RebuildHandle) {
+        *self.rebuild.lock().unwrap() = Some(handle);
+    }
+
+    /// The orchestrator of the pipelines that write to the build directory.
+    pub(crate) fn orchestrator(&self) -> &crate::pipeline::Orchestrator {
+        &self.orchestrator
+    }
+
+    /// Record the outcome of the last build for the control API.
+    pub(crate) fn set_status(&self, status: BuildStatus) {
+        *self.status.lock().unwrap() = Some(status);
+    }
+
+    /// Run a rebuild and notify the subscribers of its outcome.
+    ///
+    /// The reload is only sent once the whole rebuild succeeded; a failure is sent as an error
+    /// instead and the browser keeps the current page. Nothing is sent if the files of the build
+    /// directory are identical to the previous build, so the page keeps its state, unless the
+    /// browsers show the error of a failed rebuild. If the rebuild only changed the content of
+    /// stylesheets of the build directory, they are updated in place instead of reloading the page.
+    pub(crate) fn rebuild(
+        &self,
+        build_path: &Path,
+        rebuild: impl FnOnce() -> Result<()>,
+    ) -> Result<()> {
+        let before = Outputs::snapshot(build_path);
+        match rebuild() {
+            Ok(()) => {
+                let after = Outputs::snapshot(build_path);
+                let failed = std::mem::replace(&mut *self.failed.lock().unwrap(), false);
+                if before.0 == after.0 && !failed {
+                    log::info!("Output identical \u{2014} skipping reload");
+                    return Ok(());
+                }
+                self.broadcast(match before.css_update(&after) {
+                    Some(stylesheets) => ReloadEvent::CssUpdate(stylesheets),
+                    None => ReloadEvent::Reload,
+                });
+                Ok(())
+            }
+            Err(err) if crate::is_cancelled(&err) => Err(err),
+            Err(err) => {
+                let mut message = format!("{:#}", err);
+                if let Some(summary) = crate::diagnostics::failure_summary(&err) {
+                    message.push_str("\n\n");
+                    message.push_str(&summary);
+                }
+                *self.failed.lock().unwrap() = true;
+                self.broadcast(ReloadEvent::Error(message));
+                Err(err)
+            }
+        }
+    }
+
+    /// Register the events endpoint on the server and inject the reload script in the HTML pages.
+    #[cfg(feature = "dev-server")]
+    pub(crate) fn register(&self, server: &mut tide::Server<()>) {
+        use futures::StreamExt;
+        use tide::{http::mime, utils::After, Response};
+
+        let live_reload = self.clone();
+        server
+            .at(EVENTS_PATH)
+            .get(tide::sse::endpoint(move |_, sender| {
+                let mut rx = live_reload.subscribe();
+                async move {
+                    while let Some(event) = rx.next().await {
+                        match event {
+                            ReloadEvent::Reload => sender.send("reload", "", None).await?,
+                            ReloadEvent::CssUpdate(stylesheets) => {
+                                let data = serde_json::to_string(&stylesheets)
+                                    .expect("a list of strings; qed");
+                                sender.send("css-update", data, None).await?
+                            }
+                            ReloadEvent::Error(err) => sender.send("error", err, None).await?,
+                        }
+                    }
+                    Ok(())
+                }
+            }));
+
+        server.with(After(|mut res: Response| async move {
+            if res
+                .content_type()
+                .map(|x| x.essence() == mime::HTML.essence())
+                == Some(true)
+            {
+                let html = res.take_body().into_string().await?;
+                res.set_body(inject_script(&html, SCRIPT));
+                res.set_content_type(mime::HTML);
+            }
+            Ok(res)
+        }));
+    }
+}
+
+/// Check that an authority (`host:port`) of the headers of a request is this machine at the port
+/// of the server.
+#[cfg(feature = "dev-server")]
+fn is_own_authority(authority: &str, port: u16) -> bool {
+    let (host, authority_port) = match authority.rsplit_once(':') {
+        Some((host, x)) if !x.contains(']') => (host, x.parse::<u16>().ok()),
+        _ => (authority, Some(80)),
+    };
+    let is_loopback = host.eq_ignore_ascii_case("localhost")
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<std::net::IpAddr>()
+            .map(|x| x.to_canonical().is_loopback())
+            .unwrap_or(false);
+
+    is_loopback && authority_port == Some(port)
+}
+
+/// Register the endpoints of the control API on the server. They only answer the requests of this
+/// machine.
+///
+/// A page of another site opened in a browser of this machine could still send requests to the
+/// server, so the `Host` header must name the server on a loopback address (against DNS
+/// rebinding) and the `Origin` header, if any, must be the server itself.
+#[cfg(feature = "dev-server")]
+pub(crate) fn register_control(live_reload: &LiveReload, server: &mut tide::Server<()>) {
+    use tide::{Request, Response, StatusCode};
+
+    fn is_local(req: &Request<()>) -> bool {
+        req.peer_addr()
+            .and_then(|x| x.parse::<std::net::SocketAddr>().ok())
+            .map(|x| x.ip().to_canonical().is_loopback())
+            .unwrap_or(false)
+    }
+
+    fn is_same_origin(req: &Request<()>) -> bool {
+        let port = match req
+            .local_addr()
+            .and_then(|x| x.parse::<std::net::SocketAddr>().ok())
+        {
+            Some(x) => x.port(),
+            None => return false,
+        };
+        let host = match req.header("Host") {
+            Some(x) => x.last().as_str(),
+            None => return false,
+        };
+        let origin = req.header("Origin").map(|x| x.last().as_str());
+
+        is_own_authority(host, port)
+            && origin
+                .map(|x| {
+                    x.strip_prefix("http://")
+                        .map(|x| is_own_authority(x, port))
+                        .unwrap_or(false)
+                })
+                .unwrap_or(true)
+    }
+
+    fn forbidden(req: &Request<()>) -> Option<Response> {
+        if is_local(req) && is_same_origin(req) {
+            return None;
+        }
+        log::warn!(
+            "Rejected a request to the control API from `{}` (origin: `{}`)",
+            req.peer_addr().unwrap_or("unknown"),
+            req.header("Origin")
+                .map(|x| x.last().as_str())
+                .unwrap_or("none"),
+        );
+        Some(Response::new(StatusCode::Forbidden))
+    }
+
+    let reload = live_reload.clone();
+    server
+        .at(&format!("{}/reload", CONTROL_PATH))
+        .post(move |req: Request<()>| {
+            let live_reload = reload.clone();
+            async move {
+                if let Some(res) = forbidden(&req) {
+                    return Ok(res);
+                }
+                log::info!("Reloading the browsers (requested with the control API)");
+                live_reload.broadcast(ReloadEvent::Reload);
+                Ok(Response::new(StatusCode::NoContent))
+            }
+        });
+
+    let rebuild = live_reload.rebuild.clone();
+    server
+        .at(&format!("{}/rebuild", CONTROL_PATH))
+        .post(move |req: Request<()>| {
+            let rebuild = rebuild.lock().unwrap().clone();
+            async move {
+                if let Some(res) = forbidden(&req) {
+                    return Ok(res);
+                }
+                Ok(match rebuild {
+                    Some(handle) => {
+                        log::info!("Rebuilding (requested with the control API)");
+                        handle.trigger();
+                        Response::new(StatusCode::Accepted)
+                    }
+                    None => Response::new(StatusCode::ServiceUnavailable),
+                })
+            }
+        });
+
+    let status = live_reload.status.clone();
+    let orchestrator = live_reload.orchestrator.clone();
+    server
+        .at(&format!("{}/status", CONTROL_PATH))
+        .get(move |req: Request<()>| {
+            let status = status.lock().unwrap().clone();
+            let queue = orchestrator.state();
+            async move {
+                if let Some(res) = forbidden(&req) {
+                    return Ok(res);
+                }
+                let mut json = match status {
+                    Some(status) => status.to_json(),
+                    None => serde_json::json!({ "status": "pending" }),
+                };
+                json["queue"] = queue.as_str().into();
+                let mut res = Response::new(StatusCode::Ok);
+                res.set_body(tide::Body::from_json(&json)?);
+                Ok(res)
+            }
+        });
+}
+
+/// The content hashes of the files of the build directory, to find what a rebuild changed.
+#[derive(Debug, Default)]
+struct Outputs(HashMap<PathBuf, Vec<u8>>);
+
+impl Outputs {
+    fn snapshot(build_path: &Path) -> Self {
+        let files = crate::list_files(build_path).unwrap_or_default();
+        Self(
+            files
+                .into_par_iter()
+                .filter_map(|path| {
+                    let hash = Sha256::digest(&fs::read(&path).ok()?).to_vec();
+                    let path = path.strip_prefix(build_path).ok()?.to_path_buf();
+                    // NOTE: the list of the files is written again by every build
+                    (!crate::build_files::is_internal(&path)).then_some((path, hash))
+                })
+                .collect(),
+        )
+    }
+
+    /// The stylesheets changed between the two snapshots, sorted, if they are the only files
+    /// added, removed or modified.
+    fn css_update(&self, after: &Outputs) -> Option<Vec<String>> {
+        let mut changed: Vec<_> = after
+            .0
+            .iter()
+            .filter(|(path, hash)| self.0.get(*path) != Some(hash))
+            .map(|(path, _)| path)
+            .chain(self.0.keys().filter(|x| !after.0.contains_key(*x)))
+            .collect();
+        if changed.is_empty()
+            || !changed
+                .iter()
+                .all(|x| x.extension().map(|x| x == "css").unwrap_or(false))
+        {
+            return None;
+        }
+
+        changed.sort();
+        Some(changed.into_iter().map(|x| crate::slash_path(x)).collect())
+    }
+}
+
+/// Insert a script at the end of the body of an HTML page.
+#[cfg(feature = "dev-server")]
+pub(crate) fn inject_script(html: &str, script: &str) -> String {
+    match html.rfind("</body>") {
+        Some(i) => format!("{}{}{}", &html[..i], script, &html[i..]),
+        None => format!("{}{}", html, script),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn no_reload_after_failed_build() {
+        let live_reload = LiveReload::default();
+        let mut rx = live_reload.subscribe();
+
+        let res = live_reload.rebuild(Path::new("/nonexistent"), || {
+            crate::run_cargo(
+                Command::new("cargo").args(["build", "--manifest-path", "/nonexistent/Cargo.toml"]),
+                &crate::ConsoleReporter,
+            )?;
+            Ok(())
+        });
+
+        assert!(res.is_err());
+        assert!(matches!(rx.try_recv(), Ok(ReloadEvent::Error(_))));
+        assert!(rx.try_recv().is_err(), "no other event must be sent");
+    }
+
+    #[test]
+    fn reload_after_successful_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let live_reload = LiveReload::default();
+        let mut rx = live_reload.subscribe();
+
+        live_reload
+            .rebuild(dir.path(), || {
+                Ok(fs::write(dir.path().join("app.js"), "js")?)
+            })
+            .unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), ReloadEvent::Reload);
+    }
+
+    #[test]
+    fn no_reload_after_identical_build() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "js").unwrap();
+        let live_reload = LiveReload::default();
+        let mut rx = live_reload.subscribe();
+
+        live_reload
+            .rebuild(dir.path(), || {
+                Ok(fs::write(dir.path().join("app.js"), "js")?)
+            })
+            .unwrap();
+        assert!(rx.try_recv().is_err());
+
+        // NOTE: the error of the failed rebuild must be replaced by the page
+        let _ = live_reload.rebuild(dir.path(), || Err(anyhow::anyhow!("broken")));
+        assert!(matches!(rx.try_recv(), Ok(ReloadEvent::Error(_))));
+        live_reload.rebuild(dir.path(), || Ok(())).unwrap();
+        assert_eq!(rx.try_recv().unwrap(), ReloadEvent::Reload);
+        live_reload.rebuild(dir.path(), || Ok(())).unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn update_stylesheets_only_after_css_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("styles")).unwrap();
+        fs::write(dir.path().join("app.js"), "js").unwrap();
+        fs::write(dir.path().join("styles").join("app.css"), "a {}").unwrap();
+        let live_reload = LiveReload::default();
+        let mut rx = live_reload.subscribe();
+
+        live_reload
+            .rebuild(dir.path(), || {
+                fs::write(dir.path().join("styles").join("app.css"), "b {}")?;
+                fs::write(dir.path().join("theme.css"), "c {}")?;
+                // NOTE: the same content
+                fs::write(dir.path().join("app.js"), "js")?;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            ReloadEvent::CssUpdate(vec!["styles/app.css".to_string(), "theme.css".to_string()])
+        );
+
+        live_reload
+            .rebuild(dir.path(), || {
+                fs::write(dir.path().join("theme.css"), "d {}")?;
+                fs::write(dir.path().join("app.js"), "js 2")?;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap(), ReloadEvent::Reload);
+    }
+
+    #[cfg(feature = "dev-server")]
+    #[test]
+    fn control_api_authority() {
+        assert!(is_own_authority("127.0.0.1:3000", 3000));
+        assert!(is_own_authority("localhost:3000", 3000));
+        assert!(is_own_authority("[::1]:3000", 3000));
+        assert!(is_own_authority("localhost", 80));
+        assert!(!is_own_authority("localhost:8080", 3000));
+        assert!(!is_own_authority("[::1]", 3000));
+        assert!(!is_own_authority("evil.example:3000", 3000));
+        assert!(!is_own_authority("192.168.1.2:3000", 3000));
+    }
+}
@@ -0,0 +1,322 @@
+//! The logger of the generated command-line: `env_logger` with the level given by the flags
+//! `-v`, `-vv` and `--quiet`, or one JSON object per line with `--log-format json`.
+
+use anyhow::bail;
+use once_cell::sync::OnceCell;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+use structopt::StructOpt;
+
+static VERBOSITY: OnceCell<Verbosity> = OnceCell::new();
+static HTTP_LOGS: AtomicBool = AtomicBool::new(false);
+
+/// Verbosity and log format flags added to all the commands.
+///
+/// The macro [`crate::main`] adds them to the command-line. When it is used on a struct, a field
+/// of this type can be flattened in the struct to get the flags.
+///
+/// The environment variable `RUST_LOG` takes precedence when it is set.
+#[derive(StructOpt, Debug, Default, Clone, Copy)]
+pub struct Verbosity {
+    /// Show more logs: `-v` for the debug logs (the commands that are run), `-vv` for the trace
+    /// logs (the file events).
+    #[structopt(
+        short = "v",
+        name = "wasmbl-verbose",
+        parse(from_occurrences),
+        global = true
+    )]
+    pub verbose: u8,
+
+    /// Only show the warnings and the errors.
+    #[structopt(
+        short = "q",
+        long = "quiet",
+        name = "wasmbl-quiet",
+        conflicts_with = "wasmbl-verbose",
+        global = true
+    )]
+    pub quiet: bool,
+
+    /// Format of the logs: `text` or `json` (one JSON object per line).
+    #[structopt(
+        long = "log-format",
+        name = "wasmbl-log-format",
+        default_value = "text",
+        possible_values = &["text", "json"],
+        global = true
+    )]
+    pub log_format: LogFormat,
+}
+
+/// The format of the logs, given by the flag `--log-format`.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum LogFormat {
+    /// Lines for humans, colored in the terminals.
+    #[default]
+    Text,
+    /// One JSON object per line on the standard error, for the tools that follow the commands.
+    ///
+    /// Every object has the fields `ts` (RFC 3339, UTC), `level` (`error`, `warn`, `info`,
+    /// `debug` or `trace`), `target` and `event`, and a `message` for the events that have one.
+    /// The events and their fields are stable, new fields might be added:
+    ///
+    /// | `event`          | Fields                                                                    |
+    /// |------------------|---------------------------------------------------------------------------|
+    /// | `log`            | `message`: the other logs, filtered by `-v` and `--quiet`                 |
+    /// | `build_started`  | `profile` (`dev`, `release` or `profiling`), `rebuild` (by the watcher)   |
+    /// | `build_finished` | `profile`, `duration` (seconds), `wasm` and `js` (`path`, `size` in bytes), `optimized` |
+    /// | `build_failed`   | `profile`, `duration`, `error`                                            |
+    /// | `diagnostic`     | `code`, `message`, `file`, `line`, `column`: a message of the compiler, its `level` is `error`, `warn` or `info` |
+    /// | `request`        | `method`, `path`, `status`, `duration`: a request of the development server, with `--log` |
+    /// | `reload_sent`    | `kind` (`reload`, `css-update` or `error`), `clients`: an event sent to the browsers |
+    /// | `error`          | `message`: the error that ends the command                               |
+    ///
+    /// The events are written whatever the verbosity. The output of cargo is not forwarded: its
+    /// diagnostics are `diagnostic` events and its other lines are logs (see
+    /// [`Reporter::cargo_output`](crate::Reporter::cargo_output)). The standard output is
+    /// not affected, the output of the other commands run by `wasmbl` (the backend, the bundle
+    /// command) is not captured.
+    ///
+    /// The `error` event is only written by the `main` function generated by [`crate::main`].
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("unknown log format `{}`, expected `text` or `json`", s),
+        }
+    }
+}
+
+impl Verbosity {
+    /// The filter of `env_logger` used when `RUST_LOG` is not set.
+    fn filter(self) -> &'static str {
+        match (self.quiet, self.verbose) {
+            (true, _) => "warn",
+            (false, 0) => "info",
+            (false, 1) => "info,wasmbl=debug",
+            (false, _) => "info,wasmbl=trace",
+        }
+    }
+}
+
+/// Set the verbosity of the logger. This must be called before [`crate::Runner::init`] to have an
+/// effect.
+#[doc(hidden)]
+pub fn set_verbosity(verbosity: Verbosity) {
+    let _ = VERBOSITY.set(verbosity);
+}
+
+/// The logs are JSON objects (`--log-format json`).
+pub(crate) fn is_json() -> bool {
+    VERBOSITY
+        .get()
+        .map(|x| x.log_format == LogFormat::Json)
+        .unwrap_or(false)
+}
+
+/// Log an event: a JSON object with the fields of the payload (an object) for `--log-format
+/// json`, whatever the verbosity, or the message if there is one.
+pub(crate) fn event(
+    level: log::Level,
+    event: &str,
+    payload: serde_json::Value,
+    message: Option<std::fmt::Arguments>,
+) {
+    if !is_json() {
+        if let Some(message) = message {
+            log::log!(target: "wasmbl", level, "{}", message);
+        }
+        return;
+    }
+
+    let mut line = serde_json::Map::new();
+    line.insert("ts".into(), timestamp().into());
+    line.insert("level".into(), level.as_str().to_ascii_lowercase().into());
+    line.insert("target".into(), "wasmbl".into());
+    line.insert("event".into(), event.into());
+    if let Some(message) = message {
+        line.insert("message".into(), message.to_string().into());
+    }
+    if let serde_json::Value::Object(payload) = payload {
+        line.extend(payload);
+    }
+    write_line(&line.into());
+}
+
+/// Log the error that ends the command as an `error` event for `--log-format json` and exit, or
+/// return it to be printed by `main`. A binary ran outside of its workspace always exits with
+/// [`crate::OUTSIDE_WORKSPACE_EXIT_CODE`], failed tests and programs with their own code.
+#[doc(hidden)]
+pub fn exit_on_error(res: anyhow::Result<()>) -> anyhow::Result<()> {
+    let err = match res {
+        Ok(()) => return Ok(()),
+        Err(err) => err,
+    };
+    let code = match err.downcast_ref::<crate::Error>() {
+        Some(crate::Error::OutsideWorkspace { .. }) => crate::OUTSIDE_WORKSPACE_EXIT_CODE,
+        Some(crate::Error::TestsFailed { status } | crate::Error::RunFailed { status, .. }) => {
+            status.code().unwrap_or(1)
+        }
+        _ if is_json() => 1,
+        _ => return Err(err),
+    };
+    if is_json() {
+        event(
+            log::Level::Error,
+            "error",
+            serde_json::json!({}),
+            Some(format_args!("{:#}", err)),
+        );
+    } else {
+        eprintln!("Error: {:#}", err);
+    }
+    std::process::exit(code);
+}
+
+fn timestamp() -> String {
+    humantime::format_rfc3339_millis(SystemTime::now()).to_string()
+}
+
+/// Write a JSON object on its own line of the standard error.
+fn write_line(line: &serde_json::Value) {
+    // NOTE: the lock keeps the lines of the threads apart
+    let mut stderr = std::io::stderr().lock();
+    let _ = writeln!(stderr, "{}", line);
+}
+
+/// The middleware of the development server that logs the requests as `request` events.
+#[cfg(feature = "dev-server")]
+pub(crate) fn request_events(
+    req: tide::Request<()>,
+    next: tide::Next<'_, ()>,
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = tide::Result> + Send + '_>> {
+    let method = req.method().to_string();
+    let path = req.url().path().to_owned();
+    let start = std::time::Instant::now();
+    Box::pin(async move {
+        let res = next.run(req).await;
+        let status: u16 = res.status().into();
+        let level = if status >= 500 {
+            log::Level::Error
+        } else {
+            log::Level::Info
+        };
+        event(
+            level,
+            "request",
+            serde_json::json!({
+                "method": method,
+                "path": path,
+                "status": status,
+                "duration": start.elapsed().as_secs_f64(),
+            }),
+            None,
+        );
+        Ok(res)
+    })
+}
+
+/// Show the HTTP logs of the development server.
+#[cfg(feature = "dev-server")]
+pub(crate) fn enable_http_logs() {
+    HTTP_LOGS.store(true, Ordering::Relaxed);
+}
+
+/// Install the logger unless there is one already.
+pub(crate) fn init() {
+    let verbosity = VERBOSITY.get().copied().unwrap_or_default();
+    let inner = env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or(verbosity.filter()),
+    )
+    .build();
+    let max_level = inner.filter();
+    let logger = Logger {
+        inner,
+        filter_http: std::env::var_os("RUST_LOG").is_none(),
+        json: verbosity.log_format == LogFormat::Json,
+    };
+
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Hide the requests logged by `tide` unless the HTTP logs are enabled (or `RUST_LOG` is set).
+/// They are `request` events in JSON instead.
+struct Logger {
+    inner: env_logger::Logger,
+    filter_http: bool,
+    json: bool,
+}
+
+impl Logger {
+    fn is_hidden(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() >= log::Level::Info
+            && metadata.target().starts_with("tide")
+            && (self.json || self.filter_http && !HTTP_LOGS.load(Ordering::Relaxed))
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        !self.is_hidden(metadata) && self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.is_hidden(record.metadata()) {
+            return;
+        }
+        if !self.json {
+            self.inner.log(record);
+        } else if self.inner.matches(record) {
+            write_line(&serde_json::json!({
+                "ts": timestamp(),
+                "level": record.level().as_str().to_ascii_lowercase(),
+                "target": record.target(),
+                "event": "log",
+                "message": record.args().to_string(),
+            }));
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_verbosity() {
+        #[derive(StructOpt)]
+        struct Cli {
+            #[structopt(flatten)]
+            verbosity: Verbosity,
+        }
+
+        let filter = |args: &[&str]| Cli::from_iter_safe(args).map(|x| x.verbosity.filter());
+        assert_eq!(filter(&["app"]).unwrap(), "info");
+        assert_eq!(filter(&["app", "-v"]).unwrap(), "info,wasmbl=debug");
+        assert_eq!(filter(&["app", "-vv"]).unwrap(), "info,wasmbl=trace");
+        assert_eq!(filter(&["app", "--quiet"]).unwrap(), "warn");
+        assert!(filter(&["app", "-v", "-q"]).is_err());
+
+        let format = |args: &[&str]| Cli::from_iter_safe(args).map(|x| x.verbosity.log_format);
+        assert_eq!(format(&["app"]).unwrap(), LogFormat::Text);
+        assert_eq!(
+            format(&["app", "--log-format", "json"]).unwrap(),
+            LogFormat::Json
+        );
+        assert!(format(&["app", "--log-format", "xml"]).is_err());
+    }
+}
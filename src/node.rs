@@ -0,0 +1,119 @@
+//! The `run-node` command: build the frontend package with the JS bindings for Node.js
+//! ([`OutLayout::Node`]) and run them with `node`.
+
+use crate::{
+    build, hooks, BuildArgs, BuildProfile, BuildTarget, BuildTrigger, DefaultBuildArgs, Error,
+    OutLayout, Project,
+};
+use anyhow::{anyhow, Context};
+use std::ffi::OsString;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use structopt::StructOpt;
+
+/// Script run by `node` without `--entry`: loading the JS bindings runs the start function of the
+/// WASM, then the export `main` is called if there is one.
+const DEFAULT_ENTRY: &str = r#"const glue = require(process.env.WASMBL_JS_GLUE);
+if (typeof glue.main === "function") {
+    Promise.resolve()
+        .then(() => glue.main())
+        .catch((err) => {
+            console.error(err);
+            process.exitCode = 1;
+        });
+}
+"#;
+
+/// Run-node arguments.
+#[derive(StructOpt, Debug)]
+pub struct DefaultRunNodeArgs {
+    /// Build in release mode, with optimizations.
+    #[structopt(long)]
+    pub release: bool,
+
+    /// Script run by `node` instead of calling the export `main` of the JS bindings (their path
+    /// is in the environment variable `WASMBL_JS_GLUE`).
+    #[structopt(long, parse(from_os_str))]
+    pub entry: Option<PathBuf>,
+
+    /// Build arguments.
+    #[structopt(flatten)]
+    pub build_args: DefaultBuildArgs,
+
+    /// Arguments given to the script (after `--`).
+    #[structopt(last = true, parse(from_os_str))]
+    pub args: Vec<OsString>,
+}
+
+impl DefaultRunNodeArgs {
+    /// Run the `run-node` command.
+    ///
+    /// The frontend package is always built with the layout [`OutLayout::Node`]. The standard
+    /// input and outputs are given to `node`, [`Error::RunFailed`] is returned if it fails. The
+    /// script gets the environment variables of the processes started by wasmbl
+    /// (see [`crate::BuildArgs::bundle_command`]), it runs in the current directory.
+    pub fn run(mut self) -> Result<(), Error> {
+        let hooks = hooks()?;
+        if self.build_args.target == Some(BuildTarget::Wasi) {
+            return Err(anyhow!(
+                "the `run-node` command only supports the target `wasm32-unknown-unknown`"
+            )
+            .into());
+        }
+        if self
+            .build_args
+            .out_layout
+            .map(|x| x != OutLayout::Node)
+            .unwrap_or(false)
+        {
+            return Err(anyhow!("the `run-node` command only supports the layout `node`").into());
+        }
+        self.build_args.out_layout = Some(OutLayout::Node);
+        Project::select_frontend(&self.build_args)?;
+
+        let profile = if self.release {
+            BuildProfile::Release
+        } else {
+            BuildProfile::Dev
+        };
+        let output = build(profile, &self.build_args, hooks, false, BuildTrigger::Build)?;
+
+        // NOTE: the JS bindings might have been renamed by the `asset_filter` hook
+        let js_name = crate::slash_path(
+            &OutLayout::Node
+                .dir()
+                .join(format!("{}.js", self.build_args.out_name())),
+        );
+        let js_path = crate::written_asset(&js_name).unwrap_or(output.js_path);
+        let js_path = std::env::current_dir()
+            .context("could not get the current directory")?
+            .join(js_path);
+
+        let mut command = Command::new("node");
+        match self.entry.as_ref() {
+            Some(entry) => command.arg(entry),
+            // NOTE: everything after `--` is given to the script, even the options
+            None => command.args(["-e", DEFAULT_ENTRY, "--"]),
+        };
+        crate::process_env(&self.build_args, output.profile, &mut command);
+        command.args(&self.args).env("WASMBL_JS_GLUE", &js_path);
+
+        log::info!("Running `{}` with node", js_path.display());
+        log::debug!("Running {:?}", command);
+        match command.status() {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(Error::RunFailed {
+                program: "node".to_string(),
+                status,
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Err(anyhow!(
+                "could not find `node`: install Node.js to run the `run-node` command"
+            )
+            .into()),
+            Err(err) => Err(anyhow::Error::new(err)
+                .context("could not start `node`")
+                .into()),
+        }
+    }
+}
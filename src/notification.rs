@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+/// Minimum delay between two notifications with the same outcome.
+const RATE_LIMIT: Duration = Duration::from_secs(10);
+
+/// Sends desktop notifications at the end of the rebuilds triggered by the watcher.
+///
+/// A notification is always sent when the outcome of the build changes (success to failure or
+/// failure to success), otherwise notifications are rate limited.
+#[derive(Debug, Default)]
+pub(crate) struct Notifier {
+    last: Option<(Instant, bool)>,
+}
+
+impl Notifier {
+    /// Notify about the outcome of a rebuild. `error` is the first line of the error message if
+    /// the build failed.
+    pub(crate) fn notify(&mut self, error: Option<&str>) {
+        let success = error.is_none();
+        if let Some((at, last_success)) = self.last {
+            if last_success == success && at.elapsed() < RATE_LIMIT {
+                return;
+            }
+        }
+        self.last = Some((Instant::now(), success));
+
+        let (summary, body) = match error {
+            None => ("Rebuild succeeded", "Waiting for changes"),
+            Some(error) => ("Rebuild failed", error),
+        };
+        send(summary, body);
+    }
+}
+
+#[cfg(feature = "desktop-notifications")]
+fn send(summary: &str, body: &str) {
+    // NOTE: there is no notification service over SSH or on CI, the error is ignored on purpose.
+    if let Err(err) = notify_rust::Notification::new()
+        .appname("wasmbl")
+        .summary(summary)
+        .body(body)
+        .show()
+    {
+        log::debug!("Could not send desktop notification: {}", err);
+    }
+}
+
+#[cfg(not(feature = "desktop-notifications"))]
+fn send(_summary: &str, _body: &str) {
+    log::warn!("Desktop notifications require the feature `desktop-notifications` of wasmbl");
+}
@@ -0,0 +1,257 @@
+//! The pages of the multi-page apps: HTML templates rendered to the build directory by the default
+//! `post_build` hook, next to the files of the build they load.
+//!
+//! The templates are rendered with Tera and get the URLs of the JS bindings and of the WASM after
+//! the renames of the [`crate::Hooks::asset_filter`] hook. The pages are given to this hook like
+//! the other files of the build.
+
+use crate::{Asset, BuildArgs, BuildOutput, Hooks};
+use anyhow::{Context, Result};
+use cargo_metadata::Package;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory of the pages of the frontend package if its metadata has no key `pages`.
+const DEFAULT_DIR: &str = "pages";
+
+/// A page of a multi-page app, see [`crate::BuildArgs::pages`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Page {
+    /// The HTML template of the page.
+    pub template: PathBuf,
+    /// Where the page is written, relative to the build directory: `admin/index.html`.
+    pub path: PathBuf,
+}
+
+impl Page {
+    /// The page rendered from this template to this path of the build directory.
+    pub fn new(template: impl Into<PathBuf>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            template: template.into(),
+            path: path.into(),
+        }
+    }
+
+    /// The pages of the key `pages` of the `[package.metadata.wasmbl]` table of a package: a
+    /// directory or a table of the routes and their templates, relative to `root` (the package or
+    /// the frontend directory). The directory `pages` of `root` is used if there is no key.
+    pub(crate) fn from_metadata(package: &Package, root: &Path) -> Vec<Self> {
+        let pages = package.metadata.get("wasmbl").and_then(|x| x.get("pages"));
+
+        if let Some(table) = pages.and_then(|x| x.as_object()) {
+            return table
+                .iter()
+                .filter_map(|(route, template)| {
+                    let template = template.as_str()?;
+                    match route_path(route) {
+                        Some(path) => Some(Self::new(root.join(template), path)),
+                        None => {
+                            crate::reporter::reporter().warning(&format!(
+                                "the page `{}` is not built: its route `{}` is not a valid URL \
+                                path",
+                                template, route
+                            ));
+                            None
+                        }
+                    }
+                })
+                .collect();
+        }
+
+        match dir(package, root) {
+            Some(dir) => from_dir(&dir),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// The directory of the pages of a package, relative to `root`, if they are given by a directory
+/// that exists.
+pub(crate) fn dir(package: &Package, root: &Path) -> Option<PathBuf> {
+    let dir = match package.metadata.get("wasmbl").and_then(|x| x.get("pages")) {
+        Some(pages) => root.join(pages.as_str()?),
+        None => root.join(DEFAULT_DIR),
+    };
+
+    dir.is_dir().then_some(dir)
+}
+
+/// Every `.html` file of the directory, written at the same path in the build directory.
+fn from_dir(dir: &Path) -> Vec<Page> {
+    let files = match crate::list_files(dir) {
+        Ok(files) => files,
+        Err(err) => {
+            crate::reporter::reporter().warning(&format!(
+                "could not list the pages of `{}`: {}",
+                dir.display(),
+                err
+            ));
+            return Vec::new();
+        }
+    };
+
+    files
+        .into_iter()
+        .filter(|x| x.extension().map(|x| x == "html").unwrap_or(false))
+        .filter_map(|template| {
+            let path = template.strip_prefix(dir).ok()?.to_owned();
+            Some(Page::new(template, path))
+        })
+        .collect()
+}
+
+/// The path of the page of a route, relative to the build directory: the routes that end with a
+/// `/` or have no extension are directories, their page is their `index.html`.
+fn route_path(route: &str) -> Option<PathBuf> {
+    let path = crate::path_from_url(route.trim_matches('/'))?;
+    if route.ends_with('/') || path.extension().is_none() {
+        Some(path.join("index.html"))
+    } else {
+        Some(path)
+    }
+}
+
+/// Render the pages in the build directory.
+///
+/// The templates get the variables `base_url`, `js` and `wasm` (the URLs of the JS bindings and
+/// of the WASM), `url` (the URL of the page) and `env` (the public environment variables).
+pub(crate) fn build(
+    args: &dyn BuildArgs,
+    pages: &[Page],
+    output: &BuildOutput,
+    hooks: &Hooks,
+) -> Result<()> {
+    if pages.is_empty() {
+        return Ok(());
+    }
+    let base_url = crate::base_url(args);
+    let base_url = base_url.as_str();
+    let build_path = args.build_path();
+    let build_path = build_path.as_path();
+    let prefix = args.public_env_prefix();
+    let env = crate::env_file::public_env(&args.env_files(), &prefix)?;
+
+    // NOTE: the JS bindings and the WASM might have been renamed by the `asset_filter` hook
+    let url = |path: &Path| {
+        let relative = path.strip_prefix(build_path).unwrap_or(path);
+        let path = crate::written_asset(&crate::slash_path(relative))
+            .and_then(|x| x.strip_prefix(build_path).ok().map(Path::to_path_buf))
+            .unwrap_or_else(|| relative.to_owned());
+        format!("{}{}", base_url, crate::url_path(&path).unwrap_or_default())
+    };
+    let mut context = tera::Context::new();
+    context.insert("base_url", base_url);
+    context.insert("js", &url(&output.js_path));
+    context.insert("wasm", &url(&output.wasm_path));
+    context.insert("env", &env);
+
+    for page in pages {
+        let template = fs::read_to_string(&page.template)
+            .with_context(|| format!("could not read the page `{}`", page.template.display()))?;
+        crate::env_file::check_template(
+            &template,
+            &page.template.display().to_string(),
+            &env,
+            &prefix,
+        )?;
+        let page_url = format!(
+            "{}{}",
+            base_url,
+            crate::url_path(&page.path)
+                .unwrap_or_default()
+                .trim_end_matches("index.html")
+        );
+        context.insert("url", &page_url);
+        let html = tera::Tera::one_off(&template, &context, false)
+            .with_context(|| format!("could not render the page `{}`", page.template.display()))?;
+
+        let name = format!("pages/{}", crate::slash_path(&page.path));
+        crate::write_asset(
+            build_path,
+            hooks,
+            Asset::new(&page.path, name, html.into_bytes()),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The index document (`index.html`) served for a path that is not a file: the one of the closest
+/// directory of the path that has one, so `/admin/settings` falls back to `admin/index.html` if it
+/// exists and to the `index.html` of the serve directory otherwise.
+#[cfg(feature = "dev-server")]
+pub(crate) fn fallback_index(serve_path: &Path, path: Option<&Path>, index: &str) -> PathBuf {
+    path.into_iter()
+        .flat_map(Path::ancestors)
+        .map(|x| serve_path.join(x).join(index))
+        .find(|x| x.is_file())
+        .unwrap_or_else(|| serve_path.join(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_of_the_routes() {
+        for (route, path) in [
+            ("/", Path::new("index.html").to_owned()),
+            ("/admin/", Path::new("admin").join("index.html")),
+            ("/admin", Path::new("admin").join("index.html")),
+            ("/docs/about.html", Path::new("docs").join("about.html")),
+            ("/v1.2/", Path::new("v1.2").join("index.html")),
+        ] {
+            assert_eq!(route_path(route), Some(path), "{}", route);
+        }
+        assert_eq!(route_path("/../admin/"), None);
+    }
+
+    #[test]
+    fn pages_of_a_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("admin")).unwrap();
+        fs::write(dir.path().join("index.html"), "").unwrap();
+        fs::write(dir.path().join("admin").join("index.html"), "").unwrap();
+        fs::write(dir.path().join("admin").join("style.css"), "").unwrap();
+
+        assert_eq!(
+            from_dir(dir.path()),
+            [
+                Page::new(
+                    dir.path().join("admin").join("index.html"),
+                    Path::new("admin").join("index.html")
+                ),
+                Page::new(dir.path().join("index.html"), "index.html"),
+            ]
+        );
+    }
+
+    #[cfg(feature = "dev-server")]
+    #[test]
+    fn fallback_to_the_closest_index() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("admin").join("users")).unwrap();
+        fs::write(dir.path().join("index.html"), "").unwrap();
+        fs::write(dir.path().join("admin").join("index.html"), "").unwrap();
+
+        let admin = dir.path().join("admin").join("index.html");
+        for (path, index) in [
+            ("admin/settings", &admin),
+            ("admin", &admin),
+            ("admin/users/1", &admin),
+            ("about", &dir.path().join("index.html")),
+        ] {
+            assert_eq!(
+                &fallback_index(dir.path(), Some(Path::new(path)), "index.html"),
+                index,
+                "{}",
+                path
+            );
+        }
+        assert_eq!(
+            fallback_index(dir.path(), None, "index.html"),
+            dir.path().join("index.html")
+        );
+    }
+}
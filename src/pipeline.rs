@@ -0,0 +1,221 @@
+//! The orchestration of the pipelines of `serve`.
+//!
+//! A single save can trigger the rebuild of the frontend, the restart of the backend and the
+//! commands of the hooks. The pipelines that write to the build directory are run by the
+//! [`Orchestrator`] one at a time, so the server never sees the files of two builds mixed
+//! together. The triggers that arrive while a pipeline runs are coalesced into one pending run.
+//!
+//! The stages that don't touch the build directory are declared with [`Stage::is_parallel`]:
+//! they run alongside the others without waiting.
+
+use std::sync::{Arc, Condvar, Mutex};
+
+/// A pipeline started by a change of the files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stage {
+    /// The build of the frontend: cargo, the bindings, the hooks, the assets and the bundle. It
+    /// writes to the build directory.
+    Frontend,
+    /// The restart of the backend: cargo only writes to the target directory.
+    #[cfg_attr(feature = "dev-server", allow(dead_code))]
+    Backend,
+}
+
+impl Stage {
+    /// The stage doesn't write to the build directory: it can run alongside the other pipelines.
+    pub(crate) fn is_parallel(self) -> bool {
+        match self {
+            Stage::Frontend => false,
+            Stage::Backend => true,
+        }
+    }
+}
+
+/// The state of the queue of the pipelines, shown by the status line and the control API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum QueueState {
+    /// Nothing is running, the server waits for changes.
+    Idle,
+    /// A pipeline is running and nothing else is queued.
+    Building,
+    /// Another run is queued: it starts once the running pipeline is finished, or once the
+    /// events of the watcher are coalesced.
+    Pending,
+}
+
+impl QueueState {
+    #[cfg(feature = "dev-server")]
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            QueueState::Idle => "idle",
+            QueueState::Building => "building",
+            QueueState::Pending => "pending",
+        }
+    }
+
+    /// The end of the status line of the rebuilds.
+    pub(crate) fn describe(self) -> &'static str {
+        match self {
+            QueueState::Idle => "waiting for changes",
+            QueueState::Building => "building",
+            QueueState::Pending => "rebuild pending",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    building: bool,
+    /// A change arrived that was not handled yet.
+    pending: bool,
+    /// The pipelines waiting for the running one.
+    waiting: usize,
+}
+
+/// Runs the pipelines that write to the build directory one at a time.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Orchestrator {
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+}
+
+impl Orchestrator {
+    /// Record a change: the next run of the watcher handles it with the others that arrive in
+    /// the meantime.
+    pub(crate) fn trigger(&self) {
+        let mut queue = self.queue.0.lock().unwrap();
+        if queue.building && !queue.pending {
+            log::debug!("Queued a rebuild after the running one");
+        }
+        queue.pending = true;
+    }
+
+    /// The watcher handles the changes recorded so far, or found that they can be ignored.
+    pub(crate) fn dequeue(&self) {
+        self.queue.0.lock().unwrap().pending = false;
+    }
+
+    /// Run a pipeline once the running one is finished, unless the stage can run in parallel.
+    pub(crate) fn run<T>(&self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        if stage.is_parallel() {
+            log::debug!(
+                "Running the stage {:?} alongside the other pipelines",
+                stage
+            );
+            return f();
+        }
+
+        let (lock, cvar) = &*self.queue;
+        let mut queue = lock.lock().unwrap();
+        if queue.building {
+            log::debug!("Waiting for the running pipeline before {:?}", stage);
+            queue.waiting += 1;
+            queue = cvar.wait_while(queue, |x| x.building).unwrap();
+            queue.waiting -= 1;
+        }
+        queue.building = true;
+        drop(queue);
+
+        // NOTE: the pipeline runs the hooks, the next one must start even after a panic
+        struct Finished<'a>(&'a (Mutex<Queue>, Condvar));
+
+        impl Drop for Finished<'_> {
+            fn drop(&mut self) {
+                let (lock, cvar) = self.0;
+                lock.lock().unwrap_or_else(|x| x.into_inner()).building = false;
+                cvar.notify_one();
+            }
+        }
+
+        let _finished = Finished(&self.queue);
+        f()
+    }
+
+    pub(crate) fn state(&self) -> QueueState {
+        let queue = self.queue.0.lock().unwrap();
+        if queue.pending || queue.waiting > 0 {
+            QueueState::Pending
+        } else if queue.building {
+            QueueState::Building
+        } else {
+            QueueState::Idle
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn one_pipeline_at_a_time() {
+        let orchestrator = Orchestrator::default();
+        let running = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let orchestrator = orchestrator.clone();
+                let running = running.clone();
+                thread::spawn(move || {
+                    orchestrator.run(Stage::Frontend, || {
+                        assert_eq!(running.fetch_add(1, Ordering::SeqCst), 0);
+                        thread::sleep(Duration::from_millis(20));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    })
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(orchestrator.state(), QueueState::Idle);
+    }
+
+    #[test]
+    fn queue_state() {
+        let orchestrator = Orchestrator::default();
+        assert_eq!(orchestrator.state(), QueueState::Idle);
+        orchestrator.trigger();
+        assert_eq!(orchestrator.state(), QueueState::Pending);
+        orchestrator.dequeue();
+
+        let (started, started_rx) = mpsc::channel();
+        let (finish, finish_rx) = mpsc::channel::<()>();
+        let running = {
+            let orchestrator = orchestrator.clone();
+            thread::spawn(move || {
+                orchestrator.run(Stage::Frontend, || {
+                    started.send(()).unwrap();
+                    finish_rx.recv().unwrap();
+                })
+            })
+        };
+        started_rx.recv().unwrap();
+        assert_eq!(orchestrator.state(), QueueState::Building);
+
+        // NOTE: the backend doesn't wait for the frontend
+        assert_eq!(orchestrator.run(Stage::Backend, || 42), 42);
+
+        orchestrator.trigger();
+        orchestrator.trigger();
+        assert_eq!(orchestrator.state(), QueueState::Pending);
+        finish.send(()).unwrap();
+        running.join().unwrap();
+        assert_eq!(orchestrator.state(), QueueState::Pending);
+        orchestrator.dequeue();
+        assert_eq!(orchestrator.state(), QueueState::Idle);
+    }
+
+    #[test]
+    fn next_pipeline_after_a_panic() {
+        let orchestrator = Orchestrator::default();
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            orchestrator.run(Stage::Frontend, || panic!("broken hook"))
+        }));
+        assert!(res.is_err());
+        assert_eq!(orchestrator.state(), QueueState::Idle);
+        assert_eq!(orchestrator.run(Stage::Frontend, || 1), 1);
+    }
+}
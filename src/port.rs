@@ -0,0 +1,174 @@
+//! The errors of the address of the development server.
+
+use crate::Error;
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpListener};
+
+/// Check that the development server can listen on the address before the first build.
+pub(crate) fn check_addr(addr: SocketAddr) -> Result<(), Error> {
+    TcpListener::bind(addr)
+        .map(drop)
+        .map_err(|err| bind_error(addr, err))
+}
+
+/// The error of the development server that could not listen on the address.
+pub(crate) fn bind_error(addr: SocketAddr, err: io::Error) -> Error {
+    match err.kind() {
+        io::ErrorKind::AddrInUse => Error::AddrInUse {
+            addr: addr.to_string(),
+            holder: holder(addr.port()),
+        },
+        _ => Error::AddrNotAvailable {
+            addr: addr.to_string(),
+            source: err,
+        },
+    }
+}
+
+/// The name and the PID of the process that listens on the port, if it can be found.
+#[cfg(target_os = "linux")]
+fn holder(port: u16) -> Option<String> {
+    use std::fs;
+
+    // NOTE: the sockets that listen on the port, then the process that has one of them open
+    let inodes: Vec<String> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|x| fs::read_to_string(x).ok())
+        .flat_map(|table| {
+            table
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    let local_port = fields.get(1)?.rsplit(':').next()?;
+                    let inode = fields.get(9)?;
+                    let listening = *fields.get(3)? == "0A";
+                    (listening && u16::from_str_radix(local_port, 16).ok()? == port)
+                        .then(|| format!("socket:[{}]", inode))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if inodes.is_empty() {
+        return None;
+    }
+
+    fs::read_dir("/proc").ok()?.flatten().find_map(|entry| {
+        let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+        let holds_socket = fs::read_dir(entry.path().join("fd"))
+            .ok()?
+            .flatten()
+            .filter_map(|x| fs::read_link(x.path()).ok())
+            .any(|x| inodes.iter().any(|inode| x.as_os_str() == inode.as_str()));
+        if !holds_socket {
+            return None;
+        }
+
+        let name = fs::read_to_string(entry.path().join("comm")).ok()?;
+        Some(format!("`{}` (PID {})", name.trim(), pid))
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn holder(_port: u16) -> Option<String> {
+    None
+}
+
+/// The URL of the development server on an address, with the IPv6 addresses in brackets (and the
+/// `%` of their zone escaped).
+pub(crate) fn url(addr: SocketAddr) -> String {
+    match addr {
+        SocketAddr::V6(addr) if addr.scope_id() != 0 => format!(
+            "http://[{}%25{}]:{}",
+            addr.ip(),
+            addr.scope_id(),
+            addr.port()
+        ),
+        addr => format!("http://{}", addr),
+    }
+}
+
+/// The address of the same port that a browser of this machine can connect to: the loopback
+/// address instead of the unspecified address.
+pub(crate) fn connectable(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new([127, 0, 0, 1].into(), addr.port())
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(std::net::Ipv6Addr::LOCALHOST.into(), addr.port())
+        }
+        _ => addr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addr_in_use() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let err = check_addr(addr).unwrap_err();
+        assert!(matches!(err, Error::AddrInUse { .. }), "{:?}", err);
+        #[cfg(target_os = "linux")]
+        {
+            let message = err.to_string();
+            assert!(message.contains(&addr.to_string()), "{}", message);
+            assert!(
+                message.contains(&format!("(PID {})", std::process::id())),
+                "{}",
+                message
+            );
+        }
+
+        drop(listener);
+        check_addr(addr).unwrap();
+    }
+
+    #[test]
+    fn privileged_port() {
+        let err = bind_error(
+            "127.0.0.1:80".parse().unwrap(),
+            io::Error::from(io::ErrorKind::PermissionDenied),
+        );
+        assert!(matches!(err, Error::AddrNotAvailable { .. }));
+        assert_eq!(
+            err.to_string(),
+            "permission denied to listen on `127.0.0.1:80`: the ports below 1024 are privileged, \
+            choose a higher port with `--port`"
+        );
+
+        let err = bind_error(
+            "192.0.2.1:3000".parse().unwrap(),
+            io::Error::from(io::ErrorKind::AddrNotAvailable),
+        );
+        assert_eq!(err.to_string(), "could not listen on `192.0.2.1:3000`");
+    }
+
+    #[test]
+    fn urls_of_the_addresses() {
+        for (addr, expected) in [
+            ("127.0.0.1:3000", "http://127.0.0.1:3000"),
+            ("[::1]:3000", "http://[::1]:3000"),
+            ("[fe80::1%2]:3000", "http://[fe80::1%252]:3000"),
+        ] {
+            assert_eq!(url(addr.parse().unwrap()), expected);
+        }
+
+        assert_eq!(
+            connectable("0.0.0.0:3000".parse().unwrap()),
+            "127.0.0.1:3000".parse().unwrap()
+        );
+        assert_eq!(
+            connectable("[::]:3000".parse().unwrap()),
+            "[::1]:3000".parse().unwrap()
+        );
+        assert_eq!(
+            connectable("[::1]:3000".parse().unwrap()),
+            "[::1]:3000".parse().unwrap()
+        );
+    }
+}
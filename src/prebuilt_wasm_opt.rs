@@ -2,27 +2,46 @@ use anyhow::{Context, Result};
 use binary_install::Cache;
 use std::path::{Path, PathBuf};
 
+/// Version of binaryen that is downloaded.
+pub(crate) const BINARYEN_VERSION: &str = "97";
+
+#[cfg(target_os = "macos")]
+const BINARIES: &[&str] = &["wasm-opt", "libbinaryen"];
+#[cfg(not(target_os = "macos"))]
+const BINARIES: &[&str] = &["wasm-opt"];
+
 pub(crate) fn install_wasm_opt(target_path: impl AsRef<Path>) -> Result<PathBuf> {
-    let cache = Cache::at(target_path.as_ref());
+    if let Some(wasm_opt) = installed_wasm_opt(target_path.as_ref())? {
+        return Ok(wasm_opt);
+    }
+
+    log::info!("Downloading wasm-opt");
+    Ok(download(target_path.as_ref(), true)?.expect("install is permitted; qed"))
+}
+
+/// The path to `wasm-opt` if it has already been downloaded.
+pub(crate) fn installed_wasm_opt(target_path: impl AsRef<Path>) -> Result<Option<PathBuf>> {
+    download(target_path.as_ref(), false)
+}
+
+fn download(target_path: &Path, install_permitted: bool) -> Result<Option<PathBuf>> {
+    let cache = Cache::at(target_path);
 
     let url = format!(
         "https://github.com/WebAssembly/binaryen/releases/download/version_{version}/binaryen-version_{version}-{arch}-{os}.tar.gz",
-        version = "97",
+        version = BINARYEN_VERSION,
         arch = platforms::TARGET_ARCH,
         os = platforms::TARGET_OS,
     );
 
-    #[cfg(target_os = "macos")]
-    let binaries = &["wasm-opt", "libbinaryen"];
-    #[cfg(not(target_os = "macos"))]
-    let binaries = &["wasm-opt"];
-
-    eprintln!("Downloading wasm-opt...");
-    Ok(cache
-        .download(true, "wasm-opt", binaries, &url)
+    cache
+        .download(install_permitted, "wasm-opt", BINARIES, &url)
         .map_err(|err| err.compat())
         .with_context(|| format!("could not download binaryen: {}", url))?
-        .expect("install is permitted; qed")
-        .binary("wasm-opt")
-        .map_err(|err| err.compat())?)
+        .map(|download| {
+            download
+                .binary("wasm-opt")
+                .map_err(|err| err.compat().into())
+        })
+        .transpose()
 }
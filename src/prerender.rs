@@ -0,0 +1,349 @@
+//! The prerendering of the routes of the release builds (`--prerender`): the build directory is
+//! served on a local port, every route is loaded by a headless Chrome and its DOM is written to
+//! `<route>/index.html` in the build directory once the page is ready.
+//!
+//! The page sends its DOM to the server itself: a script injected in `index.html` waits for the
+//! ready signal and posts the serialized DOM, so Chrome only needs to load the page.
+
+use crate::{Asset, BuildArgs, Hooks};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Path of the endpoint the pages post their DOM to.
+const DOM_PATH: &str = "/__wasmbl/prerendered";
+
+/// How long a page can take to be ready.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Environment variable with the path of Chrome, it takes precedence over the lookup in `PATH`.
+const CHROME_VAR: &str = "CHROME";
+
+/// Names of the executables of Chrome looked up in `PATH`.
+const CHROME_NAMES: &[&str] = &[
+    "google-chrome",
+    "google-chrome-stable",
+    "chromium",
+    "chromium-browser",
+    "chrome",
+];
+
+/// Render the routes with a headless Chrome and write them in the build directory.
+///
+/// The prerendering is skipped with a warning if Chrome is not found. The routes that fail are
+/// reported one by one and the others are written anyway.
+pub(crate) fn prerender(args: &dyn BuildArgs, hooks: &Hooks, routes: &[String]) -> Result<()> {
+    let chrome = match find_chrome() {
+        Some(chrome) => chrome,
+        None => {
+            log::warn!(
+                "Skipping the prerendering: Chrome was not found in `PATH` (set `{}` to its path)",
+                CHROME_VAR
+            );
+            return Ok(());
+        }
+    };
+    let ready = args.prerender_ready();
+    let signal = match ready.as_deref() {
+        Some(selector) => format!("the selector `{}`", selector),
+        None => "`window.__wasmbl_prerendered`".to_string(),
+    };
+
+    let build_path = args.build_path();
+    let index_path = build_path.join("index.html");
+    let index = fs::read_to_string(&index_path)
+        .with_context(|| format!("could not read `{}`", index_path.display()))?;
+    let base_url = crate::base_url(args);
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")
+        .context("could not start the server of the prerendering")?;
+    let addr = listener.local_addr()?;
+    let (tx, rx) = mpsc::channel();
+    let app = server(
+        &build_path,
+        &base_url,
+        inject_script(&index, ready.as_deref()),
+        tx,
+    );
+    let server = async_std::task::spawn(app.listen(listener));
+
+    let user_data_dir = tempfile::tempdir().context("could not create the profile of Chrome")?;
+    let mut pages = Vec::new();
+    let mut failed = Vec::new();
+    for route in routes {
+        let res = route_path(route).and_then(|path| {
+            let url = format!("http://{}{}{}", addr, base_url, route.trim_matches('/'));
+            log::debug!("Prerendering `{}` ({})", route, url);
+            // NOTE: the DOM of a page that was not ready in time could arrive now
+            while rx.try_recv().is_ok() {}
+            Ok((
+                path,
+                render(&chrome, user_data_dir.path(), &url, &rx, &signal)?,
+            ))
+        });
+        match res {
+            Ok(page) => pages.push((route, page)),
+            Err(err) => {
+                log::error!("Could not prerender `{}`: {:#}", route, err);
+                failed.push(route.as_str());
+            }
+        }
+    }
+    async_std::task::block_on(server.cancel());
+
+    // NOTE: written at the end so all the routes are rendered from the original `index.html`
+    for (route, (path, html)) in pages {
+        let name = format!("prerender/{}", crate::slash_path(&path));
+        if let Some(path) = crate::write_asset(
+            &build_path,
+            hooks,
+            Asset::new(path, name, html.into_bytes()),
+        )? {
+            log::info!("Prerendered `{}` to `{}`", route, path.display());
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!(
+            "could not prerender {} of the {} routes: `{}`",
+            failed.len(),
+            routes.len(),
+            failed.join("`, `")
+        );
+    }
+
+    Ok(())
+}
+
+/// The path of the prerendered page of a route, relative to the build directory.
+fn route_path(route: &str) -> Result<PathBuf> {
+    let path = crate::path_from_url(route.split(['?', '#']).next().unwrap_or_default())
+        .with_context(|| format!("invalid route `{}`", route))?;
+
+    Ok(path.join("index.html"))
+}
+
+/// Chrome given by [`CHROME_VAR`] or the first one found in `PATH`, or at its default location on
+/// macOS and Windows.
+fn find_chrome() -> Option<PathBuf> {
+    if let Some(chrome) = std::env::var_os(CHROME_VAR) {
+        return Some(chrome.into());
+    }
+
+    let in_path = std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .flat_map(|dir| {
+                CHROME_NAMES.iter().map(move |name| {
+                    dir.join(name)
+                        .with_extension(std::env::consts::EXE_EXTENSION)
+                })
+            })
+            .find(|x| x.is_file())
+    });
+    let default = if cfg!(target_os = "macos") {
+        vec![PathBuf::from(
+            "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+        )]
+    } else if cfg!(windows) {
+        ["ProgramFiles", "ProgramFiles(x86)", "LocalAppData"]
+            .iter()
+            .filter_map(std::env::var_os)
+            .map(|x| {
+                PathBuf::from(x)
+                    .join("Google")
+                    .join("Chrome")
+                    .join("Application")
+                    .join("chrome.exe")
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    in_path.or_else(|| default.into_iter().find(|x| x.is_file()))
+}
+
+/// The server of the build directory, at the base URL of the build. The paths that are not files
+/// get the original `index.html`, with the script that posts the DOM.
+fn server(
+    build_path: &Path,
+    base_url: &str,
+    index: String,
+    tx: mpsc::Sender<String>,
+) -> tide::Server<()> {
+    use tide::{http::mime, Body, Request, Response};
+
+    let mut app = tide::new();
+    app.with(crate::content_type_middleware);
+
+    let tx = Arc::new(Mutex::new(tx));
+    app.at(DOM_PATH).post(move |mut req: Request<()>| {
+        let tx = tx.clone();
+        async move {
+            let html = req.body_string().await?;
+            let _ = tx.lock().unwrap().send(html);
+            Ok(Response::new(204))
+        }
+    });
+
+    let index = Arc::new(index);
+    let index_response = move |index: &str| {
+        let mut res = Response::new(200);
+        res.set_body(index);
+        res.set_content_type(mime::HTML);
+        res
+    };
+    {
+        let index = index.clone();
+        app.at(base_url).get(move |_| {
+            let index = index.clone();
+            async move { Ok(index_response(&index)) }
+        });
+    }
+    let build_path = build_path.to_owned();
+    app.at(&format!("{}*path", base_url))
+        .get(move |req: Request<()>| {
+            let index = index.clone();
+            // NOTE: the pages prerendered by a previous build are not served
+            let path = crate::path_from_url(req.param("path").unwrap())
+                .filter(|x| x.file_name().map(|x| x != "index.html").unwrap_or(false))
+                .map(|x| build_path.join(x));
+            async move {
+                match path {
+                    Some(path) if path.is_file() => {
+                        Ok(Response::from(Body::from_file(path).await?))
+                    }
+                    _ => Ok(index_response(&index)),
+                }
+            }
+        });
+
+    app
+}
+
+/// Insert the script that posts the DOM once the page is ready in `index.html`.
+///
+/// The page is ready when the selector matches an element, or when it sets
+/// `window.__wasmbl_prerendered` if there is no selector. The script removes itself before.
+fn inject_script(index: &str, ready: Option<&str>) -> String {
+    let ready = serde_json::to_string(&ready)
+        .expect("a string; qed")
+        .replace("</", "<\\/");
+    let script = format!(
+        r#"<script id="__wasmbl_prerender">(function(){{var r={};function p(){{if(!(r?document.querySelector(r):window.__wasmbl_prerendered)){{setTimeout(p,50);return;}}document.getElementById("__wasmbl_prerender").remove();var d=document.doctype?new XMLSerializer().serializeToString(document.doctype)+"\n":"";fetch("{}",{{method:"POST",body:d+document.documentElement.outerHTML}});}}p();}})();</script>"#,
+        ready, DOM_PATH
+    );
+
+    crate::live_reload::inject_script(index, &script)
+}
+
+/// Load the page with a headless Chrome and wait for its DOM.
+fn render(
+    chrome: &Path,
+    user_data_dir: &Path,
+    url: &str,
+    rx: &mpsc::Receiver<String>,
+    signal: &str,
+) -> Result<String> {
+    let mut command = Command::new(chrome);
+    command
+        .args([
+            "--headless",
+            "--disable-gpu",
+            "--no-first-run",
+            "--no-default-browser-check",
+            "--disable-extensions",
+            // NOTE: keeps Chrome running once the page is loaded
+            "--remote-debugging-port=0",
+        ])
+        .arg(format!("--user-data-dir={}", user_data_dir.display()))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // NOTE: Chrome refuses to run as root with its sandbox
+    #[cfg(unix)]
+    if unsafe { libc::geteuid() } == 0 {
+        command.arg("--no-sandbox");
+    }
+    command.arg(url);
+
+    log::debug!("Running {:?}", command);
+    let mut child = crate::process::GroupChild::spawn(&mut command)
+        .with_context(|| format!("could not start `{}`", chrome.display()))?;
+    let start = Instant::now();
+    loop {
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(html) => return Ok(html),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => bail!("the server stopped"),
+        }
+        if let Some(status) = child.try_wait()? {
+            bail!("Chrome exited before the page was ready ({})", status);
+        }
+        if start.elapsed() > TIMEOUT {
+            bail!(
+                "the page was not ready after {}s (waiting for {})",
+                TIMEOUT.as_secs(),
+                signal
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prerendered_paths() {
+        assert_eq!(route_path("/").unwrap(), Path::new("index.html"));
+        assert_eq!(
+            route_path("/blog/first%20post/?page=2").unwrap(),
+            Path::new("blog").join("first post").join("index.html")
+        );
+        assert_eq!(
+            route_path("about").unwrap(),
+            Path::new("about").join("index.html")
+        );
+        assert!(route_path("/../etc").is_err());
+    }
+
+    #[test]
+    fn serve_the_original_index() {
+        use tide::http::{Method, Request, Response, Url};
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("about")).unwrap();
+        fs::write(dir.path().join("app.js"), "js").unwrap();
+        fs::write(dir.path().join("about").join("index.html"), "old").unwrap();
+        let (tx, rx) = mpsc::channel();
+        let index = inject_script("<body></body>", Some("main </script>"));
+        assert!(index.contains(r#"var r="main <\/script>";"#), "{}", index);
+        let app = server(dir.path(), "/app/", index.clone(), tx);
+
+        let request = |method, path: &str, body: &str| {
+            let url = Url::parse(&format!("http://127.0.0.1{}", path)).unwrap();
+            let mut req = Request::new(method, url);
+            req.set_body(body);
+            let res: Response = async_std::task::block_on(app.respond(req)).unwrap();
+            res
+        };
+        for (path, expected) in [
+            ("/app/", index.as_str()),
+            ("/app/app.js", "js"),
+            ("/app/about/index.html", index.as_str()),
+            ("/app/some/route", index.as_str()),
+        ] {
+            let mut res = request(Method::Get, path, "");
+            assert_eq!(res.status(), 200, "{}", path);
+            let body = async_std::task::block_on(res.body_string()).unwrap();
+            assert_eq!(body, expected, "{}", path);
+        }
+
+        let res = request(Method::Post, DOM_PATH, "<html></html>");
+        assert_eq!(res.status(), 204);
+        assert_eq!(rx.try_recv().unwrap(), "<html></html>");
+    }
+}
@@ -0,0 +1,537 @@
+//! The child processes of the build (`cargo`, `wasm-opt`, ...).
+//!
+//! Every child process is started in its own process group (a job object on Windows) so the
+//! processes it starts itself (`rustc`, the build scripts, the test runner, ...) can be terminated
+//! with it: on Ctrl-C, when the backend is restarted or when the serve command panics. Otherwise
+//! they keep running in the background and hold the lock of the target directory.
+//!
+//! The group receives a termination signal first (`SIGTERM`, `CTRL_BREAK_EVENT` on Windows) and
+//! is killed if it is still running after [`TERMINATE_TIMEOUT`].
+
+use once_cell::sync::Lazy;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::process::{Child, Command, ExitStatus, Output};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a process group has to exit after the termination signal before being killed.
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The exit code after an interruption by Ctrl-C, like a shell does.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// The process groups that are running.
+static GROUPS: Lazy<Mutex<Vec<Group>>> = Lazy::new(Default::default);
+
+/// Ctrl-C has been pressed, the process is exiting.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Called once when the process stops, see [`on_shutdown`].
+#[allow(clippy::type_complexity)]
+static SHUTDOWN: Lazy<Mutex<Option<Box<dyn FnOnce() + Send>>>> = Lazy::new(Default::default);
+
+/// A process group, identified by the PID of its leader (and its job object on Windows).
+#[derive(Clone, Copy, PartialEq)]
+struct Group {
+    pid: u32,
+    #[cfg(windows)]
+    job: usize,
+}
+
+/// A child process started in its own process group.
+///
+/// The whole group is terminated if the instance is dropped before the child process exited.
+pub(crate) struct GroupChild {
+    child: Option<Child>,
+    group: Group,
+    /// The command for the plan of the build, if it is recorded.
+    planned: Option<crate::build_plan::Running>,
+}
+
+impl GroupChild {
+    /// Start the command in a new process group.
+    pub fn spawn(command: &mut Command) -> io::Result<Self> {
+        let planned = crate::build_plan::started(command);
+        sys::new_group(command);
+        let child = match command.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                if let Some(planned) = planned {
+                    planned.finish(None);
+                }
+                return Err(err);
+            }
+        };
+        let group = sys::register(&child)?;
+        GROUPS.lock().unwrap().push(group);
+
+        Ok(Self {
+            child: Some(child),
+            group,
+            planned,
+        })
+    }
+
+    /// Record the exit of the command in the plan of the build.
+    fn finish(&mut self, status: Option<&ExitStatus>) {
+        if let Some(planned) = self.planned.take() {
+            planned.finish(status);
+        }
+    }
+
+    /// Wait for the child process to exit, like [`Child::wait`].
+    ///
+    /// The group is not terminated by [`terminate_all`] anymore once the child process has been
+    /// reaped: its ID could be reused by another process.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        let status = self.deref_mut().wait();
+        if status.is_ok() {
+            unregister(self.group);
+        }
+        self.finish(status.as_ref().ok());
+        wait_exit();
+        status
+    }
+
+    /// Check if the child process exited without blocking, like [`Child::try_wait`].
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        let status = self.deref_mut().try_wait()?;
+        if let Some(status) = status.as_ref() {
+            unregister(self.group);
+            self.finish(Some(status));
+        }
+        Ok(status)
+    }
+
+    /// Wait for the child process to exit and collect its outputs, like [`Child::wait_with_output`].
+    #[cfg_attr(not(feature = "prebuilt-wasm-opt"), allow(dead_code))]
+    pub fn wait_with_output(mut self) -> io::Result<Output> {
+        let child = self
+            .child
+            .take()
+            .expect("the child is only taken here; qed");
+        let output = child.wait_with_output();
+        self.finish(output.as_ref().map(|x| &x.status).ok());
+        unregister(self.group);
+        wait_exit();
+        output
+    }
+
+    /// Terminate the process group and wait for the child process.
+    pub fn terminate(&mut self) -> io::Result<ExitStatus> {
+        let group = self.group;
+        let child = self
+            .child
+            .as_mut()
+            .expect("the child is only taken when consumed; qed");
+
+        if child.try_wait()?.is_none() {
+            log::debug!("Terminating the process group of {}", group.pid);
+            terminate(group, &mut || {
+                let _ = child.try_wait();
+            });
+        }
+        let status = child.wait();
+        self.finish(status.as_ref().ok());
+        unregister(group);
+        status
+    }
+}
+
+impl Deref for GroupChild {
+    type Target = Child;
+
+    fn deref(&self) -> &Child {
+        self.child
+            .as_ref()
+            .expect("the child is only taken when consumed; qed")
+    }
+}
+
+impl DerefMut for GroupChild {
+    fn deref_mut(&mut self) -> &mut Child {
+        self.child
+            .as_mut()
+            .expect("the child is only taken when consumed; qed")
+    }
+}
+
+impl Drop for GroupChild {
+    fn drop(&mut self) {
+        if self.child.is_some() {
+            let _ = self.terminate();
+        }
+        sys::release(self.group);
+    }
+}
+
+/// Terminate all the process groups that are still running.
+pub(crate) fn terminate_all() {
+    let groups = std::mem::take(&mut *GROUPS.lock().unwrap());
+
+    for group in groups {
+        log::debug!("Terminating the process group of {}", group.pid);
+        terminate(group, &mut || sys::reap(group));
+    }
+}
+
+/// Run `f` and terminate the process groups that are still running if it panics.
+pub(crate) fn terminate_on_panic<T>(f: impl FnOnce() -> T) -> T {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(value) => value,
+        Err(panic) => {
+            terminate_all();
+            std::panic::resume_unwind(panic)
+        }
+    }
+}
+
+/// Call `f` once when the process stops: on Ctrl-C before the process groups are terminated, or
+/// with [`shutdown`].
+pub(crate) fn on_shutdown(f: impl FnOnce() + Send + 'static) {
+    *SHUTDOWN.lock().unwrap() = Some(Box::new(f));
+}
+
+/// Call the function given to [`on_shutdown`] if it has not been called yet.
+pub(crate) fn shutdown() {
+    let f = SHUTDOWN.lock().unwrap().take();
+    if let Some(f) = f {
+        f();
+    }
+}
+
+/// Terminate the process groups and exit on Ctrl-C.
+///
+/// Without the handler, the process would exit immediately and the process groups would keep
+/// running because they don't receive the Ctrl-C of the terminal.
+pub(crate) fn install_interrupt_handler() -> io::Result<()> {
+    sys::install_interrupt_handler(|| {
+        log::debug!("Interrupted, terminating the child processes");
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        shutdown();
+        terminate_all();
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    })
+}
+
+/// Block forever if the process is exiting after Ctrl-C.
+///
+/// The child processes terminated by the interruption exit with an error that must not be reported
+/// before the exit.
+fn wait_exit() {
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        loop {
+            thread::park();
+        }
+    }
+}
+
+fn unregister(group: Group) {
+    GROUPS.lock().unwrap().retain(|x| *x != group);
+}
+
+/// Ask the process group to exit and kill it after [`TERMINATE_TIMEOUT`].
+///
+/// `reap` collects the exit status of the leader if it has exited: the group can't be empty
+/// before that.
+fn terminate(group: Group, reap: &mut dyn FnMut()) {
+    sys::interrupt(group);
+
+    let deadline = Instant::now() + TERMINATE_TIMEOUT;
+    loop {
+        reap();
+        if !sys::is_alive(group) {
+            return;
+        }
+        if Instant::now() >= deadline {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    log::debug!("Killing the process group of {}", group.pid);
+    sys::kill(group);
+    // NOTE: the processes have not necessarily exited when the signal returns
+    let deadline = Instant::now() + TERMINATE_TIMEOUT;
+    while Instant::now() < deadline {
+        reap();
+        if !sys::is_alive(group) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use super::Group;
+    use std::io;
+    use std::os::unix::process::CommandExt;
+    use std::process::{Child, Command};
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    /// The write end of the pipe that wakes up the thread handling the interruptions.
+    static INTERRUPT_FD: AtomicI32 = AtomicI32::new(-1);
+
+    pub fn new_group(command: &mut Command) {
+        command.process_group(0);
+    }
+
+    pub fn register(child: &Child) -> io::Result<Group> {
+        Ok(Group { pid: child.id() })
+    }
+
+    pub fn release(_group: Group) {}
+
+    pub fn interrupt(group: Group) {
+        signal(group, libc::SIGTERM);
+    }
+
+    pub fn kill(group: Group) {
+        signal(group, libc::SIGKILL);
+    }
+
+    pub fn is_alive(group: Group) -> bool {
+        // NOTE: the signal 0 only checks that the group still has a process
+        unsafe { libc::kill(-(group.pid as libc::pid_t), 0) == 0 }
+    }
+
+    pub fn reap(group: Group) {
+        unsafe {
+            libc::waitpid(
+                group.pid as libc::pid_t,
+                std::ptr::null_mut(),
+                libc::WNOHANG,
+            );
+        }
+    }
+
+    fn signal(group: Group, signal: libc::c_int) {
+        unsafe {
+            libc::kill(-(group.pid as libc::pid_t), signal);
+        }
+    }
+
+    extern "C" fn on_signal(_: libc::c_int) {
+        // NOTE: only the async-signal-safe functions can be called here, the rest is done by the
+        //       thread waiting on the pipe
+        let byte = 0u8;
+        unsafe {
+            libc::write(
+                INTERRUPT_FD.load(Ordering::Relaxed),
+                &byte as *const u8 as *const libc::c_void,
+                1,
+            );
+        }
+    }
+
+    pub fn install_interrupt_handler(handler: fn()) -> io::Result<()> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        INTERRUPT_FD.store(fds[1], Ordering::Relaxed);
+
+        std::thread::Builder::new()
+            .name("interrupt handler".to_string())
+            .spawn(move || loop {
+                let mut byte = 0u8;
+                let read =
+                    unsafe { libc::read(fds[0], &mut byte as *mut u8 as *mut libc::c_void, 1) };
+                if read == 1 {
+                    handler();
+                } else if read == 0
+                    || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted
+                {
+                    return;
+                }
+            })?;
+
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::Group;
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+    use std::os::windows::process::CommandExt;
+    use std::process::{Child, Command};
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::Console::{
+        GenerateConsoleCtrlEvent, SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_C_EVENT,
+    };
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectBasicAccountingInformation,
+        JobObjectExtendedLimitInformation, QueryInformationJobObject, SetInformationJobObject,
+        TerminateJobObject, JOBOBJECT_BASIC_ACCOUNTING_INFORMATION,
+        JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    };
+    use windows_sys::Win32::System::Threading::CREATE_NEW_PROCESS_GROUP;
+
+    static HANDLER: once_cell::sync::OnceCell<fn()> = once_cell::sync::OnceCell::new();
+
+    pub fn new_group(command: &mut Command) {
+        command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+
+    /// Put the child process in a new job object that kills its processes when it is closed.
+    pub fn register(child: &Child) -> io::Result<Group> {
+        unsafe {
+            let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+            if job.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut info = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+            info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            // NOTE: the processes started by the child before this are not in the job
+            if SetInformationJobObject(
+                job,
+                JobObjectExtendedLimitInformation,
+                &info as *const _ as *const _,
+                std::mem::size_of_val(&info) as u32,
+            ) == 0
+                || AssignProcessToJobObject(job, child.as_raw_handle() as HANDLE) == 0
+            {
+                let err = io::Error::last_os_error();
+                CloseHandle(job);
+                return Err(err);
+            }
+
+            Ok(Group {
+                pid: child.id(),
+                job: job as usize,
+            })
+        }
+    }
+
+    pub fn release(group: Group) {
+        unsafe {
+            CloseHandle(group.job as HANDLE);
+        }
+    }
+
+    pub fn interrupt(group: Group) {
+        unsafe {
+            GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, group.pid);
+        }
+    }
+
+    pub fn kill(group: Group) {
+        unsafe {
+            TerminateJobObject(group.job as HANDLE, 1);
+        }
+    }
+
+    pub fn is_alive(group: Group) -> bool {
+        let mut info = JOBOBJECT_BASIC_ACCOUNTING_INFORMATION::default();
+        let res = unsafe {
+            QueryInformationJobObject(
+                group.job as HANDLE,
+                JobObjectBasicAccountingInformation,
+                &mut info as *mut _ as *mut _,
+                std::mem::size_of_val(&info) as u32,
+                std::ptr::null_mut(),
+            )
+        };
+        res != 0 && info.ActiveProcesses > 0
+    }
+
+    pub fn reap(_group: Group) {}
+
+    unsafe extern "system" fn on_ctrl(ctrl_type: u32) -> i32 {
+        match (ctrl_type, HANDLER.get()) {
+            (CTRL_C_EVENT | CTRL_BREAK_EVENT, Some(handler)) => {
+                handler();
+                1
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn install_interrupt_handler(handler: fn()) -> io::Result<()> {
+        let _ = HANDLER.set(handler);
+        if unsafe { SetConsoleCtrlHandler(Some(on_ctrl), 1) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terminate_process_group() {
+        let mut child =
+            GroupChild::spawn(Command::new("sh").args(["-c", "sleep 30 & wait"])).unwrap();
+        let group = child.group;
+        // NOTE: give the shell the time to start `sleep`
+        thread::sleep(Duration::from_millis(200));
+
+        let start = Instant::now();
+        child.terminate().unwrap();
+        assert!(start.elapsed() < TERMINATE_TIMEOUT);
+        assert!(!sys::is_alive(group));
+        assert!(!GROUPS.lock().unwrap().contains(&group));
+    }
+
+    #[test]
+    fn unregister_after_wait() {
+        let mut child = GroupChild::spawn(Command::new("sh").args(["-c", "exit 0"])).unwrap();
+        let group = child.group;
+        assert!(GROUPS.lock().unwrap().contains(&group));
+
+        assert!(child.wait().unwrap().success());
+        assert!(!GROUPS.lock().unwrap().contains(&group));
+
+        let mut child = GroupChild::spawn(&mut Command::new("true")).unwrap();
+        let group = child.group;
+        while child.try_wait().unwrap().is_none() {
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!GROUPS.lock().unwrap().contains(&group));
+    }
+
+    #[test]
+    fn kill_process_group() {
+        let child =
+            GroupChild::spawn(Command::new("sh").args(["-c", "trap '' TERM; sleep 30"])).unwrap();
+        let group = child.group;
+        thread::sleep(Duration::from_millis(200));
+
+        let start = Instant::now();
+        drop(child);
+        assert!(start.elapsed() >= TERMINATE_TIMEOUT);
+        assert!(!sys::is_alive(group));
+    }
+
+    #[test]
+    fn shutdown_once() {
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let count = Arc::clone(&calls);
+        on_shutdown(move || {
+            count.fetch_add(1, Ordering::SeqCst);
+        });
+        shutdown();
+        shutdown();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
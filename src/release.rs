@@ -0,0 +1,190 @@
+//! The uploads after a release build of the `build` command, for the error trackers that need the
+//! sourcemaps and the WASM (its name section) of every release: the `post_release` hook, then the
+//! command of [`crate::BuildArgs::sourcemap_upload_command`] for every sourcemap and WASM.
+//!
+//! Their failures are warnings unless [`crate::BuildArgs::upload_errors_fatal`]: a flaky uploader
+//! doesn't block the release.
+
+use crate::{BuildArgs, BuildProfile, Error, Hooks};
+use anyhow::{anyhow, Context};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A release build, given to the `post_release` hook once all its files are written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Release {
+    /// The identifier of the release: [`BuildArgs::release_id`], the commit of the workspace, or
+    /// the version of the frontend package.
+    pub id: String,
+    /// The commit `HEAD` of the workspace (with `-dirty` if it has uncommitted changes), if it is
+    /// in a git repository.
+    pub git_sha: Option<String>,
+    /// The URL path where the build directory is deployed ([`BuildArgs::base_url`]).
+    pub base_url: String,
+    /// The files of the build, sorted by path.
+    pub artifacts: Vec<ReleaseArtifact>,
+}
+
+/// A file of a release build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ReleaseArtifact {
+    /// The path of the file, relative to the build directory.
+    pub path: PathBuf,
+    /// The URL of the file: the base URL followed by its path.
+    pub url: String,
+    /// The size of the file, in bytes.
+    pub size: u64,
+    /// The SHA-256 of the content, in hexadecimal.
+    pub sha256: String,
+}
+
+impl ReleaseArtifact {
+    /// The file is a sourcemap (`.map`) or a WASM: the error trackers use them to show the
+    /// sources of the stack traces. The sourcemap upload command runs for these files.
+    pub fn is_debug_info(&self) -> bool {
+        self.path
+            .extension()
+            .map(|x| x == "map" || x == "wasm")
+            .unwrap_or(false)
+    }
+}
+
+impl Release {
+    /// The release of the last build of the build directory.
+    fn of_build(args: &dyn BuildArgs) -> Result<Self, Error> {
+        let build_path = args.build_path();
+        let base_url = crate::base_url(args);
+        let mut artifacts = Vec::new();
+        for file in crate::build_files::recorded(&build_path)? {
+            let path = build_path.join(&file);
+            let content = fs::read(&path).map_err(|source| Error::Io { path, source })?;
+            artifacts.push(ReleaseArtifact {
+                url: format!("{}{}", base_url, crate::slash_path(&file)),
+                path: file,
+                size: content.len() as u64,
+                sha256: format!("{:x}", Sha256::digest(&content)),
+            });
+        }
+        artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let git_sha = crate::build_meta::read(&build_path).and_then(|x| x.commit);
+        Ok(Self {
+            id: args
+                .release_id()
+                .or_else(|| git_sha.clone())
+                .unwrap_or_else(|| args.frontend_package().version.to_string()),
+            git_sha,
+            base_url,
+            artifacts,
+        })
+    }
+}
+
+/// Run the `post_release` hook and the sourcemap upload command after a build of this profile, if
+/// it is a release build.
+pub(crate) fn upload(
+    args: &dyn BuildArgs,
+    hooks: &Hooks,
+    profile: BuildProfile,
+) -> Result<(), Error> {
+    if profile != BuildProfile::Release {
+        return Ok(());
+    }
+
+    let release = Release::of_build(args)?;
+    let res = (hooks.post_release)(args, &release).map_err(|source| Error::Hook {
+        hook: "post_release",
+        source,
+    });
+    report(args, res)?;
+
+    let line = match args.sourcemap_upload_command() {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let build_path = args.build_path();
+    for artifact in release.artifacts.iter().filter(|x| x.is_debug_info()) {
+        let file = build_path.join(&artifact.path);
+        let command_line = substitute(&line, &file, &artifact.url, &release.id);
+        let res = run(args, &command_line).with_context(|| {
+            format!(
+                "could not upload `{}` with the sourcemap upload command",
+                artifact.path.display()
+            )
+        });
+        report(args, res.map_err(Error::from))?;
+    }
+
+    Ok(())
+}
+
+/// The error of an upload, or only a warning unless [`BuildArgs::upload_errors_fatal`].
+fn report(args: &dyn BuildArgs, res: Result<(), Error>) -> Result<(), Error> {
+    match res {
+        Err(err) if !args.upload_errors_fatal() => {
+            crate::reporter::reporter().warning(&format!("{:#}", anyhow::Error::from(err)));
+            Ok(())
+        }
+        res => res,
+    }
+}
+
+/// The command line with its placeholders replaced by the values, quoted for the shell.
+fn substitute(line: &str, file: &Path, url: &str, release: &str) -> String {
+    line.replace(
+        "{file}",
+        &crate::build_plan::quote(&file.display().to_string()),
+    )
+    .replace("{url}", &crate::build_plan::quote(url))
+    .replace("{release}", &crate::build_plan::quote(release))
+}
+
+fn run(args: &dyn BuildArgs, line: &str) -> anyhow::Result<()> {
+    let mut command = crate::shell_command(line);
+    crate::process_env(args, BuildProfile::Release, &mut command);
+    command.current_dir(args.frontend_package().manifest_path.parent().unwrap());
+    log::debug!("Running {:?}", command);
+    let status = crate::process::GroupChild::spawn(&mut command)
+        .and_then(|mut x| x.wait())
+        .with_context(|| format!("could not start `{}`", line))?;
+    match status.code() {
+        _ if status.success() => Ok(()),
+        Some(code) => Err(anyhow!("`{}` exit with code {}", line, code)),
+        None => Err(anyhow!("`{}` has been terminated by a signal", line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_of_the_command() {
+        assert_eq!(
+            substitute(
+                "tracker upload --release {release} --url {url} {file}",
+                Path::new("/build/app bg.wasm"),
+                "/app/app_bg.wasm",
+                "1a2b3c",
+            ),
+            "tracker upload --release 1a2b3c --url /app/app_bg.wasm '/build/app bg.wasm'"
+        );
+    }
+
+    #[test]
+    fn debug_info_of_the_release() {
+        let artifact = |path: &str| ReleaseArtifact {
+            path: PathBuf::from(path),
+            url: format!("/{}", path),
+            size: 0,
+            sha256: String::new(),
+        };
+        assert!(artifact("app_bg.wasm").is_debug_info());
+        assert!(artifact("app.js.map").is_debug_info());
+        assert!(!artifact("app.js").is_debug_info());
+        assert!(!artifact("index.html").is_debug_info());
+    }
+}
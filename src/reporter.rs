@@ -0,0 +1,96 @@
+//! The reporter installed by [`crate::Runner::reporter`].
+
+use crate::{ConsoleReporter, Reporter};
+use once_cell::sync::OnceCell;
+
+static REPORTER: OnceCell<Box<dyn Reporter>> = OnceCell::new();
+
+/// Install the reporter. Only the first call has an effect.
+pub(crate) fn set_reporter(reporter: Box<dyn Reporter>) {
+    let _ = REPORTER.set(reporter);
+}
+
+/// The reporter installed, or the console if there is none.
+pub(crate) fn reporter() -> &'static dyn Reporter {
+    match REPORTER.get() {
+        Some(reporter) => reporter.as_ref(),
+        None => &ConsoleReporter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cargo_metadata::diagnostic::Diagnostic;
+    use std::fs;
+    use std::process::Command;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Reporter for Recorder {
+        fn diagnostic(&self, diagnostic: &Diagnostic) {
+            self.0.lock().unwrap().push(diagnostic.message.clone());
+        }
+
+        fn cargo_output(&self, line: &str) {
+            self.0.lock().unwrap().push(line.to_string());
+        }
+    }
+
+    #[test]
+    fn report_compiler_diagnostics() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"diagnostics\"\nversion = \"0.1.0\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("src/lib.rs"),
+            "pub fn f() {\n    let x = 1;\n}\n",
+        )
+        .unwrap();
+
+        let recorder = Recorder::default();
+        crate::run_cargo(
+            Command::new("cargo")
+                .args([
+                    "build",
+                    "--quiet",
+                    "--message-format=json",
+                    "--manifest-path",
+                ])
+                .arg(dir.path().join("Cargo.toml"))
+                .arg("--target-dir")
+                .arg(dir.path().join("target")),
+            &recorder,
+        )
+        .unwrap();
+
+        let messages = recorder.0.into_inner().unwrap();
+        assert!(
+            messages.iter().any(|x| x == "unused variable: `x`"),
+            "{:?}",
+            messages
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn report_cargo_output() {
+        let recorder = Recorder::default();
+        crate::run_cargo(
+            Command::new("sh").args(["-c", "echo '   Compiling app v0.1.0' >&2"]),
+            &recorder,
+        )
+        .unwrap();
+
+        assert_eq!(
+            recorder.0.into_inner().unwrap(),
+            ["   Compiling app v0.1.0"]
+        );
+    }
+}
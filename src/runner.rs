@@ -0,0 +1,559 @@
+//! A builder to configure the hooks and run the command-line without the macro [`crate::main`].
+
+use crate::{
+    Asset, BuildArgs, BuildOutput, BuildProfile, BuildTrigger, DefaultBuildArgs, DefaultCleanArgs,
+    DefaultCompletionsArgs, DefaultPath, DefaultRunArgs, DefaultRunNodeArgs, DefaultServeArgs,
+    DefaultTestArgs, DefaultVersionArgs, Error, FileWatcher, Hooks, PackageSelector, Project,
+    Release, Reporter, ServeArgs, TestArgs, Verbosity, DEFAULT_BUILD_PATH, DEFAULT_SERVE_PATH,
+    HOOKS, MANIFEST_PATH, PROJECT,
+};
+use anyhow::{Context, Result};
+use cargo_metadata::{Metadata, Package};
+use once_cell::sync::OnceCell;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::RwLock;
+use structopt::StructOpt;
+
+/// Environment variable of the manifest of the workspace, for the binaries ran outside of it.
+const MANIFEST_PATH_VAR: &str = "WASMBL_MANIFEST_PATH";
+
+/// How the frontend package is selected.
+enum Frontend {
+    Name(String),
+    ManifestPath(PathBuf),
+}
+
+/// Configure the hooks and run the command-line.
+///
+/// This is what the macro [`crate::main`] generates, for the projects that prefer to configure
+/// everything in plain Rust (an `xtask` for example):
+///
+/// ```no_run
+/// use structopt::StructOpt;
+///
+/// #[derive(StructOpt)]
+/// enum Cli {
+///     Hello,
+/// }
+///
+/// fn main() -> Result<(), wasmbl::Error> {
+///     wasmbl::Runner::new(env!("CARGO_MANIFEST_DIR"))
+///         .package("frontend")
+///         .pre_build(|_args, _profile, command, _trigger| {
+///             command.arg("--locked");
+///             Ok(())
+///         })
+///         .run_cli(|cli: Cli, _metadata, _package| match cli {
+///             Cli::Hello => Ok(println!("Hello World!")),
+///         })
+/// }
+/// ```
+///
+/// Only the default arguments of the commands are supported, the macro is needed to use custom
+/// arguments.
+pub struct Runner {
+    manifest_dir: PathBuf,
+    frontend: Frontend,
+    backend: Option<String>,
+    default_build_path: Option<DefaultPath>,
+    default_serve_path: Option<DefaultPath>,
+    hooks: Hooks,
+    reporter: Option<Box<dyn Reporter>>,
+}
+
+impl Runner {
+    /// Create a runner for the crate in `manifest_dir` (usually `env!("CARGO_MANIFEST_DIR")`).
+    ///
+    /// The frontend package is the package of this directory unless another one is selected with
+    /// [`Runner::package`] or [`Runner::manifest_path`].
+    pub fn new(manifest_dir: impl Into<PathBuf>) -> Self {
+        let manifest_dir = manifest_dir.into();
+
+        Self {
+            frontend: Frontend::ManifestPath(manifest_dir.clone()),
+            manifest_dir,
+            backend: None,
+            default_build_path: None,
+            default_serve_path: None,
+            hooks: Hooks::default(),
+            reporter: None,
+        }
+    }
+
+    /// Name of the frontend package in the workspace.
+    pub fn package(mut self, name: impl Into<String>) -> Self {
+        self.frontend = Frontend::Name(name.into());
+        self
+    }
+
+    /// Path to the manifest of the frontend package (or its directory), relative to the workspace
+    /// root. The package can be excluded from the workspace or live in a nested workspace.
+    pub fn manifest_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.frontend = Frontend::ManifestPath(path.into());
+        self
+    }
+
+    /// Name of the backend package in the workspace.
+    pub fn backend(mut self, name: impl Into<String>) -> Self {
+        self.backend = Some(name.into());
+        self
+    }
+
+    /// Provide the default build directory when it is not given on the command-line. See the
+    /// argument `default_build_path` of the macro [`crate::main`].
+    pub fn default_build_path(
+        mut self,
+        default_build_path: impl Fn(&Metadata, &Package) -> PathBuf + Send + Sync + 'static,
+    ) -> Self {
+        self.default_build_path = Some(Box::new(default_build_path));
+        self
+    }
+
+    /// Provide the directory served by the `serve` command. See the argument `default_serve_path`
+    /// of the macro [`crate::main`].
+    pub fn default_serve_path(
+        mut self,
+        default_serve_path: impl Fn(&Metadata, &Package) -> PathBuf + Send + Sync + 'static,
+    ) -> Self {
+        self.default_serve_path = Some(Box::new(default_serve_path));
+        self
+    }
+
+    /// Report the progress of the build to `reporter` instead of the console.
+    pub fn reporter(mut self, reporter: impl Reporter + 'static) -> Self {
+        self.reporter = Some(Box::new(reporter));
+        self
+    }
+
+    /// Replace all the hooks.
+    pub fn hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// See [`Hooks::pre_build`].
+    pub fn pre_build(
+        mut self,
+        pre_build: impl Fn(&dyn BuildArgs, BuildProfile, &mut Command, BuildTrigger) -> Result<()>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.hooks.pre_build = Box::new(pre_build);
+        self
+    }
+
+    /// See [`Hooks::post_build`].
+    pub fn post_build(
+        mut self,
+        post_build: impl Fn(&dyn BuildArgs, &BuildOutput) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.post_build = Box::new(move |args, output, _| post_build(args, output));
+        self
+    }
+
+    /// See [`Hooks::post_release`].
+    pub fn post_release(
+        mut self,
+        post_release: impl Fn(&dyn BuildArgs, &Release) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.post_release = Box::new(post_release);
+        self
+    }
+
+    /// See [`Hooks::bindgen`].
+    pub fn bindgen(
+        mut self,
+        bindgen: impl Fn(&dyn BuildArgs, &mut wasm_bindgen_cli_support::Bindgen) -> Result<()>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.hooks.bindgen = Box::new(bindgen);
+        self
+    }
+
+    /// See [`Hooks::asset_filter`].
+    pub fn asset_filter(
+        mut self,
+        asset_filter: impl Fn(Asset) -> Result<Option<Asset>> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.asset_filter = Box::new(asset_filter);
+        self
+    }
+
+    /// See [`Hooks::serve_middleware`].
+    #[cfg(feature = "dev-server")]
+    pub fn serve_middleware(
+        mut self,
+        serve_middleware: impl Fn(&dyn ServeArgs, &mut tide::Server<()>) -> Result<()>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.hooks.serve_middleware = Box::new(serve_middleware);
+        self
+    }
+
+    /// See [`Hooks::serve`].
+    #[cfg(feature = "dev-server")]
+    pub fn serve(
+        mut self,
+        serve: impl Fn(&dyn ServeArgs, &mut tide::Server<()>) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.serve = Box::new(serve);
+        self
+    }
+
+    /// See [`Hooks::frontend_watch`].
+    pub fn frontend_watch(
+        mut self,
+        frontend_watch: impl Fn(&dyn ServeArgs, &mut FileWatcher) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.frontend_watch = Box::new(frontend_watch);
+        self
+    }
+
+    /// See [`Hooks::backend_watch`].
+    pub fn backend_watch(
+        mut self,
+        backend_watch: impl Fn(&dyn ServeArgs, &mut FileWatcher) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.backend_watch = Box::new(backend_watch);
+        self
+    }
+
+    /// See [`Hooks::backend_command`].
+    pub fn backend_command(
+        mut self,
+        backend_command: impl Fn(&dyn ServeArgs, &mut Command) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.backend_command = Box::new(backend_command);
+        self
+    }
+
+    /// See [`Hooks::pre_test`].
+    pub fn pre_test(
+        mut self,
+        pre_test: impl Fn(&dyn TestArgs, &mut Command) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.pre_test = Box::new(pre_test);
+        self
+    }
+
+    /// An alias of [`Runner::pre_test`].
+    pub fn test(
+        self,
+        test: impl Fn(&dyn TestArgs, &mut Command) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.pre_test(test)
+    }
+
+    /// See [`Hooks::deploy`].
+    #[cfg(feature = "deploy")]
+    pub fn deploy(
+        mut self,
+        deploy: impl Fn(&crate::DefaultDeployArgs, &[PathBuf]) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.deploy = Box::new(deploy);
+        self
+    }
+
+    /// Load the metadata of the workspace and install the hooks.
+    ///
+    /// Only the first successful call has an effect, the next ones return the same values. The
+    /// metadata is read from the manifest in the directory given to [`Runner::new`] unless the
+    /// environment variable `WASMBL_MANIFEST_PATH` is set.
+    pub fn init(self) -> Result<(&'static Metadata, &'static Package), Error> {
+        static INIT: OnceCell<()> = OnceCell::new();
+
+        let Self {
+            manifest_dir,
+            frontend,
+            backend,
+            default_build_path,
+            default_serve_path,
+            hooks,
+            reporter,
+        } = self;
+
+        // NOTE: the tests of a project can initialize it many times, only the first successful
+        //       call has an effect
+        INIT.get_or_try_init(move || -> Result<()> {
+            crate::logger::init();
+
+            if let Some(reporter) = reporter {
+                crate::reporter::set_reporter(reporter);
+            }
+
+            // NOTE: the cells are only set once the project is loaded, a failed call can be
+            //       retried
+            let manifest_path = std::env::var_os(MANIFEST_PATH_VAR)
+                .map(PathBuf::from)
+                .unwrap_or_else(|| manifest_dir.join("Cargo.toml"));
+            // NOTE: cargo's own error about the missing manifest doesn't tell what to do
+            check_manifest(
+                &manifest_path,
+                std::env::var_os(MANIFEST_PATH_VAR).is_some(),
+            )?;
+
+            let frontend = match &frontend {
+                Frontend::Name(name) => PackageSelector::Name(name),
+                Frontend::ManifestPath(path) => PackageSelector::ManifestPath(path),
+            };
+            let project = Project::load(
+                &manifest_path,
+                frontend,
+                backend.as_deref(),
+                default_build_path.as_ref(),
+                default_serve_path.as_ref(),
+            )
+            .context("this binary is not meant to be ran outside of its workspace")?;
+
+            crate::process::install_interrupt_handler()
+                .context("could not install the handler of Ctrl-C")?;
+
+            if MANIFEST_PATH.set(manifest_path).is_err() {
+                panic!("the cell is initially empty; qed");
+            }
+            if DEFAULT_BUILD_PATH.set(default_build_path).is_err() {
+                panic!("the cell is initially empty; qed");
+            }
+            if DEFAULT_SERVE_PATH.set(default_serve_path).is_err() {
+                panic!("the cell is initially empty; qed");
+            }
+            if PROJECT.set(RwLock::new(project)).is_err() {
+                panic!("the cell is initially empty; qed");
+            }
+            if HOOKS.set(hooks).is_err() {
+                panic!("the cell is initially empty; qed");
+            }
+
+            Ok(())
+        })?;
+
+        let Project {
+            metadata,
+            frontend_package,
+            ..
+        } = Project::get();
+
+        Ok((metadata, frontend_package))
+    }
+
+    /// Parse the command-line and run the command.
+    ///
+    /// The commands of `wasmbl` are added next to the commands of `T` (an enum that derives
+    /// `StructOpt`) and `other_cli_commands` is called with the commands of `T`. The `serve` command
+    /// runs if no command is provided. Use an empty enum if there is no other command.
+    pub fn run_cli<T>(
+        self,
+        other_cli_commands: impl FnOnce(T, &Metadata, &Package) -> Result<()>,
+    ) -> Result<(), Error>
+    where
+        // NOTE: implemented by `#[derive(StructOpt)]`, needed to flatten the commands of `T`
+        T: structopt::StructOptInternal,
+    {
+        self.run_cli_from(std::env::args_os(), other_cli_commands)
+    }
+
+    /// Like [`Runner::run_cli`] but parse the arguments given in `cli_args` (the first one is the
+    /// name of the program).
+    pub fn run_cli_from<T, I>(
+        self,
+        cli_args: I,
+        other_cli_commands: impl FnOnce(T, &Metadata, &Package) -> Result<()>,
+    ) -> Result<(), Error>
+    where
+        T: structopt::StructOptInternal,
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        let cli_args: Vec<OsString> = cli_args.into_iter().map(Into::into).collect();
+        let cli = RunnerCli::<T>::from_iter(&cli_args);
+        crate::set_verbosity(cli.verbosity);
+        let (metadata, package) = self.init()?;
+
+        match cli.command {
+            Some(RunnerCommand::Build(args)) => {
+                args.run()?;
+            }
+            Some(RunnerCommand::Serve(args)) => args.run()?,
+            Some(RunnerCommand::Test(args)) => args.run()?,
+            Some(RunnerCommand::Run(args)) => args.run()?,
+            Some(RunnerCommand::RunNode(args)) => args.run()?,
+            Some(RunnerCommand::Clean(args)) => args.run()?,
+            Some(RunnerCommand::Completions(args)) => args.run_structopt(RunnerCli::<T>::clap())?,
+            Some(RunnerCommand::Version(args)) => args.run()?,
+            #[cfg(feature = "scaffold")]
+            Some(RunnerCommand::New(args)) => args.run()?,
+            #[cfg(feature = "deploy")]
+            Some(RunnerCommand::Deploy(args)) => args.run()?,
+            Some(RunnerCommand::Other(cli)) => other_cli_commands(cli, metadata, package)?,
+            // NOTE: the arguments are parsed again for the default command
+            None => RunnerServeCli::from_iter(&cli_args).args.run()?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse the default commands from `cli_args` (the first one is the name of the program) and run
+/// the command with `hooks`.
+///
+/// This is meant for the crates that wrap `wasmbl` with their own conventions and parse their own
+/// arguments before:
+///
+/// ```no_run
+/// fn company_hooks() -> wasmbl::Hooks {
+///     wasmbl::Hooks {
+///         pre_build: Box::new(|_args, _profile, command, _trigger| {
+///             command.arg("--locked");
+///             Ok(())
+///         }),
+///         ..Default::default()
+///     }
+/// }
+///
+/// pub fn main() -> Result<(), wasmbl::Error> {
+///     let args = std::env::args_os().filter(|x| x != "--company-flag");
+///     wasmbl::run(args, company_hooks())
+/// }
+/// ```
+///
+/// The frontend package is the package of the current directory, use a [`Runner`] to select
+/// another one.
+pub fn run<I>(cli_args: I, hooks: Hooks) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    let current_dir = std::env::current_dir().context("could not get the current directory")?;
+
+    Runner::new(current_dir)
+        .hooks(hooks)
+        .run_cli_from(cli_args, |cli: NoCommand, _, _| match cli {})
+}
+
+/// Fails with [`Error::OutsideWorkspace`] if the manifest of the workspace does not exist.
+fn check_manifest(manifest_path: &Path, from_env: bool) -> Result<(), Error> {
+    if manifest_path.is_file() {
+        return Ok(());
+    }
+
+    Err(Error::OutsideWorkspace {
+        manifest_path: manifest_path.to_path_buf(),
+        from_env,
+        current_dir: std::env::current_dir().ok(),
+    })
+}
+
+/// There is no other command than the commands of `wasmbl`.
+#[derive(StructOpt)]
+enum NoCommand {}
+
+#[derive(StructOpt)]
+struct RunnerCli<T: structopt::StructOptInternal> {
+    #[structopt(flatten)]
+    verbosity: Verbosity,
+    #[structopt(subcommand)]
+    command: Option<RunnerCommand<T>>,
+}
+
+#[derive(StructOpt)]
+enum RunnerCommand<T: structopt::StructOptInternal> {
+    Build(DefaultBuildArgs),
+    Serve(DefaultServeArgs),
+    Test(DefaultTestArgs),
+    Run(DefaultRunArgs),
+    RunNode(DefaultRunNodeArgs),
+    Clean(DefaultCleanArgs),
+    Completions(DefaultCompletionsArgs),
+    Version(DefaultVersionArgs),
+    #[cfg(feature = "scaffold")]
+    New(crate::DefaultNewArgs),
+    #[cfg(feature = "deploy")]
+    Deploy(crate::DefaultDeployArgs),
+    #[structopt(flatten)]
+    Other(T),
+}
+
+#[derive(StructOpt)]
+struct RunnerServeCli {
+    // NOTE: already parsed by `RunnerCli`
+    #[allow(dead_code)]
+    #[structopt(flatten)]
+    verbosity: Verbosity,
+    #[structopt(flatten)]
+    args: DefaultServeArgs,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Initialize the project for the tests. Only the first initialization of the test binary has
+    /// an effect so all the tests that need the project must use this one.
+    pub(crate) fn init_project() -> (&'static Metadata, &'static Package) {
+        Runner::new(env!("CARGO_MANIFEST_DIR"))
+            .package("wasmbl")
+            .default_build_path(|metadata, _package| metadata.target_directory.join("runner"))
+            .init()
+            .unwrap()
+    }
+
+    #[test]
+    fn init_selects_the_frontend_package() {
+        let (metadata, package) = init_project();
+
+        assert_eq!(package.name, "wasmbl");
+        assert_eq!(
+            DefaultBuildArgs::default().build_path(),
+            metadata.target_directory.join("runner"),
+        );
+
+        // NOTE: only the first call has an effect
+        let (_, package) = Runner::new(env!("CARGO_MANIFEST_DIR"))
+            .package("wasmbl-proc-macro")
+            .init()
+            .unwrap();
+        assert_eq!(package.name, "wasmbl");
+    }
+
+    #[test]
+    fn init_again_after_a_failure() {
+        // NOTE: it only fails if no other test has initialized the project yet
+        let res = Runner::new(env!("CARGO_MANIFEST_DIR"))
+            .package("not-a-package")
+            .init();
+        if let Err(err) = res {
+            assert!(format!("{:#}", anyhow::Error::from(err)).contains("not-a-package"));
+        }
+
+        let (_, package) = init_project();
+        assert_eq!(package.name, "wasmbl");
+    }
+
+    #[test]
+    fn manifest_outside_the_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+        let err = check_manifest(&manifest_path, true).unwrap_err();
+        assert!(
+            matches!(&err, Error::OutsideWorkspace { manifest_path: x, from_env: true, .. } if *x == manifest_path),
+            "{:?}",
+            err
+        );
+
+        std::fs::write(&manifest_path, "").unwrap();
+        assert!(check_manifest(&manifest_path, true).is_ok());
+    }
+
+    #[test]
+    fn run_default_commands() {
+        init_project();
+        run(["wasmbl", "version"], Hooks::default()).unwrap();
+    }
+}
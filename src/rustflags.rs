@@ -0,0 +1,157 @@
+//! The flags given to rustc by the build commands.
+//!
+//! The flags of the environment (`RUSTFLAGS` or `CARGO_ENCODED_RUSTFLAGS`) are always kept: the
+//! flags added by wasmbl and by the hooks are appended to them and given to cargo with
+//! `CARGO_ENCODED_RUSTFLAGS` so the flags that contain spaces survive.
+
+use std::ffi::OsStr;
+use std::process::Command;
+
+const RUSTFLAGS_VAR: &str = "RUSTFLAGS";
+const ENCODED_RUSTFLAGS_VAR: &str = "CARGO_ENCODED_RUSTFLAGS";
+
+/// Separator of the flags in `CARGO_ENCODED_RUSTFLAGS`.
+const SEPARATOR: &str = "\x1f";
+
+/// Append flags to the flags given to rustc by a cargo command.
+///
+/// The flags already set on the command are kept, or the flags of the environment if there is
+/// none: setting `RUSTFLAGS` on the command would replace the flags the user has exported.
+///
+/// ```
+/// use std::process::Command;
+///
+/// let mut command = Command::new("cargo");
+/// wasmbl::append_rustflags(&mut command, ["--cfg", "web_sys_unstable_apis"]);
+/// ```
+pub fn append_rustflags<I, S>(command: &mut Command, flags: I)
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut rustflags = command_rustflags(command).unwrap_or_else(env_rustflags);
+    rustflags.extend(flags.into_iter().map(Into::into));
+    set_rustflags(command, &rustflags);
+}
+
+/// Merge the flags of the environment with the flags set on the command (by a hook for example)
+/// and log the flags given to rustc.
+///
+/// The flags of the command that already start with the flags of the environment are kept
+/// as-is, so a hook that appended them itself does not get them twice.
+pub(crate) fn merge_rustflags(command: &mut Command) {
+    let rustflags = merge_with(command, env_rustflags());
+    log::debug!("Effective rustflags: {:?}", rustflags);
+}
+
+fn merge_with(command: &mut Command, env: Vec<String>) -> Vec<String> {
+    let rustflags = match command_rustflags(command) {
+        Some(flags) if flags.starts_with(&env) => flags,
+        Some(flags) => env.into_iter().chain(flags).collect(),
+        None => return env,
+    };
+    set_rustflags(command, &rustflags);
+    rustflags
+}
+
+/// The flags given to rustc by the environment, `CARGO_ENCODED_RUSTFLAGS` first like cargo does.
+fn env_rustflags() -> Vec<String> {
+    parse_rustflags(
+        std::env::var(ENCODED_RUSTFLAGS_VAR).ok().as_deref(),
+        std::env::var(RUSTFLAGS_VAR).ok().as_deref(),
+    )
+}
+
+/// The flags set on the command, `None` if it doesn't override the flags of the environment.
+fn command_rustflags(command: &Command) -> Option<Vec<String>> {
+    let var = |name: &str| {
+        command
+            .get_envs()
+            .find(|(key, _)| *key == OsStr::new(name))
+            .and_then(|(_, value)| value)
+            .map(|x| x.to_string_lossy().into_owned())
+    };
+    let encoded = var(ENCODED_RUSTFLAGS_VAR);
+    let plain = var(RUSTFLAGS_VAR);
+    if encoded.is_none() && plain.is_none() {
+        return None;
+    }
+
+    Some(parse_rustflags(encoded.as_deref(), plain.as_deref()))
+}
+
+fn parse_rustflags(encoded: Option<&str>, plain: Option<&str>) -> Vec<String> {
+    match (encoded, plain) {
+        (Some(""), _) => Vec::new(),
+        (Some(encoded), _) => encoded.split(SEPARATOR).map(String::from).collect(),
+        (None, Some(plain)) => plain.split_whitespace().map(String::from).collect(),
+        (None, None) => Vec::new(),
+    }
+}
+
+fn set_rustflags(command: &mut Command, flags: &[String]) {
+    command
+        .env_remove(RUSTFLAGS_VAR)
+        .env(ENCODED_RUSTFLAGS_VAR, flags.join(SEPARATOR));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_encoded_rustflags_first() {
+        assert_eq!(
+            parse_rustflags(Some("-C\x1flink-arg=a b"), Some("--cfg x")),
+            ["-C", "link-arg=a b"]
+        );
+        assert_eq!(
+            parse_rustflags(None, Some(" --cfg  tokio_unstable ")),
+            ["--cfg", "tokio_unstable"]
+        );
+        assert!(parse_rustflags(Some(""), Some("--cfg x")).is_empty());
+        assert!(parse_rustflags(None, None).is_empty());
+    }
+
+    #[test]
+    fn merge_with_env_rustflags() {
+        let env = || vec!["--cfg".to_string(), "tokio_unstable".to_string()];
+
+        let mut command = Command::new("cargo");
+        assert_eq!(merge_with(&mut command, env()), env());
+        assert!(command_rustflags(&command).is_none());
+
+        command.env(RUSTFLAGS_VAR, "-C debuginfo=2");
+        assert_eq!(
+            merge_with(&mut command, env()),
+            ["--cfg", "tokio_unstable", "-C", "debuginfo=2"]
+        );
+
+        // NOTE: the flags of the environment are not added twice
+        let mut command = Command::new("cargo");
+        command.env(RUSTFLAGS_VAR, "--cfg tokio_unstable -C debuginfo=2");
+        assert_eq!(
+            merge_with(&mut command, env()),
+            ["--cfg", "tokio_unstable", "-C", "debuginfo=2"]
+        );
+        assert_eq!(
+            command_rustflags(&command).unwrap(),
+            ["--cfg", "tokio_unstable", "-C", "debuginfo=2"]
+        );
+    }
+
+    #[test]
+    fn append_to_command_rustflags() {
+        let mut command = Command::new("cargo");
+        command.env(RUSTFLAGS_VAR, "--cfg a");
+        append_rustflags(&mut command, ["-C", "link-arg=x y"]);
+
+        assert_eq!(
+            command_rustflags(&command).unwrap(),
+            ["--cfg", "a", "-C", "link-arg=x y"]
+        );
+        assert!(command
+            .get_envs()
+            .any(|(key, value)| key == RUSTFLAGS_VAR && value.is_none()));
+    }
+}
@@ -0,0 +1,225 @@
+//! The `new` command: create a new frontend package in the workspace.
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use structopt::StructOpt;
+use toml_edit::{Array, DocumentMut, Item, Table, Value};
+
+/// The starting point of a new frontend package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    /// A library that logs a message in the console of the browser.
+    Minimal,
+    /// A Yew application with a single component.
+    Yew,
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "minimal" => Ok(Self::Minimal),
+            "yew" => Ok(Self::Yew),
+            _ => bail!("unknown template `{}`, expected `minimal` or `yew`", s),
+        }
+    }
+}
+
+/// New frontend package arguments.
+#[derive(StructOpt, Debug)]
+pub struct DefaultNewArgs {
+    /// Name of the package. It is created in a directory of the same name at the root of the
+    /// workspace.
+    pub name: String,
+
+    /// Template of the package: `minimal` or `yew`.
+    #[structopt(long, default_value = "minimal", possible_values = &["minimal", "yew"])]
+    pub template: Template,
+}
+
+impl DefaultNewArgs {
+    /// Run the `new` command.
+    pub fn run(self) -> Result<()> {
+        let workspace_root = crate::Project::get().metadata.workspace_root.clone();
+        let package_path = workspace_root.join(&self.name);
+
+        if package_path.exists() {
+            bail!(
+                "could not create the package: `{}` already exists",
+                package_path.display()
+            );
+        }
+
+        log::info!("Creating package `{}`", self.name);
+        create_package(&package_path, &self.name, self.template)?;
+
+        let manifest_path = workspace_root.join("Cargo.toml");
+        let manifest = fs::read_to_string(&manifest_path)
+            .with_context(|| format!("could not read `{}`", manifest_path.display()))?;
+        let manifest = add_workspace_member(&manifest, &self.name)?;
+        fs::write(&manifest_path, manifest)
+            .with_context(|| format!("could not write `{}`", manifest_path.display()))?;
+
+        log::info!(
+            "Package `{}` added to the workspace in `{}`",
+            self.name,
+            package_path.display()
+        );
+
+        Ok(())
+    }
+}
+
+fn create_package(path: &Path, name: &str, template: Template) -> Result<()> {
+    let (dependencies, lib) = match template {
+        Template::Minimal => (MINIMAL_DEPENDENCIES, MINIMAL_LIB),
+        Template::Yew => (YEW_DEPENDENCIES, YEW_LIB),
+    };
+
+    let files = [
+        (
+            path.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+                [lib]\ncrate-type = [\"cdylib\"]\n\n[dependencies]\n{}",
+                name, dependencies,
+            ),
+        ),
+        (path.join("src").join("lib.rs"), lib.to_string()),
+        (path.join("static").join("index.html"), INDEX.to_string()),
+    ];
+
+    for (file_path, content) in files.iter() {
+        let dir = file_path
+            .parent()
+            .expect("the files are in the package; qed");
+        fs::create_dir_all(dir)
+            .with_context(|| format!("could not create directory `{}`", dir.display()))?;
+        fs::write(file_path, content)
+            .with_context(|| format!("could not write `{}`", file_path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Add a member to the workspace of a manifest, keeping its formatting.
+fn add_workspace_member(manifest: &str, member: &str) -> Result<String> {
+    let mut document: DocumentMut = manifest
+        .parse()
+        .context("could not parse the manifest of the workspace")?;
+
+    let workspace = document
+        .entry("workspace")
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_mut()
+        .context("`workspace` is not a table in the manifest of the workspace")?;
+    let members = workspace
+        .entry("members")
+        .or_insert_with(|| Item::Value(Value::Array(Array::new())))
+        .as_array_mut()
+        .context("`workspace.members` is not an array in the manifest of the workspace")?;
+
+    if !members.iter().any(|x| x.as_str() == Some(member)) {
+        // NOTE: the new member is indented like the previous one
+        let prefix = members
+            .iter()
+            .last()
+            .and_then(|x| x.decor().prefix().cloned());
+        members.push(member);
+        if let (Some(prefix), Some(last)) = (prefix, members.iter_mut().last()) {
+            last.decor_mut().set_prefix(prefix);
+        }
+    }
+
+    Ok(document.to_string())
+}
+
+const MINIMAL_DEPENDENCIES: &str = "wasm-bindgen = \"0.2\"\n";
+
+const MINIMAL_LIB: &str = r#"use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console)]
+    fn log(message: &str);
+}
+
+#[wasm_bindgen(start)]
+pub fn run_app() {
+    log("Hello World!");
+}
+"#;
+
+const YEW_DEPENDENCIES: &str =
+    "wasm-bindgen = \"0.2\"\nyew = { version = \"0.21\", features = [\"csr\"] }\n";
+
+const YEW_LIB: &str = r#"use wasm_bindgen::prelude::*;
+use yew::prelude::*;
+
+#[function_component]
+fn App() -> Html {
+    html! {
+        <h1>{ "Hello World!" }</h1>
+    }
+}
+
+#[wasm_bindgen(start)]
+pub fn run_app() {
+    yew::Renderer::<App>::new().render();
+}
+"#;
+
+const INDEX: &str = r#"<!DOCTYPE html>
+<html>
+    <head>
+        <meta charset="utf-8"/>
+        <script type="module">
+            import init from "/app.js";
+            init(new URL("app_bg.wasm", import.meta.url));
+        </script>
+    </head>
+    <body></body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_member_to_workspace() {
+        let manifest =
+            "[package]\nname = \"app\"\n\n[workspace]\nmembers = [\n    \"backend\",\n]\n";
+        let manifest = add_workspace_member(manifest, "frontend").unwrap();
+        assert_eq!(
+            manifest,
+            "[package]\nname = \"app\"\n\n[workspace]\nmembers = [\n    \"backend\",\n    \"frontend\",\n]\n",
+        );
+        assert_eq!(
+            add_workspace_member(&manifest, "frontend").unwrap(),
+            manifest
+        );
+
+        let manifest = add_workspace_member("[package]\nname = \"app\"\n", "frontend").unwrap();
+        assert_eq!(
+            manifest,
+            "[package]\nname = \"app\"\n\n[workspace]\nmembers = [\"frontend\"]\n",
+        );
+    }
+
+    #[test]
+    fn create_minimal_package() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("my-frontend");
+        create_package(&path, "my-frontend", Template::Minimal).unwrap();
+
+        let manifest = fs::read_to_string(path.join("Cargo.toml")).unwrap();
+        assert!(manifest.contains("name = \"my-frontend\""));
+        assert!(manifest.contains("crate-type = [\"cdylib\"]"));
+        assert!(path.join("src").join("lib.rs").exists());
+        assert!(path.join("static").join("index.html").exists());
+    }
+}
@@ -0,0 +1,343 @@
+//! The configuration of the static hosts written in the build directory with the build
+//! (`--emit-server-config`).
+//!
+//! The configuration follows the files of the build: the WASM is served as `application/wasm`, the
+//! files renamed by the `asset_filter` hook (a hash in their names) are cached forever, the files
+//! precompressed next to the originals (`.br`, `.gz`) are served to the browsers that accept them
+//! and the paths that are not files fall back to `index.html` if there is one.
+
+use anyhow::{bail, Result};
+
+/// First line of the generated files, as a comment.
+const GENERATED_BY: &str = "Generated by wasmbl: this file is written again by every build.";
+
+/// `Cache-Control` of the hashed files.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// A static host for which a configuration can be written in the build directory. See
+/// [`crate::BuildArgs::emit_server_config`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ServerConfig {
+    /// The `location` blocks of nginx in `nginx.conf`, to include in the `server` block that serves
+    /// the build directory at [`crate::BuildArgs::base_url`].
+    Nginx,
+    /// A snippet in `Caddyfile`, to import in the site block that serves the build directory at
+    /// [`crate::BuildArgs::base_url`].
+    Caddy,
+    /// The files `_headers` and `_redirects` of Netlify.
+    Netlify,
+    /// The file `vercel.json` of Vercel. It has no header: JSON has no comment and Vercel rejects
+    /// the unknown keys.
+    Vercel,
+}
+
+impl std::str::FromStr for ServerConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "nginx" => Ok(Self::Nginx),
+            "caddy" => Ok(Self::Caddy),
+            "netlify" => Ok(Self::Netlify),
+            "vercel" => Ok(Self::Vercel),
+            _ => bail!(
+                "unknown server `{}`, expected `nginx`, `caddy`, `netlify` or `vercel`",
+                s
+            ),
+        }
+    }
+}
+
+/// What the configuration is generated from.
+#[derive(Debug, Default)]
+pub(crate) struct Site {
+    /// URL path where the build directory is deployed, with a `/` at both ends.
+    pub base_url: String,
+    /// Paths of the WASM files, relative to the build directory and separated by `/`.
+    pub wasm: Vec<String>,
+    /// Paths of the files renamed by the `asset_filter` hook.
+    pub hashed: Vec<String>,
+    /// Encodings of the precompressed files (`br`, `gzip`).
+    pub precompressed: Vec<&'static str>,
+    /// There is an `index.html` to fall back to.
+    pub spa: bool,
+}
+
+impl Site {
+    /// The files of the configuration of the host: their names in the build directory and their
+    /// contents.
+    pub fn config(&self, server: ServerConfig) -> Vec<(&'static str, String)> {
+        match server {
+            ServerConfig::Nginx => vec![("nginx.conf", self.nginx())],
+            ServerConfig::Caddy => vec![("Caddyfile", self.caddy())],
+            ServerConfig::Netlify => {
+                let mut files = vec![("_headers", self.netlify_headers())];
+                if self.spa {
+                    files.push((
+                        "_redirects",
+                        format!(
+                            "# {}\n{}*  {}index.html  200\n",
+                            GENERATED_BY, self.base_url, self.base_url
+                        ),
+                    ));
+                }
+                files
+            }
+            ServerConfig::Vercel => vec![("vercel.json", self.vercel())],
+        }
+    }
+
+    /// The paths with specific headers and if they are hashed and WASM, sorted.
+    fn paths(&self) -> Vec<(String, bool, bool)> {
+        let mut paths: Vec<_> = self.hashed.iter().chain(self.wasm.iter()).collect();
+        paths.sort();
+        paths.dedup();
+        paths
+            .into_iter()
+            .map(|x| {
+                (
+                    format!("{}{}", self.base_url, x),
+                    self.hashed.contains(x),
+                    self.wasm.contains(x),
+                )
+            })
+            .collect()
+    }
+
+    fn nginx(&self) -> String {
+        let precompressed: String = self
+            .precompressed
+            .iter()
+            .map(|x| match *x {
+                "br" => "    brotli_static on;\n",
+                _ => "    gzip_static on;\n",
+            })
+            .collect();
+
+        let mut config = format!(
+            "# {}\n#\n# Include it in the `server` block that serves the build directory at `{}`.\n",
+            GENERATED_BY, self.base_url
+        );
+        if self.precompressed.contains(&"br") {
+            config.push_str("# `brotli_static` requires the module `ngx_brotli`.\n");
+        }
+        // NOTE: the exact locations take precedence, they need the precompressed files too
+        for (path, hashed, wasm) in self.paths() {
+            config.push_str(&format!("\nlocation = {:?} {{\n", path));
+            if wasm {
+                config.push_str("    types { application/wasm wasm; }\n");
+            }
+            if hashed {
+                config.push_str(&format!("    add_header Cache-Control {:?};\n", IMMUTABLE));
+            }
+            config.push_str(&precompressed);
+            config.push_str("}\n");
+        }
+        config.push_str(&format!("\nlocation {:?} {{\n", self.base_url));
+        config.push_str(&precompressed);
+        if self.spa {
+            config.push_str(&format!(
+                "    try_files $uri $uri/ {:?};\n",
+                format!("{}index.html", self.base_url)
+            ));
+        } else {
+            config.push_str("    try_files $uri $uri/ =404;\n");
+        }
+        config.push_str("}\n");
+        config
+    }
+
+    fn caddy(&self) -> String {
+        let mut config = format!(
+            "# {}\n#\n# Import it in the site block that serves the build directory at `{}`.\n",
+            GENERATED_BY, self.base_url
+        );
+        let paths = self.paths();
+        let matcher = |name: &str, filter: fn(&(String, bool, bool)) -> bool| {
+            let paths: Vec<_> = paths
+                .iter()
+                .filter(|x| filter(x))
+                .map(|(path, _, _)| format!("{:?}", path))
+                .collect();
+            (!paths.is_empty()).then(|| format!("\n@{} path {}\n", name, paths.join(" ")))
+        };
+        if let Some(hashed) = matcher("hashed", |x| x.1) {
+            config.push_str(&hashed);
+            config.push_str(&format!("header @hashed Cache-Control {:?}\n", IMMUTABLE));
+        }
+        if let Some(wasm) = matcher("wasm", |x| x.2) {
+            config.push_str(&wasm);
+            config.push_str("header @wasm Content-Type application/wasm\n");
+        }
+        if self.spa {
+            config.push_str(&format!(
+                "\ntry_files {}* {{path}} {}index.html\n",
+                self.base_url, self.base_url
+            ));
+        }
+        config.push_str("\nfile_server");
+        if !self.precompressed.is_empty() {
+            config.push_str(&format!(
+                " {{\n    precompressed {}\n}}",
+                self.precompressed.join(" ")
+            ));
+        }
+        config.push('\n');
+        config
+    }
+
+    fn netlify_headers(&self) -> String {
+        let mut config = format!("# {}\n", GENERATED_BY);
+        for (path, hashed, wasm) in self.paths() {
+            config.push_str(&format!("{}\n", path));
+            if wasm {
+                config.push_str("  Content-Type: application/wasm\n");
+            }
+            if hashed {
+                config.push_str(&format!("  Cache-Control: {}\n", IMMUTABLE));
+            }
+        }
+        config
+    }
+
+    fn vercel(&self) -> String {
+        let headers: Vec<_> = self
+            .paths()
+            .into_iter()
+            .map(|(path, hashed, wasm)| {
+                let headers: Vec<_> = vec![
+                    wasm.then_some(("Content-Type", "application/wasm")),
+                    hashed.then_some(("Cache-Control", IMMUTABLE)),
+                ]
+                .into_iter()
+                .flatten()
+                .map(|(key, value)| serde_json::json!({ "key": key, "value": value }))
+                .collect();
+                serde_json::json!({ "source": path, "headers": headers })
+            })
+            .collect();
+
+        let mut config = serde_json::json!({ "headers": headers });
+        // NOTE: the rewrites only apply to the paths that are not files
+        if self.spa {
+            config["rewrites"] = serde_json::json!([{
+                "source": format!("{}(.*)", self.base_url),
+                "destination": format!("{}index.html", self.base_url),
+            }]);
+        }
+        let mut config = serde_json::to_string_pretty(&config).expect("valid JSON; qed");
+        config.push('\n');
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site() -> Site {
+        Site {
+            base_url: "/app/".to_string(),
+            wasm: vec!["app_bg-5678.wasm".to_string()],
+            hashed: vec!["app-1234.js".to_string(), "app_bg-5678.wasm".to_string()],
+            precompressed: vec!["gzip"],
+            spa: true,
+        }
+    }
+
+    #[test]
+    fn nginx_locations() {
+        let config = &site().config(ServerConfig::Nginx)[0];
+        assert_eq!(config.0, "nginx.conf");
+        assert_eq!(
+            config.1,
+            format!(
+                "# {}\n#\n# Include it in the `server` block that serves the build directory at \
+                `/app/`.\n\
+                \n\
+                location = \"/app/app-1234.js\" {{\n    \
+                    add_header Cache-Control \"{}\";\n    \
+                    gzip_static on;\n\
+                }}\n\
+                \n\
+                location = \"/app/app_bg-5678.wasm\" {{\n    \
+                    types {{ application/wasm wasm; }}\n    \
+                    add_header Cache-Control \"{}\";\n    \
+                    gzip_static on;\n\
+                }}\n\
+                \n\
+                location \"/app/\" {{\n    \
+                    gzip_static on;\n    \
+                    try_files $uri $uri/ \"/app/index.html\";\n\
+                }}\n",
+                GENERATED_BY, IMMUTABLE, IMMUTABLE
+            )
+        );
+    }
+
+    #[test]
+    fn caddy_snippet() {
+        let site = Site {
+            base_url: "/".to_string(),
+            wasm: vec!["app_bg.wasm".to_string()],
+            hashed: Vec::new(),
+            precompressed: vec!["br", "gzip"],
+            spa: false,
+        };
+        let config = &site.config(ServerConfig::Caddy)[0].1;
+        assert!(config.starts_with(&format!("# {}\n", GENERATED_BY)));
+        assert!(!config.contains("@hashed") && !config.contains("try_files"));
+        assert!(config.ends_with(
+            "\n@wasm path \"/app_bg.wasm\"\nheader @wasm Content-Type application/wasm\n\n\
+            file_server {\n    precompressed br gzip\n}\n"
+        ));
+
+        let config = &self::site().config(ServerConfig::Caddy)[0].1;
+        assert!(config.contains(&format!(
+            "@hashed path \"/app/app-1234.js\" \"/app/app_bg-5678.wasm\"\n\
+            header @hashed Cache-Control \"{}\"\n",
+            IMMUTABLE
+        )));
+        assert!(config.contains("\ntry_files /app/* {path} /app/index.html\n"));
+    }
+
+    #[test]
+    fn netlify_and_vercel_files() {
+        let files = site().config(ServerConfig::Netlify);
+        assert_eq!(
+            files,
+            [
+                (
+                    "_headers",
+                    format!(
+                        "# {}\n/app/app-1234.js\n  Cache-Control: {}\n/app/app_bg-5678.wasm\n  \
+                        Content-Type: application/wasm\n  Cache-Control: {}\n",
+                        GENERATED_BY, IMMUTABLE, IMMUTABLE
+                    )
+                ),
+                (
+                    "_redirects",
+                    format!("# {}\n/app/*  /app/index.html  200\n", GENERATED_BY)
+                ),
+            ]
+        );
+
+        let files = site().config(ServerConfig::Vercel);
+        let json: serde_json::Value = serde_json::from_str(&files[0].1).unwrap();
+        assert_eq!(files[0].0, "vercel.json");
+        assert_eq!(
+            json["headers"][1],
+            serde_json::json!({
+                "source": "/app/app_bg-5678.wasm",
+                "headers": [
+                    { "key": "Content-Type", "value": "application/wasm" },
+                    { "key": "Cache-Control", "value": IMMUTABLE },
+                ],
+            })
+        );
+        assert_eq!(
+            json["rewrites"],
+            serde_json::json!([{ "source": "/app/(.*)", "destination": "/app/index.html" }])
+        );
+    }
+}
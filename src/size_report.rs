@@ -0,0 +1,736 @@
+//! The sizes of the files of the builds: the summary printed at the end of the builds and the
+//! comparison with a previous build (`build --size-compare`).
+//!
+//! The previous build is a build directory or a manifest of its sizes written by
+//! `build --size-manifest`: `{"files": {"<path>": {"size": <bytes>, "gzip": <bytes>, "brotli":
+//! <bytes>}}}`. The paths are relative to the build directory and separated by `/`.
+//!
+//! The compressed sizes are only measured for the release builds. There is no brotli encoder in
+//! the dependencies: the brotli size comes from the program `brotli` if it is installed, or from
+//! the file precompressed next to the original (`.br`) if there is one.
+
+use anyhow::{bail, Context, Result};
+use once_cell::sync::{Lazy, OnceCell};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+/// The sizes of the files of the last build, kept for the `build` command.
+static LAST_BUILD: Lazy<Mutex<Option<Sizes>>> = Lazy::new(Default::default);
+
+/// The size of a file, raw and compressed with gzip and brotli.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct FileSize {
+    size: u64,
+    gzip: Option<u64>,
+    brotli: Option<u64>,
+}
+
+impl FileSize {
+    /// The sizes of a file compressed with gzip and brotli.
+    fn of(path: &Path, content: &[u8]) -> Result<Self> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(content)?;
+
+        Ok(Self {
+            size: content.len() as u64,
+            gzip: Some(encoder.finish()?.len() as u64),
+            brotli: brotli_size(path),
+        })
+    }
+
+    fn gzip(self) -> u64 {
+        self.gzip.unwrap_or(0)
+    }
+}
+
+/// The size of the file compressed by the program `brotli`, or of the precompressed file.
+fn brotli_size(path: &Path) -> Option<u64> {
+    static HAS_BROTLI: OnceCell<bool> = OnceCell::new();
+    let has_brotli = *HAS_BROTLI.get_or_init(|| {
+        let found = Command::new("brotli")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|x| x.success())
+            .unwrap_or(false);
+        if !found {
+            log::debug!("The program `brotli` was not found, the brotli sizes are not measured");
+        }
+        found
+    });
+
+    if has_brotli {
+        let output = Command::new("brotli")
+            .args(["--stdout", "--best"])
+            .arg(path)
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|x| x.status.success())?;
+        return Some(output.stdout.len() as u64);
+    }
+
+    let mut precompressed = path.as_os_str().to_owned();
+    precompressed.push(".br");
+    fs::metadata(precompressed).ok().map(|x| x.len())
+}
+
+/// The sizes of the files of a build, by path.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Sizes(BTreeMap<String, FileSize>);
+
+impl Sizes {
+    /// The sizes of the files of the current build, compressed or not.
+    pub fn of_build(build_path: &Path, compressed: bool) -> Result<Self> {
+        let files = crate::build_files::recorded(build_path)
+            .context("could not find the files of the build")?;
+        Self::of_files(build_path, files.iter().map(AsRef::as_ref), compressed)
+    }
+
+    /// Keep the sizes of the last build, see [`Sizes::last_build`].
+    pub fn set_last_build(self) {
+        *LAST_BUILD.lock().unwrap() = Some(self);
+    }
+
+    /// The sizes of the last build, measured at its end.
+    pub fn last_build() -> Option<Self> {
+        LAST_BUILD.lock().unwrap().take()
+    }
+
+    /// The sizes of a previous build: a manifest or a build directory. Only the files of the list
+    /// of a build directory are measured if it has one.
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            let files = match crate::build_files::recorded(path) {
+                Ok(files) => files,
+                Err(_) => crate::list_files(path)
+                    .with_context(|| format!("could not list the files of `{}`", path.display()))?
+                    .into_iter()
+                    .filter_map(|x| x.strip_prefix(path).ok().map(Path::to_path_buf))
+                    .filter(|x| !crate::build_files::is_internal(x))
+                    .collect(),
+            };
+            return Self::of_files(path, files.iter().map(AsRef::as_ref), true);
+        }
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("could not read the size manifest `{}`", path.display()))?;
+        Self::from_manifest(&content)
+            .with_context(|| format!("invalid size manifest `{}`", path.display()))
+    }
+
+    fn of_files<'a>(
+        dir: &Path,
+        files: impl Iterator<Item = &'a Path>,
+        compressed: bool,
+    ) -> Result<Self> {
+        // NOTE: the files are compressed in parallel, the map sorts them by path
+        let files: Vec<_> = files.collect();
+        let sizes = files
+            .into_par_iter()
+            .map(|file| {
+                let path = dir.join(file);
+                let size = if compressed {
+                    let content = fs::read(&path)
+                        .with_context(|| format!("could not read `{}`", path.display()))?;
+                    FileSize::of(&path, &content)?
+                } else {
+                    let metadata = fs::metadata(&path)
+                        .with_context(|| format!("could not read `{}`", path.display()))?;
+                    FileSize {
+                        size: metadata.len(),
+                        ..FileSize::default()
+                    }
+                };
+                Ok((crate::slash_path(file), size))
+            })
+            .collect::<Result<BTreeMap<_, _>>>()?;
+
+        Ok(Self(sizes))
+    }
+
+    fn from_manifest(content: &str) -> Result<Self> {
+        let manifest: serde_json::Value = serde_json::from_str(content)?;
+        let files = manifest
+            .get("files")
+            .and_then(|x| x.as_object())
+            .context("the key `files` is not an object")?;
+
+        let mut sizes = BTreeMap::new();
+        for (path, file) in files {
+            let get = |key: &str| file.get(key).and_then(|x| x.as_u64());
+            sizes.insert(
+                path.clone(),
+                FileSize {
+                    size: get("size").with_context(|| format!("`{}` has no `size`", path))?,
+                    gzip: get("gzip"),
+                    brotli: get("brotli"),
+                },
+            );
+        }
+
+        Ok(Self(sizes))
+    }
+
+    /// The manifest of the sizes, read by [`Sizes::load`].
+    pub fn to_manifest(&self) -> String {
+        let files: serde_json::Map<_, _> = self
+            .0
+            .iter()
+            .map(|(path, x)| {
+                let mut file = serde_json::Map::new();
+                for (key, size) in [
+                    ("size", Some(x.size)),
+                    ("gzip", x.gzip),
+                    ("brotli", x.brotli),
+                ] {
+                    if let Some(size) = size {
+                        file.insert(key.to_string(), size.into());
+                    }
+                }
+                (path.clone(), serde_json::Value::Object(file))
+            })
+            .collect();
+
+        serde_json::to_string_pretty(&serde_json::json!({ "files": files }))
+            .expect("a JSON value; qed")
+    }
+
+    fn total(&self) -> FileSize {
+        FileSize {
+            size: self.0.values().map(|x| x.size).sum(),
+            gzip: self.0.values().map(|x| x.gzip).sum(),
+            brotli: self.0.values().map(|x| x.brotli).sum(),
+        }
+    }
+
+    /// The table of the sizes printed at the end of the `build` command, with a total. The
+    /// compressed sizes are only shown if they have been measured and the WASM is in bold if
+    /// `color` is enabled.
+    pub fn summary(&self, wasm: &str, color: bool) -> String {
+        let total = self.total();
+        let size = |x: Option<u64>| {
+            x.map(|x| crate::format_size(x as usize))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let row = |path: &str, x: &FileSize| {
+            let mut row = vec![path.to_string(), size(Some(x.size))];
+            if total.gzip.is_some() {
+                row.push(size(x.gzip));
+                row.push(size(x.brotli));
+            }
+            row
+        };
+        let mut header = vec!["File", "Size"];
+        if total.gzip.is_some() {
+            header.extend(["Gzip", "Brotli"]);
+        }
+        let mut rows = vec![header.iter().map(|x| x.to_string()).collect::<Vec<_>>()];
+        rows.extend(self.0.iter().map(|(path, x)| row(path, x)));
+        rows.push(row("total", &total));
+
+        let mut widths = vec![0; header.len()];
+        for row in rows.iter() {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut table = String::new();
+        for row in rows.iter() {
+            let mut line = format!("{:<width$}", row[0], width = widths[0]);
+            for (cell, width) in row.iter().zip(widths.iter()).skip(1) {
+                line.push_str(&format!("  {:>width$}", cell, width = width));
+            }
+            if color && row[0] == wasm {
+                line = format!("\x1b[1m{}\x1b[0m", line);
+            }
+            table.push_str(&line);
+            table.push('\n');
+        }
+        table
+    }
+
+    /// The total of the sizes on a line, for the rebuilds: `3 files, 25 KB (9 KB gzip)`.
+    pub fn summary_line(&self) -> String {
+        let total = self.total();
+        let mut line = format!(
+            "{} files, {}",
+            self.0.len(),
+            crate::format_size(total.size as usize)
+        );
+        let compressed: Vec<_> = [("gzip", total.gzip), ("brotli", total.brotli)]
+            .iter()
+            .filter_map(|(name, x)| {
+                x.map(|x| format!("{} {}", crate::format_size(x as usize), name))
+            })
+            .collect();
+        if !compressed.is_empty() {
+            line.push_str(&format!(" ({})", compressed.join(", ")));
+        }
+        line
+    }
+}
+
+/// The format of the comparison of the sizes. See [`crate::BuildArgs::size_compare_format`].
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum SizeFormat {
+    /// A table aligned for the terminal.
+    #[default]
+    Table,
+    /// A JSON object with the files and the total.
+    Json,
+    /// A table of Markdown, to post in the comment of a pull request.
+    Markdown,
+}
+
+impl std::str::FromStr for SizeFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "markdown" => Ok(Self::Markdown),
+            _ => bail!(
+                "unknown format `{}`, expected `table`, `json` or `markdown`",
+                s
+            ),
+        }
+    }
+}
+
+/// The growth of the total gzip size above which the build fails. See
+/// [`crate::BuildArgs::size_compare_fail`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SizeThreshold {
+    /// A number of bytes: `2048`, `10KB`, `1MB`.
+    Bytes(u64),
+    /// A percentage of the previous total: `5%`.
+    Percent(f64),
+}
+
+impl std::str::FromStr for SizeThreshold {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(percent) = s.strip_suffix('%') {
+            return match percent.trim().parse::<f64>() {
+                Ok(percent) if percent >= 0.0 => Ok(Self::Percent(percent)),
+                _ => bail!("invalid percentage `{}`", s),
+            };
+        }
+
+        let upper = s.to_ascii_uppercase();
+        let (number, unit) = [("MB", 1024 * 1024), ("KB", 1024), ("B", 1)]
+            .iter()
+            .find_map(|(suffix, unit)| upper.strip_suffix(suffix).map(|x| (x, *unit)))
+            .unwrap_or((&upper, 1));
+        match number.trim().parse::<f64>() {
+            Ok(number) if number >= 0.0 => Ok(Self::Bytes((number * unit as f64).round() as u64)),
+            _ => bail!(
+                "invalid threshold `{}`, expected a size (`10KB`) or a percentage (`5%`)",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for SizeThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bytes(bytes) => write!(f, "{}", crate::format_size(*bytes as usize)),
+            Self::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// How a file changed since the previous build.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Status {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Changed => "changed",
+        }
+    }
+}
+
+/// A file that is not the same size as in the previous build.
+#[derive(Debug, PartialEq, Eq)]
+struct Row {
+    path: String,
+    status: Status,
+    previous: Option<FileSize>,
+    current: Option<FileSize>,
+}
+
+/// The comparison of the sizes of two builds.
+#[derive(Debug)]
+pub(crate) struct Report {
+    rows: Vec<Row>,
+    previous: FileSize,
+    current: FileSize,
+}
+
+impl Report {
+    pub fn new(previous: &Sizes, current: &Sizes) -> Self {
+        let mut paths: Vec<_> = previous.0.keys().chain(current.0.keys()).collect();
+        paths.sort();
+        paths.dedup();
+
+        let rows = paths
+            .into_iter()
+            .filter_map(|path| {
+                let before = previous.0.get(path).copied();
+                let after = current.0.get(path).copied();
+                let status = match (before, after) {
+                    (None, Some(_)) => Status::Added,
+                    (Some(_), None) => Status::Removed,
+                    (Some(before), Some(after)) if before != after => Status::Changed,
+                    _ => return None,
+                };
+                Some(Row {
+                    path: path.clone(),
+                    status,
+                    previous: before,
+                    current: after,
+                })
+            })
+            .collect();
+
+        Self {
+            rows,
+            previous: previous.total(),
+            current: current.total(),
+        }
+    }
+
+    /// The report in a format, ending with a new line.
+    pub fn render(&self, format: SizeFormat) -> String {
+        match format {
+            SizeFormat::Table => self.table(),
+            SizeFormat::Json => self.json(),
+            SizeFormat::Markdown => self.markdown(),
+        }
+    }
+
+    /// The cells of the rows then of the total: path, status, raw size, its delta, gzip size and
+    /// its delta.
+    fn cells(&self) -> Vec<[String; 6]> {
+        let size = |x: Option<FileSize>, f: fn(FileSize) -> u64| {
+            x.map(|x| crate::format_size(f(x) as usize))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let row =
+            |path: &str, status: &str, previous: Option<FileSize>, current: Option<FileSize>| {
+                let delta = |f: fn(FileSize) -> u64| {
+                    let before = previous.map(f).unwrap_or(0);
+                    let after = current.map(f).unwrap_or(0);
+                    delta(before, after)
+                };
+                [
+                    path.to_string(),
+                    status.to_string(),
+                    size(current, |x| x.size),
+                    delta(|x| x.size),
+                    size(current, FileSize::gzip),
+                    delta(FileSize::gzip),
+                ]
+            };
+
+        self.rows
+            .iter()
+            .map(|x| row(&x.path, x.status.as_str(), x.previous, x.current))
+            .chain(std::iter::once(row(
+                "total",
+                "",
+                Some(self.previous),
+                Some(self.current),
+            )))
+            .collect()
+    }
+
+    fn table(&self) -> String {
+        const HEADER: [&str; 6] = ["File", "Status", "Size", "Delta", "Gzip", "Delta"];
+        let cells = self.cells();
+        let mut widths = HEADER.map(str::len);
+        for row in cells.iter() {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let line = |row: &[&str]| {
+            let mut line = String::new();
+            for (i, (cell, width)) in row.iter().zip(widths.iter()).enumerate() {
+                if i < 2 {
+                    line.push_str(&format!("{:<width$}  ", cell, width = width));
+                } else {
+                    line.push_str(&format!("{:>width$}  ", cell, width = width));
+                }
+            }
+            format!("{}\n", line.trim_end())
+        };
+        let mut table = line(&HEADER);
+        for row in cells.iter() {
+            table.push_str(&line(&row.iter().map(String::as_str).collect::<Vec<_>>()));
+        }
+        table
+    }
+
+    fn markdown(&self) -> String {
+        let mut markdown = "| File | Status | Size | Delta | Gzip | Delta |\n\
+            | :--- | :--- | ---: | ---: | ---: | ---: |\n"
+            .to_string();
+        let cells = self.cells();
+        let last = cells.len() - 1;
+        for (i, row) in cells.iter().enumerate() {
+            let escape = |x: &str| x.replace('|', "\\|");
+            let row: Vec<_> = row.iter().map(|x| escape(x)).collect();
+            if i == last {
+                markdown.push_str(&format!(
+                    "| **{}** | | **{}** | **{}** | **{}** | **{}** |\n",
+                    row[0], row[2], row[3], row[4], row[5]
+                ));
+            } else {
+                markdown.push_str(&format!("| `{}` | {} |\n", row[0], row[1..].join(" | ")));
+            }
+        }
+        markdown
+    }
+
+    fn json(&self) -> String {
+        let size = |x: Option<FileSize>, f: fn(FileSize) -> u64| x.map(f);
+        let files: Vec<_> = self
+            .rows
+            .iter()
+            .map(|x| {
+                serde_json::json!({
+                    "path": x.path,
+                    "status": x.status.as_str(),
+                    "previous_size": size(x.previous, |x| x.size),
+                    "previous_gzip": x.previous.and_then(|x| x.gzip),
+                    "size": size(x.current, |x| x.size),
+                    "gzip": x.current.and_then(|x| x.gzip),
+                })
+            })
+            .collect();
+        let total = serde_json::json!({
+            "previous_size": self.previous.size,
+            "previous_gzip": self.previous.gzip,
+            "size": self.current.size,
+            "gzip": self.current.gzip,
+        });
+
+        let mut json = serde_json::to_string_pretty(&serde_json::json!({
+            "files": files,
+            "total": total,
+        }))
+        .expect("a JSON value; qed");
+        json.push('\n');
+        json
+    }
+
+    /// Fail if the total gzip size grew more than the threshold.
+    pub fn check(&self, threshold: SizeThreshold) -> Result<()> {
+        let (previous, current) = (self.previous.gzip(), self.current.gzip());
+        let growth = current.saturating_sub(previous);
+        let exceeded = match threshold {
+            SizeThreshold::Bytes(bytes) => growth > bytes,
+            SizeThreshold::Percent(percent) => {
+                growth > 0 && (previous == 0 || growth as f64 * 100.0 / previous as f64 > percent)
+            }
+        };
+        if exceeded {
+            bail!(
+                "the total gzip size grew by {}, more than the threshold of {}",
+                delta(previous, current),
+                threshold
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// The difference between two sizes and its percentage of the previous size: `+1 KB (+5.0%)`.
+fn delta(before: u64, after: u64) -> String {
+    let (sign, bytes) = if after >= before {
+        ("+", after - before)
+    } else {
+        ("-", before - after)
+    };
+    if bytes == 0 {
+        return "0 B".to_string();
+    }
+    let size = format!("{}{}", sign, crate::format_size(bytes as usize));
+    let percent = format!("{:.1}", bytes as f64 * 100.0 / before.max(1) as f64);
+    if before == 0 || percent == "0.0" {
+        return size;
+    }
+
+    format!("{} ({}{}%)", size, sign, percent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sizes(files: &[(&str, u64, u64)]) -> Sizes {
+        Sizes(
+            files
+                .iter()
+                .map(|(path, size, gzip)| {
+                    (
+                        path.to_string(),
+                        FileSize {
+                            size: *size,
+                            gzip: Some(*gzip),
+                            brotli: None,
+                        },
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn parse_thresholds() {
+        for (s, threshold) in [
+            ("2048", SizeThreshold::Bytes(2048)),
+            ("10KB", SizeThreshold::Bytes(10240)),
+            ("1.5 mb", SizeThreshold::Bytes(1572864)),
+            ("12B", SizeThreshold::Bytes(12)),
+            ("5%", SizeThreshold::Percent(5.0)),
+        ] {
+            assert_eq!(s.parse::<SizeThreshold>().unwrap(), threshold, "{}", s);
+        }
+        for s in ["", "KB", "-1", "ten%", "5GB"] {
+            assert!(s.parse::<SizeThreshold>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn compare_sizes() {
+        let previous = sizes(&[
+            ("app.js", 1000, 400),
+            ("app_bg.wasm", 20000, 8000),
+            ("old.css", 100, 80),
+        ]);
+        let current = sizes(&[
+            ("app.js", 1000, 400),
+            ("app_bg.wasm", 30000, 12000),
+            ("new.css", 200, 100),
+        ]);
+        let report = Report::new(&previous, &current);
+        assert_eq!(
+            report
+                .rows
+                .iter()
+                .map(|x| (x.path.as_str(), x.status))
+                .collect::<Vec<_>>(),
+            [
+                ("app_bg.wasm", Status::Changed),
+                ("new.css", Status::Added),
+                ("old.css", Status::Removed),
+            ]
+        );
+
+        assert_eq!(
+            report.render(SizeFormat::Table),
+            "File         Status    Size             Delta   Gzip            Delta\n\
+            app_bg.wasm  changed  29 KB   +10 KB (+50.0%)  12 KB   +4 KB (+50.0%)\n\
+            new.css      added    200 B            +200 B  100 B           +100 B\n\
+            old.css      removed      -  -100 B (-100.0%)      -  -80 B (-100.0%)\n\
+            total                 30 KB   +10 KB (+47.9%)  12 KB   +4 KB (+47.4%)\n"
+        );
+        let markdown = report.render(SizeFormat::Markdown);
+        assert!(markdown.starts_with("| File | Status |"), "{}", markdown);
+        assert!(
+            markdown.ends_with(
+                "| `old.css` | removed | - | -100 B (-100.0%) | - | -80 B (-100.0%) |\n\
+                | **total** | | **30 KB** | **+10 KB (+47.9%)** | **12 KB** | **+4 KB (+47.4%)** |\n"
+            ),
+            "{}",
+            markdown
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&report.render(SizeFormat::Json)).unwrap();
+        assert_eq!(json["files"][1]["previous_size"], serde_json::Value::Null);
+        assert_eq!(json["total"]["gzip"], 12500);
+
+        assert!(report.check(SizeThreshold::Bytes(5000)).is_ok());
+        assert!(report.check(SizeThreshold::Bytes(4000)).is_err());
+        assert!(report.check(SizeThreshold::Percent(50.0)).is_ok());
+        assert!(report.check(SizeThreshold::Percent(40.0)).is_err());
+        assert_eq!(delta(100000, 100010), "+10 B");
+        assert!(Report::new(&current, &previous)
+            .check(SizeThreshold::Bytes(0))
+            .is_ok());
+    }
+
+    #[test]
+    fn summary_of_the_build() {
+        let mut sizes = sizes(&[("app.js", 6000, 1500), ("app_bg.wasm", 20000, 8000)]);
+        sizes.0.get_mut("app_bg.wasm").unwrap().brotli = Some(7000);
+        assert_eq!(
+            sizes.summary("app_bg.wasm", false),
+            "File          Size  Gzip  Brotli\n\
+            app.js        6 KB  1 KB       -\n\
+            app_bg.wasm  20 KB  8 KB    7 KB\n\
+            total        25 KB  9 KB       -\n"
+        );
+        assert!(sizes
+            .summary("app_bg.wasm", true)
+            .contains("\x1b[1mapp_bg.wasm  20 KB  8 KB    7 KB\x1b[0m\n"));
+        assert_eq!(sizes.summary_line(), "2 files, 25 KB (9 KB gzip)");
+
+        for x in sizes.0.values_mut() {
+            *x = FileSize {
+                size: x.size,
+                ..FileSize::default()
+            };
+        }
+        assert_eq!(
+            sizes.summary("app_bg.wasm", false),
+            "File          Size\napp.js        6 KB\napp_bg.wasm  20 KB\ntotal        25 KB\n"
+        );
+        assert_eq!(sizes.summary_line(), "2 files, 25 KB");
+    }
+
+    #[test]
+    fn size_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("snippets")).unwrap();
+        fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        fs::write(dir.path().join("snippets").join("a.js"), "").unwrap();
+
+        let sizes = Sizes::load(dir.path()).unwrap();
+        assert_eq!(
+            sizes.0.keys().collect::<Vec<_>>(),
+            ["index.html", "snippets/a.js"]
+        );
+        assert_eq!(sizes.0["index.html"].size, 13);
+        assert!(sizes.0["index.html"].gzip.is_some());
+
+        let manifest = dir.path().join("sizes.json");
+        fs::write(&manifest, sizes.to_manifest()).unwrap();
+        assert_eq!(Sizes::load(&manifest).unwrap(), sizes);
+        assert!(Sizes::from_manifest(r#"{"files": {"a.js": {"gzip": 1}}}"#).is_err());
+    }
+}
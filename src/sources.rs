@@ -0,0 +1,157 @@
+//! The Rust sources served to the debuggers of the browsers (`serve --serve-sources`).
+//!
+//! The paths of the sources embedded in the debug info of the WASM are remapped by rustc to URLs of
+//! the development server (`--remap-path-prefix`), which serves the `.rs` files of the workspace and
+//! of the dependencies of the registry and of git. Nothing else can be served: the paths that go
+//! outside of these directories are refused, even through a symlink.
+
+use once_cell::sync::OnceCell;
+use std::path::{Path, PathBuf};
+
+/// Path of the sources on the development server.
+const SOURCES_PATH: &str = "/__wasmbl/sources";
+
+/// The URL of [`SOURCES_PATH`] when the sources are served, set once by the `serve` command.
+static SOURCES_URL: OnceCell<String> = OnceCell::new();
+
+/// Remap the paths of the sources of the next dev builds to the server at this address.
+pub(crate) fn enable(addr: std::net::SocketAddr) {
+    // NOTE: the browser can't connect to the unspecified address
+    let url = crate::port::url(crate::port::connectable(addr));
+    let _ = SOURCES_URL.set(format!("{}{}", url, SOURCES_PATH));
+}
+
+/// The URL of the sources if they are served.
+pub(crate) fn url() -> Option<&'static str> {
+    SOURCES_URL.get().map(String::as_str)
+}
+
+/// The directories of the sources that can be served and their names in the URLs.
+pub(crate) fn roots(workspace_root: &Path) -> Vec<(&'static str, PathBuf)> {
+    let mut roots = vec![("workspace", workspace_root.to_owned())];
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+                .map(|x| PathBuf::from(x).join(".cargo"))
+        });
+    if let Some(cargo_home) = cargo_home {
+        roots.push(("registry", cargo_home.join("registry").join("src")));
+        roots.push(("git", cargo_home.join("git").join("checkouts")));
+    }
+    roots
+}
+
+/// The flags of rustc that remap the directories of the sources to their URLs.
+pub(crate) fn remap_flags(url: &str, roots: &[(&str, PathBuf)]) -> Vec<String> {
+    roots
+        .iter()
+        .flat_map(|(name, path)| {
+            vec![
+                "--remap-path-prefix".to_string(),
+                format!("{}={}/{}", path.display(), url, name),
+            ]
+        })
+        .collect()
+}
+
+/// The file of a URL path relative to [`SOURCES_PATH`], if it is a `.rs` file inside of its
+/// directory.
+fn resolve(roots: &[(&str, PathBuf)], url_path: &str) -> Option<PathBuf> {
+    let (name, path) = url_path.split_once('/')?;
+    let (_, root) = roots.iter().find(|(x, _)| *x == name)?;
+    let path = root.join(crate::path_from_url(path)?);
+    if path.extension()? != "rs" {
+        return None;
+    }
+
+    let real_path = path.canonicalize().ok()?;
+    real_path
+        .starts_with(root.canonicalize().ok()?)
+        .then_some(real_path)
+}
+
+/// Register the route of the sources on the server.
+pub(crate) fn register(server: &mut tide::Server<()>, roots: Vec<(&'static str, PathBuf)>) {
+    use tide::{http::mime, Body, Request, Response, StatusCode};
+
+    server
+        .at(&format!("{}/*path", SOURCES_PATH))
+        .get(move |req: Request<()>| {
+            let path = resolve(&roots, req.param("path").unwrap_or_default());
+            async move {
+                match path {
+                    Some(path) => {
+                        let mut res = Response::from(Body::from_file(path).await?);
+                        res.set_content_type(mime::PLAIN);
+                        Ok(res)
+                    }
+                    None => Ok(Response::new(StatusCode::NotFound)),
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn resolve_sources_in_the_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("workspace");
+        let registry = dir.path().join("registry");
+        fs::create_dir_all(workspace.join("src")).unwrap();
+        fs::create_dir_all(registry.join("index").join("serde-1.0.0")).unwrap();
+        fs::write(workspace.join("src").join("lib.rs"), "").unwrap();
+        fs::write(workspace.join(".env"), "").unwrap();
+        fs::write(
+            registry.join("index").join("serde-1.0.0").join("lib.rs"),
+            "",
+        )
+        .unwrap();
+        fs::write(dir.path().join("secret.rs"), "").unwrap();
+        let roots = vec![("workspace", workspace.clone()), ("registry", registry)];
+
+        assert_eq!(
+            resolve(&roots, "workspace/src/lib.rs"),
+            Some(workspace.join("src").join("lib.rs").canonicalize().unwrap())
+        );
+        assert!(resolve(&roots, "registry/index/serde-1.0.0/lib.rs").is_some());
+        for url_path in [
+            "workspace/.env",
+            "workspace/missing.rs",
+            "workspace/../secret.rs",
+            "workspace/src/..%2F..%2Fsecret.rs",
+            "git/src/lib.rs",
+            "workspace",
+        ] {
+            assert_eq!(resolve(&roots, url_path), None, "{}", url_path);
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path().join("secret.rs"), workspace.join("link.rs"))
+                .unwrap();
+            assert_eq!(resolve(&roots, "workspace/link.rs"), None);
+        }
+    }
+
+    #[test]
+    fn remap_the_roots_to_the_server() {
+        let roots = vec![
+            ("workspace", PathBuf::from("/home/user/app")),
+            ("registry", PathBuf::from("/home/user/.cargo/registry/src")),
+        ];
+        assert_eq!(
+            remap_flags("http://127.0.0.1:3000/__wasmbl/sources", &roots),
+            [
+                "--remap-path-prefix",
+                "/home/user/app=http://127.0.0.1:3000/__wasmbl/sources/workspace",
+                "--remap-path-prefix",
+                "/home/user/.cargo/registry/src=http://127.0.0.1:3000/__wasmbl/sources/registry",
+            ]
+        );
+    }
+}
@@ -0,0 +1,669 @@
+//! The stages of the build pipeline, for the custom commands that need only some of them: build
+//! the WASM and analyze it, build the frontend and serve it to take screenshots, ...
+//!
+//! A build runs the stages in order, each one taking the output of the previous one:
+//!
+//! 1. [`compile`]: the `pre_build` hook and cargo, to a [`CargoArtifact`],
+//! 2. [`bindgen`]: the JS bindings of `wasm-bindgen`, to a [`BindgenOutput`],
+//! 3. [`optimize`]: `wasm-opt`, to an [`OptimizedWasm`],
+//! 4. [`assemble`]: the files of the build directory (the `post_build` hook that writes the JS,
+//!    the WASM and the assets, the bundle command, the `package.json`, ...), to an
+//!    [`AssembledBuild`].
+//!
+//! The `build` command and the builds of `serve` are these four stages followed by the report of
+//! the sizes. [`serve`] starts the HTTP server of the `serve` command on the build directory.
+//!
+//! ```no_run
+//! use anyhow::Context;
+//! use wasmbl::prelude::*;
+//! use wasmbl::stages;
+//!
+//! /// Build the WASM like the `build` command, then give it to `twiggy`.
+//! fn analyze(args: &DefaultBuildArgs) -> anyhow::Result<()> {
+//!     let hooks = stages::hooks().context("the runner has not been initialized")?;
+//!     let artifact = stages::compile(args, hooks, BuildProfile::Release, BuildTrigger::Build)?;
+//!     let optimized = stages::optimize(args, stages::bindgen(args, hooks, artifact)?)?;
+//!
+//!     let path = args.target_path().join("analyze.wasm");
+//!     std::fs::write(&path, &optimized.bindings.wasm)?;
+//!     std::process::Command::new("twiggy").arg("top").arg(&path).status()?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! # Stability
+//!
+//! The functions and the types of this module follow semver like the rest of the public API. The
+//! outputs are `#[non_exhaustive]`: fields can be added in a minor version and the outputs are
+//! only created by the stages. The events sent to the [`Reporter`](crate::Reporter), the logs and
+//! the order of the files written by [`assemble`] can change.
+
+use crate::reporter::reporter;
+use crate::{
+    build_files, build_meta, BuildArgs, BuildOutput, BuildProfile, BuildTarget, BuildTimings,
+    BuildTrigger, Error, Hooks, OutLayout, Stage, HOOKS,
+};
+use anyhow::{anyhow, Context};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The WASM compiled by cargo, the output of [`compile`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CargoArtifact {
+    /// Build profile of the WASM.
+    pub profile: BuildProfile,
+    /// Target of the WASM.
+    pub target: BuildTarget,
+    /// What started the build.
+    pub trigger: BuildTrigger,
+    /// Path of the WASM in the target directory.
+    pub wasm_path: PathBuf,
+    /// Time spent by cargo.
+    pub duration: Duration,
+    started: build_files::Started,
+}
+
+/// The JS bindings of the WASM, the output of [`bindgen`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct BindgenOutput {
+    /// The WASM compiled by cargo.
+    pub artifact: CargoArtifact,
+    /// Content of the JS bindings, empty for [`BuildTarget::Wasi`].
+    pub js: String,
+    /// The WASM processed by `wasm-bindgen`, the WASM of cargo for [`BuildTarget::Wasi`].
+    pub wasm: Vec<u8>,
+    /// The TypeScript declarations, if [`BuildArgs::typescript`] is enabled or for the layout
+    /// `pkg`.
+    pub typescript: Option<String>,
+    /// The JS snippets of the dependencies (path relative to the build directory and content).
+    pub snippets: Vec<(PathBuf, String)>,
+    /// The JS imported by the JS bindings of a bundler (`app_bg.js`), for the layout `pkg`.
+    pub bundler_js: Option<String>,
+    /// Time spent by `wasm-bindgen` (zero for [`BuildTarget::Wasi`]).
+    pub duration: Duration,
+}
+
+/// The WASM optimized by `wasm-opt`, the output of [`optimize`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct OptimizedWasm {
+    /// The JS bindings, with the optimized WASM in `bindings.wasm`.
+    pub bindings: BindgenOutput,
+    /// The WASM has been optimized. It is never the case for the dev builds, nor when `wasm-opt`
+    /// is disabled or not available.
+    pub optimized: bool,
+    /// Time spent by `wasm-opt` (zero if the WASM was not optimized).
+    pub duration: Duration,
+}
+
+/// The build directory with all the files of the build, the output of [`assemble`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct AssembledBuild {
+    /// Path of the build directory.
+    pub build_path: PathBuf,
+    /// The output of the build, as given to the `post_build` hook.
+    pub output: BuildOutput,
+}
+
+/// The hooks installed by [`crate::Runner::init`], for the commands that run the stages, or
+/// `None` if the runner has not been initialized.
+///
+/// The stages use the hooks they are given: [`Hooks::default`] can be given without a runner.
+pub fn hooks() -> Option<&'static Hooks> {
+    HOOKS.get()
+}
+
+/// Compile the frontend package with cargo, after the `pre_build` hook. The build directory is
+/// created and checked first: a build path that can't be written fails before the compilation.
+///
+/// The profile is [`BuildProfile::Profiling`] if [`BuildArgs::profiling`] is enabled.
+pub fn compile(
+    args: &dyn BuildArgs,
+    hooks: &Hooks,
+    profile: BuildProfile,
+    trigger: BuildTrigger,
+) -> Result<CargoArtifact, Error> {
+    compile_quiet(args, hooks, profile, trigger, false)
+}
+
+/// [`compile`] with `--quiet` given to cargo if `quiet` is enabled.
+pub(crate) fn compile_quiet(
+    args: &dyn BuildArgs,
+    hooks: &Hooks,
+    profile: BuildProfile,
+    trigger: BuildTrigger,
+    quiet: bool,
+) -> Result<CargoArtifact, Error> {
+    let profile = crate::resolve_profile(profile, args);
+    let target = args.target();
+    let out_name = args.out_name();
+    if out_name.is_empty() || out_name.contains(['/', '\\', '.']) {
+        return Err(anyhow!(
+            "invalid out name `{}`: it must be a file name without extension",
+            out_name
+        )
+        .into());
+    }
+    if args.out_layout() != OutLayout::Flat && target == BuildTarget::Wasi {
+        return Err(anyhow!(
+            "the layouts `pkg` and `node` are only available for the target \
+            `wasm32-unknown-unknown`"
+        )
+        .into());
+    }
+    let frontend_package = args.frontend_package();
+    let artifact_name = match target {
+        BuildTarget::Web => crate::lib_name(frontend_package),
+        BuildTarget::Wasi => crate::wasi_binary(frontend_package)?.to_owned(),
+    };
+
+    let build_path = args.build_path();
+    crate::check_build_path(
+        &build_path,
+        &args.metadata().workspace_root,
+        frontend_package.manifest_path.parent().unwrap(),
+    )?;
+    // NOTE: the rebuilds have the profile of the first build
+    if trigger != BuildTrigger::WatchRebuild {
+        build_meta::check_profile(&build_path, profile);
+    }
+    let started = build_files::BuildFiles::start(&build_path)?;
+    crate::WRITTEN_ASSETS.lock().unwrap().clear();
+
+    let mut command = crate::cargo_command("build", profile, args, quiet)?;
+
+    let reporter = reporter();
+
+    reporter.stage_started(Stage::PreBuild);
+    let start = Instant::now();
+    (hooks.pre_build)(args, profile, &mut command, trigger).map_err(|source| Error::Hook {
+        hook: "pre_build",
+        source,
+    })?;
+    crate::rustflags::merge_rustflags(&mut command);
+    reporter.stage_finished(Stage::PreBuild, start.elapsed());
+
+    reporter.stage_started(Stage::Cargo);
+    log::debug!("Running {:?}", command);
+    let start = Instant::now();
+    crate::diagnostics::start(&args.metadata().workspace_root);
+    let artifacts = crate::run_cargo(&mut command, reporter)?;
+    let duration = start.elapsed();
+    reporter.stage_finished(Stage::Cargo, duration);
+
+    let profile_path = args
+        .target_path()
+        .join(target.triple())
+        .join(match profile {
+            BuildProfile::Profiling => "release",
+            BuildProfile::Release => "release",
+            BuildProfile::Dev => "debug",
+        });
+    let wasm_path = crate::find_wasm(&artifacts, frontend_package, &profile_path, &artifact_name)?;
+
+    Ok(CargoArtifact {
+        profile,
+        target,
+        trigger,
+        wasm_path,
+        duration,
+        started,
+    })
+}
+
+/// Generate the JS bindings of the WASM with `wasm-bindgen`, after the `bindgen` hook. The WASM of
+/// [`BuildTarget::Wasi`] is only read.
+pub fn bindgen(
+    args: &dyn BuildArgs,
+    hooks: &Hooks,
+    artifact: CargoArtifact,
+) -> Result<BindgenOutput, Error> {
+    let bindings = match artifact.target {
+        BuildTarget::Web => {
+            let reporter = reporter();
+            reporter.stage_started(Stage::Bindgen);
+            let start = Instant::now();
+            let bindings = crate::bindgen(&artifact.wasm_path, artifact.profile, args, hooks)?;
+            let duration = start.elapsed();
+            reporter.stage_finished(Stage::Bindgen, duration);
+            (bindings, duration)
+        }
+        BuildTarget::Wasi => (
+            crate::Bindings {
+                js: String::new(),
+                wasm: fs::read(&artifact.wasm_path).with_context(|| {
+                    format!("could not read `{}`", artifact.wasm_path.display())
+                })?,
+                typescript: None,
+                snippets: Vec::new(),
+                start: None,
+            },
+            Duration::default(),
+        ),
+    };
+    let (
+        crate::Bindings {
+            js,
+            wasm,
+            typescript,
+            snippets,
+            start,
+        },
+        duration,
+    ) = bindings;
+
+    Ok(BindgenOutput {
+        artifact,
+        js,
+        wasm,
+        typescript,
+        snippets,
+        bundler_js: start,
+        duration,
+    })
+}
+
+/// Optimize the WASM with `wasm-opt`, except for the dev builds or if [`BuildArgs::wasm_opt`] is
+/// disabled.
+///
+/// Returns [`Error::WasmOptUnavailable`] if `wasm-opt` can't run for a release build, unless
+/// [`BuildArgs::allow_unoptimized`]: the WASM is not optimized then.
+pub fn optimize(args: &dyn BuildArgs, mut bindings: BindgenOutput) -> Result<OptimizedWasm, Error> {
+    let profile = bindings.artifact.profile;
+    let reporter = reporter();
+    if profile == BuildProfile::Dev {
+        return Ok(OptimizedWasm {
+            bindings,
+            optimized: false,
+            duration: Duration::default(),
+        });
+    }
+    if !args.wasm_opt() {
+        reporter.warning(
+            "`wasm-opt` is disabled: the WASM is NOT optimized, it is much bigger and slower",
+        );
+        return Ok(OptimizedWasm {
+            bindings,
+            optimized: false,
+            duration: Duration::default(),
+        });
+    }
+
+    reporter.stage_started(Stage::WasmOpt);
+    let start = Instant::now();
+    let options = crate::effective_wasm_opt_options(args, profile);
+    match crate::wasm_opt(&bindings.wasm, &options, args.target_path()) {
+        Ok(optimized) => {
+            let duration = start.elapsed();
+            reporter.stage_finished(Stage::WasmOpt, duration);
+            bindings.wasm = optimized;
+            Ok(OptimizedWasm {
+                bindings,
+                optimized: true,
+                duration,
+            })
+        }
+        Err(Error::WasmOptUnavailable(err))
+            if profile == BuildProfile::Profiling || args.allow_unoptimized() =>
+        {
+            reporter.warning(&format!(
+                "{:#}: the WASM is NOT optimized, it is much bigger and slower",
+                err
+            ));
+            Ok(OptimizedWasm {
+                bindings,
+                optimized: false,
+                duration: Duration::default(),
+            })
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Write the files of the build to the build directory: the snippets and the TypeScript
+/// declarations, then the `post_build` hook (the default one writes the JS, the WASM, the static
+/// files, the pages and the styles), the bundle command, the `package.json`, the prerendered
+/// routes and the configuration of the static host. The stale files of the previous builds are
+/// removed and the build is recorded.
+pub fn assemble(
+    args: &dyn BuildArgs,
+    hooks: &Hooks,
+    optimized: OptimizedWasm,
+) -> Result<AssembledBuild, Error> {
+    let OptimizedWasm {
+        bindings,
+        optimized,
+        duration: wasm_opt,
+    } = optimized;
+    let BindgenOutput {
+        artifact,
+        js,
+        wasm,
+        typescript,
+        snippets,
+        bundler_js,
+        duration: bindgen,
+    } = bindings;
+    let CargoArtifact {
+        profile,
+        target,
+        trigger,
+        duration: cargo,
+        started,
+        ..
+    } = artifact;
+
+    let out_name = args.out_name();
+    let layout = args.out_layout();
+    let build_path = args.build_path();
+    let build_files = build_files::BuildFiles::resume(&build_path, started);
+
+    let mut files = Vec::new();
+    let write_generated = |files: &mut Vec<PathBuf>, name: &Path, content: Vec<u8>| {
+        let path = layout.dir().join(name);
+        let asset = crate::Asset::new(&path, crate::slash_path(&path), content);
+        files.extend(crate::write_asset(&build_path, hooks, asset)?);
+        Ok::<_, Error>(())
+    };
+
+    if let Some(ts) = typescript.as_ref() {
+        let name = format!("{}.d.ts", out_name);
+        write_generated(&mut files, Path::new(&name), ts.clone().into_bytes())?;
+    }
+
+    // NOTE: the JS bindings of a bundler are split in two files, like with `wasm-bindgen`
+    if let Some(js) = bundler_js.as_ref() {
+        let name = format!("{}_bg.js", out_name);
+        write_generated(&mut files, Path::new(&name), js.clone().into_bytes())?;
+    }
+
+    for (path, js) in snippets.iter() {
+        write_generated(&mut files, path, js.clone().into_bytes())?;
+    }
+
+    let output = BuildOutput {
+        profile,
+        target,
+        trigger,
+        js_path: build_path
+            .join(layout.dir())
+            .join(format!("{}.js", out_name)),
+        js,
+        wasm_path: build_path.join(layout.dir()).join(match target {
+            BuildTarget::Web => format!("{}_bg.wasm", out_name),
+            BuildTarget::Wasi => format!("{}.wasm", out_name),
+        }),
+        wasm,
+        optimized,
+        snippets,
+        typescript,
+        timings: BuildTimings {
+            cargo,
+            bindgen,
+            wasm_opt,
+        },
+        files,
+    };
+
+    let reporter = reporter();
+    reporter.stage_started(Stage::PostBuild);
+    let start = Instant::now();
+    (hooks.post_build)(args, &output, hooks).map_err(|source| Error::Hook {
+        hook: "post_build",
+        source,
+    })?;
+    reporter.stage_finished(Stage::PostBuild, start.elapsed());
+
+    if target == BuildTarget::Web {
+        crate::run_bundle_command(args, hooks, &output)?;
+    }
+
+    // NOTE: after the `post_build` hook that writes the JS bindings and the WASM, so the entries
+    //       follow the names given by the `asset_filter` hook
+    if target == BuildTarget::Web && (layout == OutLayout::Pkg || args.package_json()) {
+        crate::write_package_json(
+            args,
+            &output,
+            &build_files,
+            hooks,
+            bundler_js.is_some().then(|| format!("{}_bg.js", out_name)),
+        )?;
+    }
+
+    #[cfg(feature = "prerender")]
+    if profile == BuildProfile::Release && target == BuildTarget::Web {
+        let routes = args.prerender();
+        if !routes.is_empty() {
+            reporter.stage_started(Stage::Prerender);
+            let start = Instant::now();
+            crate::prerender::prerender(args, hooks, &routes)?;
+            reporter.stage_finished(Stage::Prerender, start.elapsed());
+        }
+    }
+
+    if let Some(server) = args.emit_server_config() {
+        crate::write_server_config(args, hooks, server)?;
+    }
+
+    let rebuild = trigger == BuildTrigger::WatchRebuild;
+    let clean = args.clean();
+    for path in build_files.finish(clean.unwrap_or(!rebuild), clean == Some(true))? {
+        log::info!("Removed stale file `{}`", path.display());
+    }
+    build_meta::write(
+        &build_path,
+        profile,
+        &args.metadata().workspace_root,
+        args.reproducible(),
+    )?;
+
+    Ok(AssembledBuild { build_path, output })
+}
+
+/// The HTTP server of the `serve` command, started by [`serve`].
+#[cfg(feature = "dev-server")]
+pub struct DevServer {
+    addrs: Vec<std::net::SocketAddr>,
+    pub(crate) listen:
+        std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + Send>>,
+}
+
+#[cfg(feature = "dev-server")]
+impl DevServer {
+    /// The addresses the server listens on, with the port picked by the system if
+    /// [`crate::ServeArgs::bind`] has the port 0.
+    pub fn addrs(&self) -> &[std::net::SocketAddr] {
+        &self.addrs
+    }
+
+    /// Serve the requests, until the server fails.
+    pub async fn run(self) -> Result<(), Error> {
+        self.listen.await.map_err(Error::from)
+    }
+}
+
+#[cfg(feature = "dev-server")]
+impl std::fmt::Debug for DevServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DevServer")
+            .field("addrs", &self.addrs)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Start the HTTP server of the `serve` command on the build directory, with the `serve` and
+/// `serve_middleware` hooks. The addresses of [`crate::ServeArgs::bind`] are bound before it
+/// returns. There is no build: the live reload and the control API only report that the server
+/// waits for changes.
+#[cfg(feature = "dev-server")]
+pub fn serve(args: &dyn crate::ServeArgs, hooks: &Hooks) -> Result<DevServer, Error> {
+    start_server(args, hooks, &crate::live_reload::LiveReload::default())
+}
+
+/// [`serve`] with the live reload of the builds of the `serve` command.
+#[cfg(feature = "dev-server")]
+pub(crate) fn start_server(
+    args: &dyn crate::ServeArgs,
+    hooks: &Hooks,
+    live_reload: &crate::live_reload::LiveReload,
+) -> Result<DevServer, Error> {
+    use futures::TryFutureExt;
+
+    if args.log() {
+        crate::logger::enable_http_logs();
+    }
+    let app = crate::dev_server(args, hooks, live_reload)?;
+
+    // NOTE: bound here so the error names the address that could not be bound
+    let mut addrs = Vec::new();
+    let listeners = args
+        .bind()
+        .into_iter()
+        .map(|addr| {
+            let listener = std::net::TcpListener::bind(addr)
+                .map_err(|err| crate::port::bind_error(addr, err))?;
+            let addr = listener.local_addr().unwrap_or(addr);
+            reporter().server_started(&crate::port::url(addr));
+            addrs.push(addr);
+            Ok(listener)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(DevServer {
+        addrs,
+        listen: Box::pin(app.listen(listeners).map_err(anyhow::Error::from)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DefaultBuildArgs;
+    use structopt::StructOpt;
+
+    #[test]
+    fn invalid_out_name_before_cargo() {
+        let args = DefaultBuildArgs::from_iter(["build", "--out-name", "app.min"]);
+        let err = compile(
+            &args,
+            &Hooks::default(),
+            BuildProfile::Dev,
+            BuildTrigger::Build,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("invalid out name `app.min`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn optimize_only_the_release_builds() {
+        let dir = tempfile::tempdir().unwrap();
+        let bindings = |profile| BindgenOutput {
+            artifact: CargoArtifact {
+                profile,
+                target: BuildTarget::Web,
+                trigger: BuildTrigger::Build,
+                wasm_path: dir.path().join("app.wasm"),
+                duration: Duration::default(),
+                started: build_files::BuildFiles::start(dir.path()).unwrap(),
+            },
+            js: String::new(),
+            wasm: b"\0asm".to_vec(),
+            typescript: None,
+            snippets: Vec::new(),
+            bundler_js: None,
+            duration: Duration::default(),
+        };
+
+        let args = DefaultBuildArgs::from_iter(["build"]);
+        let optimized = optimize(&args, bindings(BuildProfile::Dev)).unwrap();
+        assert!(!optimized.optimized);
+        assert_eq!(optimized.bindings.wasm, b"\0asm");
+
+        let args = DefaultBuildArgs::from_iter(["build", "--no-wasm-opt"]);
+        let optimized = optimize(&args, bindings(BuildProfile::Release)).unwrap();
+        assert!(!optimized.optimized);
+        assert_eq!(optimized.bindings.artifact.profile, BuildProfile::Release);
+    }
+
+    #[test]
+    fn assemble_with_the_given_hooks() {
+        struct Args {
+            metadata: cargo_metadata::Metadata,
+            build_path: PathBuf,
+        }
+
+        impl BuildArgs for Args {
+            fn build_path(&self) -> PathBuf {
+                self.build_path.clone()
+            }
+
+            fn metadata(&self) -> &cargo_metadata::Metadata {
+                &self.metadata
+            }
+
+            fn frontend_package(&self) -> &cargo_metadata::Package {
+                &self.metadata.packages[0]
+            }
+
+            fn profiling(&self) -> bool {
+                false
+            }
+
+            // NOTE: the names of the written assets are shared by the tests
+            fn out_name(&self) -> &str {
+                "stages"
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"web-app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::write(root.join("src").join("lib.rs"), "").unwrap();
+        let args = Args {
+            metadata: cargo_metadata::MetadataCommand::new()
+                .manifest_path(root.join("Cargo.toml"))
+                .no_deps()
+                .exec()
+                .unwrap(),
+            build_path: root.join("build"),
+        };
+        let optimized = OptimizedWasm {
+            bindings: BindgenOutput {
+                artifact: CargoArtifact {
+                    profile: BuildProfile::Dev,
+                    target: BuildTarget::Web,
+                    trigger: BuildTrigger::Build,
+                    wasm_path: root.join("web_app.wasm"),
+                    duration: Duration::default(),
+                    started: build_files::BuildFiles::start(&args.build_path).unwrap(),
+                },
+                js: "export default function init() {}".to_string(),
+                wasm: b"\0asm".to_vec(),
+                typescript: None,
+                snippets: Vec::new(),
+                bundler_js: None,
+                duration: Duration::default(),
+            },
+            optimized: false,
+            duration: Duration::default(),
+        };
+
+        // NOTE: the default `post_build` hook writes with the hooks given to the stage
+        let assembled = assemble(&args, &Hooks::default(), optimized).unwrap();
+        assert_eq!(fs::read(&assembled.output.wasm_path).unwrap(), b"\0asm");
+        assert!(assembled.output.js_path.exists());
+    }
+}
@@ -0,0 +1,223 @@
+//! The cache of the static files copied to the build directory, kept in the target directory.
+//!
+//! The size and the modification time of every static file are recorded with the file written to
+//! the build directory by the `asset_filter` hook. The next builds don't read, filter or write the
+//! static files that did not change if their output is still in the build directory. The cache is
+//! dropped when the build program or the profile changes: the hook might have changed too.
+
+use crate::BuildProfile;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Prefix of the names of the caches in the target directory.
+pub(crate) const PREFIX: &str = ".wasmbl-static-";
+
+/// A static file when it was copied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub size: u64,
+    /// The modification time in nanoseconds since the Unix epoch.
+    pub modified: u128,
+    /// The path of the output relative to the build directory and its size, `None` if the hook
+    /// skipped the file.
+    pub output: Option<(String, u64)>,
+}
+
+impl Entry {
+    /// The size and the modification time of a file.
+    pub(crate) fn of(path: &Path, output: Option<(String, u64)>) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()?
+                .duration_since(UNIX_EPOCH)
+                .ok()?
+                .as_nanos(),
+            output,
+        })
+    }
+}
+
+/// The static files copied by the previous build to a build directory.
+#[derive(Debug)]
+pub(crate) struct StaticCache {
+    path: PathBuf,
+    /// The build program and the profile, the entries are only reused if they did not change.
+    stamp: String,
+    previous: BTreeMap<String, Entry>,
+    current: Mutex<BTreeMap<String, Entry>>,
+}
+
+impl StaticCache {
+    /// The cache of a build directory in the target directory.
+    pub(crate) fn open(target_path: &Path, build_path: &Path, profile: BuildProfile) -> Self {
+        let hash = Sha256::digest(build_path.to_string_lossy().as_bytes());
+        let key: String = hash[..8].iter().map(|x| format!("{:02x}", x)).collect();
+        let path = target_path.join(format!("{}{}.json", PREFIX, key));
+        let stamp = stamp(profile);
+        let previous = fs::read_to_string(&path)
+            .ok()
+            .and_then(|x| serde_json::from_str::<serde_json::Value>(&x).ok())
+            .filter(|x| x.get("stamp").and_then(|x| x.as_str()) == Some(stamp.as_str()))
+            .and_then(|x| x.get("files").and_then(|x| x.as_object()).map(entries))
+            .unwrap_or_default();
+
+        Self {
+            path,
+            stamp,
+            previous,
+            current: Default::default(),
+        }
+    }
+
+    /// The entry of the static file `name` if the file did not change and its output is still in
+    /// the build directory.
+    pub(crate) fn unchanged(&self, name: &str, file: &Path, build_path: &Path) -> Option<Entry> {
+        let previous = self.previous.get(name)?;
+        let entry = Entry::of(file, previous.output.clone())?;
+        if &entry != previous {
+            return None;
+        }
+        if let Some((output, size)) = entry.output.as_ref() {
+            let metadata = fs::metadata(build_path.join(output)).ok()?;
+            if !metadata.is_file() || metadata.len() != *size {
+                return None;
+            }
+        }
+        Some(entry)
+    }
+
+    /// Record a static file copied by this build.
+    pub(crate) fn insert(&self, name: String, entry: Entry) {
+        self.current.lock().unwrap().insert(name, entry);
+    }
+
+    /// Replace the cache with the files copied by this build.
+    pub(crate) fn save(self) {
+        let files: serde_json::Map<_, _> = self
+            .current
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|(name, entry)| {
+                let (output, output_size) = entry.output.unzip();
+                (
+                    name,
+                    serde_json::json!({
+                        "size": entry.size,
+                        "modified": entry.modified.to_string(),
+                        "output": output,
+                        "output_size": output_size,
+                    }),
+                )
+            })
+            .collect();
+        let cache = serde_json::json!({ "stamp": self.stamp, "files": files });
+        // NOTE: the cache is only an optimization, the build doesn't fail without it
+        if let Err(err) = fs::write(&self.path, cache.to_string()) {
+            log::debug!("Could not write `{}`: {}", self.path.display(), err);
+        }
+    }
+}
+
+fn entries(files: &serde_json::Map<String, serde_json::Value>) -> BTreeMap<String, Entry> {
+    files
+        .iter()
+        .filter_map(|(name, file)| {
+            let size = file.get("size")?.as_u64()?;
+            let modified = file.get("modified")?.as_str()?.parse().ok()?;
+            let output = match file.get("output")?.as_str() {
+                Some(output) => Some((output.to_string(), file.get("output_size")?.as_u64()?)),
+                None => None,
+            };
+            Some((
+                name.clone(),
+                Entry {
+                    size,
+                    modified,
+                    output,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// The build program (its path, size and modification time) and the profile.
+fn stamp(profile: BuildProfile) -> String {
+    let exe = std::env::current_exe().ok();
+    let entry = exe.as_deref().and_then(|x| Entry::of(x, None));
+    format!(
+        "{}:{}:{}:{}",
+        exe.as_deref()
+            .map(Path::to_string_lossy)
+            .unwrap_or_default(),
+        entry.as_ref().map(|x| x.size).unwrap_or_default(),
+        entry.as_ref().map(|x| x.modified).unwrap_or_default(),
+        profile.name()
+    )
+}
+
+/// Set the modification time of an output that is reused to now: it is produced by this build.
+pub(crate) fn touch(path: &Path) -> std::io::Result<()> {
+    fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse_the_unchanged_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (target, build, static_dir) = (
+            dir.path().join("target"),
+            dir.path().join("build"),
+            dir.path().join("static"),
+        );
+        for path in [&target, &build, &static_dir] {
+            fs::create_dir(path).unwrap();
+        }
+        let logo = static_dir.join("logo.svg");
+        fs::write(&logo, "<svg/>").unwrap();
+        let ignored = static_dir.join(".keep");
+        fs::write(&ignored, "").unwrap();
+        fs::write(build.join("logo.1234.svg"), "<svg/>").unwrap();
+
+        let cache = StaticCache::open(&target, &build, BuildProfile::Release);
+        assert_eq!(cache.unchanged("logo.svg", &logo, &build), None);
+        let output = Some(("logo.1234.svg".to_string(), 6));
+        cache.insert("logo.svg".into(), Entry::of(&logo, output.clone()).unwrap());
+        cache.insert(".keep".into(), Entry::of(&ignored, None).unwrap());
+        cache.save();
+
+        let cache = StaticCache::open(&target, &build, BuildProfile::Release);
+        let entry = cache.unchanged("logo.svg", &logo, &build).unwrap();
+        assert_eq!(entry.output, output);
+        assert_eq!(
+            cache.unchanged(".keep", &ignored, &build).unwrap().output,
+            None
+        );
+
+        let other = StaticCache::open(&target, &build, BuildProfile::Dev);
+        assert_eq!(other.unchanged("logo.svg", &logo, &build), None);
+        let other = StaticCache::open(&target, &dir.path().join("public"), BuildProfile::Release);
+        assert_eq!(other.unchanged("logo.svg", &logo, &build), None);
+
+        fs::write(build.join("logo.1234.svg"), "<svg></svg>").unwrap();
+        assert_eq!(cache.unchanged("logo.svg", &logo, &build), None);
+        fs::remove_file(build.join("logo.1234.svg")).unwrap();
+        assert_eq!(cache.unchanged("logo.svg", &logo, &build), None);
+
+        fs::write(&ignored, "x").unwrap();
+        assert_eq!(cache.unchanged(".keep", &ignored, &build), None);
+    }
+}
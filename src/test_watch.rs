@@ -0,0 +1,285 @@
+//! The `test --watch` mode: the tests of the frontend package run again when its files change.
+//!
+//! The driver of the browser is started once and every run of `wasm-bindgen-test-runner` connects
+//! to it (`CHROMEDRIVER_REMOTE`, `GECKODRIVER_REMOTE`) instead of starting its own. It is in its
+//! own process group, so it is terminated with the tests on Ctrl-C.
+
+use crate::process::GroupChild;
+use crate::watcher::{self, Change};
+use crate::{Error, FileWatcher, Hooks, Project, TestArgs, TestBrowser};
+use anyhow::{anyhow, bail, Context, Result};
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the driver of the browser has to accept the connections.
+const DRIVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Run the tests, then again after every change of the sources until Ctrl-C.
+pub(crate) fn watch<T: TestArgs>(args: &T, hooks: &Hooks) -> Result<(), Error> {
+    let driver = args
+        .browser()
+        .and_then(|browser| match Driver::start(browser) {
+            Ok(driver) => Some(driver),
+            Err(err) => {
+                log::warn!("{:#}: every run of the tests starts its own driver", err);
+                None
+            }
+        });
+    let driver_url = driver.as_ref().map(|x| x.url.as_str());
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = FileWatcher::new(tx, args)?;
+    let register = |watcher: &mut FileWatcher| watch_paths(args, watcher);
+    let build_args = args.build_args();
+    watcher.ignore_builtin(
+        &build_args.metadata().workspace_root,
+        vec![
+            build_args.build_path(),
+            build_args.target_path().to_path_buf(),
+        ],
+    );
+    watcher.register(register)?;
+
+    let mut filter = watcher::WatchFilter::new(
+        args.gitignore(),
+        crate::default_watch_extensions(build_args.frontend_package()),
+        crate::default_watch_snippet_extensions(build_args.frontend_package()),
+        Vec::new(),
+    );
+    filter.ignore_globs(&build_args.metadata().workspace_root, &args.ignore_globs())?;
+    for path in watcher.config().builtin_ignored_paths() {
+        filter.exclude(path);
+    }
+
+    run_tests(args, hooks, driver_url);
+    watcher::watch_loop(rx, &mut watcher, filter, None, |change, watcher| {
+        match change {
+            Change::Manifest => {
+                log::info!("Manifest changed, reloading metadata");
+                Project::reload()?;
+                watcher.rewatch(register)?;
+            }
+            Change::Source | Change::Snippet => {}
+            Change::Asset | Change::Bundle | Change::Served => return Ok(()),
+        }
+        run_tests(args, hooks, driver_url);
+        Ok(())
+    });
+
+    Err(anyhow!("the watcher unexpectedly stopped").into())
+}
+
+/// Watch the frontend package, its path dependencies and the paths of `WatchArgs::watch_paths`.
+fn watch_paths<T: TestArgs>(args: &T, watcher: &mut FileWatcher) -> Result<()> {
+    use notify::Watcher;
+
+    let build_args = args.build_args();
+    for (path, mode) in watcher::package_watch_paths(
+        build_args.frontend_metadata(),
+        build_args.frontend_package(),
+    ) {
+        watcher.watch(path, mode)?;
+    }
+
+    crate::watch_extra_paths(args, watcher)
+}
+
+/// Run the tests once and print their summary. The failures are only reported.
+fn run_tests(args: &dyn TestArgs, hooks: &Hooks, driver_url: Option<&str>) {
+    let start = Instant::now();
+    match run(args, hooks, driver_url) {
+        Ok((true, summary)) => log::info!(
+            "Tests passed: {} ({:.1}s)",
+            summary,
+            start.elapsed().as_secs_f32()
+        ),
+        Ok((false, summary)) if summary.is_empty() => {
+            log::error!("The tests could not be built or run")
+        }
+        Ok((false, summary)) => log::error!(
+            "Tests failed: {} ({:.1}s)",
+            summary,
+            start.elapsed().as_secs_f32()
+        ),
+        Err(err) => log::error!("{:#}", err),
+    }
+    log::info!("Waiting for changes");
+}
+
+/// Run the tests and returns if they passed with their summary. The output of the test harness is
+/// printed as it comes.
+fn run(args: &dyn TestArgs, hooks: &Hooks, driver_url: Option<&str>) -> Result<(bool, Summary)> {
+    let mut command = crate::test_command(args, hooks, driver_url)?;
+    command.stdout(Stdio::piped());
+
+    log::info!("Testing frontend");
+    log::debug!("Running {:?}", command);
+    let mut child = GroupChild::spawn(&mut command).context("could not start test process")?;
+    let mut summary = Summary::default();
+    let stdout = child.stdout.take().expect("the output is piped; qed");
+    for line in BufReader::new(stdout).lines() {
+        let line = line.context("could not read the output of the tests")?;
+        let mut out = std::io::stdout().lock();
+        let _ = writeln!(out, "{}", line);
+        summary.parse_line(&line);
+    }
+    let status = child
+        .wait()
+        .context("could not wait for the test process")?;
+
+    Ok((status.success(), summary))
+}
+
+/// The results of the test harness, read from its output.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Summary {
+    passed: usize,
+    failed: usize,
+    ignored: usize,
+    /// The names of the tests that failed.
+    failures: Vec<String>,
+    /// A line `test result:` has been read.
+    finished: bool,
+}
+
+impl Summary {
+    fn parse_line(&mut self, line: &str) {
+        if let Some(result) = line.strip_prefix("test result:") {
+            self.finished = true;
+            let words: Vec<_> = result.split_whitespace().collect();
+            for pair in words.windows(2) {
+                let count = match pair[0].parse::<usize>() {
+                    Ok(count) => count,
+                    Err(_) => continue,
+                };
+                match pair[1].trim_end_matches([';', '.']) {
+                    "passed" => self.passed += count,
+                    "failed" => self.failed += count,
+                    "ignored" => self.ignored += count,
+                    _ => {}
+                }
+            }
+        } else if let Some(name) = line
+            .strip_prefix("test ")
+            .and_then(|x| x.strip_suffix(" ... FAILED"))
+        {
+            self.failures.push(name.to_string());
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.finished
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} passed, {} failed", self.passed, self.failed)?;
+        if self.ignored > 0 {
+            write!(f, ", {} ignored", self.ignored)?;
+        }
+        if !self.failures.is_empty() {
+            write!(f, " (`{}`)", self.failures.join("`, `"))?;
+        }
+        Ok(())
+    }
+}
+
+/// The driver of the browser, terminated when it is dropped.
+struct Driver {
+    url: String,
+    _child: GroupChild,
+}
+
+impl Driver {
+    /// Start the driver of the browser (given by its variable, like for a single run) on a free
+    /// port and wait until it accepts the connections.
+    fn start(browser: TestBrowser) -> Result<Self> {
+        let (var, default) = browser.driver();
+        let program = std::env::var_os(var).unwrap_or_else(|| default.into());
+        let name = Path::new(&program).display().to_string();
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|x| x.local_addr())
+            .context("could not find a free port for the driver")?
+            .port();
+
+        let mut command = std::process::Command::new(&program);
+        command
+            .arg(format!("--port={}", port))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        log::debug!("Running {:?}", command);
+        let mut child = GroupChild::spawn(&mut command)
+            .with_context(|| format!("could not start `{}`", name))?;
+
+        let start = Instant::now();
+        while TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_err() {
+            if let Some(status) = child.try_wait()? {
+                bail!(
+                    "`{}` exited before accepting connections ({})",
+                    name,
+                    status
+                );
+            }
+            if start.elapsed() > DRIVER_TIMEOUT {
+                bail!(
+                    "`{}` did not accept connections after {}s",
+                    name,
+                    DRIVER_TIMEOUT.as_secs()
+                );
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        log::info!("Started `{}` on port {} for all the runs", name, port);
+
+        Ok(Self {
+            url: format!("http://127.0.0.1:{}", port),
+            _child: child,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_of_the_output() {
+        let mut summary = Summary::default();
+        assert!(summary.is_empty());
+        for line in [
+            "running 3 tests",
+            "test tests::ok ... ok",
+            "test tests::broken ... FAILED",
+            "test tests::slow ... ignored",
+            "test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 filtered out; finished in 0.01s",
+        ] {
+            summary.parse_line(line);
+        }
+
+        assert_eq!(
+            summary,
+            Summary {
+                passed: 1,
+                failed: 1,
+                ignored: 1,
+                failures: vec!["tests::broken".to_string()],
+                finished: true,
+            }
+        );
+        assert_eq!(
+            summary.to_string(),
+            "1 passed, 1 failed, 1 ignored (`tests::broken`)"
+        );
+
+        let mut summary = Summary::default();
+        summary.parse_line("test result: ok. 12 passed; 0 failed; 0 ignored; 0 filtered out");
+        assert_eq!(summary.to_string(), "12 passed, 0 failed");
+    }
+}
@@ -0,0 +1,507 @@
+//! The asset directives of Trunk in the HTML template of [`crate::BuildArgs::trunk_index`], for
+//! the projects that migrate from Trunk.
+//!
+//! The tags with a `data-trunk` attribute are replaced with the references to the files written
+//! by the build: `<link data-trunk rel="scss" href="main.scss">` becomes `<link rel="stylesheet"
+//! href="/main.css">`, after the renames of the [`crate::Hooks::asset_filter`] hook (the hashes of
+//! the names). Only the directives of [`DIRECTIVES`] are supported, the others fail the build.
+
+use crate::{Asset, BuildArgs, BuildOutput, Hooks};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The values of `rel` of the supported directives.
+const DIRECTIVES: &[&str] = &["rust", "scss", "css", "copy-dir", "copy-file", "icon"];
+
+/// A tag with a `data-trunk` attribute in the template.
+#[derive(Debug, PartialEq)]
+struct Directive {
+    /// The byte range of the tag in the template.
+    start: usize,
+    end: usize,
+    /// The line of the tag, starting at 1.
+    line: usize,
+    /// The name of the tag in lowercase (`link`).
+    tag: String,
+    /// The attributes in order, with their names in lowercase.
+    attributes: Vec<(String, Option<String>)>,
+}
+
+impl Directive {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(x, _)| x == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    fn rel(&self) -> &str {
+        self.attribute("rel").unwrap_or_default()
+    }
+
+    /// The attributes kept on the tag that replaces the directive: the ones that are not
+    /// `data-*`, `rel` or `href` (`media`, `sizes`, ...).
+    fn other_attributes(&self) -> String {
+        self.attributes
+            .iter()
+            .filter(|(name, _)| !name.starts_with("data-") && name != "rel" && name != "href")
+            .map(|(name, value)| match value {
+                Some(value) => format!(" {}=\"{}\"", name, escape(value)),
+                None => format!(" {}", name),
+            })
+            .collect()
+    }
+}
+
+/// The tags with a `data-trunk` attribute of an HTML document, outside of the comments.
+fn directives(html: &str) -> Vec<Directive> {
+    let mut directives = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = html[pos..].find('<') {
+        let start = pos + offset;
+        let rest = &html[start..];
+        if rest.starts_with("<!--") {
+            pos = match rest.find("-->") {
+                Some(end) => start + end + 3,
+                None => html.len(),
+            };
+            continue;
+        }
+        let (tag, attributes, len) = match parse_tag(rest) {
+            Some(x) => x,
+            None => {
+                pos = start + 1;
+                continue;
+            }
+        };
+        if attributes.iter().any(|(name, _)| name == "data-trunk") {
+            directives.push(Directive {
+                start,
+                end: start + len,
+                line: html[..start].matches('\n').count() + 1,
+                tag,
+                attributes,
+            });
+        }
+        pos = start + len;
+    }
+    directives
+}
+
+/// The name, the attributes and the length of the opening tag at the start of `html`.
+#[allow(clippy::type_complexity)]
+fn parse_tag(html: &str) -> Option<(String, Vec<(String, Option<String>)>, usize)> {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':';
+    let body = &html[1..];
+    let name_len = body.find(|c: char| !is_name(c)).unwrap_or(body.len());
+    if name_len == 0 {
+        return None;
+    }
+    let tag = body[..name_len].to_ascii_lowercase();
+
+    let mut attributes = Vec::new();
+    let mut pos = name_len;
+    loop {
+        let rest = &body[pos..];
+        let trimmed = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        pos += rest.len() - trimmed.len();
+        if trimmed.starts_with('>') {
+            return Some((tag, attributes, pos + 2));
+        }
+        let len = trimmed.find(|c: char| !is_name(c)).unwrap_or(trimmed.len());
+        if len == 0 {
+            return None;
+        }
+        let name = trimmed[..len].to_ascii_lowercase();
+        pos += len;
+
+        let rest = &body[pos..];
+        let after_space = rest.trim_start();
+        if !after_space.starts_with('=') {
+            attributes.push((name, None));
+            continue;
+        }
+        let after_equal = after_space[1..].trim_start();
+        pos += rest.len() - after_equal.len();
+        let (value, len) = match after_equal.chars().next()? {
+            quote @ ('"' | '\'') => {
+                let end = after_equal[1..].find(quote)?;
+                (&after_equal[1..end + 1], end + 2)
+            }
+            _ => {
+                let end = after_equal
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .unwrap_or(after_equal.len());
+                (&after_equal[..end], end)
+            }
+        };
+        attributes.push((name, Some(unescape(value))));
+        pos += len;
+    }
+}
+
+/// Decode the character references of the values of the attributes that the paths might contain.
+fn unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+/// Render the template and write it as the `index.html` of the build directory with the files of
+/// its directives.
+pub(crate) fn build(
+    args: &dyn BuildArgs,
+    template: &Path,
+    output: &BuildOutput,
+    hooks: &Hooks,
+) -> Result<()> {
+    let html = fs::read_to_string(template)
+        .with_context(|| format!("could not read the template `{}`", template.display()))?;
+    let build_path = args.build_path();
+    let base_url = crate::base_url(args);
+    let template_dir = template.parent().unwrap_or_else(|| Path::new(""));
+    let url = |path: &Path| {
+        let relative = path.strip_prefix(&build_path).unwrap_or(path);
+        format!(
+            "{}{}",
+            base_url,
+            crate::url_path(relative).unwrap_or_default()
+        )
+    };
+    // NOTE: the JS bindings and the WASM might have been renamed by the `asset_filter` hook
+    let written_url = |path: &Path| {
+        let relative = path.strip_prefix(&build_path).unwrap_or(path);
+        let written = crate::written_asset(&crate::slash_path(relative));
+        url(written.as_deref().unwrap_or(path))
+    };
+    let write = |path: PathBuf, name: String, content: Vec<u8>| {
+        Ok::<_, anyhow::Error>(crate::write_asset(
+            &build_path,
+            hooks,
+            Asset::new(path, name, content),
+        )?)
+    };
+    let file_name = |path: &Path| PathBuf::from(path.file_name().unwrap_or_default());
+
+    let mut rendered = String::with_capacity(html.len());
+    let mut pos = 0;
+    for directive in directives(&html) {
+        let at = || {
+            format!(
+                "the directive `<{} data-trunk{}>` at line {} of `{}`",
+                directive.tag,
+                match directive.rel() {
+                    "" => String::new(),
+                    rel => format!(" rel=\"{}\"", rel),
+                },
+                directive.line,
+                template.display()
+            )
+        };
+        if directive.tag != "link" || !DIRECTIVES.contains(&directive.rel()) {
+            bail!(
+                "{} is not supported, the supported directives are `<link data-trunk>` with \
+                `rel=\"{}\"`",
+                at(),
+                DIRECTIVES.join("\"`, `rel=\"")
+            );
+        }
+        let source = || -> Result<(PathBuf, String)> {
+            let href = match directive.attribute("href") {
+                Some(href) if !href.is_empty() => href,
+                _ => bail!("{} has no `href`", at()),
+            };
+            let source = template_dir.join(href);
+            if !source.exists() {
+                bail!("{}: `{}` does not exist", at(), source.display());
+            }
+            let name = format!("trunk/{}", href.trim_start_matches("./"));
+            Ok((source, name))
+        };
+        let target_dir = directive
+            .attribute("data-target-path")
+            .map(|x| PathBuf::from(x.trim_matches('/')))
+            .unwrap_or_default();
+
+        let replacement = match directive.rel() {
+            "rust" => {
+                format!(
+                    "<script type=\"module\">import init from \"{}\";init(\"{}\");</script>",
+                    written_url(&output.js_path),
+                    written_url(&output.wasm_path)
+                )
+            }
+            rel @ ("css" | "icon") => {
+                let (source, name) = source()?;
+                let content = fs::read(&source)
+                    .with_context(|| format!("{}: could not read `{}`", at(), source.display()))?;
+                match write(file_name(&source), name, content)? {
+                    Some(path) => format!(
+                        "<link rel=\"{}\" href=\"{}\"{}>",
+                        if rel == "css" { "stylesheet" } else { "icon" },
+                        escape(&url(&path)),
+                        directive.other_attributes()
+                    ),
+                    None => String::new(),
+                }
+            }
+            "scss" => {
+                let (source, name) = source()?;
+                let css = compile_scss(args, output, &source).with_context(at)?;
+                match write(file_name(&source).with_extension("css"), name, css)? {
+                    Some(path) => format!(
+                        "<link rel=\"stylesheet\" href=\"{}\"{}>",
+                        escape(&url(&path)),
+                        directive.other_attributes()
+                    ),
+                    None => String::new(),
+                }
+            }
+            "copy-file" => {
+                let (source, name) = source()?;
+                let content = fs::read(&source)
+                    .with_context(|| format!("{}: could not read `{}`", at(), source.display()))?;
+                write(target_dir.join(file_name(&source)), name, content)?;
+                String::new()
+            }
+            "copy-dir" => {
+                let (source, name) = source()?;
+                let target_dir = match directive.attribute("data-target-path") {
+                    Some(_) => target_dir.clone(),
+                    None => file_name(&source),
+                };
+                let files = crate::list_files(&source)
+                    .with_context(|| format!("{}: could not list `{}`", at(), source.display()))?;
+                for file in files {
+                    let path = file.strip_prefix(&source).unwrap_or(&file);
+                    let content = fs::read(&file).with_context(|| {
+                        format!("{}: could not read `{}`", at(), file.display())
+                    })?;
+                    write(
+                        target_dir.join(path),
+                        format!("{}/{}", name, crate::slash_path(path)),
+                        content,
+                    )?;
+                }
+                String::new()
+            }
+            _ => unreachable!("the directive is supported; qed"),
+        };
+
+        rendered.push_str(&html[pos..directive.start]);
+        rendered.push_str(&replacement);
+        pos = directive.end;
+    }
+    rendered.push_str(&html[pos..]);
+
+    write(
+        PathBuf::from("index.html"),
+        "trunk/index.html".to_string(),
+        rendered.into_bytes(),
+    )?;
+
+    Ok(())
+}
+
+#[cfg(feature = "sass")]
+fn compile_scss(args: &dyn BuildArgs, output: &BuildOutput, path: &Path) -> Result<Vec<u8>> {
+    match sass_rs::compile_file(path, args.sass_options(output.profile)) {
+        Ok(css) => Ok(css.into_bytes()),
+        Err(err) => bail!("could not compile `{}`: {}", path.display(), err),
+    }
+}
+
+#[cfg(not(feature = "sass"))]
+fn compile_scss(_args: &dyn BuildArgs, _output: &BuildOutput, _path: &Path) -> Result<Vec<u8>> {
+    bail!("the feature `sass` of wasmbl is not enabled")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_the_directives() {
+        let html = "<!DOCTYPE html>\n<html>\n<head>\n\
+            <!-- <link data-trunk rel=\"css\" href=\"old.css\"> -->\n\
+            <link data-trunk rel=\"scss\" href='main.scss' media=print>\n\
+            <link rel=\"stylesheet\" href=\"other.css\">\n\
+            <script data-trunk src=\"app.js\"></script>\n\
+            <link\n  data-trunk\n  rel=\"copy-dir\"\n  href=\"assets &amp; images\"\n/>\n\
+            </head>\n</html>\n";
+        let directives = directives(html);
+        assert_eq!(directives.len(), 3, "{:?}", directives);
+
+        assert_eq!(directives[0].line, 5);
+        assert_eq!(
+            &html[directives[0].start..directives[0].end],
+            "<link data-trunk rel=\"scss\" href='main.scss' media=print>"
+        );
+        assert_eq!(directives[0].rel(), "scss");
+        assert_eq!(directives[0].attribute("href"), Some("main.scss"));
+        assert_eq!(directives[0].other_attributes(), " media=\"print\"");
+
+        assert_eq!(directives[1].line, 7);
+        assert_eq!(directives[1].tag, "script");
+
+        assert_eq!(directives[2].line, 8);
+        assert_eq!(directives[2].rel(), "copy-dir");
+        assert_eq!(directives[2].attribute("href"), Some("assets & images"));
+        assert!(html[..directives[2].end].ends_with("/>"));
+    }
+
+    fn args(build_path: &Path) -> crate::DefaultBuildArgs {
+        <crate::DefaultBuildArgs as structopt::StructOpt>::from_iter_safe([
+            "build",
+            "--build-path",
+            build_path.to_str().unwrap(),
+            "--base-url",
+            "/app",
+        ])
+        .unwrap()
+    }
+
+    fn output(build_path: &Path) -> BuildOutput {
+        BuildOutput {
+            profile: crate::BuildProfile::Release,
+            target: crate::BuildTarget::Web,
+            trigger: crate::BuildTrigger::Build,
+            js_path: build_path.join("app.js"),
+            js: String::new(),
+            wasm_path: build_path.join("app_bg.wasm"),
+            wasm: Vec::new(),
+            optimized: false,
+            snippets: Vec::new(),
+            typescript: None,
+            timings: Default::default(),
+            files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn render_the_template() {
+        crate::runner::tests::init_project();
+        let dir = tempfile::tempdir().unwrap();
+        let build_path = dir.path().join("build");
+        fs::create_dir_all(dir.path().join("assets").join("img")).unwrap();
+        fs::write(
+            dir.path().join("assets").join("img").join("a.svg"),
+            "<svg/>",
+        )
+        .unwrap();
+        fs::write(dir.path().join("style.css"), "body {}").unwrap();
+        fs::write(dir.path().join("favicon.ico"), "ico").unwrap();
+        fs::write(dir.path().join("robots.txt"), "").unwrap();
+        let template = dir.path().join("index.html");
+        fs::write(
+            &template,
+            "<html><head>\n<link data-trunk rel=\"rust\" href=\"Cargo.toml\">\n\
+            <link data-trunk rel=\"css\" href=\"style.css\" media=\"screen\">\n\
+            <link data-trunk rel=\"icon\" href=\"./favicon.ico\" sizes=\"any\">\n\
+            <link data-trunk rel=\"copy-file\" href=\"robots.txt\">\n\
+            <link data-trunk rel=\"copy-dir\" href=\"assets\">\n\
+            </head></html>",
+        )
+        .unwrap();
+        let (args, output) = (args(&build_path), output(&build_path));
+        let hooks = Hooks {
+            asset_filter: Box::new(|mut asset| {
+                if asset.name == "trunk/style.css" {
+                    asset.path = asset.path.with_extension("1234.css");
+                }
+                Ok(Some(asset))
+            }),
+            ..Hooks::default()
+        };
+
+        build(&args, &template, &output, &hooks).unwrap();
+        assert_eq!(
+            fs::read_to_string(build_path.join("index.html")).unwrap(),
+            "<html><head>\n\
+            <script type=\"module\">import init from \"/app/app.js\";init(\"/app/app_bg.wasm\");\
+            </script>\n\
+            <link rel=\"stylesheet\" href=\"/app/style.1234.css\" media=\"screen\">\n\
+            <link rel=\"icon\" href=\"/app/favicon.ico\" sizes=\"any\">\n\n\n\
+            </head></html>"
+        );
+        assert!(build_path.join("robots.txt").is_file());
+        assert!(build_path
+            .join("assets")
+            .join("img")
+            .join("a.svg")
+            .is_file());
+
+        fs::write(
+            &template,
+            "<html>\n<head>\n<link data-trunk rel=\"tailwind-css\" href=\"style.css\">",
+        )
+        .unwrap();
+        let err = build(&args, &template, &output, &hooks).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "the directive `<link data-trunk rel=\"tailwind-css\">` at line 3 of `{}` is not \
+                supported, the supported directives are `<link data-trunk>` with `rel=\"rust\"`, \
+                `rel=\"scss\"`, `rel=\"css\"`, `rel=\"copy-dir\"`, `rel=\"copy-file\"`, \
+                `rel=\"icon\"`",
+                template.display()
+            )
+        );
+
+        fs::write(
+            &template,
+            "<link data-trunk rel=\"css\" href=\"missing.css\">",
+        )
+        .unwrap();
+        let err = build(&args, &template, &output, &hooks).unwrap_err();
+        assert!(err.to_string().contains("at line 1 of"), "{}", err);
+        assert!(err.to_string().contains("missing.css"), "{}", err);
+    }
+
+    #[test]
+    fn compile_the_scss() {
+        crate::runner::tests::init_project();
+        let dir = tempfile::tempdir().unwrap();
+        let build_path = dir.path().join("build");
+        fs::write(dir.path().join("main.scss"), "$c: red; body { color: $c; }").unwrap();
+        let template = dir.path().join("index.html");
+        fs::write(
+            &template,
+            "<link data-trunk rel=\"scss\" href=\"main.scss\">",
+        )
+        .unwrap();
+
+        let res = build(
+            &args(&build_path),
+            &template,
+            &output(&build_path),
+            &Hooks::default(),
+        );
+        #[cfg(feature = "sass")]
+        {
+            res.unwrap();
+            assert_eq!(
+                fs::read_to_string(build_path.join("index.html")).unwrap(),
+                "<link rel=\"stylesheet\" href=\"/app/main.css\">"
+            );
+            let css = fs::read_to_string(build_path.join("main.css")).unwrap();
+            assert_eq!(css.trim(), "body{color:red}");
+        }
+        #[cfg(not(feature = "sass"))]
+        {
+            let err = format!("{:#}", res.unwrap_err());
+            assert!(err.contains("the feature `sass`"), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! The `version` command: print the versions of the tools that produce the build.
+
+use anyhow::{bail, Context, Result};
+use cargo_metadata::Metadata;
+use std::path::Path;
+use std::process::Command;
+use structopt::StructOpt;
+
+/// Version arguments.
+#[derive(StructOpt, Debug)]
+pub struct DefaultVersionArgs {}
+
+impl DefaultVersionArgs {
+    /// Run the `version` command.
+    ///
+    /// A tool that can not be found is reported as such instead of failing the command.
+    pub fn run(self) -> Result<()> {
+        let metadata = crate::Project::get().metadata;
+
+        println!("wasmbl {}", env!("CARGO_PKG_VERSION"));
+        println!(
+            "wasm-bindgen-cli-support {} (used to generate the bindings)",
+            package_versions(metadata, "wasm-bindgen-cli-support")
+        );
+        println!(
+            "wasm-bindgen {} (in the dependencies)",
+            package_versions(metadata, "wasm-bindgen")
+        );
+        println!("{}", wasm_opt_version(metadata, &metadata.target_directory));
+
+        let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+        match command_output(Command::new(&rustc).arg("-vV")) {
+            Ok(output) => print!("{}", output),
+            Err(err) => println!("rustc: {:#}", err),
+        }
+
+        Ok(())
+    }
+}
+
+/// The versions of a package in the dependencies of the workspace.
+fn package_versions(metadata: &Metadata, name: &str) -> String {
+    let mut versions: Vec<_> = metadata
+        .packages
+        .iter()
+        .filter(|x| x.name == name)
+        .map(|x| x.version.to_string())
+        .collect();
+    versions.sort();
+
+    if versions.is_empty() {
+        "not found".to_string()
+    } else {
+        versions.join(", ")
+    }
+}
+
+#[allow(unused_variables, unreachable_code)]
+fn wasm_opt_version(metadata: &Metadata, target_path: &Path) -> String {
+    #[cfg(feature = "binaryen")]
+    return format!(
+        "binaryen {} (linked)",
+        package_versions(metadata, "binaryen-sys")
+    );
+
+    #[cfg(feature = "prebuilt-wasm-opt")]
+    return match crate::prebuilt_wasm_opt::installed_wasm_opt(target_path) {
+        Ok(Some(wasm_opt)) => {
+            let mut command = Command::new(&wasm_opt);
+            command.arg("--version");
+            #[cfg(target_os = "macos")]
+            {
+                command.env("DYLD_LIBRARY_PATH", wasm_opt.parent().unwrap());
+            }
+
+            match command_output(&mut command) {
+                Ok(output) => format!("{} (`{}`)", output.trim(), wasm_opt.display()),
+                Err(err) => format!("wasm-opt: {:#}", err),
+            }
+        }
+        Ok(None) => format!(
+            "wasm-opt version {} (not downloaded yet)",
+            crate::prebuilt_wasm_opt::BINARYEN_VERSION
+        ),
+        Err(err) => format!("wasm-opt: {:#}", err),
+    };
+
+    "wasm-opt: not used, the WASM is not optimized".to_string()
+}
+
+/// The standard output of a command that must succeed.
+fn command_output(command: &mut Command) -> Result<String> {
+    let output = command
+        .output()
+        .with_context(|| format!("could not run {:?}", command))?;
+    if !output.status.success() {
+        bail!("{:?} failed", command);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_missing_tools() {
+        let output = command_output(Command::new("cargo").arg("--version")).unwrap();
+        assert!(output.starts_with("cargo "));
+
+        let err = command_output(&mut Command::new("wasmbl-missing-tool")).unwrap_err();
+        assert!(format!("{:#}", err).starts_with("could not run \"wasmbl-missing-tool\""));
+        assert!(command_output(Command::new("cargo").arg("--unknown-flag")).is_err());
+    }
+}
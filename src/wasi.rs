@@ -0,0 +1,97 @@
+//! The `run` command: build the frontend package for `wasm32-wasi` and run it with a WASI runtime.
+
+use crate::{
+    build, hooks, BuildProfile, BuildTarget, BuildTrigger, DefaultBuildArgs, Error, Project,
+};
+use anyhow::anyhow;
+use std::ffi::OsString;
+use std::io;
+use std::process::Command;
+use structopt::StructOpt;
+
+/// The WASI runtimes supported by the `run` command, in the order they are looked for.
+const RUNTIMES: &[&str] = &["wasmtime", "wasmer"];
+
+/// Run arguments.
+#[derive(StructOpt, Debug)]
+pub struct DefaultRunArgs {
+    /// Build in release mode, with optimizations.
+    #[structopt(long)]
+    pub release: bool,
+
+    /// WASI runtime: `wasmtime` or `wasmer` (default: the first one installed).
+    #[structopt(long, possible_values = RUNTIMES)]
+    pub runtime: Option<String>,
+
+    /// Build arguments.
+    #[structopt(flatten)]
+    pub build_args: DefaultBuildArgs,
+
+    /// Arguments given to the WASM (after `--`).
+    #[structopt(last = true, parse(from_os_str))]
+    pub args: Vec<OsString>,
+}
+
+impl DefaultRunArgs {
+    /// Run the `run` command.
+    ///
+    /// The frontend package is always built for `wasm32-wasi`. The standard input and outputs are
+    /// given to the runtime. Returns [`Error::RunFailed`] if the WASM fails.
+    pub fn run(mut self) -> Result<(), Error> {
+        let hooks = hooks()?;
+        if self.build_args.target == Some(BuildTarget::Web) {
+            return Err(anyhow!("the `run` command only supports the target `wasm32-wasi`").into());
+        }
+        self.build_args.target = Some(BuildTarget::Wasi);
+        Project::select_frontend(&self.build_args)?;
+
+        let profile = if self.release {
+            BuildProfile::Release
+        } else {
+            BuildProfile::Dev
+        };
+        let output = build(profile, &self.build_args, hooks, false, BuildTrigger::Build)?;
+
+        let runtimes = match self.runtime.as_deref() {
+            Some(runtime) => vec![runtime],
+            None => RUNTIMES.to_vec(),
+        };
+        for runtime in runtimes.iter() {
+            // NOTE: everything after `--` is given to the WASM, even the options
+            let mut command = Command::new(runtime);
+            crate::process_env(&self.build_args, output.profile, &mut command);
+            command
+                .args(["run", "--"])
+                .arg(&output.wasm_path)
+                .args(&self.args);
+
+            log::info!("Running `{}` with {}", output.wasm_path.display(), runtime);
+            log::debug!("Running {:?}", command);
+            match command.status() {
+                Ok(status) if status.success() => return Ok(()),
+                Ok(status) => {
+                    return Err(Error::RunFailed {
+                        program: runtime.to_string(),
+                        status,
+                    })
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => {
+                    return Err(anyhow::Error::new(err)
+                        .context(format!("could not start `{}`", runtime))
+                        .into())
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "could not find a WASI runtime: install {}",
+            runtimes
+                .iter()
+                .map(|x| format!("`{}`", x))
+                .collect::<Vec<_>>()
+                .join(" or ")
+        )
+        .into())
+    }
+}
@@ -0,0 +1,1921 @@
+use crate::WatchArgs;
+use cargo_metadata::{Metadata, Package};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+/// Default delay used by the native watcher to debounce the events.
+pub(crate) const DEFAULT_DEBOUNCE_DELAY: Duration = Duration::from_secs(2);
+
+/// Delay during which the events following each others are coalesced into a single rebuild.
+const COALESCE_WINDOW: Duration = Duration::from_millis(200);
+
+/// Default interval of the polling watcher.
+pub(crate) const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+thread_local! {
+    /// Set by the watch loop of the thread when an event arrives while the callback is running.
+    static SUPERSEDED: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// Returns `true` if a file changed since the current rebuild of the watch loop of this thread
+/// started. It is always `false` outside of a watch loop.
+pub(crate) fn superseded() -> bool {
+    SUPERSEDED.with(|x| {
+        x.borrow()
+            .as_ref()
+            .map(|x| x.load(Ordering::SeqCst))
+            .unwrap_or(false)
+    })
+}
+
+/// A watcher passed to the watch hooks.
+///
+/// It is the native watcher of the platform unless polling has been requested or the native
+/// watcher could not be initialized. Both implement [`notify::Watcher`] so the paths are
+/// registered the same way in both modes.
+pub struct FileWatcher {
+    inner: Inner,
+    tx: mpsc::Sender<notify::DebouncedEvent>,
+    watched: HashMap<PathBuf, RecursiveMode>,
+    /// Watched paths that have been removed and are waiting to reappear.
+    missing: HashMap<PathBuf, RecursiveMode>,
+    /// The real locations that are watched and the paths they have been registered with.
+    aliases: HashMap<PathBuf, PathBuf>,
+    /// The directories symlinked inside the watched directories, watched with them.
+    links: HashMap<PathBuf, Vec<PathBuf>>,
+    config: WatchConfig,
+}
+
+enum Inner {
+    Native(RecommendedWatcher),
+    Poll(PollWatcher),
+}
+
+impl FileWatcher {
+    pub(crate) fn new(
+        tx: mpsc::Sender<notify::DebouncedEvent>,
+        args: &dyn WatchArgs,
+    ) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        if let Some(interval) = args.poll_interval() {
+            log::info!("Polling for changes every {:?}", interval);
+            return Ok(Self {
+                inner: Inner::Poll(
+                    PollWatcher::new(tx.clone(), interval)
+                        .context("could not initialize watcher")?,
+                ),
+                tx,
+                watched: HashMap::new(),
+                missing: HashMap::new(),
+                aliases: HashMap::new(),
+                links: HashMap::new(),
+                config: WatchConfig::default(),
+            });
+        }
+
+        let inner = match RecommendedWatcher::new(tx.clone(), args.debounce()) {
+            Ok(watcher) => Inner::Native(watcher),
+            Err(err) => {
+                log::warn!(
+                    "Could not initialize the native watcher ({}), falling back to polling every \
+                    {:?}",
+                    err,
+                    DEFAULT_POLL_INTERVAL,
+                );
+                Inner::Poll(
+                    PollWatcher::new(tx.clone(), DEFAULT_POLL_INTERVAL)
+                        .context("could not initialize watcher")?,
+                )
+            }
+        };
+
+        Ok(Self {
+            inner,
+            tx,
+            watched: HashMap::new(),
+            missing: HashMap::new(),
+            aliases: HashMap::new(),
+            links: HashMap::new(),
+            config: WatchConfig::default(),
+        })
+    }
+
+    /// Returns `true` if the file system is polled instead of using the native events of the
+    /// platform.
+    pub fn is_polling(&self) -> bool {
+        matches!(self.inner, Inner::Poll(_))
+    }
+
+    /// Returns a handle that can be used to trigger a rebuild from anywhere, for example from a
+    /// background thread listening to an external event source.
+    pub fn rebuild_handle(&self) -> RebuildHandle {
+        RebuildHandle {
+            tx: self.tx.clone(),
+        }
+    }
+
+    /// The filtering of the changes, with the ignores added by the watch hooks.
+    pub fn config(&self) -> &WatchConfig {
+        &self.config
+    }
+
+    /// The filtering of the changes: the watch hooks can ignore more paths or watch a directory
+    /// without its subdirectories. The configuration is reset when the hooks run again after a
+    /// change of a manifest.
+    pub fn config_mut(&mut self) -> &mut WatchConfig {
+        &mut self.config
+    }
+
+    /// Set the paths that are always ignored and the root of the patterns of
+    /// [`WatchConfig::ignore_glob`].
+    pub(crate) fn ignore_builtin(&mut self, root: &Path, paths: Vec<PathBuf>) {
+        self.config.root = root.to_path_buf();
+        self.config.builtin_paths = paths;
+    }
+
+    /// Register the paths to watch with `register` (usually the watch hooks) with a configuration
+    /// reset to the built-in ignores, then watch the paths of
+    /// [`WatchConfig::watch_path_nonrecursive`].
+    pub(crate) fn register(
+        &mut self,
+        register: impl FnOnce(&mut Self) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        use anyhow::Context;
+
+        let previous = self.config.clear();
+        if let Err(err) = register(self) {
+            self.config.restore(previous);
+            return Err(err);
+        }
+        for path in self.config.nonrecursive.clone() {
+            Watcher::watch(self, &path, RecursiveMode::NonRecursive)
+                .with_context(|| format!("could not watch `{}`", path.display()))?;
+        }
+
+        Ok(())
+    }
+
+    /// The registered paths, the ones waiting to reappear and the root of the workspace.
+    pub(crate) fn roots(&self) -> Vec<PathBuf> {
+        self.watched
+            .keys()
+            .chain(self.missing.keys())
+            .chain(Some(&self.config.root).filter(|x| !x.as_os_str().is_empty()))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns `true` if the changes of this path are already reported: it has been registered or
+    /// it is inside a directory that has been registered.
+    pub(crate) fn is_watched(&self, path: &Path) -> bool {
+        let path = canonicalize(path);
+        self.watched.iter().any(|(watched, mode)| {
+            let watched = canonicalize(watched);
+            match mode {
+                RecursiveMode::Recursive => path.starts_with(&watched),
+                RecursiveMode::NonRecursive => {
+                    path == watched
+                        || (watched.is_dir() && path.parent() == Some(watched.as_path()))
+                }
+            }
+        })
+    }
+
+    /// Register the watched paths again with `register` (see [`FileWatcher::register`]) and
+    /// unwatch the paths that have not been registered this time.
+    ///
+    /// Nothing is unwatched if `register` fails.
+    pub(crate) fn rewatch(
+        &mut self,
+        register: impl FnOnce(&mut Self) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let previous = std::mem::take(&mut self.watched);
+
+        if let Err(err) = self.register(register) {
+            self.watched.extend(previous);
+            return Err(err);
+        }
+
+        let removed: Vec<_> = previous
+            .keys()
+            .filter(|x| !self.watched.contains_key(*x))
+            .cloned()
+            .collect();
+        for path in removed.iter() {
+            log::info!("Stop watching: {}", path.display());
+            self.missing.remove(path);
+            if let Err(err) = self.unwatch_inner(path) {
+                log::debug!("Could not unwatch `{}`: {}", path.display(), err);
+            }
+        }
+        for path in self.watched.keys().filter(|x| !previous.contains_key(*x)) {
+            log::info!("Watching: {}", path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Keep watching a path that has been removed or renamed.
+    ///
+    /// Editors often save by renaming a temporary file over the original. The native watcher then
+    /// drops the watch of the original file so it is registered again. If the path doesn't exist
+    /// anymore, its parent directory is watched until it reappears.
+    fn path_removed(&mut self, path: &Path) {
+        let mode = match self.watched.get(path) {
+            Some(mode) => *mode,
+            None => return,
+        };
+
+        if path.exists() {
+            log::debug!("Watching again: {}", path.display());
+            if let Err(err) = self.watch_inner(path, mode) {
+                log::warn!("Could not watch `{}` again: {}", path.display(), err);
+            }
+            return;
+        }
+
+        log::debug!("Waiting for `{}` to reappear", path.display());
+        if let Some(parent) = path.parent() {
+            if !self.watched.contains_key(parent) {
+                if let Err(err) = self.watch_inner(parent, RecursiveMode::NonRecursive) {
+                    log::warn!("Could not watch `{}`: {}", parent.display(), err);
+                }
+            }
+        }
+        self.missing.insert(path.to_path_buf(), mode);
+    }
+
+    /// Watch again a path that has been removed and reappeared or that has been replaced.
+    fn path_created(&mut self, path: &Path) {
+        let mode = match self.missing.remove(path) {
+            Some(mode) => mode,
+            None => {
+                if let Some(mode) = self.watched.get(path).copied() {
+                    if let Err(err) = self.watch_inner(path, mode) {
+                        log::warn!("Could not watch `{}` again: {}", path.display(), err);
+                    }
+                }
+                return;
+            }
+        };
+
+        log::debug!("Watching again: {}", path.display());
+        if let Err(err) = self.watch_inner(path, mode) {
+            log::warn!("Could not watch `{}` again: {}", path.display(), err);
+        }
+
+        if let Some(parent) = path.parent() {
+            if !self.watched.contains_key(parent)
+                && !self.missing.keys().any(|x| x.parent() == Some(parent))
+            {
+                let _ = self.unwatch_inner(parent);
+            }
+        }
+    }
+
+    /// The path of an event, with the real location of a watched path replaced by the path it has
+    /// been registered with.
+    fn resolve(&self, path: &Path) -> PathBuf {
+        self.aliases
+            .iter()
+            .filter(|(real_path, _)| path.starts_with(real_path))
+            .max_by_key(|(real_path, _)| real_path.components().count())
+            .and_then(|(real_path, alias)| Some(alias.join(path.strip_prefix(real_path).ok()?)))
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+
+    /// Watch the real location of a path.
+    ///
+    /// The native watchers do not all follow the symlinks: the directories symlinked inside a
+    /// directory watched recursively are watched as well. The symlinks that lead back to a
+    /// directory already watched are skipped.
+    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) -> notify::Result<()> {
+        let real_path = canonicalize(path);
+        match &mut self.inner {
+            Inner::Native(x) => x.watch(&real_path, recursive_mode)?,
+            Inner::Poll(x) => x.watch(&real_path, recursive_mode)?,
+        }
+        self.aliases.insert(real_path.clone(), path.to_path_buf());
+
+        if recursive_mode == RecursiveMode::NonRecursive || !real_path.is_dir() {
+            return Ok(());
+        }
+        let mut links = Vec::new();
+        for link in symlinked_dirs(path) {
+            let target = canonicalize(&link);
+            let watched_elsewhere = self.aliases.get(&target).is_some_and(|x| *x != link);
+            if target.starts_with(&real_path) || watched_elsewhere {
+                log::debug!("Not following the symlink `{}`", link.display());
+                continue;
+            }
+            match self.watch_inner(&link, RecursiveMode::Recursive) {
+                Ok(()) => links.push(link),
+                Err(err) => log::warn!("Could not watch `{}`: {}", link.display(), err),
+            }
+        }
+        self.links.insert(path.to_path_buf(), links);
+
+        Ok(())
+    }
+
+    fn unwatch_inner(&mut self, path: &Path) -> notify::Result<()> {
+        for link in self.links.remove(path).unwrap_or_default() {
+            let _ = self.unwatch_inner(&link);
+        }
+
+        let real_path = self
+            .aliases
+            .iter()
+            .find(|(_, alias)| alias.as_path() == path)
+            .map(|(real_path, _)| real_path.clone())
+            .unwrap_or_else(|| canonicalize(path));
+        self.aliases.remove(&real_path);
+        match &mut self.inner {
+            Inner::Native(x) => x.unwatch(&real_path),
+            Inner::Poll(x) => x.unwatch(&real_path),
+        }
+    }
+}
+
+impl Watcher for FileWatcher {
+    /// Create a native watcher that sends raw events.
+    ///
+    /// The [`RebuildHandle`] of a watcher created this way does nothing.
+    fn new_raw(tx: mpsc::Sender<notify::RawEvent>) -> notify::Result<Self> {
+        Ok(Self {
+            inner: Inner::Native(RecommendedWatcher::new_raw(tx)?),
+            tx: mpsc::channel().0,
+            watched: HashMap::new(),
+            missing: HashMap::new(),
+            aliases: HashMap::new(),
+            links: HashMap::new(),
+            config: WatchConfig::default(),
+        })
+    }
+
+    fn new(tx: mpsc::Sender<notify::DebouncedEvent>, delay: Duration) -> notify::Result<Self> {
+        Ok(Self {
+            inner: Inner::Native(RecommendedWatcher::new(tx.clone(), delay)?),
+            tx,
+            watched: HashMap::new(),
+            missing: HashMap::new(),
+            aliases: HashMap::new(),
+            links: HashMap::new(),
+            config: WatchConfig::default(),
+        })
+    }
+
+    fn watch<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        recursive_mode: RecursiveMode,
+    ) -> notify::Result<()> {
+        self.watch_inner(path.as_ref(), recursive_mode)?;
+        self.watched
+            .insert(path.as_ref().to_path_buf(), recursive_mode);
+        Ok(())
+    }
+
+    fn unwatch<P: AsRef<Path>>(&mut self, path: P) -> notify::Result<()> {
+        self.watched.remove(path.as_ref());
+        self.missing.remove(path.as_ref());
+        self.unwatch_inner(path.as_ref())
+    }
+}
+
+/// A handle to trigger a rebuild programmatically.
+///
+/// The rebuild goes through the same pipeline as the file events. The handle can be cloned and
+/// moved to other threads.
+#[derive(Debug, Clone)]
+pub struct RebuildHandle {
+    tx: mpsc::Sender<notify::DebouncedEvent>,
+}
+
+impl RebuildHandle {
+    /// Enqueue a rebuild. This does nothing if the watcher has stopped.
+    pub fn trigger(&self) {
+        let _ = self.tx.send(notify::DebouncedEvent::Rescan);
+    }
+}
+
+/// The filtering of the changes of a [`FileWatcher`], shaped by the watch hooks with
+/// [`FileWatcher::config_mut`].
+///
+/// The built-in ignores (the build directory, the target directory and the directories `.git` and
+/// `target` wherever they are) can be inspected but not removed: the builds would trigger other
+/// builds.
+#[derive(Debug, Clone, Default)]
+pub struct WatchConfig {
+    root: PathBuf,
+    builtin_paths: Vec<PathBuf>,
+    globs: Vec<String>,
+    paths: Vec<PathBuf>,
+    nonrecursive: Vec<PathBuf>,
+    /// Incremented when the hooks run again, the filter is then updated.
+    revision: u64,
+}
+
+/// The ignores added by the hooks, kept when they fail to register the paths again.
+type HookIgnores = (Vec<String>, Vec<PathBuf>, Vec<PathBuf>);
+
+impl WatchConfig {
+    /// Ignore the changes on the paths that match this pattern, with the syntax of `.gitignore`
+    /// relative to the root of the workspace (like [`WatchArgs::ignore_globs`]).
+    pub fn ignore_glob(&mut self, glob: impl Into<String>) -> anyhow::Result<&mut Self> {
+        use anyhow::Context;
+
+        let glob = glob.into();
+        GitignoreBuilder::new(&self.root)
+            .add_line(None, &glob)
+            .with_context(|| format!("invalid ignore pattern `{}`", glob))?;
+        self.globs.push(glob);
+        Ok(self)
+    }
+
+    /// Ignore the changes on this file or inside this directory, even if it is watched.
+    pub fn ignore_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Watch the changes of the files of this directory but not the ones of its subdirectories.
+    /// The directory is watched after the hook returns.
+    pub fn watch_path_nonrecursive(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.nonrecursive.push(path.into());
+        self
+    }
+
+    /// The directories that are always ignored: the build directory and the target directory.
+    pub fn builtin_ignored_paths(&self) -> &[PathBuf] {
+        &self.builtin_paths
+    }
+
+    /// The names of the directories that are always ignored wherever they are.
+    pub fn builtin_ignored_dirs(&self) -> &'static [&'static str] {
+        BUILTIN_IGNORED_DIRS
+    }
+
+    /// The patterns added by [`WatchConfig::ignore_glob`].
+    pub fn ignored_globs(&self) -> &[String] {
+        &self.globs
+    }
+
+    /// The paths added by [`WatchConfig::ignore_path`].
+    pub fn ignored_paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// The directories added by [`WatchConfig::watch_path_nonrecursive`].
+    pub fn nonrecursive_paths(&self) -> &[PathBuf] {
+        &self.nonrecursive
+    }
+
+    /// Remove the ignores of the hooks before they run again.
+    fn clear(&mut self) -> HookIgnores {
+        self.revision += 1;
+        (
+            std::mem::take(&mut self.globs),
+            std::mem::take(&mut self.paths),
+            std::mem::take(&mut self.nonrecursive),
+        )
+    }
+
+    fn restore(&mut self, (globs, paths, nonrecursive): HookIgnores) {
+        self.globs = globs;
+        self.paths = paths;
+        self.nonrecursive = nonrecursive;
+    }
+}
+
+/// Names of the ignore files that are looked up in every directory, by order of precedence.
+const IGNORE_FILES: &[&str] = &[".ignore", ".gitignore"];
+
+/// Directories that are always ignored by the watcher, wherever they are.
+const BUILTIN_IGNORED_DIRS: &[&str] = &[".git", "target"];
+
+/// What needs to be rebuilt after a change.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Change {
+    /// The sources of the crate changed: everything needs to be rebuilt.
+    Source,
+    /// A manifest changed: everything needs to be rebuilt and the dependencies might have changed.
+    Manifest,
+    /// A JS snippet changed: the bindings need to be generated again to copy it, even if cargo
+    /// has nothing to rebuild.
+    Snippet,
+    /// An asset changed: only the `post_build` hook needs to run.
+    Asset,
+    /// A source of the bundle changed: only the bundle command needs to run.
+    Bundle,
+    /// A file of the serve directory changed: only the browser needs to be reloaded.
+    Served,
+}
+
+impl Change {
+    /// Combine two changes into the one that rebuilds everything both require.
+    fn merge(self, other: Self) -> Self {
+        use Change::*;
+
+        match (self, other) {
+            (Manifest, _) | (_, Manifest) => Manifest,
+            (Source, _) | (_, Source) => Source,
+            (Snippet, _) | (_, Snippet) => Snippet,
+            (Asset, _) | (_, Asset) => Asset,
+            (Bundle, _) | (_, Bundle) => Bundle,
+            (Served, Served) => Served,
+        }
+    }
+}
+
+/// Filters out the file events that should never trigger a rebuild.
+///
+/// The lookup follows the rules of git: the ignore files of every directory between the file and
+/// the root of the repository are considered and the deepest one takes precedence.
+pub(crate) struct WatchFilter {
+    use_gitignore: bool,
+    extensions: Vec<String>,
+    snippet_extensions: Vec<String>,
+    asset_extensions: Vec<String>,
+    /// Directories of which all the files are assets, whatever their extensions.
+    assets: Vec<PathBuf>,
+    /// Directories of the sources of the bundle.
+    bundle: Vec<PathBuf>,
+    excluded: Vec<PathBuf>,
+    served: Vec<PathBuf>,
+    globs: Option<(PathBuf, Gitignore)>,
+    /// The ignores of the watch hooks ([`WatchConfig`]) and the revision they come from.
+    hook_excluded: Vec<PathBuf>,
+    hook_globs: Option<(PathBuf, Gitignore)>,
+    revision: Option<u64>,
+    /// The watched paths and the root of the workspace: the built-in ignored directories are only
+    /// looked for inside them.
+    roots: Vec<PathBuf>,
+    cache: HashMap<PathBuf, Vec<Gitignore>>,
+}
+
+impl WatchFilter {
+    pub fn new(
+        use_gitignore: bool,
+        extensions: Vec<String>,
+        snippet_extensions: Vec<String>,
+        asset_extensions: Vec<String>,
+    ) -> Self {
+        Self {
+            use_gitignore,
+            extensions,
+            snippet_extensions,
+            asset_extensions,
+            assets: Vec::new(),
+            bundle: Vec::new(),
+            excluded: Vec::new(),
+            served: Vec::new(),
+            globs: None,
+            hook_excluded: Vec::new(),
+            hook_globs: None,
+            revision: None,
+            roots: Vec::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Ignore the changes on the paths that match these patterns, with the syntax of `.gitignore`
+    /// relative to `root`. They apply even if the ignore files are not used.
+    pub fn ignore_globs(&mut self, root: impl AsRef<Path>, globs: &[String]) -> anyhow::Result<()> {
+        use anyhow::Context;
+
+        if globs.is_empty() {
+            return Ok(());
+        }
+
+        let root = canonicalize(root.as_ref());
+        let mut builder = GitignoreBuilder::new(&root);
+        for glob in globs.iter() {
+            builder
+                .add_line(None, glob)
+                .with_context(|| format!("invalid ignore pattern `{}`", glob))?;
+        }
+        self.globs = Some((root, builder.build()?));
+        Ok(())
+    }
+
+    /// Apply the ignores added by the watch hooks, if they changed since the last time.
+    pub fn configure(&mut self, config: &WatchConfig) -> anyhow::Result<()> {
+        if self.revision == Some(config.revision) {
+            return Ok(());
+        }
+
+        self.hook_excluded = config.paths.iter().map(|x| canonicalize(x)).collect();
+        self.hook_globs = if config.globs.is_empty() {
+            None
+        } else {
+            let root = canonicalize(&config.root);
+            let mut builder = GitignoreBuilder::new(&root);
+            for glob in config.globs.iter() {
+                builder.add_line(None, glob)?;
+            }
+            Some((root, builder.build()?))
+        };
+        self.revision = Some(config.revision);
+        Ok(())
+    }
+
+    /// Returns what needs to be rebuilt after a change on this path or `None` if the change must
+    /// be ignored.
+    pub fn classify(&mut self, path: &Path) -> Option<Change> {
+        // NOTE: the serve directory is usually ignored by git, the build directory can be inside
+        let canonical_path = canonicalize(path);
+        if !self.is_excluded(path)
+            && self
+                .served
+                .iter()
+                .any(|x| path.starts_with(x) || canonical_path.starts_with(x))
+        {
+            return Some(Change::Served);
+        }
+
+        if self.is_ignored(path) {
+            return None;
+        }
+
+        if path
+            .file_name()
+            .map(|x| x == "Cargo.toml" || x == "Cargo.lock")
+            .unwrap_or(false)
+        {
+            return Some(Change::Manifest);
+        }
+
+        let canonical_path = canonicalize(path);
+        if self
+            .assets
+            .iter()
+            .any(|x| path.starts_with(x) || canonical_path.starts_with(x))
+        {
+            return Some(Change::Asset);
+        }
+
+        if self
+            .bundle
+            .iter()
+            .any(|x| path.starts_with(x) || canonical_path.starts_with(x))
+        {
+            return Some(Change::Bundle);
+        }
+
+        // NOTE: files without extension can't be identified, it's safer to rebuild everything.
+        let extension = match path.extension() {
+            Some(x) => x,
+            None => return Some(Change::Source),
+        };
+
+        if self.extensions.iter().any(|x| extension == x.as_str()) {
+            Some(Change::Source)
+        } else if self
+            .snippet_extensions
+            .iter()
+            .any(|x| extension == x.as_str())
+        {
+            Some(Change::Snippet)
+        } else if self
+            .asset_extensions
+            .iter()
+            .any(|x| extension == x.as_str())
+        {
+            Some(Change::Asset)
+        } else {
+            None
+        }
+    }
+
+    /// Ignore all the changes that happen inside this directory.
+    pub fn exclude(&mut self, path: impl AsRef<Path>) {
+        self.excluded.push(canonicalize(path.as_ref()));
+    }
+
+    /// The changes that happen inside this directory only run the `post_build` hook, whatever the
+    /// extensions of the files.
+    pub fn assets(&mut self, path: impl AsRef<Path>) {
+        self.assets.push(canonicalize(path.as_ref()));
+    }
+
+    /// The changes that happen inside this directory only run the bundle command, whatever the
+    /// extensions of the files.
+    pub fn bundle(&mut self, path: impl AsRef<Path>) {
+        self.bundle.push(canonicalize(path.as_ref()));
+    }
+
+    /// The changes that happen inside this directory only reload the browser.
+    pub fn serve(&mut self, path: impl AsRef<Path>) {
+        self.served.push(canonicalize(path.as_ref()));
+    }
+
+    /// Only look for the built-in ignored directories inside these directories.
+    pub fn roots(&mut self, roots: impl IntoIterator<Item = PathBuf>) {
+        self.roots = roots.into_iter().map(|x| canonicalize(&x)).collect();
+    }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        let canonical_path = canonicalize(path);
+        self.excluded
+            .iter()
+            .chain(self.hook_excluded.iter())
+            .any(|x| path.starts_with(x) || canonical_path.starts_with(x))
+    }
+
+    /// Returns `true` if a change on this path must not trigger a rebuild.
+    pub fn is_ignored(&mut self, path: &Path) -> bool {
+        if self.is_excluded(path) {
+            return true;
+        }
+
+        for (root, globs) in self.globs.iter().chain(self.hook_globs.iter()) {
+            let canonical_path = canonicalize(path);
+            if canonical_path.starts_with(root)
+                && globs
+                    .matched_path_or_any_parents(&canonical_path, canonical_path.is_dir())
+                    .is_ignore()
+            {
+                return true;
+            }
+        }
+
+        // NOTE: the directories above the watched ones can have any name
+        let canonical_path = canonicalize(path);
+        let relative_path = self
+            .roots
+            .iter()
+            .filter_map(|root| canonical_path.strip_prefix(root).ok())
+            .max_by_key(|x| x.components().count())
+            .unwrap_or(path);
+        if let Some(parent) = relative_path.parent() {
+            if parent
+                .components()
+                .any(|x| BUILTIN_IGNORED_DIRS.iter().any(|dir| x.as_os_str() == *dir))
+            {
+                return true;
+            }
+        }
+
+        // NOTE: an ignore file has been modified, its matcher needs to be reloaded.
+        if let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) {
+            if IGNORE_FILES.iter().any(|x| file_name == *x) {
+                self.cache.remove(dir);
+            }
+        }
+
+        if path
+            .file_name()
+            .map(|x| x.as_encoded_bytes().starts_with(b"."))
+            .unwrap_or(false)
+        {
+            return true;
+        }
+
+        if !self.use_gitignore {
+            return false;
+        }
+
+        let is_dir = path.is_dir();
+        for dir in path.ancestors().skip(1) {
+            let matchers = self
+                .cache
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| load_ignore_files(dir));
+
+            for matcher in matchers.iter() {
+                let matched = matcher.matched_path_or_any_parents(path, is_dir);
+                if matched.is_ignore() {
+                    return true;
+                } else if matched.is_whitelist() {
+                    return false;
+                }
+            }
+
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+
+        false
+    }
+}
+
+/// Process the file events until the watcher is dropped.
+///
+/// The events that are not ignored by the filter are coalesced: everything that arrives within
+/// [`COALESCE_WINDOW`] of the previous event, or while the callback is running, results in a
+/// single call to the callback.
+///
+/// The callback can give up when a file changes while it runs (see [`superseded`]) by returning
+/// [`Error::BuildCancelled`](crate::Error::BuildCancelled): its change is then merged with the
+/// next ones.
+///
+/// The events are recorded as pending runs of the orchestrator of the pipelines if there is one.
+pub(crate) fn watch_loop(
+    rx: mpsc::Receiver<notify::DebouncedEvent>,
+    watcher: &mut FileWatcher,
+    filter: WatchFilter,
+    orchestrator: Option<&crate::pipeline::Orchestrator>,
+    mut callback: impl FnMut(Change, &mut FileWatcher) -> anyhow::Result<()>,
+) {
+    let superseded = Arc::new(AtomicBool::new(false));
+    let filter = Arc::new(Mutex::new(filter));
+    configure(&filter, watcher);
+    let rx = forward_events(
+        rx,
+        filter.clone(),
+        superseded.clone(),
+        orchestrator.cloned(),
+    );
+    SUPERSEDED.with(|x| *x.borrow_mut() = Some(superseded.clone()));
+    let mut cancelled = None;
+
+    while let Ok(message) = rx.recv() {
+        let mut pending = handle_event(watcher, &filter, message);
+        let mut count = pending.is_some() as usize;
+        let mut disconnected = false;
+
+        loop {
+            match rx.recv_timeout(COALESCE_WINDOW) {
+                Ok(message) => {
+                    if let Some(change) = handle_event(watcher, &filter, message) {
+                        count += 1;
+                        pending = Some(pending.map_or(change, |x| x.merge(change)));
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    disconnected = true;
+                    break;
+                }
+            }
+        }
+
+        let pending = match (cancelled.take(), pending) {
+            (Some(cancelled), Some(change)) => Some(change.merge(cancelled)),
+            (cancelled, pending) => pending.or(cancelled),
+        };
+        // NOTE: the events that arrive from now on are queued for the next run
+        if let Some(orchestrator) = orchestrator {
+            orchestrator.dequeue();
+        }
+        if let Some(change) = pending {
+            if count > 1 {
+                log::info!("Coalesced {} events into one rebuild", count);
+            }
+            superseded.store(false, Ordering::SeqCst);
+            // NOTE: the hooks are called during the rebuild; a panic must not stop the watcher
+            match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                callback(change, watcher)
+            })) {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) if crate::is_cancelled(&err) => {
+                    log::info!("Restarting the rebuild after the new changes");
+                    cancelled = Some(change);
+                }
+                Ok(Err(err)) => log::error!("{}", err),
+                Err(_) => log::error!("The rebuild panicked"),
+            }
+            // NOTE: the hooks might have run again after a change of a manifest
+            configure(&filter, watcher);
+        }
+
+        if disconnected {
+            break;
+        }
+    }
+
+    SUPERSEDED.with(|x| *x.borrow_mut() = None);
+}
+
+fn configure(filter: &Mutex<WatchFilter>, watcher: &FileWatcher) {
+    let mut filter = filter.lock().unwrap();
+    filter.roots(watcher.roots());
+    if let Err(err) = filter.configure(watcher.config()) {
+        log::warn!("Could not apply the ignores of the watch hooks: {}", err);
+    }
+}
+
+/// Forward the events to a new channel and raise `superseded` for the events that are not ignored
+/// by the filter, so a rebuild can know about them while the watch loop waits for it.
+fn forward_events(
+    rx: mpsc::Receiver<notify::DebouncedEvent>,
+    filter: Arc<Mutex<WatchFilter>>,
+    superseded: Arc<AtomicBool>,
+    orchestrator: Option<crate::pipeline::Orchestrator>,
+) -> mpsc::Receiver<notify::DebouncedEvent> {
+    use notify::DebouncedEvent::*;
+
+    let (tx, forwarded) = mpsc::channel();
+    std::thread::spawn(move || {
+        for message in rx {
+            let path = match &message {
+                Create(path) | Write(path) | Remove(path) | Rename(_, path) => Some(path),
+                Rescan => None,
+                _ => {
+                    if tx.send(message).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            if path.is_none_or(|path| filter.lock().unwrap().classify(path).is_some()) {
+                superseded.store(true, Ordering::SeqCst);
+                if let Some(orchestrator) = &orchestrator {
+                    orchestrator.trigger();
+                }
+            }
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    forwarded
+}
+
+/// Keep the watches up to date after an event and returns what needs to be rebuilt or `None` if
+/// it must be ignored.
+fn handle_event(
+    watcher: &mut FileWatcher,
+    filter: &Mutex<WatchFilter>,
+    message: notify::DebouncedEvent,
+) -> Option<Change> {
+    use notify::DebouncedEvent::*;
+
+    let mut filter = filter.lock().unwrap();
+
+    log::trace!("File event: {:?}", message);
+    match message {
+        Create(path) => {
+            let path = watcher.resolve(&path);
+            watcher.path_created(&path);
+            filter.classify(&path)
+        }
+        Write(path) => filter.classify(&watcher.resolve(&path)),
+        Remove(path) => {
+            let path = watcher.resolve(&path);
+            watcher.path_removed(&path);
+            filter.classify(&path)
+        }
+        Rename(from, to) => {
+            let (from, to) = (watcher.resolve(&from), watcher.resolve(&to));
+            watcher.path_removed(&from);
+            watcher.path_created(&to);
+            filter.classify(&to)
+        }
+        // NOTE: a rescan is emitted when events might have been missed and by the
+        //       `RebuildHandle`
+        Rescan => Some(Change::Source),
+        Error(err, _) => {
+            log::error!("Watch error: {}", err);
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Returns the path dependencies of a package, transitively, including the ones that are not members
+/// of the workspace. Registry and git dependencies are excluded.
+pub(crate) fn local_dependencies<'a>(
+    metadata: &'a Metadata,
+    package: &'a Package,
+) -> Vec<&'a Package> {
+    let mut found = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue = vec![package];
+    seen.insert(&package.id);
+
+    while let Some(current) = queue.pop() {
+        for dependency in current.dependencies.iter() {
+            let path = match dependency.path.as_ref() {
+                Some(x) => x,
+                None => continue,
+            };
+            if let Some(dependency) = metadata
+                .packages
+                .iter()
+                .find(|x| x.source.is_none() && x.manifest_path.parent() == Some(path.as_path()))
+            {
+                if seen.insert(&dependency.id) {
+                    found.push(dependency);
+                    queue.push(dependency);
+                }
+            }
+        }
+    }
+
+    found
+}
+
+/// Returns the paths to watch for a package and its path dependencies.
+///
+/// The whole directory is watched for the members of the workspace. For the packages outside the
+/// workspace, only the `src/` directory and the manifest are watched.
+pub(crate) fn package_watch_paths(
+    metadata: &Metadata,
+    package: &Package,
+) -> Vec<(PathBuf, RecursiveMode)> {
+    let members: HashSet<_> = metadata.workspace_members.iter().collect();
+    let mut paths = Vec::new();
+
+    for package in std::iter::once(package).chain(local_dependencies(metadata, package)) {
+        let package_dir = package.manifest_path.parent().unwrap();
+        if members.contains(&package.id) {
+            paths.push((package_dir.to_path_buf(), RecursiveMode::Recursive));
+        } else {
+            let src_dir = package_dir.join("src");
+            if src_dir.exists() {
+                paths.push((src_dir, RecursiveMode::Recursive));
+            }
+            paths.push((package.manifest_path.clone(), RecursiveMode::NonRecursive));
+        }
+    }
+
+    paths
+}
+
+/// Returns `true` if both paths lead to the same location, even if they don't exist yet.
+pub(crate) fn same_path(a: &Path, b: &Path) -> bool {
+    canonicalize(a) == canonicalize(b)
+}
+
+/// Canonicalize a path that might not exist (anymore) by canonicalizing its closest existing
+/// ancestor.
+///
+/// The remaining components are joined one by one: a verbatim path on Windows (`\\?\C:\...`)
+/// does not accept `/` as a separator. The verbatim prefix is then removed so the path can be
+/// compared with the paths of the events.
+pub(crate) fn canonicalize(path: &Path) -> PathBuf {
+    let canonical = path
+        .ancestors()
+        .find_map(|ancestor| {
+            let canonical = ancestor.canonicalize().ok()?;
+            let rest = path.strip_prefix(ancestor).ok()?;
+            Some(rest.components().fold(canonical, |acc, x| acc.join(x)))
+        })
+        .unwrap_or_else(|| path.to_path_buf());
+    strip_verbatim(canonical)
+}
+
+/// The symlinks to directories inside a directory, without following them.
+///
+/// When the symlinks cannot be created without privileges (on Windows), there is usually none and
+/// the directories are watched as they are.
+fn symlinked_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut links = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(x) => x,
+                Err(_) => continue,
+            };
+            if file_type.is_symlink() && path.is_dir() {
+                links.push(path);
+            } else if file_type.is_dir()
+                && !BUILTIN_IGNORED_DIRS.iter().any(|x| entry.file_name() == *x)
+            {
+                dirs.push(path);
+            }
+        }
+    }
+    links.sort();
+    links
+}
+
+/// Remove the verbatim prefix of a path on a drive: `\\?\C:\work` becomes `C:\work`.
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    use std::path::{Component, Prefix};
+
+    let mut components = path.components();
+    if let Some(Component::Prefix(prefix)) = components.next() {
+        if let Prefix::VerbatimDisk(drive) = prefix.kind() {
+            let mut stripped = PathBuf::from(format!("{}:", drive as char));
+            stripped.push(Component::RootDir);
+            stripped.extend(components.filter(|x| *x != Component::RootDir));
+            return stripped;
+        }
+    }
+    path
+}
+
+fn load_ignore_files(dir: &Path) -> Vec<Gitignore> {
+    IGNORE_FILES
+        .iter()
+        .map(|x| dir.join(x))
+        .filter(|x| x.is_file())
+        .filter_map(|x| {
+            let (matcher, err) = Gitignore::new(&x);
+            if let Some(err) = err {
+                log::warn!("Could not parse ignore file `{}`: {}", x.display(), err);
+            }
+            (!matcher.is_empty()).then_some(matcher)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    struct Polling;
+
+    impl WatchArgs for Polling {
+        fn poll_interval(&self) -> Option<Duration> {
+            Some(DEFAULT_POLL_INTERVAL)
+        }
+    }
+
+    fn new_watcher() -> FileWatcher {
+        FileWatcher::new(mpsc::channel().0, &Polling).unwrap()
+    }
+
+    fn new_filter(use_gitignore: bool) -> WatchFilter {
+        WatchFilter::new(
+            use_gitignore,
+            vec!["rs".to_string(), "toml".to_string()],
+            vec!["js".to_string()],
+            vec!["html".to_string(), "css".to_string()],
+        )
+    }
+
+    fn setup() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("frontend").join("styles").join("node_modules")).unwrap();
+        fs::write(root.join(".gitignore"), "*.bak\nnode_modules/\n").unwrap();
+        fs::write(
+            root.join("frontend").join(".gitignore"),
+            "generated.rs\n!keep.bak\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("frontend").join("styles").join(".ignore"),
+            "*.css\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn nested_ignore_files() {
+        let dir = setup();
+        let root = dir.path();
+        let frontend = root.join("frontend");
+        let mut filter = new_filter(true);
+
+        assert!(filter.is_ignored(&root.join("main.bak")));
+        assert!(filter.is_ignored(&frontend.join("main.bak")));
+        assert!(!filter.is_ignored(&frontend.join("keep.bak")));
+        assert!(filter.is_ignored(&frontend.join("generated.rs")));
+        assert!(!filter.is_ignored(&root.join("generated.rs")));
+        assert!(filter.is_ignored(
+            &frontend
+                .join("styles")
+                .join("node_modules")
+                .join("index.js")
+        ));
+        assert!(filter.is_ignored(&frontend.join("styles").join("main.css")));
+        assert!(!filter.is_ignored(&frontend.join("main.css")));
+        assert!(!filter.is_ignored(&frontend.join("src").join("lib.rs")));
+    }
+
+    #[test]
+    fn reload_modified_ignore_file() {
+        let dir = setup();
+        let frontend = dir.path().join("frontend");
+        let mut filter = new_filter(true);
+
+        assert!(!filter.is_ignored(&frontend.join("lib.rs")));
+        fs::write(frontend.join(".gitignore"), "lib.rs\n").unwrap();
+        // NOTE: hidden files never trigger a rebuild but the ignore file is reloaded
+        assert!(filter.is_ignored(&frontend.join(".gitignore")));
+        assert!(filter.is_ignored(&frontend.join("lib.rs")));
+    }
+
+    #[test]
+    fn builtin_ignores() {
+        let dir = setup();
+        let root = dir.path();
+        let mut filter = new_filter(false);
+
+        assert!(filter.is_ignored(&root.join(".git").join("index")));
+        assert!(filter.is_ignored(&root.join("target").join("debug").join("app.wasm")));
+        assert!(!filter.is_ignored(&root.join("main.bak")));
+        assert!(!filter.is_ignored(&root.join("src").join("target")));
+        // NOTE: the hidden files are ignored even without the ignore files
+        assert!(filter.is_ignored(&root.join("src").join(".foo.swp")));
+    }
+
+    #[test]
+    fn builtin_ignores_inside_the_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("target").join("app");
+        fs::create_dir_all(root.join("src")).unwrap();
+        let mut filter = new_filter(false);
+        filter.roots(vec![root.clone()]);
+
+        assert!(!filter.is_ignored(&root.join("src").join("lib.rs")));
+        assert!(filter.is_ignored(&root.join("target").join("debug").join("app.wasm")));
+        assert!(filter.is_ignored(&root.join(".git").join("index")));
+    }
+
+    #[test]
+    fn canonicalize_missing_paths() {
+        let dir = setup();
+        let root = dir.path().canonicalize().unwrap();
+
+        assert_eq!(
+            canonicalize(&dir.path().join("build/app.js")).to_str(),
+            root.join("build").join("app.js").to_str()
+        );
+        assert!(same_path(
+            &dir.path().join("frontend/../build"),
+            &root.join("build")
+        ));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn strip_verbatim_prefix() {
+        assert_eq!(
+            strip_verbatim(PathBuf::from(r"\\?\C:\work\repo")).to_str(),
+            Some(r"C:\work\repo")
+        );
+        assert_eq!(
+            strip_verbatim(PathBuf::from(r"\\?\UNC\server\share")).to_str(),
+            Some(r"\\?\UNC\server\share")
+        );
+    }
+
+    #[test]
+    fn ignore_globs() {
+        let dir = setup();
+        let root = dir.path();
+        let mut filter = new_filter(false);
+        filter
+            .ignore_globs(root, &["*.gen.rs".to_string(), "fixtures/".to_string()])
+            .unwrap();
+
+        assert!(filter.is_ignored(&root.join("frontend").join("api.gen.rs")));
+        assert!(filter.is_ignored(&root.join("fixtures").join("data.json")));
+        assert!(!filter.is_ignored(&root.join("frontend").join("api.rs")));
+        assert!(!filter.is_ignored(&std::env::temp_dir().join("other.gen.rs")));
+        assert!(filter.ignore_globs(root, &["{a,b".to_string()]).is_err());
+    }
+
+    #[test]
+    fn special_characters_in_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("Client Work").join("über-app");
+        fs::create_dir_all(root.join("build")).unwrap();
+        fs::write(root.join(".gitignore"), "*.bak\n").unwrap();
+        let mut filter = new_filter(true);
+        filter.exclude(root.join("build"));
+        filter
+            .ignore_globs(&root, &["fixtures/".to_string()])
+            .unwrap();
+
+        assert!(filter.is_ignored(&root.join("build").join("app.js")));
+        assert!(filter.is_ignored(&root.join("fixtures").join("données.json")));
+        assert!(filter.is_ignored(&root.join("main.bak")));
+        assert_eq!(
+            filter.classify(&root.join("src").join("é t.rs")),
+            Some(Change::Source)
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let name = std::ffi::OsStr::from_bytes(b".swp\xff");
+            assert!(filter.is_ignored(&root.join("src").join(name)));
+        }
+    }
+
+    #[test]
+    fn paths_inside_the_watched_directories() {
+        let dir = setup();
+        let package = dir.path().join("frontend");
+        fs::write(package.join("Cargo.toml"), "").unwrap();
+        let mut watcher = new_watcher();
+        watcher
+            .watch(package.join("styles"), RecursiveMode::Recursive)
+            .unwrap();
+        watcher
+            .watch(package.join("Cargo.toml"), RecursiveMode::NonRecursive)
+            .unwrap();
+        watcher
+            .watch(dir.path(), RecursiveMode::NonRecursive)
+            .unwrap();
+
+        assert!(watcher.is_watched(&package.join("styles").join("node_modules").join("a.css")));
+        assert!(watcher.is_watched(&package.join("Cargo.toml")));
+        assert!(watcher.is_watched(&dir.path().join("index.html")));
+        assert!(!watcher.is_watched(&package.join("index.html")));
+        assert!(!watcher.is_watched(&package.join("Cargo.toml").join("x")));
+    }
+
+    #[test]
+    fn build_path_inside_package() {
+        use notify::DebouncedEvent::*;
+
+        let dir = setup();
+        let package = dir.path().join("frontend");
+        fs::create_dir_all(package.join("build")).unwrap();
+        let mut filter = new_filter(true);
+        // NOTE: the path registered does not need to be canonical
+        filter.exclude(package.join("styles").join("..").join("build"));
+
+        let (tx, rx) = mpsc::channel();
+        tx.send(Write(package.join("src").join("lib.rs"))).unwrap();
+        tx.send(Remove(package.join("build").join("index.html")))
+            .unwrap();
+        tx.send(Create(package.join("build").join("app.js")))
+            .unwrap();
+        tx.send(Write(package.join("build").join("app_bg.wasm")))
+            .unwrap();
+        drop(tx);
+
+        let mut builds = 0;
+        watch_loop(rx, &mut new_watcher(), filter, None, |_, _| {
+            builds += 1;
+            Ok(())
+        });
+
+        assert_eq!(builds, 1);
+    }
+
+    #[test]
+    fn coalesce_events() {
+        use notify::DebouncedEvent::*;
+
+        let dir = setup();
+        let package = dir.path().join("frontend");
+        let filter = new_filter(true);
+
+        let (tx, rx) = mpsc::channel();
+        for i in 0..100 {
+            tx.send(Write(package.join("src").join(format!("{}.rs", i))))
+                .unwrap();
+        }
+        tx.send(Write(package.join("index.html"))).unwrap();
+        tx.send(Write(package.join("Cargo.toml"))).unwrap();
+        tx.send(Write(package.join("src").join("lib.rs"))).unwrap();
+
+        let mut changes = Vec::new();
+        let handle = std::thread::spawn(move || {
+            watch_loop(rx, &mut new_watcher(), filter, None, |change, _| {
+                changes.push(change);
+                Ok(())
+            });
+            changes
+        });
+        std::thread::sleep(COALESCE_WINDOW * 3);
+        tx.send(Write(package.join("index.html"))).unwrap();
+        drop(tx);
+
+        assert_eq!(
+            handle.join().unwrap(),
+            vec![Change::Manifest, Change::Asset]
+        );
+    }
+
+    #[test]
+    fn rename_over_watched_file() {
+        use notify::DebouncedEvent::*;
+
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("Cargo.toml");
+        let tmp = dir.path().join("Cargo.toml~");
+        fs::write(&manifest, "").unwrap();
+        let mut watcher = new_watcher();
+        watcher
+            .watch(&manifest, RecursiveMode::NonRecursive)
+            .unwrap();
+        let filter = new_filter(false);
+
+        // NOTE: the editor writes a temporary file and renames it over the original
+        let (tx, rx) = mpsc::channel();
+        fs::write(&tmp, "[package]").unwrap();
+        fs::rename(&tmp, &manifest).unwrap();
+        tx.send(Remove(manifest.clone())).unwrap();
+        tx.send(Rename(tmp.clone(), manifest.clone())).unwrap();
+        // NOTE: the file is removed and recreated later
+        fs::remove_file(&manifest).unwrap();
+        tx.send(Remove(manifest.clone())).unwrap();
+        drop(tx);
+
+        let mut builds = 0;
+        watch_loop(rx, &mut watcher, filter, None, |_, _| {
+            builds += 1;
+            Ok(())
+        });
+        assert_eq!(builds, 1);
+        assert!(watcher.missing.contains_key(&manifest));
+
+        let (tx, rx) = mpsc::channel();
+        fs::write(&manifest, "").unwrap();
+        tx.send(Create(manifest.clone())).unwrap();
+        drop(tx);
+
+        watch_loop(rx, &mut watcher, new_filter(false), None, |_, _| {
+            builds += 1;
+            Ok(())
+        });
+        assert_eq!(builds, 2);
+        assert!(watcher.missing.is_empty());
+        assert!(watcher.watched.contains_key(&manifest));
+    }
+
+    #[test]
+    fn unknown_and_panicking_events() {
+        use notify::DebouncedEvent::*;
+
+        let dir = tempfile::tempdir().unwrap();
+        let filter = new_filter(false);
+
+        let (tx, rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let mut builds = 0;
+            watch_loop(rx, &mut new_watcher(), filter, None, |_, _| {
+                builds += 1;
+                if builds == 1 {
+                    panic!("hook panicked");
+                }
+                Ok(())
+            });
+            builds
+        });
+        tx.send(Remove(dir.path().join("unknown.rs"))).unwrap();
+        std::thread::sleep(COALESCE_WINDOW * 3);
+        tx.send(Write(dir.path().join("lib.rs"))).unwrap();
+        drop(tx);
+
+        assert_eq!(handle.join().unwrap(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cancel_build_waiting_for_lock() {
+        use notify::DebouncedEvent::*;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut filter = new_filter(false);
+        filter.exclude(dir.path().join("target"));
+
+        let (tx, rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let mut changes = Vec::new();
+            let mut cancelled = 0;
+            watch_loop(rx, &mut new_watcher(), filter, None, |change, _| {
+                changes.push(change);
+                if changes.len() > 1 {
+                    return Ok(());
+                }
+                // NOTE: like cargo when another cargo command is building in the target directory
+                let res = crate::run_cargo(
+                    std::process::Command::new("sh").args([
+                        "-c",
+                        "echo '    Blocking waiting for file lock on build directory' >&2; \
+                        sleep 30",
+                    ]),
+                    &crate::ConsoleReporter,
+                );
+                cancelled += matches!(res, Err(crate::Error::BuildCancelled)) as usize;
+                Ok(res.map(drop)?)
+            });
+            (changes, cancelled)
+        });
+        tx.send(Write(dir.path().join("lib.rs"))).unwrap();
+        std::thread::sleep(COALESCE_WINDOW * 3);
+        // NOTE: the changes of the excluded directories and the ignored files don't cancel the
+        //       build
+        tx.send(Write(dir.path().join("target").join("app.wasm")))
+            .unwrap();
+        tx.send(Write(dir.path().join(".lib.rs.swp"))).unwrap();
+        std::thread::sleep(COALESCE_WINDOW * 3);
+        tx.send(Write(dir.path().join("index.html"))).unwrap();
+        drop(tx);
+
+        let (changes, cancelled) = handle.join().unwrap();
+        assert_eq!(cancelled, 1);
+        assert_eq!(changes, [Change::Source, Change::Source]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cancel_check_after_a_change() {
+        use notify::DebouncedEvent::*;
+
+        let dir = tempfile::tempdir().unwrap();
+        let filter = new_filter(false);
+
+        let (tx, rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let mut changes = Vec::new();
+            let mut cancelled = 0;
+            watch_loop(rx, &mut new_watcher(), filter, None, |change, _| {
+                changes.push(change);
+                if changes.len() > 1 {
+                    return Ok(());
+                }
+                // NOTE: unlike a build, a check doesn't need to wait for a lock to be cancelled
+                let res = crate::run_cargo_cancellable(
+                    std::process::Command::new("sh").args(["-c", "sleep 30"]),
+                    &crate::ConsoleReporter,
+                    true,
+                );
+                cancelled += matches!(res, Err(crate::Error::BuildCancelled)) as usize;
+                Ok(res.map(drop)?)
+            });
+            (changes, cancelled)
+        });
+        tx.send(Write(dir.path().join("lib.rs"))).unwrap();
+        std::thread::sleep(COALESCE_WINDOW * 3);
+        tx.send(Write(dir.path().join("main.rs"))).unwrap();
+        drop(tx);
+
+        let (changes, cancelled) = handle.join().unwrap();
+        assert_eq!(cancelled, 1);
+        assert_eq!(changes, [Change::Source, Change::Source]);
+    }
+
+    #[test]
+    fn rebuild_handle() {
+        let (tx, rx) = mpsc::channel();
+        let watcher = FileWatcher::new(tx, &Polling).unwrap();
+        let handle = watcher.rebuild_handle();
+        std::thread::spawn(move || handle.trigger()).join().unwrap();
+        drop(watcher);
+
+        let orchestrator = crate::pipeline::Orchestrator::default();
+        let mut changes = Vec::new();
+        watch_loop(
+            rx,
+            &mut new_watcher(),
+            new_filter(true),
+            Some(&orchestrator),
+            |change, _| {
+                changes.push((change, orchestrator.state()));
+                Ok(())
+            },
+        );
+
+        // NOTE: the trigger is handled by the run
+        assert_eq!(
+            changes,
+            vec![(Change::Source, crate::pipeline::QueueState::Idle)]
+        );
+    }
+
+    #[test]
+    fn rewatch_drops_removed_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b, c) = (
+            dir.path().join("a"),
+            dir.path().join("b"),
+            dir.path().join("c"),
+        );
+        for path in [&a, &b, &c] {
+            fs::create_dir(path).unwrap();
+        }
+        let (tx, _rx) = mpsc::channel();
+        let mut watcher = FileWatcher::new(tx, &Polling).unwrap();
+        watcher.watch(&a, RecursiveMode::Recursive).unwrap();
+        watcher.watch(&b, RecursiveMode::Recursive).unwrap();
+
+        watcher
+            .rewatch(|watcher| {
+                watcher.watch(&a, RecursiveMode::Recursive)?;
+                watcher.watch(&c, RecursiveMode::Recursive)?;
+                Ok(())
+            })
+            .unwrap();
+        let expected: HashSet<_> = [a, c].iter().cloned().collect();
+        assert_eq!(
+            watcher.watched.keys().cloned().collect::<HashSet<_>>(),
+            expected
+        );
+
+        watcher
+            .rewatch(|_| anyhow::bail!("half-saved manifest"))
+            .unwrap_err();
+        assert_eq!(
+            watcher.watched.keys().cloned().collect::<HashSet<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn ignores_of_the_hooks() {
+        let dir = setup();
+        let root = canonicalize(dir.path());
+        let generated = root.join("frontend").join("generated");
+        fs::create_dir_all(&generated).unwrap();
+        let mut watcher = new_watcher();
+        watcher.ignore_builtin(&root, vec![root.join("build")]);
+        let register = |watcher: &mut FileWatcher| -> anyhow::Result<()> {
+            watcher
+                .config_mut()
+                .ignore_glob("*.lock")?
+                .ignore_path(root.join("frontend").join("generated").join("cache"))
+                .watch_path_nonrecursive(root.join("frontend").join("generated"));
+            assert!(watcher.config_mut().ignore_glob("{a,b").is_err());
+            Ok(())
+        };
+        watcher.register(register).unwrap();
+        assert_eq!(
+            watcher.config().builtin_ignored_paths(),
+            [root.join("build")]
+        );
+        assert_eq!(watcher.config().builtin_ignored_dirs(), [".git", "target"]);
+        assert_eq!(watcher.config().ignored_globs(), ["*.lock"]);
+        assert_eq!(
+            watcher.watched.get(&generated),
+            Some(&RecursiveMode::NonRecursive)
+        );
+
+        let mut filter = new_filter(false);
+        filter.configure(watcher.config()).unwrap();
+        assert!(filter.is_ignored(&generated.join("schema.lock")));
+        assert!(filter.is_ignored(&generated.join("cache").join("api.rs")));
+        assert_eq!(
+            filter.classify(&generated.join("api.rs")),
+            Some(Change::Source)
+        );
+
+        watcher
+            .rewatch(|_| anyhow::bail!("half-saved manifest"))
+            .unwrap_err();
+        filter.configure(watcher.config()).unwrap();
+        assert!(filter.is_ignored(&generated.join("schema.lock")));
+
+        watcher.rewatch(|_| Ok(())).unwrap();
+        assert!(watcher.config().ignored_globs().is_empty());
+        assert_eq!(
+            watcher.config().builtin_ignored_paths(),
+            [root.join("build")]
+        );
+        filter.configure(watcher.config()).unwrap();
+        assert!(!filter.is_ignored(&generated.join("schema.lock")));
+        assert!(!filter.is_ignored(&generated.join("cache").join("api.rs")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn watch_through_symlinks() {
+        use notify::DebouncedEvent::*;
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = canonicalize(dir.path());
+        let (shared, package) = (root.join("shared").join("src"), root.join("app"));
+        fs::create_dir_all(&shared).unwrap();
+        fs::create_dir_all(&package).unwrap();
+        symlink(&shared, package.join("src")).unwrap();
+        symlink(&package, shared.join("app")).unwrap();
+        symlink(&package, root.join("link")).unwrap();
+
+        let mut watcher = new_watcher();
+        watcher
+            .watch(root.join("link"), RecursiveMode::Recursive)
+            .unwrap();
+        assert_eq!(watcher.aliases[&package], root.join("link"));
+        assert_eq!(watcher.aliases[&shared], root.join("link").join("src"));
+        assert_eq!(watcher.aliases.len(), 2);
+
+        // NOTE: the events of the real locations are mapped back to the registered paths
+        let (tx, rx) = mpsc::channel();
+        tx.send(Write(shared.join("lib.rs"))).unwrap();
+        drop(tx);
+        let filter = new_filter(false);
+        let mut changes = Vec::new();
+        watch_loop(rx, &mut watcher, filter, None, |change, _| {
+            changes.push(change);
+            Ok(())
+        });
+        assert_eq!(changes, [Change::Source]);
+        assert_eq!(
+            watcher.resolve(&shared.join("lib.rs")),
+            root.join("link").join("src").join("lib.rs")
+        );
+
+        watcher.unwatch(root.join("link")).unwrap();
+        assert!(watcher.aliases.is_empty());
+        assert!(watcher.links.is_empty());
+    }
+
+    #[test]
+    fn serve_path_only_reloads() {
+        let dir = setup();
+        let serve_path = dir.path().join("frontend").join("dist");
+        fs::create_dir_all(serve_path.join("wasm")).unwrap();
+        fs::write(dir.path().join("frontend").join(".gitignore"), "dist/\n").unwrap();
+        let mut filter = new_filter(true);
+        filter.exclude(serve_path.join("wasm"));
+        filter.serve(&serve_path);
+
+        assert_eq!(
+            filter.classify(&serve_path.join("docs").join("index.html")),
+            Some(Change::Served)
+        );
+        assert_eq!(
+            filter.classify(&serve_path.join("logo.png")),
+            Some(Change::Served)
+        );
+        assert_eq!(
+            filter.classify(&serve_path.join("wasm").join("app.js")),
+            None
+        );
+        assert_eq!(Change::Served.merge(Change::Asset), Change::Asset);
+        assert_eq!(Change::Served.merge(Change::Served), Change::Served);
+    }
+
+    #[test]
+    fn classify_by_extension() {
+        let dir = setup();
+        let package = dir.path().join("frontend");
+        let mut filter = new_filter(true);
+
+        assert_eq!(
+            filter.classify(&package.join("src").join("lib.rs")),
+            Some(Change::Source)
+        );
+        assert_eq!(
+            filter.classify(&package.join("Cargo.toml")),
+            Some(Change::Manifest)
+        );
+        assert_eq!(
+            filter.classify(&dir.path().join("Cargo.lock")),
+            Some(Change::Manifest)
+        );
+        assert_eq!(
+            filter.classify(&package.join("rustfmt.toml")),
+            Some(Change::Source)
+        );
+        assert_eq!(
+            filter.classify(&package.join("LICENSE")),
+            Some(Change::Source)
+        );
+        assert_eq!(
+            filter.classify(&package.join("index.html")),
+            Some(Change::Asset)
+        );
+        assert_eq!(filter.classify(&package.join("README.md")), None);
+        assert_eq!(
+            filter.classify(&package.join("static").join("logo.png")),
+            None
+        );
+        assert_eq!(
+            filter.classify(&package.join("styles").join("main.css")),
+            None
+        );
+        assert_eq!(
+            filter.classify(&package.join("js").join("helpers.js")),
+            Some(Change::Snippet)
+        );
+    }
+
+    #[test]
+    fn classify_static_files() {
+        let dir = setup();
+        let package = dir.path().join("frontend");
+        let mut filter = new_filter(true);
+        filter.assets(package.join("static"));
+
+        for file in ["logo.png", "index.html", "main.js", "CNAME"] {
+            assert_eq!(
+                filter.classify(&package.join("static").join(file)),
+                Some(Change::Asset),
+                "{}",
+                file
+            );
+        }
+        assert_eq!(
+            filter.classify(&package.join("static").join(".DS_Store")),
+            None
+        );
+        assert_eq!(
+            filter.classify(&package.join("static").join("Cargo.toml")),
+            Some(Change::Manifest)
+        );
+        assert_eq!(
+            filter.classify(&package.join("src").join("main.js")),
+            Some(Change::Snippet)
+        );
+        assert_eq!(Change::Snippet.merge(Change::Asset), Change::Snippet);
+        assert_eq!(Change::Source.merge(Change::Snippet), Change::Source);
+    }
+
+    #[test]
+    fn classify_bundle_sources() {
+        let dir = setup();
+        let package = dir.path().join("frontend");
+        let mut filter = new_filter(true);
+        filter.assets(package.join("static"));
+        filter.bundle(package.join("ts"));
+
+        for file in ["main.ts", "components/button.tsx", "styles.css"] {
+            assert_eq!(
+                filter.classify(&package.join("ts").join(file)),
+                Some(Change::Bundle),
+                "{}",
+                file
+            );
+        }
+        assert_eq!(
+            filter.classify(&package.join("static").join("index.html")),
+            Some(Change::Asset)
+        );
+        assert_eq!(
+            filter.classify(&package.join("src").join("main.js")),
+            Some(Change::Snippet)
+        );
+        assert_eq!(Change::Bundle.merge(Change::Asset), Change::Asset);
+        assert_eq!(Change::Served.merge(Change::Bundle), Change::Bundle);
+    }
+
+    fn write_package(dir: &Path, name: &str, dependencies: &str, workspace: &str) {
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src").join("lib.rs"), "").unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}\n{}",
+                name, dependencies, workspace,
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn path_dependencies_outside_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_package(
+            &root.join("app").join("frontend"),
+            "frontend",
+            "ui-kit = { path = \"../../ui-kit\" }",
+            "",
+        );
+        fs::write(
+            root.join("app").join("Cargo.toml"),
+            "[workspace]\nmembers = [\"frontend\"]\n",
+        )
+        .unwrap();
+        write_package(
+            &root.join("ui-kit"),
+            "ui-kit",
+            "icons = { path = \"../icons\" }",
+            "",
+        );
+        write_package(&root.join("icons"), "icons", "", "");
+
+        let metadata = cargo_metadata::MetadataCommand::new()
+            .manifest_path(root.join("app").join("Cargo.toml"))
+            .exec()
+            .unwrap();
+        let frontend = metadata
+            .packages
+            .iter()
+            .find(|x| x.name == "frontend")
+            .unwrap();
+
+        let mut names: Vec<_> = local_dependencies(&metadata, frontend)
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect();
+        names.sort_unstable();
+        assert_eq!(names, vec!["icons", "ui-kit"]);
+
+        let paths = package_watch_paths(&metadata, frontend);
+        let frontend_dir = frontend.manifest_path.parent().unwrap();
+        let ui_kit_dir = frontend_dir
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("ui-kit");
+        assert!(paths.contains(&(frontend_dir.to_path_buf(), RecursiveMode::Recursive)));
+        assert!(paths.contains(&(ui_kit_dir.join("src"), RecursiveMode::Recursive)));
+        assert!(paths.contains(&(ui_kit_dir.join("Cargo.toml"), RecursiveMode::NonRecursive)));
+        assert!(!paths.contains(&(ui_kit_dir, RecursiveMode::Recursive)));
+    }
+}
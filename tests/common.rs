@@ -1,14 +1,15 @@
 use cargo_metadata::{Metadata, MetadataCommand};
 use once_cell::sync::Lazy;
 use std::collections::HashSet;
+use std::env;
 use std::io;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Output, Stdio};
 
 static METADATA: Lazy<Metadata> = Lazy::new(|| MetadataCommand::new().exec().unwrap());
 
 pub fn clean(path: &Path) {
-    let metadata = MetadataCommand::new().current_dir(&path).exec().unwrap();
+    let metadata = MetadataCommand::new().current_dir(path).exec().unwrap();
     let members: HashSet<_> = metadata.workspace_members.iter().collect();
     let members: Vec<_> = metadata
         .packages
@@ -20,7 +21,7 @@ pub fn clean(path: &Path) {
     }
     for package in members {
         let output = cargo_command(path)
-            .args(&["clean", "-p"])
+            .args(["clean", "-p"])
             .arg(&package.name)
             .output()
             .unwrap();
@@ -48,7 +49,7 @@ pub fn build_crate(path: &Path) -> Option<PathBuf> {
 
     clean(path);
     let mut child = cargo_command(path)
-        .args(&["build", "--message-format=json-render-diagnostics"])
+        .args(["build", "--message-format=json-render-diagnostics"])
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
@@ -59,17 +60,14 @@ pub fn build_crate(path: &Path) -> Option<PathBuf> {
     for message in cargo_metadata::Message::parse_stream(reader) {
         use cargo_metadata::Message;
 
-        match message.unwrap() {
-            Message::BuildScriptExecuted(script) => {
-                out_dir = Some(script.out_dir);
-            }
-            _ => (),
+        if let Message::BuildScriptExecuted(script) = message.unwrap() {
+            out_dir = Some(script.out_dir);
         }
     }
 
     let status = child.wait().expect("Couldn't get cargo's exit status");
     let mut stderr = String::new();
-    &child.stderr.unwrap().read_to_string(&mut stderr).unwrap();
+    child.stderr.unwrap().read_to_string(&mut stderr).unwrap();
     eprintln!("stderr:\n{}\n", stderr);
 
     assert!(status.success(), "build failed: {}", path.display(),);
@@ -94,6 +92,33 @@ pub fn run_cargo(path: &Path, args: &[&str]) {
     assert!(output.status.success());
 }
 
+/// Run a crate like [`run_crate`] and return its output, whether it succeeded or not.
+pub fn run_crate_output(path: &Path, run_args: &[&str]) -> Output {
+    clean(path);
+    let output = cargo_command(path)
+        .args(["run", "--"])
+        .args(run_args)
+        .output()
+        .unwrap();
+
+    println!("stdout:\n{}\n", String::from_utf8_lossy(&output.stdout));
+    eprintln!("stderr:\n{}\n", String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+/// Start a binary built by [`run_cargo`] in the directory of its crate.
+pub fn spawn_binary(path: &Path, name: &str, args: &[&str]) -> Child {
+    Command::new(METADATA.target_directory.join("debug").join(format!(
+        "{}{}",
+        name,
+        env::consts::EXE_SUFFIX
+    )))
+    .current_dir(path)
+    .args(args)
+    .spawn()
+    .unwrap()
+}
+
 fn cargo_command(path: &Path) -> Command {
     let mut command = Command::new("cargo");
 
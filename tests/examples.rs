@@ -8,7 +8,7 @@ use common::*;
 fn examples() {
     let examples = Path::new("examples");
     run_cargo(
-        &examples,
+        examples,
         &["run", "--example", "run-an-example", "--", "build"],
     );
     run_cargo(
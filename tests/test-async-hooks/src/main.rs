@@ -0,0 +1,59 @@
+use std::fs;
+use std::process::Command;
+use structopt::StructOpt;
+use wasmbl::prelude::*;
+
+#[wasmbl::main(
+    pre_build = pre_build,
+    post_build = post_build,
+    other_cli_commands = run_other_cli_commands
+)]
+#[derive(StructOpt, Debug)]
+enum Cli {
+    Hello,
+}
+
+async fn pre_build(
+    args: &DefaultBuildArgs,
+    _profile: BuildProfile,
+    _command: &mut Command,
+    _metadata: &Metadata,
+    package: &Package,
+    trigger: BuildTrigger,
+) -> anyhow::Result<()> {
+    let build_path = args.build_path();
+    fs::create_dir_all(&build_path)?;
+    fs::write(
+        build_path.join("pre-build.txt"),
+        format!("async {} {:?}", package.name, trigger),
+    )?;
+
+    Ok(())
+}
+
+async fn post_build(args: &DefaultBuildArgs, output: &BuildOutput) -> anyhow::Result<()> {
+    fs::write(&output.wasm_path, &output.wasm)?;
+    fs::write(
+        args.build_path().join("post-build.txt"),
+        format!(
+            "async {:?} {:?} {}",
+            output.profile,
+            output.trigger,
+            output.js_path.display()
+        ),
+    )?;
+
+    Ok(())
+}
+
+fn run_other_cli_commands(
+    cli: Cli,
+    _metadata: &Metadata,
+    _package: &Package,
+) -> anyhow::Result<()> {
+    match cli {
+        Cli::Hello => println!("Hello World!"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use structopt::StructOpt;
+use wasmbl::prelude::*;
+
+#[wasmbl::main(other_cli_commands = run_other_cli_commands)]
+#[derive(StructOpt, Debug)]
+enum Cli {
+    Build(DefaultBuildArgs),
+    Hello,
+}
+
+fn run_other_cli_commands(
+    cli: Cli,
+    _metadata: &Metadata,
+    _package: &Package,
+) -> anyhow::Result<()> {
+    match cli {
+        Cli::Hello => println!("Hello World!"),
+        Cli::Build(_) => unreachable!(),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_into_temp_dir() {
+        let build_path = std::env::temp_dir().join("wasmbl-test-build-only");
+        let _ = std::fs::remove_dir_all(&build_path);
+
+        let path = Cli::wasmbl_build(DefaultBuildArgs {
+            build_path: Some(build_path.clone()),
+            ..DefaultBuildArgs::default()
+        })
+        .unwrap();
+
+        assert_eq!(path, build_path);
+        assert!(build_path.join("index.html").exists());
+        assert!(build_path.join("app_bg.wasm").exists());
+    }
+}
@@ -0,0 +1,34 @@
+use structopt::StructOpt;
+use wasmbl::prelude::*;
+
+#[wasmbl::main(other_cli_commands = run_other_cli_commands)]
+#[derive(StructOpt, Debug)]
+enum Cli {
+    /// Print a greeting.
+    Hello,
+    /// Run the migrations.
+    #[cfg(feature = "experimental")]
+    Migrate,
+    /// Replace the `clean` command.
+    #[cfg(feature = "experimental")]
+    Clean {
+        #[structopt(long)]
+        dry_run: bool,
+    },
+}
+
+fn run_other_cli_commands(
+    cli: Cli,
+    _metadata: &Metadata,
+    _package: &Package,
+) -> anyhow::Result<()> {
+    match cli {
+        Cli::Hello => println!("Hello World!"),
+        #[cfg(feature = "experimental")]
+        Cli::Migrate => println!("Migrating"),
+        #[cfg(feature = "experimental")]
+        Cli::Clean { dry_run } => println!("Custom clean (dry run: {})", dry_run),
+    }
+
+    Ok(())
+}
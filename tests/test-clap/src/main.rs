@@ -0,0 +1,20 @@
+use clap::Subcommand;
+use wasmbl::prelude::*;
+
+#[wasmbl::main(parser = "clap", other_cli_commands = run_other_cli_commands)]
+#[derive(Subcommand, Debug)]
+enum Cli {
+    Hello,
+}
+
+fn run_other_cli_commands(
+    cli: Cli,
+    _metadata: &Metadata,
+    _package: &Package,
+) -> anyhow::Result<()> {
+    match cli {
+        Cli::Hello => println!("Hello World!"),
+    }
+
+    Ok(())
+}
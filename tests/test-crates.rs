@@ -1,7 +1,12 @@
 pub mod common;
 
 use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpStream;
 use std::path::Path;
+use std::process::Child;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use common::*;
 
@@ -49,6 +54,137 @@ fn test_crates() {
         let _ = fs::remove_dir_all(&build_path);
         run_crate(&crate_path, &["build"]);
         assert!(build_path.exists(), "test for `no-serve` failed");
+
+        run_crate(&crate_path, &["build", "--out-layout", "pkg"]);
+        for file in ["app.js", "app_bg.wasm", "app.d.ts", "package.json"] {
+            assert!(
+                build_path.join("pkg").join(file).exists(),
+                "test for `out-layout pkg` failed: missing `{}`",
+                file
+            );
+        }
+
+        if std::process::Command::new("node")
+            .arg("--version")
+            .output()
+            .is_ok()
+        {
+            run_crate(&crate_path, &["run-node"]);
+            assert!(
+                build_path.join("node").join("app.js").exists(),
+                "test for `run-node` failed"
+            );
+        }
+    }
+
+    {
+        let crate_path = tests.join("test-renamed-lib");
+        let build_path = crate_path.join("build");
+        let _ = fs::remove_dir_all(&build_path);
+        run_crate(&crate_path, &["build"]);
+        assert!(
+            build_path.join("app_bg.wasm").exists(),
+            "test for `renamed-lib` failed"
+        );
+    }
+
+    {
+        let crate_path = tests.join("test-clap");
+        let build_path = crate_path.join("build");
+        let _ = fs::remove_dir_all(&build_path);
+        run_crate(&crate_path, &["hello"]);
+        run_crate(&crate_path, &["build"]);
+        assert!(build_path.exists(), "test for `clap` failed");
+    }
+
+    {
+        let crate_path = tests.join("test-async-hooks");
+        let build_path = crate_path.join("build");
+        let _ = fs::remove_dir_all(&build_path);
+        run_crate(&crate_path, &["hello"]);
+        run_crate(&crate_path, &["build"]);
+        assert_eq!(
+            fs::read_to_string(build_path.join("pre-build.txt")).ok(),
+            Some("async test-async-hooks Build".to_string()),
+            "test for `async-hooks` failed"
+        );
+        assert_eq!(
+            fs::read_to_string(build_path.join("post-build.txt")).ok(),
+            Some(format!(
+                "async Release Build {}",
+                build_path.join("app.js").display()
+            )),
+            "test for `async-hooks` failed"
+        );
+        assert!(build_path.join("app_bg.wasm").exists());
+    }
+
+    {
+        let crate_path = tests.join("test-hook-paths");
+        let build_path = crate_path.join("build");
+        let _ = fs::remove_dir_all(&build_path);
+        run_crate(&crate_path, &["hello"]);
+        run_crate(&crate_path, &["build"]);
+        assert!(
+            build_path.join("pre-build.txt").exists(),
+            "test for `hook-paths` failed"
+        );
+        assert!(
+            build_path.join("post-build.txt").exists(),
+            "test for `hook-paths` failed"
+        );
+        assert!(
+            build_path.join("bindgen.txt").exists(),
+            "test for `hook-paths` failed"
+        );
+    }
+
+    {
+        let crate_path = tests.join("test-custom-args");
+        let build_path = crate_path.join("build");
+        let _ = fs::remove_dir_all(&build_path);
+        run_crate(&crate_path, &["build"]);
+        assert_eq!(
+            fs::read_to_string(build_path.join("pre-build.txt")).unwrap(),
+            "Release",
+            "test for `custom-args` failed"
+        );
+        assert!(build_path.join("app_bg.wasm").exists());
+    }
+
+    {
+        let crate_path = tests.join("test-build-only");
+        let build_path = crate_path.join("build");
+        let _ = fs::remove_dir_all(&build_path);
+        run_crate(&crate_path, &["hello"]);
+        run_crate(&crate_path, &["build"]);
+        assert!(build_path.exists(), "test for `build-only` failed");
+        test_crate(&crate_path);
+    }
+
+    {
+        let crate_path = tests.join("test-global-options");
+        run_crate(&crate_path, &["hello", "--greet"]);
+        run_crate(&crate_path, &["-v", "hello"]);
+        let build_path = crate_path.join("package");
+        let _ = fs::remove_dir_all(&build_path);
+        run_crate(&crate_path, &["package"]);
+        assert!(
+            build_path.join("app_bg.wasm").exists(),
+            "test for `global-options` failed"
+        );
+    }
+
+    {
+        let crate_path = tests.join("test-cfg-variants");
+        run_crate(&crate_path, &["hello"]);
+        run_crate(&crate_path, &["clean", "--dry-run"]);
+        let with_feature = ["run", "--features", "experimental", "--"];
+        run_cargo(&crate_path, &[&with_feature[..], &["migrate"]].concat());
+        run_cargo(
+            &crate_path,
+            &[&with_feature[..], &["clean", "--dry-run"]].concat(),
+        );
     }
 
     {
@@ -79,3 +215,177 @@ fn test_crates() {
         test_crate(&crate_path);
     }
 }
+
+#[test]
+fn serve() {
+    struct Server(Child);
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn get(path: &str) -> Option<String> {
+        let mut stream = TcpStream::connect(("127.0.0.1", 3457)).ok()?;
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        )
+        .ok()?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).ok()?;
+        Some(String::from_utf8_lossy(&response).into_owned())
+    }
+
+    let crate_path = Path::new("tests").join("test-serve");
+    let build_path = crate_path.join("build");
+    let _ = fs::remove_dir_all(&build_path);
+    run_cargo(&crate_path, &["build"]);
+    let _server = Server(spawn_binary(
+        &crate_path,
+        "test-serve",
+        &["serve", "--port", "3457"],
+    ));
+
+    // NOTE: the server starts listening after the first build of the WASM
+    let start = Instant::now();
+    let index = loop {
+        if let Some(response) = get("/") {
+            break response;
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(300),
+            "test for `serve` failed: the server did not start"
+        );
+        thread::sleep(Duration::from_millis(500));
+    };
+    assert!(index.starts_with("HTTP/1.1 200"), "{}", index);
+    assert!(index.contains("/img/logo.svg"), "{}", index);
+
+    for path in ["/app.js", "/app_bg.wasm", "/img/logo.svg"] {
+        let response = get(path).unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 200"),
+            "test for `serve` failed: {}\n{}",
+            path,
+            response
+        );
+    }
+    assert!(get("/img/logo.svg").unwrap().contains("<svg"));
+}
+
+#[test]
+fn special_characters_in_path() {
+    let source = Path::new("tests").join("test-no-serve");
+    let dir = tempfile::tempdir().unwrap();
+    let crate_path = dir.path().join("Client Work").join("über-app");
+    fs::create_dir_all(crate_path.join("src")).unwrap();
+    for file in ["src/lib.rs", "src/main.rs"] {
+        fs::copy(source.join(file), crate_path.join(file)).unwrap();
+    }
+    // NOTE: a literal string so the backslashes of the path are kept on Windows
+    let manifest = fs::read_to_string(source.join("Cargo.toml"))
+        .unwrap()
+        .replace(
+            r#"path = "../..""#,
+            &format!("path = '{}'", env!("CARGO_MANIFEST_DIR")),
+        );
+    fs::write(crate_path.join("Cargo.toml"), manifest).unwrap();
+
+    run_crate(&crate_path, &["build"]);
+    let build_path = crate_path.join("build");
+    assert!(
+        build_path.join("app_bg.wasm").exists(),
+        "test for `special_characters_in_path` failed"
+    );
+    assert!(build_path.join("app.js").exists());
+}
+
+#[test]
+fn build_without_wasm_opt() {
+    let source = Path::new("tests").join("test-no-serve");
+    let dir = tempfile::tempdir().unwrap();
+    let crate_path = dir.path().join("no-wasm-opt");
+    fs::create_dir_all(crate_path.join("src")).unwrap();
+    for file in ["src/lib.rs", "src/main.rs"] {
+        fs::copy(source.join(file), crate_path.join(file)).unwrap();
+    }
+    // NOTE: without `prebuilt-wasm-opt` nor `binaryen`, `wasm-opt` is not available
+    let manifest = fs::read_to_string(source.join("Cargo.toml"))
+        .unwrap()
+        .replace(
+            r#"path = "../..", default-features = false, features = ["prebuilt-wasm-opt"]"#,
+            &format!(
+                "path = '{}', default-features = false",
+                env!("CARGO_MANIFEST_DIR")
+            ),
+        );
+    fs::write(crate_path.join("Cargo.toml"), manifest).unwrap();
+
+    let output = run_crate_output(&crate_path, &["build"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--allow-unoptimized"));
+
+    run_crate(&crate_path, &["build", "--allow-unoptimized"]);
+    assert!(
+        crate_path.join("build").join("app_bg.wasm").exists(),
+        "test for `build_without_wasm_opt` failed"
+    );
+}
+
+#[test]
+fn reproducible_build() {
+    use sha2::{Digest, Sha256};
+    use std::collections::BTreeMap;
+
+    fn checksums(dir: &Path, prefix: &Path, sums: &mut BTreeMap<String, String>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                checksums(&path, prefix, sums);
+            } else {
+                let content = fs::read(&path).unwrap();
+                sums.insert(
+                    path.strip_prefix(prefix).unwrap().display().to_string(),
+                    format!("{:x}", Sha256::digest(&content)),
+                );
+            }
+        }
+    }
+
+    let crate_path = Path::new("tests").join("test-no-serve");
+    let build_path = crate_path.join("build");
+    let archive_path = crate_path.join("reproducible.tar.gz");
+    let build = || {
+        let _ = fs::remove_dir_all(&build_path);
+        run_crate(
+            &crate_path,
+            &[
+                "build",
+                "--reproducible",
+                "--archive",
+                "reproducible.tar.gz",
+                "--force",
+            ],
+        );
+        let mut sums = BTreeMap::new();
+        checksums(&build_path, &build_path, &mut sums);
+        sums.insert(
+            "reproducible.tar.gz".to_string(),
+            format!("{:x}", Sha256::digest(&fs::read(&archive_path).unwrap())),
+        );
+        sums
+    };
+
+    // NOTE: the time of the build is recorded in the build directory, in seconds
+    let first = build();
+    thread::sleep(Duration::from_secs(2));
+    let second = build();
+    let _ = fs::remove_file(&archive_path);
+    assert!(first.contains_key("app_bg.wasm"), "{:?}", first);
+    assert!(first.contains_key(".wasmbl-meta.json"), "{:?}", first);
+    assert_eq!(first, second, "test for `reproducible` failed");
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use wasmbl::prelude::*;
+
+#[wasmbl::main(
+    pre_build = pre_build,
+    post_build = post_build,
+    frontend_watch = frontend_watch,
+)]
+#[derive(StructOpt, Debug)]
+enum Cli {
+    Build(MyBuildArgs),
+    Serve(MyServeArgs),
+}
+
+/// All the options of the build.
+#[derive(StructOpt, Debug)]
+struct MyBuildArgs {
+    #[structopt(flatten)]
+    build_args: DefaultBuildArgs,
+}
+
+impl BuildArgs for MyBuildArgs {
+    fn build_path(&self) -> PathBuf {
+        self.build_args.build_path()
+    }
+
+    fn profiling(&self) -> bool {
+        self.build_args.profiling()
+    }
+
+    fn allow_unoptimized(&self) -> bool {
+        self.build_args.allow_unoptimized()
+    }
+}
+
+/// A reduced set of options for the builds of `serve`, which are always dev builds.
+#[derive(StructOpt, Debug)]
+struct DevBuildArgs {
+    #[structopt(long)]
+    build_path: Option<PathBuf>,
+}
+
+impl BuildArgs for DevBuildArgs {
+    fn build_path(&self) -> PathBuf {
+        self.build_path
+            .clone()
+            .unwrap_or_else(|| self.default_build_path().to_owned())
+    }
+
+    fn profiling(&self) -> bool {
+        false
+    }
+}
+
+#[derive(StructOpt, Debug)]
+struct MyServeArgs {
+    #[structopt(long, default_value = "3000")]
+    port: u16,
+    #[structopt(flatten)]
+    build_args: DevBuildArgs,
+}
+
+impl WatchArgs for MyServeArgs {}
+
+impl ServeArgs for MyServeArgs {
+    fn log(&self) -> bool {
+        false
+    }
+
+    fn ip(&self) -> IpAddr {
+        [127, 0, 0, 1].into()
+    }
+
+    fn port(&self) -> u16 {
+        self.port
+    }
+
+    fn build_args(&self) -> &dyn BuildArgs {
+        &self.build_args
+    }
+}
+
+/// Shared by `build` and `serve`: it receives the arguments of both.
+fn pre_build(
+    args: &dyn BuildArgs,
+    profile: BuildProfile,
+    _command: &mut std::process::Command,
+    _metadata: &Metadata,
+    _package: &Package,
+) -> anyhow::Result<()> {
+    let build_path = args.build_path();
+    fs::create_dir_all(&build_path)?;
+    fs::write(build_path.join("pre-build.txt"), format!("{:?}", profile))?;
+
+    Ok(())
+}
+
+fn post_build(args: &dyn BuildArgs, output: &BuildOutput) -> anyhow::Result<()> {
+    fs::write(args.build_path().join("app.js"), &output.js)?;
+    fs::write(args.build_path().join("app_bg.wasm"), &output.wasm)?;
+
+    Ok(())
+}
+
+/// Only for `serve`: it can take the concrete type of its arguments.
+fn frontend_watch(args: &MyServeArgs, watcher: &mut FileWatcher) -> anyhow::Result<()> {
+    use notify::Watcher;
+
+    let package = args.build_args().frontend_package();
+    let src = package.manifest_path.parent().unwrap().join("src");
+    watcher.watch(src, notify::RecursiveMode::Recursive)?;
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::Path;
+use structopt::StructOpt;
+use wasmbl::prelude::*;
+
+#[wasmbl::main(other_cli_commands = run_other_cli_commands)]
+#[derive(StructOpt, Debug)]
+struct Cli {
+    /// Print a greeting before running the command.
+    #[structopt(long, global = true)]
+    greet: bool,
+
+    #[structopt(flatten)]
+    verbosity: Verbosity,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    Build(DefaultBuildArgs),
+    Serve(DefaultServeArgs),
+    Hello,
+    /// Build in a custom build directory and list the files.
+    Package,
+}
+
+fn run_other_cli_commands(
+    cli: Cli,
+    _metadata: &Metadata,
+    _package: &Package,
+) -> anyhow::Result<()> {
+    if cli.greet {
+        println!("Greetings!");
+    }
+
+    match cli.command {
+        Some(Command::Hello) => println!("Hello World!"),
+        Some(Command::Package) => {
+            let build_path = Cli::wasmbl_build(DefaultBuildArgs {
+                build_path: Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("package")),
+                allow_unoptimized: true,
+                ..DefaultBuildArgs::default()
+            })?;
+            for entry in fs::read_dir(build_path)? {
+                println!("{}", entry?.file_name().to_string_lossy());
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
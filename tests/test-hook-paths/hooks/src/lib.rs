@@ -0,0 +1,41 @@
+//! Reusable hooks imported by the test crate.
+
+pub mod frontend {
+    use std::fs;
+    use std::process::Command;
+    use wasmbl::prelude::*;
+
+    pub fn pre_build(
+        args: &DefaultBuildArgs,
+        _profile: BuildProfile,
+        _command: &mut Command,
+    ) -> anyhow::Result<()> {
+        let build_path = args.build_path();
+        fs::create_dir_all(&build_path)?;
+        fs::write(build_path.join("pre-build.txt"), "external")?;
+
+        Ok(())
+    }
+
+    pub fn bindgen(args: &DefaultBuildArgs, bindgen: &mut Bindgen) -> anyhow::Result<()> {
+        bindgen.remove_producers_section(true);
+        fs::write(args.build_path().join("bindgen.txt"), "external")?;
+
+        Ok(())
+    }
+
+    /// A `post_build` hook with the deprecated signature.
+    pub fn post_build(
+        args: &DefaultBuildArgs,
+        _profile: BuildProfile,
+        _wasm_js: String,
+        wasm_bin: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        fs::write(
+            args.build_path().join("post-build.txt"),
+            format!("external {}", wasm_bin.len()),
+        )?;
+
+        Ok(())
+    }
+}
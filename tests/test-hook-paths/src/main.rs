@@ -0,0 +1,25 @@
+use structopt::StructOpt;
+
+#[wasmbl::main(
+    pre_build = test_hook_paths_hooks::frontend::pre_build,
+    post_build = test_hook_paths_hooks::frontend::post_build,
+    bindgen = test_hook_paths_hooks::frontend::bindgen,
+    other_cli_commands = commands::run,
+)]
+#[derive(StructOpt, Debug)]
+enum Cli {
+    Hello,
+}
+
+mod commands {
+    use super::Cli;
+    use wasmbl::prelude::*;
+
+    pub fn run(cli: Cli, _metadata: &Metadata, _package: &Package) -> anyhow::Result<()> {
+        match cli {
+            Cli::Hello => println!("Hello World!"),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,6 @@
+use structopt::StructOpt;
+use wasmbl::prelude::*;
+
+#[wasmbl::main()]
+#[derive(StructOpt, Debug)]
+enum Cli {}
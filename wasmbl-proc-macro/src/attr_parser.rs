@@ -1,25 +1,74 @@
+use std::collections::HashSet;
 use syn::parse::{Error, ParseStream, Result};
 use syn::{Ident, LitStr, Path, Token};
 
+/// Names of the arguments accepted by the macro.
+const ARGUMENTS: &[&str] = &[
+    "other_cli_commands",
+    "pre_build",
+    "post_build",
+    "post_release",
+    "bindgen",
+    "asset_filter",
+    "serve",
+    "serve_middleware",
+    "pre_serve",
+    "on_shutdown",
+    "frontend_watch",
+    "backend_watch",
+    "default_build_path",
+    "default_serve_path",
+    "build_args",
+    "serve_args",
+    "pre_test",
+    "test",
+    "test_args",
+    "deploy",
+    "parser",
+    "package",
+    "manifest_path",
+];
+
+/// The crate used to parse the command-line arguments.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Parser {
+    StructOpt,
+    #[cfg(feature = "clap")]
+    Clap,
+}
+
 pub struct Attr {
     pub other_cli_commands: Option<Path>,
     pub pre_build: Option<Path>,
     pub post_build: Option<Path>,
-    #[cfg(feature = "dev-server")]
+    pub post_release: Option<Path>,
+    pub bindgen: Option<Path>,
+    pub asset_filter: Option<Path>,
+    #[cfg(feature = "serve")]
     pub serve: Option<Path>,
+    #[cfg(feature = "serve")]
+    pub serve_middleware: Option<Path>,
+    pub pre_serve: Option<Path>,
+    pub on_shutdown: Option<Path>,
     pub frontend_watch: Option<Path>,
     pub frontend_pkg_name: Option<LitStr>,
-    #[cfg(not(feature = "dev-server"))]
+    pub frontend_manifest_path: Option<LitStr>,
     pub backend_watch: Option<Path>,
     pub backend_pkg_name: Option<LitStr>,
     pub default_build_path: Option<Path>,
+    pub default_serve_path: Option<Path>,
     pub build_args: Option<Path>,
     pub serve_args: Option<Path>,
+    pub pre_test: Option<Path>,
+    pub test_args: Option<Path>,
+    #[cfg(feature = "deploy")]
+    pub deploy: Option<Path>,
+    pub parser: Parser,
 }
 
 impl Attr {
     pub fn parse(input: ParseStream) -> Result<Self> {
-        let frontend_pkg_name = input.parse().ok();
+        let mut frontend_pkg_name: Option<LitStr> = input.parse().ok();
 
         if frontend_pkg_name.is_some() && !input.is_empty() {
             input.parse::<Token![,]>()?;
@@ -34,17 +83,133 @@ impl Attr {
         let mut other_cli_commands = None;
         let mut pre_build = None;
         let mut post_build = None;
-        #[cfg(feature = "dev-server")]
+        let mut post_release = None;
+        let mut bindgen = None;
+        let mut asset_filter = None;
+        #[cfg(feature = "serve")]
         let mut serve = None;
+        #[cfg(feature = "serve")]
+        let mut serve_middleware = None;
+        let mut pre_serve = None;
+        let mut on_shutdown = None;
         let mut frontend_watch = None;
-        #[cfg(not(feature = "dev-server"))]
         let mut backend_watch = None;
         let mut default_build_path = None;
+        let mut default_serve_path = None;
         let mut build_args = None;
         let mut serve_args = None;
+        let mut pre_test = None;
+        let mut test_args = None;
+        #[cfg(feature = "deploy")]
+        let mut deploy = None;
+        let mut parser = Parser::StructOpt;
+        let mut frontend_manifest_path = None;
+        let mut seen = HashSet::new();
 
         while !input.is_empty() {
             let ident: Ident = input.parse()?;
+
+            if !ARGUMENTS.iter().any(|x| ident == x) {
+                let message = match closest_argument(&ident.to_string()) {
+                    Some(x) => format!(
+                        "unknown argument `{}`, did you mean `{}`? Expected one of: {}",
+                        ident,
+                        x,
+                        ARGUMENTS.join(", "),
+                    ),
+                    None => format!(
+                        "unknown argument `{}`, expected one of: {}",
+                        ident,
+                        ARGUMENTS.join(", "),
+                    ),
+                };
+                return Err(Error::new(ident.span(), message));
+            }
+
+            // NOTE: `test` is an alias of `pre_test`
+            let name = if ident == "test" {
+                "pre_test".to_string()
+            } else {
+                ident.to_string()
+            };
+            if !seen.insert(name) {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("duplicate argument `{}`", ident),
+                ));
+            }
+
+            if ident == "package" {
+                input.parse::<Token![=]>()?;
+                let value: LitStr = input.parse()?;
+                if frontend_pkg_name.is_some() {
+                    return Err(Error::new(
+                        value.span(),
+                        "the frontend package is already given in the first positional argument",
+                    ));
+                }
+                if frontend_manifest_path.is_some() {
+                    return Err(Error::new(
+                        value.span(),
+                        "`manifest_path` cannot be used with the name of the frontend package",
+                    ));
+                }
+                frontend_pkg_name = Some(value);
+
+                match input.parse::<Token![,]>() {
+                    Ok(_) => continue,
+                    Err(_) if input.is_empty() => break,
+                    Err(err) => return Err(err),
+                }
+            }
+
+            if ident == "manifest_path" {
+                input.parse::<Token![=]>()?;
+                let value: LitStr = input.parse()?;
+                if frontend_pkg_name.is_some() {
+                    return Err(Error::new(
+                        value.span(),
+                        "`manifest_path` cannot be used with the name of the frontend package",
+                    ));
+                }
+                frontend_manifest_path = Some(value);
+
+                match input.parse::<Token![,]>() {
+                    Ok(_) => continue,
+                    Err(_) if input.is_empty() => break,
+                    Err(err) => return Err(err),
+                }
+            }
+
+            if ident == "parser" {
+                input.parse::<Token![=]>()?;
+                let value: LitStr = input.parse()?;
+                parser = match value.value().as_str() {
+                    "structopt" => Parser::StructOpt,
+                    #[cfg(feature = "clap")]
+                    "clap" => Parser::Clap,
+                    #[cfg(not(feature = "clap"))]
+                    "clap" => {
+                        return Err(Error::new(
+                            value.span(),
+                            "the feature `clap` of wasmbl must be enabled",
+                        ))
+                    }
+                    _ => {
+                        return Err(Error::new(
+                            value.span(),
+                            "invalid parser, expected \"structopt\" or \"clap\"",
+                        ))
+                    }
+                };
+
+                match input.parse::<Token![,]>() {
+                    Ok(_) => continue,
+                    Err(_) if input.is_empty() => break,
+                    Err(err) => return Err(err),
+                }
+            }
+
             let path: Path = if input.parse::<Token![=]>().is_ok() {
                 input.parse()?
             } else {
@@ -55,15 +220,43 @@ impl Attr {
                 "other_cli_commands" => other_cli_commands = Some(path),
                 "pre_build" => pre_build = Some(path),
                 "post_build" => post_build = Some(path),
-                #[cfg(feature = "dev-server")]
+                "post_release" => post_release = Some(path),
+                "bindgen" => bindgen = Some(path),
+                "asset_filter" => asset_filter = Some(path),
+                #[cfg(feature = "serve")]
                 "serve" => serve = Some(path),
-                #[cfg(not(feature = "dev-server"))]
+                #[cfg(feature = "serve")]
+                "serve_middleware" => serve_middleware = Some(path),
+                "pre_serve" => pre_serve = Some(path),
+                "on_shutdown" => on_shutdown = Some(path),
                 "backend_watch" => backend_watch = Some(path),
                 "frontend_watch" => frontend_watch = Some(path),
                 "default_build_path" => default_build_path = Some(path),
+                "default_serve_path" => default_serve_path = Some(path),
                 "build_args" => build_args = Some(path),
                 "serve_args" => serve_args = Some(path),
-                _ => return Err(Error::new(ident.span(), "invalid argument")),
+                "pre_test" | "test" => pre_test = Some(path),
+                "test_args" => test_args = Some(path),
+                #[cfg(feature = "deploy")]
+                "deploy" => deploy = Some(path),
+                #[cfg(not(feature = "serve"))]
+                "serve" | "serve_middleware" => {
+                    return Err(Error::new(
+                        ident.span(),
+                        format!(
+                            "the `{}` hook requires the feature `dev-server` of wasmbl",
+                            ident
+                        ),
+                    ))
+                }
+                #[cfg(not(feature = "deploy"))]
+                "deploy" => {
+                    return Err(Error::new(
+                        ident.span(),
+                        "the `deploy` hook requires the feature `deploy` of wasmbl",
+                    ))
+                }
+                _ => unreachable!("the argument has been checked; qed"),
             }
 
             let _comma_token: Token![,] = match input.parse() {
@@ -77,16 +270,61 @@ impl Attr {
             other_cli_commands,
             pre_build,
             post_build,
-            #[cfg(feature = "dev-server")]
+            post_release,
+            bindgen,
+            asset_filter,
+            #[cfg(feature = "serve")]
             serve,
+            #[cfg(feature = "serve")]
+            serve_middleware,
+            pre_serve,
+            on_shutdown,
             frontend_watch,
             frontend_pkg_name,
-            #[cfg(not(feature = "dev-server"))]
+            frontend_manifest_path,
             backend_watch,
             backend_pkg_name,
             default_build_path,
+            default_serve_path,
             build_args,
             serve_args,
+            pre_test,
+            test_args,
+            #[cfg(feature = "deploy")]
+            deploy,
+            parser,
         })
     }
 }
+
+/// Find the argument closest to `name` by edit distance, if it is close enough to be a typo.
+fn closest_argument(name: &str) -> Option<&'static str> {
+    let max_distance = std::cmp::max(name.chars().count(), 3) / 3;
+
+    ARGUMENTS
+        .iter()
+        .map(|x| (edit_distance(name, x), *x))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, x)| x)
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, x) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, y) in b.iter().enumerate() {
+            current[j + 1] = if x == *y {
+                previous[j]
+            } else {
+                1 + previous[j].min(previous[j + 1]).min(current[j])
+            };
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
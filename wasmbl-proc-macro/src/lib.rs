@@ -3,9 +3,8 @@
 mod attr_parser;
 mod main_generator;
 
-use cargo_metadata::MetadataCommand;
 use proc_macro::TokenStream;
-use syn::{parse_macro_input, ItemEnum};
+use syn::{parse_macro_input, Item};
 
 /// Makes an entrypoint to your binary (not WASM).
 ///
@@ -23,55 +22,311 @@ use syn::{parse_macro_input, ItemEnum};
 /// It requires to be used with `structopt` on an `enum`. Please consult the documentation of
 /// `structopt` if you don't know how to make an `enum` with it.
 ///
+/// The commands `build`, `serve`, `test`, `run`, `run-node`, `clean`, `completions` and `version`
+/// are added to the variants of the `enum`. The `test` command runs the `wasm-bindgen-test` tests
+/// of the frontend package with `wasm-bindgen-test-runner`, optionally in a browser (`--chrome` or
+/// `--firefox`, with `--headless`). With `--watch`, it runs them again when the files change and
+/// prints a summary after each run; the driver of the browser is started once for all the runs.
+/// The `build` command accepts `--target wasm32-wasi` to build the binary of the frontend package
+/// for WASI: `wasm-bindgen` is skipped and the WASM is written to `app.wasm` in the build directory
+/// (the `serve` command refuses this target). The `run` command builds it and runs it with
+/// `wasmtime` or `wasmer` (`--runtime`), the arguments after `--` are given to the WASM and the
+/// process exits with its status. A `Run(DefaultRunArgs)` variant adds it to an `enum` that
+/// defines the `Build` command itself.
+/// The `run-node` command builds the JS bindings for Node.js in `node/` in the build directory
+/// (`--out-layout node`) and runs them with `node`: loading them runs the start function, then
+/// their export `main` is called. `--entry` runs another script instead, with the path of the JS
+/// bindings in the environment variable `WASMBL_JS_GLUE`. The arguments after `--` are given to
+/// the script and the process exits with the status of `node`. A `RunNode(DefaultRunNodeArgs)`
+/// variant adds it in the same way.
+/// The `clean` command removes the build directory, the downloaded `wasm-opt` and, with `--all`,
+/// the WASM artifacts of the target directory (`--dry-run` only prints what would be removed). A
+/// build directory outside of the workspace is only removed if it is given with `--build-path`. A
+/// `Clean(DefaultCleanArgs)` variant adds it to an `enum` that defines the `Build` command itself.
+/// The `completions` command prints the completion script of the whole command-line, including
+/// the commands of the `enum`, for `bash`, `zsh`, `fish` or `powershell` (`--out` writes it to a
+/// file). A `Completions(DefaultCompletionsArgs)` variant adds it in the same way.
+/// The `version` command prints the versions of `wasmbl`, `wasm-bindgen`, `wasm-opt` and `rustc`
+/// to help with the bug reports (a `Version(DefaultVersionArgs)` variant adds it in the same way).
+/// With the feature `scaffold` of `wasmbl`, the command `new` is also added: it creates a new
+/// frontend package in the workspace (a `New(DefaultNewArgs)` variant adds it to an `enum` that
+/// defines the `Build` command itself).
+/// With the feature `deploy` of `wasmbl`, the command `deploy` is added: it makes a release build
+/// and copies the build directory to a destination, or publishes it to GitHub Pages with
+/// `--gh-pages`, or builds a container image that serves it with nginx with `--docker <IMAGE>`
+/// (a `Deploy(DefaultDeployArgs)` variant adds it to an `enum` that defines the `Build` command
+/// itself).
+/// A variant of the `enum` that has the same name as one of these commands (`Test` for example)
+/// replaces it.
+///
+/// The global flags `-v` (debug logs, like the commands that are run), `-vv` (trace logs, like the
+/// file events) and `-q`/`--quiet` (only warnings and errors) are added to all the commands. The
+/// environment variable `RUST_LOG` takes precedence over them when it is set.
+///
+/// To add global options to all the commands, the macro can also be used on a struct that has a
+/// subcommand field. The enum of the subcommand must have the variants `Build` and `Serve` (with
+/// the same types as `build_args` and `serve_args`) and `other_cli_commands` receives the whole
+/// struct:
+///
+/// ```ignore
+/// #[wasmbl::main(other_cli_commands = run_other_cli_commands)]
+/// #[derive(StructOpt)]
+/// struct Cli {
+///     #[structopt(long, global = true)]
+///     verbose: bool,
+///
+///     #[structopt(subcommand)]
+///     command: Option<Command>,
+/// }
+///
+/// #[derive(StructOpt)]
+/// enum Command {
+///     Build(DefaultBuildArgs),
+///     Serve(DefaultServeArgs),
+///     Hello,
+/// }
+/// ```
+///
+/// If the subcommand is optional, the `serve` command runs when no command is provided. The
+/// verbosity flags are only available if the struct has a field of type `Verbosity` flattened in
+/// it. The completion script can be generated from `other_cli_commands` with
+/// `DefaultCompletionsArgs::run_structopt(Cli::clap())`.
+///
+/// The `enum` can also define the `Build` command itself. Without a `Serve` variant, no `serve`
+/// command is generated (the serve related hooks and `serve_args` cannot be used then). The same
+/// goes for the `Test` variant and the `test` command:
+///
+/// ```ignore
+/// #[wasmbl::main(other_cli_commands = run_other_cli_commands)]
+/// #[derive(StructOpt)]
+/// enum Cli {
+///     Build(DefaultBuildArgs),
+///     Hello,
+/// }
+/// ```
+///
+/// A `Serve` variant requires a `Build` variant.
+///
+/// The variants can be conditionally compiled with `cfg` attributes, except `Build`. When a variant
+/// that replaces a command is disabled, the command of `wasmbl` is used instead.
+///
+/// `clap` v4 can be used instead if the feature `clap` of `wasmbl` is enabled: add
+/// `parser = "clap"` to the arguments and derive `clap::Subcommand` on the `enum`:
+///
+/// ```ignore
+/// #[wasmbl::main(parser = "clap")]
+/// #[derive(clap::Subcommand)]
+/// enum Cli {}
+/// ```
+///
 /// There are a number of named arguments you can provide to the macro:
 ///  -  `other_cli_commands`: a function that is called if you have added new commands to the
 ///     `enum`;
 ///  -  `pre_build`: a function that is called when the build has not yet started (you can tweak
-///     the command-line arguments of the build command);
+///     the command-line arguments of the build command). It also receives the metadata of the
+///     workspace and the frontend package, then the `BuildTrigger` of the build (`Build`,
+///     `ServeInitial` or `WatchRebuild`) if the function takes it. The hooks that only take the
+///     arguments, the profile and the command are deprecated but still accepted. The `RUSTFLAGS`
+///     set on the command are appended to the flags of the environment, `wasmbl::append_rustflags`
+///     adds flags without parsing them again;
 ///  -  `post_build`: a function that is called when the build is finished (after the optimization
-///     with `wasm-opt`);
+///     with `wasm-opt`). It receives the arguments and the `BuildOutput` (the JS, the WASM, their
+///     paths, the snippets, what started the build in `trigger`, ...). The hooks that take the
+///     profile, the JS and the WASM are deprecated but still accepted, the macro emits a
+///     deprecation warning;
+///  -  `post_release`: a function that is called after a release build of the `build` command,
+///     once all the files are written, with the arguments and the `Release` (its identifier, the
+///     commit, the base URL and the files with their URL, size and SHA-256), to upload the
+///     sourcemaps to an error tracker for example. Its error is only a warning unless
+///     `--upload-errors-fatal`;
+///  -  `bindgen`: a function that is called before generating the JS bindings with the arguments
+///     and the `Bindgen` builder of `wasm-bindgen` (re-exported in `wasmbl::prelude`), after
+///     wasmbl has set its options. It can enable the options that wasmbl does not set, like
+///     `reference_types` or `remove_producers_section`. The JS bindings are always generated by the
+///     library `wasm-bindgen-cli-support`, there is no `wasm-bindgen` binary to give flags to;
+///  -  `asset_filter`: a function that is called for every file written to the build directory
+///     (the JS bindings, the WASM, the snippets, the compiled CSS and the static files) with an
+///     `Asset` (its path relative to the build directory, its logical name, its content type and
+///     its content). It returns the asset to write, possibly with another content or path, or
+///     `None` to skip the file;
 ///  -  `frontend_watch`: a function that is called when the watcher is being initialized (allowing
-///      you to add extra things to watch for example);
+///     you to add extra things to watch or to get a `RebuildHandle` to trigger rebuilds from
+///     other sources for example);
 ///  -  `backend_watch`: a function that is called when the watcher is being initialized (allowing
-///      you to add extra things to watch for example);
+///     you to add extra things to watch for example);
 ///  -  `serve`: (only if built with the `serve` feature): a function that is called when the HTTP
 ///     serve is getting configured;
+///  -  `serve_middleware`: (only if built with the `serve` feature): a function that is called
+///     first when the HTTP server is getting configured. The middleware added with `Server::with`
+///     wraps all the routes: the files of the build directory, the fallback to `index.html`, the
+///     routes of the `serve` hook and the live reload;
+///  -  `pre_serve`: a function that is called once by the `serve` command, after the first build
+///     succeeded and before the HTTP server listens, with the arguments, the metadata of the
+///     workspace and the frontend package (to prepare the data of a mock API or start a companion
+///     process for example). The command fails with its error;
+///  -  `on_shutdown`: a function with the same signature that is called once when the `serve`
+///     command stops after `pre_serve`: on Ctrl-C before the child processes are terminated, or
+///     when the command fails. Its error is only reported;
 ///  -  `default_build_path`: a function that is called that provides the default directory path
 ///     when the user didn't provide it through the command-line arguments (the default is
-///     `workspace root/build`);
+///     `workspace root/build`, in the workspace of the frontend package). It receives the
+///     metadata of the workspace of the runner and the frontend package selected at runtime, if
+///     any. Return `metadata.workspace_root.join("build")` to keep building a frontend package
+///     that has its own workspace in the workspace of the runner;
+///  -  `default_serve_path`: a function that provides the directory served by the `serve` command
+///     (the default is the build directory). It has the same signature as `default_build_path`.
+///     The changes in this directory only reload the browser;
 ///  -  `build_args`: allow you to override the `build` command when providing a custom argument
 ///     (the default is `DefaultBuildArgs`);
 ///  -  `serve_args`: allow you to override the `serve` command when providing a custom argument
-///     (the default is `DefaultServeArgs`).
+///     (the default is `DefaultServeArgs`);
+///  -  `pre_test` (or its alias `test`): a function that is called before running the tests with
+///     the command about to be spawned (you can tweak its arguments and environment variables, for
+///     example `WASM_BINDGEN_TEST_TIMEOUT`);
+///  -  `test_args`: allow you to override the `test` command when providing a custom argument
+///     (the default is `DefaultTestArgs`);
+///  -  `deploy`: a function that receives the files of the release build instead of copying them
+///     to the destination (requires the feature `deploy` of `wasmbl`);
+///  -  `parser`: the crate used to parse the command-line arguments: `"structopt"` (the default)
+///     or `"clap"`;
+///  -  `package`: the name of the frontend package (the default is the package that uses the
+///     macro);
+///  -  `manifest_path`: the path to the manifest of the frontend package, relative to the
+///     workspace root (it cannot be used with the name of the frontend package). The package can
+///     be excluded from the workspace or have its own workspace: its artifacts, the files
+///     watched and the default build directory then come from its own workspace.
+///
+/// The hooks receive the types of the version of `cargo_metadata` used by `wasmbl`: import
+/// `Metadata` and `Package` from `wasmbl::prelude`, not from your own dependency on
+/// `cargo_metadata` (a different version gives errors like "expected `Metadata`, found
+/// `Metadata`").
+///
+/// The hooks receive the arguments of the command as the type of their first parameter: the type
+/// of the arguments of the command (`&MyBuildArgs`) or the trait object (`&dyn BuildArgs`,
+/// `&dyn ServeArgs` or `&dyn TestArgs`). The `build_args()` of a custom `ServeArgs` can return
+/// another type than the one of the `Build` command (a reduced set of options for the dev builds
+/// for example): the build hooks then receive both types and must take `&dyn BuildArgs`.
+///
+/// The hooks are given as paths: a function in scope (`pre_build = pre_build`, or just
+/// `pre_build`), in a module (`pre_build = hooks::pre_build`) or in another crate. The path is used
+/// as-is in the generated code, which allows sharing hooks between projects in a library.
+///
+/// The hooks can be sync functions or `async fn` and both can be mixed in the same invocation:
+///
+/// ```ignore
+/// #[wasmbl::main(pre_build = pre_build, post_build = post_build)]
+/// enum Cli {}
+///
+/// fn pre_build(
+///     args: &DefaultBuildArgs,
+///     profile: BuildProfile,
+///     command: &mut Command,
+///     metadata: &Metadata,
+///     package: &Package,
+/// ) -> Result<()> {
+///     Ok(())
+/// }
+///
+/// async fn post_build(args: &DefaultBuildArgs, output: &BuildOutput) -> Result<()> {
+///     upload_source_maps(&output.wasm).await
+/// }
+/// ```
 ///
-/// You can also change the frontend package that is built by providing its name in the first
-/// positional argument:
+/// The future of an `async fn` hook is driven to completion on the thread that calls the hook with
+/// `futures::executor::block_on`. It does not run on the executor of the development server, so a
+/// future that needs a specific runtime (like tokio) must enter it by itself.
+///
+/// You can also change the frontend package that is built by providing its name with `package`
+/// or in the first positional argument:
 ///
 /// ```ignore
-/// #[wasmbl::main("my-frontend-package")]
+/// #[wasmbl::main(package = "my-frontend-package")]
 /// enum Cli {}
 /// ```
 ///
-/// And the backend package in the second positional argument:
+/// The package is looked up in the workspace when the binary starts. If it doesn't exist, the
+/// error lists the packages of the workspace and the closest match.
+///
+/// And the backend package in the second positional argument (the frontend package is then given
+/// in the first one):
 ///
 /// ```ignore
 /// #[wasmbl::main("my-frontend-package", "my-backend-package")]
 /// enum Cli {}
 /// ```
 ///
+/// Or select the frontend package by the path of its manifest if its name is ambiguous:
+///
+/// ```ignore
+/// #[wasmbl::main(manifest_path = "frontend/Cargo.toml")]
+/// enum Cli {}
+/// ```
+///
+/// The `build` and `serve` commands of `DefaultBuildArgs` also accept `--manifest-path` or
+/// `--package <name>` to select another frontend package of the workspace at runtime. The package
+/// is then built in `build/<package>` by default, so the frontends of a workspace don't overwrite
+/// each other.
+///
+/// # Tests
+///
+/// The macro also generates the associated functions `wasmbl_build(args)` and
+/// `wasmbl_serve(args)` (if there is a `serve` command) on the type. They run the commands the same
+/// way the command-line does, without parsing any argument, so the build pipeline can be tested:
+///
+/// ```ignore
+/// #[test]
+/// fn build() {
+///     let build_path = Cli::wasmbl_build(DefaultBuildArgs {
+///         build_path: Some(std::env::temp_dir().join("my-project")),
+///         ..DefaultBuildArgs::default()
+///     })
+///     .unwrap();
+///     assert!(build_path.join("index.html").exists());
+/// }
+/// ```
+///
+/// These functions are semi-stable: their signatures follow the types of the build and serve
+/// arguments used by the macro.
+///
+/// They can also be called from `other_cli_commands` by the commands that need a build (with the
+/// hooks and the optimization), like a command that packages the release build:
+///
+/// ```ignore
+/// fn run_other_cli_commands(cli: Cli, _metadata: &Metadata, _package: &Package) -> Result<()> {
+///     match cli {
+///         Cli::Package => {
+///             let build_path = Cli::wasmbl_build(DefaultBuildArgs::default())?;
+///             // create the archive from `build_path`...
+///         }
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+///
 /// # Examples
 ///
 /// See the [`examples/`](https://github.com/IMI-eRnD-Be/wasmbl/tree/main/examples/custom-cli-command)
 /// directory.
 #[proc_macro_attribute]
 pub fn main(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let item = parse_macro_input!(item as ItemEnum);
-    let attr = parse_macro_input!(attr with attr_parser::Attr::parse);
-    let metadata = MetadataCommand::new()
-        .exec()
-        .expect("could not get metadata");
+    let item = parse_macro_input!(item as Item);
 
-    main_generator::generate(item, attr, &metadata)
-        .unwrap_or_else(|err| err.to_compile_error())
+    expand(attr, &item)
+        .unwrap_or_else(|err| {
+            let err = err.to_compile_error();
+            // NOTE: the item and an empty `main` are kept so the error is the only one reported
+            quote::quote! {
+                #item
+                #err
+                fn main() {}
+            }
+        })
         .into()
 }
+
+fn expand(attr: TokenStream, item: &Item) -> syn::Result<proc_macro2::TokenStream> {
+    let attr = syn::parse::Parser::parse(attr_parser::Attr::parse, attr)?;
+
+    main_generator::generate(item.clone(), attr)
+}
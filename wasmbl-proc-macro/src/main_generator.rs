@@ -1,25 +1,244 @@
-use crate::attr_parser::Attr;
-use cargo_metadata::Metadata;
-use proc_macro2::TokenStream;
+use crate::attr_parser::{Attr, Parser};
+use proc_macro2::{Span, TokenStream};
 use quote::{quote, quote_spanned};
 use syn::spanned::Spanned;
-use syn::{Error, ItemEnum};
+use syn::{Attribute, Error, Fields, Item, Member, Meta, NestedMeta, Path, Type};
 
-pub fn generate(item: ItemEnum, attr: Attr, metadata: &Metadata) -> syn::Result<TokenStream> {
-    let ident = &item.ident;
+const UNSUPPORTED_LAYOUT: &str = "expected an enum or a struct: the variants of an enum are added \
+    as commands next to `build` and `serve`; a struct must have exactly one field marked with \
+    `#[structopt(subcommand)]` (or `#[command(subcommand)]` with clap) whose type is an enum (or \
+    an `Option` of an enum) that has the variants `Build` and `Serve`";
+
+/// The shape of the item the macro is applied to.
+enum Layout {
+    /// An enum of additional commands.
+    Enum { has_variants: bool },
+    /// An enum that defines the `Build` command itself and optionally the `Serve`, `Test`, `Run`,
+    /// `RunNode` and `Clean` commands.
+    Commands {
+        build: Box<Type>,
+        serve: Option<Box<Type>>,
+        test: Option<Box<Type>>,
+        has_run: bool,
+        has_run_node: bool,
+        has_new: bool,
+        has_clean: bool,
+        has_deploy: bool,
+        has_completions: bool,
+        has_version: bool,
+        has_other_variants: bool,
+    },
+    /// A struct with global options and a subcommand field.
+    Struct {
+        field: Member,
+        command: Path,
+        optional: bool,
+        verbosity: Option<Member>,
+    },
+}
+
+impl Layout {
+    fn new(item: &Item) -> syn::Result<Self> {
+        let item = match item {
+            Item::Enum(item) => return Self::from_enum(item),
+            Item::Struct(item) => item,
+            _ => return Err(Error::new(item.span(), UNSUPPORTED_LAYOUT)),
+        };
+
+        let fields: Vec<_> = match &item.fields {
+            Fields::Named(fields) => fields.named.iter().collect(),
+            Fields::Unnamed(fields) => fields.unnamed.iter().collect(),
+            Fields::Unit => Vec::new(),
+        };
+        let mut subcommands = fields.iter().enumerate().filter(|(_, field)| {
+            field.attrs.iter().any(|attr| {
+                ["structopt", "clap", "command"]
+                    .iter()
+                    .any(|x| attr.path.is_ident(x))
+                    && matches!(attr.parse_meta(), Ok(Meta::List(list)) if list.nested.iter().any(
+                        |x| matches!(x, NestedMeta::Meta(Meta::Path(path)) if path.is_ident("subcommand"))
+                    ))
+            })
+        });
+
+        let (index, field) = match (subcommands.next(), subcommands.next()) {
+            (Some(x), None) => x,
+            _ => return Err(Error::new(item.ident.span(), UNSUPPORTED_LAYOUT)),
+        };
+
+        let (command, optional) = match &field.ty {
+            Type::Path(ty) => {
+                let last = ty.path.segments.last().expect("a path is never empty; qed");
+                match &last.arguments {
+                    syn::PathArguments::AngleBracketed(args) if last.ident == "Option" => {
+                        match args.args.first() {
+                            Some(syn::GenericArgument::Type(Type::Path(inner))) => {
+                                (inner.path.clone(), true)
+                            }
+                            _ => return Err(Error::new(field.ty.span(), UNSUPPORTED_LAYOUT)),
+                        }
+                    }
+                    _ => (ty.path.clone(), false),
+                }
+            }
+            _ => return Err(Error::new(field.ty.span(), UNSUPPORTED_LAYOUT)),
+        };
+
+        let field = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(index.into()),
+        };
+
+        let verbosity = fields
+            .iter()
+            .enumerate()
+            .find_map(|(index, field)| match &field.ty {
+                Type::Path(ty)
+                    if ty
+                        .path
+                        .segments
+                        .last()
+                        .is_some_and(|x| x.ident == "Verbosity") =>
+                {
+                    Some(match &field.ident {
+                        Some(ident) => Member::Named(ident.clone()),
+                        None => Member::Unnamed(index.into()),
+                    })
+                }
+                _ => None,
+            });
+
+        Ok(Self::Struct {
+            field,
+            command,
+            optional,
+            verbosity,
+        })
+    }
+
+    fn from_enum(item: &syn::ItemEnum) -> syn::Result<Self> {
+        let command_type = |name: &str| {
+            item.variants
+                .iter()
+                .find(|x| x.ident == name)
+                .map(|variant| match &variant.fields {
+                    Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                        Ok(Box::new(fields.unnamed[0].ty.clone()))
+                    }
+                    _ => Err(Error::new(
+                        variant.span(),
+                        format!("expected a single field in the command: `{}(...)`", name),
+                    )),
+                })
+                .transpose()
+        };
+
+        if let Some(build) = item.variants.iter().find(|x| x.ident == "Build") {
+            if let Some(attr) = cfg_attrs(&build.attrs).next() {
+                return Err(Error::new(
+                    attr.span(),
+                    "the `Build` command can not be conditionally compiled",
+                ));
+            }
+        }
+
+        match (command_type("Build")?, command_type("Serve")?) {
+            (None, None) => Ok(Self::Enum {
+                has_variants: !item.variants.is_empty(),
+            }),
+            (None, Some(_)) => Err(Error::new(
+                item.ident.span(),
+                "the `Serve` command requires a `Build` command: add a variant \
+                `Build(DefaultBuildArgs)` (or your own `BuildArgs`)",
+            )),
+            (Some(build), serve) => {
+                let test = command_type("Test")?;
+                let has_run = command_type("Run")?.is_some();
+                let has_run_node = command_type("RunNode")?.is_some();
+                let has_new = cfg!(feature = "scaffold") && command_type("New")?.is_some();
+                let has_clean = command_type("Clean")?.is_some();
+                let has_deploy = cfg!(feature = "deploy") && command_type("Deploy")?.is_some();
+                let has_completions = command_type("Completions")?.is_some();
+                let has_version = command_type("Version")?.is_some();
+                Ok(Self::Commands {
+                    has_other_variants: item.variants.len()
+                        > 1 + serve.is_some() as usize
+                            + test.is_some() as usize
+                            + has_run as usize
+                            + has_run_node as usize
+                            + has_new as usize
+                            + has_clean as usize
+                            + has_deploy as usize
+                            + has_completions as usize
+                            + has_version as usize,
+                    build,
+                    serve,
+                    test,
+                    has_run,
+                    has_run_node,
+                    has_new,
+                    has_clean,
+                    has_deploy,
+                    has_completions,
+                    has_version,
+                })
+            }
+        }
+    }
+}
+
+/// The `cfg` attributes of a variant or a field.
+fn cfg_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path.is_ident("cfg"))
+}
+
+/// The `cfg` attributes of the variant `name` of the enum, if it has one. The code generated for
+/// the variant is compiled under the same conditions.
+fn variant_cfgs(item: &Item, name: &str) -> Option<Vec<Attribute>> {
+    match item {
+        Item::Enum(item) => item
+            .variants
+            .iter()
+            .find(|x| x.ident == name)
+            .map(|x| cfg_attrs(&x.attrs).cloned().collect()),
+        _ => None,
+    }
+}
+
+pub fn generate(item: Item, attr: Attr) -> syn::Result<TokenStream> {
+    let layout = Layout::new(&item)?;
+    let ident = match &item {
+        Item::Enum(item) => &item.ident,
+        Item::Struct(item) => &item.ident,
+        _ => unreachable!("checked by the layout; qed"),
+    };
     let Attr {
         other_cli_commands,
         pre_build,
         post_build,
-        #[cfg(feature = "dev-server")]
+        post_release,
+        bindgen,
+        asset_filter,
+        #[cfg(feature = "serve")]
         serve,
+        #[cfg(feature = "serve")]
+        serve_middleware,
+        pre_serve,
+        on_shutdown,
         frontend_watch,
         frontend_pkg_name,
+        frontend_manifest_path,
         backend_watch,
         backend_pkg_name,
         default_build_path,
+        default_serve_path,
         build_args,
         serve_args,
+        pre_test,
+        test_args,
+        #[cfg(feature = "deploy")]
+        deploy,
+        parser,
     } = attr;
 
     if let Some(serve_args) = serve_args.as_ref() {
@@ -31,145 +250,822 @@ pub fn generate(item: ItemEnum, attr: Attr, metadata: &Metadata) -> syn::Result<
         }
     }
 
-    let build_ty = if let Some(ty) = build_args {
-        quote! { #ty }
-    } else {
-        quote! { ::wasmbl::DefaultBuildArgs }
+    if let Layout::Commands { serve: None, .. } = &layout {
+        #[cfg(feature = "serve")]
+        let serve_hooks = vec![
+            serve.as_ref().map(|x| ("serve", x)),
+            serve_middleware.as_ref().map(|x| ("serve_middleware", x)),
+        ];
+        #[cfg(not(feature = "serve"))]
+        let serve_hooks: Vec<Option<(&str, &Path)>> = Vec::new();
+        let unused = serve_hooks
+            .into_iter()
+            .flatten()
+            .chain(pre_serve.as_ref().map(|x| ("pre_serve", x)))
+            .chain(on_shutdown.as_ref().map(|x| ("on_shutdown", x)))
+            .chain(frontend_watch.as_ref().map(|x| ("frontend_watch", x)))
+            .chain(backend_watch.as_ref().map(|x| ("backend_watch", x)))
+            .chain(
+                default_serve_path
+                    .as_ref()
+                    .map(|x| ("default_serve_path", x)),
+            )
+            .chain(serve_args.as_ref().map(|x| ("serve_args", x)))
+            .next();
+        if let Some((name, path)) = unused {
+            return Err(Error::new(
+                path.span(),
+                format!("`{}` cannot be used without a `Serve` command", name),
+            ));
+        }
+    }
+
+    if let Layout::Commands { test: None, .. } = &layout {
+        let unused = pre_test
+            .as_ref()
+            .map(|x| ("pre_test", x))
+            .or_else(|| test_args.as_ref().map(|x| ("test_args", x)));
+        if let Some((name, path)) = unused {
+            return Err(Error::new(
+                path.span(),
+                format!("`{}` cannot be used without a `Test` command", name),
+            ));
+        }
+    }
+
+    #[cfg(feature = "deploy")]
+    if let (
+        Layout::Commands {
+            has_deploy: false, ..
+        },
+        Some(path),
+    ) = (&layout, deploy.as_ref())
+    {
+        return Err(Error::new(
+            path.span(),
+            "`deploy` cannot be used without a `Deploy` command",
+        ));
+    }
+
+    let build_ty = match (build_args, &layout) {
+        (Some(ty), _) => quote! { #ty },
+        (None, Layout::Commands { build, .. }) => quote! { #build },
+        (None, _) => quote! { ::wasmbl::DefaultBuildArgs },
     };
 
-    let serve_ty = if let Some(ty) = serve_args {
-        quote! { #ty }
-    } else {
-        quote! { ::wasmbl::DefaultServeArgs }
+    let serve_ty = match (serve_args, &layout) {
+        (Some(ty), _) => quote! { #ty },
+        (
+            None,
+            Layout::Commands {
+                serve: Some(serve), ..
+            },
+        ) => quote! { #serve },
+        (None, _) => quote! { ::wasmbl::DefaultServeArgs },
     };
 
+    let test_ty = match (test_args, &layout) {
+        (Some(ty), _) => quote! { #ty },
+        (
+            None,
+            Layout::Commands {
+                test: Some(test), ..
+            },
+        ) => quote! { #test },
+        (None, _) => quote! { ::wasmbl::DefaultTestArgs },
+    };
+
+    // NOTE: the commands are run through the traits with the spans of the types of the arguments,
+    //       so a missing implementation is reported on the type given by the user rather than in
+    //       the generated code
+    let run_build = quote_spanned! {build_ty.span()=> ::wasmbl::BuildArgs::run(args) };
+    let run_serve = quote_spanned! {serve_ty.span()=> ::wasmbl::ServeArgs::run(args) };
+    let run_test = quote_spanned! {test_ty.span()=> ::wasmbl::TestArgs::run(args) };
+
+    // NOTE: a hook can be a sync fn or an `async fn`, see `wasmbl::async_hooks`
+    let resolve_hook = quote! {{
+        #[allow(unused_imports)]
+        use ::wasmbl::async_hooks::{AsyncHookKind as _, SyncHookKind as _};
+        #[allow(clippy::needless_borrow)]
+        let kind = (&output).wasmbl_hook_kind();
+        kind.resolve(output)
+    }};
+
     let span = other_cli_commands.span();
-    let other_cli_commands = other_cli_commands
-        .map(|x| {
-            quote_spanned! {span=>
-                WasmRunCliCommand::Other(cli) => #x(cli, metadata, package)?,
-            }
-        })
-        .unwrap_or_else(|| {
-            if !item.variants.is_empty() {
-                quote! {
-                    cli => compile_error!(
-                        "missing `other_cli_commands` to handle all the variants",
-                    ),
+    let other_cli_commands = match &layout {
+        Layout::Enum { has_variants } => other_cli_commands
+            .map(|x| {
+                quote_spanned! {span=>
+                    WasmRunCliCommand::Other(cli) => {
+                        let output = #x(cli, metadata, package);
+                        #resolve_hook?
+                    }
                 }
-            } else {
-                quote! {
-                    WasmRunCliCommand::Other(x) => match x {},
+            })
+            .unwrap_or_else(|| {
+                if *has_variants {
+                    quote_spanned! {ident.span()=>
+                        cli => compile_error!(
+                            "missing `other_cli_commands` to handle all the variants",
+                        ),
+                    }
+                } else {
+                    quote! {
+                        WasmRunCliCommand::Other(x) => match x {},
+                    }
                 }
-            }
-        });
+            }),
+        Layout::Commands {
+            has_other_variants, ..
+        } => other_cli_commands
+            .map(|x| {
+                quote_spanned! {span=>
+                    #[allow(unreachable_patterns)]
+                    cli => {
+                        let output = #x(cli, metadata, package);
+                        #resolve_hook?
+                    }
+                }
+            })
+            .unwrap_or_else(|| {
+                if *has_other_variants {
+                    quote_spanned! {ident.span()=>
+                        cli => compile_error!(
+                            "missing `other_cli_commands` to handle all the variants",
+                        ),
+                    }
+                } else {
+                    quote! {}
+                }
+            }),
+        // NOTE: the whole struct is given to `other_cli_commands` so the global options are
+        //       available there
+        Layout::Struct { field, .. } => other_cli_commands
+            .map(|x| {
+                quote_spanned! {span=>
+                    command => {
+                        cli.#field = command;
+                        let output = #x(cli, metadata, package);
+                        #resolve_hook?
+                    }
+                }
+            })
+            .unwrap_or_default(),
+    };
 
     let pre_build = pre_build.map(|path| {
         quote_spanned! {path.span()=>
-            pre_build: Box::new(|args, profile, command| {
-                let args = args.downcast_ref::<#build_ty>()
-                    .expect("invalid type for `Build` command: the type in the command enum \
-                        must be the same than the type returned by `build_args()` \
-                        in the implementation of the trait `ServeArgs`");
-                #path(args, profile, command)
+            pre_build: Box::new(|args, profile, command, trigger| {
+                #[allow(unused_imports)]
+                use ::wasmbl::hook_args::{DeprecatedPreBuildHook as _, PreBuildHook as _};
+
+                // NOTE: the hooks without the metadata or the trigger are still supported, see
+                //       `wasmbl::hook_args`
+                let metadata = ::wasmbl::BuildArgs::metadata(args);
+                let package = ::wasmbl::BuildArgs::frontend_package(args);
+                let output = #path.call_hook(
+                    ::wasmbl::hook_args::FromArgs::from_args(args),
+                    profile,
+                    command,
+                    metadata,
+                    package,
+                    trigger,
+                );
+                #resolve_hook
             }),
         }
     });
 
     let post_build = post_build.map(|path| {
         quote_spanned! {path.span()=>
-            post_build: Box::new(|args, profile, wasm_js, wasm_bin| {
-                let args = args.downcast_ref::<#build_ty>()
-                    .expect("invalid type for `Build` command: the type in the command enum \
-                        must be the same than the type returned by `build_args()` \
-                        in the implementation of the trait `ServeArgs`");
-                #path(args, profile, wasm_js, wasm_bin)
+            post_build: Box::new(|args, build_output, _| {
+                #[allow(unused_imports)]
+                use ::wasmbl::hook_args::{DeprecatedPostBuildHook as _, PostBuildHook as _};
+
+                // NOTE: the hooks with the previous signature are still supported, see
+                //       `wasmbl::hook_args`
+                let output = #path.call_post_build(
+                    ::wasmbl::hook_args::FromArgs::from_args(args),
+                    build_output,
+                );
+                #resolve_hook
             }),
         }
     });
 
-    #[cfg(feature = "dev-server")]
+    let post_release = post_release.map(|path| {
+        quote_spanned! {path.span()=>
+            post_release: Box::new(|args, release| {
+                let output = #path(::wasmbl::hook_args::FromArgs::from_args(args), release);
+                #resolve_hook
+            }),
+        }
+    });
+
+    let bindgen = bindgen.map(|path| {
+        quote_spanned! {path.span()=>
+            bindgen: Box::new(|args, bindgen| {
+                let output = #path(::wasmbl::hook_args::FromArgs::from_args(args), bindgen);
+                #resolve_hook
+            }),
+        }
+    });
+
+    let asset_filter = asset_filter.map(|path| {
+        quote_spanned! {path.span()=>
+            asset_filter: Box::new(|asset| {
+                let output = #path(asset);
+                #resolve_hook
+            }),
+        }
+    });
+
+    #[cfg(feature = "serve")]
     let serve = serve.map(|path| {
         quote_spanned! {path.span()=>
             serve: Box::new(|args, app| {
-                let args = args.downcast_ref::<#serve_ty>().unwrap();
-                #path(args, app)
+                let output = #path(::wasmbl::hook_args::FromArgs::from_args(args), app);
+                #resolve_hook
             }),
         }
     });
-    #[cfg(not(feature = "dev-server"))]
+    #[cfg(not(feature = "serve"))]
     let serve = quote! {};
 
+    #[cfg(feature = "serve")]
+    let serve_middleware = serve_middleware.map(|path| {
+        quote_spanned! {path.span()=>
+            serve_middleware: Box::new(|args, app| {
+                let output = #path(::wasmbl::hook_args::FromArgs::from_args(args), app);
+                #resolve_hook
+            }),
+        }
+    });
+    #[cfg(not(feature = "serve"))]
+    let serve_middleware = quote! {};
+
+    let pre_serve = pre_serve.map(|path| {
+        quote_spanned! {path.span()=>
+            pre_serve: Box::new(|args| {
+                let build_args = ::wasmbl::ServeArgs::build_args(args);
+                let metadata = ::wasmbl::BuildArgs::metadata(build_args);
+                let package = ::wasmbl::BuildArgs::frontend_package(build_args);
+                let output = #path(
+                    ::wasmbl::hook_args::FromArgs::from_args(args),
+                    metadata,
+                    package,
+                );
+                #resolve_hook
+            }),
+        }
+    });
+
+    let on_shutdown = on_shutdown.map(|path| {
+        quote_spanned! {path.span()=>
+            on_shutdown: Box::new(|args| {
+                let build_args = ::wasmbl::ServeArgs::build_args(args);
+                let metadata = ::wasmbl::BuildArgs::metadata(build_args);
+                let package = ::wasmbl::BuildArgs::frontend_package(build_args);
+                let output = #path(
+                    ::wasmbl::hook_args::FromArgs::from_args(args),
+                    metadata,
+                    package,
+                );
+                #resolve_hook
+            }),
+        }
+    });
+
     let frontend_watch = frontend_watch.map(|path| {
         quote_spanned! {path.span()=>
             frontend_watch: Box::new(|args, watcher| {
-                let args = args.downcast_ref::<#serve_ty>().unwrap();
-                #path(args, watcher)
+                let output = #path(::wasmbl::hook_args::FromArgs::from_args(args), watcher);
+                #resolve_hook
             }),
         }
     });
 
-    #[cfg(not(feature = "dev-server"))]
     let backend_watch = backend_watch.map(|path| {
         quote_spanned! {path.span()=>
             backend_watch: Box::new(|args, watcher| {
-                let args = args.downcast_ref::<#serve_ty>().unwrap();
-                #path(args, watcher)
+                let output = #path(::wasmbl::hook_args::FromArgs::from_args(args), watcher);
+                #resolve_hook
             }),
         }
     });
 
-    if let Some(pkg_name) = frontend_pkg_name.as_ref() {
-        let span = pkg_name.span();
-        let pkg_name = pkg_name.value();
-        if metadata
-            .packages
-            .iter()
-            .find(|x| x.name == pkg_name)
-            .is_none()
-        {
-            return Err(Error::new(
-                span,
-                format!("package `{}` not found", pkg_name),
-            ));
+    let pre_test = pre_test.map(|path| {
+        quote_spanned! {path.span()=>
+            pre_test: Box::new(|args, command| {
+                let output = #path(::wasmbl::hook_args::FromArgs::from_args(args), command);
+                #resolve_hook
+            }),
         }
-    }
+    });
 
-    let frontend_pkg_name = frontend_pkg_name.map(|x| quote! { #x }).unwrap_or_else(|| {
-        let pkg_name = std::env::var("CARGO_PKG_NAME").unwrap();
-        quote! { #pkg_name }
+    #[cfg(feature = "deploy")]
+    let deploy = deploy.map(|path| {
+        quote_spanned! {path.span()=>
+            deploy: Box::new(|args, files| {
+                let output = #path(args, files);
+                #resolve_hook
+            }),
+        }
     });
+    #[cfg(not(feature = "deploy"))]
+    let deploy = quote! {};
 
-    if let Some(pkg_name) = backend_pkg_name.as_ref() {
-        let span = pkg_name.span();
-        let pkg_name = pkg_name.value();
-        if metadata
-            .packages
-            .iter()
-            .find(|x| x.name == pkg_name)
-            .is_none()
-        {
-            return Err(Error::new(
-                span,
-                format!("package `{}` not found", pkg_name),
-            ));
+    let frontend_pkg_name = if let Some(pkg_name) = frontend_pkg_name {
+        quote! { #pkg_name }
+    } else if frontend_manifest_path.is_some() {
+        // NOTE: only used as the name of the program when parsing the build arguments
+        quote! { env!("CARGO_PKG_NAME") }
+    } else {
+        let pkg_name = std::env::var("CARGO_PKG_NAME").map_err(|_| {
+            Error::new(
+                Span::call_site(),
+                "could not determine the frontend package, provide its name with `package`",
+            )
+        })?;
+        quote! { #pkg_name }
+    };
+
+    let frontend = if let Some(path) = frontend_manifest_path.as_ref() {
+        quote! { .manifest_path(#path) }
+    } else {
+        quote! { .package(#frontend_pkg_name) }
+    };
+
+    let backend = backend_pkg_name
+        .map(|x| quote! { .backend(#x) })
+        .unwrap_or_default();
+
+    let default_build_path = default_build_path
+        .map(|path| {
+            quote_spanned! {path.span()=>
+                .default_build_path(|metadata, package| #path(metadata, package))
+            }
+        })
+        .unwrap_or_default();
+    let default_serve_path = default_serve_path
+        .map(|path| {
+            quote_spanned! {path.span()=>
+                .default_serve_path(|metadata, package| #path(metadata, package))
+            }
+        })
+        .unwrap_or_default();
+
+    let (parse_build_args, parse_build_args_from) = match parser {
+        Parser::StructOpt => (
+            quote! { #build_ty::from_iter_safe(&[#frontend_pkg_name])? },
+            quote! { #build_ty::from_iter_safe(iter)? },
+        ),
+        #[cfg(feature = "clap")]
+        Parser::Clap => {
+            let build_cli = quote! {
+                use ::wasmbl::clap;
+
+                #[derive(clap::Parser)]
+                struct WasmRunBuildCli {
+                    #[command(flatten)]
+                    args: #build_ty,
+                }
+            };
+            (
+                quote! {{
+                    #build_cli
+                    <WasmRunBuildCli as clap::Parser>::try_parse_from(&[#frontend_pkg_name])?.args
+                }},
+                quote! {{
+                    #build_cli
+                    <WasmRunBuildCli as clap::Parser>::try_parse_from(iter)?.args
+                }},
+            )
         }
-    }
+    };
 
-    let backend_pkg_name = backend_pkg_name
-        .map(|x| quote! { Some(#x) })
-        .unwrap_or_else(|| {
-            quote! { None }
-        });
+    // NOTE: a variant of the enum replaces the command of the same name, the command is still
+    //       added when the variant is disabled by its `cfg` attributes
+    let builtin = |name: &str,
+                   variant: TokenStream,
+                   arm: TokenStream|
+     -> syn::Result<(TokenStream, TokenStream)> {
+        Ok(match variant_cfgs(&item, name) {
+            Some(cfgs) if cfgs.is_empty() => (quote! {}, quote! {}),
+            Some(cfgs) => {
+                let predicates = cfgs
+                    .iter()
+                    .map(|x| x.parse_args::<TokenStream>())
+                    .collect::<syn::Result<Vec<_>>>()?;
+                let cfg = quote! { #[cfg(not(all(#(#predicates),*)))] };
+                (quote! { #cfg #variant }, quote! { #cfg #arm })
+            }
+            None => (variant, arm),
+        })
+    };
 
-    let default_build_path = if let Some(path) = default_build_path {
-        quote_spanned! {path.span()=>
-            Some(Box::new(|metadata, package| {
-                #path(metadata, package)
-            }))
+    let (test_variant, test_arm) = builtin(
+        "Test",
+        quote! { Test(#test_ty), },
+        quote! { WasmRunCliCommand::Test(args) => #run_test?, },
+    )?;
+    // NOTE: the completion script is generated from the whole command-line
+    let completions_run = match parser {
+        Parser::StructOpt => quote! { args.run_structopt(WasmRunCli::clap())? },
+        #[cfg(feature = "clap")]
+        Parser::Clap => quote! {
+            args.run_clap(<WasmRunCli as clap::CommandFactory>::command())?
+        },
+    };
+    let (completions_variant, completions_arm) = builtin(
+        "Completions",
+        quote! { Completions(::wasmbl::DefaultCompletionsArgs), },
+        quote! { WasmRunCliCommand::Completions(args) => #completions_run, },
+    )?;
+    let (run_variant, run_arm) = builtin(
+        "Run",
+        quote! { Run(::wasmbl::DefaultRunArgs), },
+        quote! { WasmRunCliCommand::Run(args) => args.run()?, },
+    )?;
+    let (run_node_variant, run_node_arm) = builtin(
+        "RunNode",
+        quote! { RunNode(::wasmbl::DefaultRunNodeArgs), },
+        quote! { WasmRunCliCommand::RunNode(args) => args.run()?, },
+    )?;
+    let (version_variant, version_arm) = builtin(
+        "Version",
+        quote! { Version(::wasmbl::DefaultVersionArgs), },
+        quote! { WasmRunCliCommand::Version(args) => args.run()?, },
+    )?;
+    let (clean_variant, clean_arm) = builtin(
+        "Clean",
+        quote! { Clean(::wasmbl::DefaultCleanArgs), },
+        quote! { WasmRunCliCommand::Clean(args) => args.run()?, },
+    )?;
+
+    #[cfg(feature = "scaffold")]
+    let (new_variant, new_arm) = builtin(
+        "New",
+        quote! { New(::wasmbl::DefaultNewArgs), },
+        quote! { WasmRunCliCommand::New(args) => args.run()?, },
+    )?;
+    #[cfg(not(feature = "scaffold"))]
+    let (new_variant, new_arm) = (quote! {}, quote! {});
+
+    #[cfg(feature = "deploy")]
+    let (deploy_variant, deploy_arm) = builtin(
+        "Deploy",
+        quote! { Deploy(::wasmbl::DefaultDeployArgs), },
+        quote! { WasmRunCliCommand::Deploy(args) => args.run()?, },
+    )?;
+    #[cfg(not(feature = "deploy"))]
+    let (deploy_variant, deploy_arm) = (quote! {}, quote! {});
+
+    // NOTE: the verbosity is taken from the struct when it has a field of type `Verbosity`
+    let struct_verbosity = match &layout {
+        Layout::Struct {
+            verbosity: Some(field),
+            ..
+        } => quote! { cli.#field },
+        _ => quote! { ::wasmbl::Verbosity::default() },
+    };
+
+    let (cli_definition, parse_cli, parse_serve_args) = match parser {
+        Parser::StructOpt if matches!(layout, Layout::Struct { .. }) => (
+            quote! {
+                use ::wasmbl::structopt::StructOpt;
+            },
+            quote! {{
+                let cli = #ident::from_args();
+                (#struct_verbosity, cli)
+            }},
+            quote! { #serve_ty::from_args() },
+        ),
+        Parser::StructOpt if matches!(layout, Layout::Commands { .. }) => (
+            quote! {
+                use ::wasmbl::structopt::StructOpt;
+
+                #[derive(::wasmbl::structopt::StructOpt)]
+                struct WasmRunCli {
+                    #[structopt(flatten)]
+                    verbosity: ::wasmbl::Verbosity,
+                    #[structopt(subcommand)]
+                    command: #ident,
+                }
+            },
+            quote! {{
+                let cli = WasmRunCli::from_args();
+                (cli.verbosity, cli.command)
+            }},
+            quote! {},
+        ),
+        #[cfg(feature = "clap")]
+        Parser::Clap if matches!(layout, Layout::Commands { .. }) => (
+            quote! {
+                use ::wasmbl::clap;
+
+                #[derive(clap::Parser)]
+                struct WasmRunCli {
+                    #[command(flatten)]
+                    verbosity: ::wasmbl::Verbosity,
+                    #[command(subcommand)]
+                    command: #ident,
+                }
+            },
+            quote! {{
+                let cli = <WasmRunCli as clap::Parser>::parse();
+                (cli.verbosity, cli.command)
+            }},
+            quote! {},
+        ),
+        #[cfg(feature = "clap")]
+        Parser::Clap if matches!(layout, Layout::Struct { .. }) => (
+            quote! {
+                use ::wasmbl::clap;
+
+                #[derive(clap::Parser)]
+                struct WasmRunServeCli {
+                    #[command(flatten)]
+                    args: #serve_ty,
+                }
+            },
+            quote! {{
+                let cli = <#ident as clap::Parser>::parse();
+                (#struct_verbosity, cli)
+            }},
+            quote! { <WasmRunServeCli as clap::Parser>::parse().args },
+        ),
+        Parser::StructOpt => (
+            quote! {
+                use ::wasmbl::structopt::StructOpt;
+
+                #[derive(::wasmbl::structopt::StructOpt)]
+                struct WasmRunCli {
+                    #[structopt(flatten)]
+                    verbosity: ::wasmbl::Verbosity,
+                    #[structopt(subcommand)]
+                    command: Option<WasmRunCliCommand>,
+                }
+
+                #[derive(::wasmbl::structopt::StructOpt)]
+                enum WasmRunCliCommand {
+                    Build(#build_ty),
+                    Serve(#serve_ty),
+                    #test_variant
+                    #run_variant
+                    #run_node_variant
+                    #clean_variant
+                    #completions_variant
+                    #version_variant
+                    #new_variant
+                    #deploy_variant
+                    #[structopt(flatten)]
+                    Other(#ident),
+                }
+
+                // NOTE: the arguments are parsed again for the default command
+                #[derive(::wasmbl::structopt::StructOpt)]
+                struct WasmRunServeCli {
+                    #[structopt(flatten)]
+                    verbosity: ::wasmbl::Verbosity,
+                    #[structopt(flatten)]
+                    args: #serve_ty,
+                }
+            },
+            quote! {{
+                let cli = WasmRunCli::from_args();
+                (cli.verbosity, cli)
+            }},
+            quote! { WasmRunServeCli::from_args().args },
+        ),
+        #[cfg(feature = "clap")]
+        Parser::Clap => (
+            quote! {
+                use ::wasmbl::clap;
+
+                #[derive(clap::Parser)]
+                struct WasmRunCli {
+                    #[command(flatten)]
+                    verbosity: ::wasmbl::Verbosity,
+                    #[command(subcommand)]
+                    command: Option<WasmRunCliCommand>,
+                }
+
+                #[derive(clap::Subcommand)]
+                enum WasmRunCliCommand {
+                    Build(#build_ty),
+                    Serve(#serve_ty),
+                    #test_variant
+                    #run_variant
+                    #run_node_variant
+                    #clean_variant
+                    #completions_variant
+                    #version_variant
+                    #new_variant
+                    #deploy_variant
+                    #[command(flatten)]
+                    Other(#ident),
+                }
+
+                #[derive(clap::Parser)]
+                struct WasmRunServeCli {
+                    #[command(flatten)]
+                    verbosity: ::wasmbl::Verbosity,
+                    #[command(flatten)]
+                    args: #serve_ty,
+                }
+            },
+            quote! {{
+                let cli = <WasmRunCli as clap::Parser>::parse();
+                (cli.verbosity, cli)
+            }},
+            quote! { <WasmRunServeCli as clap::Parser>::parse().args },
+        ),
+    };
+
+    let run = match &layout {
+        Layout::Commands {
+            serve,
+            test,
+            has_run,
+            has_run_node,
+            has_new,
+            has_clean,
+            has_deploy,
+            has_completions,
+            has_version,
+            ..
+        } => {
+            // NOTE: the arms of the variants that have `cfg` attributes must be disabled with them
+            let cfg = |name: &str| {
+                let cfgs = variant_cfgs(&item, name).unwrap_or_default();
+                quote! { #(#cfgs)* }
+            };
+            let serve_cfg = cfg("Serve");
+            let test_cfg = cfg("Test");
+            let run_cfg = cfg("Run");
+            let run_node_cfg = cfg("RunNode");
+            let new_cfg = cfg("New");
+            let clean_cfg = cfg("Clean");
+            let deploy_cfg = cfg("Deploy");
+            let completions_cfg = cfg("Completions");
+            let version_cfg = cfg("Version");
+            let serve = serve.as_ref().map(|_| {
+                quote! {
+                    #serve_cfg
+                    #ident::Serve(args) => #run_serve?,
+                }
+            });
+            let test = test.as_ref().map(|_| {
+                quote! {
+                    #test_cfg
+                    #ident::Test(args) => #run_test?,
+                }
+            });
+            let run = if *has_run {
+                quote! {
+                    #run_cfg
+                    #ident::Run(args) => args.run()?,
+                }
+            } else {
+                quote! {}
+            };
+            let run_node = if *has_run_node {
+                quote! {
+                    #run_node_cfg
+                    #ident::RunNode(args) => args.run()?,
+                }
+            } else {
+                quote! {}
+            };
+            let new = if *has_new {
+                quote! {
+                    #new_cfg
+                    #ident::New(args) => args.run()?,
+                }
+            } else {
+                quote! {}
+            };
+            let clean = if *has_clean {
+                quote! {
+                    #clean_cfg
+                    #ident::Clean(args) => args.run()?,
+                }
+            } else {
+                quote! {}
+            };
+            let deploy = if *has_deploy {
+                quote! {
+                    #deploy_cfg
+                    #ident::Deploy(args) => args.run()?,
+                }
+            } else {
+                quote! {}
+            };
+            let completions = if *has_completions {
+                quote! {
+                    #completions_cfg
+                    #ident::Completions(args) => #completions_run,
+                }
+            } else {
+                quote! {}
+            };
+            let version = if *has_version {
+                quote! {
+                    #version_cfg
+                    #ident::Version(args) => args.run()?,
+                }
+            } else {
+                quote! {}
+            };
+            quote! {
+                match cli {
+                    #ident::Build(args) => {
+                        #run_build?;
+                    },
+                    #serve
+                    #test
+                    #run
+                    #run_node
+                    #clean
+                    #deploy
+                    #completions
+                    #version
+                    #new
+                    #other_cli_commands
+                }
+            }
         }
-    } else {
-        quote! { None }
+        Layout::Enum { .. } => quote! {
+            if let Some(cli) = cli.command {
+                match cli {
+                    WasmRunCliCommand::Build(args) => {
+                        #run_build?;
+                    },
+                    WasmRunCliCommand::Serve(args) => #run_serve?,
+                    #test_arm
+                    #run_arm
+                    #run_node_arm
+                    #clean_arm
+                    #completions_arm
+                    #version_arm
+                    #new_arm
+                    #deploy_arm
+                    #other_cli_commands
+                }
+            } else {
+                { let args = #parse_serve_args; #run_serve }?;
+            }
+        },
+        Layout::Struct {
+            field,
+            command,
+            optional: true,
+            ..
+        } => quote! {
+            #[allow(unused_mut)]
+            let mut cli = cli;
+            match cli.#field {
+                Some(#command::Build(args)) => {
+                    #run_build?;
+                },
+                Some(#command::Serve(args)) => #run_serve?,
+                None => { let args = #parse_serve_args; #run_serve }?,
+                #other_cli_commands
+            }
+        },
+        Layout::Struct {
+            field,
+            command,
+            optional: false,
+            ..
+        } => quote! {
+            #[allow(unused_mut)]
+            let mut cli = cli;
+            match cli.#field {
+                #command::Build(args) => {
+                    #run_build?;
+                },
+                #command::Serve(args) => #run_serve?,
+                #other_cli_commands
+            }
+        },
+    };
+
+    let wasmbl_serve = match &layout {
+        Layout::Commands { serve: None, .. } => quote! {},
+        _ => quote! {
+            /// Run the `serve` command with the given arguments, the same way the command-line
+            /// does.
+            ///
+            /// Like [`Self::wasmbl_build()`], this is meant for the tests of the project and for the
+            /// custom commands.
+            #[allow(dead_code)]
+            pub fn wasmbl_serve(args: #serve_ty) -> ::std::result::Result<(), ::wasmbl::Error> {
+                Self::wasmbl_init()?;
+                #run_serve
+            }
+        },
     };
 
     Ok(quote! {
@@ -178,9 +1074,8 @@ pub fn generate(item: ItemEnum, attr: Attr, metadata: &Metadata) -> syn::Result<
         impl #ident {
             fn build() -> ::wasmbl::prelude::anyhow::Result<::std::path::PathBuf>
             {
-                use ::wasmbl::BuildArgs;
-                let build_args = #build_ty::from_iter_safe(&[#frontend_pkg_name])?;
-                build_args.run()
+                let args = #parse_build_args;
+                Ok(#run_build?)
             }
 
             fn build_with_args<I>(iter: I)
@@ -189,65 +1084,80 @@ pub fn generate(item: ItemEnum, attr: Attr, metadata: &Metadata) -> syn::Result<
                 I: ::std::iter::IntoIterator,
                 I::Item: ::std::convert::Into<::std::ffi::OsString> + Clone,
             {
-                use ::wasmbl::BuildArgs;
                 let iter = ::std::iter::once(::std::ffi::OsString::from(#frontend_pkg_name))
                     .chain(iter.into_iter().map(|x| x.into()));
-                let build_args = #build_ty::from_iter_safe(iter)?;
-                build_args.run()
+                let args = #parse_build_args_from;
+                Ok(#run_build?)
             }
-        }
-
-        fn main() -> ::wasmbl::prelude::anyhow::Result<()> {
-            use ::std::path::PathBuf;
-            use ::wasmbl::structopt::StructOpt;
-            use ::wasmbl::prelude::*;
 
-            #[derive(::wasmbl::structopt::StructOpt)]
-            struct WasmRunCli {
-                #[structopt(subcommand)]
-                command: Option<WasmRunCliCommand>,
+            /// Run the `build` command with the given arguments, the same way the command-line
+            /// does.
+            ///
+            /// This is meant for the tests of the project and for the custom commands (from
+            /// `other_cli_commands`): the hooks given to the macro are used and the workspace is
+            /// loaded on the first call. Its signature is semi-stable: it follows the type of the
+            /// build arguments. The kind of the `wasmbl::Error` can be matched to handle some
+            /// failures.
+            #[allow(dead_code)]
+            pub fn wasmbl_build(args: #build_ty)
+            -> ::std::result::Result<::std::path::PathBuf, ::wasmbl::Error>
+            {
+                Self::wasmbl_init()?;
+                #run_build
             }
 
-            #[derive(::wasmbl::structopt::StructOpt)]
-            enum WasmRunCliCommand {
-                Build(#build_ty),
-                Serve(#serve_ty),
-                #[structopt(flatten)]
-                Other(#ident),
+            #wasmbl_serve
+
+            fn wasmbl_init() -> ::std::result::Result<(
+                &'static ::wasmbl::prelude::Metadata,
+                &'static ::wasmbl::prelude::Package,
+            ), ::wasmbl::Error> {
+                use ::wasmbl::prelude::*;
+
+                #[allow(clippy::needless_update)]
+                let hooks = Hooks {
+                    #pre_build
+                    #post_build
+                    #post_release
+                    #bindgen
+                    #asset_filter
+                    #serve_middleware
+                    #serve
+                    #pre_serve
+                    #on_shutdown
+                    #frontend_watch
+                    #backend_watch
+                    #pre_test
+                    #deploy
+                    .. Hooks::default()
+                };
+
+                ::wasmbl::Runner::new(env!("CARGO_MANIFEST_DIR"))
+                    #frontend
+                    #backend
+                    #default_build_path
+                    #default_serve_path
+                    .hooks(hooks)
+                    .init()
             }
+        }
 
-            let cli = WasmRunCli::from_args();
+        fn main() -> ::wasmbl::prelude::anyhow::Result<()> {
+            use ::wasmbl::prelude::*;
 
-            #[allow(clippy::needless_update)]
-            let hooks = Hooks {
-                #pre_build
-                #post_build
-                #serve
-                #frontend_watch
-                #backend_watch
-                .. Hooks::default()
-            };
+            #cli_definition
 
-            let (metadata, package) = ::wasmbl::wasmbl_init(
-                #frontend_pkg_name,
-                #backend_pkg_name,
-                #default_build_path,
-                hooks,
-            )?;
+            let (verbosity, cli) = #parse_cli;
+            ::wasmbl::set_verbosity(verbosity);
 
-            if let Some(cli) = cli.command {
-                match cli {
-                    WasmRunCliCommand::Build(args) => {
-                        args.run()?;
-                    },
-                    WasmRunCliCommand::Serve(args) => args.run()?,
-                    #other_cli_commands
-                }
-            } else {
-                #serve_ty::from_args().run()?;
-            }
+            // NOTE: the error is an event with `--log-format json`
+            ::wasmbl::exit_on_error((|| -> ::wasmbl::prelude::anyhow::Result<()> {
+                let (metadata, package) = #ident::wasmbl_init()?;
+
+                #run
 
-            Ok(())
+                Ok(())
+            })())
         }
     })
 }
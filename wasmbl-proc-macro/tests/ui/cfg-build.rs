@@ -0,0 +1,9 @@
+use structopt::StructOpt;
+use wasmbl::prelude::*;
+
+#[wasmbl::main]
+#[derive(StructOpt, Debug)]
+enum Cli {
+    #[cfg(unix)]
+    Build(DefaultBuildArgs),
+}
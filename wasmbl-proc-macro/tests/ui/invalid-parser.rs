@@ -0,0 +1,5 @@
+use structopt::StructOpt;
+
+#[wasmbl::main(parser = "getopts")]
+#[derive(StructOpt, Debug)]
+enum Cli {}
@@ -0,0 +1,5 @@
+use structopt::StructOpt;
+
+#[wasmbl::main("frontend", manifest_path = "frontend/Cargo.toml")]
+#[derive(StructOpt, Debug)]
+enum Cli {}
@@ -0,0 +1,7 @@
+use structopt::StructOpt;
+
+#[wasmbl::main]
+#[derive(StructOpt, Debug)]
+enum Cli {
+    Hello,
+}
@@ -0,0 +1,5 @@
+use structopt::StructOpt;
+
+#[wasmbl::main("frontend", package = "frontend")]
+#[derive(StructOpt, Debug)]
+enum Cli {}
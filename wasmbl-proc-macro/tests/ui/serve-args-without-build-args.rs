@@ -0,0 +1,5 @@
+use structopt::StructOpt;
+
+#[wasmbl::main(serve_args = wasmbl::DefaultServeArgs)]
+#[derive(StructOpt, Debug)]
+enum Cli {}
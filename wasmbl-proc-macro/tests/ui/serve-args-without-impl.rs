@@ -0,0 +1,15 @@
+use structopt::StructOpt;
+use wasmbl::prelude::*;
+
+#[wasmbl::main]
+#[derive(StructOpt, Debug)]
+enum Cli {
+    Build(DefaultBuildArgs),
+    Serve(MyServeArgs),
+}
+
+#[derive(StructOpt, Debug)]
+struct MyServeArgs {
+    #[structopt(flatten)]
+    build_args: DefaultBuildArgs,
+}
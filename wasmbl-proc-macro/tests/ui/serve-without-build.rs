@@ -0,0 +1,8 @@
+use structopt::StructOpt;
+use wasmbl::prelude::*;
+
+#[wasmbl::main]
+#[derive(StructOpt, Debug)]
+enum Cli {
+    Serve(DefaultServeArgs),
+}
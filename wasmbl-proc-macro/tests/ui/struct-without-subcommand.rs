@@ -0,0 +1,8 @@
+use structopt::StructOpt;
+
+#[wasmbl::main]
+#[derive(StructOpt, Debug)]
+struct Cli {
+    #[structopt(long)]
+    verbose: bool,
+}
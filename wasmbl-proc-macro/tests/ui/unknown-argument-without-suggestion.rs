@@ -0,0 +1,5 @@
+use structopt::StructOpt;
+
+#[wasmbl::main(colour = "blue")]
+#[derive(StructOpt, Debug)]
+enum Cli {}
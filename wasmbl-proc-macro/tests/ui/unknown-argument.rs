@@ -0,0 +1,14 @@
+use structopt::StructOpt;
+use wasmbl::prelude::*;
+
+#[wasmbl::main(pre_buld = pre_build)]
+#[derive(StructOpt, Debug)]
+enum Cli {}
+
+fn pre_build(
+    _args: &DefaultBuildArgs,
+    _profile: BuildProfile,
+    _command: &mut std::process::Command,
+) -> anyhow::Result<()> {
+    Ok(())
+}
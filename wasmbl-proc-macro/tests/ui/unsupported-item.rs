@@ -0,0 +1,2 @@
+#[wasmbl::main]
+fn cli() {}
@@ -0,0 +1,12 @@
+use structopt::StructOpt;
+use wasmbl::prelude::*;
+
+#[wasmbl::main(frontend_watch = frontend_watch)]
+#[derive(StructOpt, Debug)]
+enum Cli {
+    Build(DefaultBuildArgs),
+}
+
+fn frontend_watch(_args: &DefaultServeArgs, _watcher: &mut FileWatcher) -> anyhow::Result<()> {
+    Ok(())
+}